@@ -0,0 +1,202 @@
+//! Validate the freeform annotations of EAF files against a parser config
+//! file (see `eaf::config`), e.g. in CI or a pre-commit hook. Prints a
+//! report per file and tier and exits with:
+//!
+//! - 0 if there are no errors (warnings are only reported, unless
+//!   `--deny-warnings` is given),
+//! - 1 if there are,
+//! - 2 if the config or some of the files couldn't be read.
+
+use std::{fs, io, path::Path, path::PathBuf, process};
+
+use clap::Parser;
+use eaf::config::ConfigFile;
+use eaf::document::{Annotation, Eaf, Milliseconds};
+use eaf::messages::{self, Lang};
+
+#[derive(Debug, Parser)]
+#[command(name = "quetzal-check", version, about)]
+struct Args {
+    /// Parser config, as TOML, YAML or JSON (see `eaf::config`).
+    #[arg(long)]
+    config: PathBuf,
+    /// Check against this rule set of the config rather than its top
+    /// level.
+    #[arg(long)]
+    rule_set: Option<String>,
+    /// Language of the explanations of mistakes, e.g. `cs`.
+    #[arg(long, default_value = "en")]
+    lang: Lang,
+    /// Fail on warnings too, not just errors.
+    #[arg(long)]
+    deny_warnings: bool,
+    /// Only print files and tiers with mistakes, and the summary.
+    #[arg(long, short)]
+    quiet: bool,
+    /// EAF files, or directories to search for them recursively.
+    #[arg(required = true)]
+    paths: Vec<PathBuf>,
+}
+
+#[derive(Debug, Default)]
+struct Counts {
+    errors: usize,
+    warnings: usize,
+}
+
+impl Counts {
+    fn add(&mut self, other: &Counts) {
+        self.errors += other.errors;
+        self.warnings += other.warnings;
+    }
+}
+
+fn plural(n: usize, what: &str) -> String {
+    format!("{} {}{}", n, what, if n == 1 { "" } else { "s" })
+}
+
+/// Files named *.eaf under `dir`, sorted so that reports are stable.
+fn find_eafs(dir: &Path, found: &mut Vec<PathBuf>) -> io::Result<()> {
+    let mut entries = fs::read_dir(dir)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<io::Result<Vec<_>>>()?;
+    entries.sort();
+    for path in entries {
+        if path.is_dir() {
+            find_eafs(&path, found)?;
+        } else if path
+            .extension()
+            .map_or(false, |ext| ext.eq_ignore_ascii_case("eaf"))
+        {
+            found.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn seconds(ms: Option<Milliseconds>) -> String {
+    ms.map_or_else(
+        || "?".to_owned(),
+        |ms| format!("{:.3}", f64::from(ms) / 1000.),
+    )
+}
+
+fn indent(text: &str, by: &str) -> String {
+    text.lines().flat_map(|line| vec![by, line, "\n"]).collect()
+}
+
+/// The report on the annotation's mistakes, if any.
+fn check_annotation(annotation: &Annotation, args: &Args, counts: &mut Counts) -> String {
+    let parsed = match annotation.parsed() {
+        Some(parsed) => parsed,
+        None => return String::new(),
+    };
+    let mut report = String::new();
+    for mistake in &parsed.mistakes {
+        if mistake.is_warning() {
+            counts.warnings += 1;
+        } else {
+            counts.errors += 1;
+        }
+        report.push_str(&format!(
+            "    {} ({}–{}) {}: {}\n",
+            annotation.id,
+            seconds(annotation.start),
+            seconds(annotation.end),
+            if mistake.is_warning() {
+                "warning"
+            } else {
+                "error"
+            },
+            messages::message(parsed, mistake, args.lang)
+        ));
+        report.push_str(&indent(&parsed.highlight(mistake), "      "));
+    }
+    report
+}
+
+fn check_file(eaf: &Eaf, args: &Args) -> (String, Counts) {
+    let mut report = String::new();
+    let mut file_counts = Counts::default();
+    for tier in eaf.tiers() {
+        let mut counts = Counts::default();
+        let details: String = tier
+            .annotations
+            .iter()
+            .map(|a| check_annotation(a, args, &mut counts))
+            .collect();
+        if details.is_empty() && args.quiet {
+            continue;
+        }
+        report.push_str(&format!(
+            "  {}: {}, {}\n{}",
+            tier.id,
+            plural(counts.errors, "error"),
+            plural(counts.warnings, "warning"),
+            details
+        ));
+        file_counts.add(&counts);
+    }
+    (report, file_counts)
+}
+
+fn run(args: Args) -> Result<bool, String> {
+    let file = ConfigFile::from_path(&args.config).map_err(|e| e.to_string())?;
+    let config = match &args.rule_set {
+        Some(name) => file.set(name).and_then(ConfigFile::into_config),
+        None => file.into_config(),
+    }
+    .map_err(|e| e.to_string())?;
+    let mut files = vec![];
+    for path in &args.paths {
+        if path.is_dir() {
+            find_eafs(path, &mut files).map_err(|e| format!("{}: {}", path.display(), e))?;
+        } else {
+            files.push(path.clone());
+        }
+    }
+
+    let mut total = Counts::default();
+    let mut with_mistakes = 0;
+    let mut unreadable = 0;
+    for path in &files {
+        let eaf = match Eaf::from_file(path, &config) {
+            Ok(eaf) => eaf,
+            Err(e) => {
+                eprintln!("error: {}", e);
+                unreadable += 1;
+                continue;
+            }
+        };
+        let (report, counts) = check_file(&eaf, &args);
+        if counts.errors + counts.warnings > 0 {
+            with_mistakes += 1;
+        }
+        if !report.is_empty() {
+            print!("{}\n{}", path.display(), report);
+        }
+        total.add(&counts);
+    }
+    println!(
+        "{} checked: {}, {} in {}",
+        plural(files.len(), "file"),
+        plural(total.errors, "error"),
+        plural(total.warnings, "warning"),
+        plural(with_mistakes, "file")
+    );
+    if unreadable > 0 {
+        return Err(format!("{} couldn't be read", plural(unreadable, "file")));
+    }
+    Ok(total.errors == 0 && (total.warnings == 0 || !args.deny_warnings))
+}
+
+fn main() {
+    match run(Args::parse()) {
+        Ok(true) => {}
+        Ok(false) => process::exit(1),
+        Err(e) => {
+            eprintln!("error: {}", e);
+            process::exit(2);
+        }
+    }
+}
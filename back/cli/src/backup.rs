@@ -0,0 +1,86 @@
+//! Backups of the database and stored files.
+
+use std::path::PathBuf;
+
+use clap::Args;
+
+use db::backup::{self, BackupError, Manifest};
+
+#[derive(Debug, Args)]
+pub struct BackupArgs {
+    /// SQLite database to back up.
+    #[arg(long, env = "DATABASE_URL")]
+    database: String,
+    /// Directory with the stored files the database references.
+    #[arg(long, env = "ROCKET_STORAGE_DIR", default_value = "storage")]
+    storage: PathBuf,
+    /// Directory to create the backup in; must not exist yet.
+    dest: PathBuf,
+}
+
+#[derive(Debug, Args)]
+pub struct VerifyArgs {
+    /// Directory with the backup.
+    backup: PathBuf,
+}
+
+#[derive(Debug, Args)]
+pub struct RestoreArgs {
+    /// Where to restore the SQLite database to.
+    #[arg(long, env = "DATABASE_URL")]
+    database: PathBuf,
+    /// Directory to restore the stored files into.
+    #[arg(long, env = "ROCKET_STORAGE_DIR", default_value = "storage")]
+    storage: PathBuf,
+    /// Replace an existing database.
+    #[arg(long)]
+    force: bool,
+    /// Directory with the backup.
+    backup: PathBuf,
+}
+
+fn report(manifest: &Manifest) {
+    for path in &manifest.missing {
+        eprintln!("warning: {} was missing from storage at backup time", path);
+    }
+    let size: u64 = manifest.files.iter().map(|f| f.size).sum();
+    println!(
+        "database and {} file(s) ({} bytes) from {}",
+        manifest.files.len(),
+        size,
+        manifest.created_at
+    );
+}
+
+/// Returns whether all files referenced by the database were backed up.
+pub fn create(args: BackupArgs) -> Result<bool, String> {
+    let conn = db::connect(&args.database).map_err(|e| e.to_string())?;
+    let manifest = backup::create(&conn, &args.storage, &args.dest).map_err(|e| e.to_string())?;
+    report(&manifest);
+    Ok(manifest.missing.is_empty())
+}
+
+/// Returns whether the backup is intact.
+pub fn verify(args: VerifyArgs) -> Result<bool, String> {
+    match backup::verify(&args.backup) {
+        Ok(manifest) => {
+            report(&manifest);
+            Ok(true)
+        }
+        Err(BackupError::Corrupt(problems)) => {
+            for problem in &problems {
+                eprintln!("{}", problem);
+            }
+            eprintln!("{} problem(s) found", problems.len());
+            Ok(false)
+        }
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+pub fn restore(args: RestoreArgs) -> Result<bool, String> {
+    let manifest = backup::restore(&args.backup, &args.database, &args.storage, args.force)
+        .map_err(|e| e.to_string())?;
+    report(&manifest);
+    Ok(true)
+}
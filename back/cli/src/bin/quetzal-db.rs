@@ -0,0 +1,94 @@
+//! Setting up quetzal's database: `migrate` creates it if need be, brings
+//! its schema up to date and seeds the enum tables, so that a new
+//! deployment is one command.
+
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::process;
+
+use clap::{Args, Parser, Subcommand};
+use db::Conn;
+use db::{fixtures, migrations};
+
+#[derive(Debug, Parser)]
+#[command(name = "quetzal-db", version, about)]
+struct Cli {
+    /// SQLite database, created if it doesn't exist yet.
+    #[arg(long, env = "DATABASE_URL")]
+    database: String,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Apply pending migrations, then seed the enum tables.
+    Migrate {
+        /// Leave the enum tables as they are.
+        #[arg(long)]
+        no_seed: bool,
+        #[command(flatten)]
+        seed: SeedArgs,
+    },
+    /// Revert the latest migration.
+    Rollback {
+        /// Directory with the migrations, whose down scripts aren't built
+        /// in.
+        #[arg(long = "migrations", default_value = migrations::DIR)]
+        dir: PathBuf,
+    },
+    /// Add labels missing from the enum tables.
+    Seed(SeedArgs),
+}
+
+#[derive(Debug, Args)]
+struct SeedArgs {
+    /// TOML file with the labels, instead of the built-in ones (see
+    /// `db/fixtures/enums.toml` for the format).
+    #[arg(long)]
+    fixtures: Option<PathBuf>,
+}
+
+fn seed(conn: &Conn, args: SeedArgs) -> Result<(), String> {
+    let toml = match args.fixtures {
+        Some(path) => {
+            fs::read_to_string(&path).map_err(|e| format!("{}: {}", path.display(), e))?
+        }
+        None => fixtures::DEFAULT.to_owned(),
+    };
+    let fixtures = fixtures::parse(&toml).map_err(|e| e.to_string())?;
+    let added = fixtures::seed(conn, &fixtures).map_err(|e| e.to_string())?;
+    println!("added {} label(s)", added);
+    Ok(())
+}
+
+fn run(cli: Cli) -> Result<(), String> {
+    let conn = db::connect(&cli.database).map_err(|e| e.to_string())?;
+    match cli.command {
+        Command::Migrate {
+            no_seed,
+            seed: args,
+        } => {
+            migrations::run(&conn, &mut io::stdout()).map_err(|e| e.to_string())?;
+            if no_seed {
+                Ok(())
+            } else {
+                seed(&conn, args)
+            }
+        }
+        Command::Rollback { dir } => {
+            let version = migrations::revert_latest(&conn, &dir).map_err(|e| e.to_string())?;
+            println!("reverted {}", version);
+            Ok(())
+        }
+        Command::Seed(args) => seed(&conn, args),
+    }
+}
+
+fn main() {
+    if let Err(e) = run(Cli::parse()) {
+        eprintln!("error: {}", e);
+        process::exit(2);
+    }
+}
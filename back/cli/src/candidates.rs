@@ -0,0 +1,82 @@
+//! Proposals for the whitelist, blacklist and stopwords of a project, from
+//! frequency lists, see `eaf::candidates`.
+
+use std::{fs::File, path::PathBuf};
+
+use clap::Args;
+
+use db::palette;
+use eaf::candidates::{self, Frequencies, Thresholds};
+use eaf::parser::ParserConfig;
+
+#[derive(Debug, Args)]
+pub struct CandidatesArgs {
+    /// SQLite database with the project's palette.
+    #[arg(long, env = "DATABASE_URL")]
+    database: String,
+    /// ID of the project whose config to check the tokens against.
+    #[arg(long)]
+    project: i32,
+    /// Propose unknown tokens occurring at least this many times for the
+    /// whitelist.
+    #[arg(long, default_value_t = Thresholds::default().min_count)]
+    min_count: u64,
+    /// Propose allowed tokens occurring at most this many times for the
+    /// blacklist...
+    #[arg(long, default_value_t = Thresholds::default().max_rare_count)]
+    max_rare_count: u64,
+    /// ...if a token one edit away is at least this many times more
+    /// frequent.
+    #[arg(long, default_value_t = Thresholds::default().min_ratio)]
+    min_ratio: u64,
+    /// How many of the most frequent tokens to propose as stopwords.
+    #[arg(long, default_value_t = Thresholds::default().stopwords)]
+    stopwords: usize,
+    /// CSV frequency lists with a header, the token in the first column and
+    /// its count in the second; counts are summed across lists.
+    #[arg(required = true)]
+    lists: Vec<PathBuf>,
+}
+
+/// Print the proposals as TOML.
+pub fn print(args: CandidatesArgs) -> Result<bool, String> {
+    let conn = db::connect(&args.database).map_err(|e| e.to_string())?;
+    // the config holds regexes, the palette literal strings
+    let escaped =
+        |codes: Vec<String>| -> Vec<String> { codes.iter().map(|c| regex::escape(c)).collect() };
+    let chars = escaped(palette::chars(&conn, args.project).map_err(|e| e.to_string())?);
+    let attrs = escaped(palette::attr_codes(&conn, args.project).map_err(|e| e.to_string())?);
+    let config = ParserConfig::from_args(&[] as &[&str], &[] as &[&str], &chars, &attrs);
+
+    let mut frequencies = Frequencies::default();
+    for path in &args.lists {
+        let at = |e: &dyn std::fmt::Display| format!("{}: {}", path.display(), e);
+        let file = File::open(path).map_err(|e| at(&e))?;
+        for (i, record) in csv::Reader::from_reader(file).records().enumerate() {
+            let record = record.map_err(|e| at(&e))?;
+            let line = i + 2;
+            let token = record.get(0).unwrap_or_default();
+            let count = record
+                .get(1)
+                .and_then(|c| c.trim().parse().ok())
+                .ok_or_else(|| at(&format!("line {}: invalid count", line)))?;
+            frequencies.add(token, count);
+        }
+    }
+
+    let thresholds = Thresholds {
+        min_count: args.min_count,
+        max_rare_count: args.max_rare_count,
+        min_ratio: args.min_ratio,
+        stopwords: args.stopwords,
+    };
+    let candidates = candidates::propose(&frequencies, &config, &thresholds);
+    print!(
+        "# Proposed additions to the config of project {}, from {} frequency list(s).\n\
+         # Delete the entries you disagree with.\n\n{}",
+        args.project,
+        args.lists.len(),
+        candidates.to_toml()
+    );
+    Ok(true)
+}
@@ -0,0 +1,135 @@
+//! Frequency lists across the validated documents in the database, see
+//! `eaf::frequencies`.
+
+use std::collections::hash_map::{Entry, HashMap};
+use std::collections::HashSet;
+use std::{fs, io, path::PathBuf};
+
+use clap::Args;
+
+use db::docs::{self, DocFilter};
+use db::speakers::{self, SpeakerFilter};
+use db::Conn;
+use db::{parser_configs, tier_mappings, validation};
+use eaf::frequencies::{self, FrequencyList};
+use eaf::parser::ParserConfig;
+use eaf::query::Query;
+use eaf::tiers::{TierMapping, TierPattern, TierSource};
+
+#[derive(Debug, Args)]
+pub struct FrequencyArgs {
+    /// Database with the documents.
+    #[arg(long, env = "DATABASE_URL")]
+    database: String,
+    /// Directory with the stored files the database references.
+    #[arg(long, env = "ROCKET_STORAGE_DIR", default_value = "storage")]
+    storage: PathBuf,
+    /// Only count documents in this project...
+    #[arg(long)]
+    project: Option<i32>,
+    /// ...or in this corpus.
+    #[arg(long)]
+    corpus: Option<i32>,
+    /// Count matches of this query in the query language rather than all
+    /// tokens, e.g. `[] within round`.
+    #[arg(long, default_value = "[]")]
+    query: String,
+    /// Only count speakers of the gender with this ID...
+    #[arg(long)]
+    gender: Option<i32>,
+    /// ...with the education with this ID...
+    #[arg(long)]
+    education: Option<i32>,
+    /// ...and from the region with this ID.
+    #[arg(long)]
+    region: Option<i32>,
+}
+
+/// The project's rules, as the API validates with them.
+fn parser_config(conn: &Conn, project_id: i32) -> Result<ParserConfig, String> {
+    parser_configs::config_file(conn, project_id)
+        .map_err(|e| e.to_string())?
+        .into_config()
+        .map_err(|e| e.to_string())
+}
+
+fn tier_mapping(conn: &Conn, project_id: i32) -> Result<TierMapping, String> {
+    let mut rules = vec![];
+    for rule in tier_mappings::for_project(conn, project_id).map_err(|e| e.to_string())? {
+        let source = TierSource::from_label(&rule.source)
+            .ok_or_else(|| format!("unknown tier source {:?}", rule.source))?;
+        let pattern = TierPattern::new(&rule.pattern).map_err(|e| e.to_string())?;
+        rules.push((source, pattern));
+    }
+    Ok(TierMapping::new(rules))
+}
+
+/// Print the list as CSV, most frequent first.
+pub fn print(args: FrequencyArgs) -> Result<bool, String> {
+    let conn = db::connect(&args.database).map_err(|e| e.to_string())?;
+    let query = Query::parse(&args.query).map_err(|e| e.to_string())?;
+    let speaker_filter = SpeakerFilter {
+        gender_id: args.gender,
+        education_id: args.education,
+        region_id: args.region,
+    };
+    let filter = DocFilter {
+        project_id: args.project,
+        corpus_id: args.corpus,
+        assigned_to_id: None,
+        states: vec![],
+    };
+    let doc_ids: HashSet<_> = docs::list(&conn, &filter)
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .map(|doc| doc.id)
+        .collect();
+
+    let mut projects = HashMap::new();
+    let mut list = FrequencyList::default();
+    let mut ok = true;
+    for transcript in validation::transcripts(&conn, args.project).map_err(|e| e.to_string())? {
+        if !doc_ids.contains(&transcript.file.doc_id) || !transcript.is_clean() {
+            continue;
+        }
+        let project_id = transcript.project_id;
+        if let Entry::Vacant(entry) = projects.entry(project_id) {
+            let nicknames = if speaker_filter.is_empty() {
+                None
+            } else {
+                Some(
+                    speakers::nicknames(&conn, project_id, &speaker_filter)
+                        .map_err(|e| e.to_string())?,
+                )
+            };
+            entry.insert((
+                parser_config(&conn, project_id)?,
+                tier_mapping(&conn, project_id)?,
+                nicknames,
+            ));
+        }
+        let (config, mapping, nicknames) = &projects[&project_id];
+        let speaker = |nickname: &str| nicknames.as_ref().map_or(true, |n| n.contains(nickname));
+        let path = args.storage.join(&transcript.file.path);
+        let counted = fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|xml| {
+                list.add_transcript(&xml, mapping, config, &query, speaker)
+                    .map_err(|e| e.to_string())
+            });
+        if let Err(e) = counted {
+            eprintln!("skipping {}: {}", path.display(), e);
+            ok = false;
+        }
+    }
+
+    let mut writer = csv::Writer::from_writer(io::stdout());
+    writer
+        .write_record(frequencies::CSV_HEADER)
+        .map_err(|e| e.to_string())?;
+    for record in list.records() {
+        writer.write_record(record).map_err(|e| e.to_string())?;
+    }
+    writer.flush().map_err(|e| e.to_string())?;
+    Ok(ok)
+}
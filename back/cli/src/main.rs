@@ -0,0 +1,71 @@
+//! Command line interface to quetzal, mostly for administrative tasks which
+//! don't have (or don't need) a web UI.
+
+use std::process;
+
+use clap::{Parser, Subcommand};
+
+mod backup;
+mod candidates;
+mod frequencies;
+mod review;
+mod speakers;
+mod stats;
+mod tiers;
+
+#[derive(Debug, Parser)]
+#[command(name = "quetzal", version, about)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Back up the database and stored files.
+    Backup(backup::BackupArgs),
+    /// Propose whitelist, blacklist and stopword entries from frequency
+    /// lists, as TOML.
+    Candidates(candidates::CandidatesArgs),
+    /// Add, remove and rename tiers in EAF files.
+    EditTiers(tiers::EditArgs),
+    /// Frequency lists of tokens in validated documents, as CSV.
+    Frequencies(frequencies::FrequencyArgs),
+    /// Import speakers from a CSV spreadsheet.
+    ImportSpeakers(speakers::ImportArgs),
+    /// Restore a backup, after verifying it.
+    Restore(backup::RestoreArgs),
+    /// Step through mistakes in EAF files in the terminal, applying quick
+    /// fixes or acknowledging them.
+    Review(review::ReviewArgs),
+    /// Descriptive statistics of EAF transcripts, as CSV.
+    Stats(stats::StatsArgs),
+    /// Turn-taking and overlap between speakers in EAF transcripts, as CSV.
+    TurnTaking(stats::StatsArgs),
+    /// Check a backup against the checksums in its manifest.
+    VerifyBackup(backup::VerifyArgs),
+}
+
+fn main() {
+    let cli = Cli::parse();
+    let result = match cli.command {
+        Command::Backup(args) => backup::create(args),
+        Command::Candidates(args) => candidates::print(args),
+        Command::EditTiers(args) => tiers::edit(args),
+        Command::Frequencies(args) => frequencies::print(args),
+        Command::ImportSpeakers(args) => speakers::import(args),
+        Command::Restore(args) => backup::restore(args),
+        Command::Review(args) => review::review(args),
+        Command::Stats(args) => stats::print(args),
+        Command::TurnTaking(args) => stats::print_turn_taking(args),
+        Command::VerifyBackup(args) => backup::verify(args),
+    };
+    match result {
+        Ok(true) => {}
+        Ok(false) => process::exit(1),
+        Err(e) => {
+            eprintln!("error: {}", e);
+            process::exit(2);
+        }
+    }
+}
@@ -0,0 +1,495 @@
+//! Interactive review of mistakes in EAF files in the terminal, for those
+//! who'd rather not open the web UI: step through the mistakes file by
+//! file, apply quick fixes (see `eaf::fixes`) or acknowledge mistakes as
+//! intentional (see `db::acknowledgments`). Fixes are written back to the
+//! files when moving on to the next one or quitting.
+
+use std::collections::HashSet;
+use std::io::{self, Stdout};
+use std::{fs, path::PathBuf};
+
+use clap::Args;
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::execute;
+use crossterm::terminal::{
+    disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen,
+};
+use db::acknowledgments::{self, NewAcknowledgment};
+use db::Conn;
+use db::{palette, substitutions, tier_mappings};
+use eaf::annotations;
+use eaf::fixes::{self, Edit, Substitutions};
+use eaf::messages::{self, Lang};
+use eaf::prelude::*;
+use ratatui::backend::CrosstermBackend;
+use ratatui::layout::{Constraint, Direction, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, Paragraph, Wrap};
+use ratatui::{Frame, Terminal};
+
+#[derive(Debug, Args)]
+pub struct ReviewArgs {
+    /// SQLite database with the project's configuration.
+    #[arg(long, env = "DATABASE_URL")]
+    database: String,
+    /// ID of the project whose rules to check the files against.
+    #[arg(long)]
+    project: i32,
+    /// ID of the document the file belongs to; needed to acknowledge
+    /// mistakes, so only one file can be reviewed with it.
+    #[arg(long, requires = "user")]
+    doc: Option<i32>,
+    /// ID of the user acknowledging mistakes.
+    #[arg(long, requires = "doc")]
+    user: Option<i32>,
+    /// Language of the explanations of mistakes, e.g. `cs`.
+    #[arg(long, default_value = "en")]
+    lang: Lang,
+    /// EAF files, changed in place.
+    #[arg(required = true)]
+    files: Vec<PathBuf>,
+}
+
+/// What identifies an acknowledged mistake, see `db::acknowledgments`.
+type Key = (String, String, String, String, Option<i32>);
+
+struct Segment {
+    tier: String,
+    annotation: String,
+    parsed: Parsed,
+    changed: bool,
+}
+
+impl Segment {
+    fn key(&self, mistake: &Mistake) -> Key {
+        let (_, start, _) = self.parsed.span(mistake);
+        (
+            self.tier.clone(),
+            self.annotation.clone(),
+            mistake.kind().to_owned(),
+            self.parsed.source.clone(),
+            Some(start as i32),
+        )
+    }
+}
+
+struct Review {
+    conn: Conn,
+    config: ParserConfig,
+    mapping: TierMapping,
+    substitutions: Substitutions,
+    lang: Lang,
+    /// Document and user to record acknowledgments for.
+    acknowledging: Option<(i32, i32)>,
+    acknowledged: HashSet<Key>,
+    files: Vec<PathBuf>,
+    file: usize,
+    xml: String,
+    segments: Vec<Segment>,
+    /// Index into `pending()`.
+    cursor: usize,
+    /// Last thing that happened, for the status line.
+    status: String,
+}
+
+impl Review {
+    /// Mistakes of the current file still to be dealt with, as (segment,
+    /// mistake) indices.
+    fn pending(&self) -> Vec<(usize, usize)> {
+        let mut pending = vec![];
+        for (i, segment) in self.segments.iter().enumerate() {
+            for (j, mistake) in segment.parsed.mistakes.iter().enumerate() {
+                if !self.acknowledged.contains(&segment.key(mistake)) {
+                    pending.push((i, j));
+                }
+            }
+        }
+        pending
+    }
+
+    fn current(&self) -> Option<(&Segment, &Mistake)> {
+        self.pending().get(self.cursor).map(|&(i, j)| {
+            let segment = &self.segments[i];
+            (segment, &segment.parsed.mistakes[j])
+        })
+    }
+
+    fn fix(&self) -> Option<Edit> {
+        let (segment, mistake) = self.current()?;
+        fixes::quick_fix(&self.config, &segment.parsed, mistake, &self.substitutions)
+    }
+
+    fn load(&mut self) -> Result<(), String> {
+        let path = &self.files[self.file];
+        let at = |e: &dyn std::fmt::Display| format!("{}: {}", path.display(), e);
+        self.xml = fs::read_to_string(path).map_err(|e| at(&e))?;
+        self.segments = annotations::read(&self.xml)
+            .map_err(|e| at(&e))?
+            .into_iter()
+            .filter(|a| a.is_transcript(&self.mapping))
+            .map(|a| Segment {
+                parsed: Parser::parse(&self.config, self.config.tokenize(&a.value)),
+                tier: a.tier,
+                annotation: a.id,
+                changed: false,
+            })
+            .collect();
+        self.cursor = 0;
+        Ok(())
+    }
+
+    /// Write the current file back if anything in it changed.
+    fn save(&mut self) -> Result<(), String> {
+        let changed: Vec<_> = self.segments.iter().filter(|s| s.changed).collect();
+        if changed.is_empty() {
+            return Ok(());
+        }
+        let path = &self.files[self.file];
+        let xml = annotations::rewrite(&self.xml, |a| {
+            changed
+                .iter()
+                .find(|s| s.annotation == a.id)
+                .map(|s| s.parsed.source.clone())
+        })
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+        fs::write(path, &xml).map_err(|e| format!("{}: {}", path.display(), e))?;
+        self.status = format!("wrote {} segment(s) to {}", changed.len(), path.display());
+        self.xml = xml;
+        for segment in &mut self.segments {
+            segment.changed = false;
+        }
+        Ok(())
+    }
+
+    /// Save the current file and move on to the next one. Returns whether
+    /// there was one.
+    fn next_file(&mut self) -> Result<bool, String> {
+        self.save()?;
+        if self.file + 1 == self.files.len() {
+            return Ok(false);
+        }
+        self.file += 1;
+        self.load()?;
+        Ok(true)
+    }
+
+    /// Apply the current mistake's quick fix and reparse the segment. The
+    /// cursor stays put, i.e. moves on to the next mistake, unless the fix
+    /// didn't help.
+    fn apply_fix(&mut self) {
+        let edit = match self.fix() {
+            Some(edit) => edit,
+            None => {
+                self.status = "no quick fix for this mistake".to_owned();
+                return;
+            }
+        };
+        let (i, _) = self.pending()[self.cursor];
+        let segment = &mut self.segments[i];
+        let mut source = segment.parsed.source.clone();
+        source.replace_range(edit.start..edit.end, &edit.replacement);
+        segment.parsed = Parser::parse(&self.config, self.config.tokenize(&source));
+        segment.changed = true;
+        self.status = "fixed".to_owned();
+        self.clamp();
+    }
+
+    fn acknowledge(&mut self, reason: &str) -> Result<(), String> {
+        let (doc_id, author_id) = match self.acknowledging {
+            Some(ids) => ids,
+            None => {
+                self.status = "acknowledging mistakes needs --doc and --user".to_owned();
+                return Ok(());
+            }
+        };
+        let key = match self.current() {
+            Some((segment, mistake)) => segment.key(mistake),
+            None => return Ok(()),
+        };
+        let (tier, annotation, kind, segment, start) = key.clone();
+        acknowledgments::add(
+            &self.conn,
+            &NewAcknowledgment {
+                doc_id,
+                tier,
+                annotation,
+                kind,
+                segment,
+                start,
+                reason: reason.to_owned(),
+                author_id,
+            },
+        )
+        .map_err(|e| e.to_string())?;
+        self.acknowledged.insert(key);
+        self.status = "acknowledged".to_owned();
+        self.clamp();
+        Ok(())
+    }
+
+    fn clamp(&mut self) {
+        self.cursor = self.cursor.min(self.pending().len().saturating_sub(1));
+    }
+}
+
+/// The segment with the current mistake highlighted and the others
+/// underlined. Empty spans, e.g. where a delimiter should be closed, are
+/// shown as a caret.
+fn highlight<'s>(segment: &'s Segment, current: &Mistake) -> Line<'s> {
+    let parsed = &segment.parsed;
+    let spans: Vec<_> = parsed.mistakes.iter().map(|m| parsed.span(m)).collect();
+    let (_, start, end) = parsed.span(current);
+    let mut boundaries: Vec<usize> = spans
+        .iter()
+        .flat_map(|&(_, s, e)| vec![s, e])
+        .chain(vec![0, parsed.source.len()])
+        .collect();
+    boundaries.sort_unstable();
+    boundaries.dedup();
+
+    let current_style = Style::default()
+        .fg(Color::Red)
+        .add_modifier(Modifier::REVERSED | Modifier::BOLD);
+    let other_style = Style::default()
+        .fg(Color::Yellow)
+        .add_modifier(Modifier::UNDERLINED);
+    let mut line = vec![];
+    for pair in boundaries.windows(2) {
+        let (from, to) = (pair[0], pair[1]);
+        if start == end && start == from {
+            line.push(Span::styled("\u{2038}", current_style));
+        }
+        let style = if start <= from && to <= end {
+            current_style
+        } else if spans.iter().any(|&(_, s, e)| s <= from && to <= e) {
+            other_style
+        } else {
+            Style::default()
+        };
+        line.push(Span::styled(&parsed.source[from..to], style));
+    }
+    if start == end && start == parsed.source.len() {
+        line.push(Span::styled("\u{2038}", current_style));
+    }
+    Line::from(line)
+}
+
+/// Input mode: browsing, or typing the reason for an acknowledgment.
+enum Mode {
+    Browse,
+    Reason(String),
+}
+
+fn draw(frame: &mut Frame, review: &Review, mode: &Mode) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(3),
+            Constraint::Min(5),
+            Constraint::Length(3),
+        ])
+        .split(frame.size());
+
+    let pending = review.pending();
+    let header = format!(
+        "file {}/{}: {} | mistake {}/{}",
+        review.file + 1,
+        review.files.len(),
+        review.files[review.file].display(),
+        if pending.is_empty() {
+            0
+        } else {
+            review.cursor + 1
+        },
+        pending.len(),
+    );
+    frame.render_widget(
+        Paragraph::new(header).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title("quetzal review"),
+        ),
+        chunks[0],
+    );
+
+    let body = match review.current() {
+        Some((segment, mistake)) => {
+            let severity = if mistake.is_warning() {
+                "warning"
+            } else {
+                "error"
+            };
+            let fix = match review.fix() {
+                Some(edit) => format!(
+                    "quick fix: replace {:?} with {:?}",
+                    &segment.parsed.source[edit.start..edit.end],
+                    edit.replacement
+                ),
+                None => "no quick fix".to_owned(),
+            };
+            vec![
+                Line::from(format!(
+                    "{} ({}) in {}, annotation {}",
+                    mistake.kind(),
+                    severity,
+                    segment.tier,
+                    segment.annotation
+                )),
+                Line::from(messages::message(&segment.parsed, mistake, review.lang)),
+                Line::from(""),
+                highlight(segment, mistake),
+                Line::from(""),
+                Line::from(fix),
+            ]
+        }
+        None => vec![Line::from("No mistakes left in this file.")],
+    };
+    frame.render_widget(
+        Paragraph::new(body)
+            .block(Block::default().borders(Borders::ALL))
+            .wrap(Wrap { trim: false }),
+        chunks[1],
+    );
+
+    let footer = match mode {
+        Mode::Browse => format!(
+            "n/p: next/previous  f: quick fix  a: acknowledge  w: next file  q: quit | {}",
+            review.status
+        ),
+        Mode::Reason(reason) => format!("Reason (Enter to confirm, Esc to cancel): {}", reason),
+    };
+    frame.render_widget(
+        Paragraph::new(footer).block(Block::default().borders(Borders::ALL)),
+        chunks[2],
+    );
+}
+
+/// Handle a key press. Returns whether to go on.
+fn handle(review: &mut Review, mode: &mut Mode, key: KeyEvent) -> Result<bool, String> {
+    if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+        return Ok(false);
+    }
+    match mode {
+        Mode::Browse => match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return Ok(false),
+            KeyCode::Char('n') | KeyCode::Char(' ') | KeyCode::Right | KeyCode::Down => {
+                if review.cursor + 1 < review.pending().len() {
+                    review.cursor += 1;
+                } else if !review.next_file()? {
+                    review.status = "that was the last mistake".to_owned();
+                }
+            }
+            KeyCode::Char('p') | KeyCode::Left | KeyCode::Up => {
+                review.cursor = review.cursor.saturating_sub(1);
+            }
+            KeyCode::Char('f') => review.apply_fix(),
+            KeyCode::Char('a') if review.current().is_some() => {
+                *mode = Mode::Reason(String::new());
+            }
+            KeyCode::Char('w') => {
+                if !review.next_file()? {
+                    review.status = "that was the last file".to_owned();
+                }
+            }
+            _ => {}
+        },
+        Mode::Reason(reason) => match key.code {
+            KeyCode::Enter if !reason.trim().is_empty() => {
+                let reason = reason.trim().to_owned();
+                *mode = Mode::Browse;
+                review.acknowledge(&reason)?;
+            }
+            KeyCode::Esc => *mode = Mode::Browse,
+            KeyCode::Backspace => {
+                reason.pop();
+            }
+            KeyCode::Char(c) => reason.push(c),
+            _ => {}
+        },
+    }
+    Ok(true)
+}
+
+fn run(
+    terminal: &mut Terminal<CrosstermBackend<Stdout>>,
+    review: &mut Review,
+) -> Result<(), String> {
+    let mut mode = Mode::Browse;
+    loop {
+        terminal
+            .draw(|frame| draw(frame, review, &mode))
+            .map_err(|e| e.to_string())?;
+        match event::read().map_err(|e| e.to_string())? {
+            Event::Key(key) if key.kind == KeyEventKind::Press => {
+                if !handle(review, &mut mode, key)? {
+                    return review.save();
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+pub fn review(args: ReviewArgs) -> Result<bool, String> {
+    if args.doc.is_some() && args.files.len() > 1 {
+        return Err("--doc only makes sense with a single file".to_owned());
+    }
+    let conn = db::connect(&args.database).map_err(|e| e.to_string())?;
+    // the config holds regexes, the palette literal strings
+    let escaped =
+        |codes: Vec<String>| -> Vec<String> { codes.iter().map(|c| regex::escape(c)).collect() };
+    let chars = escaped(palette::chars(&conn, args.project).map_err(|e| e.to_string())?);
+    let attrs = escaped(palette::attr_codes(&conn, args.project).map_err(|e| e.to_string())?);
+    let deprecated =
+        escaped(palette::deprecated_attr_codes(&conn, args.project).map_err(|e| e.to_string())?);
+    let config = ParserConfig::from_args(&[] as &[&str], &[] as &[&str], &chars, &attrs)
+        .with_deprecated_attrs(&deprecated);
+    let mut rules = vec![];
+    for rule in tier_mappings::for_project(&conn, args.project).map_err(|e| e.to_string())? {
+        let source = TierSource::from_label(&rule.source)
+            .ok_or_else(|| format!("unknown tier source {:?}", rule.source))?;
+        let pattern = TierPattern::new(&rule.pattern).map_err(|e| e.to_string())?;
+        rules.push((source, pattern));
+    }
+    let substitutions = Substitutions::new(
+        substitutions::for_project(&conn, args.project).map_err(|e| e.to_string())?,
+    );
+    let acknowledged = match args.doc {
+        Some(doc_id) => acknowledgments::for_doc(&conn, doc_id)
+            .map_err(|e| e.to_string())?
+            .into_iter()
+            .map(|a| (a.tier, a.annotation, a.kind, a.segment, a.start))
+            .collect(),
+        None => HashSet::new(),
+    };
+
+    let mut review = Review {
+        conn,
+        config,
+        mapping: TierMapping::new(rules),
+        substitutions,
+        lang: args.lang,
+        acknowledging: args.doc.zip(args.user),
+        acknowledged,
+        files: args.files,
+        file: 0,
+        xml: String::new(),
+        segments: vec![],
+        cursor: 0,
+        status: String::new(),
+    };
+    review.load()?;
+
+    enable_raw_mode().map_err(|e| e.to_string())?;
+    let mut stdout = io::stdout();
+    execute!(stdout, EnterAlternateScreen).map_err(|e| e.to_string())?;
+    let result = Terminal::new(CrosstermBackend::new(stdout))
+        .map_err(|e| e.to_string())
+        .and_then(|mut terminal| run(&mut terminal, &mut review));
+    // restore the terminal whatever happened
+    disable_raw_mode().map_err(|e| e.to_string())?;
+    execute!(io::stdout(), LeaveAlternateScreen).map_err(|e| e.to_string())?;
+    result?;
+    Ok(true)
+}
@@ -0,0 +1,72 @@
+//! Speaker metadata management.
+
+use std::{fs::File, path::PathBuf};
+
+use clap::Args;
+
+use db::import::{self, ColumnMapping};
+
+#[derive(Debug, Args)]
+pub struct ImportArgs {
+    /// SQLite database to import into.
+    #[arg(long, env = "DATABASE_URL")]
+    database: String,
+    /// ID of the project the speakers belong to.
+    #[arg(long)]
+    project: i32,
+    /// ID of the user who recruited the speakers.
+    #[arg(long)]
+    user: i32,
+    /// Read speaker field from a differently named column, e.g.
+    /// `--map nickname=Přezdívka`; can be repeated.
+    #[arg(long = "map", value_name = "FIELD=COLUMN")]
+    mappings: Vec<String>,
+    /// Only report problems, don't import anything.
+    #[arg(long)]
+    dry_run: bool,
+    /// CSV file with a header row.
+    csv: PathBuf,
+}
+
+/// Returns whether the import went (or would go) through without problems.
+pub fn import(args: ImportArgs) -> Result<bool, String> {
+    let mut mapping = ColumnMapping::default();
+    for assignment in &args.mappings {
+        mapping.set(assignment)?;
+    }
+    let conn = db::connect(&args.database).map_err(|e| e.to_string())?;
+    let csv = File::open(&args.csv).map_err(|e| format!("{}: {}", args.csv.display(), e))?;
+    let report =
+        import::check(&conn, args.project, args.user, &mapping, csv).map_err(|e| e.to_string())?;
+
+    for problem in &report.problems {
+        let line = problem.line.map(|l| format!(":{}", l)).unwrap_or_default();
+        let column = problem
+            .column
+            .as_ref()
+            .map(|c| format!(" [{}]", c))
+            .unwrap_or_default();
+        eprintln!(
+            "{}{}{}: {}",
+            args.csv.display(),
+            line,
+            column,
+            problem.message
+        );
+    }
+    if !report.is_ok() {
+        eprintln!(
+            "{} problem(s) found, nothing imported",
+            report.problems.len()
+        );
+        return Ok(false);
+    }
+
+    if args.dry_run {
+        println!("{} speaker(s) would be imported", report.speakers.len());
+    } else {
+        let n = import::commit(&conn, &report).map_err(|e| e.to_string())?;
+        println!("{} speaker(s) imported", n);
+    }
+    Ok(true)
+}
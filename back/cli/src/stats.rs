@@ -0,0 +1,71 @@
+//! Descriptive statistics and turn-taking analysis of transcripts, see
+//! `eaf::stats` and `eaf::turns`.
+
+use std::{fs, io, path::PathBuf};
+
+use clap::Args;
+
+use eaf::annotations;
+use eaf::stats::{self, Segment};
+use eaf::tiers::{TierMapping, TierPattern, TierSource};
+use eaf::turns;
+
+#[derive(Debug, Args)]
+pub struct StatsArgs {
+    /// Which tiers are transcripts and whose, e.g. `ort@<nickname>`; can be
+    /// repeated. Without patterns, all tiers count as transcripts.
+    #[arg(long = "tier", value_name = "PATTERN")]
+    tiers: Vec<String>,
+    /// EAF files.
+    #[arg(required = true)]
+    files: Vec<PathBuf>,
+}
+
+/// Print a CSV with the given header, preceded by a file column, and the
+/// records `f` returns for each file's segments.
+fn print_csv<F>(args: StatsArgs, header: &[&str], f: F) -> Result<bool, String>
+where
+    F: Fn(&[Segment]) -> Vec<Vec<String>>,
+{
+    let mut rules = vec![];
+    for pattern in &args.tiers {
+        let pattern = TierPattern::new(pattern).map_err(|e| e.to_string())?;
+        rules.push((TierSource::TierId, pattern));
+    }
+    let mapping = TierMapping::new(rules);
+
+    let mut writer = csv::Writer::from_writer(io::stdout());
+    writer
+        .write_record(std::iter::once("file").chain(header.iter().copied()))
+        .map_err(|e| e.to_string())?;
+    for path in &args.files {
+        let xml = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let annotations =
+            annotations::read(&xml).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let file = path.display().to_string();
+        for record in f(&stats::segments(annotations, &mapping)) {
+            writer
+                .write_record(std::iter::once(file.clone()).chain(record))
+                .map_err(|e| e.to_string())?;
+        }
+    }
+    writer.flush().map_err(|e| e.to_string())?;
+    Ok(true)
+}
+
+/// A row per file and per speaker in it.
+pub fn print(args: StatsArgs) -> Result<bool, String> {
+    print_csv(args, stats::CSV_HEADER, |segments| {
+        stats::compute(segments).records()
+    })
+}
+
+/// A row per file and ordered pair of speakers in it.
+pub fn print_turn_taking(args: StatsArgs) -> Result<bool, String> {
+    print_csv(args, turns::CSV_HEADER, |segments| {
+        turns::analyze(segments)
+            .into_iter()
+            .map(|((a, b), measures)| measures.record(&a, &b))
+            .collect()
+    })
+}
@@ -0,0 +1,104 @@
+//! Bulk structural fixes of EAF tiers, see `eaf::editor`.
+
+use std::{fs, path::PathBuf};
+
+use clap::Args;
+
+use eaf::editor::{Editor, NewTier};
+
+#[derive(Debug, Args)]
+pub struct EditArgs {
+    /// Remove a tier along with its dependents; can be repeated.
+    #[arg(long = "remove", value_name = "TIER")]
+    removals: Vec<String>,
+    /// Rename a tier, e.g. `--rename ort@Jana=ort@J`; can be repeated.
+    #[arg(long = "rename", value_name = "OLD=NEW")]
+    renames: Vec<String>,
+    /// Add an empty tier with an existing linguistic type, depending on
+    /// the parent tier if given, e.g. `--add fon@Jana:fon:ort@Jana`; can be
+    /// repeated.
+    #[arg(long = "add", value_name = "TIER:TYPE[:PARENT]")]
+    additions: Vec<String>,
+    /// Only report problems, don't change any files.
+    #[arg(long)]
+    dry_run: bool,
+    /// EAF files, changed in place.
+    #[arg(required = true)]
+    files: Vec<PathBuf>,
+}
+
+fn parse_rename(rename: &str) -> Result<(&str, &str), String> {
+    match rename.split_once('=') {
+        Some((from, to)) if !from.is_empty() && !to.is_empty() => Ok((from, to)),
+        _ => Err(format!("invalid rename {:?}, expected OLD=NEW", rename)),
+    }
+}
+
+fn parse_addition(addition: &str) -> Result<NewTier, String> {
+    let parts: Vec<_> = addition.splitn(3, ':').collect();
+    match parts[..] {
+        [id, linguistic_type] | [id, linguistic_type, _]
+            if !id.is_empty() && !linguistic_type.is_empty() =>
+        {
+            Ok(NewTier {
+                id: id.to_owned(),
+                linguistic_type: linguistic_type.to_owned(),
+                parent: parts.get(2).map(|p| p.to_string()),
+                participant: None,
+                annotator: None,
+            })
+        }
+        _ => Err(format!(
+            "invalid tier {:?}, expected TIER:TYPE[:PARENT]",
+            addition
+        )),
+    }
+}
+
+/// Removals go first, then renames, then additions. A file is only written
+/// if all edits apply to it. Returns whether they applied to all files.
+pub fn edit(args: EditArgs) -> Result<bool, String> {
+    let renames = args
+        .renames
+        .iter()
+        .map(|r| parse_rename(r))
+        .collect::<Result<Vec<_>, _>>()?;
+    let additions = args
+        .additions
+        .iter()
+        .map(|a| parse_addition(a))
+        .collect::<Result<Vec<_>, _>>()?;
+    if args.removals.is_empty() && renames.is_empty() && additions.is_empty() {
+        return Err("nothing to do, see --help".to_owned());
+    }
+
+    let mut ok = true;
+    for path in &args.files {
+        let edited = fs::read_to_string(path)
+            .map_err(|e| e.to_string())
+            .and_then(|xml| {
+                let mut editor = Editor::parse(&xml).map_err(|e| e.to_string())?;
+                for id in &args.removals {
+                    editor.remove_tier(id).map_err(|e| e.to_string())?;
+                }
+                for (from, to) in &renames {
+                    editor.rename_tier(from, to).map_err(|e| e.to_string())?;
+                }
+                for tier in &additions {
+                    editor.add_tier(tier).map_err(|e| e.to_string())?;
+                }
+                Ok(editor.to_xml())
+            });
+        match edited {
+            Ok(xml) if !args.dry_run => {
+                fs::write(path, xml).map_err(|e| format!("{}: {}", path.display(), e))?
+            }
+            Ok(_) => {}
+            Err(e) => {
+                eprintln!("{}: {}", path.display(), e);
+                ok = false;
+            }
+        }
+    }
+    Ok(ok)
+}
@@ -0,0 +1,7 @@
+drop table digest_settings;
+drop table validation_runs;
+
+alter table docs drop column done_at;
+alter table docs drop column due_at;
+alter table docs drop column assigned_at;
+alter table users drop column email;
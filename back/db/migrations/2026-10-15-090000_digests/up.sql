@@ -0,0 +1,47 @@
+-- Weekly supervisor digests {{{1
+
+-- Columns needed by the digest {{{2
+
+-- where to send the digest; only supervisors need this for now
+alter table users add column email text;
+
+-- when the current assignment was made, when it's due and when it was
+-- marked as done
+alter table docs add column assigned_at timestamp;
+alter table docs add column due_at timestamp;
+alter table docs add column done_at timestamp;
+
+-- Validation runs {{{2
+
+-- one row per validation of a document's transcript, so that we can
+-- tell which documents keep failing
+create table validation_runs (
+  id integer primary key not null,
+  doc_id integer not null references docs (id)
+    on update cascade on delete cascade,
+  created_at timestamp not null default current_timestamp,
+  mistakes integer not null
+);
+
+-- Digest settings {{{2
+
+-- weekday is 1 (Monday) to 7 (Sunday), hour is 0 to 23, both in server
+-- local time
+create table digest_settings (
+  project_id integer primary key not null references projects (id)
+    on update cascade on delete cascade,
+  enabled boolean not null default 1,
+  weekday integer not null default 1
+    check (weekday between 1 and 7),
+  hour integer not null default 8
+    check (hour between 0 and 23),
+  last_sent_at timestamp
+);
+
+-- Toy data {{{1
+
+update users set email = 'supervisor@example.com' where username = 'supervisor';
+
+insert into digest_settings (project_id) values (1), (2);
+
+-- vim: foldmethod=marker:
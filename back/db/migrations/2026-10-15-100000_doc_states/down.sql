@@ -0,0 +1,3 @@
+drop index docs_project_state;
+alter table docs drop column state_id;
+drop table enum_doc_states;
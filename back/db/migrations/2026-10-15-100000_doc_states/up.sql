@@ -0,0 +1,28 @@
+-- Document lifecycle {{{1
+
+-- new: nobody's working on the document yet
+-- assigned: a transcriber is working on it
+-- submitted: the transcriber is done, awaiting review by a supervisor
+-- returned: the supervisor sent it back for more work
+-- accepted: the supervisor is happy with it
+create table enum_doc_states (
+  id integer primary key not null,
+  label text unique not null
+);
+insert into enum_doc_states (label) values
+  ('new'), ('assigned'), ('submitted'), ('returned'), ('accepted');
+
+alter table docs add column state_id integer not null default 1
+  references enum_doc_states (id) on update cascade on delete restrict;
+
+update docs set state_id = case
+  when done then 3
+  when assigned_to_id is not null then 2
+  else 1
+end;
+
+-- most listings are per project and state, e.g. "what needs reviewing in
+-- project X"
+create index docs_project_state on docs (project_id, state_id);
+
+-- vim: foldmethod=marker:
@@ -0,0 +1,2 @@
+drop table reviews;
+drop table enum_return_reasons;
@@ -0,0 +1,32 @@
+-- Reviews {{{1
+
+-- why a document was returned to the transcriber
+create table enum_return_reasons (
+  id integer primary key not null,
+  label text unique not null
+);
+insert into enum_return_reasons (label) values
+  ('přepis'), ('segmentace'), ('mluvčí'), ('metadata'), ('jiné');
+
+-- one row per supervisor verdict on a submitted document; returned
+-- documents must state a reason
+create table reviews (
+  id integer primary key not null,
+  doc_id integer not null references docs (id)
+    on update cascade on delete cascade,
+  reviewer_id integer not null references users (id)
+    on update cascade on delete restrict,
+  -- the validation run the reviewer was looking at, if any
+  validation_run_id integer references validation_runs (id)
+    on update cascade on delete set null,
+  accepted boolean not null,
+  reason_id integer references enum_return_reasons (id)
+    on update cascade on delete restrict,
+  notes text not null default '',
+  created_at timestamp not null default current_timestamp,
+  check (accepted or reason_id is not null)
+);
+
+create index reviews_doc on reviews (doc_id);
+
+-- vim: foldmethod=marker:
@@ -0,0 +1,3 @@
+drop index validation_runs_user;
+drop table mistakes;
+alter table validation_runs drop column user_id;
@@ -0,0 +1,28 @@
+-- Persisted mistakes {{{1
+
+-- whose work was validated, for per-transcriber feedback
+alter table validation_runs add column user_id integer references users (id)
+  on update cascade on delete set null;
+
+-- individual mistakes found by a validation run
+create table mistakes (
+  id integer primary key not null,
+  run_id integer not null references validation_runs (id)
+    on update cascade on delete cascade,
+  -- where in the EAF the mistake was found
+  tier text not null,
+  annotation text not null,
+  -- snake_case name of the eaf::parser::Mistake variant, e.g. bad_token
+  kind text not null,
+  -- source of the offending segment, so that examples can be shown without
+  -- going back to the EAF (which may have changed since)
+  segment text not null,
+  -- byte offsets of the problematic part of the segment, if known
+  start integer,
+  end integer
+);
+
+create index mistakes_run on mistakes (run_id);
+create index validation_runs_user on validation_runs (user_id);
+
+-- vim: foldmethod=marker:
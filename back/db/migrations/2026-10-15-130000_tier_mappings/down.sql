@@ -0,0 +1 @@
+drop table tier_mappings;
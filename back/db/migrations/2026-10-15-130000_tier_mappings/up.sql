@@ -0,0 +1,21 @@
+-- Tier-to-speaker mapping {{{1
+
+-- per-project rules for figuring out which speaker a tier belongs to,
+-- tried in order of priority (lowest first); pattern contains a single
+-- <nickname> placeholder and is matched against either the tier ID or its
+-- PARTICIPANT attribute, depending on source
+create table tier_mappings (
+  id integer primary key not null,
+  project_id integer not null references projects (id)
+    on update cascade on delete cascade,
+  priority integer not null,
+  source text not null check (source in ('tier_id', 'participant')),
+  pattern text not null,
+  unique (project_id, priority)
+);
+
+-- the convention so far
+insert into tier_mappings (project_id, priority, source, pattern)
+  select id, 1, 'tier_id', 'ort@<nickname>' from projects;
+
+-- vim: foldmethod=marker:
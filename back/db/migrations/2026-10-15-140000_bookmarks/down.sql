@@ -0,0 +1,2 @@
+drop index bookmarks_doc;
+drop table bookmarks;
@@ -0,0 +1,21 @@
+-- Bookmarks {{{1
+
+-- annotations which a user marked as interesting or problematic, so that
+-- they can find them again across documents; annotations are identified
+-- the same way as in mistakes
+create table bookmarks (
+  id integer primary key not null,
+  user_id integer not null references users (id)
+    on update cascade on delete cascade,
+  doc_id integer not null references docs (id)
+    on update cascade on delete cascade,
+  tier text not null,
+  annotation text not null,
+  note text not null default '',
+  created_at timestamp not null default current_timestamp,
+  unique (user_id, doc_id, annotation)
+);
+
+create index bookmarks_doc on bookmarks (doc_id);
+
+-- vim: foldmethod=marker:
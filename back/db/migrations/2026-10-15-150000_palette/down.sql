@@ -0,0 +1 @@
+drop table palette_entries;
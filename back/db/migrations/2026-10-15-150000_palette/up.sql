@@ -0,0 +1,22 @@
+-- Special-character palette {{{1
+
+-- special characters (or grapheme sequences) and attribute codes (as used
+-- after < in transcripts) of each project; these are both offered in the
+-- frontend's insert-character palette and fed to the parser as allowed
+-- atoms and after-angle codes respectively
+create table palette_entries (
+  id integer primary key not null,
+  project_id integer not null references projects (id)
+    on update cascade on delete cascade,
+  kind text not null check (kind in ('char', 'attr')),
+  -- order within the palette
+  position integer not null,
+  value text not null,
+  description text not null default '',
+  -- e.g. Ctrl+Alt+S
+  shortcut text,
+  unique (project_id, kind, value),
+  unique (project_id, shortcut)
+);
+
+-- vim: foldmethod=marker:
@@ -0,0 +1,15 @@
+-- Spell-checking dictionaries {{{1
+
+-- Hunspell dictionaries used for spelling suggestions in each project, by
+-- name (<name>.aff and <name>.dic in the configured dictionary directory),
+-- consulted in order of priority (lowest first)
+create table project_dictionaries (
+  project_id integer not null references projects (id)
+    on update cascade on delete cascade,
+  priority integer not null,
+  dictionary text not null,
+  primary key (project_id, dictionary),
+  unique (project_id, priority)
+);
+
+-- vim: foldmethod=marker:
@@ -0,0 +1,2 @@
+drop index files_doc;
+drop table files;
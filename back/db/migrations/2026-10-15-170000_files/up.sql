@@ -0,0 +1,21 @@
+-- Stored files {{{1
+
+-- files belonging to documents (draft transcripts, recordings, ...), stored
+-- under the configured storage directory at path
+create table files (
+  id integer primary key not null,
+  doc_id integer not null references docs (id)
+    on update cascade on delete cascade,
+  -- what the file is for, e.g. draft_eaf
+  role text not null,
+  path text not null unique,
+  mime text not null,
+  size integer not null,
+  created_by integer references users (id)
+    on update cascade on delete set null,
+  created_at timestamp not null default current_timestamp
+);
+
+create index files_doc on files (doc_id);
+
+-- vim: foldmethod=marker:
@@ -0,0 +1,3 @@
+drop index jobs_state;
+drop table jobs;
+alter table files drop column source_id;
@@ -0,0 +1,27 @@
+-- Derived files {{{1
+
+-- e.g. a transcoded recording points to the uploaded original
+alter table files add column source_id integer references files (id)
+  on update cascade on delete cascade;
+
+-- Background jobs {{{1
+
+-- work which takes too long to be done while handling a request, picked up
+-- by a worker thread in order of creation
+create table jobs (
+  id integer primary key not null,
+  -- what to do, e.g. transcode
+  kind text not null,
+  -- the file to do it with
+  file_id integer not null references files (id)
+    on update cascade on delete cascade,
+  state text not null default 'queued'
+    check (state in ('queued', 'running', 'done', 'failed')),
+  error text,
+  created_at timestamp not null default current_timestamp,
+  finished_at timestamp
+);
+
+create index jobs_state on jobs (state);
+
+-- vim: foldmethod=marker:
@@ -0,0 +1,42 @@
+drop view view_doc2speaker;
+drop view view_docs;
+
+-- documents in several corpora keep only the oldest one
+alter table docs add column corpus_id integer references corpora (id)
+  on update cascade on delete restrict;
+update docs set corpus_id = (
+  select min(corpus_id) from doc2corpus where doc2corpus.doc_id = docs.id
+);
+
+drop index doc2corpus_corpus;
+drop table doc2corpus;
+
+create view view_docs as
+  select
+    docs.id as id,
+    projects.label as project,
+    corpora.label as corpus,
+    place,
+    region,
+    date
+  from docs
+  join projects on projects.id = project_id
+  join corpora on corpora.id = corpus_id
+  natural join view_geo;
+
+create view view_doc2speaker as
+  select
+    doc2speaker.id as id,
+    view_docs.project as project,
+    view_docs.corpus as corpus,
+    view_docs.place as doc_place,
+    view_docs.region as doc_region,
+    gender,
+    (case when date - year < 35 then 'mladší' else 'starší' end) as age,
+    (case when education = 'VŠ' then 'vyšší' else 'nižší' end) as education,
+    view_speakers.place as spk_place,
+    view_speakers.region as spk_region,
+    words
+  from doc2speaker
+  join view_speakers on doc2speaker.speaker_id = view_speakers.id
+  join view_docs on doc2speaker.doc_id = view_docs.id;
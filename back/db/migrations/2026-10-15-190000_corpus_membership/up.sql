@@ -0,0 +1,59 @@
+-- Corpus membership {{{1
+
+-- a document can belong to several corpora, e.g. the main corpus and
+-- a themed sub-corpus
+create table doc2corpus (
+  doc_id integer not null references docs (id)
+    on update cascade on delete cascade,
+  corpus_id integer not null references corpora (id)
+    on update cascade on delete restrict,
+  primary key (doc_id, corpus_id)
+);
+
+create index doc2corpus_corpus on doc2corpus (corpus_id);
+
+insert into doc2corpus (doc_id, corpus_id)
+  select id, corpus_id from docs where corpus_id is not null;
+
+-- Views {{{1
+
+-- the views refer to docs.corpus_id, so they have to go before the column
+drop view view_doc2speaker;
+drop view view_docs;
+
+alter table docs drop column corpus_id;
+
+-- one row per document and corpus it belongs to, so that statistics can
+-- still be broken down by corpus
+create view view_docs as
+  select
+    docs.id as id,
+    projects.label as project,
+    corpora.label as corpus,
+    place,
+    region,
+    date
+  from docs
+  join projects on projects.id = project_id
+  join doc2corpus on doc2corpus.doc_id = docs.id
+  join corpora on corpora.id = doc2corpus.corpus_id
+  natural join view_geo;
+
+create view view_doc2speaker as
+  select
+    doc2speaker.id as id,
+    view_docs.project as project,
+    view_docs.corpus as corpus,
+    view_docs.place as doc_place,
+    view_docs.region as doc_region,
+    gender,
+    (case when date - year < 35 then 'mladší' else 'starší' end) as age,
+    (case when education = 'VŠ' then 'vyšší' else 'nižší' end) as education,
+    view_speakers.place as spk_place,
+    view_speakers.region as spk_region,
+    words
+  from doc2speaker
+  join view_speakers on doc2speaker.speaker_id = view_speakers.id
+  join view_docs on doc2speaker.doc_id = view_docs.id;
+
+-- vim: foldmethod=marker:
@@ -0,0 +1,4 @@
+alter table enum_places drop column dialect_area_id;
+alter table enum_places drop column longitude;
+alter table enum_places drop column latitude;
+drop table enum_dialect_areas;
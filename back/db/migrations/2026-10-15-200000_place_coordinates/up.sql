@@ -0,0 +1,34 @@
+-- Dialect areas {{{1
+
+-- broader than regions, which some of them cut across, e.g. the
+-- borderlands
+create table enum_dialect_areas (
+  id integer primary key not null,
+  label text unique not null
+);
+insert into enum_dialect_areas (label) values
+  ('česká'),
+  ('středomoravská'),
+  ('východomoravská'),
+  ('slezská'),
+  ('smíšená');
+
+-- Places {{{1
+
+-- WGS 84, for plotting places on a map; unknown for places added before
+-- coordinates were tracked
+alter table enum_places add column latitude real
+  check (latitude between -90 and 90);
+alter table enum_places add column longitude real
+  check (longitude between -180 and 180);
+alter table enum_places add column dialect_area_id integer
+  references enum_dialect_areas (id) on update cascade on delete restrict;
+
+update enum_places set latitude = 50.0875, longitude = 14.4214, dialect_area_id = 1
+  where label = 'Praha';
+update enum_places set latitude = 49.1951, longitude = 16.6068, dialect_area_id = 2
+  where label = 'Brno';
+update enum_places set latitude = 49.8209, longitude = 18.2625, dialect_area_id = 4
+  where label = 'Ostrava';
+
+-- vim: foldmethod=marker:
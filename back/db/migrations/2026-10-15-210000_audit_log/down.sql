@@ -0,0 +1,3 @@
+drop index audit_log_user;
+drop index audit_log_entity;
+drop table audit_log;
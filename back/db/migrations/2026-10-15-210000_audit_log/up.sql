@@ -0,0 +1,23 @@
+-- Audit log {{{1
+
+-- who changed what, for accountability and for undoing bulk operations
+-- by hand if need be
+create table audit_log (
+  id integer primary key not null,
+  -- null for changes made by the system itself
+  user_id integer references users (id)
+    on update cascade on delete set null,
+  -- what was done, e.g. speaker.merge
+  action text not null,
+  -- what it was done to, e.g. speaker 12
+  entity text not null,
+  entity_id integer not null,
+  -- JSON with whatever is needed to reconstruct the previous state
+  details text not null default '{}',
+  created_at timestamp not null default current_timestamp
+);
+
+create index audit_log_entity on audit_log (entity, entity_id);
+create index audit_log_user on audit_log (user_id);
+
+-- vim: foldmethod=marker:
@@ -0,0 +1 @@
+alter table palette_entries drop column deprecated;
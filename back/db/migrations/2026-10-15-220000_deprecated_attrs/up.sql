@@ -0,0 +1,9 @@
+-- Deprecated attribute codes {{{1
+
+-- attribute codes which are being phased out: they're still recognized in
+-- transcripts, but reported as warnings and no longer offered in the
+-- palette
+alter table palette_entries add column deprecated boolean not null default 0
+  check (kind = 'attr' or not deprecated);
+
+-- vim: foldmethod=marker:
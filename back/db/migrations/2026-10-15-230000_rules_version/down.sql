@@ -0,0 +1 @@
+alter table validation_runs drop column rules_version;
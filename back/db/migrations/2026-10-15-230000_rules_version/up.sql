@@ -0,0 +1,8 @@
+-- Rules versions {{{1
+
+-- which rules a validation run enforced (cf. eaf::parser::ParserConfig::
+-- version), so that runs done before the rules were tightened can be
+-- recognized; unknown for runs recorded before versions were tracked
+alter table validation_runs add column rules_version text;
+
+-- vim: foldmethod=marker:
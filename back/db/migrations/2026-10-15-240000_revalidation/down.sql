@@ -0,0 +1,5 @@
+drop table scheduled_tasks;
+
+alter table validation_runs drop column regression;
+alter table validation_runs drop column checksum;
+alter table validation_runs drop column file_id;
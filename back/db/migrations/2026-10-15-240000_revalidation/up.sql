@@ -0,0 +1,19 @@
+-- Revalidation {{{1
+
+-- which stored file a validation run checked and its SHA-256, so that runs
+-- whose file has been replaced or modified since can be recognized; unknown
+-- for runs of transcripts submitted directly rather than stored
+alter table validation_runs add column file_id integer references files(id);
+alter table validation_runs add column checksum text;
+-- the run found more mistakes than the document's previous one
+alter table validation_runs add column regression boolean not null default 0;
+
+-- Scheduled tasks {{{1
+
+-- when periodic tasks which aren't tied to a project last ran
+create table scheduled_tasks (
+  name text primary key not null,
+  last_run_at timestamp not null
+);
+
+-- vim: foldmethod=marker:
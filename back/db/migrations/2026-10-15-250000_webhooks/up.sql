@@ -0,0 +1,36 @@
+-- Webhooks {{{1
+
+-- URLs notified about events in a project, e.g. documents changing state,
+-- so that external pipelines don't need to poll; payloads are signed with
+-- the secret
+create table webhooks (
+  id integer primary key not null,
+  project_id integer not null references projects(id) on delete cascade,
+  url text not null,
+  secret text not null,
+  active boolean not null default 1,
+  created_at timestamp not null default current_timestamp
+);
+create index webhooks_project on webhooks(project_id);
+
+-- Deliveries {{{1
+
+-- one per event and webhook; failed deliveries are retried with a backoff
+-- until they run out of attempts
+create table webhook_deliveries (
+  id integer primary key not null,
+  webhook_id integer not null references webhooks(id) on delete cascade,
+  event text not null,
+  payload text not null,
+  state text not null default 'queued' check (state in ('queued', 'done', 'failed')),
+  attempts integer not null default 0,
+  -- HTTP status of the last attempt, if a response was received at all
+  status integer,
+  error text,
+  created_at timestamp not null default current_timestamp,
+  next_attempt_at timestamp not null default current_timestamp
+);
+create index webhook_deliveries_webhook on webhook_deliveries(webhook_id);
+create index webhook_deliveries_state on webhook_deliveries(state, next_attempt_at);
+
+-- vim: foldmethod=marker:
@@ -0,0 +1 @@
+alter table corpora drop column released_at;
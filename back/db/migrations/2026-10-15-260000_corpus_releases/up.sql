@@ -0,0 +1,7 @@
+-- Corpus releases {{{1
+
+-- accepted documents of released corpora are available through the public
+-- API, anonymized
+alter table corpora add column released_at timestamp;
+
+-- vim: foldmethod=marker:
@@ -0,0 +1,13 @@
+-- Grapheme substitutions {{{1
+
+-- Commonly mistyped graphemes in each project and what they should be
+-- replaced with, offered as quick fixes for mistakes covering them
+create table substitutions (
+  project_id integer not null references projects (id)
+    on update cascade on delete cascade,
+  source text not null,
+  target text not null,
+  primary key (project_id, source)
+);
+
+-- vim: foldmethod=marker:
@@ -0,0 +1,4 @@
+drop trigger docs_assignee_member;
+drop trigger doc2speaker_same_project_update;
+drop trigger doc2speaker_same_project;
+drop table project_members;
@@ -0,0 +1,56 @@
+-- Project members {{{1
+
+-- Users only see and work on projects they're members of, so that one
+-- deployment can host unrelated transcription campaigns; admins see all
+-- projects regardless
+create table project_members (
+  project_id integer not null references projects (id)
+    on update cascade on delete cascade,
+  user_id integer not null references users (id)
+    on update cascade on delete cascade,
+  primary key (project_id, user_id)
+);
+create index project_members_user on project_members (user_id);
+
+-- existing users are members of the projects they've worked on
+insert or ignore into project_members (project_id, user_id)
+  select project_id, assigned_to_id from docs where assigned_to_id is not null
+  union select project_id, assigned_by_id from docs where assigned_by_id is not null
+  union select project_id, user_id from speakers
+  union select docs.project_id, files.created_by
+    from files join docs on docs.id = files.doc_id
+    where files.created_by is not null;
+
+-- Cross-project links {{{1
+
+-- speakers of one project can't appear in documents of another
+create trigger doc2speaker_same_project
+before insert on doc2speaker
+when (select project_id from docs where id = new.doc_id)
+  is not (select project_id from speakers where id = new.speaker_id)
+begin
+  select raise(abort, 'speaker belongs to a different project than document');
+end;
+
+create trigger doc2speaker_same_project_update
+before update on doc2speaker
+when (select project_id from docs where id = new.doc_id)
+  is not (select project_id from speakers where id = new.speaker_id)
+begin
+  select raise(abort, 'speaker belongs to a different project than document');
+end;
+
+-- documents can only be assigned to members of their project, or admins
+create trigger docs_assignee_member
+before update of assigned_to_id on docs
+when new.assigned_to_id is not null
+  and not exists (
+    select 1 from project_members
+    where project_id = new.project_id and user_id = new.assigned_to_id
+  )
+  and (select role_id from users where id = new.assigned_to_id) is not 3
+begin
+  select raise(abort, 'assignee is not a member of the document''s project');
+end;
+
+-- vim: foldmethod=marker:
@@ -0,0 +1,14 @@
+drop trigger docs_assignee_member;
+create trigger docs_assignee_member
+before update of assigned_to_id on docs
+when new.assigned_to_id is not null
+  and not exists (
+    select 1 from project_members
+    where project_id = new.project_id and user_id = new.assigned_to_id
+  )
+  and (select role_id from users where id = new.assigned_to_id) is not 3
+begin
+  select raise(abort, 'assignee is not a member of the document''s project');
+end;
+drop table role_permissions;
+drop table enum_permissions;
@@ -0,0 +1,71 @@
+-- Permissions {{{1
+
+-- What users may do is decided by the permissions granted to their role,
+-- so that new roles (e.g. reviewers who can't edit configs) don't need code
+-- changes
+create table enum_permissions (
+  id integer primary key not null,
+  label text unique not null,
+  description text not null
+);
+insert into enum_permissions (id, label, description) values
+  (1, 'project.all', 'access all projects, not just those one is a member of'),
+  (2, 'member.edit', 'add users to and remove them from projects'),
+  (3, 'role.edit', 'create roles and change their permissions'),
+  (4, 'user.act_for', 'act on behalf of other users, e.g. manage their bookmarks'),
+  (5, 'doc.assign', 'assign documents to transcribers'),
+  (6, 'doc.review', 'accept or return submitted documents'),
+  (7, 'doc.edit', 'upload and change transcripts, recordings and document metadata'),
+  (8, 'speaker.edit', 'import and merge speakers'),
+  (9, 'config.edit', 'change palettes, dictionaries, substitutions and tier mappings'),
+  (10, 'webhook.edit', 'add, change and remove webhooks'),
+  (11, 'geo.edit', 'add, change and remove places and regions'),
+  (12, 'export.bundle', 'download anonymized bundles of documents'),
+  (13, 'export.release', 'release corpora and stamp their headers'),
+  (14, 'backup.manage', 'create and verify backups'),
+  (15, 'maintenance.run', 'run bulk replacements and repository maintenance');
+
+create table role_permissions (
+  role_id integer not null references enum_roles (id)
+    on update cascade on delete cascade,
+  permission_id integer not null references enum_permissions (id)
+    on update cascade on delete cascade,
+  primary key (role_id, permission_id)
+);
+
+-- what the fixed roles could do before: admins (3) anything, supervisors (2)
+-- run their projects, regular users (1) transcribe
+insert into role_permissions (role_id, permission_id)
+  select 3, id from enum_permissions;
+insert into role_permissions (role_id, permission_id)
+  select 2, id from enum_permissions
+  where label in (
+    'doc.assign', 'doc.review', 'doc.edit', 'speaker.edit', 'config.edit',
+    'webhook.edit', 'export.bundle'
+  );
+insert into role_permissions (role_id, permission_id)
+  select 1, id from enum_permissions where label = 'doc.edit';
+
+-- Assignments {{{1
+
+-- documents can be assigned to users with access to all projects, rather
+-- than to admins
+drop trigger docs_assignee_member;
+create trigger docs_assignee_member
+before update of assigned_to_id on docs
+when new.assigned_to_id is not null
+  and not exists (
+    select 1 from project_members
+    where project_id = new.project_id and user_id = new.assigned_to_id
+  )
+  and not exists (
+    select 1 from users
+      join role_permissions on role_permissions.role_id = users.role_id
+      join enum_permissions on enum_permissions.id = role_permissions.permission_id
+    where users.id = new.assigned_to_id and enum_permissions.label = 'project.all'
+  )
+begin
+  select raise(abort, 'assignee is not a member of the document''s project');
+end;
+
+-- vim: foldmethod=marker:
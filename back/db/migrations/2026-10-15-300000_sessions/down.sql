@@ -0,0 +1,3 @@
+delete from role_permissions where permission_id = 16;
+delete from enum_permissions where id = 16;
+drop table sessions;
@@ -0,0 +1,19 @@
+-- Sessions {{{1
+
+-- Only a hash of each session's token is stored, the token itself is only
+-- ever known to the client
+create table sessions (
+  id integer primary key not null,
+  user_id integer not null references users (id)
+    on update cascade on delete cascade,
+  token_hash text unique not null,
+  -- e.g. the client's user agent, to tell sessions apart
+  device text not null,
+  created_at timestamp not null default current_timestamp,
+  last_seen_at timestamp not null default current_timestamp
+);
+create index sessions_user on sessions (user_id);
+
+insert into enum_permissions (id, label, description) values
+  (16, 'session.revoke', 'log other users out of all their sessions');
+insert into role_permissions (role_id, permission_id) values (3, 16);
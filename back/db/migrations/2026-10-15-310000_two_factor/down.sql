@@ -0,0 +1,4 @@
+delete from role_permissions where permission_id = 17;
+delete from enum_permissions where id = 17;
+drop table recovery_codes;
+drop table two_factor;
@@ -0,0 +1,28 @@
+-- Two-factor authentication {{{1
+
+-- TOTP (RFC 6238) secrets of users who've enrolled; enabled once they've
+-- confirmed their authenticator app works by entering a code from it
+create table two_factor (
+  user_id integer primary key not null references users (id)
+    on update cascade on delete cascade,
+  -- base32, as shown to the user
+  secret text not null,
+  enabled_at timestamp,
+  -- time step of the last accepted code, so that codes can't be replayed
+  last_step integer
+);
+
+-- single-use codes for when the authenticator app is lost
+create table recovery_codes (
+  id integer primary key not null,
+  user_id integer not null references users (id)
+    on update cascade on delete cascade,
+  code_hash text not null,
+  used_at timestamp
+);
+create index recovery_codes_user on recovery_codes (user_id);
+
+-- only accounts which can do serious damage need the extra protection
+insert into enum_permissions (id, label, description) values
+  (17, 'account.two_factor', 'protect one''s account with two-factor authentication');
+insert into role_permissions (role_id, permission_id) values (2, 17), (3, 17);
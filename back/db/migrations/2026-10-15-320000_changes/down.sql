@@ -0,0 +1,10 @@
+drop trigger validation_runs_changes_delete;
+drop trigger validation_runs_changes_insert;
+drop trigger reviews_changes_delete;
+drop trigger reviews_changes_insert;
+drop trigger docs_changes_delete;
+drop trigger docs_changes_assign;
+drop trigger docs_changes_update;
+drop trigger docs_changes_move;
+drop trigger docs_changes_insert;
+drop table changes;
@@ -0,0 +1,106 @@
+-- Changes {{{1
+
+-- A feed of changes to documents and what clients show alongside them, for
+-- clients syncing incrementally (see db::sync). IDs are never reused, so the
+-- last one a client has seen serves as its cursor. Only which entity
+-- changed is recorded: clients get its state as of when they sync, and
+-- entities which are gone or no longer accessible count as deleted.
+create table changes (
+  id integer primary key autoincrement not null,
+  -- document, assignment, review or validation_run
+  entity text not null,
+  entity_id integer not null,
+  doc_id integer not null,
+  -- for filtering by access, as deleted documents can't be looked up
+  project_id integer not null,
+  changed_at timestamp not null default current_timestamp
+);
+create index changes_project on changes (project_id, id);
+
+-- Documents and their assignments {{{2
+
+create trigger docs_changes_insert
+after insert on docs
+begin
+  insert into changes (entity, entity_id, doc_id, project_id) values
+    ('document', new.id, new.id, new.project_id),
+    ('assignment', new.id, new.id, new.project_id);
+end;
+
+-- users with access to the old project only see the document go away
+create trigger docs_changes_move
+after update of project_id on docs
+when new.project_id != old.project_id
+begin
+  insert into changes (entity, entity_id, doc_id, project_id) values
+    ('document', old.id, old.id, old.project_id),
+    ('assignment', old.id, old.id, old.project_id);
+end;
+
+create trigger docs_changes_update
+after update of project_id, done, place_id, done_at, state_id on docs
+begin
+  insert into changes (entity, entity_id, doc_id, project_id) values
+    ('document', new.id, new.id, new.project_id);
+end;
+
+create trigger docs_changes_assign
+after update of project_id, assigned_to_id, assigned_by_id, assigned_at, due_at on docs
+begin
+  insert into changes (entity, entity_id, doc_id, project_id) values
+    ('assignment', new.id, new.id, new.project_id);
+end;
+
+create trigger docs_changes_delete
+after delete on docs
+begin
+  insert into changes (entity, entity_id, doc_id, project_id) values
+    ('document', old.id, old.id, old.project_id),
+    ('assignment', old.id, old.id, old.project_id);
+end;
+
+-- Reviews and validation reports {{{2
+
+create trigger reviews_changes_insert
+after insert on reviews
+begin
+  insert into changes (entity, entity_id, doc_id, project_id)
+    select 'review', new.id, new.doc_id, project_id from docs where id = new.doc_id;
+end;
+
+-- nothing to record if the whole document is gone
+create trigger reviews_changes_delete
+after delete on reviews
+begin
+  insert into changes (entity, entity_id, doc_id, project_id)
+    select 'review', old.id, old.doc_id, project_id from docs where id = old.doc_id;
+end;
+
+create trigger validation_runs_changes_insert
+after insert on validation_runs
+begin
+  insert into changes (entity, entity_id, doc_id, project_id)
+    select 'validation_run', new.id, new.doc_id, project_id from docs where id = new.doc_id;
+end;
+
+create trigger validation_runs_changes_delete
+after delete on validation_runs
+begin
+  insert into changes (entity, entity_id, doc_id, project_id)
+    select 'validation_run', old.id, old.doc_id, project_id
+    from docs where id = old.doc_id;
+end;
+
+-- Existing data {{{2
+
+-- so that syncing from scratch gets everything
+insert into changes (entity, entity_id, doc_id, project_id)
+  select 'document', id, id, project_id from docs;
+insert into changes (entity, entity_id, doc_id, project_id)
+  select 'assignment', id, id, project_id from docs;
+insert into changes (entity, entity_id, doc_id, project_id)
+  select 'review', reviews.id, doc_id, project_id
+  from reviews join docs on docs.id = doc_id order by reviews.id;
+insert into changes (entity, entity_id, doc_id, project_id)
+  select 'validation_run', validation_runs.id, doc_id, project_id
+  from validation_runs join docs on docs.id = doc_id order by validation_runs.id;
@@ -0,0 +1,22 @@
+-- Metadata discrepancies {{{1
+
+-- Where a document's session metadata (CMDI or IMDI) disagrees with the DB,
+-- as found by the latest cross-check (see db::metadata). Neither source is
+-- trusted over the other, someone has to look into it.
+create table metadata_discrepancies (
+  id integer primary key not null,
+  doc_id integer not null references docs (id)
+    on update cascade on delete cascade,
+  -- the metadata file checked
+  file_id integer not null references files (id)
+    on update cascade on delete cascade,
+  -- date, place, region, speaker, gender or year
+  field text not null,
+  -- nickname of the speaker for speaker fields
+  speaker text,
+  -- missing on either side e.g. for speakers only one of them knows about
+  in_metadata text,
+  in_db text,
+  created_at timestamp not null default current_timestamp
+);
+create index metadata_discrepancies_doc on metadata_discrepancies (doc_id);
@@ -0,0 +1,3 @@
+drop trigger review_comments_changes_delete;
+drop trigger review_comments_changes_insert;
+drop table review_comments;
@@ -0,0 +1,41 @@
+-- Review comments {{{1
+
+-- Reviewers' comments on documents, anchored either to an annotation or to
+-- a stretch of the recording, e.g. one nobody transcribed
+create table review_comments (
+  id integer primary key not null,
+  doc_id integer not null references docs (id)
+    on update cascade on delete cascade,
+  author_id integer not null references users (id)
+    on update cascade on delete restrict,
+  tier text,
+  annotation text,
+  -- in milliseconds
+  start_ms integer,
+  end_ms integer,
+  body text not null,
+  created_at timestamp not null default current_timestamp,
+  check (
+    (tier is not null and annotation is not null
+      and start_ms is null and end_ms is null)
+    or (tier is null and annotation is null
+      and start_ms >= 0 and end_ms > start_ms)
+  )
+);
+create index review_comments_doc on review_comments (doc_id);
+
+-- Sync {{{1
+
+create trigger review_comments_changes_insert
+after insert on review_comments
+begin
+  insert into changes (entity, entity_id, doc_id, project_id)
+    select 'comment', new.id, new.doc_id, project_id from docs where id = new.doc_id;
+end;
+
+create trigger review_comments_changes_delete
+after delete on review_comments
+begin
+  insert into changes (entity, entity_id, doc_id, project_id)
+    select 'comment', old.id, old.doc_id, project_id from docs where id = old.doc_id;
+end;
@@ -0,0 +1,22 @@
+-- Word alignments {{{1
+
+-- Word-level times from forced alignment. They belong to a particular
+-- version of the transcript (file), since words are numbered within their
+-- annotation, which edits would throw off.
+create table word_alignments (
+  id integer primary key not null,
+  doc_id integer not null references docs (id)
+    on update cascade on delete cascade,
+  file_id integer not null references files (id)
+    on update cascade on delete cascade,
+  annotation text not null,
+  -- index of the word within the annotation, from 0
+  word integer not null check (word >= 0),
+  -- the word as aligned, to catch mismatched numbering
+  form text not null,
+  -- in milliseconds
+  start_ms integer not null check (start_ms >= 0),
+  end_ms integer not null check (end_ms >= start_ms),
+  unique (file_id, annotation, word)
+);
+create index word_alignments_doc on word_alignments (doc_id);
@@ -0,0 +1,4 @@
+alter table validation_runs drop column acknowledged;
+alter table mistakes drop column acknowledged;
+
+drop table acknowledged_mistakes;
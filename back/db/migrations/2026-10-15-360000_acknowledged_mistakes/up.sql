@@ -0,0 +1,32 @@
+-- Acknowledged mistakes {{{1
+
+-- mistakes reviewers have accepted as intentional, e.g. a nonstandard form
+-- the speaker really used; they're matched against the mistakes found by
+-- later validations of the document by where they are and what they look
+-- like, so editing the segment voids the acknowledgment
+create table acknowledged_mistakes (
+  id integer primary key not null,
+  doc_id integer not null references docs (id)
+    on update cascade on delete cascade,
+  tier text not null,
+  annotation text not null,
+  kind text not null,
+  segment text not null,
+  -- byte offset of the mistake in the segment, if known
+  start integer,
+  reason text not null check (reason <> ''),
+  author_id integer not null references users (id)
+    on update cascade on delete restrict,
+  created_at timestamp not null default current_timestamp
+);
+create unique index acknowledged_mistakes_unique on acknowledged_mistakes
+  (doc_id, tier, annotation, kind, segment, ifnull(start, -1));
+
+-- Validation runs {{{1
+
+-- acknowledged mistakes are still stored with each run, but they're not
+-- counted in validation_runs.mistakes, only separately
+alter table mistakes add column acknowledged boolean not null default 0;
+alter table validation_runs add column acknowledged integer not null default 0;
+
+-- vim: foldmethod=marker:
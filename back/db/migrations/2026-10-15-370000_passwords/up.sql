@@ -0,0 +1,6 @@
+-- Passwords {{{1
+
+-- PHC strings (argon2id, with the salt and parameters), so that the hashing
+-- parameters can be changed without invalidating existing passwords; users
+-- without a password can't log in
+alter table users add column password_hash text;
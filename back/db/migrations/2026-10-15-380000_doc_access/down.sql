@@ -0,0 +1,2 @@
+delete from role_permissions where permission_id = 18;
+delete from enum_permissions where id = 18;
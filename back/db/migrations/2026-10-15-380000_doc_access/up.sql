@@ -0,0 +1,7 @@
+-- Document access {{{1
+
+-- without this permission, users (i.e. transcribers) only see and edit the
+-- documents assigned to them
+insert into enum_permissions (id, label, description) values
+  (18, 'doc.all', 'see and edit all documents of one''s projects, not just those assigned to one');
+insert into role_permissions (role_id, permission_id) values (2, 18), (3, 18);
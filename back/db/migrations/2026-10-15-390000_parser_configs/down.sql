@@ -0,0 +1 @@
+drop table parser_configs;
@@ -0,0 +1,18 @@
+-- Parser configs {{{1
+
+-- patterns the project's transcripts are validated against on top of its
+-- palette, as JSON arrays of regexes; projects without a row only use the
+-- palette
+create table parser_configs (
+  project_id integer primary key not null references projects (id)
+    on update cascade on delete cascade,
+  whitelist text not null default '[]',
+  blacklist text not null default '[]',
+  atoms text not null default '[]',
+  after_angle text not null default '[]',
+  updated_by integer references users (id)
+    on update cascade on delete set null,
+  updated_at timestamp not null default current_timestamp
+);
+
+-- vim: foldmethod=marker:
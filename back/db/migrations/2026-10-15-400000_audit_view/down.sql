@@ -0,0 +1,2 @@
+delete from role_permissions where permission_id = 19;
+delete from enum_permissions where id = 19;
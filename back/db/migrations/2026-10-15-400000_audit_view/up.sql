@@ -0,0 +1,8 @@
+-- Audit log access {{{1
+
+-- supervisors check who changed what in their projects
+insert into enum_permissions (id, label, description) values
+  (19, 'audit.view', 'see the audit log of documents and users');
+insert into role_permissions (role_id, permission_id) values (2, 19), (3, 19);
+
+-- vim: foldmethod=marker:
@@ -0,0 +1,22 @@
+-- Tier validation policies {{{1
+
+-- per-project rules for how a tier's annotations are validated, tried in
+-- order of priority (lowest first); tiers is a regex matched against the
+-- whole tier ID or linguistic type, depending on target, and pattern is
+-- the regex values must match under the 'pattern' policy; tiers no rule
+-- selects are validated as freeform transcript, or against their
+-- controlled vocabulary if they have one
+create table tier_policies (
+  id integer primary key not null,
+  project_id integer not null references projects (id)
+    on update cascade on delete cascade,
+  priority integer not null,
+  target text not null check (target in ('tier_id', 'linguistic_type')),
+  tiers text not null,
+  policy text not null
+    check (policy in ('freeform', 'vocabulary', 'pattern', 'skip')),
+  pattern text check ((policy = 'pattern') = (pattern is not null)),
+  unique (project_id, priority)
+);
+
+-- vim: foldmethod=marker:
@@ -0,0 +1,28 @@
+-- Document revisions {{{1
+
+-- every saved version of a document's transcript, numbered from 1 per
+-- document; the file itself is in files, the revision just gives it a
+-- stable number and author for browsing the history, diffing and restoring
+create table document_revisions (
+  id integer primary key not null,
+  doc_id integer not null references docs (id)
+    on update cascade on delete cascade,
+  number integer not null check (number > 0),
+  file_id integer not null unique references files (id)
+    on update cascade on delete cascade,
+  author_id integer references users (id)
+    on update cascade on delete set null,
+  created_at timestamp not null default current_timestamp,
+  unique (doc_id, number)
+);
+
+-- transcripts stored so far, in the order they were stored
+insert into document_revisions (doc_id, number, file_id, author_id, created_at)
+  select f.doc_id,
+    (select count(*) from files g
+      where g.doc_id = f.doc_id and g.role = 'eaf' and g.id <= f.id),
+    f.id, f.created_by, f.created_at
+  from files f
+  where f.role = 'eaf';
+
+-- vim: foldmethod=marker:
@@ -0,0 +1,7 @@
+-- Anonymization spans {{{1
+
+-- attribute codes (as a JSON array of regexes) of angle spans whose tokens
+-- are replaced by placeholders in anonymized exports, e.g. personal names
+alter table parser_configs add column anonymize text not null default '[]';
+
+-- vim: foldmethod=marker:
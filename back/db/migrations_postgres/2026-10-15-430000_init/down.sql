@@ -0,0 +1,52 @@
+drop table changes;
+drop function record_doc_changes, record_doc_move, record_doc_change,
+  record_doc_child_change cascade;
+drop view view_doc2speaker;
+drop view view_docs;
+drop view view_speakers;
+drop table review_comments;
+drop table reviews;
+drop table acknowledged_mistakes;
+drop table mistakes;
+drop table validation_runs;
+drop table bookmarks;
+drop table word_alignments;
+drop table metadata_discrepancies;
+drop table jobs;
+drop table document_revisions;
+drop table files;
+drop table doc2corpus;
+drop table doc2speaker;
+drop table docs;
+drop function check_docs_assignee_member;
+drop function check_doc2speaker_same_project;
+drop table speakers;
+drop table webhook_deliveries;
+drop table webhooks;
+drop table parser_configs;
+drop table substitutions;
+drop table project_dictionaries;
+drop table palette_entries;
+drop table tier_policies;
+drop table tier_mappings;
+drop table scheduled_tasks;
+drop table audit_log;
+drop table recovery_codes;
+drop table two_factor;
+drop table sessions;
+drop table digest_settings;
+drop table project_members;
+drop table corpora;
+drop table projects;
+drop table users;
+drop view view_geo;
+drop table role_permissions;
+drop table enum_permissions;
+drop table enum_return_reasons;
+drop table enum_doc_states;
+drop table enum_places;
+drop table enum_dialect_areas;
+drop table enum_regions;
+drop table enum_educations;
+drop table enum_genders;
+drop table enum_roles;
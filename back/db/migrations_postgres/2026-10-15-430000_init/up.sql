@@ -0,0 +1,752 @@
+-- The schema as of the SQLite migrations up to and including
+-- 2026-10-15-420000_document_revisions, in one go; see there for what the
+-- tables and columns are for. Later changes need a migration here as well.
+-- Unlike the SQLite migrations, this doesn't add toy data, only the enums.
+
+-- Immutable tables (= "enums") {{{1
+
+create table enum_roles (
+  id serial primary key,
+  label text unique not null
+);
+insert into enum_roles (label) values ('regular'), ('supervisor'), ('admin');
+
+create table enum_genders (
+  id serial primary key,
+  label text unique not null
+);
+insert into enum_genders (label) values ('muž'), ('žena');
+
+create table enum_educations (
+  id serial primary key,
+  label text unique not null
+);
+insert into enum_educations (label) values ('ZŠ'), ('SŠ'), ('SOŠ'), ('VŠ');
+
+create table enum_regions (
+  id serial primary key,
+  label text unique not null
+);
+insert into enum_regions (label) values
+  ('severovýchodočeská'),
+  ('středočeská'),
+  ('západočeská'),
+  ('jihočeská'),
+  ('českomoravská'),
+  ('středomoravská'),
+  ('východomoravská'),
+  ('slezská'),
+  ('pohraničí české'),
+  ('pohraničí moravské a slezské'),
+  ('zahraničí');
+
+create table enum_dialect_areas (
+  id serial primary key,
+  label text unique not null
+);
+insert into enum_dialect_areas (label) values
+  ('česká'),
+  ('středomoravská'),
+  ('východomoravská'),
+  ('slezská'),
+  ('smíšená');
+
+create table enum_places (
+  id serial primary key,
+  label text unique not null,
+  region_id integer not null references enum_regions (id)
+    on update cascade on delete restrict,
+  latitude double precision check (latitude between -90 and 90),
+  longitude double precision check (longitude between -180 and 180),
+  dialect_area_id integer references enum_dialect_areas (id)
+    on update cascade on delete restrict
+);
+insert into enum_places (label, region_id, latitude, longitude, dialect_area_id) values
+  ('Praha', 2, 50.0875, 14.4214, 1),
+  ('Brno', 6, 49.1951, 16.6068, 2),
+  ('Ostrava', 8, 49.8209, 18.2625, 4);
+
+create table enum_doc_states (
+  id serial primary key,
+  label text unique not null
+);
+insert into enum_doc_states (label) values
+  ('new'), ('assigned'), ('submitted'), ('returned'), ('accepted');
+
+create table enum_return_reasons (
+  id serial primary key,
+  label text unique not null
+);
+insert into enum_return_reasons (label) values
+  ('přepis'), ('segmentace'), ('mluvčí'), ('metadata'), ('jiné');
+
+-- IDs are given explicitly, as in the SQLite migrations which add them
+create table enum_permissions (
+  id integer primary key,
+  label text unique not null,
+  description text not null
+);
+insert into enum_permissions (id, label, description) values
+  (1, 'project.all', 'access all projects, not just those one is a member of'),
+  (2, 'member.edit', 'add users to and remove them from projects'),
+  (3, 'role.edit', 'create roles and change their permissions'),
+  (4, 'user.act_for', 'act on behalf of other users, e.g. manage their bookmarks'),
+  (5, 'doc.assign', 'assign documents to transcribers'),
+  (6, 'doc.review', 'accept or return submitted documents'),
+  (7, 'doc.edit', 'upload and change transcripts, recordings and document metadata'),
+  (8, 'speaker.edit', 'import and merge speakers'),
+  (9, 'config.edit', 'change palettes, dictionaries, substitutions and tier mappings'),
+  (10, 'webhook.edit', 'add, change and remove webhooks'),
+  (11, 'geo.edit', 'add, change and remove places and regions'),
+  (12, 'export.bundle', 'download anonymized bundles of documents'),
+  (13, 'export.release', 'release corpora and stamp their headers'),
+  (14, 'backup.manage', 'create and verify backups'),
+  (15, 'maintenance.run', 'run bulk replacements and repository maintenance'),
+  (16, 'session.revoke', 'log other users out of all their sessions'),
+  (17, 'account.two_factor', 'protect one''s account with two-factor authentication'),
+  (18, 'doc.all', 'see and edit all documents of one''s projects, not just those assigned to one'),
+  (19, 'audit.view', 'see the audit log of documents and users');
+
+create table role_permissions (
+  role_id integer not null references enum_roles (id)
+    on update cascade on delete cascade,
+  permission_id integer not null references enum_permissions (id)
+    on update cascade on delete cascade,
+  primary key (role_id, permission_id)
+);
+insert into role_permissions (role_id, permission_id)
+  select 3, id from enum_permissions;
+insert into role_permissions (role_id, permission_id)
+  select 2, id from enum_permissions
+  where label in (
+    'doc.assign', 'doc.review', 'doc.edit', 'speaker.edit', 'config.edit',
+    'webhook.edit', 'export.bundle', 'account.two_factor', 'doc.all', 'audit.view'
+  );
+insert into role_permissions (role_id, permission_id)
+  select 1, id from enum_permissions where label = 'doc.edit';
+
+-- Views {{{2
+
+create view view_geo as
+  select
+    enum_places.id as place_id,
+    enum_places.label as place,
+    enum_regions.label as region
+  from enum_places
+  join enum_regions on enum_places.region_id = enum_regions.id;
+
+-- Mutable tables {{{1
+
+-- Project management {{{2
+
+create table users (
+  id serial primary key,
+  username text unique not null,
+  role_id integer not null references enum_roles (id)
+    on update cascade on delete restrict,
+  badge text unique,
+  supervisor_id integer references users (id)
+    on update cascade on delete restrict,
+  email text,
+  password_hash text
+);
+
+create table projects (
+  id serial primary key,
+  label text unique not null,
+  badge text unique not null
+);
+
+create table corpora (
+  id serial primary key,
+  label text unique not null,
+  released_at timestamptz
+);
+
+create table project_members (
+  project_id integer not null references projects (id)
+    on update cascade on delete cascade,
+  user_id integer not null references users (id)
+    on update cascade on delete cascade,
+  primary key (project_id, user_id)
+);
+create index project_members_user on project_members (user_id);
+
+create table digest_settings (
+  project_id integer primary key references projects (id)
+    on update cascade on delete cascade,
+  enabled boolean not null default true,
+  weekday integer not null default 1
+    check (weekday between 1 and 7),
+  hour integer not null default 8
+    check (hour between 0 and 23),
+  last_sent_at timestamptz
+);
+
+create table sessions (
+  id serial primary key,
+  user_id integer not null references users (id)
+    on update cascade on delete cascade,
+  token_hash text unique not null,
+  device text not null,
+  created_at timestamptz not null default now(),
+  last_seen_at timestamptz not null default now()
+);
+create index sessions_user on sessions (user_id);
+
+create table two_factor (
+  user_id integer primary key references users (id)
+    on update cascade on delete cascade,
+  secret text not null,
+  enabled_at timestamptz,
+  last_step bigint
+);
+
+create table recovery_codes (
+  id serial primary key,
+  user_id integer not null references users (id)
+    on update cascade on delete cascade,
+  code_hash text not null,
+  used_at timestamptz
+);
+create index recovery_codes_user on recovery_codes (user_id);
+
+create table audit_log (
+  id serial primary key,
+  user_id integer references users (id)
+    on update cascade on delete set null,
+  action text not null,
+  entity text not null,
+  entity_id integer not null,
+  details text not null default '{}',
+  created_at timestamptz not null default now()
+);
+create index audit_log_entity on audit_log (entity, entity_id);
+create index audit_log_user on audit_log (user_id);
+
+create table scheduled_tasks (
+  name text primary key,
+  last_run_at timestamptz not null
+);
+
+-- Project configuration {{{2
+
+create table tier_mappings (
+  id serial primary key,
+  project_id integer not null references projects (id)
+    on update cascade on delete cascade,
+  priority integer not null,
+  source text not null check (source in ('tier_id', 'participant')),
+  pattern text not null,
+  unique (project_id, priority)
+);
+
+create table tier_policies (
+  id serial primary key,
+  project_id integer not null references projects (id)
+    on update cascade on delete cascade,
+  priority integer not null,
+  target text not null check (target in ('tier_id', 'linguistic_type')),
+  tiers text not null,
+  policy text not null
+    check (policy in ('freeform', 'vocabulary', 'pattern', 'skip')),
+  pattern text check ((policy = 'pattern') = (pattern is not null)),
+  unique (project_id, priority)
+);
+
+create table palette_entries (
+  id serial primary key,
+  project_id integer not null references projects (id)
+    on update cascade on delete cascade,
+  kind text not null check (kind in ('char', 'attr')),
+  position integer not null,
+  value text not null,
+  description text not null default '',
+  shortcut text,
+  deprecated boolean not null default false
+    check (kind = 'attr' or not deprecated),
+  unique (project_id, kind, value),
+  unique (project_id, shortcut)
+);
+
+create table project_dictionaries (
+  project_id integer not null references projects (id)
+    on update cascade on delete cascade,
+  priority integer not null,
+  dictionary text not null,
+  primary key (project_id, dictionary),
+  unique (project_id, priority)
+);
+
+create table substitutions (
+  project_id integer not null references projects (id)
+    on update cascade on delete cascade,
+  source text not null,
+  target text not null,
+  primary key (project_id, source)
+);
+
+create table parser_configs (
+  project_id integer primary key references projects (id)
+    on update cascade on delete cascade,
+  whitelist text not null default '[]',
+  blacklist text not null default '[]',
+  atoms text not null default '[]',
+  after_angle text not null default '[]',
+  updated_by integer references users (id)
+    on update cascade on delete set null,
+  updated_at timestamptz not null default now()
+);
+
+create table webhooks (
+  id serial primary key,
+  project_id integer not null references projects (id) on delete cascade,
+  url text not null,
+  secret text not null,
+  active boolean not null default true,
+  created_at timestamptz not null default now()
+);
+create index webhooks_project on webhooks (project_id);
+
+create table webhook_deliveries (
+  id serial primary key,
+  webhook_id integer not null references webhooks (id) on delete cascade,
+  event text not null,
+  payload text not null,
+  state text not null default 'queued' check (state in ('queued', 'done', 'failed')),
+  attempts integer not null default 0,
+  status integer,
+  error text,
+  created_at timestamptz not null default now(),
+  next_attempt_at timestamptz not null default now()
+);
+create index webhook_deliveries_webhook on webhook_deliveries (webhook_id);
+create index webhook_deliveries_state on webhook_deliveries (state, next_attempt_at);
+
+-- Data {{{2
+
+create table speakers (
+  id serial primary key,
+  user_id integer not null references users (id)
+    on update cascade on delete restrict,
+  project_id integer not null references projects (id)
+    on update cascade on delete restrict,
+  nickname text not null,
+  gender_id integer not null,
+  education_id integer not null,
+  place_id integer not null,
+  year integer not null
+);
+
+create table docs (
+  id serial primary key,
+  project_id integer not null references projects (id)
+    on update cascade on delete restrict,
+  assigned_to_id integer references users (id)
+    on update cascade on delete restrict,
+  assigned_by_id integer references users (id)
+    on update cascade on delete restrict,
+  done boolean,
+  date timestamptz not null,
+  place_id integer not null,
+  assigned_at timestamptz,
+  due_at timestamptz,
+  done_at timestamptz,
+  state_id integer not null default 1 references enum_doc_states (id)
+    on update cascade on delete restrict
+);
+create index docs_project_state on docs (project_id, state_id);
+
+create table doc2speaker (
+  id serial primary key,
+  doc_id integer not null references docs (id)
+    on update cascade on delete restrict,
+  speaker_id integer not null references speakers (id)
+    on update cascade on delete restrict,
+  words integer
+);
+
+create table doc2corpus (
+  doc_id integer not null references docs (id)
+    on update cascade on delete cascade,
+  corpus_id integer not null references corpora (id)
+    on update cascade on delete restrict,
+  primary key (doc_id, corpus_id)
+);
+create index doc2corpus_corpus on doc2corpus (corpus_id);
+
+create table files (
+  id serial primary key,
+  doc_id integer not null references docs (id)
+    on update cascade on delete cascade,
+  role text not null,
+  path text not null unique,
+  mime text not null,
+  size bigint not null,
+  created_by integer references users (id)
+    on update cascade on delete set null,
+  created_at timestamptz not null default now(),
+  source_id integer references files (id)
+    on update cascade on delete cascade
+);
+create index files_doc on files (doc_id);
+
+create table document_revisions (
+  id serial primary key,
+  doc_id integer not null references docs (id)
+    on update cascade on delete cascade,
+  number integer not null check (number > 0),
+  file_id integer not null unique references files (id)
+    on update cascade on delete cascade,
+  author_id integer references users (id)
+    on update cascade on delete set null,
+  created_at timestamptz not null default now(),
+  unique (doc_id, number)
+);
+
+create table jobs (
+  id serial primary key,
+  kind text not null,
+  file_id integer not null references files (id)
+    on update cascade on delete cascade,
+  state text not null default 'queued'
+    check (state in ('queued', 'running', 'done', 'failed')),
+  error text,
+  created_at timestamptz not null default now(),
+  finished_at timestamptz
+);
+create index jobs_state on jobs (state);
+
+create table metadata_discrepancies (
+  id serial primary key,
+  doc_id integer not null references docs (id)
+    on update cascade on delete cascade,
+  file_id integer not null references files (id)
+    on update cascade on delete cascade,
+  field text not null,
+  speaker text,
+  in_metadata text,
+  in_db text,
+  created_at timestamptz not null default now()
+);
+create index metadata_discrepancies_doc on metadata_discrepancies (doc_id);
+
+create table word_alignments (
+  id serial primary key,
+  doc_id integer not null references docs (id)
+    on update cascade on delete cascade,
+  file_id integer not null references files (id)
+    on update cascade on delete cascade,
+  annotation text not null,
+  word integer not null check (word >= 0),
+  form text not null,
+  start_ms integer not null check (start_ms >= 0),
+  end_ms integer not null check (end_ms >= start_ms),
+  unique (file_id, annotation, word)
+);
+create index word_alignments_doc on word_alignments (doc_id);
+
+create table bookmarks (
+  id serial primary key,
+  user_id integer not null references users (id)
+    on update cascade on delete cascade,
+  doc_id integer not null references docs (id)
+    on update cascade on delete cascade,
+  tier text not null,
+  annotation text not null,
+  note text not null default '',
+  created_at timestamptz not null default now(),
+  unique (user_id, doc_id, annotation)
+);
+create index bookmarks_doc on bookmarks (doc_id);
+
+-- Validation {{{2
+
+create table validation_runs (
+  id serial primary key,
+  doc_id integer not null references docs (id)
+    on update cascade on delete cascade,
+  created_at timestamptz not null default now(),
+  mistakes integer not null,
+  user_id integer references users (id)
+    on update cascade on delete set null,
+  rules_version text,
+  file_id integer references files (id),
+  checksum text,
+  regression boolean not null default false,
+  acknowledged integer not null default 0
+);
+create index validation_runs_user on validation_runs (user_id);
+
+create table mistakes (
+  id serial primary key,
+  run_id integer not null references validation_runs (id)
+    on update cascade on delete cascade,
+  tier text not null,
+  annotation text not null,
+  kind text not null,
+  segment text not null,
+  start integer,
+  "end" integer,
+  acknowledged boolean not null default false
+);
+create index mistakes_run on mistakes (run_id);
+
+create table acknowledged_mistakes (
+  id serial primary key,
+  doc_id integer not null references docs (id)
+    on update cascade on delete cascade,
+  tier text not null,
+  annotation text not null,
+  kind text not null,
+  segment text not null,
+  start integer,
+  reason text not null check (reason <> ''),
+  author_id integer not null references users (id)
+    on update cascade on delete restrict,
+  created_at timestamptz not null default now()
+);
+create unique index acknowledged_mistakes_unique on acknowledged_mistakes
+  (doc_id, tier, annotation, kind, segment, coalesce(start, -1));
+
+-- Reviews {{{2
+
+create table reviews (
+  id serial primary key,
+  doc_id integer not null references docs (id)
+    on update cascade on delete cascade,
+  reviewer_id integer not null references users (id)
+    on update cascade on delete restrict,
+  validation_run_id integer references validation_runs (id)
+    on update cascade on delete set null,
+  accepted boolean not null,
+  reason_id integer references enum_return_reasons (id)
+    on update cascade on delete restrict,
+  notes text not null default '',
+  created_at timestamptz not null default now(),
+  check (accepted or reason_id is not null)
+);
+create index reviews_doc on reviews (doc_id);
+
+create table review_comments (
+  id serial primary key,
+  doc_id integer not null references docs (id)
+    on update cascade on delete cascade,
+  author_id integer not null references users (id)
+    on update cascade on delete restrict,
+  tier text,
+  annotation text,
+  start_ms integer,
+  end_ms integer,
+  body text not null,
+  created_at timestamptz not null default now(),
+  check (
+    (tier is not null and annotation is not null
+      and start_ms is null and end_ms is null)
+    or (tier is null and annotation is null
+      and start_ms >= 0 and end_ms > start_ms)
+  )
+);
+create index review_comments_doc on review_comments (doc_id);
+
+-- Views {{{2
+
+create view view_speakers as
+  select
+    speakers.id as id,
+    projects.label as project,
+    nickname,
+    enum_genders.label as gender,
+    enum_educations.label as education,
+    place,
+    region,
+    year
+  from speakers
+  join projects on projects.id = project_id
+  join enum_genders on enum_genders.id = gender_id
+  join enum_educations on enum_educations.id = education_id
+  natural join view_geo;
+
+create view view_docs as
+  select
+    docs.id as id,
+    projects.label as project,
+    corpora.label as corpus,
+    place,
+    region,
+    date
+  from docs
+  join projects on projects.id = project_id
+  join doc2corpus on doc2corpus.doc_id = docs.id
+  join corpora on corpora.id = doc2corpus.corpus_id
+  natural join view_geo;
+
+create view view_doc2speaker as
+  select
+    doc2speaker.id as id,
+    view_docs.project as project,
+    view_docs.corpus as corpus,
+    view_docs.place as doc_place,
+    view_docs.region as doc_region,
+    gender,
+    (case when extract(year from date) - year < 35 then 'mladší' else 'starší' end) as age,
+    (case when education = 'VŠ' then 'vyšší' else 'nižší' end) as education,
+    view_speakers.place as spk_place,
+    view_speakers.region as spk_region,
+    words
+  from doc2speaker
+  join view_speakers on doc2speaker.speaker_id = view_speakers.id
+  join view_docs on doc2speaker.doc_id = view_docs.id;
+
+-- Cross-project links {{{2
+
+create function check_doc2speaker_same_project() returns trigger as $$
+begin
+  if (select project_id from docs where id = new.doc_id)
+    is distinct from (select project_id from speakers where id = new.speaker_id)
+  then
+    raise exception 'speaker belongs to a different project than document';
+  end if;
+  return new;
+end;
+$$ language plpgsql;
+
+create trigger doc2speaker_same_project
+before insert or update on doc2speaker
+for each row execute function check_doc2speaker_same_project();
+
+create function check_docs_assignee_member() returns trigger as $$
+begin
+  if new.assigned_to_id is not null
+    and not exists (
+      select 1 from project_members
+      where project_id = new.project_id and user_id = new.assigned_to_id
+    )
+    and not exists (
+      select 1 from users
+        join role_permissions on role_permissions.role_id = users.role_id
+        join enum_permissions on enum_permissions.id = role_permissions.permission_id
+      where users.id = new.assigned_to_id and enum_permissions.label = 'project.all'
+    )
+  then
+    raise exception 'assignee is not a member of the document''s project';
+  end if;
+  return new;
+end;
+$$ language plpgsql;
+
+create trigger docs_assignee_member
+before update of assigned_to_id on docs
+for each row execute function check_docs_assignee_member();
+
+-- Changes {{{1
+
+create table changes (
+  id serial primary key,
+  entity text not null,
+  entity_id integer not null,
+  doc_id integer not null,
+  project_id integer not null,
+  changed_at timestamptz not null default now()
+);
+create index changes_project on changes (project_id, id);
+
+-- Documents and their assignments {{{2
+
+create function record_doc_changes() returns trigger as $$
+begin
+  if tg_op = 'INSERT' then
+    insert into changes (entity, entity_id, doc_id, project_id) values
+      ('document', new.id, new.id, new.project_id),
+      ('assignment', new.id, new.id, new.project_id);
+  elsif tg_op = 'DELETE' then
+    insert into changes (entity, entity_id, doc_id, project_id) values
+      ('document', old.id, old.id, old.project_id),
+      ('assignment', old.id, old.id, old.project_id);
+  end if;
+  return null;
+end;
+$$ language plpgsql;
+
+create trigger docs_changes_insert
+after insert on docs
+for each row execute function record_doc_changes();
+
+create trigger docs_changes_delete
+after delete on docs
+for each row execute function record_doc_changes();
+
+-- users with access to the old project only see the document go away
+create function record_doc_move() returns trigger as $$
+begin
+  insert into changes (entity, entity_id, doc_id, project_id) values
+    ('document', old.id, old.id, old.project_id),
+    ('assignment', old.id, old.id, old.project_id);
+  return null;
+end;
+$$ language plpgsql;
+
+create trigger docs_changes_move
+after update of project_id on docs
+for each row when (new.project_id <> old.project_id)
+execute function record_doc_move();
+
+create function record_doc_change() returns trigger as $$
+begin
+  insert into changes (entity, entity_id, doc_id, project_id) values
+    (tg_argv[0], new.id, new.id, new.project_id);
+  return null;
+end;
+$$ language plpgsql;
+
+create trigger docs_changes_update
+after update of project_id, done, place_id, done_at, state_id on docs
+for each row execute function record_doc_change('document');
+
+create trigger docs_changes_assign
+after update of project_id, assigned_to_id, assigned_by_id, assigned_at, due_at on docs
+for each row execute function record_doc_change('assignment');
+
+-- Reviews, comments and validation reports {{{2
+
+-- nothing to record on deletion if the whole document is gone
+create function record_doc_child_change() returns trigger as $$
+declare
+  row record;
+begin
+  if tg_op = 'DELETE' then
+    row := old;
+  else
+    row := new;
+  end if;
+  insert into changes (entity, entity_id, doc_id, project_id)
+    select tg_argv[0], row.id, row.doc_id, project_id from docs where id = row.doc_id;
+  return null;
+end;
+$$ language plpgsql;
+
+create trigger reviews_changes_insert
+after insert on reviews
+for each row execute function record_doc_child_change('review');
+
+create trigger reviews_changes_delete
+after delete on reviews
+for each row execute function record_doc_child_change('review');
+
+create trigger validation_runs_changes_insert
+after insert on validation_runs
+for each row execute function record_doc_child_change('validation_run');
+
+create trigger validation_runs_changes_delete
+after delete on validation_runs
+for each row execute function record_doc_child_change('validation_run');
+
+create trigger review_comments_changes_insert
+after insert on review_comments
+for each row execute function record_doc_child_change('comment');
+
+create trigger review_comments_changes_delete
+after delete on review_comments
+for each row execute function record_doc_child_change('comment');
+
+-- vim: foldmethod=marker:
@@ -0,0 +1 @@
+alter table parser_configs drop column anonymize;
@@ -0,0 +1,7 @@
+-- Attribute values {{{1
+
+-- keys allowed in key=value attributes after <, e.g. <SM_lang=EN ...>, with
+-- the regexes their values have to match, as a JSON object
+alter table parser_configs add column attr_values text not null default '{}';
+
+-- vim: foldmethod=marker:
@@ -0,0 +1,7 @@
+-- Whitespace reporting {{{1
+
+-- whether irregular whitespace in segments is reported as a mistake rather
+-- than normalized away before validation
+alter table parser_configs add column report_whitespace boolean not null default false;
+
+-- vim: foldmethod=marker:
@@ -0,0 +1,139 @@
+//! Mistakes reviewers have acknowledged as intentional, e.g. a nonstandard
+//! form the speaker really used. Later validations of the document still
+//! record them, but they don't count towards the run's mistakes (see
+//! `validation::record_run`).
+
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+
+use super::schema::{acknowledged_mistakes, users};
+use super::validation::NewMistake;
+use super::Conn;
+
+#[derive(Debug, Insertable)]
+#[table_name = "acknowledged_mistakes"]
+pub struct NewAcknowledgment {
+    pub doc_id: i32,
+    pub tier: String,
+    pub annotation: String,
+    pub kind: String,
+    /// Source of the segment, as validated.
+    pub segment: String,
+    /// Byte offset of the mistake in the segment, if known.
+    pub start: Option<i32>,
+    pub reason: String,
+    pub author_id: i32,
+}
+
+#[derive(Debug, Queryable)]
+pub struct Acknowledgment {
+    pub id: i32,
+    pub doc_id: i32,
+    pub tier: String,
+    pub annotation: String,
+    pub kind: String,
+    pub segment: String,
+    pub start: Option<i32>,
+    pub reason: String,
+    pub author_id: i32,
+    pub author: String,
+    pub created_at: NaiveDateTime,
+}
+
+impl Acknowledgment {
+    /// Whether the mistake is the one acknowledged. If the segment has been
+    /// edited since, it's not.
+    pub fn covers(&self, mistake: &NewMistake) -> bool {
+        self.tier == mistake.tier
+            && self.annotation == mistake.annotation
+            && self.kind == mistake.kind
+            && self.segment == mistake.segment
+            && self.start == mistake.start
+    }
+}
+
+/// Returns the ID of the acknowledgment. The same mistake can only be
+/// acknowledged once, which the DB enforces.
+pub fn add(conn: &Conn, acknowledgment: &NewAcknowledgment) -> QueryResult<i32> {
+    conn.transaction(|| {
+        diesel::insert_into(acknowledged_mistakes::table)
+            .values(acknowledgment)
+            .execute(conn)?;
+        acknowledged_mistakes::table
+            .select(acknowledged_mistakes::id)
+            .order(acknowledged_mistakes::id.desc())
+            .first(conn)
+    })
+}
+
+/// The document's acknowledged mistakes, oldest first.
+pub fn for_doc(conn: &Conn, doc_id: i32) -> QueryResult<Vec<Acknowledgment>> {
+    acknowledged_mistakes::table
+        .inner_join(users::table)
+        .filter(acknowledged_mistakes::doc_id.eq(doc_id))
+        .select((
+            acknowledged_mistakes::id,
+            acknowledged_mistakes::doc_id,
+            acknowledged_mistakes::tier,
+            acknowledged_mistakes::annotation,
+            acknowledged_mistakes::kind,
+            acknowledged_mistakes::segment,
+            acknowledged_mistakes::start,
+            acknowledged_mistakes::reason,
+            acknowledged_mistakes::author_id,
+            users::username,
+            acknowledged_mistakes::created_at,
+        ))
+        .order(acknowledged_mistakes::id)
+        .load(conn)
+}
+
+/// Withdraw the document's acknowledgment, so that the mistake counts
+/// again from the next validation on. Returns whether there was anything to
+/// withdraw.
+pub fn remove(conn: &Conn, doc_id: i32, id: i32) -> QueryResult<bool> {
+    let removed = diesel::delete(
+        acknowledged_mistakes::table
+            .find(id)
+            .filter(acknowledged_mistakes::doc_id.eq(doc_id)),
+    )
+    .execute(conn)?;
+    Ok(removed > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    #[test]
+    fn test_covers() {
+        let mistake = NewMistake {
+            tier: "ort@JD".to_owned(),
+            annotation: "a1".to_owned(),
+            kind: "bad_token".to_owned(),
+            segment: "no tagže".to_owned(),
+            start: Some(3),
+            end: Some(9),
+        };
+        let mut acknowledgment = Acknowledgment {
+            id: 1,
+            doc_id: 1,
+            tier: mistake.tier.clone(),
+            annotation: mistake.annotation.clone(),
+            kind: mistake.kind.clone(),
+            segment: mistake.segment.clone(),
+            start: mistake.start,
+            reason: "dialectal".to_owned(),
+            author_id: 1,
+            author: "admin".to_owned(),
+            created_at: NaiveDate::from_ymd_opt(2026, 10, 15)
+                .unwrap()
+                .and_hms_opt(0, 0, 0)
+                .unwrap(),
+        };
+        assert!(acknowledgment.covers(&mistake));
+        acknowledgment.segment = "no tagže jo".to_owned();
+        assert!(!acknowledgment.covers(&mistake));
+    }
+}
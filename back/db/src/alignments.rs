@@ -0,0 +1,80 @@
+//! Word-level time alignments, e.g. from forced alignment of the recording
+//! with the transcript. They're stored per version of the transcript, as
+//! words are identified by their index within an annotation (see
+//! `eaf::conllu::words`), which doesn't survive edits.
+
+use diesel::prelude::*;
+
+use super::schema::word_alignments;
+use super::Conn;
+
+#[derive(Debug, Clone, PartialEq, Queryable)]
+pub struct WordAlignment {
+    pub annotation: String,
+    /// Index of the word within the annotation, from 0.
+    pub word: i32,
+    pub form: String,
+    /// In milliseconds.
+    pub start: i32,
+    pub end: i32,
+}
+
+#[derive(Insertable)]
+#[table_name = "word_alignments"]
+struct NewWordAlignment<'a> {
+    doc_id: i32,
+    file_id: i32,
+    annotation: &'a str,
+    word: i32,
+    form: &'a str,
+    start_ms: i32,
+    end_ms: i32,
+}
+
+/// Replace the alignments of the document's file. Returns how many were
+/// stored.
+pub fn replace(
+    conn: &Conn,
+    doc_id: i32,
+    file_id: i32,
+    alignments: &[WordAlignment],
+) -> QueryResult<usize> {
+    let rows: Vec<_> = alignments
+        .iter()
+        .map(|a| NewWordAlignment {
+            doc_id,
+            file_id,
+            annotation: &a.annotation,
+            word: a.word,
+            form: &a.form,
+            start_ms: a.start,
+            end_ms: a.end,
+        })
+        .collect();
+    conn.transaction(|| {
+        diesel::delete(word_alignments::table.filter(word_alignments::file_id.eq(file_id)))
+            .execute(conn)?;
+        // SQLite limits the number of bound parameters per statement
+        for chunk in rows.chunks(100) {
+            diesel::insert_into(word_alignments::table)
+                .values(chunk)
+                .execute(conn)?;
+        }
+        Ok(rows.len())
+    })
+}
+
+/// The file's alignments, in start order.
+pub fn for_file(conn: &Conn, file_id: i32) -> QueryResult<Vec<WordAlignment>> {
+    word_alignments::table
+        .filter(word_alignments::file_id.eq(file_id))
+        .select((
+            word_alignments::annotation,
+            word_alignments::word,
+            word_alignments::form,
+            word_alignments::start_ms,
+            word_alignments::end_ms,
+        ))
+        .order((word_alignments::start_ms, word_alignments::id))
+        .load(conn)
+}
@@ -0,0 +1,109 @@
+//! Record of who changed what.
+
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use serde_json::Value;
+
+use super::schema::{audit_log, users};
+use super::Conn;
+
+/// Record an action done to an entity, e.g. `speaker.merge` of speaker 12.
+/// `details` should contain whatever is needed to reconstruct the previous
+/// state.
+pub fn record(
+    conn: &Conn,
+    user_id: Option<i32>,
+    action: &str,
+    entity: &str,
+    entity_id: i32,
+    details: &Value,
+) -> QueryResult<()> {
+    diesel::insert_into(audit_log::table)
+        .values((
+            audit_log::user_id.eq(user_id),
+            audit_log::action.eq(action),
+            audit_log::entity.eq(entity),
+            audit_log::entity_id.eq(entity_id),
+            audit_log::details.eq(details.to_string()),
+        ))
+        .execute(conn)?;
+    Ok(())
+}
+
+#[derive(Debug)]
+pub struct Entry {
+    pub id: i32,
+    pub user_id: Option<i32>,
+    pub username: Option<String>,
+    pub action: String,
+    pub entity: String,
+    pub entity_id: i32,
+    pub details: Value,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Debug, Default)]
+pub struct AuditFilter<'a> {
+    pub user_id: Option<i32>,
+    /// E.g. `("document", 42)`.
+    pub entity: Option<(&'a str, i32)>,
+    /// Only entries older than this one, for paging.
+    pub before: Option<i32>,
+    pub limit: i64,
+}
+
+/// Matching entries, newest first.
+pub fn query(conn: &Conn, filter: &AuditFilter) -> QueryResult<Vec<Entry>> {
+    let mut query = audit_log::table
+        .left_join(users::table)
+        .select((
+            audit_log::id,
+            audit_log::user_id,
+            users::username.nullable(),
+            audit_log::action,
+            audit_log::entity,
+            audit_log::entity_id,
+            audit_log::details,
+            audit_log::created_at,
+        ))
+        .order(audit_log::id.desc())
+        .limit(filter.limit)
+        .into_boxed();
+    if let Some(user_id) = filter.user_id {
+        query = query.filter(audit_log::user_id.eq(user_id));
+    }
+    if let Some((entity, entity_id)) = filter.entity {
+        query = query
+            .filter(audit_log::entity.eq(entity))
+            .filter(audit_log::entity_id.eq(entity_id));
+    }
+    if let Some(before) = filter.before {
+        query = query.filter(audit_log::id.lt(before));
+    }
+    let rows = query.load::<(
+        i32,
+        Option<i32>,
+        Option<String>,
+        String,
+        String,
+        i32,
+        String,
+        NaiveDateTime,
+    )>(conn)?;
+    Ok(rows
+        .into_iter()
+        .map(
+            |(id, user_id, username, action, entity, entity_id, details, created_at)| Entry {
+                id,
+                user_id,
+                username,
+                action,
+                entity,
+                entity_id,
+                // written by `record`, so it's JSON
+                details: serde_json::from_str(&details).unwrap_or(Value::Null),
+                created_at,
+            },
+        )
+        .collect())
+}
@@ -0,0 +1,292 @@
+//! Consistent backups of the database along with the stored files it
+//! references, and verified restores of them.
+//!
+//! A backup is a directory holding a snapshot of the database (taken with
+//! `VACUUM INTO`, so it's consistent even while the server is running), a
+//! copy of each file recorded in the snapshot under `files/`, and a
+//! manifest with their sizes and SHA-256 checksums. Restoring checks the
+//! whole backup against the manifest before touching anything.
+//!
+//! Only SQLite databases can be backed up this way, Postgres has its own
+//! tools for that (`pg_dump`).
+
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Component, Path, PathBuf};
+
+use chrono::Local;
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::Conn;
+
+pub const MANIFEST: &str = "manifest.json";
+pub const DATABASE: &str = "quetzal.db";
+const FILES: &str = "files";
+/// Bump when the layout of backups changes.
+const FORMAT: u32 = 1;
+
+#[derive(Debug)]
+pub enum BackupError {
+    Io(PathBuf, io::Error),
+    Db(diesel::result::Error),
+    Connection(ConnectionError),
+    BadManifest(String),
+    /// Refusing to overwrite an existing backup or database.
+    Exists(PathBuf),
+    /// Files which are missing from the backup or don't match the manifest.
+    Corrupt(Vec<String>),
+    /// The database isn't SQLite.
+    Unsupported,
+}
+
+impl fmt::Display for BackupError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BackupError::Io(path, e) => write!(f, "{}: {}", path.display(), e),
+            BackupError::Db(e) => e.fmt(f),
+            BackupError::Connection(e) => e.fmt(f),
+            BackupError::BadManifest(e) => write!(f, "bad manifest: {}", e),
+            BackupError::Exists(path) => write!(f, "{} already exists", path.display()),
+            BackupError::Corrupt(problems) => {
+                write!(
+                    f,
+                    "backup doesn't match its manifest: {}",
+                    problems.join("; ")
+                )
+            }
+            BackupError::Unsupported => write!(f, "only SQLite databases can be backed up"),
+        }
+    }
+}
+
+impl From<diesel::result::Error> for BackupError {
+    fn from(e: diesel::result::Error) -> Self {
+        BackupError::Db(e)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Entry {
+    /// Relative to the storage directory (or the backup directory, for the
+    /// database).
+    pub path: String,
+    pub size: u64,
+    pub sha256: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Manifest {
+    pub format: u32,
+    pub created_at: String,
+    pub database: Entry,
+    pub files: Vec<Entry>,
+    /// Files recorded in the database which weren't found in storage when
+    /// the backup was made.
+    pub missing: Vec<String>,
+}
+
+/// Copy `from` to `to` (if given), returning the size and checksum of the
+/// contents.
+fn copy_hashed(from: &Path, to: Option<&Path>) -> Result<(u64, String), BackupError> {
+    let mut reader = File::open(from).map_err(|e| BackupError::Io(from.to_owned(), e))?;
+    let mut writer = match to {
+        Some(to) => Some(File::create(to).map_err(|e| BackupError::Io(to.to_owned(), e))?),
+        None => None,
+    };
+    let mut hasher = Sha256::new();
+    let mut buffer = [0; 64 * 1024];
+    let mut size = 0;
+    loop {
+        let n = reader
+            .read(&mut buffer)
+            .map_err(|e| BackupError::Io(from.to_owned(), e))?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buffer[..n]);
+        if let (Some(writer), Some(to)) = (&mut writer, to) {
+            writer
+                .write_all(&buffer[..n])
+                .map_err(|e| BackupError::Io(to.to_owned(), e))?;
+        }
+        size += n as u64;
+    }
+    Ok((size, format!("{:x}", hasher.finalize())))
+}
+
+fn create_parent(path: &Path) -> Result<(), BackupError> {
+    match path.parent() {
+        Some(dir) => fs::create_dir_all(dir).map_err(|e| BackupError::Io(dir.to_owned(), e)),
+        None => Ok(()),
+    }
+}
+
+/// Paths in a manifest must stay within the directory they're relative to,
+/// lest a tampered backup overwrite arbitrary files on restore.
+fn is_contained(path: &str) -> bool {
+    let path = Path::new(path);
+    path.components().count() > 0 && path.components().all(|c| matches!(c, Component::Normal(_)))
+}
+
+/// Write a snapshot of the database to `path`. Returns the paths of the
+/// files it references.
+#[cfg(feature = "sqlite")]
+fn take_snapshot(conn: &Conn, path: &Path) -> Result<Vec<String>, BackupError> {
+    use super::schema::files;
+    use diesel::sql_types::Text;
+
+    diesel::sql_query("VACUUM INTO ?")
+        .bind::<Text, _>(path.to_string_lossy())
+        .execute(conn)?;
+    // the snapshot says which files belong to it, the live database may
+    // have moved on already
+    let snapshot_conn =
+        Conn::establish(&path.to_string_lossy()).map_err(BackupError::Connection)?;
+    let paths = files::table
+        .select(files::path)
+        .distinct()
+        .order(files::path)
+        .load::<String>(&snapshot_conn)?;
+    Ok(paths)
+}
+
+#[cfg(feature = "postgres")]
+fn take_snapshot(_conn: &Conn, _path: &Path) -> Result<Vec<String>, BackupError> {
+    Err(BackupError::Unsupported)
+}
+
+/// Back up the database and the files it references in `storage_dir` into
+/// the directory `dest`, which must not exist yet.
+pub fn create(conn: &Conn, storage_dir: &Path, dest: &Path) -> Result<Manifest, BackupError> {
+    if dest.exists() {
+        return Err(BackupError::Exists(dest.to_owned()));
+    }
+    fs::create_dir_all(dest).map_err(|e| BackupError::Io(dest.to_owned(), e))?;
+    let snapshot = dest.join(DATABASE);
+    let paths = take_snapshot(conn, &snapshot)?;
+
+    let mut entries = vec![];
+    let mut missing = vec![];
+    for path in paths {
+        let from = storage_dir.join(&path);
+        if !is_contained(&path) || !from.is_file() {
+            missing.push(path);
+            continue;
+        }
+        let to = dest.join(FILES).join(&path);
+        create_parent(&to)?;
+        let (size, sha256) = copy_hashed(&from, Some(&to))?;
+        entries.push(Entry { path, size, sha256 });
+    }
+
+    let (size, sha256) = copy_hashed(&snapshot, None)?;
+    let manifest = Manifest {
+        format: FORMAT,
+        created_at: Local::now().naive_local().to_string(),
+        database: Entry {
+            path: DATABASE.to_owned(),
+            size,
+            sha256,
+        },
+        files: entries,
+        missing,
+    };
+    let manifest_path = dest.join(MANIFEST);
+    let json = serde_json::to_string_pretty(&manifest)
+        .map_err(|e| BackupError::BadManifest(e.to_string()))?
+        + "\n";
+    fs::write(&manifest_path, json).map_err(|e| BackupError::Io(manifest_path, e))?;
+    Ok(manifest)
+}
+
+pub fn read_manifest(backup: &Path) -> Result<Manifest, BackupError> {
+    let path = backup.join(MANIFEST);
+    let json = fs::read_to_string(&path).map_err(|e| BackupError::Io(path, e))?;
+    let manifest: Manifest =
+        serde_json::from_str(&json).map_err(|e| BackupError::BadManifest(e.to_string()))?;
+    if manifest.format != FORMAT {
+        return Err(BackupError::BadManifest(format!(
+            "unsupported format {}",
+            manifest.format
+        )));
+    }
+    Ok(manifest)
+}
+
+/// Check that everything listed in the backup's manifest is there, intact.
+pub fn verify(backup: &Path) -> Result<Manifest, BackupError> {
+    let manifest = read_manifest(backup)?;
+    let entries = std::iter::once((backup.to_owned(), &manifest.database)).chain(
+        manifest
+            .files
+            .iter()
+            .map(|entry| (backup.join(FILES), entry)),
+    );
+    let mut problems = vec![];
+    for (dir, entry) in entries {
+        if !is_contained(&entry.path) {
+            problems.push(format!("{}: path outside of backup", entry.path));
+            continue;
+        }
+        match copy_hashed(&dir.join(&entry.path), None) {
+            Ok((size, sha256)) if size == entry.size && sha256 == entry.sha256 => {}
+            Ok(_) => problems.push(format!("{}: checksum mismatch", entry.path)),
+            Err(e) => problems.push(e.to_string()),
+        }
+    }
+    if problems.is_empty() {
+        Ok(manifest)
+    } else {
+        Err(BackupError::Corrupt(problems))
+    }
+}
+
+/// Verify the backup, then restore its database to `database` and its files
+/// into `storage_dir`. An existing database is only replaced if
+/// `overwrite` is set.
+pub fn restore(
+    backup: &Path,
+    database: &Path,
+    storage_dir: &Path,
+    overwrite: bool,
+) -> Result<Manifest, BackupError> {
+    if cfg!(feature = "postgres") {
+        return Err(BackupError::Unsupported);
+    }
+    let manifest = verify(backup)?;
+    if database.exists() && !overwrite {
+        return Err(BackupError::Exists(database.to_owned()));
+    }
+
+    for entry in &manifest.files {
+        let to = storage_dir.join(&entry.path);
+        create_parent(&to)?;
+        copy_hashed(&backup.join(FILES).join(&entry.path), Some(&to))?;
+    }
+    // files first, so that the database never references files which aren't
+    // there yet; the database itself is swapped in atomically
+    create_parent(database)?;
+    let mut partial = database.as_os_str().to_owned();
+    partial.push(".restoring");
+    let partial = PathBuf::from(partial);
+    copy_hashed(&backup.join(DATABASE), Some(&partial))?;
+    fs::rename(&partial, database).map_err(|e| BackupError::Io(database.to_owned(), e))?;
+    Ok(manifest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_contained() {
+        assert!(is_contained("docs/1/transcript.eaf"));
+        assert!(!is_contained("../etc/passwd"));
+        assert!(!is_contained("/etc/passwd"));
+        assert!(!is_contained("docs/../../x"));
+        assert!(!is_contained(""));
+    }
+}
@@ -0,0 +1,99 @@
+//! Annotations which users marked for later, e.g. interesting or
+//! problematic segments a reviewer wants to come back to.
+
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+
+use super::schema::{bookmarks, docs, projects};
+use super::Conn;
+
+#[derive(Debug, Insertable)]
+#[table_name = "bookmarks"]
+pub struct NewBookmark {
+    pub user_id: i32,
+    pub doc_id: i32,
+    pub tier: String,
+    pub annotation: String,
+    pub note: String,
+}
+
+#[derive(Debug, Queryable)]
+pub struct Bookmark {
+    pub id: i32,
+    pub doc_id: i32,
+    pub project: String,
+    pub tier: String,
+    pub annotation: String,
+    pub note: String,
+    pub created_at: NaiveDateTime,
+}
+
+/// Bookmark an annotation. Bookmarking it again just updates the note.
+/// Returns the ID of the bookmark.
+pub fn add(conn: &Conn, bookmark: &NewBookmark) -> QueryResult<i32> {
+    conn.transaction(|| {
+        let existing = bookmarks::table
+            .filter(bookmarks::user_id.eq(bookmark.user_id))
+            .filter(bookmarks::doc_id.eq(bookmark.doc_id))
+            .filter(bookmarks::annotation.eq(&bookmark.annotation))
+            .select(bookmarks::id)
+            .first::<i32>(conn)
+            .optional()?;
+        if let Some(id) = existing {
+            diesel::update(bookmarks::table.find(id))
+                .set(bookmarks::note.eq(&bookmark.note))
+                .execute(conn)?;
+            return Ok(id);
+        }
+        diesel::insert_into(bookmarks::table)
+            .values(bookmark)
+            .execute(conn)?;
+        bookmarks::table
+            .select(bookmarks::id)
+            .order(bookmarks::id.desc())
+            .first(conn)
+    })
+}
+
+/// Remove one of the user's bookmarks. Returns whether there was anything
+/// to remove.
+pub fn remove(conn: &Conn, id: i32, user_id: i32) -> QueryResult<bool> {
+    let removed = diesel::delete(
+        bookmarks::table
+            .find(id)
+            .filter(bookmarks::user_id.eq(user_id)),
+    )
+    .execute(conn)?;
+    Ok(removed > 0)
+}
+
+/// The user's bookmarks across all documents, optionally only in one
+/// document or project, newest first.
+pub fn for_user(
+    conn: &Conn,
+    user_id: i32,
+    doc_id: Option<i32>,
+    project_id: Option<i32>,
+) -> QueryResult<Vec<Bookmark>> {
+    let mut query = bookmarks::table
+        .inner_join(docs::table.inner_join(projects::table))
+        .filter(bookmarks::user_id.eq(user_id))
+        .select((
+            bookmarks::id,
+            bookmarks::doc_id,
+            projects::label,
+            bookmarks::tier,
+            bookmarks::annotation,
+            bookmarks::note,
+            bookmarks::created_at,
+        ))
+        .order((bookmarks::created_at.desc(), bookmarks::id.desc()))
+        .into_boxed();
+    if let Some(doc_id) = doc_id {
+        query = query.filter(bookmarks::doc_id.eq(doc_id));
+    }
+    if let Some(project_id) = project_id {
+        query = query.filter(docs::project_id.eq(project_id));
+    }
+    query.load(conn)
+}
@@ -0,0 +1,103 @@
+//! Metadata of documents bundled for sharing outside the project. Only
+//! coarse-grained information is included: places are reduced to regions,
+//! and nothing about who recorded or transcribed the documents.
+
+use std::collections::HashMap;
+
+use diesel::prelude::*;
+
+use super::corpora;
+use super::schema::{
+    doc2speaker, docs, enum_educations, enum_genders, enum_places, enum_regions, projects, speakers,
+};
+use super::Conn;
+
+#[derive(Debug)]
+pub struct BundleSpeaker {
+    pub id: i32,
+    pub nickname: String,
+    pub gender: String,
+    pub education: String,
+    pub region: String,
+    pub year: i32,
+}
+
+#[derive(Debug)]
+pub struct BundleDoc {
+    pub id: i32,
+    pub project_id: i32,
+    pub project: String,
+    pub corpora: Vec<String>,
+    pub region: String,
+    pub speakers: Vec<BundleSpeaker>,
+}
+
+fn labels(rows: Vec<(i32, String)>) -> HashMap<i32, String> {
+    rows.into_iter().collect()
+}
+
+/// Metadata of the documents, in the order given. Fails with `NotFound` if
+/// any of them doesn't exist.
+pub fn metadata(conn: &Conn, doc_ids: &[i32]) -> QueryResult<Vec<BundleDoc>> {
+    let genders = labels(enum_genders::table.load(conn)?);
+    let educations = labels(enum_educations::table.load(conn)?);
+    let regions = labels(
+        enum_places::table
+            .inner_join(enum_regions::table)
+            .select((enum_places::id, enum_regions::label))
+            .load(conn)?,
+    );
+    let label = |labels: &HashMap<i32, String>, id| labels.get(&id).cloned().unwrap_or_default();
+
+    let mut corpus_labels = corpora::labels_for_docs(conn, doc_ids)?;
+    let mut docs: HashMap<i32, BundleDoc> = docs::table
+        .inner_join(projects::table)
+        .filter(docs::id.eq_any(doc_ids))
+        .select((docs::id, docs::project_id, projects::label, docs::place_id))
+        .load::<(i32, i32, String, i32)>(conn)?
+        .into_iter()
+        .map(|(id, project_id, project, place_id)| {
+            let doc = BundleDoc {
+                id,
+                project_id,
+                project,
+                corpora: corpus_labels.remove(&id).unwrap_or_default(),
+                region: label(&regions, place_id),
+                speakers: vec![],
+            };
+            (id, doc)
+        })
+        .collect();
+
+    let links = doc2speaker::table
+        .inner_join(speakers::table)
+        .filter(doc2speaker::doc_id.eq_any(doc_ids))
+        .select((
+            doc2speaker::doc_id,
+            speakers::id,
+            speakers::nickname,
+            speakers::gender_id,
+            speakers::education_id,
+            speakers::place_id,
+            speakers::year,
+        ))
+        .order(doc2speaker::id)
+        .load::<(i32, i32, String, i32, i32, i32, i32)>(conn)?;
+    for (doc_id, id, nickname, gender_id, education_id, place_id, year) in links {
+        if let Some(doc) = docs.get_mut(&doc_id) {
+            doc.speakers.push(BundleSpeaker {
+                id,
+                nickname,
+                gender: label(&genders, gender_id),
+                education: label(&educations, education_id),
+                region: label(&regions, place_id),
+                year,
+            });
+        }
+    }
+
+    doc_ids
+        .iter()
+        .map(|id| docs.remove(id).ok_or(diesel::result::Error::NotFound))
+        .collect()
+}
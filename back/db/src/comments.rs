@@ -0,0 +1,158 @@
+//! Reviewers' comments on documents. Unlike bookmarks, they're shared with
+//! everyone working on the document, and besides annotations, they can be
+//! anchored to any stretch of the recording, e.g. to flag speech nobody
+//! transcribed.
+
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+
+use super::schema::{review_comments, users};
+use super::Conn;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Anchor {
+    Annotation {
+        tier: String,
+        annotation: String,
+    },
+    /// In milliseconds, `start < end`.
+    Time {
+        start: i32,
+        end: i32,
+    },
+}
+
+#[derive(Debug)]
+pub struct NewComment {
+    pub doc_id: i32,
+    pub author_id: i32,
+    pub anchor: Anchor,
+    pub body: String,
+}
+
+#[derive(Debug)]
+pub struct Comment {
+    pub id: i32,
+    pub doc_id: i32,
+    pub author_id: i32,
+    pub author: String,
+    pub anchor: Anchor,
+    pub body: String,
+    pub created_at: NaiveDateTime,
+}
+
+type Row = (
+    i32,
+    i32,
+    i32,
+    String,
+    Option<String>,
+    Option<String>,
+    Option<i32>,
+    Option<i32>,
+    String,
+    NaiveDateTime,
+);
+
+fn from_row(row: Row) -> Comment {
+    let (id, doc_id, author_id, author, tier, annotation, start, end, body, created_at) = row;
+    // the table's check constraint guarantees one or the other
+    let anchor = match (tier, annotation, start, end) {
+        (Some(tier), Some(annotation), _, _) => Anchor::Annotation { tier, annotation },
+        (_, _, start, end) => Anchor::Time {
+            start: start.unwrap_or_default(),
+            end: end.unwrap_or_default(),
+        },
+    };
+    Comment {
+        id,
+        doc_id,
+        author_id,
+        author,
+        anchor,
+        body,
+        created_at,
+    }
+}
+
+/// Returns the ID of the comment.
+pub fn add(conn: &Conn, comment: &NewComment) -> QueryResult<i32> {
+    let (tier, annotation, start, end) = match &comment.anchor {
+        Anchor::Annotation { tier, annotation } => (Some(tier), Some(annotation), None, None),
+        Anchor::Time { start, end } => (None, None, Some(*start), Some(*end)),
+    };
+    conn.transaction(|| {
+        diesel::insert_into(review_comments::table)
+            .values((
+                review_comments::doc_id.eq(comment.doc_id),
+                review_comments::author_id.eq(comment.author_id),
+                review_comments::tier.eq(tier),
+                review_comments::annotation.eq(annotation),
+                review_comments::start_ms.eq(start),
+                review_comments::end_ms.eq(end),
+                review_comments::body.eq(&comment.body),
+            ))
+            .execute(conn)?;
+        review_comments::table
+            .select(review_comments::id)
+            .order(review_comments::id.desc())
+            .first(conn)
+    })
+}
+
+fn load(conn: &Conn, doc_id: Option<i32>, ids: Option<&[i32]>) -> QueryResult<Vec<Comment>> {
+    let mut query = review_comments::table
+        .inner_join(users::table)
+        .select((
+            review_comments::id,
+            review_comments::doc_id,
+            review_comments::author_id,
+            users::username,
+            review_comments::tier,
+            review_comments::annotation,
+            review_comments::start_ms,
+            review_comments::end_ms,
+            review_comments::body,
+            review_comments::created_at,
+        ))
+        .order(review_comments::id)
+        .into_boxed();
+    if let Some(doc_id) = doc_id {
+        query = query.filter(review_comments::doc_id.eq(doc_id));
+    }
+    if let Some(ids) = ids {
+        query = query.filter(review_comments::id.eq_any(ids));
+    }
+    Ok(query.load(conn)?.into_iter().map(from_row).collect())
+}
+
+/// The document's comments, oldest first.
+pub fn for_doc(conn: &Conn, doc_id: i32) -> QueryResult<Vec<Comment>> {
+    load(conn, Some(doc_id), None)
+}
+
+pub fn with_ids(conn: &Conn, ids: &[i32]) -> QueryResult<Vec<Comment>> {
+    load(conn, None, Some(ids))
+}
+
+/// The author of the document's comment, if it exists.
+pub fn author_of(conn: &Conn, doc_id: i32, id: i32) -> QueryResult<Option<i32>> {
+    review_comments::table
+        .find(id)
+        .filter(review_comments::doc_id.eq(doc_id))
+        .select(review_comments::author_id)
+        .first(conn)
+        .optional()
+}
+
+/// Remove the document's comment. Returns whether there was anything to
+/// remove.
+pub fn remove(conn: &Conn, doc_id: i32, id: i32) -> QueryResult<bool> {
+    let removed = diesel::delete(
+        review_comments::table
+            .find(id)
+            .filter(review_comments::doc_id.eq(doc_id)),
+    )
+    .execute(conn)?;
+    Ok(removed > 0)
+}
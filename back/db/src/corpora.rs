@@ -0,0 +1,110 @@
+//! Corpora and which documents belong to them. A document can be part of
+//! several corpora, e.g. the main corpus and a themed sub-corpus.
+//!
+//! Once a corpus is released, its accepted documents are made available
+//! through the public API.
+
+use std::collections::HashMap;
+
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+
+use super::docs::DocState;
+use super::schema::{corpora, doc2corpus, docs};
+use super::Conn;
+
+#[derive(Debug, Queryable)]
+pub struct Corpus {
+    pub id: i32,
+    pub label: String,
+    pub released_at: Option<NaiveDateTime>,
+}
+
+pub fn all(conn: &Conn) -> QueryResult<Vec<Corpus>> {
+    corpora::table.order(corpora::label).load(conn)
+}
+
+/// Labels of the corpora each of the documents belongs to, in alphabetical
+/// order. Documents which aren't part of any corpus are missing.
+pub fn labels_for_docs(conn: &Conn, doc_ids: &[i32]) -> QueryResult<HashMap<i32, Vec<String>>> {
+    let mut labels: HashMap<i32, Vec<String>> = HashMap::new();
+    let rows = doc2corpus::table
+        .inner_join(corpora::table)
+        .filter(doc2corpus::doc_id.eq_any(doc_ids))
+        .select((doc2corpus::doc_id, corpora::label))
+        .order(corpora::label)
+        .load::<(i32, String)>(conn)?;
+    for (doc_id, label) in rows {
+        labels.entry(doc_id).or_default().push(label);
+    }
+    Ok(labels)
+}
+
+pub fn for_doc(conn: &Conn, doc_id: i32) -> QueryResult<Vec<Corpus>> {
+    doc2corpus::table
+        .inner_join(corpora::table)
+        .filter(doc2corpus::doc_id.eq(doc_id))
+        .select(corpora::all_columns)
+        .order(corpora::label)
+        .load(conn)
+}
+
+/// Replace the corpora the document belongs to.
+pub fn replace_for_doc(conn: &Conn, doc_id: i32, corpus_ids: &[i32]) -> QueryResult<()> {
+    conn.transaction(|| {
+        diesel::delete(doc2corpus::table.filter(doc2corpus::doc_id.eq(doc_id))).execute(conn)?;
+        for &corpus_id in corpus_ids {
+            diesel::insert_into(doc2corpus::table)
+                .values((
+                    doc2corpus::doc_id.eq(doc_id),
+                    doc2corpus::corpus_id.eq(corpus_id),
+                ))
+                .execute(conn)?;
+        }
+        Ok(())
+    })
+}
+
+/// Release the corpus as of `at`, or withdraw it if `None`. Returns whether
+/// the corpus exists.
+pub fn set_released(conn: &Conn, corpus_id: i32, at: Option<NaiveDateTime>) -> QueryResult<bool> {
+    diesel::update(corpora::table.find(corpus_id))
+        .set(corpora::released_at.eq(at))
+        .execute(conn)
+        .map(|n| n > 0)
+}
+
+pub fn released(conn: &Conn) -> QueryResult<Vec<Corpus>> {
+    corpora::table
+        .filter(corpora::released_at.is_not_null())
+        .order(corpora::label)
+        .load(conn)
+}
+
+/// Accepted documents in released corpora, optionally only in the given
+/// one, along with the released corpora they belong to.
+pub fn released_docs(conn: &Conn, corpus_id: Option<i32>) -> QueryResult<Vec<(i32, Vec<Corpus>)>> {
+    let mut query = doc2corpus::table
+        .inner_join(corpora::table)
+        .inner_join(docs::table)
+        .filter(corpora::released_at.is_not_null())
+        .filter(docs::state_id.eq(DocState::Accepted.id()))
+        .select((doc2corpus::doc_id, corpora::all_columns))
+        .order((doc2corpus::doc_id, corpora::label))
+        .into_boxed();
+    if let Some(corpus_id) = corpus_id {
+        let in_corpus = doc2corpus::table
+            .filter(doc2corpus::corpus_id.eq(corpus_id))
+            .select(doc2corpus::doc_id)
+            .load::<i32>(conn)?;
+        query = query.filter(doc2corpus::doc_id.eq_any(in_corpus));
+    }
+    let mut released: Vec<(i32, Vec<Corpus>)> = vec![];
+    for (doc_id, corpus) in query.load::<(i32, Corpus)>(conn)? {
+        match released.last_mut() {
+            Some((last, corpora)) if *last == doc_id => corpora.push(corpus),
+            _ => released.push((doc_id, vec![corpus])),
+        }
+    }
+    Ok(released)
+}
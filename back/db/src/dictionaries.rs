@@ -0,0 +1,37 @@
+//! Which spell-checking dictionaries each project uses.
+
+use diesel::prelude::*;
+
+use super::schema::project_dictionaries;
+use super::Conn;
+
+/// Names of the project's dictionaries, in the order in which they should
+/// be consulted.
+pub fn for_project(conn: &Conn, project_id: i32) -> QueryResult<Vec<String>> {
+    project_dictionaries::table
+        .filter(project_dictionaries::project_id.eq(project_id))
+        .order(project_dictionaries::priority)
+        .select(project_dictionaries::dictionary)
+        .load(conn)
+}
+
+/// Replace the project's dictionaries; their priority is given by their
+/// order.
+pub fn replace(conn: &Conn, project_id: i32, names: &[String]) -> QueryResult<()> {
+    conn.transaction(|| {
+        diesel::delete(
+            project_dictionaries::table.filter(project_dictionaries::project_id.eq(project_id)),
+        )
+        .execute(conn)?;
+        for (i, name) in names.iter().enumerate() {
+            diesel::insert_into(project_dictionaries::table)
+                .values((
+                    project_dictionaries::project_id.eq(project_id),
+                    project_dictionaries::priority.eq(i as i32 + 1),
+                    project_dictionaries::dictionary.eq(name),
+                ))
+                .execute(conn)?;
+        }
+        Ok(())
+    })
+}
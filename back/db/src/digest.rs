@@ -0,0 +1,213 @@
+//! Queries backing the weekly digests sent to supervisors.
+
+use std::collections::HashMap;
+
+use chrono::{Datelike, Duration, NaiveDateTime, Timelike};
+use diesel::prelude::*;
+
+use super::permissions::{self, DOC_ASSIGN};
+use super::schema::{digest_settings, docs, projects, users, validation_runs};
+use super::Conn;
+
+/// How many documents to list as validation failure hotspots.
+const HOTSPOTS: usize = 5;
+
+#[derive(Debug, Queryable)]
+pub struct DigestSettings {
+    pub project_id: i32,
+    pub project: String,
+    pub weekday: i32,
+    pub hour: i32,
+    pub last_sent_at: Option<NaiveDateTime>,
+}
+
+impl DigestSettings {
+    /// Whether the digest should go out at `now`, i.e. it's the right day
+    /// and hour, and it hasn't been sent yet this week.
+    pub fn is_due(&self, now: NaiveDateTime) -> bool {
+        let right_time = now.weekday().number_from_monday() as i32 == self.weekday
+            && now.hour() as i32 >= self.hour;
+        let not_sent_yet = self
+            .last_sent_at
+            .map(|sent| now - sent > Duration::days(1))
+            .unwrap_or(true);
+        right_time && not_sent_yet
+    }
+}
+
+#[derive(Debug, Queryable)]
+pub struct Recipient {
+    pub id: i32,
+    pub username: String,
+    pub email: String,
+}
+
+#[derive(Debug)]
+pub struct DocSummary {
+    pub id: i32,
+    pub assigned_to: Option<String>,
+    pub due_at: Option<NaiveDateTime>,
+}
+
+#[derive(Debug)]
+pub struct Hotspot {
+    pub doc_id: i32,
+    pub mistakes: i32,
+    pub validated_at: NaiveDateTime,
+}
+
+#[derive(Debug)]
+pub struct Digest {
+    pub since: NaiveDateTime,
+    /// Documents marked as done since the previous digest.
+    pub submitted: Vec<DocSummary>,
+    /// Documents which aren't done yet even though they're past due.
+    pub overdue: Vec<DocSummary>,
+    /// Documents with the most mistakes in their latest validation run.
+    pub hotspots: Vec<Hotspot>,
+}
+
+impl Digest {
+    pub fn is_empty(&self) -> bool {
+        self.submitted.is_empty() && self.overdue.is_empty() && self.hotspots.is_empty()
+    }
+}
+
+/// Settings of enabled digests which should be sent out at `now`.
+pub fn due_digests(conn: &Conn, now: NaiveDateTime) -> QueryResult<Vec<DigestSettings>> {
+    let settings = digest_settings::table
+        .inner_join(projects::table)
+        .filter(digest_settings::enabled.eq(true))
+        .select((
+            digest_settings::project_id,
+            projects::label,
+            digest_settings::weekday,
+            digest_settings::hour,
+            digest_settings::last_sent_at,
+        ))
+        .load::<DigestSettings>(conn)?;
+    Ok(settings.into_iter().filter(|s| s.is_due(now)).collect())
+}
+
+/// Users allowed to assign documents (i.e. supervisors), with an e-mail
+/// address, who've assigned documents in the project.
+pub fn recipients(conn: &Conn, project_id: i32) -> QueryResult<Vec<Recipient>> {
+    let assigners = docs::table
+        .filter(docs::project_id.eq(project_id))
+        .select(docs::assigned_by_id)
+        .distinct()
+        .load::<Option<i32>>(conn)?;
+    let assigners: Vec<_> = assigners.into_iter().flatten().collect();
+    let allowed = permissions::holders(conn, DOC_ASSIGN)?;
+    let recipients = users::table
+        .filter(users::id.eq_any(assigners))
+        .filter(users::id.eq_any(allowed))
+        .select((users::id, users::username, users::email))
+        .load::<(i32, String, Option<String>)>(conn)?;
+    Ok(recipients
+        .into_iter()
+        .filter_map(|(id, username, email)| {
+            email.map(|email| Recipient {
+                id,
+                username,
+                email,
+            })
+        })
+        .collect())
+}
+
+/// Summarize activity in the project since `since` for documents assigned by
+/// the given supervisor.
+pub fn digest(
+    conn: &Conn,
+    project_id: i32,
+    supervisor_id: i32,
+    since: NaiveDateTime,
+    now: NaiveDateTime,
+) -> QueryResult<Digest> {
+    let supervised = docs::table
+        .filter(docs::project_id.eq(project_id))
+        .filter(docs::assigned_by_id.eq(supervisor_id));
+
+    let submitted = supervised
+        .filter(docs::done.eq(true))
+        .filter(docs::done_at.ge(since))
+        .select((docs::id, docs::assigned_to_id, docs::due_at))
+        .order(docs::done_at)
+        .load::<(i32, Option<i32>, Option<NaiveDateTime>)>(conn)?;
+    let overdue = supervised
+        .filter(docs::done.is_null().or(docs::done.eq(false)))
+        .filter(docs::due_at.lt(now))
+        .select((docs::id, docs::assigned_to_id, docs::due_at))
+        .order(docs::due_at)
+        .load::<(i32, Option<i32>, Option<NaiveDateTime>)>(conn)?;
+
+    let user_ids = submitted
+        .iter()
+        .chain(overdue.iter())
+        .filter_map(|(_, assigned_to, _)| *assigned_to);
+    let usernames: HashMap<_, _> = users::table
+        .filter(users::id.eq_any(user_ids.collect::<Vec<_>>()))
+        .select((users::id, users::username))
+        .load::<(i32, String)>(conn)?
+        .into_iter()
+        .collect();
+    let summarize =
+        |(id, assigned_to, due_at): (i32, Option<i32>, Option<NaiveDateTime>)| DocSummary {
+            id,
+            assigned_to: assigned_to.and_then(|u| usernames.get(&u).cloned()),
+            due_at,
+        };
+
+    Ok(Digest {
+        since,
+        submitted: submitted.into_iter().map(summarize).collect(),
+        overdue: overdue.into_iter().map(summarize).collect(),
+        hotspots: hotspots(conn, project_id, supervisor_id, since)?,
+    })
+}
+
+fn hotspots(
+    conn: &Conn,
+    project_id: i32,
+    supervisor_id: i32,
+    since: NaiveDateTime,
+) -> QueryResult<Vec<Hotspot>> {
+    let runs = validation_runs::table
+        .inner_join(docs::table)
+        .filter(docs::project_id.eq(project_id))
+        .filter(docs::assigned_by_id.eq(supervisor_id))
+        .filter(validation_runs::created_at.ge(since))
+        .select((
+            validation_runs::doc_id,
+            validation_runs::mistakes,
+            validation_runs::created_at,
+        ))
+        .order(validation_runs::created_at)
+        .load::<(i32, i32, NaiveDateTime)>(conn)?;
+
+    // runs are ordered chronologically, so later ones overwrite earlier ones
+    let mut latest = HashMap::new();
+    for (doc_id, mistakes, validated_at) in runs {
+        latest.insert(
+            doc_id,
+            Hotspot {
+                doc_id,
+                mistakes,
+                validated_at,
+            },
+        );
+    }
+    let mut hotspots: Vec<_> = latest.into_values().collect();
+    hotspots.retain(|h| h.mistakes > 0);
+    hotspots.sort_unstable_by_key(|h| (-h.mistakes, h.doc_id));
+    hotspots.truncate(HOTSPOTS);
+    Ok(hotspots)
+}
+
+pub fn mark_sent(conn: &Conn, project_id: i32, now: NaiveDateTime) -> QueryResult<()> {
+    diesel::update(digest_settings::table.find(project_id))
+        .set(digest_settings::last_sent_at.eq(now))
+        .execute(conn)
+        .map(|_| ())
+}
@@ -0,0 +1,359 @@
+//! Documents and their lifecycle.
+
+use std::collections::BTreeMap;
+use std::{fmt, str::FromStr};
+
+use chrono::NaiveDateTime;
+use diesel::dsl::now;
+use diesel::prelude::*;
+
+use super::corpora;
+use super::schema::{doc2corpus, doc2speaker, docs, enum_doc_states, projects, users};
+use super::Conn;
+
+/// Lifecycle states of a document. Discriminants are IDs in
+/// `enum_doc_states`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DocState {
+    New = 1,
+    Assigned = 2,
+    Submitted = 3,
+    Returned = 4,
+    Accepted = 5,
+}
+
+impl DocState {
+    pub const ALL: [DocState; 5] = [
+        DocState::New,
+        DocState::Assigned,
+        DocState::Submitted,
+        DocState::Returned,
+        DocState::Accepted,
+    ];
+
+    pub fn id(self) -> i32 {
+        self as i32
+    }
+
+    pub fn from_id(id: i32) -> Option<Self> {
+        DocState::ALL.iter().find(|state| state.id() == id).copied()
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            DocState::New => "new",
+            DocState::Assigned => "assigned",
+            DocState::Submitted => "submitted",
+            DocState::Returned => "returned",
+            DocState::Accepted => "accepted",
+        }
+    }
+}
+
+impl fmt::Display for DocState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.label())
+    }
+}
+
+impl FromStr for DocState {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        DocState::ALL
+            .iter()
+            .find(|state| state.label() == s)
+            .copied()
+            .ok_or_else(|| format!("unknown document state {:?}", s))
+    }
+}
+
+#[derive(Debug)]
+pub struct DocRow {
+    pub id: i32,
+    pub project_id: i32,
+    pub project: String,
+    /// Labels of the corpora the document belongs to.
+    pub corpora: Vec<String>,
+    pub state: String,
+    pub assigned_to_id: Option<i32>,
+    /// Whether the assignee has marked the transcript as done.
+    pub done: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct DocFilter {
+    pub project_id: Option<i32>,
+    pub corpus_id: Option<i32>,
+    pub assigned_to_id: Option<i32>,
+    /// Empty means any state.
+    pub states: Vec<DocState>,
+}
+
+pub fn list(conn: &Conn, filter: &DocFilter) -> QueryResult<Vec<DocRow>> {
+    let mut query = docs::table
+        .inner_join(projects::table)
+        .inner_join(enum_doc_states::table)
+        .select((
+            docs::id,
+            docs::project_id,
+            projects::label,
+            enum_doc_states::label,
+            docs::assigned_to_id,
+            docs::done,
+        ))
+        .order(docs::id)
+        .into_boxed();
+    if let Some(project_id) = filter.project_id {
+        query = query.filter(docs::project_id.eq(project_id));
+    }
+    if let Some(corpus_id) = filter.corpus_id {
+        query = query.filter(
+            docs::id.eq_any(
+                doc2corpus::table
+                    .filter(doc2corpus::corpus_id.eq(corpus_id))
+                    .select(doc2corpus::doc_id),
+            ),
+        );
+    }
+    if let Some(user_id) = filter.assigned_to_id {
+        query = query.filter(docs::assigned_to_id.eq(user_id));
+    }
+    if !filter.states.is_empty() {
+        let states: Vec<_> = filter.states.iter().map(|s| s.id()).collect();
+        query = query.filter(docs::state_id.eq_any(states));
+    }
+    let rows = query.load::<(i32, i32, String, String, Option<i32>, Option<bool>)>(conn)?;
+    let ids: Vec<_> = rows.iter().map(|r| r.0).collect();
+    let mut labels = corpora::labels_for_docs(conn, &ids)?;
+    Ok(rows
+        .into_iter()
+        .map(
+            |(id, project_id, project, state, assigned_to_id, done)| DocRow {
+                id,
+                project_id,
+                project,
+                corpora: labels.remove(&id).unwrap_or_default(),
+                state,
+                assigned_to_id,
+                done: done.unwrap_or(false),
+            },
+        )
+        .collect())
+}
+
+pub fn project_of(conn: &Conn, doc_id: i32) -> QueryResult<i32> {
+    docs::table
+        .find(doc_id)
+        .select(docs::project_id)
+        .first(conn)
+}
+
+/// The document's project and assignee, if any, for deciding who may
+/// access it.
+pub fn access_of(conn: &Conn, doc_id: i32) -> QueryResult<(i32, Option<i32>)> {
+    docs::table
+        .find(doc_id)
+        .select((docs::project_id, docs::assigned_to_id))
+        .first(conn)
+}
+
+/// ID of the project with the given label, which is unique.
+pub fn project_by_label(conn: &Conn, label: &str) -> QueryResult<i32> {
+    projects::table
+        .filter(projects::label.eq(label))
+        .select(projects::id)
+        .first(conn)
+}
+
+/// Create a new document in the same project, corpora and place as the
+/// template, linked to the same speakers. Returns the new document's ID.
+pub fn duplicate(conn: &Conn, template_id: i32, date: Option<NaiveDateTime>) -> QueryResult<i32> {
+    conn.transaction(|| {
+        // copied in SQL, as dates in older data aren't necessarily full
+        // timestamps
+        let copied = diesel::insert_into(docs::table)
+            .values(docs::table.filter(docs::id.eq(template_id)).select((
+                docs::project_id,
+                docs::place_id,
+                docs::date,
+            )))
+            .into_columns((docs::project_id, docs::place_id, docs::date))
+            .execute(conn)?;
+        if copied == 0 {
+            return Err(diesel::result::Error::NotFound);
+        }
+        let doc_id = docs::table
+            .select(docs::id)
+            .order(docs::id.desc())
+            .first(conn)?;
+        if let Some(date) = date {
+            diesel::update(docs::table.find(doc_id))
+                .set(docs::date.eq(date))
+                .execute(conn)?;
+        }
+        let corpus_ids = doc2corpus::table
+            .filter(doc2corpus::doc_id.eq(template_id))
+            .select(doc2corpus::corpus_id)
+            .load::<i32>(conn)?;
+        corpora::replace_for_doc(conn, doc_id, &corpus_ids)?;
+        let speaker_ids = doc2speaker::table
+            .filter(doc2speaker::doc_id.eq(template_id))
+            .select(doc2speaker::speaker_id)
+            .load::<i32>(conn)?;
+        for speaker_id in speaker_ids {
+            diesel::insert_into(doc2speaker::table)
+                .values((
+                    doc2speaker::doc_id.eq(doc_id),
+                    doc2speaker::speaker_id.eq(speaker_id),
+                ))
+                .execute(conn)?;
+        }
+        Ok(doc_id)
+    })
+}
+
+/// Documents assigned to the user, e.g. a transcriber's work queue.
+pub fn for_user(conn: &Conn, user_id: i32) -> QueryResult<Vec<DocRow>> {
+    list(
+        conn,
+        &DocFilter {
+            assigned_to_id: Some(user_id),
+            ..DocFilter::default()
+        },
+    )
+}
+
+/// Assign the document to a transcriber, starting over if it was assigned
+/// to someone else or already done. Returns whether there was such a
+/// document.
+pub fn assign(
+    conn: &Conn,
+    doc_id: i32,
+    user_id: i32,
+    assigned_by: i32,
+    due_at: Option<NaiveDateTime>,
+) -> QueryResult<bool> {
+    diesel::update(docs::table.find(doc_id))
+        .set((
+            docs::assigned_to_id.eq(user_id),
+            docs::assigned_by_id.eq(assigned_by),
+            docs::assigned_at.eq(now),
+            docs::due_at.eq(due_at),
+            docs::done.eq(false),
+            docs::done_at.eq(None::<NaiveDateTime>),
+            docs::state_id.eq(DocState::Assigned.id()),
+        ))
+        .execute(conn)
+        .map(|n| n > 0)
+}
+
+/// The document's lifecycle state.
+pub fn state_of(conn: &Conn, doc_id: i32) -> QueryResult<DocState> {
+    let state_id = docs::table
+        .find(doc_id)
+        .select(docs::state_id)
+        .first::<i32>(conn)?;
+    Ok(DocState::from_id(state_id).unwrap_or(DocState::New))
+}
+
+/// Mark the transcript as done, submitting it for review. Returns whether
+/// the document was in progress, i.e. assigned or returned.
+pub fn mark_done(conn: &Conn, doc_id: i32) -> QueryResult<bool> {
+    let in_progress = vec![DocState::Assigned.id(), DocState::Returned.id()];
+    diesel::update(
+        docs::table
+            .find(doc_id)
+            .filter(docs::state_id.eq_any(in_progress)),
+    )
+    .set((
+        docs::done.eq(true),
+        docs::done_at.eq(now),
+        docs::state_id.eq(DocState::Submitted.id()),
+    ))
+    .execute(conn)
+    .map(|n| n > 0)
+}
+
+/// How many documents in each state a transcriber has.
+#[derive(Debug)]
+pub struct Workload {
+    pub user_id: i32,
+    pub username: String,
+    pub states: BTreeMap<&'static str, i64>,
+    /// Not done yet, past their due date.
+    pub overdue: i64,
+}
+
+/// Workload of everyone with documents assigned in the project as of `at`,
+/// ordered by username.
+pub fn workload(conn: &Conn, project_id: i32, at: NaiveDateTime) -> QueryResult<Vec<Workload>> {
+    let rows = docs::table
+        .filter(docs::project_id.eq(project_id))
+        .select((
+            docs::assigned_to_id,
+            docs::state_id,
+            docs::done,
+            docs::due_at,
+        ))
+        .load::<(Option<i32>, i32, Option<bool>, Option<NaiveDateTime>)>(conn)?;
+    let user_ids: Vec<_> = rows.iter().filter_map(|r| r.0).collect();
+    let mut workloads: Vec<_> = users::table
+        .filter(users::id.eq_any(user_ids))
+        .select((users::id, users::username))
+        .load::<(i32, String)>(conn)?
+        .into_iter()
+        .map(|(user_id, username)| Workload {
+            user_id,
+            username,
+            states: BTreeMap::new(),
+            overdue: 0,
+        })
+        .collect();
+    workloads.sort_by(|a, b| a.username.cmp(&b.username));
+    for (assigned_to_id, state_id, done, due_at) in rows {
+        let workload = match workloads
+            .iter_mut()
+            .find(|w| Some(w.user_id) == assigned_to_id)
+        {
+            Some(workload) => workload,
+            None => continue,
+        };
+        let state = DocState::from_id(state_id).unwrap_or(DocState::New);
+        *workload.states.entry(state.label()).or_insert(0) += 1;
+        if !done.unwrap_or(false) && due_at.map_or(false, |due_at| due_at < at) {
+            workload.overdue += 1;
+        }
+    }
+    Ok(workloads)
+}
+
+/// Documents which the transcriber considers done but which haven't been
+/// reviewed yet.
+pub fn awaiting_review(conn: &Conn, project_id: i32) -> QueryResult<Vec<DocRow>> {
+    list(
+        conn,
+        &DocFilter {
+            project_id: Some(project_id),
+            corpus_id: None,
+            assigned_to_id: None,
+            states: vec![DocState::Submitted],
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_labels_roundtrip() {
+        for state in &DocState::ALL {
+            assert_eq!(state.label().parse::<DocState>(), Ok(*state));
+        }
+        assert!("done".parse::<DocState>().is_err());
+        for state in &DocState::ALL {
+            assert_eq!(DocState::from_id(state.id()), Some(*state));
+        }
+    }
+}
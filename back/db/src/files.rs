@@ -0,0 +1,84 @@
+//! Bookkeeping for files stored alongside documents. The files themselves
+//! live on disk, the DB only records where and what they are.
+
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+
+use super::schema::files;
+use super::Conn;
+
+/// Transcript proper.
+pub const EAF: &str = "eaf";
+/// Draft transcript, e.g. pre-filled by speech recognition.
+pub const DRAFT_EAF: &str = "draft_eaf";
+/// Session metadata (CMDI or IMDI) accompanying the transcript, see
+/// `metadata`.
+pub const METADATA: &str = "metadata";
+/// Recording as uploaded (WAV or FLAC).
+pub const AUDIO: &str = "audio";
+/// Web-friendly transcodes of the recording, see `jobs::TRANSCODE`.
+pub const AUDIO_OPUS: &str = "audio_opus";
+pub const AUDIO_MP3: &str = "audio_mp3";
+/// Low-bitrate version for quick previews.
+pub const AUDIO_PREVIEW: &str = "audio_preview";
+
+#[derive(Debug, Insertable)]
+#[table_name = "files"]
+pub struct NewFile {
+    pub doc_id: i32,
+    pub role: String,
+    /// Relative to the storage directory.
+    pub path: String,
+    pub mime: String,
+    pub size: i64,
+    pub created_by: Option<i32>,
+    /// The file this one was derived from, if any.
+    pub source_id: Option<i32>,
+}
+
+#[derive(Debug, Queryable)]
+pub struct File {
+    pub id: i32,
+    pub doc_id: i32,
+    pub role: String,
+    pub path: String,
+    pub mime: String,
+    pub size: i64,
+    pub created_by: Option<i32>,
+    pub created_at: NaiveDateTime,
+    pub source_id: Option<i32>,
+}
+
+pub fn add(conn: &Conn, file: &NewFile) -> QueryResult<i32> {
+    conn.transaction(|| {
+        diesel::insert_into(files::table)
+            .values(file)
+            .execute(conn)?;
+        files::table
+            .select(files::id)
+            .order(files::id.desc())
+            .first(conn)
+    })
+}
+
+pub fn get(conn: &Conn, id: i32) -> QueryResult<File> {
+    files::table.find(id).first(conn)
+}
+
+/// The document's newest file with one of the given roles.
+pub fn latest(conn: &Conn, doc_id: i32, roles: &[&str]) -> QueryResult<Option<File>> {
+    files::table
+        .filter(files::doc_id.eq(doc_id))
+        .filter(files::role.eq_any(roles))
+        .order((files::created_at.desc(), files::id.desc()))
+        .first(conn)
+        .optional()
+}
+
+/// The document's files, newest first.
+pub fn for_doc(conn: &Conn, doc_id: i32) -> QueryResult<Vec<File>> {
+    files::table
+        .filter(files::doc_id.eq(doc_id))
+        .order((files::created_at.desc(), files::id.desc()))
+        .load(conn)
+}
@@ -0,0 +1,137 @@
+//! Labels of the enum tables, for seeding new databases (the default ones
+//! are in `fixtures/enums.toml`). Seeding only adds labels which are
+//! missing, so it can be repeated, and existing ones keep their IDs.
+
+use std::fmt;
+
+use diesel::prelude::*;
+use serde::Deserialize;
+
+use super::schema::{
+    enum_dialect_areas, enum_educations, enum_genders, enum_places, enum_regions, enum_roles,
+};
+use super::Conn;
+
+/// The fixtures the binaries are built with.
+pub const DEFAULT: &str = include_str!("../fixtures/enums.toml");
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Fixtures {
+    pub roles: Vec<String>,
+    pub genders: Vec<String>,
+    pub educations: Vec<String>,
+    pub regions: Vec<String>,
+    pub dialect_areas: Vec<String>,
+    pub places: Vec<Place>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Place {
+    pub label: String,
+    /// Label of the place's region, which must be in the fixtures or the DB.
+    pub region: String,
+    /// Ditto for its dialect area, if known.
+    pub dialect_area: Option<String>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+}
+
+#[derive(Debug)]
+pub enum SeedError {
+    Parse(toml::de::Error),
+    UnknownRegion(String),
+    UnknownDialectArea(String),
+    Db(diesel::result::Error),
+}
+
+impl fmt::Display for SeedError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SeedError::Parse(e) => write!(f, "bad fixtures: {}", e),
+            SeedError::UnknownRegion(label) => write!(f, "unknown region {:?}", label),
+            SeedError::UnknownDialectArea(label) => write!(f, "unknown dialect area {:?}", label),
+            SeedError::Db(e) => e.fmt(f),
+        }
+    }
+}
+
+impl From<diesel::result::Error> for SeedError {
+    fn from(e: diesel::result::Error) -> Self {
+        SeedError::Db(e)
+    }
+}
+
+pub fn parse(toml: &str) -> Result<Fixtures, SeedError> {
+    toml::from_str(toml).map_err(SeedError::Parse)
+}
+
+/// Add `$labels` missing from the enum table `$table`, counting them in
+/// `$added`.
+macro_rules! seed_labels {
+    ($conn:expr, $added:ident, $table:ident, $labels:expr) => {
+        for label in $labels {
+            let exists = $table::table
+                .filter($table::label.eq(label))
+                .select($table::id)
+                .first::<i32>($conn)
+                .optional()?
+                .is_some();
+            if !exists {
+                $added += diesel::insert_into($table::table)
+                    .values($table::label.eq(label))
+                    .execute($conn)?;
+            }
+        }
+    };
+}
+
+/// Add the labels missing from the DB. Returns how many were added.
+pub fn seed(conn: &Conn, fixtures: &Fixtures) -> Result<usize, SeedError> {
+    conn.transaction(|| {
+        let mut added = 0;
+        seed_labels!(conn, added, enum_roles, &fixtures.roles);
+        seed_labels!(conn, added, enum_genders, &fixtures.genders);
+        seed_labels!(conn, added, enum_educations, &fixtures.educations);
+        seed_labels!(conn, added, enum_regions, &fixtures.regions);
+        seed_labels!(conn, added, enum_dialect_areas, &fixtures.dialect_areas);
+        for place in &fixtures.places {
+            let exists = enum_places::table
+                .filter(enum_places::label.eq(&place.label))
+                .select(enum_places::id)
+                .first::<i32>(conn)
+                .optional()?
+                .is_some();
+            if exists {
+                continue;
+            }
+            let region_id = enum_regions::table
+                .filter(enum_regions::label.eq(&place.region))
+                .select(enum_regions::id)
+                .first::<i32>(conn)
+                .optional()?
+                .ok_or_else(|| SeedError::UnknownRegion(place.region.clone()))?;
+            let dialect_area_id = match &place.dialect_area {
+                Some(label) => Some(
+                    enum_dialect_areas::table
+                        .filter(enum_dialect_areas::label.eq(label))
+                        .select(enum_dialect_areas::id)
+                        .first::<i32>(conn)
+                        .optional()?
+                        .ok_or_else(|| SeedError::UnknownDialectArea(label.clone()))?,
+                ),
+                None => None,
+            };
+            added += diesel::insert_into(enum_places::table)
+                .values((
+                    enum_places::label.eq(&place.label),
+                    enum_places::region_id.eq(region_id),
+                    enum_places::latitude.eq(place.latitude),
+                    enum_places::longitude.eq(place.longitude),
+                    enum_places::dialect_area_id.eq(dialect_area_id),
+                ))
+                .execute(conn)?;
+        }
+        Ok(added)
+    })
+}
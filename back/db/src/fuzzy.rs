@@ -0,0 +1,112 @@
+//! Typo-tolerant matching of search queries against short labels (place
+//! names, usernames, nicknames…).
+//!
+//! Matching ignores case and diacritics, because people type "Usti" when
+//! they mean "Ústí".
+
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+
+/// How well a query matches a label; variants are ordered from best to worst.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Match {
+    /// The label starts with the query.
+    Prefix,
+    /// Some later word of the label starts with the query.
+    WordPrefix,
+    /// A word of the label starts with something within the given edit
+    /// distance of the query.
+    Fuzzy(usize),
+}
+
+/// Lowercase and strip diacritics.
+pub fn normalize(s: &str) -> String {
+    s.nfd()
+        .filter(|c| !is_combining_mark(*c))
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// How many typos to tolerate in a query of the given length (in chars).
+fn max_typos(len: usize) -> usize {
+    len / 4
+}
+
+/// Match an already normalized query against a label.
+pub fn score(query: &str, label: &str) -> Option<Match> {
+    let label = normalize(label);
+    if label.starts_with(query) {
+        return Some(Match::Prefix);
+    }
+    let words = label
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty());
+    if words.clone().any(|w| w.starts_with(query)) {
+        return Some(Match::WordPrefix);
+    }
+
+    let query: Vec<_> = query.chars().collect();
+    let max = max_typos(query.len());
+    if max == 0 {
+        return None;
+    }
+    words
+        .map(|w| {
+            // compare against a prefix of the word of the same length as
+            // the query, allowing for the query to be a bit shorter or longer
+            let w: Vec<_> = w.chars().collect();
+            (query.len().saturating_sub(max)..=query.len() + max)
+                .map(|len| levenshtein(&query, &w[..len.min(w.len())]))
+                .min()
+                .unwrap_or(usize::MAX)
+        })
+        .min()
+        .filter(|&d| d <= max)
+        .map(Match::Fuzzy)
+}
+
+fn levenshtein(a: &[char], b: &[char]) -> usize {
+    let mut prev: Vec<_> = (0..=b.len()).collect();
+    let mut curr = vec![0; b.len() + 1];
+    for (i, ca) in a.iter().enumerate() {
+        curr[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let subst = prev[j] + if ca == cb { 0 } else { 1 };
+            curr[j + 1] = subst.min(prev[j + 1] + 1).min(curr[j] + 1);
+        }
+        std::mem::swap(&mut prev, &mut curr);
+    }
+    prev[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(normalize("Ústí nad Labem"), "usti nad labem");
+        assert_eq!(normalize("ČESKÉ Budějovice"), "ceske budejovice");
+    }
+
+    #[test]
+    fn test_score() {
+        assert_eq!(score("usti", "Ústí nad Labem"), Some(Match::Prefix));
+        assert_eq!(score("lab", "Ústí nad Labem"), Some(Match::WordPrefix));
+        assert_eq!(score("ostrva", "Ostrava"), Some(Match::Fuzzy(1)));
+        assert_eq!(score("ostarva", "Ostrava"), None, "too many typos");
+        assert_eq!(score("brmo", "Brno"), Some(Match::Fuzzy(1)));
+        assert_eq!(
+            score("prg", "Praha"),
+            None,
+            "short queries must match exactly"
+        );
+        assert_eq!(score("olomouc", "Ostrava"), None);
+    }
+
+    #[test]
+    fn test_ordering() {
+        assert!(Match::Prefix < Match::WordPrefix);
+        assert!(Match::WordPrefix < Match::Fuzzy(0));
+        assert!(Match::Fuzzy(1) < Match::Fuzzy(2));
+    }
+}
@@ -0,0 +1,333 @@
+//! Places and regions, as used for speaker and document metadata, and
+//! their coverage by the corpus, for planning recruitment.
+
+use std::collections::HashMap;
+
+use diesel::prelude::*;
+
+use super::fuzzy::{self, Match};
+use super::schema::{
+    doc2corpus, doc2speaker, docs, enum_dialect_areas, enum_places, enum_regions, speakers,
+};
+use super::Conn;
+
+#[derive(Debug)]
+pub struct Completion {
+    pub id: i32,
+    pub label: String,
+    /// Only set for places.
+    pub region: Option<String>,
+    /// How many speakers and documents refer to this place/region.
+    pub uses: usize,
+}
+
+/// Rank candidates by match quality first, then by how often they're used,
+/// and keep the best `limit` of them.
+fn rank(query: &str, candidates: Vec<Completion>, limit: usize) -> Vec<Completion> {
+    let query = fuzzy::normalize(query.trim());
+    let mut matches: Vec<(Match, Completion)> = candidates
+        .into_iter()
+        .filter_map(|c| fuzzy::score(&query, &c.label).map(|m| (m, c)))
+        .collect();
+    matches.sort_by(|(m1, c1), (m2, c2)| {
+        m1.cmp(m2)
+            .then(c2.uses.cmp(&c1.uses))
+            .then_with(|| c1.label.cmp(&c2.label))
+    });
+    matches.into_iter().take(limit).map(|(_, c)| c).collect()
+}
+
+/// Number of speakers and documents per place ID.
+fn place_uses(conn: &Conn) -> QueryResult<HashMap<i32, usize>> {
+    let mut uses = HashMap::new();
+    let speaker_places = speakers::table
+        .select(speakers::place_id)
+        .load::<i32>(conn)?;
+    let doc_places = docs::table.select(docs::place_id).load::<i32>(conn)?;
+    for place_id in speaker_places.into_iter().chain(doc_places) {
+        *uses.entry(place_id).or_insert(0) += 1;
+    }
+    Ok(uses)
+}
+
+pub fn complete_places(conn: &Conn, query: &str, limit: usize) -> QueryResult<Vec<Completion>> {
+    let uses = place_uses(conn)?;
+    let places = enum_places::table
+        .inner_join(enum_regions::table)
+        .select((enum_places::id, enum_places::label, enum_regions::label))
+        .load::<(i32, String, String)>(conn)?
+        .into_iter()
+        .map(|(id, label, region)| Completion {
+            id,
+            label,
+            region: Some(region),
+            uses: uses.get(&id).copied().unwrap_or(0),
+        })
+        .collect();
+    Ok(rank(query, places, limit))
+}
+
+pub fn complete_regions(conn: &Conn, query: &str, limit: usize) -> QueryResult<Vec<Completion>> {
+    let place_uses = place_uses(conn)?;
+    let mut uses = HashMap::new();
+    let places = enum_places::table
+        .select((enum_places::id, enum_places::region_id))
+        .load::<(i32, i32)>(conn)?;
+    for (place_id, region_id) in places {
+        *uses.entry(region_id).or_insert(0) += place_uses.get(&place_id).copied().unwrap_or(0);
+    }
+    let regions = enum_regions::table
+        .load::<(i32, String)>(conn)?
+        .into_iter()
+        .map(|(id, label)| Completion {
+            id,
+            label,
+            region: None,
+            uses: uses.get(&id).copied().unwrap_or(0),
+        })
+        .collect();
+    Ok(rank(query, regions, limit))
+}
+
+/// A row of one of the simple enum tables.
+#[derive(Debug, Queryable)]
+pub struct Label {
+    pub id: i32,
+    pub label: String,
+}
+
+#[derive(Debug, Queryable)]
+pub struct Place {
+    pub id: i32,
+    pub label: String,
+    pub region_id: i32,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub dialect_area_id: Option<i32>,
+}
+
+#[derive(Debug, Insertable, AsChangeset)]
+#[table_name = "enum_places"]
+#[changeset_options(treat_none_as_null = "true")]
+pub struct PlaceData {
+    pub label: String,
+    pub region_id: i32,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub dialect_area_id: Option<i32>,
+}
+
+pub fn regions(conn: &Conn) -> QueryResult<Vec<Label>> {
+    enum_regions::table.order(enum_regions::label).load(conn)
+}
+
+pub fn dialect_areas(conn: &Conn) -> QueryResult<Vec<Label>> {
+    enum_dialect_areas::table
+        .order(enum_dialect_areas::id)
+        .load(conn)
+}
+
+pub fn places(conn: &Conn, region_id: Option<i32>) -> QueryResult<Vec<Place>> {
+    let mut query = enum_places::table
+        .select((
+            enum_places::id,
+            enum_places::label,
+            enum_places::region_id,
+            enum_places::latitude,
+            enum_places::longitude,
+            enum_places::dialect_area_id,
+        ))
+        .order(enum_places::label)
+        .into_boxed();
+    if let Some(region_id) = region_id {
+        query = query.filter(enum_places::region_id.eq(region_id));
+    }
+    query.load(conn)
+}
+
+pub fn add_region(conn: &Conn, label: &str) -> QueryResult<i32> {
+    conn.transaction(|| {
+        diesel::insert_into(enum_regions::table)
+            .values(enum_regions::label.eq(label))
+            .execute(conn)?;
+        enum_regions::table
+            .select(enum_regions::id)
+            .order(enum_regions::id.desc())
+            .first(conn)
+    })
+}
+
+/// Returns whether the region exists.
+pub fn rename_region(conn: &Conn, id: i32, label: &str) -> QueryResult<bool> {
+    let updated = diesel::update(enum_regions::table.find(id))
+        .set(enum_regions::label.eq(label))
+        .execute(conn)?;
+    Ok(updated > 0)
+}
+
+/// Remove a region without any places. Returns whether there was anything
+/// to remove.
+pub fn remove_region(conn: &Conn, id: i32) -> Result<bool, InUse> {
+    conn.transaction(|| {
+        let places = enum_places::table
+            .filter(enum_places::region_id.eq(id))
+            .count()
+            .get_result::<i64>(conn)?;
+        if places > 0 {
+            return Err(InUse::Uses(places as usize));
+        }
+        let removed = diesel::delete(enum_regions::table.find(id)).execute(conn)?;
+        Ok(removed > 0)
+    })
+}
+
+pub fn add_place(conn: &Conn, place: &PlaceData) -> QueryResult<i32> {
+    conn.transaction(|| {
+        diesel::insert_into(enum_places::table)
+            .values(place)
+            .execute(conn)?;
+        enum_places::table
+            .select(enum_places::id)
+            .order(enum_places::id.desc())
+            .first(conn)
+    })
+}
+
+/// Returns whether the place exists.
+pub fn update_place(conn: &Conn, id: i32, place: &PlaceData) -> QueryResult<bool> {
+    let updated = diesel::update(enum_places::table.find(id))
+        .set(place)
+        .execute(conn)?;
+    Ok(updated > 0)
+}
+
+/// Why a place or region couldn't be removed.
+#[derive(Debug)]
+pub enum InUse {
+    /// Number of speakers and documents (for places) or places (for
+    /// regions) referring to it.
+    Uses(usize),
+    Db(diesel::result::Error),
+}
+
+impl From<diesel::result::Error> for InUse {
+    fn from(e: diesel::result::Error) -> Self {
+        InUse::Db(e)
+    }
+}
+
+/// Remove a place no speakers or documents refer to. Returns whether there
+/// was anything to remove.
+pub fn remove_place(conn: &Conn, id: i32) -> Result<bool, InUse> {
+    conn.transaction(|| {
+        let uses = place_uses(conn)?.get(&id).copied().unwrap_or(0);
+        if uses > 0 {
+            return Err(InUse::Uses(uses));
+        }
+        let removed = diesel::delete(enum_places::table.find(id)).execute(conn)?;
+        Ok(removed > 0)
+    })
+}
+
+#[derive(Debug)]
+pub struct Coverage {
+    pub place_id: i32,
+    pub place: String,
+    pub region: String,
+    pub dialect_area: Option<String>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub documents: usize,
+    pub speakers: usize,
+}
+
+/// Number of documents recorded and speakers coming from each place,
+/// optionally only in one project and/or corpus (in which case speakers
+/// are those appearing in the corpus' documents). Places with neither are
+/// left out.
+pub fn coverage(
+    conn: &Conn,
+    project_id: Option<i32>,
+    corpus_id: Option<i32>,
+) -> QueryResult<Vec<Coverage>> {
+    let mut doc_query = docs::table.select((docs::id, docs::place_id)).into_boxed();
+    if let Some(project_id) = project_id {
+        doc_query = doc_query.filter(docs::project_id.eq(project_id));
+    }
+    if let Some(corpus_id) = corpus_id {
+        doc_query = doc_query.filter(
+            docs::id.eq_any(
+                doc2corpus::table
+                    .filter(doc2corpus::corpus_id.eq(corpus_id))
+                    .select(doc2corpus::doc_id),
+            ),
+        );
+    }
+    let doc_places = doc_query.load::<(i32, i32)>(conn)?;
+
+    let speaker_places = match corpus_id {
+        Some(_) => {
+            let doc_ids: Vec<_> = doc_places.iter().map(|(id, _)| *id).collect();
+            doc2speaker::table
+                .inner_join(speakers::table)
+                .filter(doc2speaker::doc_id.eq_any(doc_ids))
+                .select((speakers::id, speakers::place_id))
+                .distinct()
+                .load::<(i32, i32)>(conn)?
+        }
+        None => {
+            let mut query = speakers::table
+                .select((speakers::id, speakers::place_id))
+                .into_boxed();
+            if let Some(project_id) = project_id {
+                query = query.filter(speakers::project_id.eq(project_id));
+            }
+            query.load(conn)?
+        }
+    };
+
+    let mut counts: HashMap<i32, (usize, usize)> = HashMap::new();
+    for (_, place_id) in doc_places {
+        counts.entry(place_id).or_default().0 += 1;
+    }
+    for (_, place_id) in speaker_places {
+        counts.entry(place_id).or_default().1 += 1;
+    }
+
+    let rows = enum_places::table
+        .inner_join(enum_regions::table)
+        .left_join(enum_dialect_areas::table)
+        .select((
+            enum_places::id,
+            enum_places::label,
+            enum_regions::label,
+            enum_dialect_areas::label.nullable(),
+            enum_places::latitude,
+            enum_places::longitude,
+        ))
+        .order(enum_places::label)
+        .load::<(
+            i32,
+            String,
+            String,
+            Option<String>,
+            Option<f64>,
+            Option<f64>,
+        )>(conn)?;
+    Ok(rows
+        .into_iter()
+        .filter_map(|(id, place, region, dialect_area, latitude, longitude)| {
+            let (documents, speakers) = counts.get(&id).copied()?;
+            Some(Coverage {
+                place_id: id,
+                place,
+                region,
+                dialect_area,
+                latitude,
+                longitude,
+                documents,
+                speakers,
+            })
+        })
+        .collect())
+}
@@ -0,0 +1,249 @@
+//! Bulk import of speaker metadata from CSV spreadsheets.
+//!
+//! Import happens in two phases: all rows are first checked against the
+//! database and each other, collecting *all* problems found, so that the
+//! spreadsheet can be fixed in one go. Only if there are none are the
+//! speakers inserted, in a single transaction.
+
+use std::{collections::HashMap, io::Read};
+
+use chrono::{Datelike, Local};
+use diesel::prelude::*;
+
+use super::schema::{enum_educations, enum_genders, enum_places, speakers};
+use super::Conn;
+
+/// Earliest birth year we consider plausible.
+pub(crate) const MIN_YEAR: i32 = 1900;
+
+/// Which CSV column holds which speaker field. Defaults to columns named
+/// after the fields themselves.
+#[derive(Debug, Clone)]
+pub struct ColumnMapping {
+    pub nickname: String,
+    pub gender: String,
+    pub education: String,
+    pub place: String,
+    pub year: String,
+}
+
+impl Default for ColumnMapping {
+    fn default() -> Self {
+        Self {
+            nickname: "nickname".to_owned(),
+            gender: "gender".to_owned(),
+            education: "education".to_owned(),
+            place: "place".to_owned(),
+            year: "year".to_owned(),
+        }
+    }
+}
+
+impl ColumnMapping {
+    /// Override the column for a field given as `field=Column`.
+    pub fn set(&mut self, assignment: &str) -> Result<(), String> {
+        let mut split = assignment.splitn(2, '=');
+        let (field, column) = match (split.next(), split.next()) {
+            (Some(field), Some(column)) => (field.trim(), column.trim().to_owned()),
+            _ => return Err(format!("expected field=Column, got {:?}", assignment)),
+        };
+        match field {
+            "nickname" => self.nickname = column,
+            "gender" => self.gender = column,
+            "education" => self.education = column,
+            "place" => self.place = column,
+            "year" => self.year = column,
+            _ => return Err(format!("unknown speaker field {:?}", field)),
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "speakers"]
+pub struct NewSpeaker {
+    pub user_id: i32,
+    pub project_id: i32,
+    pub nickname: String,
+    pub gender_id: i32,
+    pub education_id: i32,
+    pub place_id: i32,
+    pub year: i32,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Problem {
+    /// Line number in the CSV file, counting the header as line 1; `None`
+    /// for problems with the file as a whole.
+    pub line: Option<usize>,
+    pub column: Option<String>,
+    pub message: String,
+}
+
+#[derive(Debug, Default)]
+pub struct Report {
+    pub speakers: Vec<NewSpeaker>,
+    pub problems: Vec<Problem>,
+}
+
+impl Report {
+    pub fn is_ok(&self) -> bool {
+        self.problems.is_empty()
+    }
+
+    fn problem(&mut self, line: Option<usize>, column: Option<&str>, message: String) {
+        self.problems.push(Problem {
+            line,
+            column: column.map(str::to_owned),
+            message,
+        });
+    }
+}
+
+fn by_label(rows: Vec<(i32, String)>) -> HashMap<String, i32> {
+    rows.into_iter().map(|(id, label)| (label, id)).collect()
+}
+
+/// Read and check speakers in CSV format, to be owned by the given user and
+/// assigned to the given project. Nothing is written to the database.
+pub fn check<R: Read>(
+    conn: &Conn,
+    project_id: i32,
+    user_id: i32,
+    mapping: &ColumnMapping,
+    csv: R,
+) -> QueryResult<Report> {
+    let mut report = Report::default();
+    let mut reader = csv::Reader::from_reader(csv);
+
+    let headers = match reader.headers() {
+        Ok(headers) => headers.clone(),
+        Err(e) => {
+            report.problem(None, None, format!("cannot read CSV header: {}", e));
+            return Ok(report);
+        }
+    };
+    let mut columns = HashMap::new();
+    for (field, column) in &[
+        ("nickname", &mapping.nickname),
+        ("gender", &mapping.gender),
+        ("education", &mapping.education),
+        ("place", &mapping.place),
+        ("year", &mapping.year),
+    ] {
+        match headers.iter().position(|h| h == column.as_str()) {
+            Some(i) => {
+                columns.insert(*field, i);
+            }
+            None => report.problem(
+                None,
+                Some(column),
+                format!("missing column for speaker field {}", field),
+            ),
+        }
+    }
+    if !report.is_ok() {
+        return Ok(report);
+    }
+
+    let genders = by_label(enum_genders::table.load(conn)?);
+    let educations = by_label(enum_educations::table.load(conn)?);
+    let places = by_label(
+        enum_places::table
+            .select((enum_places::id, enum_places::label))
+            .load(conn)?,
+    );
+    let mut nicknames: HashMap<String, Option<usize>> = speakers::table
+        .filter(speakers::project_id.eq(project_id))
+        .select(speakers::nickname)
+        .load::<String>(conn)?
+        .into_iter()
+        .map(|n| (n, None))
+        .collect();
+    let max_year = Local::now().year();
+
+    for (i, record) in reader.records().enumerate() {
+        // header is line 1
+        let line = Some(i + 2);
+        let record = match record {
+            Ok(record) => record,
+            Err(e) => {
+                report.problem(line, None, e.to_string());
+                continue;
+            }
+        };
+        let get = |field| record.get(columns[field]).unwrap_or_default().trim();
+        let mut row_ok = true;
+
+        let nickname = get("nickname");
+        if nickname.is_empty() {
+            row_ok = false;
+            report.problem(line, Some(&mapping.nickname), "empty nickname".to_owned());
+        } else if let Some(prev) = nicknames.get(nickname) {
+            row_ok = false;
+            let message = match prev {
+                Some(prev) => format!("nickname {:?} already used on line {}", nickname, prev),
+                None => format!("nickname {:?} already exists in project", nickname),
+            };
+            report.problem(line, Some(&mapping.nickname), message);
+        } else {
+            nicknames.insert(nickname.to_owned(), line);
+        }
+
+        let mut lookup = |field, column: &str, ids: &HashMap<String, i32>| {
+            let label = get(field);
+            let id = ids.get(label).copied();
+            if id.is_none() {
+                report.problem(line, Some(column), format!("unknown {} {:?}", field, label));
+            }
+            id
+        };
+        let gender_id = lookup("gender", &mapping.gender, &genders);
+        let education_id = lookup("education", &mapping.education, &educations);
+        let place_id = lookup("place", &mapping.place, &places);
+
+        let year = match get("year").parse::<i32>() {
+            Ok(year) if (MIN_YEAR..=max_year).contains(&year) => Some(year),
+            _ => {
+                report.problem(
+                    line,
+                    Some(&mapping.year),
+                    format!(
+                        "bad year {:?}, expected a number between {} and {}",
+                        get("year"),
+                        MIN_YEAR,
+                        max_year
+                    ),
+                );
+                None
+            }
+        };
+
+        if let (true, Some(gender_id), Some(education_id), Some(place_id), Some(year)) =
+            (row_ok, gender_id, education_id, place_id, year)
+        {
+            report.speakers.push(NewSpeaker {
+                user_id,
+                project_id,
+                nickname: nickname.to_owned(),
+                gender_id,
+                education_id,
+                place_id,
+                year,
+            });
+        }
+    }
+    Ok(report)
+}
+
+/// Insert checked speakers. Refuses to do anything if there were problems.
+pub fn commit(conn: &Conn, report: &Report) -> QueryResult<usize> {
+    if !report.is_ok() {
+        return Ok(0);
+    }
+    conn.transaction(|| {
+        diesel::insert_into(speakers::table)
+            .values(&report.speakers)
+            .execute(conn)
+    })
+}
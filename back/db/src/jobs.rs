@@ -0,0 +1,100 @@
+//! A simple queue of background jobs, each concerning a stored file.
+
+use chrono::{Local, NaiveDateTime};
+use diesel::prelude::*;
+
+use super::schema::jobs;
+use super::Conn;
+
+/// Transcode an uploaded recording to web-friendly formats.
+pub const TRANSCODE: &str = "transcode";
+/// Validate a stored transcript against its project's current rules.
+pub const REVALIDATE: &str = "revalidate";
+/// Add a validated transcript to the full-text index.
+pub const INDEX: &str = "index";
+
+const QUEUED: &str = "queued";
+const RUNNING: &str = "running";
+const DONE: &str = "done";
+const FAILED: &str = "failed";
+
+#[derive(Debug, Queryable)]
+pub struct Job {
+    pub id: i32,
+    pub kind: String,
+    pub file_id: i32,
+    pub state: String,
+    pub error: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub finished_at: Option<NaiveDateTime>,
+}
+
+pub fn enqueue(conn: &Conn, kind: &str, file_id: i32) -> QueryResult<i32> {
+    conn.transaction(|| {
+        diesel::insert_into(jobs::table)
+            .values((jobs::kind.eq(kind), jobs::file_id.eq(file_id)))
+            .execute(conn)?;
+        jobs::table
+            .select(jobs::id)
+            .order(jobs::id.desc())
+            .first(conn)
+    })
+}
+
+/// Whether a job of the given kind is already waiting for the file.
+pub fn is_queued(conn: &Conn, kind: &str, file_id: i32) -> QueryResult<bool> {
+    jobs::table
+        .filter(jobs::kind.eq(kind))
+        .filter(jobs::file_id.eq(file_id))
+        .filter(jobs::state.eq_any(&[QUEUED, RUNNING]))
+        .select(jobs::id)
+        .first::<i32>(conn)
+        .optional()
+        .map(|job| job.is_some())
+}
+
+/// Take the oldest queued job, marking it as running.
+pub fn claim_next(conn: &Conn) -> QueryResult<Option<Job>> {
+    conn.transaction(|| {
+        let job = jobs::table
+            .filter(jobs::state.eq(QUEUED))
+            .order(jobs::id)
+            .first::<Job>(conn)
+            .optional()?;
+        if let Some(job) = &job {
+            diesel::update(jobs::table.find(job.id))
+                .set(jobs::state.eq(RUNNING))
+                .execute(conn)?;
+        }
+        Ok(job)
+    })
+}
+
+pub fn finish(conn: &Conn, id: i32, result: Result<(), String>) -> QueryResult<()> {
+    let (state, error) = match result {
+        Ok(()) => (DONE, None),
+        Err(e) => (FAILED, Some(e)),
+    };
+    diesel::update(jobs::table.find(id))
+        .set((
+            jobs::state.eq(state),
+            jobs::error.eq(error),
+            jobs::finished_at.eq(Local::now().naive_local()),
+        ))
+        .execute(conn)?;
+    Ok(())
+}
+
+/// Put jobs which were interrupted (e.g. by a restart) back in the queue.
+pub fn requeue_running(conn: &Conn) -> QueryResult<usize> {
+    diesel::update(jobs::table.filter(jobs::state.eq(RUNNING)))
+        .set(jobs::state.eq(QUEUED))
+        .execute(conn)
+}
+
+pub fn for_file(conn: &Conn, file_id: i32) -> QueryResult<Vec<Job>> {
+    jobs::table
+        .filter(jobs::file_id.eq(file_id))
+        .order(jobs::id)
+        .load(conn)
+}
@@ -0,0 +1,90 @@
+#[cfg(all(feature = "sqlite", feature = "postgres"))]
+compile_error!("only one of the sqlite and postgres features can be enabled");
+#[cfg(not(any(feature = "sqlite", feature = "postgres")))]
+compile_error!("one of the sqlite and postgres features has to be enabled");
+
+#[macro_use]
+extern crate diesel;
+#[macro_use]
+extern crate diesel_migrations;
+
+pub mod acknowledgments;
+pub mod alignments;
+pub mod audit;
+pub mod backup;
+pub mod bookmarks;
+pub mod bundle;
+pub mod comments;
+pub mod corpora;
+pub mod dictionaries;
+pub mod digest;
+pub mod docs;
+pub mod files;
+pub mod fixtures;
+pub mod fuzzy;
+pub mod geo;
+pub mod import;
+pub mod jobs;
+pub mod members;
+pub mod metadata;
+pub mod migrations;
+pub mod palette;
+pub mod parser_configs;
+pub mod people;
+pub mod permissions;
+pub mod pool;
+pub mod project_stats;
+pub mod reviews;
+pub mod revisions;
+#[cfg(feature = "sqlite")]
+pub mod schema;
+#[cfg(feature = "postgres")]
+#[path = "schema_postgres.rs"]
+pub mod schema;
+pub mod sessions;
+pub mod speakers;
+pub mod substitutions;
+pub mod sync;
+pub mod tasks;
+pub mod tier_mappings;
+pub mod tier_policies;
+pub mod two_factor;
+pub mod users;
+pub mod validation;
+pub mod webhooks;
+
+use diesel::prelude::*;
+
+/// Connection to the database of the backend the crate is built for, see
+/// the `sqlite` and `postgres` features.
+#[cfg(feature = "sqlite")]
+pub type Conn = SqliteConnection;
+#[cfg(feature = "postgres")]
+pub type Conn = PgConnection;
+
+/// The backend itself, e.g. for boxed queries.
+pub type Backend = <Conn as Connection>::Backend;
+
+/// How long to wait for other connections (e.g. the web server's background
+/// threads) to release a lock on the database before giving up.
+#[cfg(feature = "sqlite")]
+const BUSY_TIMEOUT_MS: u32 = 5000;
+
+pub fn connect(database_url: &str) -> ConnectionResult<Conn> {
+    let conn = Conn::establish(database_url)?;
+    configure(&conn).map_err(|e| ConnectionError::BadConnection(e.to_string()))?;
+    Ok(conn)
+}
+
+/// Settings every connection needs, however it's made (see also `pool`).
+#[cfg(feature = "sqlite")]
+fn configure(conn: &Conn) -> QueryResult<()> {
+    conn.execute(&format!("PRAGMA busy_timeout = {}", BUSY_TIMEOUT_MS))?;
+    Ok(())
+}
+
+/// Postgres waits for locks by itself.
+#[cfg(feature = "postgres")]
+fn configure(_conn: &Conn) -> QueryResult<()> {
+    Ok(())
+}
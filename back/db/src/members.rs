@@ -0,0 +1,91 @@
+//! Membership of users in projects. Users only have access to the
+//! projects they're members of, except for those with the `project.all`
+//! permission (i.e. admins), who have access to all of them.
+
+use diesel::prelude::*;
+
+use super::permissions::{self, PROJECT_ALL};
+use super::schema::{project_members, projects, users as users_table};
+use super::users;
+use super::Conn;
+
+#[derive(Debug, Queryable)]
+pub struct Member {
+    pub id: i32,
+    pub username: String,
+    pub role_id: i32,
+}
+
+/// What the user has access to.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Access {
+    All,
+    Projects(Vec<i32>),
+}
+
+impl Access {
+    pub fn allows(&self, project_id: i32) -> bool {
+        match self {
+            Access::All => true,
+            Access::Projects(ids) => ids.contains(&project_id),
+        }
+    }
+}
+
+pub fn access(conn: &Conn, user_id: i32) -> QueryResult<Access> {
+    if permissions::for_user(conn, user_id)?
+        .iter()
+        .any(|p| p == PROJECT_ALL)
+    {
+        return Ok(Access::All);
+    }
+    project_members::table
+        .filter(project_members::user_id.eq(user_id))
+        .select(project_members::project_id)
+        .order(project_members::project_id)
+        .load(conn)
+        .map(Access::Projects)
+}
+
+pub fn for_project(conn: &Conn, project_id: i32) -> QueryResult<Vec<Member>> {
+    project_members::table
+        .inner_join(users_table::table)
+        .filter(project_members::project_id.eq(project_id))
+        .select((users_table::id, users_table::username, users_table::role_id))
+        .order(users_table::username)
+        .load(conn)
+}
+
+/// Whether the user wasn't a member already. Fails with `NotFound` if
+/// there's no such project or user.
+pub fn add(conn: &Conn, project_id: i32, user_id: i32) -> QueryResult<bool> {
+    conn.transaction(|| {
+        projects::table
+            .find(project_id)
+            .select(projects::id)
+            .first::<i32>(conn)?;
+        users::role(conn, user_id)?;
+        let member = project_members::table
+            .find((project_id, user_id))
+            .select(project_members::user_id)
+            .first::<i32>(conn)
+            .optional()?;
+        if member.is_some() {
+            return Ok(false);
+        }
+        diesel::insert_into(project_members::table)
+            .values((
+                project_members::project_id.eq(project_id),
+                project_members::user_id.eq(user_id),
+            ))
+            .execute(conn)
+            .map(|n| n > 0)
+    })
+}
+
+/// Whether the user was a member.
+pub fn remove(conn: &Conn, project_id: i32, user_id: i32) -> QueryResult<bool> {
+    diesel::delete(project_members::table.find((project_id, user_id)))
+        .execute(conn)
+        .map(|n| n > 0)
+}
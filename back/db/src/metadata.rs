@@ -0,0 +1,245 @@
+//! Cross-checking documents against their session metadata (CMDI or IMDI
+//! files, see `eaf::metadata`). What the DB doesn't know yet is filled in,
+//! i.e. speakers of the project are linked to the document; where the two
+//! disagree, neither is trusted and the discrepancy is recorded for someone
+//! to look into.
+
+use std::collections::HashMap;
+
+use chrono::NaiveDateTime;
+use diesel::dsl::sql;
+use diesel::prelude::*;
+use diesel::sql_types::Text;
+
+use super::schema::{
+    doc2speaker, docs, enum_genders, enum_places, enum_regions, metadata_discrepancies, speakers,
+};
+use super::Conn;
+
+/// IDs in `enum_genders`.
+pub const MALE: i32 = 1;
+pub const FEMALE: i32 = 2;
+
+#[derive(Debug, Default)]
+pub struct SessionSpeaker {
+    /// Names the speaker may go by, most specific first.
+    pub names: Vec<String>,
+    pub gender_id: Option<i32>,
+    pub year: Option<i32>,
+}
+
+/// What the metadata says about the recording.
+#[derive(Debug, Default)]
+pub struct Session {
+    /// As `YYYY-MM-DD`, possibly with the day or month missing.
+    pub date: Option<String>,
+    pub place: Option<String>,
+    pub region: Option<String>,
+    pub speakers: Vec<SessionSpeaker>,
+}
+
+#[derive(Debug, Queryable)]
+pub struct Discrepancy {
+    pub field: String,
+    /// Nickname of the speaker, for the gender and year fields.
+    pub speaker: Option<String>,
+    pub in_metadata: Option<String>,
+    pub in_db: Option<String>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Debug, Default)]
+pub struct CrossCheck {
+    /// Nicknames of the project's speakers newly linked to the document.
+    pub linked: Vec<String>,
+    pub discrepancies: usize,
+}
+
+#[derive(Insertable)]
+#[table_name = "metadata_discrepancies"]
+struct NewDiscrepancy<'a> {
+    doc_id: i32,
+    file_id: i32,
+    field: &'a str,
+    speaker: Option<&'a str>,
+    in_metadata: Option<String>,
+    in_db: Option<String>,
+}
+
+fn same(metadata: &str, db: &str) -> bool {
+    metadata.trim().to_lowercase() == db.trim().to_lowercase()
+}
+
+/// Dates in metadata may be partial, e.g. just the year and month.
+fn same_date(metadata: &str, db: &str) -> bool {
+    let metadata = metadata.trim();
+    !metadata.is_empty() && db.starts_with(metadata)
+}
+
+/// (ID, nickname, gender ID, year of birth)
+type SpeakerRow = (i32, String, i32, i32);
+
+/// Cross-check the document against the session metadata from the given
+/// file, replacing the discrepancies found before.
+pub fn cross_check(
+    conn: &Conn,
+    doc_id: i32,
+    file_id: i32,
+    session: &Session,
+) -> QueryResult<CrossCheck> {
+    conn.transaction(|| {
+        let (project_id, date, place, region) = docs::table
+            .find(doc_id)
+            .inner_join(enum_places::table.on(enum_places::id.eq(docs::place_id)))
+            .inner_join(enum_regions::table.on(enum_regions::id.eq(enum_places::region_id)))
+            // dates are stored without times too, which diesel wouldn't
+            // take for timestamps
+            .select((
+                docs::project_id,
+                sql::<Text>("docs.date"),
+                enum_places::label,
+                enum_regions::label,
+            ))
+            .first::<(i32, String, String, String)>(conn)?;
+        let genders: HashMap<i32, String> = enum_genders::table.load(conn)?.into_iter().collect();
+        let gender = |id: i32| genders.get(&id).cloned().unwrap_or_else(|| id.to_string());
+        let columns = (
+            speakers::id,
+            speakers::nickname,
+            speakers::gender_id,
+            speakers::year,
+        );
+        let mut linked: Vec<SpeakerRow> = doc2speaker::table
+            .inner_join(speakers::table)
+            .filter(doc2speaker::doc_id.eq(doc_id))
+            .select(columns)
+            .load(conn)?;
+        let candidates: Vec<SpeakerRow> = speakers::table
+            .filter(speakers::project_id.eq(project_id))
+            .select(columns)
+            .load(conn)?;
+
+        let mut found = vec![];
+        let mut add = |field, speaker: Option<&str>, in_metadata, in_db| {
+            found.push((field, speaker.map(str::to_owned), in_metadata, in_db));
+        };
+        if let Some(metadata) = &session.date {
+            if !same_date(metadata, &date) {
+                add("date", None, Some(metadata.clone()), Some(date.clone()));
+            }
+        }
+        for (field, metadata, db) in &[
+            ("place", &session.place, &place),
+            ("region", &session.region, &region),
+        ] {
+            match metadata {
+                Some(metadata) if !same(metadata, db) => {
+                    add(field, None, Some(metadata.clone()), Some(db.to_string()))
+                }
+                _ => {}
+            }
+        }
+
+        let mut check = CrossCheck::default();
+        let mut matched = vec![];
+        for speaker in &session.speakers {
+            let named = |row: &&SpeakerRow| speaker.names.iter().any(|n| same(n, &row.1));
+            let row = match linked.iter().find(named) {
+                Some(row) => row.clone(),
+                None => match candidates.iter().find(named) {
+                    Some(row) => {
+                        diesel::insert_into(doc2speaker::table)
+                            .values((
+                                doc2speaker::doc_id.eq(doc_id),
+                                doc2speaker::speaker_id.eq(row.0),
+                            ))
+                            .execute(conn)?;
+                        check.linked.push(row.1.clone());
+                        linked.push(row.clone());
+                        row.clone()
+                    }
+                    None => {
+                        add("speaker", None, speaker.names.first().cloned(), None);
+                        continue;
+                    }
+                },
+            };
+            let (id, nickname, gender_id, year) = row;
+            matched.push(id);
+            match speaker.gender_id {
+                Some(metadata) if metadata != gender_id => add(
+                    "gender",
+                    Some(&nickname),
+                    Some(gender(metadata)),
+                    Some(gender(gender_id)),
+                ),
+                _ => {}
+            }
+            match speaker.year {
+                Some(metadata) if metadata != year => add(
+                    "year",
+                    Some(&nickname),
+                    Some(metadata.to_string()),
+                    Some(year.to_string()),
+                ),
+                _ => {}
+            }
+        }
+        for (id, nickname, _, _) in &linked {
+            if !matched.contains(id) {
+                add("speaker", None, None, Some(nickname.clone()));
+            }
+        }
+
+        diesel::delete(
+            metadata_discrepancies::table.filter(metadata_discrepancies::doc_id.eq(doc_id)),
+        )
+        .execute(conn)?;
+        let rows: Vec<_> = found
+            .iter()
+            .map(|(field, speaker, in_metadata, in_db)| NewDiscrepancy {
+                doc_id,
+                file_id,
+                field,
+                speaker: speaker.as_deref(),
+                in_metadata: in_metadata.clone(),
+                in_db: in_db.clone(),
+            })
+            .collect();
+        diesel::insert_into(metadata_discrepancies::table)
+            .values(&rows)
+            .execute(conn)?;
+        check.discrepancies = rows.len();
+        Ok(check)
+    })
+}
+
+/// Discrepancies found by the latest cross-check of the document.
+pub fn discrepancies(conn: &Conn, doc_id: i32) -> QueryResult<Vec<Discrepancy>> {
+    metadata_discrepancies::table
+        .filter(metadata_discrepancies::doc_id.eq(doc_id))
+        .select((
+            metadata_discrepancies::field,
+            metadata_discrepancies::speaker,
+            metadata_discrepancies::in_metadata,
+            metadata_discrepancies::in_db,
+            metadata_discrepancies::created_at,
+        ))
+        .order(metadata_discrepancies::id)
+        .load(conn)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_same() {
+        assert!(same("Praha ", "praha"));
+        assert!(!same("Praha", "Brno"));
+        assert!(same_date("2019-03", "2019-03-01 00:00:00"));
+        assert!(same_date("2019-03-01", "2019-03-01"));
+        assert!(!same_date("2019-03-02", "2019-03-01"));
+        assert!(!same_date("", "2019-03-01"));
+    }
+}
@@ -0,0 +1,35 @@
+//! The schema, as migrations embedded in the binaries, so that setting up a
+//! deployment's database needs neither the sources nor diesel's CLI. Each
+//! backend has its own: SQLite's in `migrations`, Postgres' in
+//! `migrations_postgres`.
+
+use std::io;
+use std::path::Path;
+
+use diesel_migrations::{self, RunMigrationsError};
+
+use super::Conn;
+
+#[cfg(feature = "sqlite")]
+embed_migrations!("migrations");
+#[cfg(feature = "postgres")]
+embed_migrations!("migrations_postgres");
+
+/// Where the migrations are in the source tree. Only their up scripts are
+/// embedded, so rolling back needs them.
+#[cfg(feature = "sqlite")]
+pub const DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/migrations");
+#[cfg(feature = "postgres")]
+pub const DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/migrations_postgres");
+
+/// Apply the migrations which haven't been applied yet, listing them in
+/// `out`.
+pub fn run(conn: &Conn, out: &mut impl io::Write) -> Result<(), RunMigrationsError> {
+    embedded_migrations::run_with_output(conn, out)
+}
+
+/// Revert the latest applied migration, using its down script in `dir`.
+/// Returns its version.
+pub fn revert_latest(conn: &Conn, dir: &Path) -> Result<String, RunMigrationsError> {
+    diesel_migrations::revert_latest_migration_in_directory(conn, dir)
+}
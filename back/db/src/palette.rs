@@ -0,0 +1,87 @@
+//! Per-project special characters and attribute codes. The same entries
+//! drive the frontend's insert-character palette and the parser's notion of
+//! which atoms and after-angle codes are allowed.
+
+use diesel::prelude::*;
+
+use super::schema::palette_entries;
+use super::Conn;
+
+pub const CHAR: &str = "char";
+pub const ATTR: &str = "attr";
+
+#[derive(Debug, Queryable)]
+pub struct Entry {
+    /// `CHAR` or `ATTR`.
+    pub kind: String,
+    pub value: String,
+    pub description: String,
+    pub shortcut: Option<String>,
+    /// Only attribute codes can be deprecated.
+    pub deprecated: bool,
+}
+
+/// Entries in palette order, characters first.
+pub fn for_project(conn: &Conn, project_id: i32) -> QueryResult<Vec<Entry>> {
+    palette_entries::table
+        .filter(palette_entries::project_id.eq(project_id))
+        // 'attr' < 'char', hence desc
+        .order((palette_entries::kind.desc(), palette_entries::position))
+        .select((
+            palette_entries::kind,
+            palette_entries::value,
+            palette_entries::description,
+            palette_entries::shortcut,
+            palette_entries::deprecated,
+        ))
+        .load(conn)
+}
+
+/// Replace the project's palette; positions are given by the order of the
+/// entries.
+pub fn replace(conn: &Conn, project_id: i32, entries: &[Entry]) -> QueryResult<()> {
+    conn.transaction(|| {
+        diesel::delete(palette_entries::table.filter(palette_entries::project_id.eq(project_id)))
+            .execute(conn)?;
+        for (i, entry) in entries.iter().enumerate() {
+            diesel::insert_into(palette_entries::table)
+                .values((
+                    palette_entries::project_id.eq(project_id),
+                    palette_entries::kind.eq(&entry.kind),
+                    palette_entries::position.eq(i as i32 + 1),
+                    palette_entries::value.eq(&entry.value),
+                    palette_entries::description.eq(&entry.description),
+                    palette_entries::shortcut.eq(&entry.shortcut),
+                    palette_entries::deprecated.eq(entry.deprecated),
+                ))
+                .execute(conn)?;
+        }
+        Ok(())
+    })
+}
+
+fn values(conn: &Conn, project_id: i32, kind: &str, deprecated: bool) -> QueryResult<Vec<String>> {
+    palette_entries::table
+        .filter(palette_entries::project_id.eq(project_id))
+        .filter(palette_entries::kind.eq(kind))
+        .filter(palette_entries::deprecated.eq(deprecated))
+        .order(palette_entries::position)
+        .select(palette_entries::value)
+        .load(conn)
+}
+
+/// Special characters, to be allowed as parser atoms.
+pub fn chars(conn: &Conn, project_id: i32) -> QueryResult<Vec<String>> {
+    values(conn, project_id, CHAR, false)
+}
+
+/// Attribute codes, to be allowed after `<`.
+pub fn attr_codes(conn: &Conn, project_id: i32) -> QueryResult<Vec<String>> {
+    values(conn, project_id, ATTR, false)
+}
+
+/// Attribute codes which are still recognized after `<`, but reported as
+/// warnings.
+pub fn deprecated_attr_codes(conn: &Conn, project_id: i32) -> QueryResult<Vec<String>> {
+    values(conn, project_id, ATTR, true)
+}
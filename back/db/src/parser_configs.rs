@@ -0,0 +1,148 @@
+//! Per-project parser patterns beyond what the palette allows: whole tokens
+//! to allow or disallow, and atoms and after-angle codes too complex for
+//! palette entries, and the codes of spans to anonymize in exports. They're
+//! regexes, stored as JSON arrays. Also the keys of key=value attributes,
+//! with regexes for their values, stored as a JSON object, and whether
+//! irregular whitespace is reported.
+
+use std::collections::BTreeMap;
+
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use diesel::result::Error;
+use eaf::config::ConfigFile;
+
+use super::palette;
+use super::schema::parser_configs;
+use super::Conn;
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Patterns {
+    pub whitelist: Vec<String>,
+    pub blacklist: Vec<String>,
+    pub atoms: Vec<String>,
+    pub after_angle: Vec<String>,
+    pub anonymize: Vec<String>,
+    pub attr_values: BTreeMap<String, String>,
+    pub report_whitespace: bool,
+}
+
+#[derive(Debug)]
+pub struct ParserConfig {
+    pub patterns: Patterns,
+    pub updated_by: Option<i32>,
+    pub updated_at: NaiveDateTime,
+}
+
+fn decode<T: serde::de::DeserializeOwned>(json: &str) -> QueryResult<T> {
+    serde_json::from_str(json).map_err(|e| Error::DeserializationError(Box::new(e)))
+}
+
+fn encode<T: serde::Serialize + ?Sized>(patterns: &T) -> String {
+    serde_json::to_string(patterns).expect("strings serialize")
+}
+
+/// The project's patterns, if it has any.
+pub fn for_project(conn: &Conn, project_id: i32) -> QueryResult<Option<ParserConfig>> {
+    let row = parser_configs::table
+        .find(project_id)
+        .select((
+            parser_configs::whitelist,
+            parser_configs::blacklist,
+            parser_configs::atoms,
+            parser_configs::after_angle,
+            parser_configs::anonymize,
+            parser_configs::attr_values,
+            parser_configs::report_whitespace,
+            parser_configs::updated_by,
+            parser_configs::updated_at,
+        ))
+        .first::<(
+            String,
+            String,
+            String,
+            String,
+            String,
+            String,
+            bool,
+            Option<i32>,
+            NaiveDateTime,
+        )>(conn)
+        .optional()?;
+    row.map(
+        |(
+            whitelist,
+            blacklist,
+            atoms,
+            after_angle,
+            anonymize,
+            attr_values,
+            report_whitespace,
+            updated_by,
+            updated_at,
+        )| {
+            Ok(ParserConfig {
+                patterns: Patterns {
+                    whitelist: decode(&whitelist)?,
+                    blacklist: decode(&blacklist)?,
+                    atoms: decode(&atoms)?,
+                    after_angle: decode(&after_angle)?,
+                    anonymize: decode(&anonymize)?,
+                    attr_values: decode(&attr_values)?,
+                    report_whitespace,
+                },
+                updated_by,
+                updated_at,
+            })
+        },
+    )
+    .transpose()
+}
+
+/// Replace the project's patterns.
+pub fn set(conn: &Conn, project_id: i32, patterns: &Patterns, user_id: i32) -> QueryResult<()> {
+    conn.transaction(|| {
+        diesel::delete(parser_configs::table.find(project_id)).execute(conn)?;
+        diesel::insert_into(parser_configs::table)
+            .values((
+                parser_configs::project_id.eq(project_id),
+                parser_configs::whitelist.eq(encode(&patterns.whitelist)),
+                parser_configs::blacklist.eq(encode(&patterns.blacklist)),
+                parser_configs::atoms.eq(encode(&patterns.atoms)),
+                parser_configs::after_angle.eq(encode(&patterns.after_angle)),
+                parser_configs::anonymize.eq(encode(&patterns.anonymize)),
+                parser_configs::attr_values.eq(encode(&patterns.attr_values)),
+                parser_configs::report_whitespace.eq(patterns.report_whitespace),
+                parser_configs::updated_by.eq(user_id),
+                parser_configs::updated_at.eq(diesel::dsl::now),
+            ))
+            .execute(conn)
+            .map(drop)
+    })
+}
+
+/// What the project's parser config is built from: its palette of special
+/// characters and attribute codes, plus its patterns.
+pub fn config_file(conn: &Conn, project_id: i32) -> QueryResult<ConfigFile> {
+    // the config holds regexes, the palette literal strings
+    let escaped =
+        |codes: Vec<String>| -> Vec<String> { codes.iter().map(|c| regex::escape(c)).collect() };
+    let patterns = for_project(conn, project_id)?
+        .map(|c| c.patterns)
+        .unwrap_or_default();
+    let mut atoms = escaped(palette::chars(conn, project_id)?);
+    atoms.extend(patterns.atoms);
+    let mut after_angle = escaped(palette::attr_codes(conn, project_id)?);
+    after_angle.extend(patterns.after_angle);
+    Ok(ConfigFile {
+        whitelist: patterns.whitelist,
+        blacklist: patterns.blacklist,
+        atoms,
+        after_angle,
+        deprecated_attrs: escaped(palette::deprecated_attr_codes(conn, project_id)?),
+        anonymize: patterns.anonymize,
+        attr_values: patterns.attr_values,
+        report_whitespace: Some(patterns.report_whitespace),
+        ..ConfigFile::default()
+    })
+}
@@ -0,0 +1,96 @@
+//! Typo-tolerant lookup of users and speakers, for pickers in the UI.
+
+use diesel::prelude::*;
+
+use super::fuzzy::{self, Match};
+use super::members::Access;
+use super::schema::{project_members, projects, speakers, users};
+use super::Conn;
+
+#[derive(Debug, Queryable)]
+pub struct UserHit {
+    pub id: i32,
+    pub username: String,
+    pub badge: Option<String>,
+    pub role_id: i32,
+}
+
+#[derive(Debug, Queryable)]
+pub struct SpeakerHit {
+    pub id: i32,
+    pub nickname: String,
+    pub project: String,
+    pub year: i32,
+}
+
+/// Best match of the query against any of the given fields.
+fn best<'a, I: IntoIterator<Item = &'a str>>(query: &str, fields: I) -> Option<Match> {
+    fields
+        .into_iter()
+        .filter_map(|f| fuzzy::score(query, f))
+        .min()
+}
+
+fn take_best<T>(mut hits: Vec<(Match, T)>, key: impl Fn(&T) -> &str, limit: usize) -> Vec<T> {
+    hits.sort_by(|(m1, h1), (m2, h2)| m1.cmp(m2).then_with(|| key(h1).cmp(key(h2))));
+    hits.into_iter().take(limit).map(|(_, h)| h).collect()
+}
+
+/// Search users by username or badge, among members of the projects the
+/// searcher has access to.
+pub fn search_users(
+    conn: &Conn,
+    query: &str,
+    access: &Access,
+    limit: usize,
+) -> QueryResult<Vec<UserHit>> {
+    let query = fuzzy::normalize(query.trim());
+    let mut candidates = users::table
+        .select((users::id, users::username, users::badge, users::role_id))
+        .into_boxed();
+    if let Access::Projects(project_ids) = access {
+        let members = project_members::table
+            .filter(project_members::project_id.eq_any(project_ids.clone()))
+            .select(project_members::user_id);
+        candidates = candidates.filter(users::id.eq_any(members));
+    }
+    let hits = candidates
+        .load::<UserHit>(conn)?
+        .into_iter()
+        .filter_map(|u| {
+            let fields = Some(u.username.as_str())
+                .into_iter()
+                .chain(u.badge.as_deref());
+            best(&query, fields).map(|m| (m, u))
+        })
+        .collect();
+    Ok(take_best(hits, |u| &u.username, limit))
+}
+
+/// Search speakers by nickname, optionally only within a project.
+pub fn search_speakers(
+    conn: &Conn,
+    query: &str,
+    project_id: Option<i32>,
+    limit: usize,
+) -> QueryResult<Vec<SpeakerHit>> {
+    let query = fuzzy::normalize(query.trim());
+    let mut candidates = speakers::table
+        .inner_join(projects::table)
+        .select((
+            speakers::id,
+            speakers::nickname,
+            projects::label,
+            speakers::year,
+        ))
+        .into_boxed();
+    if let Some(project_id) = project_id {
+        candidates = candidates.filter(speakers::project_id.eq(project_id));
+    }
+    let hits = candidates
+        .load::<SpeakerHit>(conn)?
+        .into_iter()
+        .filter_map(|s| fuzzy::score(&query, &s.nickname).map(|m| (m, s)))
+        .collect();
+    Ok(take_best(hits, |s| &s.nickname, limit))
+}
@@ -0,0 +1,185 @@
+//! What users may do, as granted to their roles. Permissions are rows in
+//! `enum_permissions`, the labels below are the ones the code checks for.
+
+use diesel::prelude::*;
+
+use super::schema::{enum_permissions, enum_roles, role_permissions, users};
+use super::Conn;
+
+pub const PROJECT_ALL: &str = "project.all";
+pub const MEMBER_EDIT: &str = "member.edit";
+pub const ROLE_EDIT: &str = "role.edit";
+pub const USER_ACT_FOR: &str = "user.act_for";
+pub const DOC_ALL: &str = "doc.all";
+pub const DOC_ASSIGN: &str = "doc.assign";
+pub const DOC_REVIEW: &str = "doc.review";
+pub const DOC_EDIT: &str = "doc.edit";
+pub const SPEAKER_EDIT: &str = "speaker.edit";
+pub const CONFIG_EDIT: &str = "config.edit";
+pub const WEBHOOK_EDIT: &str = "webhook.edit";
+pub const GEO_EDIT: &str = "geo.edit";
+pub const EXPORT_BUNDLE: &str = "export.bundle";
+pub const EXPORT_RELEASE: &str = "export.release";
+pub const BACKUP_MANAGE: &str = "backup.manage";
+pub const MAINTENANCE_RUN: &str = "maintenance.run";
+pub const SESSION_REVOKE: &str = "session.revoke";
+pub const ACCOUNT_TWO_FACTOR: &str = "account.two_factor";
+pub const AUDIT_VIEW: &str = "audit.view";
+
+#[derive(Debug, Queryable)]
+pub struct Permission {
+    pub id: i32,
+    pub label: String,
+    pub description: String,
+}
+
+#[derive(Debug)]
+pub struct Role {
+    pub id: i32,
+    pub label: String,
+    pub permissions: Vec<String>,
+}
+
+pub fn all(conn: &Conn) -> QueryResult<Vec<Permission>> {
+    enum_permissions::table
+        .order(enum_permissions::label)
+        .load(conn)
+}
+
+/// Labels of the user's permissions. Fails with `NotFound` if there's no
+/// such user.
+pub fn for_user(conn: &Conn, user_id: i32) -> QueryResult<Vec<String>> {
+    let role_id = super::users::role(conn, user_id)?;
+    role_permissions::table
+        .inner_join(enum_permissions::table)
+        .filter(role_permissions::role_id.eq(role_id))
+        .select(enum_permissions::label)
+        .order(enum_permissions::label)
+        .load(conn)
+}
+
+/// Users with the permission, e.g. to notify them.
+pub fn holders(conn: &Conn, permission: &str) -> QueryResult<Vec<i32>> {
+    users::table
+        .inner_join(role_permissions::table.on(role_permissions::role_id.eq(users::role_id)))
+        .inner_join(
+            enum_permissions::table.on(enum_permissions::id.eq(role_permissions::permission_id)),
+        )
+        .filter(enum_permissions::label.eq(permission))
+        .select(users::id)
+        .load(conn)
+}
+
+pub fn roles(conn: &Conn) -> QueryResult<Vec<Role>> {
+    let grants = role_permissions::table
+        .inner_join(enum_permissions::table)
+        .select((role_permissions::role_id, enum_permissions::label))
+        .order(enum_permissions::label)
+        .load::<(i32, String)>(conn)?;
+    Ok(enum_roles::table
+        .select((enum_roles::id, enum_roles::label))
+        .order(enum_roles::id)
+        .load::<(i32, String)>(conn)?
+        .into_iter()
+        .map(|(id, label)| Role {
+            id,
+            label,
+            permissions: grants
+                .iter()
+                .filter(|(role_id, _)| *role_id == id)
+                .map(|(_, permission)| permission.clone())
+                .collect(),
+        })
+        .collect())
+}
+
+#[derive(Debug)]
+pub enum RoleError {
+    UnknownPermission(String),
+    DuplicateRole(String),
+    Db(diesel::result::Error),
+}
+
+impl std::fmt::Display for RoleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            RoleError::UnknownPermission(label) => write!(f, "unknown permission {:?}", label),
+            RoleError::DuplicateRole(label) => write!(f, "role {:?} already exists", label),
+            RoleError::Db(e) => e.fmt(f),
+        }
+    }
+}
+
+impl From<diesel::result::Error> for RoleError {
+    fn from(e: diesel::result::Error) -> Self {
+        RoleError::Db(e)
+    }
+}
+
+fn permission_ids(conn: &Conn, labels: &[String]) -> Result<Vec<i32>, RoleError> {
+    let known = enum_permissions::table
+        .select((enum_permissions::id, enum_permissions::label))
+        .load::<(i32, String)>(conn)?;
+    labels
+        .iter()
+        .map(|label| {
+            known
+                .iter()
+                .find(|(_, l)| l == label)
+                .map(|(id, _)| *id)
+                .ok_or_else(|| RoleError::UnknownPermission(label.clone()))
+        })
+        .collect()
+}
+
+/// Replace the role's permissions. Fails with `NotFound` if there's no such
+/// role.
+pub fn set_permissions(conn: &Conn, role_id: i32, labels: &[String]) -> Result<(), RoleError> {
+    conn.transaction(|| {
+        enum_roles::table
+            .find(role_id)
+            .select(enum_roles::id)
+            .first::<i32>(conn)?;
+        let mut ids = permission_ids(conn, labels)?;
+        ids.sort_unstable();
+        ids.dedup();
+        diesel::delete(role_permissions::table.filter(role_permissions::role_id.eq(role_id)))
+            .execute(conn)?;
+        let rows: Vec<_> = ids
+            .into_iter()
+            .map(|id| {
+                (
+                    role_permissions::role_id.eq(role_id),
+                    role_permissions::permission_id.eq(id),
+                )
+            })
+            .collect();
+        diesel::insert_into(role_permissions::table)
+            .values(&rows)
+            .execute(conn)?;
+        Ok(())
+    })
+}
+
+/// Returns the new role's ID.
+pub fn add_role(conn: &Conn, label: &str, permissions: &[String]) -> Result<i32, RoleError> {
+    conn.transaction(|| {
+        let exists = enum_roles::table
+            .filter(enum_roles::label.eq(label))
+            .select(enum_roles::id)
+            .first::<i32>(conn)
+            .optional()?;
+        if exists.is_some() {
+            return Err(RoleError::DuplicateRole(label.to_owned()));
+        }
+        diesel::insert_into(enum_roles::table)
+            .values(enum_roles::label.eq(label))
+            .execute(conn)?;
+        let id = enum_roles::table
+            .select(enum_roles::id)
+            .order(enum_roles::id.desc())
+            .first(conn)?;
+        set_permissions(conn, id, permissions)?;
+        Ok(id)
+    })
+}
@@ -0,0 +1,27 @@
+//! A pool of connections for long-running servers, so that requests don't
+//! each pay for opening the database. Pooled connections are set up the same
+//! way as those from `connect`.
+
+use diesel::r2d2::{self, ConnectionManager, CustomizeConnection, PoolError};
+
+use super::Conn;
+
+pub type Pool = r2d2::Pool<ConnectionManager<Conn>>;
+pub type PooledConn = r2d2::PooledConnection<ConnectionManager<Conn>>;
+
+#[derive(Debug)]
+struct Configure;
+
+impl CustomizeConnection<Conn, r2d2::Error> for Configure {
+    fn on_acquire(&self, conn: &mut Conn) -> Result<(), r2d2::Error> {
+        super::configure(conn).map_err(r2d2::Error::QueryError)
+    }
+}
+
+/// A pool of at most `size` connections to the database.
+pub fn new(database_url: &str, size: u32) -> Result<Pool, PoolError> {
+    r2d2::Pool::builder()
+        .max_size(size)
+        .connection_customizer(Box::new(Configure))
+        .build(ConnectionManager::new(database_url))
+}
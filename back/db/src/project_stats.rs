@@ -0,0 +1,245 @@
+//! Aggregate numbers about a project for its dashboard: how far along its
+//! documents are, how many words have been transcribed (see
+//! `speakers::set_words`) and how many mistakes are left, overall and per
+//! region of the documents' places.
+
+use std::collections::{BTreeMap, HashMap};
+
+use diesel::prelude::*;
+
+use super::schema::{doc2speaker, docs, enum_places, enum_regions, speakers, users};
+use super::validation;
+use super::Conn;
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Totals {
+    pub docs: i64,
+    pub done: i64,
+    pub assigned: i64,
+    pub words: i64,
+    /// Found by the latest validations, not counting acknowledged ones.
+    pub mistakes: i64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RegionTotals {
+    pub region_id: i32,
+    pub region: String,
+    pub totals: Totals,
+}
+
+/// Words transcribed of a speaker, or by a transcriber.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Words {
+    pub id: i32,
+    pub name: String,
+    pub words: i64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProjectStats {
+    pub totals: Totals,
+    /// Ordered by label.
+    pub regions: Vec<RegionTotals>,
+    /// Ordered by nickname.
+    pub speakers: Vec<Words>,
+    /// Everyone with documents assigned, ordered by username.
+    pub transcribers: Vec<Words>,
+}
+
+#[derive(Debug, Clone)]
+struct DocRow {
+    id: i32,
+    assigned_to_id: Option<i32>,
+    done: bool,
+    /// ID and label.
+    region: (i32, String),
+}
+
+#[derive(Debug, Clone)]
+struct LinkRow {
+    doc_id: i32,
+    speaker_id: i32,
+    nickname: String,
+    words: Option<i32>,
+}
+
+fn words_of(totals: BTreeMap<(String, i32), i64>) -> Vec<Words> {
+    totals
+        .into_iter()
+        .map(|((name, id), words)| Words { id, name, words })
+        .collect()
+}
+
+fn aggregate(
+    docs: &[DocRow],
+    links: &[LinkRow],
+    mistakes: &HashMap<i32, i32>,
+    usernames: &HashMap<i32, String>,
+) -> ProjectStats {
+    let mut doc_words: HashMap<i32, i64> = HashMap::new();
+    let mut speakers = BTreeMap::new();
+    for link in links {
+        let words = i64::from(link.words.unwrap_or(0));
+        *doc_words.entry(link.doc_id).or_default() += words;
+        *speakers
+            .entry((link.nickname.clone(), link.speaker_id))
+            .or_default() += words;
+    }
+
+    let mut totals = Totals::default();
+    let mut regions: BTreeMap<(String, i32), Totals> = BTreeMap::new();
+    let mut transcribers = BTreeMap::new();
+    for doc in docs {
+        let words = doc_words.get(&doc.id).copied().unwrap_or(0);
+        let (region_id, region) = &doc.region;
+        for totals in [
+            &mut totals,
+            regions.entry((region.clone(), *region_id)).or_default(),
+        ] {
+            totals.docs += 1;
+            totals.done += i64::from(doc.done);
+            totals.assigned += i64::from(doc.assigned_to_id.is_some());
+            totals.words += words;
+            totals.mistakes += i64::from(mistakes.get(&doc.id).copied().unwrap_or(0));
+        }
+        if let Some(user_id) = doc.assigned_to_id {
+            let username = usernames.get(&user_id).cloned().unwrap_or_default();
+            *transcribers.entry((username, user_id)).or_default() += words;
+        }
+    }
+
+    ProjectStats {
+        totals,
+        regions: regions
+            .into_iter()
+            .map(|((region, region_id), totals)| RegionTotals {
+                region_id,
+                region,
+                totals,
+            })
+            .collect(),
+        speakers: words_of(speakers),
+        transcribers: words_of(transcribers),
+    }
+}
+
+/// The project's numbers as they stand.
+pub fn for_project(conn: &Conn, project_id: i32) -> QueryResult<ProjectStats> {
+    let regions: HashMap<i32, (i32, String)> = enum_places::table
+        .inner_join(enum_regions::table)
+        .select((enum_places::id, enum_regions::id, enum_regions::label))
+        .load::<(i32, i32, String)>(conn)?
+        .into_iter()
+        .map(|(place_id, region_id, region)| (place_id, (region_id, region)))
+        .collect();
+    let docs: Vec<_> = docs::table
+        .filter(docs::project_id.eq(project_id))
+        .select((docs::id, docs::assigned_to_id, docs::done, docs::place_id))
+        .load::<(i32, Option<i32>, Option<bool>, i32)>(conn)?
+        .into_iter()
+        .map(|(id, assigned_to_id, done, place_id)| DocRow {
+            id,
+            assigned_to_id,
+            done: done.unwrap_or(false),
+            region: regions.get(&place_id).cloned().unwrap_or_default(),
+        })
+        .collect();
+    let links: Vec<_> = doc2speaker::table
+        .inner_join(docs::table)
+        .inner_join(speakers::table)
+        .filter(docs::project_id.eq(project_id))
+        .select((
+            doc2speaker::doc_id,
+            doc2speaker::speaker_id,
+            speakers::nickname,
+            doc2speaker::words,
+        ))
+        .load::<(i32, i32, String, Option<i32>)>(conn)?
+        .into_iter()
+        .map(|(doc_id, speaker_id, nickname, words)| LinkRow {
+            doc_id,
+            speaker_id,
+            nickname,
+            words,
+        })
+        .collect();
+    let mistakes = validation::outstanding(conn, project_id)?;
+    let user_ids: Vec<_> = docs.iter().filter_map(|d| d.assigned_to_id).collect();
+    let usernames = users::table
+        .filter(users::id.eq_any(user_ids))
+        .select((users::id, users::username))
+        .load::<(i32, String)>(conn)?
+        .into_iter()
+        .collect();
+    Ok(aggregate(&docs, &links, &mistakes, &usernames))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn doc(id: i32, assigned_to_id: Option<i32>, done: bool, region_id: i32) -> DocRow {
+        let region = if region_id == 1 { "Čechy" } else { "Morava" };
+        DocRow {
+            id,
+            assigned_to_id,
+            done,
+            region: (region_id, region.to_owned()),
+        }
+    }
+
+    fn link(doc_id: i32, speaker_id: i32, words: Option<i32>) -> LinkRow {
+        LinkRow {
+            doc_id,
+            speaker_id,
+            nickname: format!("speaker {}", speaker_id),
+            words,
+        }
+    }
+
+    #[test]
+    fn test_aggregate() {
+        let docs = [
+            doc(1, Some(7), true, 2),
+            doc(2, Some(7), false, 1),
+            doc(3, None, false, 2),
+        ];
+        let links = [
+            link(1, 10, Some(100)),
+            link(1, 11, Some(50)),
+            link(2, 10, None),
+            link(3, 11, Some(5)),
+        ];
+        let mistakes = vec![(2, 3), (3, 1)].into_iter().collect();
+        let usernames = vec![(7, "jana".to_owned())].into_iter().collect();
+        let stats = aggregate(&docs, &links, &mistakes, &usernames);
+
+        assert_eq!(
+            stats.totals,
+            Totals {
+                docs: 3,
+                done: 1,
+                assigned: 2,
+                words: 155,
+                mistakes: 4,
+            }
+        );
+        let regions: Vec<_> = stats
+            .regions
+            .iter()
+            .map(|r| (r.region.as_str(), r.totals.docs, r.totals.words))
+            .collect();
+        assert_eq!(regions, [("Morava", 2, 155), ("Čechy", 1, 0)]);
+        let speakers: Vec<_> = stats.speakers.iter().map(|s| (s.id, s.words)).collect();
+        assert_eq!(speakers, [(10, 100), (11, 55)]);
+        assert_eq!(
+            stats.transcribers,
+            [Words {
+                id: 7,
+                name: "jana".to_owned(),
+                words: 150,
+            }]
+        );
+    }
+}
@@ -0,0 +1,172 @@
+//! Supervisor verdicts on submitted documents.
+
+use std::fmt;
+
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+
+use super::docs::DocState;
+use super::schema::{docs, enum_return_reasons, reviews, users, validation_runs};
+use super::Conn;
+
+#[derive(Debug)]
+pub enum ReviewError {
+    Db(diesel::result::Error),
+    /// Only submitted documents can be reviewed.
+    NotSubmitted(DocState),
+    /// Returned documents need a reason.
+    MissingReason,
+    UnknownReason(String),
+}
+
+impl fmt::Display for ReviewError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReviewError::Db(e) => e.fmt(f),
+            ReviewError::NotSubmitted(state) => {
+                write!(
+                    f,
+                    "document is {}, only submitted documents can be reviewed",
+                    state
+                )
+            }
+            ReviewError::MissingReason => f.write_str("returned documents need a reason"),
+            ReviewError::UnknownReason(reason) => write!(f, "unknown return reason {:?}", reason),
+        }
+    }
+}
+
+impl From<diesel::result::Error> for ReviewError {
+    fn from(e: diesel::result::Error) -> Self {
+        ReviewError::Db(e)
+    }
+}
+
+#[derive(Debug)]
+pub struct Verdict {
+    pub reviewer_id: i32,
+    pub accepted: bool,
+    /// Label from `enum_return_reasons`.
+    pub reason: Option<String>,
+    pub notes: String,
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "reviews"]
+struct NewReview {
+    doc_id: i32,
+    reviewer_id: i32,
+    validation_run_id: Option<i32>,
+    accepted: bool,
+    reason_id: Option<i32>,
+    notes: String,
+}
+
+#[derive(Debug, Queryable)]
+pub struct Review {
+    pub id: i32,
+    pub reviewer: String,
+    pub validation_run_id: Option<i32>,
+    pub accepted: bool,
+    pub reason: Option<String>,
+    pub notes: String,
+    pub created_at: NaiveDateTime,
+}
+
+/// Record a verdict on a submitted document and move it to the accepted or
+/// returned state accordingly. The review is linked to the document's latest
+/// validation run.
+pub fn record(conn: &Conn, doc_id: i32, verdict: Verdict) -> Result<i32, ReviewError> {
+    conn.transaction(|| {
+        let state_id = docs::table
+            .find(doc_id)
+            .select(docs::state_id)
+            .first::<i32>(conn)?;
+        if state_id != DocState::Submitted.id() {
+            let state = DocState::from_id(state_id).unwrap_or(DocState::New);
+            return Err(ReviewError::NotSubmitted(state));
+        }
+
+        let reason_id = match &verdict.reason {
+            Some(reason) => Some(
+                enum_return_reasons::table
+                    .filter(enum_return_reasons::label.eq(reason))
+                    .select(enum_return_reasons::id)
+                    .first::<i32>(conn)
+                    .optional()?
+                    .ok_or_else(|| ReviewError::UnknownReason(reason.clone()))?,
+            ),
+            None if !verdict.accepted => return Err(ReviewError::MissingReason),
+            None => None,
+        };
+        let validation_run_id = validation_runs::table
+            .filter(validation_runs::doc_id.eq(doc_id))
+            .order(validation_runs::created_at.desc())
+            .select(validation_runs::id)
+            .first::<i32>(conn)
+            .optional()?;
+
+        diesel::insert_into(reviews::table)
+            .values(&NewReview {
+                doc_id,
+                reviewer_id: verdict.reviewer_id,
+                validation_run_id,
+                accepted: verdict.accepted,
+                reason_id,
+                notes: verdict.notes,
+            })
+            .execute(conn)?;
+        let (state, done) = if verdict.accepted {
+            (DocState::Accepted, true)
+        } else {
+            (DocState::Returned, false)
+        };
+        diesel::update(docs::table.find(doc_id))
+            .set((docs::state_id.eq(state.id()), docs::done.eq(done)))
+            .execute(conn)?;
+
+        Ok(reviews::table
+            .select(reviews::id)
+            .order(reviews::id.desc())
+            .first(conn)?)
+    })
+}
+
+/// All reviews of a document, oldest first.
+pub fn for_doc(conn: &Conn, doc_id: i32) -> QueryResult<Vec<Review>> {
+    reviews::table
+        .inner_join(users::table)
+        .left_join(enum_return_reasons::table)
+        .filter(reviews::doc_id.eq(doc_id))
+        .select((
+            reviews::id,
+            users::username,
+            reviews::validation_run_id,
+            reviews::accepted,
+            enum_return_reasons::label.nullable(),
+            reviews::notes,
+            reviews::created_at,
+        ))
+        .order(reviews::created_at)
+        .load(conn)
+}
+
+/// How many times documents in the project were returned for each reason.
+pub fn return_reasons(conn: &Conn, project_id: i32) -> QueryResult<Vec<(String, i64)>> {
+    let returned = reviews::table
+        .inner_join(docs::table)
+        .inner_join(enum_return_reasons::table)
+        .filter(docs::project_id.eq(project_id))
+        .select(enum_return_reasons::id)
+        .load::<i32>(conn)?;
+    let reasons = enum_return_reasons::table
+        .order(enum_return_reasons::id)
+        .load::<(i32, String)>(conn)?;
+    Ok(reasons
+        .into_iter()
+        .map(|(id, label)| {
+            let count = returned.iter().filter(|&&r| r == id).count() as i64;
+            (label, count)
+        })
+        .collect())
+}
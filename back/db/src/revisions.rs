@@ -0,0 +1,79 @@
+//! The history of documents' transcripts: every version stored gets the
+//! next revision number of its document, see `add`.
+
+use chrono::NaiveDateTime;
+use diesel::dsl::max;
+use diesel::prelude::*;
+
+use super::schema::{document_revisions, files, users};
+use super::Conn;
+
+#[derive(Debug, Queryable)]
+pub struct Revision {
+    pub id: i32,
+    pub doc_id: i32,
+    /// From 1, per document.
+    pub number: i32,
+    pub file_id: i32,
+    /// Of the file, relative to the storage directory.
+    pub path: String,
+    pub author_id: Option<i32>,
+    pub author: Option<String>,
+    pub created_at: NaiveDateTime,
+}
+
+/// Record the stored transcript as the document's newest revision. Returns
+/// the revision's number.
+pub fn add(conn: &Conn, doc_id: i32, file_id: i32, author_id: Option<i32>) -> QueryResult<i32> {
+    conn.transaction(|| {
+        let last: Option<i32> = document_revisions::table
+            .filter(document_revisions::doc_id.eq(doc_id))
+            .select(max(document_revisions::number))
+            .first(conn)?;
+        let number = last.unwrap_or(0) + 1;
+        diesel::insert_into(document_revisions::table)
+            .values((
+                document_revisions::doc_id.eq(doc_id),
+                document_revisions::number.eq(number),
+                document_revisions::file_id.eq(file_id),
+                document_revisions::author_id.eq(author_id),
+            ))
+            .execute(conn)?;
+        Ok(number)
+    })
+}
+
+fn select(conn: &Conn, doc_id: i32, number: Option<i32>) -> QueryResult<Vec<Revision>> {
+    let mut query = document_revisions::table
+        .inner_join(files::table)
+        .left_join(users::table)
+        .filter(document_revisions::doc_id.eq(doc_id))
+        .select((
+            document_revisions::id,
+            document_revisions::doc_id,
+            document_revisions::number,
+            document_revisions::file_id,
+            files::path,
+            document_revisions::author_id,
+            users::username.nullable(),
+            document_revisions::created_at,
+        ))
+        .order(document_revisions::number.desc())
+        .into_boxed();
+    if let Some(number) = number {
+        query = query.filter(document_revisions::number.eq(number));
+    }
+    query.load(conn)
+}
+
+/// The document's revisions, newest first.
+pub fn for_doc(conn: &Conn, doc_id: i32) -> QueryResult<Vec<Revision>> {
+    select(conn, doc_id, None)
+}
+
+/// The document's revision with the given number.
+pub fn get(conn: &Conn, doc_id: i32, number: i32) -> QueryResult<Revision> {
+    select(conn, doc_id, Some(number))?
+        .pop()
+        .ok_or(diesel::result::Error::NotFound)
+}
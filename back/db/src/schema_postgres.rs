@@ -0,0 +1,562 @@
+table! {
+    acknowledged_mistakes (id) {
+        id -> Integer,
+        doc_id -> Integer,
+        tier -> Text,
+        annotation -> Text,
+        kind -> Text,
+        segment -> Text,
+        start -> Nullable<Integer>,
+        reason -> Text,
+        author_id -> Integer,
+        created_at -> Timestamptz,
+    }
+}
+
+table! {
+    audit_log (id) {
+        id -> Integer,
+        user_id -> Nullable<Integer>,
+        action -> Text,
+        entity -> Text,
+        entity_id -> Integer,
+        details -> Text,
+        created_at -> Timestamptz,
+    }
+}
+
+table! {
+    bookmarks (id) {
+        id -> Integer,
+        user_id -> Integer,
+        doc_id -> Integer,
+        tier -> Text,
+        annotation -> Text,
+        note -> Text,
+        created_at -> Timestamptz,
+    }
+}
+
+table! {
+    changes (id) {
+        id -> Integer,
+        entity -> Text,
+        entity_id -> Integer,
+        doc_id -> Integer,
+        project_id -> Integer,
+        changed_at -> Timestamptz,
+    }
+}
+
+table! {
+    corpora (id) {
+        id -> Integer,
+        label -> Text,
+        released_at -> Nullable<Timestamptz>,
+    }
+}
+
+table! {
+    digest_settings (project_id) {
+        project_id -> Integer,
+        enabled -> Bool,
+        weekday -> Integer,
+        hour -> Integer,
+        last_sent_at -> Nullable<Timestamptz>,
+    }
+}
+
+table! {
+    doc2corpus (doc_id, corpus_id) {
+        doc_id -> Integer,
+        corpus_id -> Integer,
+    }
+}
+
+table! {
+    doc2speaker (id) {
+        id -> Integer,
+        doc_id -> Integer,
+        speaker_id -> Integer,
+        words -> Nullable<Integer>,
+    }
+}
+
+table! {
+    docs (id) {
+        id -> Integer,
+        project_id -> Integer,
+        assigned_to_id -> Nullable<Integer>,
+        assigned_by_id -> Nullable<Integer>,
+        done -> Nullable<Bool>,
+        date -> Timestamptz,
+        place_id -> Integer,
+        assigned_at -> Nullable<Timestamptz>,
+        due_at -> Nullable<Timestamptz>,
+        done_at -> Nullable<Timestamptz>,
+        state_id -> Integer,
+    }
+}
+
+table! {
+    document_revisions (id) {
+        id -> Integer,
+        doc_id -> Integer,
+        number -> Integer,
+        file_id -> Integer,
+        author_id -> Nullable<Integer>,
+        created_at -> Timestamptz,
+    }
+}
+
+table! {
+    enum_dialect_areas (id) {
+        id -> Integer,
+        label -> Text,
+    }
+}
+
+table! {
+    enum_doc_states (id) {
+        id -> Integer,
+        label -> Text,
+    }
+}
+
+table! {
+    enum_educations (id) {
+        id -> Integer,
+        label -> Text,
+    }
+}
+
+table! {
+    enum_genders (id) {
+        id -> Integer,
+        label -> Text,
+    }
+}
+
+table! {
+    enum_permissions (id) {
+        id -> Integer,
+        label -> Text,
+        description -> Text,
+    }
+}
+
+table! {
+    enum_places (id) {
+        id -> Integer,
+        label -> Text,
+        region_id -> Integer,
+        latitude -> Nullable<Double>,
+        longitude -> Nullable<Double>,
+        dialect_area_id -> Nullable<Integer>,
+    }
+}
+
+table! {
+    enum_regions (id) {
+        id -> Integer,
+        label -> Text,
+    }
+}
+
+table! {
+    enum_return_reasons (id) {
+        id -> Integer,
+        label -> Text,
+    }
+}
+
+table! {
+    enum_roles (id) {
+        id -> Integer,
+        label -> Text,
+    }
+}
+
+table! {
+    files (id) {
+        id -> Integer,
+        doc_id -> Integer,
+        role -> Text,
+        path -> Text,
+        mime -> Text,
+        size -> BigInt,
+        created_by -> Nullable<Integer>,
+        created_at -> Timestamptz,
+        source_id -> Nullable<Integer>,
+    }
+}
+
+table! {
+    jobs (id) {
+        id -> Integer,
+        kind -> Text,
+        file_id -> Integer,
+        state -> Text,
+        error -> Nullable<Text>,
+        created_at -> Timestamptz,
+        finished_at -> Nullable<Timestamptz>,
+    }
+}
+
+table! {
+    metadata_discrepancies (id) {
+        id -> Integer,
+        doc_id -> Integer,
+        file_id -> Integer,
+        field -> Text,
+        speaker -> Nullable<Text>,
+        in_metadata -> Nullable<Text>,
+        in_db -> Nullable<Text>,
+        created_at -> Timestamptz,
+    }
+}
+
+table! {
+    mistakes (id) {
+        id -> Integer,
+        run_id -> Integer,
+        tier -> Text,
+        annotation -> Text,
+        kind -> Text,
+        segment -> Text,
+        start -> Nullable<Integer>,
+        end -> Nullable<Integer>,
+        acknowledged -> Bool,
+    }
+}
+
+table! {
+    palette_entries (id) {
+        id -> Integer,
+        project_id -> Integer,
+        kind -> Text,
+        position -> Integer,
+        value -> Text,
+        description -> Text,
+        shortcut -> Nullable<Text>,
+        deprecated -> Bool,
+    }
+}
+
+table! {
+    parser_configs (project_id) {
+        project_id -> Integer,
+        whitelist -> Text,
+        blacklist -> Text,
+        atoms -> Text,
+        after_angle -> Text,
+        updated_by -> Nullable<Integer>,
+        updated_at -> Timestamptz,
+        anonymize -> Text,
+        attr_values -> Text,
+        report_whitespace -> Bool,
+    }
+}
+
+table! {
+    project_dictionaries (project_id, dictionary) {
+        project_id -> Integer,
+        priority -> Integer,
+        dictionary -> Text,
+    }
+}
+
+table! {
+    project_members (project_id, user_id) {
+        project_id -> Integer,
+        user_id -> Integer,
+    }
+}
+
+table! {
+    projects (id) {
+        id -> Integer,
+        label -> Text,
+        badge -> Text,
+    }
+}
+
+table! {
+    recovery_codes (id) {
+        id -> Integer,
+        user_id -> Integer,
+        code_hash -> Text,
+        used_at -> Nullable<Timestamptz>,
+    }
+}
+
+table! {
+    review_comments (id) {
+        id -> Integer,
+        doc_id -> Integer,
+        author_id -> Integer,
+        tier -> Nullable<Text>,
+        annotation -> Nullable<Text>,
+        start_ms -> Nullable<Integer>,
+        end_ms -> Nullable<Integer>,
+        body -> Text,
+        created_at -> Timestamptz,
+    }
+}
+
+table! {
+    reviews (id) {
+        id -> Integer,
+        doc_id -> Integer,
+        reviewer_id -> Integer,
+        validation_run_id -> Nullable<Integer>,
+        accepted -> Bool,
+        reason_id -> Nullable<Integer>,
+        notes -> Text,
+        created_at -> Timestamptz,
+    }
+}
+
+table! {
+    role_permissions (role_id, permission_id) {
+        role_id -> Integer,
+        permission_id -> Integer,
+    }
+}
+
+table! {
+    scheduled_tasks (name) {
+        name -> Text,
+        last_run_at -> Timestamptz,
+    }
+}
+
+table! {
+    sessions (id) {
+        id -> Integer,
+        user_id -> Integer,
+        token_hash -> Text,
+        device -> Text,
+        created_at -> Timestamptz,
+        last_seen_at -> Timestamptz,
+    }
+}
+
+table! {
+    speakers (id) {
+        id -> Integer,
+        user_id -> Integer,
+        project_id -> Integer,
+        nickname -> Text,
+        gender_id -> Integer,
+        education_id -> Integer,
+        place_id -> Integer,
+        year -> Integer,
+    }
+}
+
+table! {
+    substitutions (project_id, source) {
+        project_id -> Integer,
+        source -> Text,
+        target -> Text,
+    }
+}
+
+table! {
+    tier_mappings (id) {
+        id -> Integer,
+        project_id -> Integer,
+        priority -> Integer,
+        source -> Text,
+        pattern -> Text,
+    }
+}
+
+table! {
+    tier_policies (id) {
+        id -> Integer,
+        project_id -> Integer,
+        priority -> Integer,
+        target -> Text,
+        tiers -> Text,
+        policy -> Text,
+        pattern -> Nullable<Text>,
+    }
+}
+
+table! {
+    two_factor (user_id) {
+        user_id -> Integer,
+        secret -> Text,
+        enabled_at -> Nullable<Timestamptz>,
+        last_step -> Nullable<BigInt>,
+    }
+}
+
+table! {
+    users (id) {
+        id -> Integer,
+        username -> Text,
+        role_id -> Integer,
+        badge -> Nullable<Text>,
+        supervisor_id -> Nullable<Integer>,
+        email -> Nullable<Text>,
+        password_hash -> Nullable<Text>,
+    }
+}
+
+table! {
+    validation_runs (id) {
+        id -> Integer,
+        doc_id -> Integer,
+        created_at -> Timestamptz,
+        mistakes -> Integer,
+        user_id -> Nullable<Integer>,
+        rules_version -> Nullable<Text>,
+        file_id -> Nullable<Integer>,
+        checksum -> Nullable<Text>,
+        regression -> Bool,
+        acknowledged -> Integer,
+    }
+}
+
+table! {
+    webhook_deliveries (id) {
+        id -> Integer,
+        webhook_id -> Integer,
+        event -> Text,
+        payload -> Text,
+        state -> Text,
+        attempts -> Integer,
+        status -> Nullable<Integer>,
+        error -> Nullable<Text>,
+        created_at -> Timestamptz,
+        next_attempt_at -> Timestamptz,
+    }
+}
+
+table! {
+    webhooks (id) {
+        id -> Integer,
+        project_id -> Integer,
+        url -> Text,
+        secret -> Text,
+        active -> Bool,
+        created_at -> Timestamptz,
+    }
+}
+
+table! {
+    word_alignments (id) {
+        id -> Integer,
+        doc_id -> Integer,
+        file_id -> Integer,
+        annotation -> Text,
+        word -> Integer,
+        form -> Text,
+        start_ms -> Integer,
+        end_ms -> Integer,
+    }
+}
+
+joinable!(acknowledged_mistakes -> docs (doc_id));
+joinable!(acknowledged_mistakes -> users (author_id));
+joinable!(audit_log -> users (user_id));
+joinable!(bookmarks -> docs (doc_id));
+joinable!(bookmarks -> users (user_id));
+joinable!(digest_settings -> projects (project_id));
+joinable!(doc2corpus -> corpora (corpus_id));
+joinable!(doc2corpus -> docs (doc_id));
+joinable!(doc2speaker -> docs (doc_id));
+joinable!(doc2speaker -> speakers (speaker_id));
+joinable!(docs -> enum_doc_states (state_id));
+joinable!(docs -> projects (project_id));
+joinable!(document_revisions -> docs (doc_id));
+joinable!(document_revisions -> files (file_id));
+joinable!(document_revisions -> users (author_id));
+joinable!(enum_places -> enum_dialect_areas (dialect_area_id));
+joinable!(enum_places -> enum_regions (region_id));
+joinable!(files -> docs (doc_id));
+joinable!(files -> users (created_by));
+joinable!(jobs -> files (file_id));
+joinable!(metadata_discrepancies -> docs (doc_id));
+joinable!(metadata_discrepancies -> files (file_id));
+joinable!(mistakes -> validation_runs (run_id));
+joinable!(palette_entries -> projects (project_id));
+joinable!(parser_configs -> projects (project_id));
+joinable!(parser_configs -> users (updated_by));
+joinable!(project_dictionaries -> projects (project_id));
+joinable!(project_members -> projects (project_id));
+joinable!(project_members -> users (user_id));
+joinable!(recovery_codes -> users (user_id));
+joinable!(review_comments -> docs (doc_id));
+joinable!(review_comments -> users (author_id));
+joinable!(reviews -> docs (doc_id));
+joinable!(reviews -> enum_return_reasons (reason_id));
+joinable!(reviews -> users (reviewer_id));
+joinable!(reviews -> validation_runs (validation_run_id));
+joinable!(role_permissions -> enum_permissions (permission_id));
+joinable!(role_permissions -> enum_roles (role_id));
+joinable!(sessions -> users (user_id));
+joinable!(speakers -> projects (project_id));
+joinable!(speakers -> users (user_id));
+joinable!(substitutions -> projects (project_id));
+joinable!(tier_mappings -> projects (project_id));
+joinable!(tier_policies -> projects (project_id));
+joinable!(two_factor -> users (user_id));
+joinable!(users -> enum_roles (role_id));
+joinable!(validation_runs -> docs (doc_id));
+joinable!(validation_runs -> files (file_id));
+joinable!(validation_runs -> users (user_id));
+joinable!(webhook_deliveries -> webhooks (webhook_id));
+joinable!(webhooks -> projects (project_id));
+joinable!(word_alignments -> docs (doc_id));
+joinable!(word_alignments -> files (file_id));
+
+allow_tables_to_appear_in_same_query!(
+    acknowledged_mistakes,
+    audit_log,
+    bookmarks,
+    changes,
+    corpora,
+    digest_settings,
+    doc2corpus,
+    doc2speaker,
+    docs,
+    document_revisions,
+    enum_dialect_areas,
+    enum_doc_states,
+    enum_educations,
+    enum_genders,
+    enum_permissions,
+    enum_places,
+    enum_regions,
+    enum_return_reasons,
+    enum_roles,
+    files,
+    jobs,
+    metadata_discrepancies,
+    mistakes,
+    palette_entries,
+    parser_configs,
+    project_dictionaries,
+    project_members,
+    projects,
+    recovery_codes,
+    review_comments,
+    reviews,
+    role_permissions,
+    scheduled_tasks,
+    sessions,
+    speakers,
+    substitutions,
+    tier_mappings,
+    tier_policies,
+    two_factor,
+    users,
+    validation_runs,
+    webhook_deliveries,
+    webhooks,
+    word_alignments,
+);
@@ -0,0 +1,98 @@
+//! Sessions of logged in users. Clients hold a session's token, only its
+//! hash is stored, so that a leaked database doesn't leak sessions.
+
+use chrono::NaiveDateTime;
+use diesel::dsl::now;
+use diesel::prelude::*;
+use sha2::{Digest, Sha256};
+
+use super::schema::sessions;
+use super::Conn;
+
+#[derive(Debug, Queryable)]
+pub struct Session {
+    pub id: i32,
+    pub device: String,
+    pub created_at: NaiveDateTime,
+    pub last_seen_at: NaiveDateTime,
+}
+
+pub(crate) fn hash(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+/// Returns the new session's ID.
+pub fn create(conn: &Conn, user_id: i32, token: &str, device: &str) -> QueryResult<i32> {
+    conn.transaction(|| {
+        diesel::insert_into(sessions::table)
+            .values((
+                sessions::user_id.eq(user_id),
+                sessions::token_hash.eq(hash(token)),
+                sessions::device.eq(device),
+            ))
+            .execute(conn)?;
+        sessions::table
+            .select(sessions::id)
+            .order(sessions::id.desc())
+            .first(conn)
+    })
+}
+
+/// The session with the token and its user, if it hasn't been revoked.
+/// Marks the session as seen just now.
+pub fn resume(conn: &Conn, token: &str) -> QueryResult<Option<(i32, i32)>> {
+    let session = sessions::table
+        .filter(sessions::token_hash.eq(hash(token)))
+        .select((sessions::id, sessions::user_id))
+        .first::<(i32, i32)>(conn)
+        .optional()?;
+    if let Some((id, _)) = session {
+        diesel::update(sessions::table.find(id))
+            .set(sessions::last_seen_at.eq(now))
+            .execute(conn)?;
+    }
+    Ok(session)
+}
+
+/// The user's sessions, most recently seen first.
+pub fn for_user(conn: &Conn, user_id: i32) -> QueryResult<Vec<Session>> {
+    sessions::table
+        .filter(sessions::user_id.eq(user_id))
+        .select((
+            sessions::id,
+            sessions::device,
+            sessions::created_at,
+            sessions::last_seen_at,
+        ))
+        .order(sessions::last_seen_at.desc())
+        .load(conn)
+}
+
+/// Whether the user had such a session.
+pub fn revoke(conn: &Conn, user_id: i32, id: i32) -> QueryResult<bool> {
+    diesel::delete(
+        sessions::table
+            .filter(sessions::user_id.eq(user_id))
+            .filter(sessions::id.eq(id)),
+    )
+    .execute(conn)
+    .map(|n| n > 0)
+}
+
+/// Logs the user out everywhere. Returns the number of revoked sessions.
+pub fn revoke_all(conn: &Conn, user_id: i32) -> QueryResult<usize> {
+    diesel::delete(sessions::table.filter(sessions::user_id.eq(user_id))).execute(conn)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash() {
+        assert_eq!(
+            hash("abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+}
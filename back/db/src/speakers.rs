@@ -0,0 +1,490 @@
+//! Speaker records: editing them one by one and linking them to the
+//! documents they appear in, as well as finding speakers recorded more
+//! than once, e.g. in different projects, and merging them. Projects are
+//! isolated, so only speakers from the same project can be merged.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use chrono::{Datelike, Local};
+use diesel::prelude::*;
+use serde_json::json;
+
+use super::audit;
+use super::fuzzy;
+use super::import::{NewSpeaker, MIN_YEAR};
+use super::schema::{doc2speaker, enum_educations, enum_genders, enum_places, projects, speakers};
+use super::Conn;
+
+pub const MERGE: &str = "speaker.merge";
+
+/// Normalized nickname, year of birth and place ID.
+type Identity = (String, i32, i32);
+
+#[derive(Debug, Queryable)]
+pub struct Speaker {
+    pub id: i32,
+    pub user_id: i32,
+    pub project_id: i32,
+    pub nickname: String,
+    pub gender_id: i32,
+    pub education_id: i32,
+    pub place_id: i32,
+    pub year: i32,
+}
+
+#[derive(Debug)]
+pub struct Candidate {
+    pub id: i32,
+    pub nickname: String,
+    pub project_id: i32,
+    pub project: String,
+    pub place: String,
+    pub year: i32,
+    /// Number of documents the speaker appears in.
+    pub docs: usize,
+}
+
+/// Speakers with the same nickname (ignoring case and diacritics), year of
+/// birth and place, who are likely the same person. Only groups which
+/// include a speaker from the given project are returned, if any.
+pub fn duplicates(conn: &Conn, project_id: Option<i32>) -> QueryResult<Vec<Vec<Candidate>>> {
+    let mut docs: HashMap<i32, usize> = HashMap::new();
+    for speaker_id in doc2speaker::table
+        .select(doc2speaker::speaker_id)
+        .load::<i32>(conn)?
+    {
+        *docs.entry(speaker_id).or_insert(0) += 1;
+    }
+    let rows = speakers::table
+        .inner_join(projects::table)
+        .inner_join(enum_places::table.on(enum_places::id.eq(speakers::place_id)))
+        .select((
+            speakers::id,
+            speakers::project_id,
+            speakers::nickname,
+            projects::label,
+            speakers::place_id,
+            enum_places::label,
+            speakers::year,
+        ))
+        .order(speakers::id)
+        .load::<(i32, i32, String, String, i32, String, i32)>(conn)?;
+
+    let mut groups: Vec<(Identity, Vec<(i32, Candidate)>)> = vec![];
+    for (id, speaker_project, nickname, project, place_id, place, year) in rows {
+        let key = (fuzzy::normalize(nickname.trim()), year, place_id);
+        let candidate = Candidate {
+            id,
+            nickname,
+            project_id: speaker_project,
+            project,
+            place,
+            year,
+            docs: docs.get(&id).copied().unwrap_or(0),
+        };
+        match groups.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, group)) => group.push((speaker_project, candidate)),
+            None => groups.push((key, vec![(speaker_project, candidate)])),
+        }
+    }
+    Ok(groups
+        .into_iter()
+        .map(|(_, group)| group)
+        .filter(|group| group.len() > 1)
+        .filter(|group| project_id.map_or(true, |p| group.iter().any(|(gp, _)| *gp == p)))
+        .map(|group| group.into_iter().map(|(_, c)| c).collect())
+        .collect())
+}
+
+#[derive(Debug)]
+pub enum MergeError {
+    /// The given speaker ID doesn't exist.
+    NotFound(i32),
+    SameSpeaker,
+    DifferentProjects,
+    Db(diesel::result::Error),
+}
+
+impl fmt::Display for MergeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MergeError::NotFound(id) => write!(f, "no such speaker {}", id),
+            MergeError::SameSpeaker => write!(f, "can't merge a speaker with itself"),
+            MergeError::DifferentProjects => {
+                write!(f, "can't merge speakers from different projects")
+            }
+            MergeError::Db(e) => e.fmt(f),
+        }
+    }
+}
+
+impl From<diesel::result::Error> for MergeError {
+    fn from(e: diesel::result::Error) -> Self {
+        MergeError::Db(e)
+    }
+}
+
+/// Speakers recorded in the document, by ID.
+pub fn for_doc(conn: &Conn, doc_id: i32) -> QueryResult<Vec<Speaker>> {
+    speakers::table
+        .inner_join(doc2speaker::table)
+        .filter(doc2speaker::doc_id.eq(doc_id))
+        .select(speakers::all_columns)
+        .order(speakers::id)
+        .load(conn)
+}
+
+pub fn project_of(conn: &Conn, speaker_id: i32) -> QueryResult<i32> {
+    speakers::table
+        .find(speaker_id)
+        .select(speakers::project_id)
+        .first(conn)
+}
+
+fn get(conn: &Conn, id: i32) -> Result<Speaker, MergeError> {
+    speakers::table
+        .find(id)
+        .first(conn)
+        .optional()?
+        .ok_or(MergeError::NotFound(id))
+}
+
+/// Merge speaker `from_id` into `into_id`: documents linked to the former
+/// are linked to the latter instead, and the former is deleted. If both
+/// appear in the same document, the word count is kept from `into_id`,
+/// unless it's unknown. The merged speaker's original record and links are
+/// kept in the audit log. Returns the number of documents relinked.
+pub fn merge(
+    conn: &Conn,
+    from_id: i32,
+    into_id: i32,
+    user_id: Option<i32>,
+) -> Result<usize, MergeError> {
+    if from_id == into_id {
+        return Err(MergeError::SameSpeaker);
+    }
+    conn.transaction(|| {
+        let from = get(conn, from_id)?;
+        if get(conn, into_id)?.project_id != from.project_id {
+            return Err(MergeError::DifferentProjects);
+        }
+
+        let links = doc2speaker::table
+            .filter(doc2speaker::speaker_id.eq(from_id))
+            .select((doc2speaker::id, doc2speaker::doc_id, doc2speaker::words))
+            .load::<(i32, i32, Option<i32>)>(conn)?;
+        for &(link_id, doc_id, words) in &links {
+            let existing = doc2speaker::table
+                .filter(doc2speaker::doc_id.eq(doc_id))
+                .filter(doc2speaker::speaker_id.eq(into_id))
+                .select((doc2speaker::id, doc2speaker::words))
+                .first::<(i32, Option<i32>)>(conn)
+                .optional()?;
+            match existing {
+                Some((existing_id, existing_words)) => {
+                    if existing_words.is_none() {
+                        diesel::update(doc2speaker::table.find(existing_id))
+                            .set(doc2speaker::words.eq(words))
+                            .execute(conn)?;
+                    }
+                    diesel::delete(doc2speaker::table.find(link_id)).execute(conn)?;
+                }
+                None => {
+                    diesel::update(doc2speaker::table.find(link_id))
+                        .set(doc2speaker::speaker_id.eq(into_id))
+                        .execute(conn)?;
+                }
+            }
+        }
+        diesel::delete(speakers::table.find(from_id)).execute(conn)?;
+
+        let links: Vec<_> = links
+            .iter()
+            .map(|(id, doc_id, words)| json!({ "id": id, "doc_id": doc_id, "words": words }))
+            .collect();
+        let details = json!({
+            "into_id": into_id,
+            "speaker": {
+                "user_id": from.user_id,
+                "project_id": from.project_id,
+                "nickname": from.nickname,
+                "gender_id": from.gender_id,
+                "education_id": from.education_id,
+                "place_id": from.place_id,
+                "year": from.year,
+            },
+            "doc2speaker": links,
+        });
+        audit::record(conn, user_id, MERGE, "speaker", from_id, &details)?;
+        Ok(links.len())
+    })
+}
+
+#[derive(Debug)]
+pub enum EditError {
+    NotFound(i32),
+    /// Another speaker of the project goes by the nickname already.
+    NicknameTaken(String),
+    /// An empty nickname, unknown gender, education or place, or an
+    /// implausible year.
+    Invalid(String),
+    /// The speaker can't be deleted while linked to this many documents.
+    Linked(usize),
+    Db(diesel::result::Error),
+}
+
+impl fmt::Display for EditError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EditError::NotFound(id) => write!(f, "no such speaker {}", id),
+            EditError::NicknameTaken(nickname) => {
+                write!(f, "nickname {:?} already exists in project", nickname)
+            }
+            EditError::Invalid(e) => f.write_str(e),
+            EditError::Linked(docs) => write!(
+                f,
+                "speaker appears in {} document(s), unlink or merge them first",
+                docs
+            ),
+            EditError::Db(e) => e.fmt(f),
+        }
+    }
+}
+
+impl From<diesel::result::Error> for EditError {
+    fn from(e: diesel::result::Error) -> Self {
+        EditError::Db(e)
+    }
+}
+
+/// Genders speakers can be recorded with, as `(id, label)`.
+pub fn genders(conn: &Conn) -> QueryResult<Vec<(i32, String)>> {
+    enum_genders::table.order(enum_genders::id).load(conn)
+}
+
+/// Levels of education speakers can be recorded with, as `(id, label)`.
+pub fn educations(conn: &Conn) -> QueryResult<Vec<(i32, String)>> {
+    enum_educations::table.order(enum_educations::id).load(conn)
+}
+
+/// Attributes to select speakers by, e.g. for frequency lists; all of the
+/// given ones have to match.
+#[derive(Debug, Default, Clone)]
+pub struct SpeakerFilter {
+    pub gender_id: Option<i32>,
+    pub education_id: Option<i32>,
+    pub region_id: Option<i32>,
+}
+
+impl SpeakerFilter {
+    pub fn is_empty(&self) -> bool {
+        self.gender_id.is_none() && self.education_id.is_none() && self.region_id.is_none()
+    }
+}
+
+/// Nicknames of the project's speakers matching the filter.
+pub fn nicknames(
+    conn: &Conn,
+    project_id: i32,
+    filter: &SpeakerFilter,
+) -> QueryResult<HashSet<String>> {
+    let mut query = speakers::table
+        .filter(speakers::project_id.eq(project_id))
+        .select(speakers::nickname)
+        .into_boxed();
+    if let Some(gender_id) = filter.gender_id {
+        query = query.filter(speakers::gender_id.eq(gender_id));
+    }
+    if let Some(education_id) = filter.education_id {
+        query = query.filter(speakers::education_id.eq(education_id));
+    }
+    if let Some(region_id) = filter.region_id {
+        query = query.filter(
+            speakers::place_id.eq_any(
+                enum_places::table
+                    .filter(enum_places::region_id.eq(region_id))
+                    .select(enum_places::id),
+            ),
+        );
+    }
+    Ok(query.load::<String>(conn)?.into_iter().collect())
+}
+
+/// The project's speakers, by nickname.
+pub fn for_project(conn: &Conn, project_id: i32) -> QueryResult<Vec<Speaker>> {
+    speakers::table
+        .filter(speakers::project_id.eq(project_id))
+        .order((speakers::nickname, speakers::id))
+        .load(conn)
+}
+
+pub fn find(conn: &Conn, id: i32) -> Result<Speaker, EditError> {
+    speakers::table
+        .find(id)
+        .first(conn)
+        .optional()?
+        .ok_or(EditError::NotFound(id))
+}
+
+/// Checks the speaker the way `import::check` checks spreadsheet rows;
+/// `id` is that of the speaker being edited, if any.
+fn check(conn: &Conn, speaker: &NewSpeaker, id: Option<i32>) -> Result<(), EditError> {
+    if speaker.nickname.trim().is_empty() {
+        return Err(EditError::Invalid("empty nickname".to_owned()));
+    }
+    let taken = speakers::table
+        .filter(speakers::project_id.eq(speaker.project_id))
+        .filter(speakers::nickname.eq(&speaker.nickname))
+        .filter(speakers::id.ne(id.unwrap_or(0)))
+        .select(speakers::id)
+        .first::<i32>(conn)
+        .optional()?;
+    if taken.is_some() {
+        return Err(EditError::NicknameTaken(speaker.nickname.clone()));
+    }
+    let genders: i64 = enum_genders::table
+        .find(speaker.gender_id)
+        .count()
+        .get_result(conn)?;
+    let educations: i64 = enum_educations::table
+        .find(speaker.education_id)
+        .count()
+        .get_result(conn)?;
+    let places: i64 = enum_places::table
+        .find(speaker.place_id)
+        .count()
+        .get_result(conn)?;
+    for &(field, count, id) in &[
+        ("gender", genders, speaker.gender_id),
+        ("education", educations, speaker.education_id),
+        ("place", places, speaker.place_id),
+    ] {
+        if count == 0 {
+            return Err(EditError::Invalid(format!("unknown {} {}", field, id)));
+        }
+    }
+    let max_year = Local::now().year();
+    if !(MIN_YEAR..=max_year).contains(&speaker.year) {
+        return Err(EditError::Invalid(format!(
+            "bad year {}, expected a number between {} and {}",
+            speaker.year, MIN_YEAR, max_year
+        )));
+    }
+    Ok(())
+}
+
+/// Returns the new speaker's ID.
+pub fn create(conn: &Conn, speaker: &NewSpeaker) -> Result<i32, EditError> {
+    conn.transaction(|| {
+        check(conn, speaker, None)?;
+        diesel::insert_into(speakers::table)
+            .values(speaker)
+            .execute(conn)?;
+        Ok(speakers::table
+            .select(speakers::id)
+            .order(speakers::id.desc())
+            .first(conn)?)
+    })
+}
+
+/// Update everything but the project, which the speaker stays in.
+pub fn update(conn: &Conn, id: i32, speaker: &NewSpeaker) -> Result<(), EditError> {
+    conn.transaction(|| {
+        let project_id = find(conn, id)?.project_id;
+        let speaker = NewSpeaker {
+            project_id,
+            nickname: speaker.nickname.clone(),
+            ..*speaker
+        };
+        check(conn, &speaker, Some(id))?;
+        diesel::update(speakers::table.find(id))
+            .set((
+                speakers::user_id.eq(speaker.user_id),
+                speakers::nickname.eq(&speaker.nickname),
+                speakers::gender_id.eq(speaker.gender_id),
+                speakers::education_id.eq(speaker.education_id),
+                speakers::place_id.eq(speaker.place_id),
+                speakers::year.eq(speaker.year),
+            ))
+            .execute(conn)?;
+        Ok(())
+    })
+}
+
+/// Delete a speaker who doesn't appear in any documents.
+pub fn delete(conn: &Conn, id: i32) -> Result<(), EditError> {
+    conn.transaction(|| {
+        find(conn, id)?;
+        let docs: i64 = doc2speaker::table
+            .filter(doc2speaker::speaker_id.eq(id))
+            .count()
+            .get_result(conn)?;
+        if docs > 0 {
+            return Err(EditError::Linked(docs as usize));
+        }
+        diesel::delete(speakers::table.find(id)).execute(conn)?;
+        Ok(())
+    })
+}
+
+/// Speakers linked to the document, by ID, with their word counts in it.
+pub fn links(conn: &Conn, doc_id: i32) -> QueryResult<Vec<(Speaker, Option<i32>)>> {
+    speakers::table
+        .inner_join(doc2speaker::table)
+        .filter(doc2speaker::doc_id.eq(doc_id))
+        .select((speakers::all_columns, doc2speaker::words))
+        .order(speakers::id)
+        .load(conn)
+}
+
+/// Link the speaker to the document, or update the word count if they're
+/// linked already. Returns whether the link is new.
+pub fn link(conn: &Conn, doc_id: i32, speaker_id: i32, words: Option<i32>) -> QueryResult<bool> {
+    conn.transaction(|| {
+        let updated = diesel::update(
+            doc2speaker::table
+                .filter(doc2speaker::doc_id.eq(doc_id))
+                .filter(doc2speaker::speaker_id.eq(speaker_id)),
+        )
+        .set(doc2speaker::words.eq(words))
+        .execute(conn)?;
+        if updated > 0 {
+            return Ok(false);
+        }
+        diesel::insert_into(doc2speaker::table)
+            .values((
+                doc2speaker::doc_id.eq(doc_id),
+                doc2speaker::speaker_id.eq(speaker_id),
+                doc2speaker::words.eq(words),
+            ))
+            .execute(conn)?;
+        Ok(true)
+    })
+}
+
+/// Returns whether the speaker was linked to the document.
+pub fn unlink(conn: &Conn, doc_id: i32, speaker_id: i32) -> QueryResult<bool> {
+    let removed = diesel::delete(
+        doc2speaker::table
+            .filter(doc2speaker::doc_id.eq(doc_id))
+            .filter(doc2speaker::speaker_id.eq(speaker_id)),
+    )
+    .execute(conn)?;
+    Ok(removed > 0)
+}
+
+/// Store the word counts of speakers in the document, given as `(speaker
+/// ID, words)`. Speakers not linked to the document yet are linked, those
+/// linked but not given are counted as saying nothing.
+pub fn set_words(conn: &Conn, doc_id: i32, words: &[(i32, i32)]) -> QueryResult<()> {
+    conn.transaction(|| {
+        diesel::update(doc2speaker::table.filter(doc2speaker::doc_id.eq(doc_id)))
+            .set(doc2speaker::words.eq(0))
+            .execute(conn)?;
+        for &(speaker_id, count) in words {
+            link(conn, doc_id, speaker_id, Some(count))?;
+        }
+        Ok(())
+    })
+}
@@ -0,0 +1,33 @@
+//! Which commonly mistyped graphemes each project offers to substitute.
+
+use diesel::prelude::*;
+
+use super::schema::substitutions;
+use super::Conn;
+
+/// The project's substitutions, as (source, target) pairs.
+pub fn for_project(conn: &Conn, project_id: i32) -> QueryResult<Vec<(String, String)>> {
+    substitutions::table
+        .filter(substitutions::project_id.eq(project_id))
+        .order(substitutions::source)
+        .select((substitutions::source, substitutions::target))
+        .load(conn)
+}
+
+/// Replace the project's substitutions.
+pub fn replace(conn: &Conn, project_id: i32, pairs: &[(String, String)]) -> QueryResult<()> {
+    conn.transaction(|| {
+        diesel::delete(substitutions::table.filter(substitutions::project_id.eq(project_id)))
+            .execute(conn)?;
+        for (source, target) in pairs {
+            diesel::insert_into(substitutions::table)
+                .values((
+                    substitutions::project_id.eq(project_id),
+                    substitutions::source.eq(source),
+                    substitutions::target.eq(target),
+                ))
+                .execute(conn)?;
+        }
+        Ok(())
+    })
+}
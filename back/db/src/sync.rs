@@ -0,0 +1,224 @@
+//! Incremental sync for clients which keep a local copy of what they work
+//! on, e.g. for reviewing offline. Changes to documents, their assignments,
+//! reviews, review comments and validation reports are recorded by triggers in the `changes`
+//! table; a client passes the cursor it got last time and gets the current
+//! state of everything that changed since.
+
+use std::collections::HashSet;
+
+use chrono::NaiveDateTime;
+use diesel::dsl::max;
+use diesel::prelude::*;
+use diesel::sql_types::Integer;
+
+use super::comments::{self, Comment};
+use super::members::Access;
+use super::reviews::Review;
+use super::schema::{
+    changes, docs, enum_doc_states, enum_return_reasons, review_comments, reviews, users,
+    validation_runs,
+};
+use super::validation::Run;
+use super::{Backend, Conn};
+
+/// Kinds of entities in `changes`. Assignments are identified by the
+/// document ID.
+pub const DOCUMENT: &str = "document";
+pub const ASSIGNMENT: &str = "assignment";
+pub const REVIEW: &str = "review";
+pub const COMMENT: &str = "comment";
+pub const VALIDATION_RUN: &str = "validation_run";
+
+#[derive(Debug, Queryable)]
+pub struct SyncDoc {
+    pub id: i32,
+    pub project_id: i32,
+    pub state: String,
+    pub place_id: i32,
+    pub done_at: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Queryable)]
+pub struct Assignment {
+    pub doc_id: i32,
+    pub assigned_to_id: Option<i32>,
+    pub assigned_by_id: Option<i32>,
+    pub assigned_at: Option<NaiveDateTime>,
+    pub due_at: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Default)]
+pub struct Changes {
+    /// To pass on the next sync.
+    pub cursor: i32,
+    /// Whether there are more changes, i.e. the client should sync again
+    /// right away.
+    pub more: bool,
+    pub documents: Vec<SyncDoc>,
+    pub assignments: Vec<Assignment>,
+    /// Along with the ID of the document reviewed.
+    pub reviews: Vec<(i32, Review)>,
+    pub comments: Vec<Comment>,
+    pub validation_runs: Vec<Run>,
+    /// Entities which changed but are gone or no longer accessible, as
+    /// (entity, ID).
+    pub deleted: Vec<(String, i32)>,
+}
+
+/// IDs of changed entities of the given kind, in order of their first
+/// change.
+fn ids_of(rows: &[(i32, String, i32)], entity: &str) -> Vec<i32> {
+    let mut seen = HashSet::new();
+    rows.iter()
+        .filter(|(_, e, id)| e == entity && seen.insert(*id))
+        .map(|(_, _, id)| *id)
+        .collect()
+}
+
+/// Record which of the IDs weren't found as deleted.
+fn gone(entity: &str, ids: &[i32], found: &[i32], deleted: &mut Vec<(String, i32)>) {
+    deleted.extend(
+        ids.iter()
+            .filter(|id| !found.contains(id))
+            .map(|id| (entity.to_owned(), *id)),
+    );
+}
+
+/// IDs of the documents the user has access to.
+fn accessible(access: &Access) -> docs::BoxedQuery<Backend, Integer> {
+    let query = docs::table.select(docs::id).into_boxed();
+    match access {
+        Access::All => query,
+        Access::Projects(project_ids) => query.filter(docs::project_id.eq_any(project_ids)),
+    }
+}
+
+/// Up to `limit` changes after the cursor (0 to get everything) in projects
+/// the user has access to, each entity with its current state.
+pub fn since(conn: &Conn, access: &Access, cursor: i32, limit: i64) -> QueryResult<Changes> {
+    conn.transaction(|| {
+        // later changes are left for the next sync
+        let latest = changes::table
+            .select(max(changes::id))
+            .first::<Option<i32>>(conn)?
+            .unwrap_or(0);
+        let mut query = changes::table
+            .filter(changes::id.gt(cursor))
+            .filter(changes::id.le(latest))
+            .select((changes::id, changes::entity, changes::entity_id))
+            .order(changes::id)
+            .limit(limit + 1)
+            .into_boxed();
+        if let Access::Projects(project_ids) = access {
+            query = query.filter(changes::project_id.eq_any(project_ids));
+        }
+        let mut rows = query.load::<(i32, String, i32)>(conn)?;
+        let more = rows.len() as i64 > limit;
+        rows.truncate(limit.max(0) as usize);
+        let cursor = match rows.last() {
+            Some((id, _, _)) if more => *id,
+            // skipping changes in projects the user can't access
+            _ => latest.max(cursor),
+        };
+        let mut changes = Changes {
+            cursor,
+            more,
+            ..Changes::default()
+        };
+
+        let ids = ids_of(&rows, DOCUMENT);
+        changes.documents = docs::table
+            .inner_join(enum_doc_states::table)
+            .filter(docs::id.eq_any(&ids))
+            .filter(docs::id.eq_any(accessible(access)))
+            .select((
+                docs::id,
+                docs::project_id,
+                enum_doc_states::label,
+                docs::place_id,
+                docs::done_at,
+            ))
+            .order(docs::id)
+            .load(conn)?;
+        let found: Vec<_> = changes.documents.iter().map(|d| d.id).collect();
+        gone(DOCUMENT, &ids, &found, &mut changes.deleted);
+
+        let ids = ids_of(&rows, ASSIGNMENT);
+        changes.assignments = docs::table
+            .filter(docs::id.eq_any(&ids))
+            .filter(docs::id.eq_any(accessible(access)))
+            .select((
+                docs::id,
+                docs::assigned_to_id,
+                docs::assigned_by_id,
+                docs::assigned_at,
+                docs::due_at,
+            ))
+            .order(docs::id)
+            .load(conn)?;
+        let found: Vec<_> = changes.assignments.iter().map(|a| a.doc_id).collect();
+        gone(ASSIGNMENT, &ids, &found, &mut changes.deleted);
+
+        let ids = ids_of(&rows, REVIEW);
+        changes.reviews = reviews::table
+            .inner_join(users::table)
+            .left_join(enum_return_reasons::table)
+            .filter(reviews::id.eq_any(&ids))
+            .filter(reviews::doc_id.eq_any(accessible(access)))
+            .select((
+                reviews::doc_id,
+                (
+                    reviews::id,
+                    users::username,
+                    reviews::validation_run_id,
+                    reviews::accepted,
+                    enum_return_reasons::label.nullable(),
+                    reviews::notes,
+                    reviews::created_at,
+                ),
+            ))
+            .order(reviews::id)
+            .load(conn)?;
+        let found: Vec<_> = changes.reviews.iter().map(|(_, r)| r.id).collect();
+        gone(REVIEW, &ids, &found, &mut changes.deleted);
+
+        let ids = ids_of(&rows, COMMENT);
+        let visible: Vec<i32> = review_comments::table
+            .filter(review_comments::id.eq_any(&ids))
+            .filter(review_comments::doc_id.eq_any(accessible(access)))
+            .select(review_comments::id)
+            .load(conn)?;
+        changes.comments = comments::with_ids(conn, &visible)?;
+        let found: Vec<_> = changes.comments.iter().map(|c| c.id).collect();
+        gone(COMMENT, &ids, &found, &mut changes.deleted);
+
+        let ids = ids_of(&rows, VALIDATION_RUN);
+        changes.validation_runs = validation_runs::table
+            .filter(validation_runs::id.eq_any(&ids))
+            .filter(validation_runs::doc_id.eq_any(accessible(access)))
+            .order(validation_runs::id)
+            .load(conn)?;
+        let found: Vec<_> = changes.validation_runs.iter().map(|r| r.id).collect();
+        gone(VALIDATION_RUN, &ids, &found, &mut changes.deleted);
+
+        Ok(changes)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ids_of() {
+        let rows = vec![
+            (1, DOCUMENT.to_owned(), 7),
+            (2, REVIEW.to_owned(), 3),
+            (3, DOCUMENT.to_owned(), 5),
+            (4, DOCUMENT.to_owned(), 7),
+        ];
+        assert_eq!(ids_of(&rows, DOCUMENT), vec![7, 5]);
+        assert_eq!(ids_of(&rows, REVIEW), vec![3]);
+        assert!(ids_of(&rows, ASSIGNMENT).is_empty());
+    }
+}
@@ -0,0 +1,66 @@
+//! Bookkeeping for periodic tasks which aren't tied to a project, so that
+//! they run once a day regardless of how often the scheduler wakes up or
+//! whether the server was restarted in between.
+
+use chrono::{NaiveDateTime, Timelike};
+use diesel::prelude::*;
+
+use super::schema::scheduled_tasks;
+use super::Conn;
+
+/// Revalidate documents whose validation is stale or whose transcript has
+/// changed.
+pub const NIGHTLY_REVALIDATION: &str = "nightly_revalidation";
+
+/// Whether a daily task should run at `now`, i.e. it's past the given hour,
+/// and it hasn't run yet today.
+pub fn is_due(last_run_at: Option<NaiveDateTime>, now: NaiveDateTime, hour: u32) -> bool {
+    let right_time = now.hour() >= hour;
+    let not_run_yet = last_run_at
+        .map(|last| last.date() < now.date())
+        .unwrap_or(true);
+    right_time && not_run_yet
+}
+
+pub fn last_run(conn: &Conn, name: &str) -> QueryResult<Option<NaiveDateTime>> {
+    scheduled_tasks::table
+        .find(name)
+        .select(scheduled_tasks::last_run_at)
+        .first(conn)
+        .optional()
+}
+
+pub fn mark_run(conn: &Conn, name: &str, now: NaiveDateTime) -> QueryResult<()> {
+    conn.transaction(|| {
+        diesel::delete(scheduled_tasks::table.find(name)).execute(conn)?;
+        diesel::insert_into(scheduled_tasks::table)
+            .values((
+                scheduled_tasks::name.eq(name),
+                scheduled_tasks::last_run_at.eq(now),
+            ))
+            .execute(conn)
+            .map(|_| ())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn at(day: u32, hour: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2026, 10, day)
+            .unwrap()
+            .and_hms_opt(hour, 0, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_is_due() {
+        assert!(is_due(None, at(15, 2), 2));
+        assert!(!is_due(None, at(15, 1), 2));
+        assert!(is_due(Some(at(14, 2)), at(15, 3), 2));
+        assert!(!is_due(Some(at(15, 2)), at(15, 23), 2));
+        assert!(!is_due(Some(at(14, 23)), at(15, 1), 2));
+    }
+}
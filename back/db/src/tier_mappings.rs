@@ -0,0 +1,62 @@
+//! Per-project rules mapping tiers to speakers.
+
+use diesel::prelude::*;
+
+use super::schema::{doc2speaker, speakers, tier_mappings};
+use super::Conn;
+
+#[derive(Debug, Queryable)]
+pub struct TierRule {
+    pub source: String,
+    pub pattern: String,
+}
+
+/// Rules in the order in which they should be tried.
+pub fn for_project(conn: &Conn, project_id: i32) -> QueryResult<Vec<TierRule>> {
+    tier_mappings::table
+        .filter(tier_mappings::project_id.eq(project_id))
+        .order(tier_mappings::priority)
+        .select((tier_mappings::source, tier_mappings::pattern))
+        .load(conn)
+}
+
+/// Replace the project's rules; their priority is given by their order.
+pub fn replace(conn: &Conn, project_id: i32, rules: &[TierRule]) -> QueryResult<()> {
+    conn.transaction(|| {
+        diesel::delete(tier_mappings::table.filter(tier_mappings::project_id.eq(project_id)))
+            .execute(conn)?;
+        for (i, rule) in rules.iter().enumerate() {
+            diesel::insert_into(tier_mappings::table)
+                .values((
+                    tier_mappings::project_id.eq(project_id),
+                    tier_mappings::priority.eq(i as i32 + 1),
+                    tier_mappings::source.eq(&rule.source),
+                    tier_mappings::pattern.eq(&rule.pattern),
+                ))
+                .execute(conn)?;
+        }
+        Ok(())
+    })
+}
+
+/// ID of the speaker with the given nickname, preferring speakers already
+/// linked to the document over other speakers in the document's project.
+pub fn speaker_for_nickname(conn: &Conn, doc_id: i32, nickname: &str) -> QueryResult<Option<i32>> {
+    let linked = doc2speaker::table
+        .inner_join(speakers::table)
+        .filter(doc2speaker::doc_id.eq(doc_id))
+        .filter(speakers::nickname.eq(nickname))
+        .select(speakers::id)
+        .first::<i32>(conn)
+        .optional()?;
+    if linked.is_some() {
+        return Ok(linked);
+    }
+    let project_id = super::docs::project_of(conn, doc_id)?;
+    speakers::table
+        .filter(speakers::project_id.eq(project_id))
+        .filter(speakers::nickname.eq(nickname))
+        .select(speakers::id)
+        .first(conn)
+        .optional()
+}
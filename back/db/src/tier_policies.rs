@@ -0,0 +1,49 @@
+//! Per-project rules saying how tiers are validated.
+
+use diesel::prelude::*;
+
+use super::schema::tier_policies;
+use super::Conn;
+
+#[derive(Debug, Queryable)]
+pub struct PolicyRule {
+    pub target: String,
+    pub tiers: String,
+    pub policy: String,
+    pub pattern: Option<String>,
+}
+
+/// Rules in the order in which they should be tried.
+pub fn for_project(conn: &Conn, project_id: i32) -> QueryResult<Vec<PolicyRule>> {
+    tier_policies::table
+        .filter(tier_policies::project_id.eq(project_id))
+        .order(tier_policies::priority)
+        .select((
+            tier_policies::target,
+            tier_policies::tiers,
+            tier_policies::policy,
+            tier_policies::pattern,
+        ))
+        .load(conn)
+}
+
+/// Replace the project's rules; their priority is given by their order.
+pub fn replace(conn: &Conn, project_id: i32, rules: &[PolicyRule]) -> QueryResult<()> {
+    conn.transaction(|| {
+        diesel::delete(tier_policies::table.filter(tier_policies::project_id.eq(project_id)))
+            .execute(conn)?;
+        for (i, rule) in rules.iter().enumerate() {
+            diesel::insert_into(tier_policies::table)
+                .values((
+                    tier_policies::project_id.eq(project_id),
+                    tier_policies::priority.eq(i as i32 + 1),
+                    tier_policies::target.eq(&rule.target),
+                    tier_policies::tiers.eq(&rule.tiers),
+                    tier_policies::policy.eq(&rule.policy),
+                    tier_policies::pattern.eq(&rule.pattern),
+                ))
+                .execute(conn)?;
+        }
+        Ok(())
+    })
+}
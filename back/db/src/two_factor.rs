@@ -0,0 +1,290 @@
+//! Two-factor authentication with time-based one-time passwords (TOTP, RFC
+//! 6238) from an authenticator app, or single-use recovery codes for when
+//! the app is lost.
+
+use data_encoding::BASE32_NOPAD;
+use diesel::dsl::now;
+use diesel::prelude::*;
+use hmac::{Hmac, Mac, NewMac};
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use rand::Rng;
+use sha1::Sha1;
+
+use super::schema::{recovery_codes, two_factor};
+use super::sessions::hash;
+use super::Conn;
+
+/// How long each code is valid for, the default of authenticator apps.
+const STEP_SECONDS: u64 = 30;
+const DIGITS: u32 = 6;
+/// How many steps a code may be off by, to tolerate clock drift.
+const SKEW: i64 = 1;
+/// 160 bits, as recommended by RFC 4226.
+const SECRET_BYTES: usize = 20;
+const RECOVERY_CODES: usize = 10;
+/// Random bytes in a recovery code, i.e. 8 base32 characters.
+const RECOVERY_CODE_BYTES: usize = 5;
+
+#[derive(Debug)]
+pub enum TwoFactorError {
+    NotEnrolled,
+    AlreadyEnabled,
+    BadCode,
+    Db(diesel::result::Error),
+}
+
+impl std::fmt::Display for TwoFactorError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            TwoFactorError::NotEnrolled => write!(f, "two-factor authentication isn't set up"),
+            TwoFactorError::AlreadyEnabled => {
+                write!(f, "two-factor authentication is already enabled")
+            }
+            TwoFactorError::BadCode => write!(f, "invalid or already used code"),
+            TwoFactorError::Db(e) => e.fmt(f),
+        }
+    }
+}
+
+impl From<diesel::result::Error> for TwoFactorError {
+    fn from(e: diesel::result::Error) -> Self {
+        TwoFactorError::Db(e)
+    }
+}
+
+/// HOTP (RFC 4226) of the counter.
+fn hotp(key: &[u8], counter: u64) -> u32 {
+    let mut mac = Hmac::<Sha1>::new_varkey(key).expect("HMAC takes keys of any size");
+    mac.update(&counter.to_be_bytes());
+    let digest = mac.finalize().into_bytes();
+    let offset = (digest[digest.len() - 1] & 0xf) as usize;
+    let mut truncated = [0; 4];
+    truncated.copy_from_slice(&digest[offset..offset + 4]);
+    (u32::from_be_bytes(truncated) & 0x7fff_ffff) % 10u32.pow(DIGITS)
+}
+
+/// The time step within `SKEW` of `unix_time` at which the secret yields
+/// the code, if any.
+fn matching_step(secret: &str, code: &str, unix_time: u64) -> Option<i64> {
+    let key = BASE32_NOPAD.decode(secret.as_bytes()).ok()?;
+    let code = code.trim();
+    if code.len() != DIGITS as usize {
+        return None;
+    }
+    let code: u32 = code.parse().ok()?;
+    let current = (unix_time / STEP_SECONDS) as i64;
+    (current - SKEW..=current + SKEW).find(|&step| step >= 0 && hotp(&key, step as u64) == code)
+}
+
+/// An `otpauth://` URI for authenticator apps, usually shown as a QR code.
+pub fn provisioning_uri(secret: &str, account: &str, issuer: &str) -> String {
+    let issuer = utf8_percent_encode(issuer, NON_ALPHANUMERIC).to_string();
+    format!(
+        "otpauth://totp/{issuer}:{account}?secret={secret}&issuer={issuer}\
+         &algorithm=SHA1&digits={digits}&period={period}",
+        issuer = issuer,
+        account = utf8_percent_encode(account, NON_ALPHANUMERIC),
+        secret = secret,
+        digits = DIGITS,
+        period = STEP_SECONDS,
+    )
+}
+
+/// Recovery codes are case insensitive and may be grouped with dashes or
+/// spaces.
+fn normalize_recovery_code(code: &str) -> String {
+    code.chars()
+        .filter(|c| !c.is_whitespace() && *c != '-')
+        .collect::<String>()
+        .to_uppercase()
+}
+
+pub fn is_enabled(conn: &Conn, user_id: i32) -> QueryResult<bool> {
+    two_factor::table
+        .find(user_id)
+        .filter(two_factor::enabled_at.is_not_null())
+        .select(two_factor::user_id)
+        .first::<i32>(conn)
+        .optional()
+        .map(|row| row.is_some())
+}
+
+/// Generate a new secret for the user, replacing any unconfirmed one. It
+/// takes effect once confirmed.
+pub fn enroll(conn: &Conn, user_id: i32) -> Result<String, TwoFactorError> {
+    conn.transaction(|| {
+        if is_enabled(conn, user_id)? {
+            return Err(TwoFactorError::AlreadyEnabled);
+        }
+        let secret = BASE32_NOPAD.encode(&rand::thread_rng().gen::<[u8; SECRET_BYTES]>());
+        diesel::delete(two_factor::table.find(user_id)).execute(conn)?;
+        diesel::insert_into(two_factor::table)
+            .values((
+                two_factor::user_id.eq(user_id),
+                two_factor::secret.eq(&secret),
+            ))
+            .execute(conn)?;
+        Ok(secret)
+    })
+}
+
+/// Replace the user's recovery codes with new ones.
+pub fn regenerate_recovery_codes(conn: &Conn, user_id: i32) -> QueryResult<Vec<String>> {
+    let mut rng = rand::thread_rng();
+    let codes: Vec<_> = (0..RECOVERY_CODES)
+        .map(|_| BASE32_NOPAD.encode(&rng.gen::<[u8; RECOVERY_CODE_BYTES]>()))
+        .collect();
+    conn.transaction::<_, diesel::result::Error, _>(|| {
+        diesel::delete(recovery_codes::table.filter(recovery_codes::user_id.eq(user_id)))
+            .execute(conn)?;
+        let rows: Vec<_> = codes
+            .iter()
+            .map(|code| {
+                (
+                    recovery_codes::user_id.eq(user_id),
+                    recovery_codes::code_hash.eq(hash(code)),
+                )
+            })
+            .collect();
+        diesel::insert_into(recovery_codes::table)
+            .values(&rows)
+            .execute(conn)?;
+        Ok(())
+    })?;
+    Ok(codes
+        .iter()
+        .map(|code| format!("{}-{}", &code[..4], &code[4..]))
+        .collect())
+}
+
+/// Check a code from the authenticator app, which mustn't have been used
+/// yet.
+fn verify_totp(
+    conn: &Conn,
+    user_id: i32,
+    code: &str,
+    unix_time: u64,
+) -> Result<(), TwoFactorError> {
+    let (secret, last_step) = two_factor::table
+        .find(user_id)
+        .select((two_factor::secret, two_factor::last_step))
+        .first::<(String, Option<i64>)>(conn)
+        .optional()?
+        .ok_or(TwoFactorError::NotEnrolled)?;
+    match matching_step(&secret, code, unix_time) {
+        Some(step) if last_step.map_or(true, |last| step > last) => {
+            diesel::update(two_factor::table.find(user_id))
+                .set(two_factor::last_step.eq(step))
+                .execute(conn)?;
+            Ok(())
+        }
+        _ => Err(TwoFactorError::BadCode),
+    }
+}
+
+/// Enable two-factor authentication once the user has entered a code from
+/// their app. Returns their recovery codes, which are only ever shown now.
+pub fn confirm(
+    conn: &Conn,
+    user_id: i32,
+    code: &str,
+    unix_time: u64,
+) -> Result<Vec<String>, TwoFactorError> {
+    conn.transaction(|| {
+        if is_enabled(conn, user_id)? {
+            return Err(TwoFactorError::AlreadyEnabled);
+        }
+        verify_totp(conn, user_id, code, unix_time)?;
+        diesel::update(two_factor::table.find(user_id))
+            .set(two_factor::enabled_at.eq(now))
+            .execute(conn)?;
+        Ok(regenerate_recovery_codes(conn, user_id)?)
+    })
+}
+
+/// Check the second factor of a user who has it enabled: either a code
+/// from their app, or an unused recovery code, which is then used up.
+pub fn verify(conn: &Conn, user_id: i32, code: &str, unix_time: u64) -> Result<(), TwoFactorError> {
+    conn.transaction(|| {
+        if !is_enabled(conn, user_id)? {
+            return Err(TwoFactorError::NotEnrolled);
+        }
+        match verify_totp(conn, user_id, code, unix_time) {
+            Err(TwoFactorError::BadCode) => {}
+            result => return result,
+        }
+        let used = diesel::update(
+            recovery_codes::table
+                .filter(recovery_codes::user_id.eq(user_id))
+                .filter(recovery_codes::code_hash.eq(hash(&normalize_recovery_code(code))))
+                .filter(recovery_codes::used_at.is_null()),
+        )
+        .set(recovery_codes::used_at.eq(now))
+        .execute(conn)?;
+        if used > 0 {
+            Ok(())
+        } else {
+            Err(TwoFactorError::BadCode)
+        }
+    })
+}
+
+/// How many of the user's recovery codes haven't been used yet.
+pub fn recovery_codes_left(conn: &Conn, user_id: i32) -> QueryResult<i64> {
+    recovery_codes::table
+        .filter(recovery_codes::user_id.eq(user_id))
+        .filter(recovery_codes::used_at.is_null())
+        .count()
+        .get_result(conn)
+}
+
+pub fn disable(conn: &Conn, user_id: i32) -> QueryResult<()> {
+    conn.transaction(|| {
+        diesel::delete(recovery_codes::table.filter(recovery_codes::user_id.eq(user_id)))
+            .execute(conn)?;
+        diesel::delete(two_factor::table.find(user_id)).execute(conn)?;
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The SHA-1 test vectors of RFC 6238, truncated to 6 digits.
+    #[test]
+    fn test_totp() {
+        let secret = BASE32_NOPAD.encode(b"12345678901234567890");
+        for (time, code) in &[
+            (59, "287082"),
+            (1111111109, "081804"),
+            (1234567890, "005924"),
+            (2000000000, "279037"),
+        ] {
+            let step = (time / STEP_SECONDS) as i64;
+            assert_eq!(matching_step(&secret, code, *time), Some(step));
+            // tolerate a bit of clock drift, but not too much
+            assert_eq!(
+                matching_step(&secret, code, time + STEP_SECONDS),
+                Some(step)
+            );
+            assert_eq!(matching_step(&secret, code, time + 3 * STEP_SECONDS), None);
+        }
+        assert_eq!(matching_step(&secret, "28708", 59), None);
+        assert_eq!(matching_step(&secret, "abcdef", 59), None);
+    }
+
+    #[test]
+    fn test_normalize_recovery_code() {
+        assert_eq!(normalize_recovery_code(" abcd-ef23 "), "ABCDEF23");
+    }
+
+    #[test]
+    fn test_provisioning_uri() {
+        assert_eq!(
+            provisioning_uri("JBSWY3DP", "jan.novak", "Quetzal"),
+            "otpauth://totp/Quetzal:jan%2Enovak?secret=JBSWY3DP&issuer=Quetzal\
+             &algorithm=SHA1&digits=6&period=30"
+        );
+    }
+}
@@ -0,0 +1,105 @@
+//! Users of the app, i.e. transcribers, supervisors and admins.
+
+use argon2::password_hash::rand_core::OsRng;
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use diesel::prelude::*;
+
+use super::schema::users;
+use super::Conn;
+
+#[derive(Debug, Queryable)]
+pub struct Identity {
+    pub username: String,
+    pub email: Option<String>,
+}
+
+/// How the user signs their work, e.g. commits in version control.
+pub fn identity(conn: &Conn, user_id: i32) -> QueryResult<Identity> {
+    users::table
+        .find(user_id)
+        .select((users::username, users::email))
+        .first(conn)
+}
+
+pub fn role(conn: &Conn, user_id: i32) -> QueryResult<i32> {
+    users::table
+        .find(user_id)
+        .select(users::role_id)
+        .first(conn)
+}
+
+fn verify(hash: Option<String>, password: &str) -> bool {
+    let hash = match &hash {
+        Some(hash) => hash,
+        None => return false,
+    };
+    PasswordHash::new(hash)
+        .map(|hash| {
+            Argon2::default()
+                .verify_password(password.as_bytes(), &hash)
+                .is_ok()
+        })
+        .unwrap_or(false)
+}
+
+/// ID of the user with the username and password, if any.
+pub fn authenticate(conn: &Conn, username: &str, password: &str) -> QueryResult<Option<i32>> {
+    let user = users::table
+        .filter(users::username.eq(username))
+        .select((users::id, users::password_hash))
+        .first::<(i32, Option<String>)>(conn)
+        .optional()?;
+    Ok(user.and_then(|(id, hash)| Some(id).filter(|_| verify(hash, password))))
+}
+
+/// Whether the user has set a password, i.e. can log in.
+pub fn has_password(conn: &Conn, user_id: i32) -> QueryResult<bool> {
+    users::table
+        .find(user_id)
+        .select(users::password_hash)
+        .first::<Option<String>>(conn)
+        .map(|hash| hash.is_some())
+}
+
+/// Whether the password is the user's. Fails with `NotFound` if there's no
+/// such user.
+pub fn check_password(conn: &Conn, user_id: i32, password: &str) -> QueryResult<bool> {
+    let hash = users::table
+        .find(user_id)
+        .select(users::password_hash)
+        .first(conn)?;
+    Ok(verify(hash, password))
+}
+
+/// Whether there was such a user.
+pub fn set_password(conn: &Conn, user_id: i32, password: &str) -> QueryResult<bool> {
+    let salt = SaltString::generate(&mut OsRng);
+    let hash = Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        // only fails for parameters out of range, and these are the defaults
+        .expect("argon2 with default parameters")
+        .to_string();
+    diesel::update(users::table.find(user_id))
+        .set(users::password_hash.eq(hash))
+        .execute(conn)
+        .map(|n| n > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verify() {
+        let salt = SaltString::generate(&mut OsRng);
+        let hash = Argon2::default()
+            .hash_password(b"correct horse", &salt)
+            .unwrap()
+            .to_string();
+        assert!(verify(Some(hash.clone()), "correct horse"));
+        assert!(!verify(Some(hash), "battery staple"));
+        assert!(!verify(None, ""));
+        assert!(!verify(Some("not a hash".to_owned()), "not a hash"));
+    }
+}
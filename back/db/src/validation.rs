@@ -0,0 +1,335 @@
+//! Persisted results of validating documents' transcripts.
+
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::{Datelike, NaiveDateTime};
+use diesel::prelude::*;
+
+use super::acknowledgments;
+use super::files::{File, EAF};
+use super::schema::{docs, files, mistakes, validation_runs};
+use super::Conn;
+
+/// How many example segments to show per mistake kind.
+const EXAMPLES: usize = 3;
+
+#[derive(Debug, Clone, Insertable)]
+#[table_name = "mistakes"]
+pub struct NewMistake {
+    pub tier: String,
+    pub annotation: String,
+    pub kind: String,
+    pub segment: String,
+    pub start: Option<i32>,
+    pub end: Option<i32>,
+}
+
+pub struct NewRun<'a> {
+    pub doc_id: i32,
+    /// Missing for automatic revalidations.
+    pub user_id: Option<i32>,
+    pub rules_version: &'a str,
+    /// The stored transcript which was validated, if any, and its checksum.
+    pub file_id: Option<i32>,
+    pub checksum: Option<&'a str>,
+}
+
+/// Store the result of validating a document, flagging it as a regression
+/// if more mistakes were found than by the document's previous validation.
+/// Mistakes acknowledged as intentional are stored, but not counted (see
+/// `acknowledgments`). Returns the ID of the new run.
+pub fn record_run(conn: &Conn, run: &NewRun, found: &[NewMistake]) -> QueryResult<i32> {
+    conn.transaction(|| {
+        let acknowledgments = acknowledgments::for_doc(conn, run.doc_id)?;
+        let acknowledged: Vec<bool> = found
+            .iter()
+            .map(|mistake| acknowledgments.iter().any(|a| a.covers(mistake)))
+            .collect();
+        let ignored = acknowledged.iter().filter(|&&a| a).count();
+        let counted = found.len() - ignored;
+        let previous = latest_run(conn, run.doc_id)?;
+        let regression = previous.map_or(false, |p| counted > p.mistakes as usize);
+        diesel::insert_into(validation_runs::table)
+            .values((
+                validation_runs::doc_id.eq(run.doc_id),
+                validation_runs::user_id.eq(run.user_id),
+                validation_runs::mistakes.eq(counted as i32),
+                validation_runs::acknowledged.eq(ignored as i32),
+                validation_runs::rules_version.eq(run.rules_version),
+                validation_runs::file_id.eq(run.file_id),
+                validation_runs::checksum.eq(run.checksum),
+                validation_runs::regression.eq(regression),
+            ))
+            .execute(conn)?;
+        let run_id = validation_runs::table
+            .select(validation_runs::id)
+            .order(validation_runs::id.desc())
+            .first(conn)?;
+        for (mistake, acknowledged) in found.iter().zip(acknowledged) {
+            diesel::insert_into(mistakes::table)
+                .values((
+                    mistakes::run_id.eq(run_id),
+                    mistakes::acknowledged.eq(acknowledged),
+                    mistake,
+                ))
+                .execute(conn)?;
+        }
+        Ok(run_id)
+    })
+}
+
+#[derive(Debug)]
+pub struct Example {
+    pub doc_id: i32,
+    pub tier: String,
+    pub annotation: String,
+    pub segment: String,
+    pub start: Option<i32>,
+    pub end: Option<i32>,
+}
+
+#[derive(Debug)]
+pub struct MistakePattern {
+    pub kind: String,
+    pub count: usize,
+    /// Counts per ISO week, as `(year, week, count)`, oldest first.
+    pub weekly: Vec<(i32, u32, usize)>,
+    /// The most recent occurrences.
+    pub examples: Vec<Example>,
+}
+
+#[derive(Default)]
+struct Tally {
+    count: usize,
+    weekly: BTreeMap<(i32, u32), usize>,
+    examples: Vec<Example>,
+}
+
+/// Kinds of mistakes the user made in their work validated since `since`,
+/// most frequent first.
+pub fn mistake_patterns(
+    conn: &Conn,
+    user_id: i32,
+    since: NaiveDateTime,
+) -> QueryResult<Vec<MistakePattern>> {
+    let rows = mistakes::table
+        .inner_join(validation_runs::table)
+        .filter(validation_runs::user_id.eq(user_id))
+        .filter(validation_runs::created_at.ge(since))
+        .select((
+            validation_runs::doc_id,
+            validation_runs::created_at,
+            mistakes::tier,
+            mistakes::annotation,
+            mistakes::kind,
+            mistakes::segment,
+            mistakes::start,
+            mistakes::end,
+        ))
+        .order(validation_runs::created_at.desc())
+        .load::<(
+            i32,
+            NaiveDateTime,
+            String,
+            String,
+            String,
+            String,
+            Option<i32>,
+            Option<i32>,
+        )>(conn)?;
+
+    let mut patterns: HashMap<String, Tally> = HashMap::new();
+    for (doc_id, created_at, tier, annotation, kind, segment, start, end) in rows {
+        let tally = patterns.entry(kind).or_default();
+        tally.count += 1;
+        let week = created_at.iso_week();
+        *tally.weekly.entry((week.year(), week.week())).or_insert(0) += 1;
+        // rows are ordered newest first
+        if tally.examples.len() < EXAMPLES {
+            tally.examples.push(Example {
+                doc_id,
+                tier,
+                annotation,
+                segment,
+                start,
+                end,
+            });
+        }
+    }
+
+    let mut patterns: Vec<_> = patterns
+        .into_iter()
+        .map(|(kind, tally)| MistakePattern {
+            kind,
+            count: tally.count,
+            weekly: tally
+                .weekly
+                .into_iter()
+                .map(|((y, w), c)| (y, w, c))
+                .collect(),
+            examples: tally.examples,
+        })
+        .collect();
+    patterns.sort_by(|p1, p2| p2.count.cmp(&p1.count).then_with(|| p1.kind.cmp(&p2.kind)));
+    Ok(patterns)
+}
+
+/// IDs of the latest validation run of each document matching the filter.
+fn latest_runs(conn: &Conn, doc_id: Option<i32>, project_id: Option<i32>) -> QueryResult<Vec<i32>> {
+    let mut query = validation_runs::table
+        .inner_join(docs::table)
+        .select((validation_runs::id, validation_runs::doc_id))
+        .into_boxed();
+    if let Some(doc_id) = doc_id {
+        query = query.filter(validation_runs::doc_id.eq(doc_id));
+    }
+    if let Some(project_id) = project_id {
+        query = query.filter(docs::project_id.eq(project_id));
+    }
+    let mut latest = HashMap::new();
+    for (run_id, doc_id) in query.load::<(i32, i32)>(conn)? {
+        let latest = latest.entry(doc_id).or_insert(run_id);
+        *latest = run_id.max(*latest);
+    }
+    Ok(latest.into_values().collect())
+}
+
+fn count_kinds(conn: &Conn, run_ids: Vec<i32>) -> QueryResult<Vec<(String, i64)>> {
+    let mut counts = BTreeMap::new();
+    for kind in mistakes::table
+        .filter(mistakes::run_id.eq_any(run_ids))
+        .select(mistakes::kind)
+        .load::<String>(conn)?
+    {
+        *counts.entry(kind).or_insert(0) += 1;
+    }
+    Ok(counts.into_iter().collect())
+}
+
+/// Number of mistakes of each kind found by the latest validation of the
+/// document.
+pub fn kinds_for_doc(conn: &Conn, doc_id: i32) -> QueryResult<Vec<(String, i64)>> {
+    count_kinds(conn, latest_runs(conn, Some(doc_id), None)?)
+}
+
+/// Number of mistakes of each kind found by the latest validations of all
+/// documents in the project.
+pub fn kinds_for_project(conn: &Conn, project_id: i32) -> QueryResult<Vec<(String, i64)>> {
+    count_kinds(conn, latest_runs(conn, None, Some(project_id))?)
+}
+
+/// Mistakes found by the latest validation of each validated document in
+/// the project, not counting the acknowledged ones, by document ID.
+pub fn outstanding(conn: &Conn, project_id: i32) -> QueryResult<HashMap<i32, i32>> {
+    let run_ids = latest_runs(conn, None, Some(project_id))?;
+    Ok(validation_runs::table
+        .filter(validation_runs::id.eq_any(run_ids))
+        .select((validation_runs::doc_id, validation_runs::mistakes))
+        .load(conn)?
+        .into_iter()
+        .collect())
+}
+
+#[derive(Debug, Queryable)]
+pub struct Run {
+    pub id: i32,
+    pub doc_id: i32,
+    pub created_at: NaiveDateTime,
+    /// Not counting the acknowledged ones.
+    pub mistakes: i32,
+    pub user_id: Option<i32>,
+    pub rules_version: Option<String>,
+    pub file_id: Option<i32>,
+    pub checksum: Option<String>,
+    pub regression: bool,
+    pub acknowledged: i32,
+}
+
+/// The latest validation run of the document, if it's been validated.
+pub fn latest_run(conn: &Conn, doc_id: i32) -> QueryResult<Option<Run>> {
+    validation_runs::table
+        .filter(validation_runs::doc_id.eq(doc_id))
+        .order(validation_runs::id.desc())
+        .first(conn)
+        .optional()
+}
+
+/// Latest validation runs of documents in the project which were done
+/// using rules other than the given (current) version, oldest first.
+pub fn stale_runs(conn: &Conn, project_id: i32, rules_version: &str) -> QueryResult<Vec<Run>> {
+    let run_ids = latest_runs(conn, None, Some(project_id))?;
+    validation_runs::table
+        .filter(validation_runs::id.eq_any(run_ids))
+        .filter(
+            validation_runs::rules_version
+                .ne(rules_version)
+                .or(validation_runs::rules_version.is_null()),
+        )
+        .order(validation_runs::created_at)
+        .load(conn)
+}
+
+/// Latest validation runs of documents in the project which found more
+/// mistakes than the run before them, newest first.
+pub fn regressions(conn: &Conn, project_id: i32) -> QueryResult<Vec<Run>> {
+    let run_ids = latest_runs(conn, None, Some(project_id))?;
+    validation_runs::table
+        .filter(validation_runs::id.eq_any(run_ids))
+        .filter(validation_runs::regression)
+        .order(validation_runs::created_at.desc())
+        .load(conn)
+}
+
+#[derive(Debug)]
+pub struct Transcript {
+    pub project_id: i32,
+    /// The document's latest stored transcript.
+    pub file: File,
+    /// The document's latest validation run, if any.
+    pub run: Option<Run>,
+}
+
+impl Transcript {
+    /// Whether the latest validation is of this transcript and found no
+    /// mistakes besides acknowledged ones.
+    pub fn is_clean(&self) -> bool {
+        self.run.as_ref().map_or(false, |run| {
+            run.file_id == Some(self.file.id) && run.mistakes == 0
+        })
+    }
+}
+
+/// Documents which have a stored transcript, optionally only those in the
+/// given project, along with their latest validation, so that it can be
+/// checked whether it still holds.
+pub fn transcripts(conn: &Conn, project_id: Option<i32>) -> QueryResult<Vec<Transcript>> {
+    let mut query = files::table
+        .inner_join(docs::table)
+        .filter(files::role.eq(EAF))
+        .select((docs::project_id, files::all_columns))
+        .order((files::created_at, files::id))
+        .into_boxed();
+    if let Some(project_id) = project_id {
+        query = query.filter(docs::project_id.eq(project_id));
+    }
+    // oldest first, so the latest file of each document wins
+    let mut latest = BTreeMap::new();
+    for (project_id, file) in query.load::<(i32, File)>(conn)? {
+        latest.insert(file.doc_id, (project_id, file));
+    }
+
+    let mut runs: HashMap<i32, Run> = validation_runs::table
+        .filter(validation_runs::id.eq_any(latest_runs(conn, None, project_id)?))
+        .load::<Run>(conn)?
+        .into_iter()
+        .map(|r| (r.doc_id, r))
+        .collect();
+    Ok(latest
+        .into_iter()
+        .map(|(doc_id, (project_id, file))| Transcript {
+            project_id,
+            file,
+            run: runs.remove(&doc_id),
+        })
+        .collect())
+}
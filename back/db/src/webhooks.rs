@@ -0,0 +1,180 @@
+//! Webhooks notifying external services about events in a project, and the
+//! queue of their deliveries.
+
+use chrono::{Duration, NaiveDateTime};
+use diesel::prelude::*;
+
+use super::schema::{projects, webhook_deliveries, webhooks};
+use super::Conn;
+
+/// A document moved to another state.
+pub const STATE_CHANGED: &str = "document.state_changed";
+/// A document's transcript was validated.
+pub const VALIDATION_COMPLETED: &str = "validation.completed";
+
+const QUEUED: &str = "queued";
+const DONE: &str = "done";
+const FAILED: &str = "failed";
+
+/// How many times a delivery is attempted before giving up on it.
+pub const MAX_ATTEMPTS: i32 = 5;
+
+#[derive(Debug, Queryable)]
+pub struct Webhook {
+    pub id: i32,
+    pub project_id: i32,
+    pub url: String,
+    pub secret: String,
+    pub active: bool,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Debug, Insertable, AsChangeset)]
+#[table_name = "webhooks"]
+pub struct WebhookData {
+    pub url: String,
+    pub secret: String,
+    pub active: bool,
+}
+
+#[derive(Debug, Queryable)]
+pub struct Delivery {
+    pub id: i32,
+    pub webhook_id: i32,
+    pub event: String,
+    pub payload: String,
+    pub state: String,
+    pub attempts: i32,
+    pub status: Option<i32>,
+    pub error: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub next_attempt_at: NaiveDateTime,
+}
+
+pub fn get(conn: &Conn, id: i32) -> QueryResult<Webhook> {
+    webhooks::table.find(id).first(conn)
+}
+
+pub fn for_project(conn: &Conn, project_id: i32) -> QueryResult<Vec<Webhook>> {
+    webhooks::table
+        .filter(webhooks::project_id.eq(project_id))
+        .order(webhooks::id)
+        .load(conn)
+}
+
+/// Fails with `NotFound` if there's no such project.
+pub fn add(conn: &Conn, project_id: i32, data: &WebhookData) -> QueryResult<i32> {
+    conn.transaction(|| {
+        projects::table
+            .find(project_id)
+            .select(projects::id)
+            .first::<i32>(conn)?;
+        diesel::insert_into(webhooks::table)
+            .values((webhooks::project_id.eq(project_id), data))
+            .execute(conn)?;
+        webhooks::table
+            .select(webhooks::id)
+            .order(webhooks::id.desc())
+            .first(conn)
+    })
+}
+
+/// Returns whether the webhook exists.
+pub fn update(conn: &Conn, id: i32, data: &WebhookData) -> QueryResult<bool> {
+    diesel::update(webhooks::table.find(id))
+        .set(data)
+        .execute(conn)
+        .map(|n| n > 0)
+}
+
+/// Remove the webhook along with its deliveries. Returns whether it
+/// existed.
+pub fn remove(conn: &Conn, id: i32) -> QueryResult<bool> {
+    conn.transaction(|| {
+        diesel::delete(webhook_deliveries::table.filter(webhook_deliveries::webhook_id.eq(id)))
+            .execute(conn)?;
+        diesel::delete(webhooks::table.find(id))
+            .execute(conn)
+            .map(|n| n > 0)
+    })
+}
+
+/// Queue delivery of an event to all active webhooks of the project.
+/// Returns how many deliveries were queued.
+pub fn enqueue(conn: &Conn, project_id: i32, event: &str, payload: &str) -> QueryResult<usize> {
+    conn.transaction(|| {
+        let hook_ids = webhooks::table
+            .filter(webhooks::project_id.eq(project_id))
+            .filter(webhooks::active)
+            .select(webhooks::id)
+            .load::<i32>(conn)?;
+        for &webhook_id in &hook_ids {
+            diesel::insert_into(webhook_deliveries::table)
+                .values((
+                    webhook_deliveries::webhook_id.eq(webhook_id),
+                    webhook_deliveries::event.eq(event),
+                    webhook_deliveries::payload.eq(payload),
+                ))
+                .execute(conn)?;
+        }
+        Ok(hook_ids.len())
+    })
+}
+
+/// Queued deliveries whose next attempt is due at `now`, oldest first,
+/// along with the URL and secret of their webhook.
+pub fn due(conn: &Conn, now: NaiveDateTime) -> QueryResult<Vec<(Delivery, String, String)>> {
+    webhook_deliveries::table
+        .inner_join(webhooks::table)
+        .filter(webhook_deliveries::state.eq(QUEUED))
+        .filter(webhook_deliveries::next_attempt_at.le(now))
+        .select((
+            webhook_deliveries::all_columns,
+            webhooks::url,
+            webhooks::secret,
+        ))
+        .order(webhook_deliveries::id)
+        .load(conn)
+}
+
+/// How long to wait before attempting a delivery again after the given
+/// number of failed attempts: 1, 4, 16… minutes.
+fn backoff(attempts: i32) -> Duration {
+    Duration::minutes(4i64.pow(attempts.max(1) as u32 - 1))
+}
+
+/// Record the outcome of an attempt to deliver: the HTTP status, if any
+/// response was received, and an error if the delivery failed.
+pub fn finish(
+    conn: &Conn,
+    delivery: &Delivery,
+    status: Option<i32>,
+    error: Option<String>,
+    now: NaiveDateTime,
+) -> QueryResult<()> {
+    let attempts = delivery.attempts + 1;
+    let state = match error {
+        None => DONE,
+        Some(_) if attempts >= MAX_ATTEMPTS => FAILED,
+        Some(_) => QUEUED,
+    };
+    diesel::update(webhook_deliveries::table.find(delivery.id))
+        .set((
+            webhook_deliveries::state.eq(state),
+            webhook_deliveries::attempts.eq(attempts),
+            webhook_deliveries::status.eq(status),
+            webhook_deliveries::error.eq(error),
+            webhook_deliveries::next_attempt_at.eq(now + backoff(attempts)),
+        ))
+        .execute(conn)
+        .map(|_| ())
+}
+
+/// The webhook's most recent deliveries, newest first.
+pub fn deliveries(conn: &Conn, webhook_id: i32, limit: usize) -> QueryResult<Vec<Delivery>> {
+    webhook_deliveries::table
+        .filter(webhook_deliveries::webhook_id.eq(webhook_id))
+        .order(webhook_deliveries::id.desc())
+        .limit(limit as i64)
+        .load(conn)
+}
@@ -0,0 +1,90 @@
+//! Reading long transcripts, e.g. `cargo bench -p eaf`. The
+//! synthetic document has 4 speakers taking turns, each with a dependent
+//! tier, i.e. about as many annotations as 3 hours of conversation.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use eaf::annotations;
+use eaf::document::Eaf;
+use eaf::parser::ParserConfig;
+use eaf::timeslots;
+
+const TURNS: usize = 10_000;
+const SPEAKERS: &[&str] = &["JD", "MK", "PN", "ZS"];
+
+fn synthetic() -> String {
+    let mut xml = String::from(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<ANNOTATION_DOCUMENT AUTHOR="bench" DATE="2020-01-01T00:00:00+01:00" FORMAT="3.0" VERSION="3.0">
+    <HEADER MEDIA_FILE="" TIME_UNITS="milliseconds"/>
+    <TIME_ORDER>
+"#,
+    );
+    for i in 0..=TURNS {
+        xml.push_str(&format!(
+            "        <TIME_SLOT TIME_SLOT_ID=\"ts{}\" TIME_VALUE=\"{}\"/>\n",
+            i,
+            i * 1000
+        ));
+    }
+    xml.push_str("    </TIME_ORDER>\n");
+    for (s, speaker) in SPEAKERS.iter().enumerate() {
+        xml.push_str(&format!(
+            "    <TIER LINGUISTIC_TYPE_REF=\"ort\" PARTICIPANT=\"{0}\" TIER_ID=\"ort@{0}\">\n",
+            speaker
+        ));
+        for i in (s..TURNS).step_by(SPEAKERS.len()) {
+            xml.push_str(&format!(
+                "        <ANNOTATION>\n            <ALIGNABLE_ANNOTATION ANNOTATION_ID=\"a{0}\" \
+                 TIME_SLOT_REF1=\"ts{0}\" TIME_SLOT_REF2=\"ts{1}\">\n                \
+                 <ANNOTATION_VALUE>no tak &lt;SM (jo)&gt; [to je] {0}</ANNOTATION_VALUE>\n            \
+                 </ALIGNABLE_ANNOTATION>\n        </ANNOTATION>\n",
+                i,
+                i + 1
+            ));
+        }
+        xml.push_str("    </TIER>\n");
+        xml.push_str(&format!(
+            "    <TIER LINGUISTIC_TYPE_REF=\"fon\" PARENT_REF=\"ort@{0}\" TIER_ID=\"fon@{0}\">\n",
+            speaker
+        ));
+        for i in (s..TURNS).step_by(SPEAKERS.len()) {
+            xml.push_str(&format!(
+                "        <ANNOTATION>\n            <REF_ANNOTATION ANNOTATION_ID=\"f{0}\" \
+                 ANNOTATION_REF=\"a{0}\">\n                \
+                 <ANNOTATION_VALUE>no tag jo to je</ANNOTATION_VALUE>\n            \
+                 </REF_ANNOTATION>\n        </ANNOTATION>\n",
+                i
+            ));
+        }
+        xml.push_str("    </TIER>\n");
+    }
+    xml.push_str(
+        "    <LINGUISTIC_TYPE LINGUISTIC_TYPE_ID=\"ort\" TIME_ALIGNABLE=\"true\"/>\n    \
+         <LINGUISTIC_TYPE CONSTRAINTS=\"Symbolic_Association\" LINGUISTIC_TYPE_ID=\"fon\" \
+         TIME_ALIGNABLE=\"false\"/>\n</ANNOTATION_DOCUMENT>\n",
+    );
+    xml
+}
+
+fn bench_annotations(c: &mut Criterion) {
+    let xml = synthetic();
+    c.bench_function("annotations", |b| {
+        b.iter(|| annotations::read(&xml).unwrap())
+    });
+}
+
+fn bench_timeslots(c: &mut Criterion) {
+    let xml = synthetic();
+    c.bench_function("timeslots", |b| b.iter(|| timeslots::check(&xml).unwrap()));
+}
+
+fn bench_document(c: &mut Criterion) {
+    let xml = synthetic();
+    let config = ParserConfig::from_args(&[] as &[&str], &[] as &[&str], &["[a-z]"], &["SM"]);
+    c.bench_function("document", |b| {
+        b.iter(|| Eaf::parse(&xml, &config).unwrap())
+    });
+}
+
+criterion_group!(benches, bench_annotations, bench_timeslots, bench_document);
+criterion_main!(benches);
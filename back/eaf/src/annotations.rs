@@ -0,0 +1,404 @@
+//! Quick extraction and rewriting of annotation values in an EAF, e.g. for
+//! validating a stored transcript, without building a full document model.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use sxd_document::dom::{ChildOfElement, Element};
+use sxd_document::{parser, writer};
+
+use super::document::Alignment;
+use super::stream::{self, Skeleton};
+use super::template::annotation_document;
+use super::tiers::TierMapping;
+
+#[derive(Debug)]
+pub struct ReadError(pub(crate) String);
+
+impl fmt::Display for ReadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "can't read EAF: {}", self.0)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Annotation {
+    pub tier: String,
+    pub participant: Option<String>,
+    pub linguistic_type: String,
+    pub id: String,
+    pub value: String,
+    /// In milliseconds. Reference annotations take them from their
+    /// parents, unaligned time slots are interpolated; they're only missing
+    /// if there's nothing to go by.
+    pub start: Option<u32>,
+    pub end: Option<u32>,
+    /// Whether `start` or `end` is interpolated rather than aligned with
+    /// the recording.
+    pub interpolated: bool,
+}
+
+impl Annotation {
+    /// Whether the annotation is on a transcript tier according to the
+    /// mapping, i.e. one belonging to a speaker. Without any rules, there's
+    /// no telling transcript tiers from the others, so all of them count.
+    pub fn is_transcript(&self, mapping: &TierMapping) -> bool {
+        mapping.rules().is_empty()
+            || mapping
+                .nickname(&self.tier, self.participant.as_deref())
+                .is_some()
+    }
+}
+
+pub(crate) fn children(element: Element<'_>) -> impl Iterator<Item = Element<'_>> {
+    element.children().into_iter().filter_map(|c| match c {
+        ChildOfElement::Element(e) => Some(e),
+        _ => None,
+    })
+}
+
+/// Child elements with the given name.
+pub(crate) fn named<'d>(
+    element: Element<'d>,
+    name: &'static str,
+) -> impl Iterator<Item = Element<'d>> {
+    children(element).filter(move |e| e.name().local_part() == name)
+}
+
+/// Times of the time slots, in milliseconds, and whether they're
+/// interpolated.
+pub(crate) type Times<'d> = HashMap<&'d str, (u32, bool)>;
+
+/// Spread unaligned slots evenly between the nearest aligned ones in
+/// `chain`, leaving those before the first and after the last aligned one
+/// alone.
+fn interpolate<'d>(chain: &[&'d str], times: &mut Times<'d>) {
+    let mut previous: Option<(usize, u32)> = None;
+    for (i, slot) in chain.iter().enumerate() {
+        let value = match times.get(slot) {
+            Some(&(value, false)) => value,
+            _ => continue,
+        };
+        if let Some((j, previous_value)) = previous {
+            let steps = (i - j) as u64;
+            for (k, between) in chain[j + 1..i].iter().enumerate() {
+                if !times.contains_key(between) && previous_value <= value {
+                    let offset = u64::from(value - previous_value) * (k as u64 + 1) / steps;
+                    times.insert(between, (previous_value + offset as u32, true));
+                }
+            }
+        }
+        previous = Some((i, value));
+    }
+}
+
+/// Times of all time slots which have one or can be interpolated, the way
+/// ELAN does it: unaligned slots are spread evenly between the aligned ones
+/// around them, first along chains of adjacent annotations on the same tier
+/// (as in time subdivision), then in TIME_ORDER.
+pub(crate) fn slot_times(eaf: &Skeleton) -> Times<'_> {
+    let mut times: Times<'_> = eaf
+        .time_slots
+        .iter()
+        .filter_map(|slot| Some((slot.id.as_str(), (slot.value?, false))))
+        .collect();
+    for tier in &eaf.tiers {
+        let mut chain = vec![];
+        for annotation in &tier.annotations {
+            let (start, end) = match &annotation.alignment {
+                Alignment::Alignable {
+                    start_slot,
+                    end_slot,
+                } if !start_slot.is_empty() && !end_slot.is_empty() => {
+                    (start_slot.as_str(), end_slot.as_str())
+                }
+                _ => continue,
+            };
+            if chain.last() != Some(&start) {
+                interpolate(&chain, &mut times);
+                chain = vec![start];
+            }
+            chain.push(end);
+        }
+        interpolate(&chain, &mut times);
+    }
+    let order: Vec<_> = eaf.time_slots.iter().map(|slot| slot.id.as_str()).collect();
+    interpolate(&order, &mut times);
+    times
+}
+
+/// Start, end and whether either is interpolated.
+pub(crate) type Time = (Option<u32>, Option<u32>, bool);
+
+/// Times of annotations, tier by tier in document order.
+pub(crate) fn annotation_times(eaf: &Skeleton) -> Vec<Vec<Time>> {
+    let slots = slot_times(eaf);
+
+    // alignable annotations get their times from their slots, reference
+    // ones from their parents, those sharing a parent on the same tier
+    // (symbolic subdivision) each getting an even share of its time
+    let mut times: HashMap<&str, Time> = HashMap::new();
+    let mut references: Vec<(&str, &str, &str)> = vec![];
+    for tier in &eaf.tiers {
+        for annotation in &tier.annotations {
+            match &annotation.alignment {
+                Alignment::Ref { parent, .. } => {
+                    references.push((&tier.id, &annotation.id, parent));
+                }
+                Alignment::Alignable {
+                    start_slot,
+                    end_slot,
+                } => {
+                    let (start, end) =
+                        (slots.get(start_slot.as_str()), slots.get(end_slot.as_str()));
+                    let interpolated = [start, end].iter().any(|t| t.map_or(false, |t| t.1));
+                    let time = (start.map(|t| t.0), end.map(|t| t.0), interpolated);
+                    times.insert(&annotation.id, time);
+                }
+            }
+        }
+    }
+    // parents may be references themselves, resolve them layer by layer
+    while !references.is_empty() {
+        let (resolved, pending): (Vec<_>, Vec<_>) = references
+            .into_iter()
+            .partition(|(_, _, parent)| times.contains_key(parent));
+        if resolved.is_empty() {
+            break;
+        }
+        let mut siblings: HashMap<(&str, &str), Vec<&str>> = HashMap::new();
+        for (tier, id, parent) in resolved {
+            siblings.entry((tier, parent)).or_default().push(id);
+        }
+        for ((_, parent), ids) in siblings {
+            let (start, end, interpolated) = times[parent];
+            let n = ids.len() as u64;
+            for (i, id) in ids.into_iter().enumerate() {
+                let time = match (start, end) {
+                    (Some(start), Some(end)) if n > 1 && start <= end => {
+                        let share = |k: u64| start + (u64::from(end - start) * k / n) as u32;
+                        (Some(share(i as u64)), Some(share(i as u64 + 1)), true)
+                    }
+                    _ => (start, end, interpolated),
+                };
+                times.insert(id, time);
+            }
+        }
+        references = pending;
+    }
+
+    eaf.tiers
+        .iter()
+        .map(|tier| {
+            tier.annotations
+                .iter()
+                .map(|a| times.get(a.id.as_str()).copied().unwrap_or_default())
+                .collect()
+        })
+        .collect()
+}
+
+/// All annotations, alignable and reference ones alike, tier by tier in
+/// document order.
+pub fn read(xml: &str) -> Result<Vec<Annotation>, ReadError> {
+    let eaf = stream::read(xml.as_bytes())?;
+    let times = annotation_times(&eaf);
+    let mut annotations = vec![];
+    for (tier, times) in eaf.tiers.into_iter().zip(times) {
+        for (annotation, (start, end, interpolated)) in tier.annotations.into_iter().zip(times) {
+            annotations.push(Annotation {
+                tier: tier.id.clone(),
+                participant: tier.participant.clone(),
+                linguistic_type: tier.linguistic_type.clone(),
+                id: annotation.id,
+                value: annotation.value,
+                start,
+                end,
+                interpolated,
+            });
+        }
+    }
+    Ok(annotations)
+}
+
+/// Replace annotation values with whatever `f` returns for them, leaving
+/// them alone where it returns `None`. Annotations without a value element
+/// can't be rewritten and are skipped.
+pub fn rewrite<F>(xml: &str, mut f: F) -> Result<String, ReadError>
+where
+    F: FnMut(&Annotation) -> Option<String>,
+{
+    // in the same order as the elements below
+    let mut annotations = read(xml)?.into_iter();
+    let package = parser::parse(xml).map_err(|e| ReadError(format!("{:?}", e)))?;
+    let doc = package.as_document();
+    let root = annotation_document(&doc)
+        .ok_or_else(|| ReadError("missing ANNOTATION_DOCUMENT".to_owned()))?;
+
+    // TIER > ANNOTATION > ALIGNABLE_ANNOTATION|REF_ANNOTATION > ANNOTATION_VALUE
+    for tier in named(root, "TIER") {
+        for inner in named(tier, "ANNOTATION").flat_map(children) {
+            let annotation = annotations
+                .next()
+                .ok_or_else(|| ReadError("annotations changed while rewriting".to_owned()))?;
+            if let Some(element) = named(inner, "ANNOTATION_VALUE").next() {
+                if let Some(value) = f(&annotation) {
+                    element.set_text(&value);
+                }
+            }
+        }
+    }
+    let mut out = vec![];
+    // writing to a Vec can't fail
+    writer::format_document(&doc, &mut out).unwrap();
+    String::from_utf8(out).map_err(|e| ReadError(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EAF: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<ANNOTATION_DOCUMENT AUTHOR="" DATE="2020-01-01T00:00:00+01:00" FORMAT="3.0" VERSION="3.0">
+    <HEADER MEDIA_FILE="" TIME_UNITS="milliseconds"/>
+    <TIME_ORDER>
+        <TIME_SLOT TIME_SLOT_ID="ts1" TIME_VALUE="0"/>
+        <TIME_SLOT TIME_SLOT_ID="ts2" TIME_VALUE="100"/>
+    </TIME_ORDER>
+    <TIER LINGUISTIC_TYPE_REF="ort" PARTICIPANT="Jana" TIER_ID="ort@Jana">
+        <ANNOTATION>
+            <ALIGNABLE_ANNOTATION ANNOTATION_ID="a1" TIME_SLOT_REF1="ts1" TIME_SLOT_REF2="ts2">
+                <ANNOTATION_VALUE>no tak &lt;SM ahoj&gt;</ANNOTATION_VALUE>
+            </ALIGNABLE_ANNOTATION>
+        </ANNOTATION>
+    </TIER>
+    <TIER LINGUISTIC_TYPE_REF="fon" PARENT_REF="ort@Jana" TIER_ID="fon@Jana">
+        <ANNOTATION>
+            <REF_ANNOTATION ANNOTATION_ID="a2" ANNOTATION_REF="a1">
+                <ANNOTATION_VALUE/>
+            </REF_ANNOTATION>
+        </ANNOTATION>
+    </TIER>
+</ANNOTATION_DOCUMENT>"#;
+
+    #[test]
+    fn test_read() {
+        assert_eq!(
+            read(EAF).unwrap(),
+            vec![
+                Annotation {
+                    tier: "ort@Jana".to_owned(),
+                    participant: Some("Jana".to_owned()),
+                    linguistic_type: "ort".to_owned(),
+                    id: "a1".to_owned(),
+                    value: "no tak <SM ahoj>".to_owned(),
+                    start: Some(0),
+                    end: Some(100),
+                    interpolated: false,
+                },
+                Annotation {
+                    tier: "fon@Jana".to_owned(),
+                    participant: None,
+                    linguistic_type: "fon".to_owned(),
+                    id: "a2".to_owned(),
+                    value: String::new(),
+                    start: Some(0),
+                    end: Some(100),
+                    interpolated: false,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_interpolate() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<ANNOTATION_DOCUMENT AUTHOR="" DATE="2020-01-01T00:00:00+01:00" FORMAT="3.0" VERSION="3.0">
+    <HEADER MEDIA_FILE="" TIME_UNITS="milliseconds"/>
+    <TIME_ORDER>
+        <TIME_SLOT TIME_SLOT_ID="ts1" TIME_VALUE="0"/>
+        <TIME_SLOT TIME_SLOT_ID="ts2" TIME_VALUE="300"/>
+        <TIME_SLOT TIME_SLOT_ID="ts3"/>
+        <TIME_SLOT TIME_SLOT_ID="ts4"/>
+        <TIME_SLOT TIME_SLOT_ID="ts5"/>
+    </TIME_ORDER>
+    <TIER LINGUISTIC_TYPE_REF="ort" TIER_ID="ort">
+        <ANNOTATION>
+            <ALIGNABLE_ANNOTATION ANNOTATION_ID="a1" TIME_SLOT_REF1="ts1" TIME_SLOT_REF2="ts3">
+                <ANNOTATION_VALUE>no</ANNOTATION_VALUE>
+            </ALIGNABLE_ANNOTATION>
+        </ANNOTATION>
+        <ANNOTATION>
+            <ALIGNABLE_ANNOTATION ANNOTATION_ID="a2" TIME_SLOT_REF1="ts3" TIME_SLOT_REF2="ts4">
+                <ANNOTATION_VALUE>tak</ANNOTATION_VALUE>
+            </ALIGNABLE_ANNOTATION>
+        </ANNOTATION>
+        <ANNOTATION>
+            <ALIGNABLE_ANNOTATION ANNOTATION_ID="a3" TIME_SLOT_REF1="ts4" TIME_SLOT_REF2="ts2">
+                <ANNOTATION_VALUE>jo</ANNOTATION_VALUE>
+            </ALIGNABLE_ANNOTATION>
+        </ANNOTATION>
+        <ANNOTATION>
+            <ALIGNABLE_ANNOTATION ANNOTATION_ID="a4" TIME_SLOT_REF1="ts2" TIME_SLOT_REF2="ts5">
+                <ANNOTATION_VALUE>hm</ANNOTATION_VALUE>
+            </ALIGNABLE_ANNOTATION>
+        </ANNOTATION>
+    </TIER>
+    <TIER LINGUISTIC_TYPE_REF="word" PARENT_REF="ort" TIER_ID="word">
+        <ANNOTATION>
+            <REF_ANNOTATION ANNOTATION_ID="a5" ANNOTATION_REF="a6">
+                <ANNOTATION_VALUE>x</ANNOTATION_VALUE>
+            </REF_ANNOTATION>
+        </ANNOTATION>
+        <ANNOTATION>
+            <REF_ANNOTATION ANNOTATION_ID="a6" ANNOTATION_REF="a1">
+                <ANNOTATION_VALUE>n</ANNOTATION_VALUE>
+            </REF_ANNOTATION>
+        </ANNOTATION>
+        <ANNOTATION>
+            <REF_ANNOTATION ANNOTATION_ID="a7" ANNOTATION_REF="a1">
+                <ANNOTATION_VALUE>o</ANNOTATION_VALUE>
+            </REF_ANNOTATION>
+        </ANNOTATION>
+    </TIER>
+</ANNOTATION_DOCUMENT>"#;
+        let times: Vec<_> = read(xml)
+            .unwrap()
+            .into_iter()
+            .map(|a| (a.id, a.start, a.end, a.interpolated))
+            .collect();
+        let time = |id: &str, start, end, interpolated| (id.to_owned(), start, end, interpolated);
+        assert_eq!(
+            times,
+            vec![
+                time("a1", Some(0), Some(100), true),
+                time("a2", Some(100), Some(200), true),
+                time("a3", Some(200), Some(300), true),
+                // nothing to go by after the last aligned slot
+                time("a4", Some(300), None, false),
+                time("a5", Some(0), Some(50), true),
+                time("a6", Some(0), Some(50), true),
+                time("a7", Some(50), Some(100), true),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rewrite() {
+        let xml = rewrite(EAF, |a| {
+            if a.tier == "ort@Jana" {
+                Some(a.value.replace("SM", "SJ"))
+            } else {
+                None
+            }
+        })
+        .unwrap();
+        let values: Vec<_> = read(&xml).unwrap().into_iter().map(|a| a.value).collect();
+        assert_eq!(values, vec!["no tak <SJ ahoj>", ""]);
+    }
+
+    #[test]
+    fn test_not_eaf() {
+        assert!(read("<html/>").is_err());
+    }
+}
@@ -0,0 +1,370 @@
+//! Strip an EAF of information identifying the speakers, for sharing
+//! transcripts outside the project.
+//!
+//! Speaker nicknames are replaced by pseudonyms in tier IDs, participants
+//! and references between tiers, as well as wherever the speakers mention
+//! each other in the annotations. Media links, the author and tier
+//! annotators are dropped, as are header properties other than
+//! `lastUsedAnnotationId`.
+//!
+//! Other names can be marked up in the transcripts themselves, with angle
+//! spans configured for anonymization (see
+//! `ParserConfig::with_anonymized`), e.g. `<NP Jana Nováková>`. Their
+//! tokens are replaced by placeholders (see `Placeholders`).
+
+use std::collections::HashMap;
+use std::fmt;
+
+use regex::Regex;
+use sxd_document::dom::{ChildOfElement, Element};
+use sxd_document::{parser, writer};
+
+use super::parser::{Node, Parser, ParserConfig};
+use super::template::{annotation_document, MEDIA};
+use super::tiers::TierMapping;
+use super::tokenizer::{DelimKind, Token};
+
+#[derive(Debug)]
+pub struct AnonymizeError(String);
+
+impl fmt::Display for AnonymizeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "can't anonymize EAF: {}", self.0)
+    }
+}
+
+fn children(element: Element<'_>) -> impl Iterator<Item = Element<'_>> {
+    element.children().into_iter().filter_map(|c| match c {
+        ChildOfElement::Element(e) => Some(e),
+        _ => None,
+    })
+}
+
+/// Whole-word occurrences of any of the names.
+fn names_regex<'n, I: Iterator<Item = &'n String>>(names: I) -> Option<Regex> {
+    let mut names: Vec<_> = names.map(|n| regex::escape(n)).collect();
+    if names.is_empty() {
+        return None;
+    }
+    // prefer longer names, e.g. "Jana Nováková" over "Jana"
+    names.sort_unstable_by_key(|n| std::cmp::Reverse(n.len()));
+    Some(Regex::new(&format!(r"\b(?:{})\b", names.join("|"))).unwrap())
+}
+
+/// Anonymize the EAF. Speakers are identified using the mapping; tiers it
+/// doesn't cover are attributed to a speaker whose nickname occurs in the
+/// tier ID, or failing that, treated as belonging to a speaker named after
+/// the participant (or the tier ID itself). `pseudonym` must consistently
+/// return the same pseudonym for the same nickname.
+pub fn anonymize<F>(
+    xml: &str,
+    mapping: &TierMapping,
+    mut pseudonym: F,
+) -> Result<String, AnonymizeError>
+where
+    F: FnMut(&str) -> String,
+{
+    let package = parser::parse(xml).map_err(|e| AnonymizeError(format!("{:?}", e)))?;
+    let doc = package.as_document();
+    let root = annotation_document(&doc)
+        .ok_or_else(|| AnonymizeError("missing ANNOTATION_DOCUMENT".to_owned()))?;
+    root.set_attribute_value("AUTHOR", "");
+
+    let tiers: Vec<_> = children(root)
+        .filter(|e| e.name().local_part() == "TIER")
+        .collect();
+    let mut pseudonyms = HashMap::new();
+    for tier in &tiers {
+        let tier_id = tier.attribute_value("TIER_ID").unwrap_or_default();
+        if let Some(nickname) = mapping.nickname(tier_id, tier.attribute_value("PARTICIPANT")) {
+            pseudonyms
+                .entry(nickname.to_owned())
+                .or_insert_with(|| pseudonym(nickname));
+        }
+    }
+    let known = names_regex(pseudonyms.keys());
+    for tier in &tiers {
+        let tier_id = tier.attribute_value("TIER_ID").unwrap_or_default();
+        let participant = tier.attribute_value("PARTICIPANT");
+        let mentioned = known.as_ref().map_or(false, |re| re.is_match(tier_id));
+        if mapping.nickname(tier_id, participant).is_none() && !mentioned {
+            let name = participant.filter(|p| !p.is_empty()).unwrap_or(tier_id);
+            pseudonyms
+                .entry(name.to_owned())
+                .or_insert_with(|| pseudonym(name));
+        }
+    }
+
+    let names = match names_regex(pseudonyms.keys()) {
+        Some(names) => names,
+        // no tiers, nothing to rename
+        None => Regex::new(r"[^\s\S]").unwrap(),
+    };
+    let rename = |s: &str| -> String {
+        names
+            .replace_all(s, |caps: &regex::Captures| pseudonyms[&caps[0]].clone())
+            .into_owned()
+    };
+    for tier in &tiers {
+        for &attr in &["TIER_ID", "PARTICIPANT", "PARENT_REF"] {
+            if let Some(value) = tier.attribute_value(attr) {
+                let renamed = rename(value);
+                tier.set_attribute_value(attr, &renamed);
+            }
+        }
+        tier.remove_attribute("ANNOTATOR");
+        rewrite_values(*tier, &rename);
+    }
+
+    for header in children(root).filter(|e| e.name().local_part() == "HEADER") {
+        header.set_attribute_value("MEDIA_FILE", "");
+        for child in children(header) {
+            match child.name().local_part() {
+                name if MEDIA.contains(&name) => child.remove_from_parent(),
+                "PROPERTY" if child.attribute_value("NAME") != Some("lastUsedAnnotationId") => {
+                    child.remove_from_parent()
+                }
+                _ => {}
+            }
+        }
+    }
+
+    write(&doc)
+}
+
+/// Rewrite the values of the tier's annotations, leaving those `rewrite`
+/// doesn't change untouched.
+fn rewrite_values<F: FnMut(&str) -> String>(tier: Element<'_>, mut rewrite: F) {
+    for annotation in children(tier).flat_map(children) {
+        for value in children(annotation).filter(|e| e.name().local_part() == "ANNOTATION_VALUE") {
+            let text: String = value
+                .children()
+                .into_iter()
+                .filter_map(|c| c.text())
+                .map(|t| t.text())
+                .collect();
+            let rewritten = rewrite(&text);
+            if rewritten != text {
+                value.set_text(&rewritten);
+            }
+        }
+    }
+}
+
+fn write(doc: &sxd_document::dom::Document<'_>) -> Result<String, AnonymizeError> {
+    let mut out = vec![];
+    // writing to a Vec can't fail
+    writer::format_document(doc, &mut out).unwrap();
+    String::from_utf8(out).map_err(|e| AnonymizeError(e.to_string()))
+}
+
+/// Placeholders for what's in anonymization spans: `NP1`, `NP2` etc. in
+/// order of first appearance. A referent is identified by the span's
+/// tokens, lowercased, so reusing the placeholders across the segments (or
+/// documents) of an export keeps them consistent, e.g. `<NP Petr>` and
+/// `<NP petr>` both become `<NP NP1>`.
+#[derive(Debug, Default)]
+pub struct Placeholders(HashMap<String, String>);
+
+impl Placeholders {
+    pub fn get(&mut self, referent: &str) -> String {
+        let next = self.0.len() + 1;
+        self.0
+            .entry(referent.to_lowercase())
+            .or_insert_with(|| format!("NP{}", next))
+            .clone()
+    }
+
+    /// The segment with each token in an anonymization span replaced by the
+    /// span's placeholder. Tokens are replaced one by one, so that words
+    /// keep their numbering (cf. `conllu`) and `<NP Jana Nováková>` becomes
+    /// `<NP NP1 NP1>`. Nested spans count as part of the outermost one.
+    /// Spans with invalid codes aren't recognized as anything, so only
+    /// validated transcripts should be relied on to come out clean.
+    pub fn scrub(&mut self, config: &ParserConfig, value: &str) -> String {
+        let parsed = Parser::parse(config, config.tokenize(value));
+        // per open angle span, whether it's an anonymization span
+        let mut angles: Vec<bool> = vec![];
+        let mut spans: Vec<Vec<Token>> = vec![];
+        let mut inside = false;
+        for node in &parsed.nodes {
+            match node {
+                Node::Open(DelimKind::Angle) => angles.push(false),
+                Node::Close(DelimKind::Angle) => {
+                    angles.pop();
+                    inside &= angles.contains(&true);
+                }
+                Node::AttrList(attrs) => {
+                    // or'ed, as spans nested where they aren't allowed
+                    // have their codes but no `Open` of their own
+                    if let Some(anonymized) = angles.last_mut() {
+                        *anonymized |= attrs.iter().any(|attr| config.anonymizes(&attr.key));
+                    }
+                }
+                Node::Token(token) if angles.contains(&true) => {
+                    if !inside {
+                        spans.push(vec![]);
+                        inside = true;
+                    }
+                    if let Some(span) = spans.last_mut() {
+                        span.push(*token);
+                    }
+                }
+                _ => {}
+            }
+        }
+        if spans.is_empty() {
+            return value.to_owned();
+        }
+
+        let mut replacements = vec![];
+        for span in spans {
+            let words: Vec<_> = span
+                .iter()
+                .map(|token| &parsed.source[token.start..token.end])
+                .collect();
+            let placeholder = self.get(&words.join(" "));
+            replacements.extend(span.into_iter().map(|token| (token, placeholder.clone())));
+        }
+        replacements.sort_unstable_by_key(|(token, _)| token.start);
+        let mut scrubbed = String::new();
+        let mut end = 0;
+        for (token, placeholder) in replacements {
+            scrubbed.push_str(&parsed.source[end..token.start]);
+            scrubbed.push_str(&placeholder);
+            end = token.end;
+        }
+        scrubbed.push_str(&parsed.source[end..]);
+        scrubbed
+    }
+
+    /// Scrub the values of all annotations in the EAF, see `scrub`. Unlike
+    /// `anonymize`, this leaves everything else as it is, so it's usually
+    /// combined with it.
+    pub fn scrub_eaf(
+        &mut self,
+        xml: &str,
+        config: &ParserConfig,
+    ) -> Result<String, AnonymizeError> {
+        let package = parser::parse(xml).map_err(|e| AnonymizeError(format!("{:?}", e)))?;
+        let doc = package.as_document();
+        let root = annotation_document(&doc)
+            .ok_or_else(|| AnonymizeError("missing ANNOTATION_DOCUMENT".to_owned()))?;
+        for tier in children(root).filter(|e| e.name().local_part() == "TIER") {
+            rewrite_values(tier, |value| self.scrub(config, value));
+        }
+        write(&doc)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tiers::{TierPattern, TierSource};
+
+    const EAF: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<ANNOTATION_DOCUMENT AUTHOR="Karel Novák" DATE="2020-01-01T00:00:00+01:00" FORMAT="3.0" VERSION="3.0">
+    <HEADER MEDIA_FILE="" TIME_UNITS="milliseconds">
+        <MEDIA_DESCRIPTOR MEDIA_URL="file:///home/karel/Jana.wav" MIME_TYPE="audio/x-wav"/>
+        <PROPERTY NAME="URN">urn:nl-mpi-tools-elan-eaf:karel</PROPERTY>
+        <PROPERTY NAME="lastUsedAnnotationId">2</PROPERTY>
+    </HEADER>
+    <TIME_ORDER>
+        <TIME_SLOT TIME_SLOT_ID="ts1" TIME_VALUE="0"/>
+        <TIME_SLOT TIME_SLOT_ID="ts2" TIME_VALUE="100"/>
+    </TIME_ORDER>
+    <TIER ANNOTATOR="Karel" LINGUISTIC_TYPE_REF="ort" PARTICIPANT="Jana" TIER_ID="ort@Jana">
+        <ANNOTATION>
+            <ALIGNABLE_ANNOTATION ANNOTATION_ID="a1" TIME_SLOT_REF1="ts1" TIME_SLOT_REF2="ts2">
+                <ANNOTATION_VALUE>no tak Petr říkal že Janák</ANNOTATION_VALUE>
+            </ALIGNABLE_ANNOTATION>
+        </ANNOTATION>
+    </TIER>
+    <TIER LINGUISTIC_TYPE_REF="fon" PARENT_REF="ort@Jana" PARTICIPANT="Jana" TIER_ID="fon@Jana"/>
+    <TIER LINGUISTIC_TYPE_REF="ort" PARTICIPANT="Petr" TIER_ID="ort@Petr">
+        <ANNOTATION>
+            <ALIGNABLE_ANNOTATION ANNOTATION_ID="a2" TIME_SLOT_REF1="ts1" TIME_SLOT_REF2="ts2">
+                <ANNOTATION_VALUE>ahoj Jano</ANNOTATION_VALUE>
+            </ALIGNABLE_ANNOTATION>
+        </ANNOTATION>
+    </TIER>
+    <TIER LINGUISTIC_TYPE_REF="ort" PARTICIPANT="" TIER_ID="Marie Veselá"/>
+</ANNOTATION_DOCUMENT>"#;
+
+    fn anonymized() -> String {
+        let mapping = TierMapping::new(vec![(
+            TierSource::TierId,
+            TierPattern::new("ort@<nickname>").unwrap(),
+        )]);
+        let mut assigned = vec![];
+        anonymize(EAF, &mapping, |name| {
+            assigned.push(name.to_owned());
+            format!("S{}", assigned.len())
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_speakers() {
+        let eaf = anonymized();
+        assert!(eaf.contains("TIER_ID='ort@S1'"));
+        assert!(eaf.contains("PARTICIPANT='S1'"));
+        assert!(eaf.contains("TIER_ID='fon@S1'"));
+        assert!(eaf.contains("PARENT_REF='ort@S1'"));
+        assert!(eaf.contains("TIER_ID='ort@S2'"));
+        assert!(eaf.contains("TIER_ID='S3'"));
+        assert!(!eaf.contains("Jana"));
+        assert!(!eaf.contains("Marie"));
+    }
+
+    #[test]
+    fn test_annotations() {
+        let eaf = anonymized();
+        assert!(eaf.contains(">no tak S2 říkal že Janák<"));
+        // inflected forms can't be recognized
+        assert!(eaf.contains(">ahoj Jano<"));
+    }
+
+    #[test]
+    fn test_header() {
+        let eaf = anonymized();
+        assert!(!eaf.contains("Karel"));
+        assert!(!eaf.contains("karel"));
+        assert!(!eaf.contains("MEDIA_DESCRIPTOR"));
+        assert!(eaf.contains("lastUsedAnnotationId"));
+        assert!(eaf.contains("AUTHOR=''"));
+    }
+
+    #[test]
+    fn test_scrub() {
+        let config = ParserConfig::from_args::<&str, &str, &str, _>(&[], &[], &[], &["NP", "SM"])
+            .with_anonymized(&["NP"]);
+        let mut placeholders = Placeholders::default();
+        let mut scrub = |value| placeholders.scrub(&config, value);
+        assert_eq!(
+            scrub("no <NP Jana Nováková> a <NP Petr>"),
+            "no <NP NP1 NP1> a <NP NP2>"
+        );
+        assert_eq!(scrub("<SM ahoj> <NP petr>"), "<SM ahoj> <NP NP2>");
+        assert_eq!(scrub("<NP_SM Jana (Nováková)>"), "<NP_SM NP1 (NP1)>");
+        assert_eq!(scrub("<NP tam <SM Praha>>"), "<NP NP3 <SM NP3>>");
+        assert_eq!(scrub("bez  jmen"), "bez  jmen");
+    }
+
+    #[test]
+    fn test_scrub_eaf() {
+        let config = ParserConfig::from_args::<&str, &str, &str, _>(&[], &[], &[], &["NP"])
+            .with_anonymized(&["NP"]);
+        let xml = EAF.replace("říkal že Janák", "<NP Karel> říkal");
+        let eaf = Placeholders::default().scrub_eaf(&xml, &config).unwrap();
+        assert!(eaf.contains("NP NP1"));
+        assert!(!eaf.contains("NP Karel"));
+        // the rest is left to `anonymize`
+        assert!(eaf.contains("AUTHOR='Karel Novák'"));
+    }
+
+    #[test]
+    fn test_not_eaf() {
+        assert!(anonymize("<html/>", &TierMapping::default(), |n| n.to_owned()).is_err());
+    }
+}
@@ -0,0 +1,188 @@
+//! Read the output of automatic speech recognition, so that it can serve as
+//! a draft transcript.
+//!
+//! Two formats are supported: Whisper's JSON output (optionally with
+//! per-segment `speaker` labels, as added by diarization tools such as
+//! WhisperX), and plain text with one segment per line, in the form
+//! `START END TEXT` or `SPEAKER START END TEXT` with tab-separated fields,
+//! where timestamps are either seconds (`12.5`) or `[HH:]MM:SS[.mmm]`.
+
+use std::fmt;
+
+use serde::Deserialize;
+
+pub type Milliseconds = u32;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Segment {
+    pub start: Milliseconds,
+    pub end: Milliseconds,
+    pub text: String,
+    /// Diarization label, if any.
+    pub speaker: Option<String>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ReadError {
+    /// 1-based line in plain text input.
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl fmt::Display for ReadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "line {}: {}", line, self.message),
+            None => f.write_str(&self.message),
+        }
+    }
+}
+
+impl ReadError {
+    fn new<M: ToString>(line: Option<usize>, message: M) -> Self {
+        Self {
+            line,
+            message: message.to_string(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct WhisperOutput {
+    segments: Vec<WhisperSegment>,
+}
+
+#[derive(Deserialize)]
+struct WhisperSegment {
+    start: f64,
+    end: f64,
+    text: String,
+    speaker: Option<String>,
+}
+
+fn seconds_to_ms(seconds: f64) -> Option<Milliseconds> {
+    if seconds.is_finite() && seconds >= 0.0 && seconds * 1000.0 <= Milliseconds::MAX as f64 {
+        Some((seconds * 1000.0).round() as Milliseconds)
+    } else {
+        None
+    }
+}
+
+fn check(segment: Segment, line: Option<usize>) -> Result<Option<Segment>, ReadError> {
+    if segment.end < segment.start {
+        return Err(ReadError::new(line, "segment ends before it starts"));
+    }
+    // silence is better than empty annotations
+    if segment.text.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(segment))
+}
+
+pub fn from_whisper_json(json: &str) -> Result<Vec<Segment>, ReadError> {
+    let output: WhisperOutput = serde_json::from_str(json).map_err(|e| ReadError::new(None, e))?;
+    let mut segments = vec![];
+    for (i, s) in output.segments.into_iter().enumerate() {
+        let timestamp = |t| {
+            seconds_to_ms(t)
+                .ok_or_else(|| ReadError::new(None, format!("segment {}: bad timestamp {}", i, t)))
+        };
+        let segment = Segment {
+            start: timestamp(s.start)?,
+            end: timestamp(s.end)?,
+            text: s.text.trim().to_owned(),
+            speaker: s.speaker,
+        };
+        segments.extend(check(segment, None)?);
+    }
+    Ok(segments)
+}
+
+fn parse_timestamp(s: &str) -> Option<Milliseconds> {
+    let mut seconds = 0.0;
+    for part in s.split(':') {
+        let part: f64 = part.parse().ok()?;
+        seconds = seconds * 60.0 + part;
+    }
+    if s.split(':').count() > 3 {
+        return None;
+    }
+    seconds_to_ms(seconds)
+}
+
+pub fn from_plain(text: &str) -> Result<Vec<Segment>, ReadError> {
+    let mut segments = vec![];
+    for (i, line) in text.lines().enumerate() {
+        let line_no = Some(i + 1);
+        if line.trim().is_empty() {
+            continue;
+        }
+        let fields: Vec<_> = line.split('\t').collect();
+        let (speaker, start, end, text) = match fields[..] {
+            [start, end, text] => (None, start, end, text),
+            [speaker, start, end, text] => (Some(speaker.trim().to_owned()), start, end, text),
+            _ => {
+                return Err(ReadError::new(
+                    line_no,
+                    "expected 3 or 4 tab-separated fields",
+                ))
+            }
+        };
+        let timestamp = |t: &str| {
+            parse_timestamp(t.trim())
+                .ok_or_else(|| ReadError::new(line_no, format!("bad timestamp {:?}", t)))
+        };
+        let segment = Segment {
+            start: timestamp(start)?,
+            end: timestamp(end)?,
+            text: text.trim().to_owned(),
+            speaker,
+        };
+        segments.extend(check(segment, line_no)?);
+    }
+    Ok(segments)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_whisper() {
+        let json = r#"{
+            "text": " Dobrý den. Ahoj.",
+            "segments": [
+                {"id": 0, "start": 0.0, "end": 1.52, "text": " Dobrý den."},
+                {"id": 1, "start": 1.52, "end": 2.0, "text": " "},
+                {"id": 2, "start": 2.0, "end": 2.5, "text": " Ahoj.", "speaker": "SPEAKER_01"}
+            ]
+        }"#;
+        let segments = from_whisper_json(json).unwrap();
+        assert_eq!(segments.len(), 2);
+        assert_eq!(
+            segments[0],
+            Segment {
+                start: 0,
+                end: 1520,
+                text: "Dobrý den.".to_owned(),
+                speaker: None,
+            }
+        );
+        assert_eq!(segments[1].speaker.as_deref(), Some("SPEAKER_01"));
+        assert!(from_whisper_json("{}").is_err());
+    }
+
+    #[test]
+    fn test_plain() {
+        let text = "0.5\t1:02.25\tno tak\n\nJana\t1:01:00\t1:01:01\tjo\n";
+        let segments = from_plain(text).unwrap();
+        assert_eq!(segments[0].start, 500);
+        assert_eq!(segments[0].end, 62_250);
+        assert_eq!(segments[1].start, 3_660_000);
+        assert_eq!(segments[1].speaker.as_deref(), Some("Jana"));
+
+        let error = from_plain("1\t0.5\tpozpátku").unwrap_err();
+        assert_eq!(error.line, Some(1));
+        assert_eq!(from_plain("\nx\t1\tjo").unwrap_err().line, Some(2));
+    }
+}
@@ -0,0 +1,282 @@
+//! Proposals for changes to a project's parser config based on token
+//! frequencies, for a human to review:
+//!
+//! - whitelist candidates are frequent tokens the config flags as unknown,
+//!   which are probably legitimate,
+//! - blacklist candidates are rare tokens the config allows, which differ
+//!   by a single edit from a much more frequent one, so they're probably
+//!   typos,
+//! - stopwords are the most frequent allowed tokens.
+
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use super::parser::{Mistake, Parser, ParserConfig};
+
+#[derive(Debug, Clone)]
+pub struct Thresholds {
+    /// Minimum count of unknown tokens to propose for the whitelist.
+    pub min_count: u64,
+    /// Maximum count of tokens to propose for the blacklist.
+    pub max_rare_count: u64,
+    /// How many times more frequent a similar token must be for a rare one
+    /// to count as its typo.
+    pub min_ratio: u64,
+    /// How many stopwords to propose.
+    pub stopwords: usize,
+}
+
+impl Default for Thresholds {
+    fn default() -> Self {
+        Self {
+            min_count: 5,
+            max_rare_count: 2,
+            min_ratio: 20,
+            stopwords: 100,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Candidate {
+    pub token: String,
+    pub count: u64,
+    /// For blacklist candidates, the frequent token they're similar to.
+    pub similar_to: Option<(String, u64)>,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Candidates {
+    pub whitelist: Vec<Candidate>,
+    pub blacklist: Vec<Candidate>,
+    pub stopwords: Vec<Candidate>,
+}
+
+/// Counts of tokens, possibly summed over several frequency lists.
+#[derive(Debug, Default)]
+pub struct Frequencies(HashMap<String, u64>);
+
+impl Frequencies {
+    pub fn add(&mut self, token: &str, count: u64) {
+        *self.0.entry(token.to_owned()).or_default() += count;
+    }
+
+    /// Most frequent first, ties in alphabetical order.
+    fn sorted(&self) -> Vec<(&str, u64)> {
+        let mut sorted: Vec<_> = self.0.iter().map(|(t, &c)| (t.as_str(), c)).collect();
+        sorted.sort_unstable_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        sorted
+    }
+}
+
+fn is_unknown(config: &ParserConfig, token: &str) -> bool {
+    Parser::parse(config, config.tokenize(token))
+        .mistakes
+        .iter()
+        .any(|m| matches!(m, Mistake::BadToken { .. } | Mistake::BadSubstr { .. }))
+}
+
+/// The token itself and all variants with a single character deleted: two
+/// tokens share one of these iff they differ by at most one edit
+/// (substitution, insertion, deletion or transposition of neighbours).
+fn deletions(token: &str) -> Vec<String> {
+    let chars: Vec<_> = token.chars().collect();
+    let mut variants = vec![token.to_owned()];
+    for i in 0..chars.len() {
+        variants.push(chars[..i].iter().chain(&chars[i + 1..]).collect::<String>());
+    }
+    variants
+}
+
+pub fn propose(
+    frequencies: &Frequencies,
+    config: &ParserConfig,
+    thresholds: &Thresholds,
+) -> Candidates {
+    let mut candidates = Candidates::default();
+    let mut known = vec![];
+    for (token, count) in frequencies.sorted() {
+        if is_unknown(config, token) {
+            if count >= thresholds.min_count {
+                candidates.whitelist.push(Candidate {
+                    token: token.to_owned(),
+                    count,
+                    similar_to: None,
+                });
+            }
+        } else {
+            known.push((token, count));
+        }
+    }
+
+    candidates.stopwords = known
+        .iter()
+        .take(thresholds.stopwords)
+        .map(|&(token, count)| Candidate {
+            token: token.to_owned(),
+            count,
+            similar_to: None,
+        })
+        .collect();
+
+    // frequent tokens by their deletion variants, most frequent first
+    let mut frequent: HashMap<String, Vec<(&str, u64)>> = HashMap::new();
+    let floor = thresholds.max_rare_count.max(1) * thresholds.min_ratio;
+    for &(token, count) in known.iter().take_while(|(_, c)| *c >= floor) {
+        for variant in deletions(token) {
+            frequent.entry(variant).or_default().push((token, count));
+        }
+    }
+    for &(token, count) in known.iter().rev() {
+        if count > thresholds.max_rare_count {
+            break;
+        }
+        let similar = deletions(token)
+            .iter()
+            .filter_map(|v| frequent.get(v))
+            .flatten()
+            .filter(|&&(t, c)| t != token && c >= count * thresholds.min_ratio)
+            .max_by(|a, b| a.1.cmp(&b.1).then(b.0.cmp(a.0)))
+            .copied();
+        if let Some((similar, similar_count)) = similar {
+            candidates.blacklist.push(Candidate {
+                token: token.to_owned(),
+                count,
+                similar_to: Some((similar.to_owned(), similar_count)),
+            });
+        }
+    }
+    candidates.blacklist.sort_by(|a, b| a.token.cmp(&b.token));
+    candidates
+}
+
+/// A TOML basic string.
+fn quote(s: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            c if c.is_control() => write!(quoted, "\\u{:04X}", c as u32).unwrap(),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+impl Candidates {
+    /// Additions to the config's lists as TOML, with counts in comments.
+    /// Reviewers delete the lines they disagree with.
+    pub fn to_toml(&self) -> String {
+        let sections = [
+            (
+                "whitelist",
+                "frequent tokens currently flagged as unknown",
+                &self.whitelist,
+            ),
+            (
+                "blacklist",
+                "rare tokens one edit away from a much more frequent one, probably typos",
+                &self.blacklist,
+            ),
+            (
+                "stopwords",
+                "the most frequent allowed tokens",
+                &self.stopwords,
+            ),
+        ];
+        let mut toml = String::new();
+        for (name, description, candidates) in &sections {
+            if !toml.is_empty() {
+                toml.push('\n');
+            }
+            writeln!(toml, "[{}]\n# {}\nadd = [", name, description).unwrap();
+            for candidate in candidates.iter() {
+                write!(
+                    toml,
+                    "    {}, # {}",
+                    quote(&candidate.token),
+                    candidate.count
+                )
+                .unwrap();
+                if let Some((similar, count)) = &candidate.similar_to {
+                    write!(toml, ", cf. {} ({})", quote(similar), count).unwrap();
+                }
+                toml.push('\n');
+            }
+            toml.push_str("]\n");
+        }
+        toml
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frequencies(counts: &[(&str, u64)]) -> Frequencies {
+        let mut frequencies = Frequencies::default();
+        for &(token, count) in counts {
+            frequencies.add(token, count);
+        }
+        frequencies
+    }
+
+    fn candidate(token: &str, count: u64, similar_to: Option<(&str, u64)>) -> Candidate {
+        Candidate {
+            token: token.to_owned(),
+            count,
+            similar_to: similar_to.map(|(t, c)| (t.to_owned(), c)),
+        }
+    }
+
+    #[test]
+    fn test_deletions() {
+        assert_eq!(deletions("ač"), vec!["ač", "č", "a"]);
+    }
+
+    #[test]
+    fn test_propose() {
+        let atoms: Vec<_> = "ahojtkn".chars().map(|c| c.to_string()).collect();
+        let config = ParserConfig::from_args::<&str, &str, _, &str>(&[], &[], &atoms, &[]);
+        let frequencies = frequencies(&[
+            ("ahoj", 30),
+            ("tak", 20),
+            ("ahoj", 30),
+            ("no", 10),
+            ("mhm", 8),
+            ("xx", 2),
+            ("ahjo", 1),
+            ("tk", 1),
+            ("nn", 1),
+        ]);
+        let thresholds = Thresholds {
+            min_count: 5,
+            max_rare_count: 1,
+            min_ratio: 20,
+            stopwords: 2,
+        };
+        let candidates = propose(&frequencies, &config, &thresholds);
+        assert_eq!(
+            candidates,
+            Candidates {
+                whitelist: vec![candidate("mhm", 8, None)],
+                blacklist: vec![
+                    candidate("ahjo", 1, Some(("ahoj", 60))),
+                    candidate("tk", 1, Some(("tak", 20))),
+                ],
+                stopwords: vec![candidate("ahoj", 60, None), candidate("tak", 20, None)],
+            }
+        );
+        let toml = candidates.to_toml();
+        assert!(toml.starts_with("[whitelist]\n"));
+        assert!(toml.contains("    \"ahjo\", # 1, cf. \"ahoj\" (60)\n"));
+        assert!(toml.contains("[whitelist]\n# frequent tokens currently flagged as unknown\nadd = [\n    \"mhm\", # 8\n]\n"));
+    }
+
+    #[test]
+    fn test_quote() {
+        assert_eq!(quote("a\"b\\c\t"), r#""a\"b\\c\u0009""#);
+    }
+}
@@ -0,0 +1,155 @@
+//! Canonical formatting of EAFs, so that diffs between versions only show
+//! changes to their content: attributes in alphabetical order, one element
+//! per line indented by four spaces, double quotes, and text and attribute
+//! values in Unicode NFC.
+
+use std::fmt;
+
+use sxd_document::dom::{ChildOfElement, Element};
+use sxd_document::parser;
+use sxd_document::writer::Writer;
+use unicode_normalization::UnicodeNormalization;
+
+const INDENT: &str = "    ";
+
+#[derive(Debug)]
+pub struct CanonicalError(String);
+
+impl fmt::Display for CanonicalError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "can't normalize EAF: {}", self.0)
+    }
+}
+
+fn nfc(s: &str) -> String {
+    s.nfc().collect()
+}
+
+fn is_blank(child: &ChildOfElement<'_>) -> bool {
+    match child {
+        ChildOfElement::Text(t) => t.text().trim().is_empty(),
+        _ => false,
+    }
+}
+
+fn normalize_element(element: Element<'_>, depth: usize) {
+    let doc = element.document();
+
+    let mut attributes: Vec<_> = element
+        .attributes()
+        .into_iter()
+        .map(|a| (a.name(), a.preferred_prefix(), nfc(a.value())))
+        .collect();
+    attributes.sort_by(|a, b| {
+        (a.0.namespace_uri(), a.0.local_part()).cmp(&(b.0.namespace_uri(), b.0.local_part()))
+    });
+    for (name, _, _) in &attributes {
+        element.remove_attribute(*name);
+    }
+    for (name, prefix, value) in attributes {
+        element
+            .set_attribute_value(name, &value)
+            .set_preferred_prefix(prefix);
+    }
+
+    let children = element.children();
+    let has_elements = children
+        .iter()
+        .any(|c| matches!(c, ChildOfElement::Element(_)));
+    // leave mixed content alone save for NFC, whitespace may matter there
+    let mixed = has_elements && children.iter().any(|c| c.text().is_some() && !is_blank(c));
+    if !has_elements || mixed {
+        for child in children {
+            match child {
+                ChildOfElement::Text(t) => t.set_text(&nfc(t.text())),
+                ChildOfElement::Element(e) => normalize_element(e, depth + 1),
+                _ => {}
+            }
+        }
+        return;
+    }
+
+    let indent = format!("\n{}", INDENT.repeat(depth + 1));
+    let mut indented = vec![];
+    for child in children.into_iter().filter(|c| !is_blank(c)) {
+        if let ChildOfElement::Element(e) = child {
+            normalize_element(e, depth + 1);
+        }
+        indented.push(ChildOfElement::Text(doc.create_text(&indent)));
+        indented.push(child);
+    }
+    let end = format!("\n{}", INDENT.repeat(depth));
+    indented.push(ChildOfElement::Text(doc.create_text(&end)));
+    element.replace_children(indented);
+}
+
+/// The EAF in canonical formatting. Normalizing is idempotent.
+pub fn normalize(xml: &str) -> Result<String, CanonicalError> {
+    let package = parser::parse(xml).map_err(|e| CanonicalError(format!("{:?}", e)))?;
+    let doc = package.as_document();
+    let root = doc
+        .root()
+        .children()
+        .into_iter()
+        .find_map(|c| c.element())
+        .ok_or_else(|| CanonicalError("missing root element".to_owned()))?;
+    normalize_element(root, 0);
+
+    let mut out = vec![];
+    // writing to a Vec can't fail
+    Writer::new()
+        .set_single_quotes(false)
+        .set_write_encoding(true)
+        .format_document(&doc, &mut out)
+        .unwrap();
+    let mut xml = String::from_utf8(out).map_err(|e| CanonicalError(e.to_string()))?;
+    // the writer puts the root element right after the XML declaration
+    if let Some(end) = xml.find("?>") {
+        xml.insert(end + 2, '\n');
+    }
+    xml.push('\n');
+    Ok(xml)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EAF: &str = "<?xml version='1.0' encoding='UTF-8'?>
+<ANNOTATION_DOCUMENT xmlns:xsi='http://www.w3.org/2001/XMLSchema-instance' VERSION='3.0' AUTHOR='' xsi:noNamespaceSchemaLocation='http://www.mpi.nl/tools/elan/EAFv3.0.xsd'>
+  <HEADER TIME_UNITS='milliseconds' MEDIA_FILE=''/><TIME_ORDER>
+        <TIME_SLOT TIME_VALUE='0' TIME_SLOT_ID='ts1'/>
+  </TIME_ORDER>
+<TIER TIER_ID='ort@A' LINGUISTIC_TYPE_REF='ort'><ANNOTATION><ALIGNABLE_ANNOTATION ANNOTATION_ID='a1' TIME_SLOT_REF1='ts1' TIME_SLOT_REF2='ts1'><ANNOTATION_VALUE> kafe\u{301} </ANNOTATION_VALUE></ALIGNABLE_ANNOTATION></ANNOTATION></TIER>
+</ANNOTATION_DOCUMENT>";
+
+    // sxd writes namespace declarations last, in single quotes
+    #[test]
+    fn test_normalize() {
+        let xml = normalize(EAF).unwrap();
+        assert_eq!(
+            xml,
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<ANNOTATION_DOCUMENT AUTHOR="" VERSION="3.0" xsi:noNamespaceSchemaLocation="http://www.mpi.nl/tools/elan/EAFv3.0.xsd" xmlns:xsi='http://www.w3.org/2001/XMLSchema-instance'>
+    <HEADER MEDIA_FILE="" TIME_UNITS="milliseconds"/>
+    <TIME_ORDER>
+        <TIME_SLOT TIME_SLOT_ID="ts1" TIME_VALUE="0"/>
+    </TIME_ORDER>
+    <TIER LINGUISTIC_TYPE_REF="ort" TIER_ID="ort@A">
+        <ANNOTATION>
+            <ALIGNABLE_ANNOTATION ANNOTATION_ID="a1" TIME_SLOT_REF1="ts1" TIME_SLOT_REF2="ts1">
+                <ANNOTATION_VALUE> kafé </ANNOTATION_VALUE>
+            </ALIGNABLE_ANNOTATION>
+        </ANNOTATION>
+    </TIER>
+</ANNOTATION_DOCUMENT>
+"#
+        );
+        assert_eq!(normalize(&xml).unwrap(), xml);
+    }
+
+    #[test]
+    fn test_not_xml() {
+        assert!(normalize("<ANNOTATION_DOCUMENT>").is_err());
+    }
+}
@@ -0,0 +1,850 @@
+//! Parser configs kept in files, so that they can be versioned alongside
+//! the transcripts they apply to. TOML, YAML and JSON are supported, told
+//! apart by the file extension. All fields are optional:
+//!
+//! ```toml
+//! whitelist = ["ehm", "mhm"]
+//! blacklist = ["nj"]
+//! atoms = ["[a-záčďéěíňóřšťúůýž]", "ch"]
+//! after_angle = ["SM", "ZA", "NP"]
+//! deprecated_attrs = ["CZ"]
+//! anonymize = ["NP"]
+//! nested = ["round"]
+//! span_labels = "allowed"
+//! report_whitespace = true
+//!
+//! [attr_values]
+//! lang = "CS|EN|DE"
+//!
+//! [[delims]]
+//! open = "("
+//! close = ")"
+//! kind = "round"
+//! ```
+//!
+//! The lists hold regexes, as in `ParserConfig::from_args`. Invalid ones
+//! are reported with the line they're on, like syntax errors. `anonymize`
+//! codes mark spans to anonymize, see `ParserConfig::with_anonymized`.
+//! `attr_values` are the keys of key=value attributes after <, e.g.
+//! `<SM_lang=EN …>`, with regexes for their values, see
+//! `ParserConfig::with_attr_values`. `span_labels` is `off` (the default),
+//! `allowed` or `required`, see `ParserConfig::with_span_labels`. With
+//! `report_whitespace`, irregular whitespace in segments is reported rather
+//! than normalized, see `ParserConfig::with_whitespace`. `delims` replace
+//! the default `()`, `[]` and `<>` as a whole, see
+//! `ParserConfig::with_delims`.
+//!
+//! A file can also define named rule sets, e.g. a shared convention and
+//! its variants. A set (or the config itself) can extend another one,
+//! adding to its lists and removing inherited entries, given verbatim
+//! (`attr_values` by key):
+//!
+//! ```toml
+//! extends = "dialects"
+//! blacklist = ["nj"]
+//!
+//! [remove]
+//! whitelist = ["mhm"]
+//!
+//! [sets.base]
+//! whitelist = ["ehm", "mhm"]
+//! atoms = ["[a-záčďéěíňóřšťúůýž]", "ch"]
+//!
+//! [sets.dialects]
+//! extends = "base"
+//! whitelist = ["dyť"]
+//! ```
+//!
+//! Sets are resolved when the file is loaded, all of them, so that unknown
+//! sets, cycles and removals of entries which aren't inherited are reported
+//! even in sets not currently in use.
+
+use std::{collections::BTreeMap, fmt, fs, path::Path};
+
+use regex::Regex;
+use serde::de::{Deserializer, Error as _};
+use serde::Deserialize;
+
+use super::parser::{ParserConfig, SpanLabels};
+use super::tokenizer::{Delim, DelimKind, TokenizerConfig, TokenizerConfigError, WhitespacePolicy};
+
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConfigFile {
+    #[serde(deserialize_with = "patterns")]
+    pub whitelist: Vec<String>,
+    #[serde(deserialize_with = "patterns")]
+    pub blacklist: Vec<String>,
+    #[serde(deserialize_with = "patterns")]
+    pub atoms: Vec<String>,
+    #[serde(deserialize_with = "patterns")]
+    pub after_angle: Vec<String>,
+    #[serde(deserialize_with = "patterns")]
+    pub deprecated_attrs: Vec<String>,
+    #[serde(deserialize_with = "patterns")]
+    pub anonymize: Vec<String>,
+    /// `round`, `square` or `angle`, see `ParserConfig::with_nesting`.
+    #[serde(deserialize_with = "delim_kinds")]
+    pub nested: Vec<String>,
+    /// Patterns of values by key, overriding those of the same keys in
+    /// the extended set.
+    #[serde(deserialize_with = "value_patterns")]
+    pub attr_values: BTreeMap<String, String>,
+    /// `off`, `allowed` or `required`, overriding the extended set's.
+    #[serde(deserialize_with = "span_labels_name")]
+    pub span_labels: Option<String>,
+    /// Overriding the extended set's, if given.
+    pub report_whitespace: Option<bool>,
+    /// Replacing the extended set's, if given.
+    pub delims: Option<Vec<DelimEntry>>,
+    /// Name of the set in `sets` whose lists these add to.
+    pub extends: Option<String>,
+    /// Entries of the extended set's lists to leave out.
+    pub remove: Removals,
+    /// Named rule sets, only at the top level of a file.
+    pub sets: BTreeMap<String, ConfigFile>,
+}
+
+/// A pair of delimiters, see `tokenizer::Delim`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DelimEntry {
+    pub open: String,
+    pub close: String,
+    /// `round`, `square` or `angle`.
+    #[serde(deserialize_with = "delim_kind_name")]
+    pub kind: String,
+}
+
+/// Entries to remove from the lists of `ConfigFile::extends`.
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Removals {
+    pub whitelist: Vec<String>,
+    pub blacklist: Vec<String>,
+    pub atoms: Vec<String>,
+    pub after_angle: Vec<String>,
+    pub deprecated_attrs: Vec<String>,
+    pub anonymize: Vec<String>,
+    pub nested: Vec<String>,
+    /// Keys, values don't need repeating.
+    pub attr_values: Vec<String>,
+}
+
+impl Removals {
+    fn lists(&self) -> [(&'static str, &Vec<String>); 7] {
+        [
+            ("whitelist", &self.whitelist),
+            ("blacklist", &self.blacklist),
+            ("atoms", &self.atoms),
+            ("after_angle", &self.after_angle),
+            ("deprecated_attrs", &self.deprecated_attrs),
+            ("anonymize", &self.anonymize),
+            ("nested", &self.nested),
+        ]
+    }
+}
+
+fn delim_kind(name: &str) -> Result<DelimKind, ConfigError> {
+    match name {
+        "round" => Ok(DelimKind::Round),
+        "square" => Ok(DelimKind::Square),
+        "angle" => Ok(DelimKind::Angle),
+        _ => Err(ConfigError::UnknownDelim(name.to_owned())),
+    }
+}
+
+fn span_labels(name: &str) -> Result<SpanLabels, ConfigError> {
+    match name {
+        "off" => Ok(SpanLabels::Off),
+        "allowed" => Ok(SpanLabels::Allowed),
+        "required" => Ok(SpanLabels::Required),
+        _ => Err(ConfigError::UnknownSpanLabels(name.to_owned())),
+    }
+}
+
+/// A regex, checked while deserializing so that the error is reported at
+/// its location in the file.
+struct Pattern(String);
+
+impl<'de> Deserialize<'de> for Pattern {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let pattern = String::deserialize(deserializer)?;
+        check_pattern(&pattern).map_err(D::Error::custom)?;
+        Ok(Pattern(pattern))
+    }
+}
+
+fn check_pattern(pattern: &str) -> Result<(), String> {
+    Regex::new(pattern).map(drop).map_err(|e| {
+        format!(
+            "invalid pattern {:?}: {}",
+            pattern,
+            // the regex error repeats the pattern with a caret under the
+            // problem on preceding lines
+            e.to_string()
+                .lines()
+                .last()
+                .unwrap_or_default()
+                .trim_start_matches("error: ")
+        )
+    })
+}
+
+fn patterns<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    Ok(Vec::<Pattern>::deserialize(deserializer)?
+        .into_iter()
+        .map(|p| p.0)
+        .collect())
+}
+
+fn value_patterns<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<BTreeMap<String, String>, D::Error> {
+    Ok(BTreeMap::<String, Pattern>::deserialize(deserializer)?
+        .into_iter()
+        .map(|(key, p)| (key, p.0))
+        .collect())
+}
+
+fn span_labels_name<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<String>, D::Error> {
+    let name = String::deserialize(deserializer)?;
+    span_labels(&name).map_err(D::Error::custom)?;
+    Ok(Some(name))
+}
+
+struct DelimName(String);
+
+impl<'de> Deserialize<'de> for DelimName {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        delim_kind(&name).map_err(D::Error::custom)?;
+        Ok(DelimName(name))
+    }
+}
+
+fn delim_kind_name<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    Ok(DelimName::deserialize(deserializer)?.0)
+}
+
+fn delim_kinds<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    Ok(Vec::<DelimName>::deserialize(deserializer)?
+        .into_iter()
+        .map(|n| n.0)
+        .collect())
+}
+
+/// Why the list element is invalid, if it is.
+fn invalid_element(field: &str, value: &str) -> Option<String> {
+    if field == "nested" {
+        delim_kind(value).err().map(|e| e.to_string())
+    } else {
+        check_pattern(value).err()
+    }
+}
+
+/// 1-based line and column of the byte offset.
+fn line_column(source: &str, offset: usize) -> (usize, usize) {
+    let before = &source[..offset];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    (
+        before.matches('\n').count() + 1,
+        before[line_start..].chars().count() + 1,
+    )
+}
+
+// Neither `toml` nor `serde_yaml` report invalid list elements at the
+// element, but at the start of the list, which isn't much help with one
+// pattern per line. So on error, look for the offending element itself.
+
+fn toml_element_error(toml: &str) -> Option<String> {
+    let lists: BTreeMap<String, Vec<toml::Spanned<String>>> = toml::from_str(toml).ok()?;
+    lists.iter().find_map(|(field, items)| {
+        items.iter().find_map(|item| {
+            let error = invalid_element(field, item.get_ref())?;
+            let (line, column) = line_column(toml, item.span().start);
+            Some(format!(
+                "TOML parse error at line {}, column {}: {}",
+                line, column, error
+            ))
+        })
+    })
+}
+
+/// There are no spans in YAML values, so this takes the first line from
+/// the start of the list on which the element appears verbatim.
+fn yaml_element_error(yaml: &str, error: &serde_yaml::Error) -> Option<String> {
+    let list_start = error.location()?.index();
+    let lists: BTreeMap<String, Vec<String>> = serde_yaml::from_str(yaml).ok()?;
+    lists.iter().find_map(|(field, items)| {
+        items.iter().find_map(|item| {
+            let error = invalid_element(field, item)?;
+            let offset = yaml[list_start..].find(item.as_str())? + list_start;
+            let (line, column) = line_column(yaml, offset);
+            Some(format!("{} at line {} column {}", error, line, column))
+        })
+    })
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(String),
+    /// Neither TOML, YAML nor JSON, judging by the extension.
+    UnknownFormat(String),
+    /// Malformed file, with the location of the problem in the message.
+    Syntax(String),
+    Pattern {
+        field: &'static str,
+        pattern: String,
+        error: String,
+    },
+    UnknownDelim(String),
+    UnknownSpanLabels(String),
+    Delims(TokenizerConfigError),
+    /// A set extended by the named set (or by the top level if `None`)
+    /// isn't defined.
+    UnknownSet {
+        name: String,
+        extended_by: Option<String>,
+    },
+    /// Sets extending each other, the first one repeated at the end.
+    Cycle(Vec<String>),
+    /// The set (or the top level) removes an entry it doesn't inherit.
+    NotInherited {
+        set: Option<String>,
+        field: &'static str,
+        entry: String,
+    },
+    /// The set defines sets of its own.
+    NestedSets(String),
+}
+
+/// How errors refer to a set, `None` being the top level.
+fn set_name(set: &Option<String>) -> String {
+    match set {
+        Some(name) => format!("rule set {:?}", name),
+        None => "the config".to_owned(),
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Io(e) | ConfigError::Syntax(e) => f.write_str(e),
+            ConfigError::UnknownFormat(path) => write!(
+                f,
+                "{}: unknown config format, expected .toml, .yaml, .yml or .json",
+                path
+            ),
+            ConfigError::Pattern {
+                field,
+                pattern,
+                error,
+            } => write!(f, "invalid pattern {:?} in {}: {}", pattern, field, error),
+            ConfigError::UnknownDelim(kind) => write!(
+                f,
+                "unknown delimiter kind {:?}, expected round, square or angle",
+                kind
+            ),
+            ConfigError::UnknownSpanLabels(name) => write!(
+                f,
+                "unknown span_labels {:?}, expected off, allowed or required",
+                name
+            ),
+            ConfigError::Delims(e) => write!(f, "{}", e),
+            ConfigError::UnknownSet { name, extended_by } => write!(
+                f,
+                "unknown rule set {:?}, extended by {}",
+                name,
+                set_name(extended_by)
+            ),
+            ConfigError::Cycle(names) => write!(
+                f,
+                "rule sets extend each other in a cycle: {}",
+                names.join(" -> ")
+            ),
+            ConfigError::NotInherited { set, field, entry } => write!(
+                f,
+                "{} removes {:?} from {}, but doesn't inherit it",
+                set_name(set),
+                entry,
+                field
+            ),
+            ConfigError::NestedSets(name) => {
+                write!(f, "rule set {:?} defines sets of its own", name)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl ConfigFile {
+    fn lists(&self) -> [(&'static str, &Vec<String>); 7] {
+        [
+            ("whitelist", &self.whitelist),
+            ("blacklist", &self.blacklist),
+            ("atoms", &self.atoms),
+            ("after_angle", &self.after_angle),
+            ("deprecated_attrs", &self.deprecated_attrs),
+            ("anonymize", &self.anonymize),
+            ("nested", &self.nested),
+        ]
+    }
+
+    fn lists_mut(&mut self) -> [(&'static str, &mut Vec<String>); 7] {
+        [
+            ("whitelist", &mut self.whitelist),
+            ("blacklist", &mut self.blacklist),
+            ("atoms", &mut self.atoms),
+            ("after_angle", &mut self.after_angle),
+            ("deprecated_attrs", &mut self.deprecated_attrs),
+            ("anonymize", &mut self.anonymize),
+            ("nested", &mut self.nested),
+        ]
+    }
+
+    /// The lists of `name` (`None` for the top level), with whatever it
+    /// extends applied. `chain` holds the sets being resolved, innermost
+    /// last, for detecting cycles.
+    fn flatten(
+        &self,
+        name: Option<&str>,
+        chain: &mut Vec<String>,
+    ) -> Result<ConfigFile, ConfigError> {
+        let set = match name {
+            Some(name) => &self.sets[name],
+            None => self,
+        };
+        let mut flat = match &set.extends {
+            None => ConfigFile::default(),
+            Some(parent) => {
+                if let Some(i) = chain.iter().position(|n| n == parent) {
+                    let mut cycle = chain[i..].to_vec();
+                    cycle.push(parent.clone());
+                    return Err(ConfigError::Cycle(cycle));
+                }
+                if !self.sets.contains_key(parent) {
+                    return Err(ConfigError::UnknownSet {
+                        name: parent.clone(),
+                        extended_by: name.map(str::to_owned),
+                    });
+                }
+                chain.push(parent.clone());
+                let flat = self.flatten(Some(parent), chain)?;
+                chain.pop();
+                flat
+            }
+        };
+        let removed = set.remove.lists();
+        let added = set.lists();
+        for (i, (field, list)) in flat.lists_mut().iter_mut().enumerate() {
+            for entry in removed[i].1 {
+                match list.iter().position(|e| e == entry) {
+                    Some(at) => {
+                        list.remove(at);
+                    }
+                    None => {
+                        return Err(ConfigError::NotInherited {
+                            set: name.map(str::to_owned),
+                            field: *field,
+                            entry: entry.clone(),
+                        })
+                    }
+                }
+            }
+            for entry in added[i].1 {
+                if !list.contains(entry) {
+                    list.push(entry.clone());
+                }
+            }
+        }
+        for key in &set.remove.attr_values {
+            if flat.attr_values.remove(key).is_none() {
+                return Err(ConfigError::NotInherited {
+                    set: name.map(str::to_owned),
+                    field: "attr_values",
+                    entry: key.clone(),
+                });
+            }
+        }
+        flat.attr_values.extend(set.attr_values.clone());
+        if set.span_labels.is_some() {
+            flat.span_labels = set.span_labels.clone();
+        }
+        if set.report_whitespace.is_some() {
+            flat.report_whitespace = set.report_whitespace;
+        }
+        if set.delims.is_some() {
+            flat.delims = set.delims.clone();
+        }
+        Ok(flat)
+    }
+
+    /// Check that all sets resolve, so that mistakes in the file are caught
+    /// early, whichever set ends up being used.
+    fn check_sets(&self) -> Result<(), ConfigError> {
+        for (name, set) in &self.sets {
+            if !set.sets.is_empty() {
+                return Err(ConfigError::NestedSets(name.clone()));
+            }
+            self.flatten(Some(name), &mut vec![name.clone()])?;
+        }
+        Ok(())
+    }
+
+    /// The top-level config, with whatever it extends applied, i.e. with
+    /// `extends`, `remove` and `sets` empty.
+    pub fn resolve(&self) -> Result<ConfigFile, ConfigError> {
+        self.check_sets()?;
+        self.flatten(None, &mut vec![])
+    }
+
+    /// The named set of the file, resolved like `resolve` does the top
+    /// level.
+    pub fn set(&self, name: &str) -> Result<ConfigFile, ConfigError> {
+        self.check_sets()?;
+        if !self.sets.contains_key(name) {
+            return Err(ConfigError::UnknownSet {
+                name: name.to_owned(),
+                extended_by: None,
+            });
+        }
+        self.flatten(Some(name), &mut vec![name.to_owned()])
+    }
+
+    /// Checks each pattern separately, so that the error can say which one
+    /// is wrong; `ParserConfig::from_args` would panic on the joined
+    /// regex. (Deserialized configs have been checked already, but they
+    /// can be built in code too.) Rule sets are resolved first, see
+    /// `resolve`.
+    pub fn into_config(self) -> Result<ParserConfig, ConfigError> {
+        let config = self.resolve()?;
+        let values: Vec<_> = config.attr_values.values().cloned().collect();
+        let fields = [
+            ("whitelist", &config.whitelist),
+            ("blacklist", &config.blacklist),
+            ("atoms", &config.atoms),
+            ("after_angle", &config.after_angle),
+            ("deprecated_attrs", &config.deprecated_attrs),
+            ("anonymize", &config.anonymize),
+            ("attr_values", &values),
+        ];
+        for (field, patterns) in &fields {
+            for pattern in patterns.iter() {
+                if let Err(e) = Regex::new(pattern) {
+                    return Err(ConfigError::Pattern {
+                        field,
+                        pattern: pattern.clone(),
+                        error: e.to_string(),
+                    });
+                }
+            }
+        }
+        let nested = config
+            .nested
+            .iter()
+            .map(|kind| delim_kind(kind))
+            .collect::<Result<Vec<_>, _>>()?;
+        let labels = match &config.span_labels {
+            Some(name) => span_labels(name)?,
+            None => SpanLabels::default(),
+        };
+        let attr_values: Vec<_> = config
+            .attr_values
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+            .collect();
+        let delims = match &config.delims {
+            Some(delims) => {
+                let delims = delims
+                    .iter()
+                    .map(|d| Ok(Delim::new(&d.open, &d.close, delim_kind(&d.kind)?)))
+                    .collect::<Result<Vec<_>, ConfigError>>()?;
+                Some(TokenizerConfig::new(delims).map_err(ConfigError::Delims)?)
+            }
+            None => None,
+        };
+        let parser_config = ParserConfig::from_args(
+            &config.whitelist,
+            &config.blacklist,
+            &config.atoms,
+            &config.after_angle,
+        )
+        .with_deprecated_attrs(&config.deprecated_attrs)
+        .with_anonymized(&config.anonymize)
+        .with_nesting(&nested)
+        .with_attr_values(&attr_values)
+        .with_span_labels(labels)
+        .with_whitespace(if config.report_whitespace == Some(true) {
+            WhitespacePolicy::Report
+        } else {
+            WhitespacePolicy::Normalize
+        });
+        Ok(match delims {
+            Some(delims) => parser_config.with_delims(delims),
+            None => parser_config,
+        })
+    }
+}
+
+impl ConfigFile {
+    pub fn from_json(json: &str) -> Result<Self, ConfigError> {
+        serde_json::from_str(json).map_err(|e| ConfigError::Syntax(e.to_string()))
+    }
+
+    pub fn from_toml(toml: &str) -> Result<Self, ConfigError> {
+        toml::from_str(toml).map_err(|e| {
+            ConfigError::Syntax(toml_element_error(toml).unwrap_or_else(|| e.to_string()))
+        })
+    }
+
+    pub fn from_yaml(yaml: &str) -> Result<Self, ConfigError> {
+        serde_yaml::from_str(yaml).map_err(|e| {
+            ConfigError::Syntax(yaml_element_error(yaml, &e).unwrap_or_else(|| e.to_string()))
+        })
+    }
+
+    /// The format is given by the extension: `.toml`, `.yaml`, `.yml` or
+    /// `.json`.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self, ConfigError> {
+        let path = path.as_ref();
+        let from_str = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => Self::from_toml,
+            Some("yaml") | Some("yml") => Self::from_yaml,
+            Some("json") => Self::from_json,
+            _ => return Err(ConfigError::UnknownFormat(path.display().to_string())),
+        };
+        let source = fs::read_to_string(path)
+            .map_err(|e| ConfigError::Io(format!("{}: {}", path.display(), e)))?;
+        from_str(&source).map_err(|e| match e {
+            ConfigError::Syntax(e) => ConfigError::Syntax(format!("{}: {}", path.display(), e)),
+            e => e,
+        })
+    }
+}
+
+/// Configs from files, built from the top level (see `ConfigFile::resolve`).
+impl ParserConfig {
+    pub fn from_json(json: &str) -> Result<Self, ConfigError> {
+        ConfigFile::from_json(json)?.into_config()
+    }
+
+    pub fn from_toml(toml: &str) -> Result<Self, ConfigError> {
+        ConfigFile::from_toml(toml)?.into_config()
+    }
+
+    pub fn from_yaml(yaml: &str) -> Result<Self, ConfigError> {
+        ConfigFile::from_yaml(yaml)?.into_config()
+    }
+
+    /// See `ConfigFile::from_path`.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self, ConfigError> {
+        ConfigFile::from_path(path)?.into_config()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::tokenizer;
+
+    #[test]
+    fn test_formats() {
+        let expected = ParserConfig::from_args(&[] as &[&str], &[] as &[&str], &["[a-z]"], &["SM"])
+            .with_nesting(&[DelimKind::Round]);
+        let configs = [
+            ParserConfig::from_json(
+                r#"{"atoms": ["[a-z]"], "after_angle": ["SM"], "nested": ["round"]}"#,
+            ),
+            ParserConfig::from_toml(
+                "atoms = ['[a-z]']\nafter_angle = ['SM']\nnested = ['round']\n",
+            ),
+            ParserConfig::from_yaml("atoms: ['[a-z]']\nafter_angle: [SM]\nnested:\n  - round\n"),
+        ];
+        for config in configs.iter() {
+            let config = config.as_ref().unwrap();
+            assert_eq!(config.version(), expected.version());
+            let parse = |s| Parser::parse(config, tokenizer::tokenize(s));
+            assert!(!parse("no (tak (jo)) <SM no>").has_mistakes());
+            assert!(parse("no <ZA no>").has_mistakes());
+        }
+    }
+
+    #[test]
+    fn test_errors() {
+        let error = |result: Result<ParserConfig, ConfigError>| result.unwrap_err().to_string();
+        let json = error(ParserConfig::from_json(
+            "{\n  \"atoms\": [\n    \"a\",\n    \"[a-z\"\n  ]\n}",
+        ));
+        assert!(json.contains("invalid pattern \"[a-z\": unclosed character class"));
+        // serde_json reports the position after the value, i.e. the next line
+        assert!(json.contains("line 5"), "{}", json);
+        let toml = error(ParserConfig::from_toml("atoms = [\n  'a',\n  '[a-z',\n]\n"));
+        assert!(toml.contains("invalid pattern \"[a-z\""));
+        assert!(toml.contains("line 3"), "{}", toml);
+        let yaml = error(ParserConfig::from_yaml("atoms:\n  - a\n  - '[a-z'\n"));
+        assert!(yaml.contains("invalid pattern \"[a-z\""));
+        assert!(yaml.contains("line 3"), "{}", yaml);
+
+        assert!(error(ParserConfig::from_yaml("atoms: []\natom: []\n")).contains("line 2"));
+        assert!(error(ParserConfig::from_toml("nested = ['curly']")).contains("\"curly\""));
+        assert_eq!(
+            error(
+                ConfigFile {
+                    atoms: vec!["(".to_owned()],
+                    ..ConfigFile::default()
+                }
+                .into_config()
+            )
+            .lines()
+            .next(),
+            Some("invalid pattern \"(\" in atoms: regex parse error:")
+        );
+        assert!(matches!(
+            ParserConfig::from_path("rules.ini"),
+            Err(ConfigError::UnknownFormat(_))
+        ));
+    }
+
+    const SETS: &str = r#"
+extends = "dialects"
+blacklist = ["nj"]
+
+[remove]
+whitelist = ["mhm"]
+
+[sets.base]
+whitelist = ["ehm", "mhm"]
+atoms = ["[a-z]"]
+
+[sets.dialects]
+extends = "base"
+whitelist = ["dyť", "ehm"]
+"#;
+
+    #[test]
+    fn test_sets() {
+        let file = ConfigFile::from_toml(SETS).unwrap();
+        let top = file.resolve().unwrap();
+        assert_eq!(top.whitelist, vec!["ehm", "dyť"]);
+        assert_eq!(top.blacklist, vec!["nj"]);
+        assert_eq!(top.atoms, vec!["[a-z]"]);
+        assert!(top.extends.is_none() && top.sets.is_empty());
+        let dialects = file.set("dialects").unwrap();
+        assert_eq!(dialects.whitelist, vec!["ehm", "mhm", "dyť"]);
+        assert!(dialects.blacklist.is_empty());
+
+        let config = ParserConfig::from_toml(SETS).unwrap();
+        let parse = |s| Parser::parse(&config, tokenizer::tokenize(s));
+        assert!(!parse("ehm dyť").has_mistakes());
+        assert!(parse("nj").has_mistakes());
+        assert_eq!(config.version(), top.into_config().unwrap().version());
+    }
+
+    #[test]
+    fn test_attr_values() {
+        let toml = "extends = 'base'\n[attr_values]\nlang = 'CS|EN'\n\
+                    [sets.base.attr_values]\nlang = 'CS'\nage = '[0-9]+'\n";
+        let file = ConfigFile::from_toml(toml).unwrap();
+        assert_eq!(file.resolve().unwrap().attr_values["lang"], "CS|EN");
+        let config = file.into_config().unwrap();
+        let parse = |s| Parser::parse(&config, tokenizer::tokenize(s));
+        assert!(!parse("<lang=EN_age=20 no>").has_mistakes());
+        assert!(parse("<age=old no>").has_mistakes());
+
+        let toml = "extends = 'base'\nremove = { attr_values = ['age'] }\n\
+                    [sets.base.attr_values]\nage = '[0-9]+'\n";
+        let top = ConfigFile::from_toml(toml).unwrap().resolve().unwrap();
+        assert!(top.attr_values.is_empty());
+
+        let error = ParserConfig::from_toml("[attr_values]\nlang = '(EN'\n").unwrap_err();
+        assert!(
+            error.to_string().contains("invalid pattern \"(EN\""),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn test_span_labels() {
+        let toml =
+            "extends = 'base'\n[sets.base]\nafter_angle = ['SM']\nspan_labels = 'required'\n";
+        let config = ParserConfig::from_toml(toml).unwrap();
+        let parse = |s| Parser::parse(&config, tokenizer::tokenize(s));
+        assert!(!parse("<SM no SM>").has_mistakes());
+        assert!(parse("<SM no >").has_mistakes());
+
+        let config = ParserConfig::from_toml(&format!("span_labels = 'off'\n{}", toml)).unwrap();
+        assert!(!Parser::parse(&config, tokenizer::tokenize("<SM no >")).has_mistakes());
+
+        let error = ParserConfig::from_toml("span_labels = 'always'").unwrap_err();
+        assert!(error.to_string().contains("\"always\""), "{}", error);
+    }
+
+    #[test]
+    fn test_report_whitespace() {
+        let toml = "extends = 'base'\n[sets.base]\nreport_whitespace = true\n";
+        let config = ParserConfig::from_toml(toml).unwrap();
+        let parsed = Parser::parse(&config, config.tokenize("no  tak"));
+        assert!(parsed.has_mistakes());
+
+        let toml = format!("report_whitespace = false\n{}", toml);
+        let config = ParserConfig::from_toml(&toml).unwrap();
+        assert!(!Parser::parse(&config, config.tokenize("no  tak")).has_mistakes());
+    }
+
+    #[test]
+    fn test_delims() {
+        let toml = "extends = 'base'\natoms = ['[a-z]']\n[[sets.base.delims]]\n\
+                    open = '{'\nclose = '}'\nkind = 'round'\n";
+        let config = ParserConfig::from_toml(toml).unwrap();
+        assert!(!Parser::parse(&config, config.tokenize("no {tak}")).has_mistakes());
+        // the defaults are replaced, not added to
+        assert!(Parser::parse(&config, config.tokenize("no (tak)")).has_mistakes());
+
+        let error = |toml| ParserConfig::from_toml(toml).unwrap_err().to_string();
+        assert!(
+            error("[[delims]]\nopen = '{'\nclose = '}'\nkind = 'curly'\n").contains("\"curly\"")
+        );
+        assert_eq!(error("delims = []"), "invalid delimiters: none given");
+    }
+
+    #[test]
+    fn test_set_errors() {
+        let error = |toml: &str| ConfigFile::from_toml(toml).unwrap().resolve().unwrap_err();
+        assert_eq!(
+            error("extends = 'base'").to_string(),
+            "unknown rule set \"base\", extended by the config"
+        );
+        assert_eq!(
+            error("[sets.a]\nextends = 'b'\n[sets.b]\nextends = 'c'\n[sets.c]\nextends = 'b'")
+                .to_string(),
+            "rule sets extend each other in a cycle: b -> c -> b"
+        );
+        // even in sets nothing extends
+        assert!(matches!(
+            error("[sets.a]\nextends = 'a'"),
+            ConfigError::Cycle(_)
+        ));
+        assert_eq!(
+            error("[sets.a]\nwhitelist = ['x']\n[sets.b]\nextends = 'a'\nremove = { whitelist = ['y'] }")
+                .to_string(),
+            "rule set \"b\" removes \"y\" from whitelist, but doesn't inherit it"
+        );
+        assert!(matches!(
+            error("[sets.a.sets.b]"),
+            ConfigError::NestedSets(name) if name == "a"
+        ));
+        assert!(matches!(
+            ConfigFile::default().set("a"),
+            Err(ConfigError::UnknownSet {
+                extended_by: None,
+                ..
+            })
+        ));
+    }
+}
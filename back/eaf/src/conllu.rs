@@ -0,0 +1,121 @@
+//! Export of transcripts to CoNLL-U, one sentence per transcript annotation
+//! and one line per word. Nothing is annotated beyond the word forms, but
+//! tools for adding lemmas, tags and dependencies take it as input.
+//!
+//! Words are what the parser considers words (see `Parsed::flagged_tokens`)
+//! and they're numbered the same way in word-level alignments, whose times
+//! go to the MISC column as `AlignBegin` and `AlignEnd` in milliseconds,
+//! following the spoken UD treebanks.
+
+use std::collections::HashMap;
+
+use super::annotations::Annotation;
+use super::parser::{Parsed, Parser, ParserConfig};
+use super::tiers::TierMapping;
+use super::tokenizer;
+
+/// Word-level times in milliseconds, keyed by annotation ID and word index.
+pub type WordTimes = HashMap<(String, usize), (u32, u32)>;
+
+/// Parse with no restrictions on tokens, so that word numbering doesn't
+/// depend on the project's rules.
+fn parse(value: &str) -> Parsed {
+    let config = ParserConfig::from_args::<&str, &str, &str, &str>(&[], &[], &[], &[]);
+    Parser::parse(&config, tokenizer::tokenize(value))
+}
+
+/// The words of an annotation's value, in the order they're numbered in.
+pub fn words(value: &str) -> Vec<String> {
+    let parsed = parse(value);
+    parsed
+        .flagged_tokens()
+        .iter()
+        .map(|(token, _)| parsed.source[token.start..token.end].to_owned())
+        .collect()
+}
+
+/// The speaker's nickname, falling back to the participant and the tier.
+pub(crate) fn speaker<'a>(annotation: &'a Annotation, mapping: &'a TierMapping) -> &'a str {
+    mapping
+        .nickname(&annotation.tier, annotation.participant.as_deref())
+        .or(annotation.participant.as_deref())
+        .unwrap_or(&annotation.tier)
+}
+
+/// Comment values can't span lines.
+fn comment(value: &str) -> String {
+    value.replace(['\n', '\r'], " ")
+}
+
+pub fn write(annotations: &[Annotation], mapping: &TierMapping, times: &WordTimes) -> String {
+    let mut out = String::new();
+    for annotation in annotations {
+        if !annotation.is_transcript(mapping) {
+            continue;
+        }
+        let parsed = parse(&annotation.value);
+        let words = parsed.flagged_tokens();
+        if words.is_empty() {
+            continue;
+        }
+        let speaker = speaker(annotation, mapping);
+        out.push_str(&format!("# sent_id = {}\n", comment(&annotation.id)));
+        out.push_str(&format!("# speaker = {}\n", comment(speaker)));
+        out.push_str(&format!("# text = {}\n", comment(&parsed.source)));
+        for (i, (token, _)) in words.iter().enumerate() {
+            let form = &parsed.source[token.start..token.end];
+            let misc = match times.get(&(annotation.id.clone(), i)) {
+                Some((start, end)) => format!("AlignBegin={}|AlignEnd={}", start, end),
+                None => "_".to_owned(),
+            };
+            out.push_str(&format!(
+                "{}\t{}\t_\t_\t_\t_\t_\t_\t_\t{}\n",
+                i + 1,
+                form,
+                misc
+            ));
+        }
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn annotation(id: &str, value: &str) -> Annotation {
+        Annotation {
+            tier: "ort@JD".to_owned(),
+            participant: Some("JD".to_owned()),
+            linguistic_type: "ort".to_owned(),
+            id: id.to_owned(),
+            value: value.to_owned(),
+            start: Some(0),
+            end: Some(1000),
+            interpolated: false,
+        }
+    }
+
+    #[test]
+    fn test_words() {
+        assert_eq!(words("no (tak) <SM jo>"), ["no", "tak", "jo"]);
+    }
+
+    #[test]
+    fn test_write() {
+        let mut times = WordTimes::new();
+        times.insert(("a1".to_owned(), 1), (120, 480));
+        let conllu = write(
+            &[annotation("a1", "no (tak)"), annotation("a2", "")],
+            &TierMapping::default(),
+            &times,
+        );
+        assert_eq!(
+            conllu,
+            "# sent_id = a1\n# speaker = JD\n# text = no (tak)\n\
+             1\tno\t_\t_\t_\t_\t_\t_\t_\t_\n\
+             2\ttak\t_\t_\t_\t_\t_\t_\t_\tAlignBegin=120|AlignEnd=480\n\n"
+        );
+    }
+}
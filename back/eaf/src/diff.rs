@@ -0,0 +1,140 @@
+//! Differences between two versions of a transcript at the level of
+//! annotations, e.g. for reviewing what a revision changed. Annotations are
+//! matched by tier and ID; formatting and other changes to the document
+//! don't show up.
+
+use std::collections::HashMap;
+
+use super::annotations::{self, Annotation, ReadError};
+
+#[derive(Debug, PartialEq)]
+pub enum Change {
+    Added(Annotation),
+    Removed(Annotation),
+    /// The annotation's value or times changed.
+    Changed {
+        before: Annotation,
+        after: Annotation,
+    },
+}
+
+impl Change {
+    /// The annotation's tier and ID.
+    pub fn annotation(&self) -> (&str, &str) {
+        let annotation = match self {
+            Change::Added(a) | Change::Removed(a) | Change::Changed { after: a, .. } => a,
+        };
+        (&annotation.tier, &annotation.id)
+    }
+}
+
+/// Changes from `before` to `after`, in document order of the latter, with
+/// removed annotations last in document order of the former.
+pub fn diff(before: &str, after: &str) -> Result<Vec<Change>, ReadError> {
+    let mut old: Vec<_> = annotations::read(before)?.into_iter().map(Some).collect();
+    let index: HashMap<_, _> = old
+        .iter()
+        .enumerate()
+        .filter_map(|(i, a)| a.as_ref().map(|a| ((a.tier.clone(), a.id.clone()), i)))
+        .collect();
+
+    let mut changes = vec![];
+    for annotation in annotations::read(after)? {
+        let previous = index
+            .get(&(annotation.tier.clone(), annotation.id.clone()))
+            .and_then(|&i| old[i].take());
+        match previous {
+            None => changes.push(Change::Added(annotation)),
+            Some(previous)
+                if previous.value != annotation.value
+                    || previous.start != annotation.start
+                    || previous.end != annotation.end =>
+            {
+                changes.push(Change::Changed {
+                    before: previous,
+                    after: annotation,
+                })
+            }
+            Some(_) => {}
+        }
+    }
+    changes.extend(old.into_iter().flatten().map(Change::Removed));
+    Ok(changes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eaf(annotations: &[(&str, &str, &str)]) -> String {
+        let annotations: String = annotations
+            .iter()
+            .map(|(id, end, value)| {
+                format!(
+                    r#"<ANNOTATION><ALIGNABLE_ANNOTATION ANNOTATION_ID="{}" TIME_SLOT_REF1="ts1" TIME_SLOT_REF2="{}"><ANNOTATION_VALUE>{}</ANNOTATION_VALUE></ALIGNABLE_ANNOTATION></ANNOTATION>"#,
+                    id, end, value
+                )
+            })
+            .collect();
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<ANNOTATION_DOCUMENT AUTHOR="" DATE="2020-01-01T00:00:00+01:00" FORMAT="3.0" VERSION="3.0">
+    <HEADER MEDIA_FILE="" TIME_UNITS="milliseconds"/>
+    <TIME_ORDER>
+        <TIME_SLOT TIME_SLOT_ID="ts1" TIME_VALUE="0"/>
+        <TIME_SLOT TIME_SLOT_ID="ts2" TIME_VALUE="1000"/>
+        <TIME_SLOT TIME_SLOT_ID="ts3" TIME_VALUE="2000"/>
+    </TIME_ORDER>
+    <TIER LINGUISTIC_TYPE_REF="ort" TIER_ID="ort@Jana">{}</TIER>
+</ANNOTATION_DOCUMENT>"#,
+            annotations
+        )
+    }
+
+    fn ids(changes: &[Change]) -> Vec<(&str, &str)> {
+        changes
+            .iter()
+            .map(|c| {
+                let kind = match c {
+                    Change::Added(_) => "added",
+                    Change::Removed(_) => "removed",
+                    Change::Changed { .. } => "changed",
+                };
+                (kind, c.annotation().1)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_diff() {
+        let before = eaf(&[
+            ("a1", "ts2", "no"),
+            ("a2", "ts2", "jo"),
+            ("a3", "ts2", "tak"),
+        ]);
+        assert_eq!(diff(&before, &before).unwrap(), []);
+
+        let after = eaf(&[
+            ("a4", "ts2", "hm"),
+            ("a1", "ts2", "no jo"),
+            ("a3", "ts3", "tak"),
+        ]);
+        let changes = diff(&before, &after).unwrap();
+        assert_eq!(
+            ids(&changes),
+            [
+                ("added", "a4"),
+                ("changed", "a1"),
+                ("changed", "a3"),
+                ("removed", "a2")
+            ]
+        );
+        match &changes[1] {
+            Change::Changed { before, after } => {
+                assert_eq!(before.value, "no");
+                assert_eq!(after.value, "no jo");
+            }
+            change => panic!("unexpected {:?}", change),
+        }
+    }
+}
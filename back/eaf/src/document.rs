@@ -1,55 +1,406 @@
-//! Parse an entire EAF file.
+//! Parse an entire EAF file: header, time order, linguistic types,
+//! controlled vocabularies and tiers with their annotations, freeform
+//! ones parsed according to the project's transcription rules. For just
+//! the annotation values, `annotations` is quicker.
 
-use std::{collections::HashMap, fs, io::BufReader, path::Path};
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
 
-use sxd_document::parser;
-use sxd_xpath::{evaluate_xpath, Value};
+use super::annotations::{self, ReadError};
+use super::header::Header;
+use super::parser::{Parsed, Parser, ParserConfig};
+use super::policies::{Policy, TierPolicies};
+use super::stream;
 
-use super::parser::Parsed;
-
-enum AnnotationContent {
+#[derive(Debug)]
+pub enum AnnotationContent {
     Freeform(Parsed),
     // TODO: maybe a ref into a vocab collection instead? a pain to pass around though
     ControlledVocab(String),
+    /// From a tier whose values must match a pattern (see
+    /// `policies::Policy::Pattern`), and whether it does.
+    Coded {
+        value: String,
+        matches: bool,
+    },
+    /// From a tier which isn't validated.
+    Unchecked(String),
+}
+
+pub type Milliseconds = u32;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimeSlot {
+    pub id: String,
+    /// Missing for slots not aligned with the recording.
+    pub value: Option<Milliseconds>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LinguisticType {
+    pub id: String,
+    pub time_alignable: bool,
+    /// How annotations on tiers of this type relate to their parents, e.g.
+    /// `Symbolic_Association`; missing for independent tiers.
+    pub constraint: Option<String>,
+    pub controlled_vocabulary: Option<String>,
+}
+
+/// A recording the document transcribes, from the header.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MediaDescriptor {
+    pub url: String,
+    pub mime_type: Option<String>,
+    pub relative_url: Option<String>,
+    /// Where in the recording the document's time 0 is, in milliseconds.
+    pub time_origin: Option<u32>,
+}
+
+/// An entry of a controlled vocabulary, with its value in each of the
+/// vocabulary's languages.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CvEntry {
+    /// Missing in EAF 2.x.
+    pub id: Option<String>,
+    pub values: Vec<String>,
 }
 
-type Milliseconds = u32;
+#[derive(Debug, Clone, PartialEq)]
+pub struct ControlledVocabulary {
+    pub id: String,
+    /// The external resource the entries come from (EXT_REF), in which
+    /// case the document doesn't list them.
+    pub external: Option<String>,
+    pub entries: Vec<CvEntry>,
+}
+
+impl ControlledVocabulary {
+    /// Whether the value is one of the entries', in any language.
+    pub fn contains(&self, value: &str) -> bool {
+        self.entries
+            .iter()
+            .any(|e| e.values.iter().any(|v| v == value))
+    }
+}
 
-struct Annotation {
-    content: AnnotationContent,
-    start: Milliseconds,
-    end: Milliseconds,
+#[derive(Debug, Clone, PartialEq)]
+pub enum Alignment {
+    /// Delimited by time slots of the time order.
+    Alignable {
+        start_slot: String,
+        end_slot: String,
+    },
+    /// Depending on an annotation of the parent tier, after the previous
+    /// annotation depending on it, if any (symbolic subdivision).
+    Ref {
+        parent: String,
+        previous: Option<String>,
+    },
 }
 
-struct Tier {
-    id: String,
-    time_slots: HashMap<String, Milliseconds>,
-    annotations: Vec<Annotation>,
+#[derive(Debug)]
+pub struct Annotation {
+    pub id: String,
+    pub alignment: Alignment,
+    pub content: AnnotationContent,
+    /// As in `annotations::Annotation`: reference annotations take them
+    /// from their parents, unaligned slots are interpolated.
+    pub start: Option<Milliseconds>,
+    pub end: Option<Milliseconds>,
+    pub interpolated: bool,
 }
 
-struct Eaf {
+impl Annotation {
+    /// The parsed value, unless it comes from a controlled vocabulary.
+    pub fn parsed(&self) -> Option<&Parsed> {
+        match &self.content {
+            AnnotationContent::Freeform(parsed) => Some(parsed),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Tier {
+    pub id: String,
+    pub linguistic_type: String,
+    pub participant: Option<String>,
+    pub annotator: Option<String>,
+    pub parent: Option<String>,
+    /// In document order.
+    pub annotations: Vec<Annotation>,
+}
+
+#[derive(Debug)]
+pub struct Eaf {
     // TODO: speaker and doc metadata? we probably want to vc those in the repo as well,
     // but we might just fetch them from the db as needed instead of storing them here
+    header: Header,
+    media: Vec<MediaDescriptor>,
+    time_slots: Vec<TimeSlot>,
+    linguistic_types: Vec<LinguisticType>,
+    controlled_vocabularies: Vec<ControlledVocabulary>,
     tiers: Vec<Tier>,
 }
 
 impl Eaf {
-    fn from_file<P: AsRef<Path>>(path: P) -> Self {
-        let xml = fs::read_to_string(path).expect("failed to open EAF file");
-        let xml = parser::parse(&xml).expect("failed to parse EAF XML");
-        let doc = xml.as_document();
-        let adoc = evaluate_xpath(&doc, "/ANNOTATION_DOCUMENT").expect("XPath evaluation failed");
-        dbg!(&adoc);
-        todo!()
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    pub fn header_mut(&mut self) -> &mut Header {
+        &mut self.header
+    }
+
+    /// The recordings, the primary one first.
+    pub fn media(&self) -> &[MediaDescriptor] {
+        &self.media
+    }
+
+    pub fn time_slots(&self) -> &[TimeSlot] {
+        &self.time_slots
+    }
+
+    pub fn linguistic_types(&self) -> &[LinguisticType] {
+        &self.linguistic_types
+    }
+
+    pub fn controlled_vocabularies(&self) -> &[ControlledVocabulary] {
+        &self.controlled_vocabularies
+    }
+
+    /// The vocabulary of the tier's linguistic type, if it has one and the
+    /// document defines it.
+    pub fn vocabulary(&self, tier: &Tier) -> Option<&ControlledVocabulary> {
+        let id = self
+            .linguistic_types
+            .iter()
+            .find(|t| t.id == tier.linguistic_type)?
+            .controlled_vocabulary
+            .as_ref()?;
+        self.controlled_vocabularies.iter().find(|v| &v.id == id)
+    }
+
+    pub fn tiers(&self) -> &[Tier] {
+        &self.tiers
+    }
+
+    pub fn tier(&self, id: &str) -> Option<&Tier> {
+        self.tiers.iter().find(|t| t.id == id)
+    }
+
+    /// Freeform values are parsed according to `config`; those from
+    /// controlled vocabularies (on tiers whose type has one, or referring
+    /// to an entry) are kept as they are.
+    pub fn parse(xml: &str, config: &ParserConfig) -> Result<Self, ReadError> {
+        Self::parse_with_policies(xml, config, &TierPolicies::default())
+    }
+
+    /// Like `parse`, with the tiers `policies` select treated according to
+    /// their policy rather than by whether they have a vocabulary.
+    pub fn parse_with_policies(
+        xml: &str,
+        config: &ParserConfig,
+        policies: &TierPolicies,
+    ) -> Result<Self, ReadError> {
+        Self::read(xml.as_bytes(), config, policies)
+    }
+
+    /// Like `parse`, reading the file as it goes rather than all at once.
+    pub fn from_file<P: AsRef<Path>>(path: P, config: &ParserConfig) -> Result<Self, ReadError> {
+        Self::from_file_with_policies(path, config, &TierPolicies::default())
+    }
+
+    /// Like `parse_with_policies`, reading the file as it goes.
+    pub fn from_file_with_policies<P: AsRef<Path>>(
+        path: P,
+        config: &ParserConfig,
+        policies: &TierPolicies,
+    ) -> Result<Self, ReadError> {
+        let path = path.as_ref();
+        let file = File::open(path).map_err(|e| ReadError(format!("{}: {}", path.display(), e)))?;
+        Self::read(BufReader::new(file), config, policies)
+    }
+
+    fn read<R: BufRead>(
+        input: R,
+        config: &ParserConfig,
+        policies: &TierPolicies,
+    ) -> Result<Self, ReadError> {
+        let eaf = stream::read(input)?;
+        let times = annotations::annotation_times(&eaf);
+        let linguistic_types = eaf.linguistic_types;
+        let tiers = eaf
+            .tiers
+            .into_iter()
+            .zip(times)
+            .map(|(tier, times)| {
+                let policy = policies.policy(&tier.id, &tier.linguistic_type);
+                let vocabulary = linguistic_types
+                    .iter()
+                    .any(|t| t.id == tier.linguistic_type && t.controlled_vocabulary.is_some());
+                let annotations = tier
+                    .annotations
+                    .into_iter()
+                    .zip(times)
+                    .map(|(annotation, (start, end, interpolated))| {
+                        let value = annotation.value;
+                        let content = match policy {
+                            Some(Policy::Vocabulary) => AnnotationContent::ControlledVocab(value),
+                            Some(Policy::Pattern(pattern)) => AnnotationContent::Coded {
+                                matches: pattern.is_match(&value),
+                                value,
+                            },
+                            Some(Policy::Skip) => AnnotationContent::Unchecked(value),
+                            None if vocabulary || annotation.cve_ref => {
+                                AnnotationContent::ControlledVocab(value)
+                            }
+                            Some(Policy::Freeform) | None => {
+                                let parsed = Parser::parse(config, config.tokenize(&value));
+                                AnnotationContent::Freeform(parsed)
+                            }
+                        };
+                        Annotation {
+                            id: annotation.id,
+                            alignment: annotation.alignment,
+                            content,
+                            start,
+                            end,
+                            interpolated,
+                        }
+                    })
+                    .collect();
+                Tier {
+                    id: tier.id,
+                    linguistic_type: tier.linguistic_type,
+                    participant: tier.participant,
+                    annotator: tier.annotator,
+                    parent: tier.parent,
+                    annotations,
+                }
+            })
+            .collect();
+        Ok(Self {
+            header: eaf.header,
+            media: eaf.media,
+            time_slots: eaf.time_slots,
+            linguistic_types,
+            controlled_vocabularies: eaf.controlled_vocabularies,
+            tiers,
+        })
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::policies::PolicyRule;
+
+    const EAF: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<ANNOTATION_DOCUMENT AUTHOR="Jana" DATE="2020-01-01T00:00:00+01:00" FORMAT="3.0" VERSION="3.0">
+    <HEADER MEDIA_FILE="" TIME_UNITS="milliseconds"/>
+    <TIME_ORDER>
+        <TIME_SLOT TIME_SLOT_ID="ts1" TIME_VALUE="0"/>
+        <TIME_SLOT TIME_SLOT_ID="ts2"/>
+        <TIME_SLOT TIME_SLOT_ID="ts3" TIME_VALUE="1000"/>
+    </TIME_ORDER>
+    <TIER LINGUISTIC_TYPE_REF="ort" PARTICIPANT="Jana" TIER_ID="ort@Jana">
+        <ANNOTATION>
+            <ALIGNABLE_ANNOTATION ANNOTATION_ID="a1" TIME_SLOT_REF1="ts1" TIME_SLOT_REF2="ts2">
+                <ANNOTATION_VALUE>no (tak</ANNOTATION_VALUE>
+            </ALIGNABLE_ANNOTATION>
+        </ANNOTATION>
+        <ANNOTATION>
+            <ALIGNABLE_ANNOTATION ANNOTATION_ID="a2" TIME_SLOT_REF1="ts2" TIME_SLOT_REF2="ts3">
+                <ANNOTATION_VALUE>jo</ANNOTATION_VALUE>
+            </ALIGNABLE_ANNOTATION>
+        </ANNOTATION>
+    </TIER>
+    <TIER LINGUISTIC_TYPE_REF="pos" PARENT_REF="ort@Jana" TIER_ID="pos@Jana">
+        <ANNOTATION>
+            <REF_ANNOTATION ANNOTATION_ID="a3" ANNOTATION_REF="a2" CVE_REF="cv1">
+                <ANNOTATION_VALUE>PART</ANNOTATION_VALUE>
+            </REF_ANNOTATION>
+        </ANNOTATION>
+    </TIER>
+    <LINGUISTIC_TYPE GRAPHIC_REFERENCES="false" LINGUISTIC_TYPE_ID="ort" TIME_ALIGNABLE="true"/>
+    <LINGUISTIC_TYPE CONSTRAINTS="Symbolic_Association" CONTROLLED_VOCABULARY_REF="pos" LINGUISTIC_TYPE_ID="pos" TIME_ALIGNABLE="false"/>
+    <CONTROLLED_VOCABULARY CV_ID="pos">
+        <CV_ENTRY_ML CVE_ID="cv1">
+            <CVE_VALUE LANG_REF="eng">PART</CVE_VALUE>
+        </CV_ENTRY_ML>
+    </CONTROLLED_VOCABULARY>
+</ANNOTATION_DOCUMENT>"#;
 
     #[test]
-    fn test() {
-        let eaf = Eaf::from_file("19A029F.eaf");
+    fn test_parse() {
+        let config = ParserConfig::from_args::<&str, &str, &str, &str>(&[], &[], &[], &[]);
+        let eaf = Eaf::parse(EAF, &config).unwrap();
+        assert_eq!(eaf.header().author(), "Jana");
+        assert_eq!(eaf.time_slots().len(), 3);
+        assert_eq!(eaf.time_slots()[1].value, None);
+        assert_eq!(
+            eaf.linguistic_types()[1].constraint.as_deref(),
+            Some("Symbolic_Association")
+        );
+        assert!(!eaf.linguistic_types()[1].time_alignable);
+
+        let ort = eaf.tier("ort@Jana").unwrap();
+        assert_eq!(ort.participant.as_deref(), Some("Jana"));
+        let a1 = &ort.annotations[0];
+        assert_eq!(
+            a1.alignment,
+            Alignment::Alignable {
+                start_slot: "ts1".to_owned(),
+                end_slot: "ts2".to_owned()
+            }
+        );
+        assert_eq!(
+            (a1.start, a1.end, a1.interpolated),
+            (Some(0), Some(500), true)
+        );
+        assert!(a1.parsed().unwrap().has_mistakes());
+        assert!(!ort.annotations[1].parsed().unwrap().has_mistakes());
+
+        let pos = eaf.tier("pos@Jana").unwrap();
+        assert_eq!(pos.parent.as_deref(), Some("ort@Jana"));
+        let a3 = &pos.annotations[0];
+        assert!(matches!(&a3.content, AnnotationContent::ControlledVocab(v) if v == "PART"));
+        assert_eq!((a3.start, a3.end), (Some(500), Some(1000)));
+
+        assert_eq!(eaf.controlled_vocabularies().len(), 1);
+        assert!(eaf.vocabulary(ort).is_none());
+        let vocabulary = eaf.vocabulary(pos).unwrap();
+        assert!(vocabulary.contains("PART"));
+        assert!(!vocabulary.contains("part"));
+    }
+
+    #[test]
+    fn test_policies() {
+        let config = ParserConfig::from_args::<&str, &str, &str, &str>(&[], &[], &[], &[]);
+        let policies = TierPolicies::new(vec![
+            PolicyRule::new("tier_id", "ort@.*", "skip", None).unwrap(),
+            PolicyRule::new("linguistic_type", "pos", "pattern", Some("[A-Z]+")).unwrap(),
+        ]);
+        let eaf = Eaf::parse_with_policies(EAF, &config, &policies).unwrap();
+        let ort = eaf.tier("ort@Jana").unwrap();
+        assert!(
+            matches!(&ort.annotations[0].content, AnnotationContent::Unchecked(v) if v == "no (tak")
+        );
+        assert!(ort.annotations[0].parsed().is_none());
+        let a3 = &eaf.tier("pos@Jana").unwrap().annotations[0];
+        assert!(matches!(
+            &a3.content,
+            AnnotationContent::Coded { matches: true, .. }
+        ));
+
+        let policies = TierPolicies::new(vec![PolicyRule::new(
+            "tier_id", "pos@Jana", "freeform", None,
+        )
+        .unwrap()]);
+        let eaf = Eaf::parse_with_policies(EAF, &config, &policies).unwrap();
+        let a3 = &eaf.tier("pos@Jana").unwrap().annotations[0];
+        assert!(a3.parsed().is_some());
     }
 }
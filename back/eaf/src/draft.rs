@@ -0,0 +1,174 @@
+//! Write new EAF files from scratch, e.g. draft transcripts pre-filled by
+//! speech recognition.
+
+use sxd_document::{writer, Package};
+
+use super::asr::{Milliseconds, Segment};
+
+const XSI: &str = "http://www.w3.org/2001/XMLSchema-instance";
+const SCHEMA: &str = "http://www.mpi.nl/tools/elan/EAFv3.0.xsd";
+const LINGUISTIC_TYPE: &str = "default-lt";
+
+#[derive(Debug)]
+pub struct DraftTier {
+    pub id: String,
+    pub participant: Option<String>,
+    pub segments: Vec<Segment>,
+}
+
+#[derive(Debug)]
+pub struct Draft {
+    pub author: String,
+    /// ISO 8601, e.g. `2026-10-15T12:00:00+02:00`.
+    pub date: String,
+    pub media_url: Option<String>,
+    pub tiers: Vec<DraftTier>,
+}
+
+impl Draft {
+    pub fn to_xml(&self) -> String {
+        let package = Package::new();
+        let doc = package.as_document();
+
+        let root = doc.create_element("ANNOTATION_DOCUMENT");
+        root.set_attribute_value("AUTHOR", &self.author);
+        root.set_attribute_value("DATE", &self.date);
+        root.set_attribute_value("FORMAT", "3.0");
+        root.set_attribute_value("VERSION", "3.0");
+        root.set_attribute_value((XSI, "noNamespaceSchemaLocation"), SCHEMA)
+            .set_preferred_prefix(Some("xsi"));
+        doc.root().append_child(root);
+
+        let header = doc.create_element("HEADER");
+        header.set_attribute_value("MEDIA_FILE", "");
+        header.set_attribute_value("TIME_UNITS", "milliseconds");
+        if let Some(url) = &self.media_url {
+            let media = doc.create_element("MEDIA_DESCRIPTOR");
+            media.set_attribute_value("MEDIA_URL", url);
+            media.set_attribute_value("MIME_TYPE", mime_type(url));
+            header.append_child(media);
+        }
+        root.append_child(header);
+
+        // each annotation gets its own pair of time slots, numbered in
+        // chronological order as ELAN does
+        let mut times: Vec<(Milliseconds, usize)> = self
+            .tiers
+            .iter()
+            .flat_map(|t| &t.segments)
+            .flat_map(|s| vec![s.start, s.end])
+            .enumerate()
+            .map(|(i, t)| (t, i))
+            .collect();
+        times.sort();
+        let mut slot_ids = vec![String::new(); times.len()];
+        let time_order = doc.create_element("TIME_ORDER");
+        for (n, (time, i)) in times.into_iter().enumerate() {
+            let id = format!("ts{}", n + 1);
+            let slot = doc.create_element("TIME_SLOT");
+            slot.set_attribute_value("TIME_SLOT_ID", &id);
+            slot.set_attribute_value("TIME_VALUE", &time.to_string());
+            time_order.append_child(slot);
+            slot_ids[i] = id;
+        }
+        root.append_child(time_order);
+
+        let mut n = 0;
+        for tier in &self.tiers {
+            let element = doc.create_element("TIER");
+            element.set_attribute_value("LINGUISTIC_TYPE_REF", LINGUISTIC_TYPE);
+            if let Some(participant) = &tier.participant {
+                element.set_attribute_value("PARTICIPANT", participant);
+            }
+            element.set_attribute_value("TIER_ID", &tier.id);
+            for segment in &tier.segments {
+                let alignable = doc.create_element("ALIGNABLE_ANNOTATION");
+                alignable.set_attribute_value("ANNOTATION_ID", &format!("a{}", n / 2 + 1));
+                alignable.set_attribute_value("TIME_SLOT_REF1", &slot_ids[n]);
+                alignable.set_attribute_value("TIME_SLOT_REF2", &slot_ids[n + 1]);
+                let value = doc.create_element("ANNOTATION_VALUE");
+                value.set_text(&segment.text);
+                alignable.append_child(value);
+                let annotation = doc.create_element("ANNOTATION");
+                annotation.append_child(alignable);
+                element.append_child(annotation);
+                n += 2;
+            }
+            root.append_child(element);
+        }
+
+        let linguistic_type = doc.create_element("LINGUISTIC_TYPE");
+        linguistic_type.set_attribute_value("GRAPHIC_REFERENCES", "false");
+        linguistic_type.set_attribute_value("LINGUISTIC_TYPE_ID", LINGUISTIC_TYPE);
+        linguistic_type.set_attribute_value("TIME_ALIGNABLE", "true");
+        root.append_child(linguistic_type);
+
+        let mut xml = vec![];
+        // writing to a Vec can't fail
+        writer::format_document(&doc, &mut xml).unwrap();
+        String::from_utf8(xml).unwrap()
+    }
+}
+
+fn mime_type(url: &str) -> &'static str {
+    let ext = url.rsplit('.').next().unwrap_or("").to_lowercase();
+    match ext.as_str() {
+        "mp3" => "audio/mpeg",
+        "opus" | "ogg" => "audio/ogg",
+        "flac" => "audio/flac",
+        "mp4" => "video/mp4",
+        _ => "audio/x-wav",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_xml() {
+        let segment = |start, end, text: &str| Segment {
+            start,
+            end,
+            text: text.to_owned(),
+            speaker: None,
+        };
+        let draft = Draft {
+            author: "asr".to_owned(),
+            date: "2026-10-15T12:00:00+02:00".to_owned(),
+            media_url: Some("file:///rec.wav".to_owned()),
+            tiers: vec![
+                DraftTier {
+                    id: "ort@Jana".to_owned(),
+                    participant: Some("Jana".to_owned()),
+                    segments: vec![segment(1000, 2000, "a <b> & c")],
+                },
+                DraftTier {
+                    id: "ort@Petr".to_owned(),
+                    participant: None,
+                    segments: vec![segment(500, 1500, "no")],
+                },
+            ],
+        };
+        let xml = draft.to_xml();
+        let package = sxd_document::parser::parse(&xml).unwrap();
+        let doc = package.as_document();
+        let value = |xpath| sxd_xpath::evaluate_xpath(&doc, xpath).unwrap().string();
+        assert_eq!(
+            value("//ALIGNABLE_ANNOTATION[@ANNOTATION_ID='a1']/ANNOTATION_VALUE"),
+            "a <b> & c"
+        );
+        // chronological numbering of time slots across tiers
+        assert_eq!(
+            value("//ALIGNABLE_ANNOTATION[@ANNOTATION_ID='a2']/@TIME_SLOT_REF1"),
+            "ts1"
+        );
+        assert_eq!(
+            value("//TIME_SLOT[@TIME_SLOT_ID='ts3']/@TIME_VALUE"),
+            "1500"
+        );
+        assert_eq!(value("//TIER[@TIER_ID='ort@Jana']/@PARTICIPANT"), "Jana");
+        assert_eq!(value("//MEDIA_DESCRIPTOR/@MIME_TYPE"), "audio/x-wav");
+        assert!(xml.contains(" xsi:noNamespaceSchemaLocation="));
+    }
+}
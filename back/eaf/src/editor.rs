@@ -0,0 +1,337 @@
+//! Structural edits of an EAF's tiers, keeping references between tiers,
+//! their linguistic types and the time slots consistent, e.g. for bulk
+//! fixes across many files.
+
+use std::collections::HashSet;
+use std::fmt;
+
+use sxd_document::dom::{ChildOfElement, Element};
+use sxd_document::{parser, writer, Package};
+
+use super::annotations::{children, named};
+use super::template::annotation_document;
+
+#[derive(Debug)]
+pub struct EditError(String);
+
+impl fmt::Display for EditError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "can't edit EAF: {}", self.0)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NewTier {
+    pub id: String,
+    pub linguistic_type: String,
+    /// Dependent tiers need a linguistic type with constraints.
+    pub parent: Option<String>,
+    /// Defaults to the parent's participant.
+    pub participant: Option<String>,
+    pub annotator: Option<String>,
+}
+
+/// An EAF opened for editing.
+pub struct Editor {
+    package: Package,
+}
+
+impl Editor {
+    pub fn parse(xml: &str) -> Result<Self, EditError> {
+        let package = parser::parse(xml).map_err(|e| EditError(format!("{:?}", e)))?;
+        if annotation_document(&package.as_document()).is_none() {
+            return Err(EditError("missing ANNOTATION_DOCUMENT".to_owned()));
+        }
+        Ok(Self { package })
+    }
+
+    fn root(&self) -> Element<'_> {
+        // checked when parsing
+        annotation_document(&self.package.as_document()).unwrap()
+    }
+
+    fn tier(&self, id: &str) -> Option<Element<'_>> {
+        named(self.root(), "TIER").find(|t| t.attribute_value("TIER_ID") == Some(id))
+    }
+
+    /// In document order.
+    pub fn tier_ids(&self) -> Vec<String> {
+        named(self.root(), "TIER")
+            .filter_map(|t| t.attribute_value("TIER_ID"))
+            .map(str::to_owned)
+            .collect()
+    }
+
+    /// Add an empty tier after the existing ones. The linguistic type must
+    /// exist and be constrained iff the tier is a dependent one.
+    pub fn add_tier(&mut self, tier: &NewTier) -> Result<(), EditError> {
+        let root = self.root();
+        if tier.id.trim().is_empty() {
+            return Err(EditError("empty tier ID".to_owned()));
+        }
+        if self.tier(&tier.id).is_some() {
+            return Err(EditError(format!("tier {:?} already exists", tier.id)));
+        }
+        let linguistic_type = named(root, "LINGUISTIC_TYPE")
+            .find(|t| t.attribute_value("LINGUISTIC_TYPE_ID") == Some(&tier.linguistic_type))
+            .ok_or_else(|| {
+                EditError(format!(
+                    "no such linguistic type {:?}",
+                    tier.linguistic_type
+                ))
+            })?;
+        let constrained = linguistic_type.attribute_value("CONSTRAINTS").is_some();
+        let parent = match &tier.parent {
+            Some(parent) => Some(
+                self.tier(parent)
+                    .ok_or_else(|| EditError(format!("no such parent tier {:?}", parent)))?,
+            ),
+            None => None,
+        };
+        if constrained != parent.is_some() {
+            return Err(EditError(format!(
+                "linguistic type {:?} is {}for dependent tiers",
+                tier.linguistic_type,
+                if constrained { "" } else { "not " }
+            )));
+        }
+
+        let doc = self.package.as_document();
+        let element = doc.create_element("TIER");
+        element.set_attribute_value("LINGUISTIC_TYPE_REF", &tier.linguistic_type);
+        let participant = tier
+            .participant
+            .as_deref()
+            .or_else(|| parent.and_then(|p| p.attribute_value("PARTICIPANT")));
+        if let Some(participant) = participant {
+            element.set_attribute_value("PARTICIPANT", participant);
+        }
+        if let Some(annotator) = &tier.annotator {
+            element.set_attribute_value("ANNOTATOR", annotator);
+        }
+        if let Some(parent) = &tier.parent {
+            element.set_attribute_value("PARENT_REF", parent);
+        }
+        element.set_attribute_value("TIER_ID", &tier.id);
+
+        // tiers go before linguistic types and the rest
+        let mut root_children = root.children();
+        let is = |child: &ChildOfElement<'_>, name| match child {
+            ChildOfElement::Element(e) => e.name().local_part() == name,
+            _ => false,
+        };
+        let at = match root_children.iter().rposition(|c| is(c, "TIER")) {
+            Some(last) => last + 1,
+            None => root_children
+                .iter()
+                .position(|c| is(c, "LINGUISTIC_TYPE"))
+                .unwrap_or(root_children.len()),
+        };
+        root_children.insert(at, ChildOfElement::Element(element));
+        root.replace_children(root_children);
+        Ok(())
+    }
+
+    /// Remove the tier along with the tiers depending on it, as well as
+    /// time slots nothing refers to anymore. Returns the IDs of all removed
+    /// tiers.
+    pub fn remove_tier(&mut self, id: &str) -> Result<Vec<String>, EditError> {
+        if self.tier(id).is_none() {
+            return Err(EditError(format!("no such tier {:?}", id)));
+        }
+        let root = self.root();
+        let mut removed = vec![id.to_owned()];
+        // dependents may come before their parents in the document
+        loop {
+            let more: Vec<_> = named(root, "TIER")
+                .filter(|t| {
+                    t.attribute_value("PARENT_REF")
+                        .map_or(false, |p| removed.iter().any(|r| r == p))
+                })
+                .filter_map(|t| t.attribute_value("TIER_ID"))
+                .filter(|t| !removed.iter().any(|r| r == t))
+                .map(str::to_owned)
+                .collect();
+            if more.is_empty() {
+                break;
+            }
+            removed.extend(more);
+        }
+
+        let slot_refs = |tier: Element<'_>| -> Vec<String> {
+            children(tier)
+                .flat_map(children)
+                .flat_map(|a| {
+                    let refs = ["TIME_SLOT_REF1", "TIME_SLOT_REF2"];
+                    refs.iter()
+                        .filter_map(|&r| a.attribute_value(r))
+                        .map(str::to_owned)
+                        .collect::<Vec<_>>()
+                })
+                .collect()
+        };
+        let mut orphaned = HashSet::new();
+        for tier in named(root, "TIER").collect::<Vec<_>>() {
+            let tier_id = tier.attribute_value("TIER_ID").unwrap_or_default();
+            if removed.iter().any(|r| r == tier_id) {
+                orphaned.extend(slot_refs(tier));
+                tier.remove_from_parent();
+            }
+        }
+        for tier in named(root, "TIER") {
+            for slot in slot_refs(tier) {
+                orphaned.remove(&slot);
+            }
+        }
+        for slot in named(root, "TIME_ORDER").flat_map(|o| named(o, "TIME_SLOT")) {
+            if slot
+                .attribute_value("TIME_SLOT_ID")
+                .map_or(false, |s| orphaned.contains(s))
+            {
+                slot.remove_from_parent();
+            }
+        }
+        Ok(removed)
+    }
+
+    /// Rename the tier, updating the references of its dependents.
+    pub fn rename_tier(&mut self, from: &str, to: &str) -> Result<(), EditError> {
+        let tier = self
+            .tier(from)
+            .ok_or_else(|| EditError(format!("no such tier {:?}", from)))?;
+        if to.trim().is_empty() {
+            return Err(EditError("empty tier ID".to_owned()));
+        }
+        if from == to {
+            return Ok(());
+        }
+        if self.tier(to).is_some() {
+            return Err(EditError(format!("tier {:?} already exists", to)));
+        }
+        tier.set_attribute_value("TIER_ID", to);
+        for dependent in named(self.root(), "TIER") {
+            if dependent.attribute_value("PARENT_REF") == Some(from) {
+                dependent.set_attribute_value("PARENT_REF", to);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn to_xml(&self) -> String {
+        let mut out = vec![];
+        // writing to a Vec can't fail, and its contents were parsed from a
+        // string
+        writer::format_document(&self.package.as_document(), &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::annotations;
+
+    const EAF: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<ANNOTATION_DOCUMENT AUTHOR="" DATE="2020-01-01T00:00:00+01:00" FORMAT="3.0" VERSION="3.0">
+    <HEADER MEDIA_FILE="" TIME_UNITS="milliseconds"/>
+    <TIME_ORDER>
+        <TIME_SLOT TIME_SLOT_ID="ts1" TIME_VALUE="0"/>
+        <TIME_SLOT TIME_SLOT_ID="ts2" TIME_VALUE="100"/>
+        <TIME_SLOT TIME_SLOT_ID="ts3" TIME_VALUE="200"/>
+    </TIME_ORDER>
+    <TIER LINGUISTIC_TYPE_REF="fon" PARENT_REF="ort@Jana" TIER_ID="fon@Jana">
+        <ANNOTATION>
+            <REF_ANNOTATION ANNOTATION_ID="a3" ANNOTATION_REF="a1">
+                <ANNOTATION_VALUE>ahoj</ANNOTATION_VALUE>
+            </REF_ANNOTATION>
+        </ANNOTATION>
+    </TIER>
+    <TIER LINGUISTIC_TYPE_REF="ort" PARTICIPANT="Jana" TIER_ID="ort@Jana">
+        <ANNOTATION>
+            <ALIGNABLE_ANNOTATION ANNOTATION_ID="a1" TIME_SLOT_REF1="ts1" TIME_SLOT_REF2="ts3">
+                <ANNOTATION_VALUE>ahoj</ANNOTATION_VALUE>
+            </ALIGNABLE_ANNOTATION>
+        </ANNOTATION>
+    </TIER>
+    <TIER LINGUISTIC_TYPE_REF="ort" PARTICIPANT="Petr" TIER_ID="ort@Petr">
+        <ANNOTATION>
+            <ALIGNABLE_ANNOTATION ANNOTATION_ID="a2" TIME_SLOT_REF1="ts2" TIME_SLOT_REF2="ts3">
+                <ANNOTATION_VALUE>jo</ANNOTATION_VALUE>
+            </ALIGNABLE_ANNOTATION>
+        </ANNOTATION>
+    </TIER>
+    <LINGUISTIC_TYPE GRAPHIC_REFERENCES="false" LINGUISTIC_TYPE_ID="ort" TIME_ALIGNABLE="true"/>
+    <LINGUISTIC_TYPE CONSTRAINTS="Symbolic_Association" GRAPHIC_REFERENCES="false" LINGUISTIC_TYPE_ID="fon" TIME_ALIGNABLE="false"/>
+    <CONSTRAINT DESCRIPTION="1-1 association with a parent annotation" STEREOTYPE="Symbolic_Association"/>
+</ANNOTATION_DOCUMENT>"#;
+
+    fn new_tier(id: &str, linguistic_type: &str, parent: Option<&str>) -> NewTier {
+        NewTier {
+            id: id.to_owned(),
+            linguistic_type: linguistic_type.to_owned(),
+            parent: parent.map(str::to_owned),
+            participant: None,
+            annotator: None,
+        }
+    }
+
+    #[test]
+    fn test_add_tier() {
+        let mut editor = Editor::parse(EAF).unwrap();
+        editor
+            .add_tier(&new_tier("fon@Petr", "fon", Some("ort@Petr")))
+            .unwrap();
+        assert!(editor.add_tier(&new_tier("ort@Petr", "ort", None)).is_err());
+        assert!(editor.add_tier(&new_tier("x", "nope", None)).is_err());
+        assert!(editor.add_tier(&new_tier("x", "fon", None)).is_err());
+        assert!(editor
+            .add_tier(&new_tier("x", "ort", Some("ort@Jana")))
+            .is_err());
+        assert!(editor
+            .add_tier(&new_tier("x", "fon", Some("nope")))
+            .is_err());
+
+        let xml = editor.to_xml();
+        assert_eq!(
+            Editor::parse(&xml).unwrap().tier_ids(),
+            vec!["fon@Jana", "ort@Jana", "ort@Petr", "fon@Petr"]
+        );
+        // before the linguistic types, inheriting the participant
+        let tier = xml.find("TIER_ID='fon@Petr'").unwrap();
+        assert!(tier < xml.find("<LINGUISTIC_TYPE").unwrap());
+        assert_eq!(xml.matches("PARTICIPANT='Petr'").count(), 2);
+    }
+
+    #[test]
+    fn test_remove_tier() {
+        let mut editor = Editor::parse(EAF).unwrap();
+        assert_eq!(
+            editor.remove_tier("ort@Jana").unwrap(),
+            vec!["ort@Jana", "fon@Jana"]
+        );
+        assert!(editor.remove_tier("ort@Jana").is_err());
+        let xml = editor.to_xml();
+        assert_eq!(Editor::parse(&xml).unwrap().tier_ids(), vec!["ort@Petr"]);
+        // ts1 was only used by the removed tier
+        assert!(!xml.contains("ts1"));
+        assert!(xml.contains("ts3"));
+    }
+
+    #[test]
+    fn test_rename_tier() {
+        let mut editor = Editor::parse(EAF).unwrap();
+        editor.rename_tier("ort@Jana", "ort@Jana2").unwrap();
+        assert!(editor.rename_tier("ort@Jana", "x").is_err());
+        assert!(editor.rename_tier("ort@Jana2", "ort@Petr").is_err());
+        let annotations = annotations::read(&editor.to_xml()).unwrap();
+        let tiers: Vec<_> = annotations.iter().map(|a| a.tier.as_str()).collect();
+        assert_eq!(tiers, vec!["fon@Jana", "ort@Jana2", "ort@Petr"]);
+        let xml = editor.to_xml();
+        assert!(xml.contains("PARENT_REF='ort@Jana2'"));
+    }
+
+    #[test]
+    fn test_not_eaf() {
+        assert!(Editor::parse("<html/>").is_err());
+    }
+}
@@ -0,0 +1,275 @@
+//! Machine-applicable fixes for mistakes with an obvious remedy, so that
+//! editors can offer to apply them with a single click.
+
+use std::cmp::Reverse;
+
+use super::parser::{Mistake, Parsed, ParserConfig, WhitespaceKind};
+
+/// Replace the `start..end` byte range of the (normalized) segment with
+/// `replacement`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Edit {
+    pub start: usize,
+    pub end: usize,
+    pub replacement: String,
+}
+
+/// Commonly mistyped graphemes and what they should be replaced with, e.g.
+/// a precomposed character for its decomposed variant.
+#[derive(Debug, Default)]
+pub struct Substitutions(Vec<(String, String)>);
+
+impl Substitutions {
+    pub fn new<S: Into<String>, T: Into<String>>(pairs: impl IntoIterator<Item = (S, T)>) -> Self {
+        let mut pairs: Vec<_> = pairs
+            .into_iter()
+            .map(|(s, t)| (s.into(), t.into()))
+            .filter(|(s, _)| !s.is_empty())
+            .collect();
+        // prefer longer matches, like the parser's atoms
+        pairs.sort_by_key(|(s, _)| Reverse(s.len()));
+        Self(pairs)
+    }
+
+    /// Replace all non-overlapping occurrences, scanning left to right.
+    pub fn apply(&self, text: &str) -> String {
+        let mut applied = String::new();
+        let mut rest = text;
+        'outer: while let Some(c) = rest.chars().next() {
+            for (source, target) in &self.0 {
+                if rest.starts_with(source.as_str()) {
+                    applied.push_str(target);
+                    rest = &rest[source.len()..];
+                    continue 'outer;
+                }
+            }
+            applied.push(c);
+            rest = &rest[c.len_utf8()..];
+        }
+        applied
+    }
+}
+
+/// The fix for the mistake, if there's an obvious one, `config` being the
+/// one the segment was parsed with:
+///
+/// - unclosed delimiters are closed at the end of the segment, with the
+///   config's counterpart of the opening one,
+/// - duplicate attribute codes are removed (but not keys repeated with
+///   different values, as it's not clear which one is meant),
+/// - missing span labels are added (mismatched ones are left alone, as it's
+///   not clear whether the label or the opening attributes are wrong),
+/// - bad substrings are replaced according to `substitutions`, if they
+///   cover them,
+/// - leading and trailing whitespace is removed, other irregular
+///   whitespace replaced with a single space.
+pub fn quick_fix(
+    config: &ParserConfig,
+    parsed: &Parsed,
+    mistake: &Mistake,
+    substitutions: &Substitutions,
+) -> Option<Edit> {
+    let (_, start, end) = parsed.span(mistake);
+    match mistake {
+        Mistake::UnclosedDelim { .. } => {
+            let closing = config.delims().closing(&parsed.source[start..end])?;
+            // before any trailing whitespace, which is reported separately
+            let at = parsed.source.trim_end().len();
+            Some(Edit {
+                start: at,
+                end: at,
+                replacement: closing.to_owned(),
+            })
+        }
+        Mistake::DuplicateAttr { .. } => {
+            let mut codes: Vec<&str> = vec![];
+            for code in parsed.source[start..end].split('_') {
+                if !codes.contains(&code) {
+                    codes.push(code);
+                }
+            }
+            let replacement = codes.join("_");
+            if replacement == parsed.source[start..end] {
+                None
+            } else {
+                Some(Edit {
+                    start,
+                    end,
+                    replacement,
+                })
+            }
+        }
+        // a missing label is reported at the >, so it goes before it
+        Mistake::MismatchedSpanLabel {
+            label, expected, ..
+        } if label.is_empty() => Some(Edit {
+            start,
+            end: start,
+            replacement: expected.clone(),
+        }),
+        Mistake::BadSubstr { .. } => {
+            let bad = &parsed.source[start..end];
+            let replacement = substitutions.apply(bad);
+            if replacement == bad {
+                None
+            } else {
+                Some(Edit {
+                    start,
+                    end,
+                    replacement,
+                })
+            }
+        }
+        Mistake::Whitespace { kind, .. } => {
+            let replacement = match kind {
+                WhitespaceKind::Leading | WhitespaceKind::Trailing => "",
+                WhitespaceKind::Double | WhitespaceKind::NonSpace => " ",
+            };
+            Some(Edit {
+                start,
+                end,
+                replacement: replacement.to_owned(),
+            })
+        }
+        _ => None,
+    }
+}
+
+impl Parsed {
+    /// Fixes for all mistakes with an obvious one (see `quick_fix`), in
+    /// source order, without overlaps. Spans left unclosed are closed
+    /// innermost first.
+    pub fn fixes(&self, config: &ParserConfig, substitutions: &Substitutions) -> Vec<Edit> {
+        let mut edits: Vec<_> = self
+            .mistakes
+            .iter()
+            .filter_map(|mistake| {
+                let edit = quick_fix(config, self, mistake, substitutions)?;
+                let opened_at = match mistake {
+                    Mistake::UnclosedDelim { at, .. } => *at,
+                    _ => 0,
+                };
+                Some((edit, Reverse(opened_at)))
+            })
+            .collect();
+        edits.sort_by_key(|(edit, opened_at)| (edit.start, edit.end, *opened_at));
+        let mut fixes: Vec<Edit> = vec![];
+        for (edit, _) in edits {
+            // an insertion at the end of the previous edit doesn't overlap it
+            if fixes.last().map_or(true, |prev| edit.start >= prev.end) {
+                fixes.push(edit);
+            }
+        }
+        fixes
+    }
+
+    /// The source with all fixes applied, e.g. for a one-click correction.
+    /// Mistakes without an obvious fix remain.
+    pub fn apply_fixes(&self, config: &ParserConfig, substitutions: &Substitutions) -> String {
+        let mut source = self.source.clone();
+        // back to front, so that offsets of the remaining edits stay valid
+        for edit in self.fixes(config, substitutions).iter().rev() {
+            source.replace_range(edit.start..edit.end, &edit.replacement);
+        }
+        source
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{Parser, ParserConfig, SpanLabels};
+    use crate::tokenizer::{self, Delim, DelimKind, TokenizerConfig, WhitespacePolicy};
+
+    fn fixes(segment: &str, policy: WhitespacePolicy) -> Vec<Option<Edit>> {
+        let config = ParserConfig::from_args(&[] as &[&str], &[] as &[&str], &["a", "ř"], &["SM"]);
+        let substitutions = Substitutions::new(vec![("rz", "ř"), ("á", "a")]);
+        let parsed = Parser::parse(&config, tokenizer::tokenize_with(segment, policy));
+        parsed
+            .mistakes
+            .iter()
+            .map(|m| quick_fix(&config, &parsed, m, &substitutions))
+            .collect()
+    }
+
+    fn edit(start: usize, end: usize, replacement: &str) -> Option<Edit> {
+        Some(Edit {
+            start,
+            end,
+            replacement: replacement.to_owned(),
+        })
+    }
+
+    #[test]
+    fn test_substitutions() {
+        let substitutions = Substitutions::new(vec![("r", "x"), ("rz", "ř")]);
+        assert_eq!(substitutions.apply("rzar"), "řax");
+        assert_eq!(Substitutions::default().apply("rz"), "rz");
+    }
+
+    #[test]
+    fn test_quick_fixes() {
+        assert_eq!(
+            fixes("(a <SM_SM a", WhitespacePolicy::Normalize),
+            vec![edit(4, 9, "SM"), edit(11, 11, ")"), edit(11, 11, ">")]
+        );
+        assert_eq!(
+            fixes("arza rzb", WhitespacePolicy::Normalize),
+            vec![edit(1, 3, "ř"), edit(5, 8, "řb")]
+        );
+        assert_eq!(
+            fixes("a  a ", WhitespacePolicy::Report),
+            vec![edit(1, 3, " "), edit(4, 5, "")]
+        );
+        assert_eq!(fixes("<SJ a>", WhitespacePolicy::Normalize), vec![None]);
+
+        let config = ParserConfig::from_args(&[] as &[&str], &[] as &[&str], &["a"], &["SM"])
+            .with_span_labels(SpanLabels::Required);
+        let parsed = Parser::parse(&config, tokenizer::tokenize("<SM a > <SM a>"));
+        let labels: Vec<_> = parsed
+            .mistakes
+            .iter()
+            .map(|m| quick_fix(&config, &parsed, m, &Substitutions::default()))
+            .collect();
+        // the second span has a label, just a wrong one
+        assert_eq!(labels, vec![edit(6, 6, "SM"), None]);
+    }
+
+    #[test]
+    fn test_apply_fixes() {
+        let config = ParserConfig::from_args(&[] as &[&str], &[] as &[&str], &["a", "ř"], &["SM"]);
+        let substitutions = Substitutions::new(vec![("rz", "ř")]);
+        let apply = |segment: &str, policy| {
+            Parser::parse(&config, tokenizer::tokenize_with(segment, policy))
+                .apply_fixes(&config, &substitutions)
+        };
+        assert_eq!(
+            apply("(a <SM_SM a", WhitespacePolicy::Normalize),
+            "(a <SM a>)"
+        );
+        assert_eq!(apply("[a (arza ", WhitespacePolicy::Report), "[a (ařa)]");
+        // no obvious fix
+        assert_eq!(apply("a x", WhitespacePolicy::Normalize), "a x");
+    }
+
+    #[test]
+    fn test_custom_delims() {
+        let delims = TokenizerConfig::new(vec![
+            Delim::new("{", "}", DelimKind::Round),
+            Delim::new("//", "//", DelimKind::Square),
+            Delim::new("<", ">", DelimKind::Angle),
+        ])
+        .unwrap();
+        let config = ParserConfig::from_args(&[] as &[&str], &[] as &[&str], &["a"], &["SM"])
+            .with_delims(delims);
+        let substitutions = Substitutions::default();
+        let parsed = Parser::parse(&config, config.tokenize("{a //a"));
+        assert_eq!(
+            parsed.fixes(&config, &substitutions),
+            vec![edit(6, 6, "//").unwrap(), edit(6, 6, "}").unwrap()]
+        );
+        let fixed = parsed.apply_fixes(&config, &substitutions);
+        assert_eq!(fixed, "{a //a//}");
+        assert!(!Parser::parse(&config, config.tokenize(&fixed)).has_mistakes());
+    }
+}
@@ -0,0 +1,175 @@
+//! Frequency lists of tokens or, more generally, of matches of a query (see
+//! `query`), e.g. only of tokens in uncertain passages (`[] within round`)
+//! or of bigrams (`[] []`). Matches are counted lowercased, like types in
+//! `stats`, with the tokens of multi-token matches separated by spaces.
+//!
+//! The CSV has the same shape as the lists `candidates` takes.
+
+use std::collections::HashMap;
+
+use super::annotations::{self, ReadError};
+use super::parser::{Parsed, Parser, ParserConfig};
+use super::query::Query;
+use super::tiers::TierMapping;
+
+/// Columns of `FrequencyList::records`.
+pub const CSV_HEADER: &[&str] = &["token", "count"];
+
+#[derive(Debug, Default, PartialEq)]
+pub struct FrequencyList(HashMap<String, u64>);
+
+impl FrequencyList {
+    /// Count the matches of the query in the segment.
+    pub fn add(&mut self, parsed: &Parsed, query: &Query) {
+        let tokens = parsed.flagged_tokens();
+        for m in query.find(parsed) {
+            let words: Vec<_> = tokens[m.first..=m.last]
+                .iter()
+                .map(|(token, _)| &parsed.source[token.start..token.end])
+                .collect();
+            *self.0.entry(words.join(" ").to_lowercase()).or_default() += 1;
+        }
+    }
+
+    /// Count the matches of the query in the transcript's segments of the
+    /// speakers `speaker` accepts. Speakers are identified as in
+    /// `stats::segments`, but segments are parsed with the project's rules,
+    /// so that attribute codes can be queried.
+    pub fn add_transcript<F>(
+        &mut self,
+        xml: &str,
+        mapping: &TierMapping,
+        config: &ParserConfig,
+        query: &Query,
+        speaker: F,
+    ) -> Result<(), ReadError>
+    where
+        F: Fn(&str) -> bool,
+    {
+        for annotation in annotations::read(xml)? {
+            if !annotation.is_transcript(mapping) {
+                continue;
+            }
+            let participant = match annotation.participant.as_deref() {
+                Some("") => None,
+                participant => participant,
+            };
+            let nickname = mapping
+                .nickname(&annotation.tier, annotation.participant.as_deref())
+                .or(participant)
+                .unwrap_or(&annotation.tier);
+            if speaker(nickname) {
+                self.add(
+                    &Parser::parse(config, config.tokenize(&annotation.value)),
+                    query,
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Number of matches counted.
+    pub fn total(&self) -> u64 {
+        self.0.values().sum()
+    }
+
+    /// Most frequent first, ties in alphabetical order.
+    pub fn sorted(&self) -> Vec<(&str, u64)> {
+        let mut sorted: Vec<_> = self.0.iter().map(|(t, &c)| (t.as_str(), c)).collect();
+        sorted.sort_unstable_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        sorted
+    }
+
+    /// CSV records, cf. `CSV_HEADER`, most frequent first.
+    pub fn records(&self) -> Vec<Vec<String>> {
+        self.sorted()
+            .into_iter()
+            .map(|(token, count)| vec![token.to_owned(), count.to_string()])
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tiers::{TierPattern, TierSource};
+
+    fn config() -> ParserConfig {
+        ParserConfig::from_args::<&str, &str, &str, _>(&[], &[], &[], &["SM"])
+    }
+
+    fn count(query: &str, segments: &[&str]) -> Vec<(String, u64)> {
+        let config = config();
+        let query = Query::parse(query).unwrap();
+        let mut list = FrequencyList::default();
+        for segment in segments {
+            list.add(&Parser::parse(&config, config.tokenize(segment)), &query);
+        }
+        list.sorted()
+            .into_iter()
+            .map(|(t, c)| (t.to_owned(), c))
+            .collect()
+    }
+
+    #[test]
+    fn test_add() {
+        let segments = ["no tak (no) <SM tak>", "Tak jo (5)"];
+        assert_eq!(
+            count("[]", &segments),
+            vec![
+                ("tak".to_owned(), 3),
+                ("no".to_owned(), 2),
+                ("5".to_owned(), 1),
+                ("jo".to_owned(), 1),
+            ]
+        );
+        assert_eq!(
+            count("[] within round", &segments),
+            vec![("5".to_owned(), 1), ("no".to_owned(), 1)]
+        );
+        assert_eq!(
+            count(r#"[] within angle(code="SM")"#, &segments),
+            vec![("tak".to_owned(), 1)]
+        );
+        assert_eq!(
+            count("[] []", &segments[1..]),
+            vec![("jo 5".to_owned(), 1), ("tak jo".to_owned(), 1)]
+        );
+    }
+
+    #[test]
+    fn test_add_transcript() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<ANNOTATION_DOCUMENT>
+  <TIME_ORDER>
+    <TIME_SLOT TIME_SLOT_ID="ts1" TIME_VALUE="0"/>
+    <TIME_SLOT TIME_SLOT_ID="ts2" TIME_VALUE="1000"/>
+  </TIME_ORDER>
+  <TIER TIER_ID="ort@A" LINGUISTIC_TYPE_REF="ort">
+    <ANNOTATION><ALIGNABLE_ANNOTATION ANNOTATION_ID="a1" TIME_SLOT_REF1="ts1" TIME_SLOT_REF2="ts2">
+      <ANNOTATION_VALUE>no tak</ANNOTATION_VALUE>
+    </ALIGNABLE_ANNOTATION></ANNOTATION>
+  </TIER>
+  <TIER TIER_ID="ort@B" LINGUISTIC_TYPE_REF="ort">
+    <ANNOTATION><ALIGNABLE_ANNOTATION ANNOTATION_ID="a2" TIME_SLOT_REF1="ts1" TIME_SLOT_REF2="ts2">
+      <ANNOTATION_VALUE>no jo</ANNOTATION_VALUE>
+    </ALIGNABLE_ANNOTATION></ANNOTATION>
+  </TIER>
+  <TIER TIER_ID="comments" LINGUISTIC_TYPE_REF="ort">
+    <ANNOTATION><ALIGNABLE_ANNOTATION ANNOTATION_ID="a3" TIME_SLOT_REF1="ts1" TIME_SLOT_REF2="ts2">
+      <ANNOTATION_VALUE>no</ANNOTATION_VALUE>
+    </ALIGNABLE_ANNOTATION></ANNOTATION>
+  </TIER>
+</ANNOTATION_DOCUMENT>"#;
+        let mapping = TierMapping::new(vec![(
+            TierSource::TierId,
+            TierPattern::new("ort@<nickname>").unwrap(),
+        )]);
+        let query = Query::parse("[]").unwrap();
+        let mut list = FrequencyList::default();
+        list.add_transcript(xml, &mapping, &config(), &query, |speaker| speaker == "A")
+            .unwrap();
+        assert_eq!(list.records(), vec![vec!["no", "1"], vec!["tak", "1"]]);
+        assert_eq!(list.total(), 2);
+    }
+}
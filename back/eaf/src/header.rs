@@ -0,0 +1,214 @@
+//! Document-level metadata of an EAF: the author and date on the root
+//! element, licenses and header properties, e.g. for stamping the documents
+//! of a project with the same metadata at release time.
+
+use std::fmt;
+
+use sxd_document::dom::ChildOfElement;
+use sxd_document::{parser, writer};
+
+use super::annotations::named;
+use super::stream;
+use super::template::annotation_document;
+
+#[derive(Debug)]
+pub struct HeaderError(String);
+
+impl fmt::Display for HeaderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "can't access EAF header: {}", self.0)
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct License {
+    pub url: Option<String>,
+    pub text: String,
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Header {
+    author: String,
+    /// An `xsd:dateTime`.
+    date: String,
+    licenses: Vec<License>,
+    /// In document order, names are unique.
+    properties: Vec<(String, String)>,
+}
+
+impl Header {
+    pub fn author(&self) -> &str {
+        &self.author
+    }
+
+    pub fn set_author(&mut self, author: impl Into<String>) {
+        self.author = author.into();
+    }
+
+    pub fn date(&self) -> &str {
+        &self.date
+    }
+
+    pub fn set_date(&mut self, date: impl Into<String>) {
+        self.date = date.into();
+    }
+
+    pub fn licenses(&self) -> &[License] {
+        &self.licenses
+    }
+
+    pub fn set_licenses(&mut self, licenses: Vec<License>) {
+        self.licenses = licenses;
+    }
+
+    pub fn properties(&self) -> &[(String, String)] {
+        &self.properties
+    }
+
+    pub fn property(&self, name: &str) -> Option<&str> {
+        self.properties
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Replace the property's value, or add it at the end if it's new.
+    pub fn set_property(&mut self, name: impl Into<String>, value: impl Into<String>) {
+        let (name, value) = (name.into(), value.into());
+        match self.properties.iter_mut().find(|(n, _)| *n == name) {
+            Some((_, v)) => *v = value,
+            None => self.properties.push((name, value)),
+        }
+    }
+
+    /// Whether the property was there.
+    pub fn remove_property(&mut self, name: &str) -> bool {
+        let len = self.properties.len();
+        self.properties.retain(|(n, _)| n != name);
+        self.properties.len() < len
+    }
+}
+
+pub fn read(xml: &str) -> Result<Header, HeaderError> {
+    stream::read(xml.as_bytes())
+        .map(|eaf| eaf.header)
+        .map_err(|e| HeaderError(e.0))
+}
+
+/// Replace the EAF's header metadata with `header`, leaving media
+/// descriptors etc. alone.
+pub fn write(xml: &str, header: &Header) -> Result<String, HeaderError> {
+    let package = parser::parse(xml).map_err(|e| HeaderError(format!("{:?}", e)))?;
+    let doc = package.as_document();
+    let root = annotation_document(&doc)
+        .ok_or_else(|| HeaderError("missing ANNOTATION_DOCUMENT".to_owned()))?;
+    let header_element = named(root, "HEADER")
+        .next()
+        .ok_or_else(|| HeaderError("missing HEADER".to_owned()))?;
+
+    root.set_attribute_value("AUTHOR", &header.author);
+    root.set_attribute_value("DATE", &header.date);
+
+    // licenses come right before the header, properties at its end
+    let mut root_children = vec![];
+    for child in root.children() {
+        match child {
+            ChildOfElement::Element(e) if e.name().local_part() == "LICENSE" => continue,
+            ChildOfElement::Element(e) if e == header_element => {
+                for license in &header.licenses {
+                    let element = doc.create_element("LICENSE");
+                    if let Some(url) = &license.url {
+                        element.set_attribute_value("LICENSE_URL", url);
+                    }
+                    element.set_text(&license.text);
+                    root_children.push(ChildOfElement::Element(element));
+                }
+            }
+            _ => {}
+        }
+        root_children.push(child);
+    }
+    root.replace_children(root_children);
+
+    for property in named(header_element, "PROPERTY").collect::<Vec<_>>() {
+        property.remove_from_parent();
+    }
+    for (name, value) in &header.properties {
+        let element = doc.create_element("PROPERTY");
+        element.set_attribute_value("NAME", name);
+        element.set_text(value);
+        header_element.append_child(element);
+    }
+
+    let mut out = vec![];
+    // writing to a Vec can't fail
+    writer::format_document(&doc, &mut out).unwrap();
+    String::from_utf8(out).map_err(|e| HeaderError(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EAF: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<ANNOTATION_DOCUMENT AUTHOR="Jana" DATE="2020-01-01T00:00:00+01:00" FORMAT="3.0" VERSION="3.0">
+    <LICENSE LICENSE_URL="https://example.com/old">Old</LICENSE>
+    <HEADER MEDIA_FILE="" TIME_UNITS="milliseconds">
+        <MEDIA_DESCRIPTOR MEDIA_URL="file:///rec.wav" MIME_TYPE="audio/x-wav"/>
+        <PROPERTY NAME="URN">urn:nl-mpi-tools-elan-eaf:1</PROPERTY>
+        <PROPERTY NAME="lastUsedAnnotationId">2</PROPERTY>
+        <PROPERTY NAME="URN">duplicate</PROPERTY>
+    </HEADER>
+    <TIME_ORDER/>
+</ANNOTATION_DOCUMENT>"#;
+
+    #[test]
+    fn test_read() {
+        let header = read(EAF).unwrap();
+        assert_eq!(header.author(), "Jana");
+        assert_eq!(header.date(), "2020-01-01T00:00:00+01:00");
+        assert_eq!(
+            header.licenses(),
+            &[License {
+                url: Some("https://example.com/old".to_owned()),
+                text: "Old".to_owned(),
+            }]
+        );
+        assert_eq!(header.properties().len(), 2);
+        assert_eq!(header.property("URN"), Some("urn:nl-mpi-tools-elan-eaf:1"));
+    }
+
+    #[test]
+    fn test_write() {
+        let mut header = read(EAF).unwrap();
+        header.set_author("ORTOFON");
+        header.set_licenses(vec![License {
+            url: None,
+            text: "CC BY-NC-SA 4.0".to_owned(),
+        }]);
+        header.set_property("lastUsedAnnotationId", "3");
+        header.set_property("release", "v1");
+        assert!(header.remove_property("URN"));
+        assert!(!header.remove_property("URN"));
+
+        let xml = write(EAF, &header).unwrap();
+        assert_eq!(read(&xml).unwrap(), header);
+        assert!(xml.contains("MEDIA_DESCRIPTOR"));
+        let license = xml.find("<LICENSE").unwrap();
+        assert!(license < xml.find("<HEADER").unwrap());
+        assert_eq!(xml.matches("<LICENSE").count(), 1);
+        assert_eq!(
+            header.properties(),
+            &[
+                ("lastUsedAnnotationId".to_owned(), "3".to_owned()),
+                ("release".to_owned(), "v1".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_not_eaf() {
+        assert!(read("<html/>").is_err());
+        assert!(write("<ANNOTATION_DOCUMENT/>", &Header::default()).is_err());
+    }
+}
@@ -0,0 +1,284 @@
+//! Render parsed segments as HTML, so that the frontend and printable
+//! reports show transcripts the same way.
+//!
+//! The output is a flat sequence of properly nested `<span>` elements:
+//!
+//! - `span span-round`, `span span-square`, `span span-angle` for paired
+//!   delimiter spans, including the delimiters themselves; angle spans also
+//!   get a `code-XY` class per attribute code,
+//! - `delim` for the delimiters, `attrs` for the attribute codes and `token`
+//!   for other tokens,
+//! - `mistake mistake-<kind>` (plus `warning`, cf. `Mistake::is_warning`)
+//!   for the extent of each mistake; mistakes without an extent (e.g. a
+//!   missing closing delimiter) are rendered as empty elements.
+//!
+//! Spans in transcripts can overlap without nesting, e.g. `[a <SM b] c>`, in
+//! which case elements are split as needed.
+
+use std::cmp::Reverse;
+
+use super::parser::Parsed;
+use super::tokenizer::{DelimKind, TokenKind};
+
+#[derive(Debug)]
+struct Mark {
+    start: usize,
+    end: usize,
+    class: String,
+    /// Breaks ties between marks with the same extent: lower ranks enclose
+    /// higher ones.
+    rank: u8,
+}
+
+fn escape(text: &str, html: &mut String) {
+    for c in text.chars() {
+        match c {
+            '&' => html.push_str("&amp;"),
+            '<' => html.push_str("&lt;"),
+            '>' => html.push_str("&gt;"),
+            '"' => html.push_str("&quot;"),
+            '\'' => html.push_str("&#39;"),
+            c => html.push(c),
+        }
+    }
+}
+
+/// Attribute codes are user-defined, keep only what's safe in a class name.
+fn code_class(code: &str) -> Option<String> {
+    let code: String = code
+        .chars()
+        .filter(|c| c.is_alphanumeric() || *c == '-' || *c == '_')
+        .collect();
+    if code.is_empty() {
+        None
+    } else {
+        Some(format!("code-{}", code))
+    }
+}
+
+fn kind_label(kind: DelimKind) -> &'static str {
+    match kind {
+        DelimKind::Round => "round",
+        DelimKind::Square => "square",
+        DelimKind::Angle => "angle",
+    }
+}
+
+/// Delimiter spans as the parser sees them: an opening delimiter only counts
+/// if there's no open span of the same kind, a closing one only if there
+/// is, and spans left open extend to the end of the segment.
+fn marks(parsed: &Parsed) -> Vec<Mark> {
+    let mut marks = vec![];
+    let mut open: Vec<(DelimKind, usize, Vec<String>)> = vec![];
+    let mut after_angle = false;
+    for token in &parsed.tokens {
+        let text = &parsed.source[token.start..token.end];
+        let (class, rank) = match token.kind {
+            TokenKind::Open(kind) => {
+                if !open.iter().any(|(k, _, _)| *k == kind) {
+                    open.push((kind, token.start, vec![]));
+                }
+                ("delim", 2)
+            }
+            TokenKind::Close(kind) => {
+                if let Some(i) = open.iter().position(|(k, _, _)| *k == kind) {
+                    let (kind, start, classes) = open.remove(i);
+                    marks.push(span_mark(kind, start, token.end, classes));
+                }
+                ("delim", 2)
+            }
+            TokenKind::NonDelim if after_angle => {
+                // the attribute codes belong to the angle span opened last
+                if let Some((_, _, classes)) = open
+                    .iter_mut()
+                    .rev()
+                    .find(|(k, _, _)| *k == DelimKind::Angle)
+                {
+                    // by key only for key=value attributes
+                    classes.extend(
+                        text.split('_')
+                            .filter_map(|attr| code_class(attr.split('=').next().unwrap_or(attr))),
+                    );
+                }
+                ("attrs", 2)
+            }
+            TokenKind::NonDelim => ("token", 2),
+        };
+        after_angle = token.kind == TokenKind::Open(DelimKind::Angle);
+        marks.push(Mark {
+            start: token.start,
+            end: token.end,
+            class: class.to_owned(),
+            rank,
+        });
+    }
+    for (kind, start, classes) in open {
+        marks.push(span_mark(kind, start, parsed.source.len(), classes));
+    }
+
+    for mistake in &parsed.mistakes {
+        let (_, start, end) = parsed.span(mistake);
+        let mut class = format!("mistake mistake-{}", mistake.kind().replace('_', "-"));
+        if mistake.is_warning() {
+            class.push_str(" warning");
+        }
+        marks.push(Mark {
+            start,
+            end,
+            class,
+            rank: 1,
+        });
+    }
+    marks
+}
+
+fn span_mark(kind: DelimKind, start: usize, end: usize, codes: Vec<String>) -> Mark {
+    let mut class = format!("span span-{}", kind_label(kind));
+    for code in codes {
+        class.push(' ');
+        class.push_str(&code);
+    }
+    Mark {
+        start,
+        end,
+        class,
+        rank: 0,
+    }
+}
+
+fn open_tag(mark: &Mark, html: &mut String) {
+    html.push_str("<span class=\"");
+    escape(&mark.class, html);
+    html.push_str("\">");
+}
+
+pub fn render(parsed: &Parsed) -> String {
+    let marks = marks(parsed);
+    let mut boundaries: Vec<usize> = marks
+        .iter()
+        .flat_map(|m| vec![m.start, m.end])
+        .chain(vec![0, parsed.source.len()])
+        .collect();
+    boundaries.sort_unstable();
+    boundaries.dedup();
+
+    let mut html = String::new();
+    let mut stack: Vec<&Mark> = vec![];
+    for (i, &at) in boundaries.iter().enumerate() {
+        // a piece of source between this boundary and the next one is
+        // covered by the same marks throughout
+        let next = boundaries.get(i + 1).copied();
+        let mut wanted: Vec<&Mark> = match next {
+            Some(next) => marks
+                .iter()
+                .filter(|m| m.start <= at && next <= m.end && m.start < m.end)
+                .collect(),
+            None => vec![],
+        };
+        wanted.sort_by_key(|m| (m.start, Reverse(m.end), m.rank));
+        let common = stack
+            .iter()
+            .zip(&wanted)
+            .take_while(|(a, b)| std::ptr::eq(**a, **b))
+            .count();
+        while stack.len() > common {
+            stack.pop();
+            html.push_str("</span>");
+        }
+        for mark in marks.iter().filter(|m| m.start == at && m.end == at) {
+            open_tag(mark, &mut html);
+            html.push_str("</span>");
+        }
+        if let Some(next) = next {
+            for mark in &wanted[common..] {
+                open_tag(mark, &mut html);
+                stack.push(mark);
+            }
+            escape(&parsed.source[at..next], &mut html);
+        }
+    }
+    html
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{Parser, ParserConfig};
+    use crate::tokenizer;
+
+    fn render_str(segment: &str) -> String {
+        let config = ParserConfig::from_args::<&str, &str, &str, _>(&[], &[], &[], &["SM"]);
+        render(&Parser::parse(&config, tokenizer::tokenize(segment)))
+    }
+
+    #[test]
+    fn test_plain() {
+        assert_eq!(
+            render_str("no tak"),
+            r#"<span class="token">no</span> <span class="token">tak</span>"#
+        );
+    }
+
+    #[test]
+    fn test_spans() {
+        assert_eq!(
+            render_str("<SM tak>"),
+            concat!(
+                r#"<span class="span span-angle code-SM">"#,
+                r#"<span class="delim">&lt;</span>"#,
+                r#"<span class="attrs">SM</span> "#,
+                r#"<span class="token">tak</span>"#,
+                r#"<span class="delim">&gt;</span>"#,
+                "</span>"
+            )
+        );
+    }
+
+    #[test]
+    fn test_overlapping() {
+        assert_eq!(
+            render_str("[a <SM b] c>"),
+            concat!(
+                r#"<span class="span span-square">"#,
+                r#"<span class="delim">[</span><span class="token">a</span> "#,
+                r#"<span class="span span-angle code-SM">"#,
+                r#"<span class="delim">&lt;</span><span class="attrs">SM</span> "#,
+                r#"<span class="token">b</span><span class="delim">]</span>"#,
+                "</span></span>",
+                r#"<span class="span span-angle code-SM">"#,
+                r#" <span class="token">c</span><span class="delim">&gt;</span>"#,
+                "</span>"
+            )
+        );
+    }
+
+    #[test]
+    fn test_mistakes() {
+        assert_eq!(
+            render_str("(tak"),
+            concat!(
+                r#"<span class="span span-round">"#,
+                r#"<span class="mistake mistake-unclosed-delim">"#,
+                r#"<span class="delim">(</span></span>"#,
+                r#"<span class="token">tak</span></span>"#
+            )
+        );
+        assert_eq!(
+            render_str("a)"),
+            concat!(
+                r#"<span class="token">a</span>"#,
+                r#"<span class="mistake mistake-closing-unopened-delim">"#,
+                r#"<span class="delim">)</span></span>"#
+            )
+        );
+        assert_eq!(
+            render_str("<"),
+            concat!(
+                r#"<span class="span span-angle">"#,
+                r#"<span class="mistake mistake-unclosed-delim">"#,
+                r#"<span class="delim">&lt;</span></span></span>"#,
+                r#"<span class="mistake mistake-missing-attrs"></span>"#
+            )
+        );
+    }
+}
@@ -0,0 +1,246 @@
+//! Time intervals of alignable annotations which can't be right: those
+//! overlapping another annotation on the same tier (i.e. the same speaker
+//! talking over themselves) and those of zero length. Annotations ending
+//! before they start are `timeslots::SlotMistake::Reversed`, and aren't
+//! checked here. Like `timeslots`, these are mistakes of the document
+//! rather than of single segments.
+
+use super::annotations::{slot_times, ReadError};
+use super::document::Alignment;
+use super::stream;
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum IntervalMistake {
+    /// An annotation starting before an earlier one on the same tier ends.
+    Overlap {
+        tier: String,
+        annotation: String,
+        previous: String,
+    },
+    /// An annotation starting and ending at the same time, at `slot`.
+    ZeroLength {
+        tier: String,
+        annotation: String,
+        slot: String,
+    },
+}
+
+impl IntervalMistake {
+    /// All values returned by `IntervalMistake::kind`.
+    pub const KINDS: &'static [&'static str] =
+        &["overlapping_annotation", "zero_length_annotation"];
+
+    /// Stable name of the kind of mistake, cf. `parser::Mistake::kind`.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            IntervalMistake::Overlap { .. } => "overlapping_annotation",
+            IntervalMistake::ZeroLength { .. } => "zero_length_annotation",
+        }
+    }
+
+    /// The tier and annotation concerned.
+    pub fn annotation(&self) -> (&str, &str) {
+        match self {
+            IntervalMistake::Overlap {
+                tier, annotation, ..
+            }
+            | IntervalMistake::ZeroLength {
+                tier, annotation, ..
+            } => (tier, annotation),
+        }
+    }
+
+    /// The annotation overlapped, or the time slot of a zero-length one.
+    pub fn related(&self) -> &str {
+        match self {
+            IntervalMistake::Overlap { previous, .. } => previous,
+            IntervalMistake::ZeroLength { slot, .. } => slot,
+        }
+    }
+}
+
+/// Mistakes in the intervals of the document's annotations, tier by tier,
+/// each in order of start time. Times are interpolated where needed, the
+/// way `annotations::read` does it; annotations which can't be placed even
+/// so are left to `timeslots::check`. Annotations merely touching (one
+/// ending where the next starts) don't overlap.
+pub fn check(xml: &str) -> Result<Vec<IntervalMistake>, ReadError> {
+    let eaf = stream::read(xml.as_bytes())?;
+    let times = slot_times(&eaf);
+
+    let mut mistakes = vec![];
+    for tier in &eaf.tiers {
+        let mut intervals = vec![];
+        for annotation in &tier.annotations {
+            let (start_slot, end_slot) = match &annotation.alignment {
+                Alignment::Alignable {
+                    start_slot,
+                    end_slot,
+                } => (start_slot.as_str(), end_slot.as_str()),
+                Alignment::Ref { .. } => continue,
+            };
+            match (times.get(start_slot), times.get(end_slot)) {
+                (Some(&(start, _)), Some(&(end, _))) if start == end => {
+                    mistakes.push(IntervalMistake::ZeroLength {
+                        tier: tier.id.clone(),
+                        annotation: annotation.id.clone(),
+                        slot: start_slot.to_owned(),
+                    })
+                }
+                (Some(&(start, _)), Some(&(end, _))) if start < end => {
+                    intervals.push((start, end, annotation.id.as_str()))
+                }
+                _ => {}
+            }
+        }
+        // stable, so that annotations starting at the same time stay in
+        // document order
+        intervals.sort_by_key(|&(start, _, _)| start);
+        let mut latest: Option<(u32, &str)> = None;
+        for (start, end, id) in intervals {
+            match latest {
+                Some((latest_end, previous)) if start < latest_end => {
+                    mistakes.push(IntervalMistake::Overlap {
+                        tier: tier.id.clone(),
+                        annotation: id.to_owned(),
+                        previous: previous.to_owned(),
+                    });
+                    if end > latest_end {
+                        latest = Some((end, id));
+                    }
+                }
+                _ => latest = Some((end, id)),
+            }
+        }
+    }
+    Ok(mistakes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eaf(slots: &[(&str, Option<u32>)], annotations: &[(&str, &str, &str)]) -> String {
+        let slots: Vec<_> = slots
+            .iter()
+            .map(|&(id, value)| match value {
+                Some(value) => format!(
+                    r#"<TIME_SLOT TIME_SLOT_ID="{}" TIME_VALUE="{}"/>"#,
+                    id, value
+                ),
+                None => format!(r#"<TIME_SLOT TIME_SLOT_ID="{}"/>"#, id),
+            })
+            .collect();
+        let annotations: Vec<_> = annotations
+            .iter()
+            .map(|(id, start, end)| {
+                format!(
+                    r#"<ANNOTATION><ALIGNABLE_ANNOTATION ANNOTATION_ID="{}" TIME_SLOT_REF1="{}" TIME_SLOT_REF2="{}"><ANNOTATION_VALUE>jo</ANNOTATION_VALUE></ALIGNABLE_ANNOTATION></ANNOTATION>"#,
+                    id, start, end
+                )
+            })
+            .collect();
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<ANNOTATION_DOCUMENT AUTHOR="" DATE="2020-01-01T00:00:00+01:00" FORMAT="3.0" VERSION="3.0">
+    <HEADER MEDIA_FILE="" TIME_UNITS="milliseconds"/>
+    <TIME_ORDER>{}</TIME_ORDER>
+    <TIER LINGUISTIC_TYPE_REF="ort" TIER_ID="ort@Jana">{}</TIER>
+    <TIER LINGUISTIC_TYPE_REF="ort" TIER_ID="ort@P"><ANNOTATION><ALIGNABLE_ANNOTATION ANNOTATION_ID="p1" TIME_SLOT_REF1="ts1" TIME_SLOT_REF2="ts3"><ANNOTATION_VALUE>no</ANNOTATION_VALUE></ALIGNABLE_ANNOTATION></ANNOTATION></TIER>
+</ANNOTATION_DOCUMENT>"#,
+            slots.concat(),
+            annotations.concat()
+        )
+    }
+
+    fn overlap(annotation: &str, previous: &str) -> IntervalMistake {
+        IntervalMistake::Overlap {
+            tier: "ort@Jana".to_owned(),
+            annotation: annotation.to_owned(),
+            previous: previous.to_owned(),
+        }
+    }
+
+    #[test]
+    fn test_fine() {
+        let slots = [
+            ("ts1", Some(0)),
+            ("ts2", Some(1_000)),
+            ("ts3", Some(2_000)),
+            ("ts4", None),
+            ("ts5", Some(3_000)),
+        ];
+        // touching, interpolated, and overlapping only another speaker
+        let annotations = [
+            ("a1", "ts1", "ts2"),
+            ("a2", "ts2", "ts4"),
+            ("a3", "ts4", "ts5"),
+        ];
+        assert_eq!(check(&eaf(&slots, &annotations)).unwrap(), []);
+    }
+
+    #[test]
+    fn test_overlaps() {
+        let slots = [
+            ("ts1", Some(0)),
+            ("ts2", Some(1_000)),
+            ("ts3", Some(2_000)),
+            ("ts4", Some(3_000)),
+            ("ts5", Some(4_000)),
+        ];
+        // out of document order, one spanning several others
+        let annotations = [
+            ("a1", "ts2", "ts5"),
+            ("a2", "ts1", "ts3"),
+            ("a3", "ts3", "ts4"),
+            ("a4", "ts4", "ts5"),
+        ];
+        let mistakes = check(&eaf(&slots, &annotations)).unwrap();
+        assert_eq!(
+            mistakes,
+            [
+                overlap("a1", "a2"),
+                overlap("a3", "a1"),
+                overlap("a4", "a1")
+            ]
+        );
+        assert_eq!(mistakes[0].kind(), "overlapping_annotation");
+        assert_eq!(mistakes[0].annotation(), ("ort@Jana", "a1"));
+        assert_eq!(mistakes[0].related(), "a2");
+    }
+
+    #[test]
+    fn test_zero_length_and_reversed() {
+        let slots = [("ts1", Some(0)), ("ts2", Some(1_000)), ("ts3", Some(1_000))];
+        let annotations = [
+            ("a1", "ts2", "ts3"),
+            ("a2", "ts1", "ts1"),
+            ("a3", "ts2", "ts1"),
+            ("a4", "ts1", "ts6"),
+        ];
+        let mistakes = check(&eaf(&slots, &annotations)).unwrap();
+        assert_eq!(
+            mistakes,
+            [
+                IntervalMistake::ZeroLength {
+                    tier: "ort@Jana".to_owned(),
+                    annotation: "a1".to_owned(),
+                    slot: "ts2".to_owned(),
+                },
+                IntervalMistake::ZeroLength {
+                    tier: "ort@Jana".to_owned(),
+                    annotation: "a2".to_owned(),
+                    slot: "ts1".to_owned(),
+                },
+            ]
+        );
+        for mistake in &mistakes {
+            assert!(IntervalMistake::KINDS.contains(&mistake.kind()));
+        }
+    }
+
+    #[test]
+    fn test_not_eaf() {
+        assert!(check("<html/>").is_err());
+    }
+}
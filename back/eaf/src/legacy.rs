@@ -0,0 +1,207 @@
+//! Read our old plain-text transcripts, so that they can be converted to
+//! EAF.
+//!
+//! Each turn starts with the speaker's nickname and a colon, lines without
+//! one continue the previous turn. Timestamps are given as `[mm:ss]` (or
+//! `[h:mm:ss]`) markers, either on their own line or inline:
+//!
+//! ```text
+//! [00:00]
+//! Jana: dobrý den
+//! Petr: no dobrý [00:04] tak co
+//!   máte nového
+//! ```
+//!
+//! Only the markers are exact, segments between them are aligned
+//! approximately, proportionally to their length in words.
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use super::asr::{Milliseconds, ReadError, Segment};
+
+/// Assumed duration of a word when there's no marker to go by, i.e. after
+/// the last one.
+const WORD_MS: Milliseconds = 350;
+
+/// Longest prefix before a colon which is still considered a nickname.
+const MAX_NICKNAME: usize = 30;
+
+enum Item {
+    Marker(Milliseconds, usize),
+    Text { speaker: String, text: String },
+}
+
+fn parse_marker(caps: &regex::Captures) -> Option<Milliseconds> {
+    let field = |i| {
+        caps.get(i)
+            .map_or(Some(0), |m| m.as_str().parse::<u32>().ok())
+    };
+    let (h, m, s) = (field(1)?, field(2)?, field(3)?);
+    if s >= 60 || (caps.get(1).is_some() && m >= 60) {
+        return None;
+    }
+    Some(((h * 60 + m) * 60 + s) * 1000)
+}
+
+fn split_speaker(line: &str) -> Option<(&str, &str)> {
+    let colon = line.find(':')?;
+    let nickname = line[..colon].trim();
+    if nickname.is_empty()
+        || nickname.chars().count() > MAX_NICKNAME
+        || nickname.contains(|c: char| c == '[' || c == ']')
+    {
+        return None;
+    }
+    Some((nickname, &line[colon + 1..]))
+}
+
+fn items(text: &str) -> Result<Vec<Item>, ReadError> {
+    lazy_static! {
+        static ref MARKER_RE: Regex = Regex::new(r"\[(?:(\d+):)?(\d+):(\d+)\]").unwrap();
+    }
+    let mut items = vec![];
+    let mut speaker: Option<String> = None;
+    for (i, line) in text.lines().enumerate() {
+        let line_no = i + 1;
+        // a marker may precede the speaker, e.g. "[01:02] Jana: ..."
+        let leading = MARKER_RE
+            .find(line.trim_start())
+            .filter(|m| m.start() == 0)
+            .map_or(0, |m| line.len() - line.trim_start().len() + m.end());
+        let (before, rest) = line.split_at(leading);
+        let rest = match split_speaker(rest) {
+            Some((nickname, rest)) => {
+                speaker = Some(nickname.to_owned());
+                rest
+            }
+            None => rest,
+        };
+
+        let push_text = |items: &mut Vec<Item>, text: &str| -> Result<(), ReadError> {
+            let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+            if text.is_empty() {
+                return Ok(());
+            }
+            let speaker = speaker.clone().ok_or_else(|| ReadError {
+                line: Some(line_no),
+                message: "text before the first speaker".to_owned(),
+            })?;
+            match items.last_mut() {
+                // continuation line
+                Some(Item::Text {
+                    speaker: s,
+                    text: t,
+                }) if *s == speaker => {
+                    t.push(' ');
+                    t.push_str(&text);
+                }
+                _ => items.push(Item::Text { speaker, text }),
+            }
+            Ok(())
+        };
+
+        let mut last = 0;
+        for source in &[before, rest] {
+            for caps in MARKER_RE.captures_iter(source) {
+                let m = caps.get(0).unwrap();
+                push_text(&mut items, &source[last..m.start()])?;
+                let ms = parse_marker(&caps).ok_or_else(|| ReadError {
+                    line: Some(line_no),
+                    message: format!("bad timestamp {}", m.as_str()),
+                })?;
+                items.push(Item::Marker(ms, line_no));
+                last = m.end();
+            }
+            push_text(&mut items, &source[last..])?;
+            last = 0;
+        }
+    }
+    Ok(items)
+}
+
+fn words(text: &str) -> u32 {
+    text.split_whitespace().count().max(1) as u32
+}
+
+pub fn read(text: &str) -> Result<Vec<Segment>, ReadError> {
+    let items = items(text)?;
+    let mut segments = vec![];
+    let mut start = 0;
+    let mut pending: Vec<(String, String)> = vec![];
+
+    // spread the pending chunks of text evenly between start and end
+    let mut flush =
+        |pending: &mut Vec<(String, String)>, start: Milliseconds, end: Option<Milliseconds>| {
+            let total: u32 = pending.iter().map(|(_, t)| words(t)).sum();
+            let end = end.unwrap_or(start + total * WORD_MS);
+            let mut elapsed = 0;
+            for (speaker, text) in pending.drain(..) {
+                let seg_start = start + (end - start) * elapsed / total.max(1);
+                elapsed += words(&text);
+                let seg_end = start + (end - start) * elapsed / total.max(1);
+                segments.push(Segment {
+                    start: seg_start,
+                    end: seg_end,
+                    text,
+                    speaker: Some(speaker),
+                });
+            }
+        };
+
+    for item in items {
+        match item {
+            Item::Marker(ms, line) => {
+                if ms < start {
+                    return Err(ReadError {
+                        line: Some(line),
+                        message: "timestamp goes back in time".to_owned(),
+                    });
+                }
+                flush(&mut pending, start, Some(ms));
+                start = ms;
+            }
+            Item::Text { speaker, text } => pending.push((speaker, text)),
+        }
+    }
+    flush(&mut pending, start, None);
+    Ok(segments)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn segment(start: Milliseconds, end: Milliseconds, speaker: &str, text: &str) -> Segment {
+        Segment {
+            start,
+            end,
+            text: text.to_owned(),
+            speaker: Some(speaker.to_owned()),
+        }
+    }
+
+    #[test]
+    fn test_read() {
+        let text = "[00:00]\nJana: dobrý den\nPetr: no dobrý [00:04] tak co\n  máte nového\n[1:00:00] Jana: nic\n";
+        assert_eq!(
+            read(text).unwrap(),
+            vec![
+                segment(0, 2000, "Jana", "dobrý den"),
+                segment(2000, 4000, "Petr", "no dobrý"),
+                segment(4000, 3_600_000, "Petr", "tak co máte nového"),
+                segment(3_600_000, 3_600_000 + WORD_MS, "Jana", "nic"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(read("bez mluvčího").unwrap_err().line, Some(1));
+        assert_eq!(
+            read("Jana: [00:10] a\n[00:05] b").unwrap_err().line,
+            Some(2)
+        );
+        assert_eq!(read("Jana: [00:61] a").unwrap_err().line, Some(1));
+    }
+}
@@ -1,3 +1,45 @@
+//! Reading, validating and rewriting ELAN transcripts (EAF files) following
+//! the project's transcription conventions.
+//!
+//! The modules below are the crate's public API; anything they don't export
+//! is an implementation detail which may change without notice. The most
+//! commonly needed types are re-exported in `prelude`.
+
+pub mod annotations;
+pub mod anonymize;
+pub mod asr;
+pub mod candidates;
+pub mod canonical;
+pub mod config;
+pub mod conllu;
+pub mod diff;
 pub mod document;
+pub mod draft;
+pub mod editor;
+pub mod fixes;
+pub mod frequencies;
+pub mod header;
+pub mod html;
+pub mod intervals;
+pub mod legacy;
+pub mod media;
+pub mod messages;
+pub mod metadata;
+pub mod offsets;
 pub mod parser;
+pub mod policies;
+pub mod prelude;
+pub mod query;
+#[cfg(feature = "spellcheck")]
+pub mod spelling;
+pub mod stats;
+mod stream;
+pub mod template;
+pub mod tiers;
+pub mod timeslots;
 pub mod tokenizer;
+pub mod turns;
+pub mod vertical;
+pub mod vocabularies;
+#[cfg(feature = "wasm")]
+pub mod wasm;
@@ -0,0 +1,199 @@
+//! Consistency of the document's time alignment with its recording: no
+//! annotation should end after the recording does. The recording's duration
+//! is given, or with the `probe` feature read off the WAV or FLAC file
+//! itself. Annotations referring to time slots which don't exist are
+//! `timeslots::SlotMistake::MissingSlot`. Like `timeslots`, these are
+//! mistakes of the document rather than of single segments.
+
+use std::fmt;
+#[cfg(feature = "probe")]
+use std::fs::File;
+#[cfg(feature = "probe")]
+use std::io::Read;
+#[cfg(feature = "probe")]
+use std::path::Path;
+
+use super::annotations::{slot_times, ReadError};
+use super::document::Alignment;
+use super::stream;
+
+/// An annotation which ends (or starts, if it's reversed) after the end of
+/// the recording.
+#[derive(Debug, PartialEq, Clone)]
+pub struct BeyondMedia {
+    pub tier: String,
+    pub annotation: String,
+    /// The annotation's later time slot.
+    pub slot: String,
+    /// Time of the slot in the recording, in milliseconds, i.e. including
+    /// the primary media's TIME_ORIGIN.
+    pub time: u32,
+    /// Of the recording, in milliseconds.
+    pub duration: u32,
+}
+
+impl BeyondMedia {
+    /// Its kind, cf. `parser::Mistake::kind`.
+    pub const KIND: &'static str = "beyond_media";
+}
+
+/// Annotations beyond the end of a recording `duration` milliseconds long,
+/// tier by tier. Times are interpolated where needed, the way
+/// `annotations::read` does it; annotations which can't be placed even so
+/// are left to `timeslots::check`.
+pub fn check(xml: &str, duration: u32) -> Result<Vec<BeyondMedia>, ReadError> {
+    let eaf = stream::read(xml.as_bytes())?;
+    let times = slot_times(&eaf);
+    let origin = eaf.media.first().and_then(|m| m.time_origin).unwrap_or(0);
+
+    let mut mistakes = vec![];
+    for tier in &eaf.tiers {
+        for annotation in &tier.annotations {
+            let slots = match &annotation.alignment {
+                Alignment::Alignable {
+                    start_slot,
+                    end_slot,
+                } => [start_slot.as_str(), end_slot.as_str()],
+                Alignment::Ref { .. } => continue,
+            };
+            let latest = slots
+                .iter()
+                .filter_map(|&slot| Some((slot, times.get(slot)?.0.saturating_add(origin))))
+                .max_by_key(|&(_, time)| time);
+            if let Some((slot, time)) = latest.filter(|&(_, time)| time > duration) {
+                mistakes.push(BeyondMedia {
+                    tier: tier.id.clone(),
+                    annotation: annotation.id.clone(),
+                    slot: slot.to_owned(),
+                    time,
+                    duration,
+                });
+            }
+        }
+    }
+    Ok(mistakes)
+}
+
+#[derive(Debug)]
+pub struct ProbeError(String);
+
+impl fmt::Display for ProbeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "can't probe recording: {}", self.0)
+    }
+}
+
+impl std::error::Error for ProbeError {}
+
+/// Duration of the WAV or FLAC recording at `path`, in milliseconds, going
+/// by its header.
+#[cfg(feature = "probe")]
+pub fn probe(path: &Path) -> Result<u32, ProbeError> {
+    let error = |e: &dyn fmt::Display| ProbeError(format!("{}: {}", path.display(), e));
+    let mut magic = [0; 4];
+    File::open(path)
+        .and_then(|mut file| file.read_exact(&mut magic))
+        .map_err(|e| error(&e))?;
+    let (samples, sample_rate) = if &magic == b"fLaC" {
+        let info = claxon::FlacReader::open(path)
+            .map_err(|e| error(&e))?
+            .streaminfo();
+        let samples = info
+            .samples
+            .ok_or_else(|| error(&"number of samples unknown"))?;
+        (samples, info.sample_rate)
+    } else {
+        let reader = hound::WavReader::open(path).map_err(|e| error(&e))?;
+        (u64::from(reader.duration()), reader.spec().sample_rate)
+    };
+    if sample_rate == 0 {
+        return Err(error(&"sample rate is 0"));
+    }
+    Ok((samples * 1000 / u64::from(sample_rate)).min(u64::from(u32::MAX)) as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eaf(media: &str) -> String {
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<ANNOTATION_DOCUMENT AUTHOR="" DATE="2020-01-01T00:00:00+01:00" FORMAT="3.0" VERSION="3.0">
+    <HEADER MEDIA_FILE="" TIME_UNITS="milliseconds">{}</HEADER>
+    <TIME_ORDER>
+        <TIME_SLOT TIME_SLOT_ID="ts1" TIME_VALUE="0"/>
+        <TIME_SLOT TIME_SLOT_ID="ts2" TIME_VALUE="1000"/>
+        <TIME_SLOT TIME_SLOT_ID="ts3"/>
+        <TIME_SLOT TIME_SLOT_ID="ts4" TIME_VALUE="3000"/>
+    </TIME_ORDER>
+    <TIER LINGUISTIC_TYPE_REF="ort" TIER_ID="ort@Jana">
+        <ANNOTATION><ALIGNABLE_ANNOTATION ANNOTATION_ID="a1" TIME_SLOT_REF1="ts1" TIME_SLOT_REF2="ts2"><ANNOTATION_VALUE>no</ANNOTATION_VALUE></ALIGNABLE_ANNOTATION></ANNOTATION>
+        <ANNOTATION><ALIGNABLE_ANNOTATION ANNOTATION_ID="a2" TIME_SLOT_REF1="ts2" TIME_SLOT_REF2="ts3"><ANNOTATION_VALUE>jo</ANNOTATION_VALUE></ALIGNABLE_ANNOTATION></ANNOTATION>
+        <ANNOTATION><ALIGNABLE_ANNOTATION ANNOTATION_ID="a3" TIME_SLOT_REF1="ts4" TIME_SLOT_REF2="ts3"><ANNOTATION_VALUE>tak</ANNOTATION_VALUE></ALIGNABLE_ANNOTATION></ANNOTATION>
+    </TIER>
+    <TIER LINGUISTIC_TYPE_REF="ort" TIER_ID="ort@P">
+        <ANNOTATION><ALIGNABLE_ANNOTATION ANNOTATION_ID="p1" TIME_SLOT_REF1="ts1" TIME_SLOT_REF2="ts5"><ANNOTATION_VALUE>hm</ANNOTATION_VALUE></ALIGNABLE_ANNOTATION></ANNOTATION>
+    </TIER>
+</ANNOTATION_DOCUMENT>"#,
+            media
+        )
+    }
+
+    fn beyond(annotation: &str, slot: &str, time: u32, duration: u32) -> BeyondMedia {
+        BeyondMedia {
+            tier: "ort@Jana".to_owned(),
+            annotation: annotation.to_owned(),
+            slot: slot.to_owned(),
+            time,
+            duration,
+        }
+    }
+
+    #[test]
+    fn test_check() {
+        let xml = eaf("");
+        assert_eq!(check(&xml, 3_000).unwrap(), []);
+        // ts3 is interpolated, a3 is reversed, the missing ts5 is ignored
+        assert_eq!(
+            check(&xml, 1_500).unwrap(),
+            [
+                beyond("a2", "ts3", 2_000, 1_500),
+                beyond("a3", "ts4", 3_000, 1_500)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_time_origin() {
+        let xml = eaf(
+            r#"<MEDIA_DESCRIPTOR MEDIA_URL="file:///rec.wav" TIME_ORIGIN="500"/>
+            <MEDIA_DESCRIPTOR MEDIA_URL="file:///rec.mp4" TIME_ORIGIN="5000"/>"#,
+        );
+        assert_eq!(
+            check(&xml, 3_000).unwrap(),
+            [beyond("a3", "ts4", 3_500, 3_000)]
+        );
+    }
+
+    #[cfg(feature = "probe")]
+    #[test]
+    fn test_probe() {
+        let path = std::env::temp_dir().join(format!("eaf-probe-{}.wav", std::process::id()));
+        let spec = hound::WavSpec {
+            channels: 2,
+            sample_rate: 8_000,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+        for _ in 0..2 * 12_000 {
+            writer.write_sample(0i16).unwrap();
+        }
+        writer.finalize().unwrap();
+        let duration = probe(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(duration.unwrap(), 1_500);
+        assert!(probe(Path::new("/nonexistent.wav")).is_err());
+    }
+}
@@ -0,0 +1,314 @@
+//! Human-readable explanations of mistakes, for transcribers rather than
+//! programs. Messages come from a catalog per language, so that e.g. Czech
+//! transcribers can read them in Czech:
+//!
+//! ```
+//! use eaf::messages::{self, Lang};
+//! use eaf::prelude::*;
+//!
+//! let config = ParserConfig::from_args::<&str, &str, &str, &str>(&[], &[], &[], &[]);
+//! let parsed = Parser::parse(&config, tokenize("no (tak"));
+//! assert_eq!(
+//!     messages::render(&parsed, &parsed.mistakes[0], Lang::Cs),
+//!     "Neuzavřená kulatá závorka.\nno (tak\n   ^"
+//! );
+//! ```
+
+use std::{fmt, str::FromStr};
+
+use super::offsets::Unit;
+use super::parser::{Mistake, Parsed, WhitespaceKind};
+use super::tokenizer::DelimKind;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum Lang {
+    #[default]
+    En,
+    Cs,
+}
+
+impl Lang {
+    pub const ALL: [Lang; 2] = [Lang::En, Lang::Cs];
+
+    /// ISO 639-1 code.
+    pub fn code(self) -> &'static str {
+        match self {
+            Lang::En => "en",
+            Lang::Cs => "cs",
+        }
+    }
+}
+
+impl fmt::Display for Lang {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.code())
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct UnknownLang(pub String);
+
+impl fmt::Display for UnknownLang {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown language {:?}, expected one of en, cs", self.0)
+    }
+}
+
+impl std::error::Error for UnknownLang {}
+
+impl FromStr for Lang {
+    type Err = UnknownLang;
+
+    /// Accepts regional variants too, e.g. `cs-CZ`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let primary = s.split(|c| c == '-' || c == '_').next().unwrap_or("");
+        Lang::ALL
+            .iter()
+            .copied()
+            .find(|lang| lang.code().eq_ignore_ascii_case(primary))
+            .ok_or_else(|| UnknownLang(s.to_owned()))
+    }
+}
+
+// Templates are keyed by `Mistake::kind`, whitespace mistakes additionally
+// by their `WhitespaceKind`. Placeholders: {text} is the offending part of
+// the segment, {attr} the attribute code, {delim} the name of the
+// delimiter.
+
+const EN: &[(&str, &str)] = &[
+    ("bad_token", "“{text}” is not allowed here."),
+    ("bad_substr", "“{text}” is not allowed in a word."),
+    ("bad_attr", "Unknown attribute code “{attr}”."),
+    (
+        "bad_attr_value",
+        "The value of the attribute “{attr}” in “{text}” is not allowed.",
+    ),
+    (
+        "deprecated_attr",
+        "The attribute code “{attr}” is deprecated.",
+    ),
+    (
+        "duplicate_attr",
+        "The attribute code “{attr}” is given more than once.",
+    ),
+    (
+        "nested_delim",
+        "A {delim} can't be nested inside another one.",
+    ),
+    (
+        "closing_unopened_delim",
+        "Closing {delim} without an opening one.",
+    ),
+    ("unclosed_delim", "Unclosed {delim}."),
+    (
+        "missing_attrs",
+        "An opening angle bracket must be followed by attribute codes.",
+    ),
+    (
+        "mismatched_span_label",
+        "The span should be closed with its attributes, i.e. “{attr}>”.",
+    ),
+    (
+        "whitespace_leading",
+        "Whitespace at the start of the segment.",
+    ),
+    (
+        "whitespace_trailing",
+        "Whitespace at the end of the segment.",
+    ),
+    ("whitespace_double", "More than one space in a row."),
+    (
+        "whitespace_non_space",
+        "Whitespace other than a plain space.",
+    ),
+    ("round", "round bracket"),
+    ("square", "square bracket"),
+    ("angle", "angle bracket"),
+];
+
+const CS: &[(&str, &str)] = &[
+    ("bad_token", "„{text}“ sem nepatří."),
+    ("bad_substr", "„{text}“ nemůže být součástí slova."),
+    ("bad_attr", "Neznámý atribut „{attr}“."),
+    (
+        "bad_attr_value",
+        "Nepovolená hodnota atributu „{attr}“ v „{text}“.",
+    ),
+    ("deprecated_attr", "Atribut „{attr}“ se už nepoužívá."),
+    ("duplicate_attr", "Atribut „{attr}“ je uveden víckrát."),
+    ("nested_delim", "Tato {delim} nemůže být uvnitř jiné."),
+    ("closing_unopened_delim", "Uzavírací {delim} bez otevírací."),
+    ("unclosed_delim", "Neuzavřená {delim}."),
+    (
+        "missing_attrs",
+        "Za otevírací lomenou závorkou musí následovat atributy.",
+    ),
+    (
+        "mismatched_span_label",
+        "Úsek by měl končit svými atributy, tj. „{attr}>“.",
+    ),
+    ("whitespace_leading", "Mezera na začátku segmentu."),
+    ("whitespace_trailing", "Mezera na konci segmentu."),
+    ("whitespace_double", "Víc mezer za sebou."),
+    (
+        "whitespace_non_space",
+        "Jiný bílý znak než obyčejná mezera.",
+    ),
+    ("round", "kulatá závorka"),
+    ("square", "hranatá závorka"),
+    ("angle", "lomená závorka"),
+];
+
+fn catalog(lang: Lang) -> &'static [(&'static str, &'static str)] {
+    match lang {
+        Lang::En => EN,
+        Lang::Cs => CS,
+    }
+}
+
+/// Falls back to English, then to the key itself, so that a missing
+/// translation never hides a mistake.
+fn lookup(lang: Lang, key: &str) -> &'static str {
+    [catalog(lang), EN]
+        .iter()
+        .flat_map(|c| c.iter())
+        .find(|(k, _)| *k == key)
+        .map(|(_, v)| *v)
+        .unwrap_or("{text}")
+}
+
+fn delim_key(kind: DelimKind) -> &'static str {
+    match kind {
+        DelimKind::Round => "round",
+        DelimKind::Square => "square",
+        DelimKind::Angle => "angle",
+    }
+}
+
+fn whitespace_key(kind: WhitespaceKind) -> &'static str {
+    match kind {
+        WhitespaceKind::Leading => "whitespace_leading",
+        WhitespaceKind::Trailing => "whitespace_trailing",
+        WhitespaceKind::Double => "whitespace_double",
+        WhitespaceKind::NonSpace => "whitespace_non_space",
+    }
+}
+
+/// One-line explanation of the mistake.
+pub fn message(parsed: &Parsed, mistake: &Mistake, lang: Lang) -> String {
+    let (_, start, end) = parsed.span(mistake);
+    let (key, attr, delim) = match mistake {
+        Mistake::BadAttr { attr, .. }
+        | Mistake::DeprecatedAttr { attr, .. }
+        | Mistake::DuplicateAttr { attr, .. }
+        | Mistake::BadAttrValue { key: attr, .. }
+        | Mistake::MismatchedSpanLabel { expected: attr, .. } => {
+            (mistake.kind(), attr.as_str(), "")
+        }
+        Mistake::NestedDelim { kind, .. }
+        | Mistake::ClosingUnopenedDelim { kind, .. }
+        | Mistake::UnclosedDelim { kind, .. } => {
+            (mistake.kind(), "", lookup(lang, delim_key(*kind)))
+        }
+        Mistake::Whitespace { kind, .. } => (whitespace_key(*kind), "", ""),
+        _ => (mistake.kind(), "", ""),
+    };
+    lookup(lang, key)
+        .replace("{text}", &parsed.source[start..end])
+        .replace("{attr}", attr)
+        .replace("{delim}", delim)
+}
+
+impl Parsed {
+    /// The segment with the mistake's span underlined by carets on the
+    /// line below, counted in graphemes so that they line up in a terminal.
+    /// Empty spans, e.g. where a delimiter should be closed, get a single
+    /// caret.
+    pub fn highlight(&self, mistake: &Mistake) -> String {
+        let (_, start, end) = self.span_in(mistake, Unit::Grapheme);
+        let width = (end - start).max(1);
+        format!(
+            "{}\n{}{}",
+            self.source,
+            " ".repeat(start),
+            "^".repeat(width)
+        )
+    }
+}
+
+/// The message followed by the highlighted segment.
+pub fn render(parsed: &Parsed, mistake: &Mistake, lang: Lang) -> String {
+    format!(
+        "{}\n{}",
+        message(parsed, mistake, lang),
+        parsed.highlight(mistake)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{Parser, ParserConfig};
+    use crate::tokenizer::{self, WhitespacePolicy};
+
+    fn parse(segment: &str) -> Parsed {
+        let config = ParserConfig::from_args(&[] as &[&str], &[] as &[&str], &["a", "ř"], &["SM"]);
+        Parser::parse(
+            &config,
+            tokenizer::tokenize_with(segment, WhitespacePolicy::Report),
+        )
+    }
+
+    #[test]
+    fn test_lang() {
+        assert_eq!("cs".parse(), Ok(Lang::Cs));
+        assert_eq!("cs-CZ".parse(), Ok(Lang::Cs));
+        assert_eq!("EN".parse(), Ok(Lang::En));
+        assert!("de".parse::<Lang>().is_err());
+    }
+
+    #[test]
+    fn test_catalogs_complete() {
+        for lang in Lang::ALL.iter() {
+            for (key, _) in EN {
+                assert!(
+                    catalog(*lang).iter().any(|(k, _)| k == key),
+                    "{} missing from {}",
+                    key,
+                    lang
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_message() {
+        let parsed = parse("<XY x a)");
+        let messages: Vec<_> = parsed
+            .mistakes
+            .iter()
+            .map(|m| message(&parsed, m, Lang::En))
+            .collect();
+        assert!(messages.contains(&"Unknown attribute code “XY”.".to_owned()));
+        assert!(messages.contains(&"“x” is not allowed in a word.".to_owned()));
+        assert!(messages.contains(&"Closing round bracket without an opening one.".to_owned()));
+        assert!(messages.contains(&"Unclosed angle bracket.".to_owned()));
+
+        let parsed = parse("a  a");
+        assert_eq!(
+            message(&parsed, &parsed.mistakes[0], Lang::Cs),
+            "Víc mezer za sebou."
+        );
+    }
+
+    #[test]
+    fn test_highlight() {
+        let parsed = parse("ř x");
+        assert_eq!(parsed.highlight(&parsed.mistakes[0]), "ř x\n  ^");
+        let parsed = parse("[ř");
+        assert_eq!(
+            render(&parsed, &parsed.mistakes[0], Lang::En),
+            "Unclosed square bracket.\n[ř\n^"
+        );
+    }
+}
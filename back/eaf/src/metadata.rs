@@ -0,0 +1,277 @@
+//! Session metadata from CMDI or IMDI files, which archives and ELAN users
+//! keep next to EAFs (e.g. `rec01.eaf` and `rec01.cmdi`): when and where
+//! the recording was made and who speaks in it.
+//!
+//! Only what's needed to cross-check documents is read. IMDI sessions and
+//! the CMDI profiles derived from them (e.g. `lat-session`) name these the
+//! same, so elements are looked up by local name, regardless of namespace
+//! and of how deep the CMDI profile nests them.
+
+use std::fmt;
+use std::path::Path;
+
+use sxd_document::dom::Element;
+use sxd_document::parser;
+
+use super::annotations::{children, named};
+
+/// Extensions of metadata files accompanying EAFs.
+pub const EXTENSIONS: [&str; 2] = ["cmdi", "imdi"];
+
+#[derive(Debug)]
+pub struct MetadataError(String);
+
+impl fmt::Display for MetadataError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "can't read metadata: {}", self.0)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Cmdi,
+    Imdi,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Sex {
+    Male,
+    Female,
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Actor {
+    /// Usually what the speaker goes by in the transcript.
+    pub name: Option<String>,
+    pub full_name: Option<String>,
+    pub code: Option<String>,
+    /// E.g. speaker, interviewer.
+    pub role: Option<String>,
+    pub sex: Option<Sex>,
+    pub birth_year: Option<i32>,
+}
+
+impl Actor {
+    /// Names the actor may be identified by, most specific first.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        vec![&self.name, &self.code, &self.full_name]
+            .into_iter()
+            .filter_map(|n| n.as_deref())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Metadata {
+    pub format: Format,
+    /// As `YYYY-MM-DD`, possibly with the day or month missing.
+    pub date: Option<String>,
+    /// The town or village, which IMDI calls the address.
+    pub place: Option<String>,
+    pub region: Option<String>,
+    pub actors: Vec<Actor>,
+}
+
+/// Whether `name` is a metadata file accompanying the EAF `eaf_name`, i.e.
+/// has the same stem and one of `EXTENSIONS`.
+pub fn is_sidecar_of(eaf_name: &str, name: &str) -> bool {
+    let (eaf, candidate) = (Path::new(eaf_name), Path::new(name));
+    let extension = candidate
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(str::to_lowercase);
+    match extension {
+        Some(extension) if EXTENSIONS.contains(&extension.as_str()) => {
+            eaf.file_stem() == candidate.file_stem()
+        }
+        _ => false,
+    }
+}
+
+fn descendants<'d>(element: Element<'d>, name: &'static str, out: &mut Vec<Element<'d>>) {
+    for child in children(element) {
+        if child.name().local_part() == name {
+            out.push(child);
+        } else {
+            descendants(child, name, out);
+        }
+    }
+}
+
+/// The first element with the given name in document order.
+fn find<'d>(element: Element<'d>, name: &'static str) -> Option<Element<'d>> {
+    let mut found = vec![];
+    descendants(element, name, &mut found);
+    found.into_iter().next()
+}
+
+/// Text of the named child. Archives fill in placeholders rather than
+/// leaving fields out, these count as missing.
+fn text(element: Element, name: &'static str) -> Option<String> {
+    let value: String = named(element, name)
+        .next()?
+        .children()
+        .into_iter()
+        .filter_map(|c| c.text().map(|t| t.text()))
+        .collect();
+    let value = value.trim();
+    match value.to_lowercase().as_str() {
+        "" | "unknown" | "unspecified" | "not applicable" => None,
+        _ => Some(value.to_owned()),
+    }
+}
+
+fn sex(value: &str) -> Option<Sex> {
+    match value.to_lowercase().as_str() {
+        "male" | "m" => Some(Sex::Male),
+        "female" | "f" => Some(Sex::Female),
+        _ => None,
+    }
+}
+
+/// The year of an ISO date, or of a bare year.
+fn year(date: &str) -> Option<i32> {
+    let year = date.split('-').next()?;
+    if year.len() == 4 {
+        year.parse().ok()
+    } else {
+        None
+    }
+}
+
+fn actor(element: Element) -> Actor {
+    Actor {
+        name: text(element, "Name"),
+        full_name: text(element, "FullName"),
+        code: text(element, "Code"),
+        role: text(element, "Role"),
+        sex: text(element, "Sex").as_deref().and_then(sex),
+        birth_year: text(element, "BirthDate").as_deref().and_then(year),
+    }
+}
+
+pub fn read(xml: &str) -> Result<Metadata, MetadataError> {
+    let package = parser::parse(xml).map_err(|e| MetadataError(format!("{:?}", e)))?;
+    let doc = package.as_document();
+    let root = doc
+        .root()
+        .children()
+        .into_iter()
+        .find_map(|c| c.element())
+        .ok_or_else(|| MetadataError("empty document".to_owned()))?;
+    let format = match root.name().local_part() {
+        "CMD" => Format::Cmdi,
+        "METATRANSCRIPT" => Format::Imdi,
+        other => return Err(MetadataError(format!("unknown root element {}", other))),
+    };
+    let session =
+        find(root, "Session").ok_or_else(|| MetadataError("missing Session".to_owned()))?;
+    let location = find(session, "Location");
+    let mut actors = vec![];
+    descendants(session, "Actor", &mut actors);
+    Ok(Metadata {
+        format,
+        date: text(session, "Date"),
+        place: location.and_then(|l| text(l, "Address")),
+        region: location.and_then(|l| text(l, "Region")),
+        actors: actors.into_iter().map(actor).collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IMDI: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<METATRANSCRIPT xmlns="http://www.mpi.nl/IMDI/Schema/IMDI" Type="SESSION">
+  <Session>
+    <Name>rec01</Name>
+    <Date>2019-03-01</Date>
+    <MDGroup>
+      <Location>
+        <Continent>Europe</Continent>
+        <Country>Czech Republic</Country>
+        <Region>středočeská</Region>
+        <Address>Praha</Address>
+      </Location>
+      <Actors>
+        <Actor>
+          <Role>Speaker</Role>
+          <Name>John Doe</Name>
+          <FullName>Unknown</FullName>
+          <Code>JD</Code>
+          <BirthDate>1988</BirthDate>
+          <Sex>Male</Sex>
+        </Actor>
+        <Actor>
+          <Role>Interviewer</Role>
+          <Name>Jane Doe</Name>
+          <BirthDate>Unspecified</BirthDate>
+          <Sex>female</Sex>
+        </Actor>
+      </Actors>
+    </MDGroup>
+  </Session>
+</METATRANSCRIPT>"#;
+
+    #[test]
+    fn test_imdi() {
+        let metadata = read(IMDI).unwrap();
+        assert_eq!(metadata.format, Format::Imdi);
+        assert_eq!(metadata.date.as_deref(), Some("2019-03-01"));
+        assert_eq!(metadata.place.as_deref(), Some("Praha"));
+        assert_eq!(metadata.region.as_deref(), Some("středočeská"));
+        assert_eq!(
+            metadata.actors,
+            vec![
+                Actor {
+                    name: Some("John Doe".to_owned()),
+                    full_name: None,
+                    code: Some("JD".to_owned()),
+                    role: Some("Speaker".to_owned()),
+                    sex: Some(Sex::Male),
+                    birth_year: Some(1988),
+                },
+                Actor {
+                    name: Some("Jane Doe".to_owned()),
+                    role: Some("Interviewer".to_owned()),
+                    sex: Some(Sex::Female),
+                    ..Actor::default()
+                },
+            ]
+        );
+        let names: Vec<_> = metadata.actors[0].names().collect();
+        assert_eq!(names, ["John Doe", "JD"]);
+    }
+
+    #[test]
+    fn test_cmdi() {
+        let cmdi = r#"<CMD xmlns="http://www.clarin.eu/cmd/" CMDVersion="1.1">
+  <Header/>
+  <Resources/>
+  <Components>
+    <lat-session>
+      <Session>
+        <Date>1999-12</Date>
+        <Location><Address>Brno</Address></Location>
+        <Actors><Actor><Name>Petr</Name><BirthDate>1950-04-02</BirthDate></Actor></Actors>
+      </Session>
+    </lat-session>
+  </Components>
+</CMD>"#;
+        let metadata = read(cmdi).unwrap();
+        assert_eq!(metadata.format, Format::Cmdi);
+        assert_eq!(metadata.date.as_deref(), Some("1999-12"));
+        assert_eq!(metadata.place.as_deref(), Some("Brno"));
+        assert_eq!(metadata.region, None);
+        assert_eq!(metadata.actors[0].birth_year, Some(1950));
+        assert!(read("<ANNOTATION_DOCUMENT/>").is_err());
+    }
+
+    #[test]
+    fn test_is_sidecar_of() {
+        assert!(is_sidecar_of("rec01.eaf", "rec01.cmdi"));
+        assert!(is_sidecar_of("rec01.eaf", "rec01.IMDI"));
+        assert!(!is_sidecar_of("rec01.eaf", "rec02.cmdi"));
+        assert!(!is_sidecar_of("rec01.eaf", "rec01.wav"));
+    }
+}
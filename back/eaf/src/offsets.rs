@@ -0,0 +1,92 @@
+//! Offsets in units other than bytes. `Token`s and `Mistake`s count bytes
+//! of the (normalized) source, but e.g. JS strings are indexed by UTF-16
+//! code units and carets in editors move by graphemes.
+
+use unicode_segmentation::UnicodeSegmentation;
+
+use super::parser::{Mistake, Parsed};
+use super::tokenizer::Token;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum Unit {
+    Byte,
+    /// Unicode scalar values, e.g. Python string indices.
+    Char,
+    /// Extended grapheme clusters, i.e. what users perceive as characters.
+    Grapheme,
+    /// UTF-16 code units, e.g. JS string indices.
+    Utf16,
+}
+
+/// The byte offset into `source` in `unit`s. An offset within a grapheme
+/// counts it as a whole, i.e. it's rounded up to the next grapheme
+/// boundary.
+pub fn convert(source: &str, byte_offset: usize, unit: Unit) -> usize {
+    match unit {
+        Unit::Byte => byte_offset,
+        Unit::Char => source[..byte_offset].chars().count(),
+        Unit::Grapheme => source
+            .grapheme_indices(true)
+            .take_while(|&(i, _)| i < byte_offset)
+            .count(),
+        Unit::Utf16 => source[..byte_offset].encode_utf16().count(),
+    }
+}
+
+impl Parsed {
+    /// The byte offset into the source in `unit`s, see `convert`.
+    pub fn offset(&self, byte_offset: usize, unit: Unit) -> usize {
+        convert(&self.source, byte_offset, unit)
+    }
+
+    /// Like `span`, with the start and end in `unit`s.
+    pub fn span_in(&self, mistake: &Mistake, unit: Unit) -> (Option<usize>, usize, usize) {
+        let (at, start, end) = self.span(mistake);
+        (at, self.offset(start, unit), self.offset(end, unit))
+    }
+
+    /// Start and end of the token in `unit`s.
+    pub fn token_span(&self, token: &Token, unit: Unit) -> (usize, usize) {
+        (self.offset(token.start, unit), self.offset(token.end, unit))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{Parser, ParserConfig};
+    use crate::tokenizer;
+
+    #[test]
+    fn test_convert() {
+        // decomposed ř, and an emoji outside the BMP
+        let source = "r\u{30c}ek \u{1f600} x";
+        let x = source.len() - 1;
+        assert_eq!(convert(source, x, Unit::Byte), 11);
+        assert_eq!(convert(source, x, Unit::Char), 7);
+        assert_eq!(convert(source, x, Unit::Grapheme), 6);
+        assert_eq!(convert(source, x, Unit::Utf16), 8);
+        // within ř
+        assert_eq!(convert(source, 1, Unit::Grapheme), 1);
+    }
+
+    #[test]
+    fn test_span_in() {
+        let config =
+            ParserConfig::from_args(&[] as &[&str], &[] as &[&str], &["[a-zř]"], &[] as &[&str]);
+        let parsed = Parser::parse(&config, tokenizer::tokenize("řek (Q"));
+        let spans: Vec<_> = parsed
+            .mistakes
+            .iter()
+            .map(|m| parsed.span_in(m, Unit::Utf16))
+            .collect();
+        assert_eq!(spans, [(Some(2), 5, 6), (Some(1), 4, 5)]);
+        assert_eq!(parsed.token_span(&parsed.tokens[0], Unit::Byte), (0, 4));
+        assert_eq!(parsed.token_span(&parsed.tokens[0], Unit::Char), (0, 3));
+    }
+}
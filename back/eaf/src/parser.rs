@@ -3,36 +3,132 @@
 //! This is where *all* kinds of mistakes are detected and recorded. If there
 //! are any, the user will thus get a full list of what's wrong, so that they
 //! can fix everything in one go.
+//!
+//! With the `serde` feature, parse results can be (de)serialized, e.g. to
+//! send them to the frontend as JSON. The shape is part of the API: enums are
+//! tagged with a snake_case `"type"` (for mistakes, the same as
+//! `Mistake::kind`), offsets are bytes into `Parsed::source`, `at` being a
+//! token index, and attributes are strings as written, e.g. `"lang=EN"`.
+//! E.g. the parse of `(no` serializes as:
+//!
+//! ```json
+//! {
+//!   "source": "(no",
+//!   "tokens": [
+//!     {"kind": {"type": "open", "delim": "round"}, "start": 0, "end": 1},
+//!     {"kind": {"type": "non_delim"}, "start": 1, "end": 3}
+//!   ],
+//!   "nodes": [
+//!     {"type": "open", "value": "round"},
+//!     {"type": "token", "value": {"kind": {"type": "non_delim"}, "start": 1, "end": 3}}
+//!   ],
+//!   "mistakes": [{"type": "unclosed_delim", "kind": "round", "at": 0}]
+//! }
+//! ```
 
 use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::fmt;
 
 use lazy_static::lazy_static;
 use regex::{Matches, Regex};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
 use super::tokenizer::{
+    self,
     DelimKind::{self, *},
     Token,
     TokenKind::*,
-    Tokenized,
+    Tokenized, TokenizerConfig, WhitespacePolicy,
 };
 
 // NOTE: The Node could also just be a single struct per token, with
 // optional information as to which kinds of spans (possibly with which
 // attributes) it's contained in. Better for searching, worse for
-// serialization, which is our primary use case here.
+// serialization, which is our primary use case here. For searching and
+// statistics, see `Parsed::flagged_tokens`.
 #[derive(Debug, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(tag = "type", content = "value", rename_all = "snake_case")
+)]
 pub enum Node {
-    AttrList(Vec<String>),
+    AttrList(Vec<Attr>),
     Open(DelimKind),
     Close(DelimKind),
     Token(Token),
 }
 
-// TODO: for use on the client, these indices will either have to be recomputed
-// in JS-appropriate terms (UTF-16 code units or codepoints), or the appropriate
-// markup highlighting problematic regions will have to be added server-side,
-// possibly as a rich data structure -- some kind of vec of spans with annotations.
+/// An attribute in the `_`-separated list after <: either a bare code,
+/// e.g. `SM`, or a key with a value, e.g. `lang=EN` (see
+/// `ParserConfig::with_attr_values`). Serialized as written, i.e. as a
+/// string.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Attr {
+    pub key: String,
+    pub value: Option<String>,
+}
+
+impl Attr {
+    pub fn code(code: &str) -> Self {
+        Attr {
+            key: code.to_owned(),
+            value: None,
+        }
+    }
+
+    pub fn pair(key: &str, value: &str) -> Self {
+        Attr {
+            key: key.to_owned(),
+            value: Some(value.to_owned()),
+        }
+    }
+}
+
+/// As written in transcripts.
+impl fmt::Display for Attr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.value {
+            Some(value) => write!(f, "{}={}", self.key, value),
+            None => f.write_str(&self.key),
+        }
+    }
+}
+
+/// As written in transcripts, cf. `Display`.
+impl From<&str> for Attr {
+    fn from(s: &str) -> Self {
+        match s.split_once('=') {
+            Some((key, value)) => Attr::pair(key, value),
+            None => Attr::code(s),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Serialize for Attr {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for Attr {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(Attr::from(String::deserialize(deserializer)?.as_str()))
+    }
+}
+
+// NOTE: for use on the client, these indices have to be recomputed in
+// JS-appropriate terms (UTF-16 code units), as `wasm` does for its spans.
 #[derive(Debug, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(tag = "type", rename_all = "snake_case")
+)]
 pub enum Mistake {
     // at is for token offsets
     BadToken {
@@ -43,10 +139,26 @@ pub enum Mistake {
         end: usize,
         at: usize,
     },
+    /// An unknown code, or the key of a key=value attribute.
     BadAttr {
         attr: String,
         at: usize,
     },
+    /// The value of a key=value attribute doesn't match the key's pattern.
+    BadAttrValue {
+        key: String,
+        value: String,
+        at: usize,
+    },
+    DeprecatedAttr {
+        attr: String,
+        at: usize,
+    },
+    /// The same code given more than once after <.
+    DuplicateAttr {
+        attr: String,
+        at: usize,
+    },
     NestedDelim {
         kind: DelimKind,
         outermost_start: usize,
@@ -63,9 +175,99 @@ pub enum Mistake {
     MissingAttrs {
         at: usize,
     },
+    /// The label before > (e.g. `SM` in `SM>`) isn't the attribute list the
+    /// span was opened with, or is missing (empty, `at` being the >) where
+    /// labels are required, see `ParserConfig::with_span_labels`.
+    MismatchedSpanLabel {
+        label: String,
+        expected: String,
+        at: usize,
+    },
+    // start and end are byte offsets into the source; only reported when
+    // whitespace wasn't normalized by the tokenizer
+    Whitespace {
+        kind: WhitespaceKind,
+        start: usize,
+        end: usize,
+    },
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum WhitespaceKind {
+    Leading,
+    Trailing,
+    /// More than one space in a row.
+    Double,
+    /// Tabs, newlines etc.
+    NonSpace,
+}
+
+impl Mistake {
+    /// All values returned by `Mistake::kind`.
+    pub const KINDS: &'static [&'static str] = &[
+        "bad_token",
+        "bad_substr",
+        "bad_attr",
+        "bad_attr_value",
+        "deprecated_attr",
+        "duplicate_attr",
+        "nested_delim",
+        "closing_unopened_delim",
+        "unclosed_delim",
+        "missing_attrs",
+        "mismatched_span_label",
+        "whitespace",
+    ];
+
+    /// Stable name of the kind of mistake, e.g. for storing it in the DB or
+    /// aggregating statistics.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Mistake::BadToken { .. } => "bad_token",
+            Mistake::BadSubstr { .. } => "bad_substr",
+            Mistake::BadAttr { .. } => "bad_attr",
+            Mistake::BadAttrValue { .. } => "bad_attr_value",
+            Mistake::DeprecatedAttr { .. } => "deprecated_attr",
+            Mistake::DuplicateAttr { .. } => "duplicate_attr",
+            Mistake::NestedDelim { .. } => "nested_delim",
+            Mistake::ClosingUnopenedDelim { .. } => "closing_unopened_delim",
+            Mistake::UnclosedDelim { .. } => "unclosed_delim",
+            Mistake::MissingAttrs { .. } => "missing_attrs",
+            Mistake::MismatchedSpanLabel { .. } => "mismatched_span_label",
+            Mistake::Whitespace { .. } => "whitespace",
+        }
+    }
+
+    /// Warnings are about things which don't affect the structure of the
+    /// transcript, they're merely untidy.
+    pub fn is_warning(&self) -> bool {
+        matches!(
+            self,
+            Mistake::Whitespace { .. }
+                | Mistake::DeprecatedAttr { .. }
+                | Mistake::DuplicateAttr { .. }
+        )
+    }
+}
+
+/// Which spans a token is part of, as given by the delimiters around it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TokenFlags {
+    /// Inside round brackets, i.e. uncertain.
+    pub uncertain: bool,
+    /// Inside square brackets, i.e. overlapping speech.
+    pub overlap: bool,
+    /// The attributes of the enclosing angle span, if any.
+    pub attrs: Option<Vec<Attr>>,
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Parsed {
     pub source: String,
     pub tokens: Vec<Token>,
@@ -77,7 +279,92 @@ impl Parsed {
     pub fn has_mistakes(&self) -> bool {
         !self.mistakes.is_empty()
     }
+
+    /// The tokens among the nodes, with the spans they're part of. In
+    /// nested angle spans, tokens have the codes of all enclosing ones.
+    pub fn flagged_tokens(&self) -> Vec<(Token, TokenFlags)> {
+        let (mut round, mut square) = (0, 0);
+        let mut angles: Vec<Vec<Attr>> = vec![];
+        let mut tokens = vec![];
+        for node in &self.nodes {
+            match node {
+                Node::Open(Round) => round += 1,
+                Node::Close(Round) => round -= 1,
+                Node::Open(Square) => square += 1,
+                Node::Close(Square) => square -= 1,
+                Node::Open(Angle) => angles.push(vec![]),
+                Node::Close(Angle) => {
+                    angles.pop();
+                }
+                Node::AttrList(codes) => {
+                    if let Some(attrs) = angles.last_mut() {
+                        attrs.extend(codes.iter().cloned());
+                    }
+                }
+                Node::Token(token) => {
+                    let flags = TokenFlags {
+                        uncertain: round > 0,
+                        overlap: square > 0,
+                        attrs: if angles.is_empty() {
+                            None
+                        } else {
+                            Some(angles.concat())
+                        },
+                    };
+                    tokens.push((*token, flags));
+                }
+            }
+        }
+        tokens
+    }
+
+    /// Byte span of the mistake within the source, and the index of the
+    /// offending token, if any.
+    pub fn span(&self, mistake: &Mistake) -> (Option<usize>, usize, usize) {
+        let at = match mistake {
+            Mistake::BadToken { at }
+            | Mistake::BadSubstr { at, .. }
+            | Mistake::BadAttr { at, .. }
+            | Mistake::BadAttrValue { at, .. }
+            | Mistake::DeprecatedAttr { at, .. }
+            | Mistake::DuplicateAttr { at, .. }
+            | Mistake::NestedDelim { at, .. }
+            | Mistake::ClosingUnopenedDelim { at, .. }
+            | Mistake::UnclosedDelim { at, .. }
+            | Mistake::MissingAttrs { at }
+            | Mistake::MismatchedSpanLabel { at, .. } => *at,
+            Mistake::Whitespace { start, end, .. } => return (None, *start, *end),
+        };
+        let (start, end) = match self.tokens.get(at) {
+            Some(token) => match mistake {
+                Mistake::BadSubstr { start, end, .. } => (token.start + start, token.start + end),
+                _ => (token.start, token.end),
+            },
+            None => (self.source.len(), self.source.len()),
+        };
+        (Some(at), start, end)
+    }
 }
+
+/// Whether angle spans are closed with their attribute list as a label,
+/// e.g. `<SM_ZA … SM_ZA>`.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum SpanLabels {
+    /// `>` closes blindly, a word right before it is just a word.
+    #[default]
+    Off,
+    /// A label may be given: a token right before `>` which parses as an
+    /// attribute list is taken for one.
+    Allowed,
+    /// Whatever is right before `>` is the label, which must be there.
+    Required,
+}
+
+/// Bump whenever the parser starts reporting mistakes it previously didn't
+/// (or vice versa), so that validations done by older versions are
+/// recognized as stale.
+pub(crate) const RULES_REVISION: u32 = 2;
+
 #[derive(Debug)]
 pub struct ParserConfig {
     /// Full tokens that are explicitly allowed.
@@ -88,6 +375,21 @@ pub struct ParserConfig {
     atoms: Option<Regex>,
     /// Codes allowed in a _-separated list after <.
     after_angle: Option<Regex>,
+    /// Codes still recognized after <, but being phased out.
+    deprecated_attrs: Option<Regex>,
+    /// Codes of angle spans whose tokens are replaced in anonymized exports.
+    anonymized: Option<Regex>,
+    /// Keys allowed in key=value attributes after <, with the patterns of
+    /// their values.
+    attr_values: BTreeMap<String, Regex>,
+    /// Kinds of spans which may contain spans of the same kind.
+    nested: Vec<DelimKind>,
+    span_labels: SpanLabels,
+    /// Whether irregular whitespace is normalized away when tokenizing or
+    /// kept and reported.
+    whitespace: WhitespacePolicy,
+    /// The project's delimiters, if not the default ones.
+    delims: Option<TokenizerConfig>,
 }
 
 impl ParserConfig {
@@ -117,9 +419,165 @@ impl ParserConfig {
             blacklist: Self::slice_to_regex(blacklist),
             atoms,
             after_angle: Self::slice_to_regex(after_angle),
+            deprecated_attrs: None,
+            anonymized: None,
+            attr_values: BTreeMap::new(),
+            nested: vec![],
+            span_labels: SpanLabels::default(),
+            whitespace: WhitespacePolicy::default(),
+            delims: None,
         }
     }
 
+    /// Recognize these codes after < too, but report them as warnings.
+    pub fn with_deprecated_attrs<D: std::borrow::Borrow<str>>(mut self, codes: &[D]) -> Self {
+        self.deprecated_attrs = Self::slice_to_regex(codes);
+        self
+    }
+
+    /// Mark angle spans with any of these codes for anonymization, e.g. of
+    /// personal names (see `anonymize::Placeholders`). The codes still need
+    /// to be allowed after <; validation doesn't change.
+    pub fn with_anonymized<N: std::borrow::Borrow<str>>(mut self, codes: &[N]) -> Self {
+        self.anonymized = Self::slice_to_regex(codes);
+        self
+    }
+
+    /// Allow key=value attributes after <, e.g. `<SM_lang=EN …>`, with these
+    /// keys, each with a pattern its values must match (as a whole).
+    pub fn with_attr_values<K, V>(mut self, schema: &[(K, V)]) -> Self
+    where
+        K: std::borrow::Borrow<str>,
+        V: std::borrow::Borrow<str>,
+    {
+        self.attr_values = schema
+            .iter()
+            .map(|(key, value)| {
+                let re = Regex::new(&format!(r"\A(?:{})\z", value.borrow())).unwrap();
+                (key.borrow().to_owned(), re)
+            })
+            .collect();
+        self
+    }
+
+    /// Allow spans of these kinds to be nested, e.g. `(a (b) c)`, rather
+    /// than reporting `NestedDelim`.
+    pub fn with_nesting(mut self, kinds: &[DelimKind]) -> Self {
+        self.nested = kinds.to_vec();
+        self
+    }
+
+    /// Allow or require angle spans to be closed with a label repeating
+    /// their attributes, e.g. `<SM … SM>`, checking that it matches.
+    pub fn with_span_labels(mut self, labels: SpanLabels) -> Self {
+        self.span_labels = labels;
+        self
+    }
+
+    /// Report irregular whitespace rather than normalizing it, see
+    /// `tokenize`.
+    pub fn with_whitespace(mut self, policy: WhitespacePolicy) -> Self {
+        self.whitespace = policy;
+        self
+    }
+
+    /// Delimit spans with these rather than `()`, `[]` and `<>`, see
+    /// `tokenize`.
+    pub fn with_delims(mut self, delims: TokenizerConfig) -> Self {
+        self.delims = Some(delims);
+        self
+    }
+
+    /// Tokenize a segment the way the config's rules expect, i.e. with its
+    /// delimiters, keeping whitespace as is if it's to be reported.
+    pub fn tokenize(&self, source: &str) -> Tokenized {
+        self.delims().tokenize(source, self.whitespace)
+    }
+
+    /// The delimiters `tokenize` uses, see `with_delims`.
+    pub fn delims(&self) -> &TokenizerConfig {
+        match &self.delims {
+            Some(delims) => delims,
+            None => tokenizer::default_config(),
+        }
+    }
+
+    /// Identifies the rules the config enforces, for recording alongside
+    /// validation results: the parser's `RULES_REVISION` plus a hash of the
+    /// config. It's stable across builds, but it changes whenever the
+    /// config does, even if it's just listing the same atoms in a different
+    /// order.
+    pub fn version(&self) -> String {
+        // FNV-1a, as std's hashers aren't guaranteed to be stable
+        let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+        let regexes = [
+            &self.whitelist,
+            &self.blacklist,
+            &self.atoms,
+            &self.after_angle,
+            &self.deprecated_attrs,
+        ];
+        for re in &regexes {
+            let source = re.as_ref().map_or("", |re| re.as_str());
+            // terminate each regex so that e.g. moving a pattern from the
+            // whitelist to the blacklist changes the hash
+            for byte in source.bytes().chain(std::iter::once(0)) {
+                hash ^= u64::from(byte);
+                hash = hash.wrapping_mul(0x0100_0000_01b3);
+            }
+        }
+        // only if set, so that versions of configs without nesting stay the
+        // same as before it was possible
+        let mut nested: Vec<_> = self.nested.iter().map(|k| format!("{:?}", k)).collect();
+        nested.sort_unstable();
+        nested.dedup();
+        for byte in nested.concat().bytes() {
+            hash ^= u64::from(byte);
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+        // likewise
+        if self.whitespace != WhitespacePolicy::Normalize {
+            for byte in format!("{:?}", self.whitespace).bytes() {
+                hash ^= u64::from(byte);
+                hash = hash.wrapping_mul(0x0100_0000_01b3);
+            }
+        }
+        for delim in self.delims.iter().flat_map(|d| d.delims()) {
+            let kind = format!("{:?}", delim.kind);
+            let entry = [delim.open.as_str(), delim.close.as_str(), kind.as_str()];
+            for byte in entry
+                .iter()
+                .flat_map(|s| s.bytes().chain(std::iter::once(0)))
+            {
+                hash ^= u64::from(byte);
+                hash = hash.wrapping_mul(0x0100_0000_01b3);
+            }
+        }
+        if self.span_labels != SpanLabels::Off {
+            for byte in format!("{:?}", self.span_labels).bytes() {
+                hash ^= u64::from(byte);
+                hash = hash.wrapping_mul(0x0100_0000_01b3);
+            }
+        }
+        for (key, value) in &self.attr_values {
+            let entry = [key.as_str(), value.as_str()];
+            for byte in entry
+                .iter()
+                .flat_map(|s| s.bytes().chain(std::iter::once(0)))
+            {
+                hash ^= u64::from(byte);
+                hash = hash.wrapping_mul(0x0100_0000_01b3);
+            }
+        }
+        format!("r{}-{:016x}", RULES_REVISION, hash)
+    }
+
+    /// The pattern `with_anonymized` codes are matched against, if any, so
+    /// that anonymized exports can tell when it changes.
+    pub fn anonymized(&self) -> Option<&str> {
+        self.anonymized.as_ref().map(|re| re.as_str())
+    }
+
     fn slice_to_regex<S: std::borrow::Borrow<str>>(slice: &[S]) -> Option<Regex> {
         let joined = slice.join("|");
         if joined.is_empty() {
@@ -147,6 +605,31 @@ impl ParserConfig {
         Self::is_match(&self.after_angle, s)
     }
 
+    fn in_deprecated_attrs(&self, s: &str) -> bool {
+        Self::is_match(&self.deprecated_attrs, s)
+    }
+
+    pub fn anonymizes(&self, code: &str) -> bool {
+        Self::is_match(&self.anonymized, code)
+    }
+
+    fn attr_value_pattern(&self, key: &str) -> Option<&Regex> {
+        self.attr_values.get(key)
+    }
+
+    /// Whether all of the codes in the `_`-separated list are allowed after
+    /// <, i.e. whether it's an attribute list rather than a word.
+    fn is_attr_list(&self, s: &str) -> bool {
+        s.split('_').all(|code| match code.split_once('=') {
+            Some((key, _)) => self.attr_values.contains_key(key),
+            None => self.in_after_angle(code) || self.in_deprecated_attrs(code),
+        })
+    }
+
+    fn allows_nesting(&self, kind: DelimKind) -> bool {
+        self.nested.contains(&kind)
+    }
+
     fn maybe_iter_atoms<'r, 't>(&'r self, s: &'t str) -> Option<Matches<'r, 't>> {
         self.atoms.as_ref().map(|re| re.find_iter(s))
     }
@@ -162,9 +645,14 @@ pub struct Parser<'c> {
     nodes: Vec<Node>,
     mistakes: Vec<Mistake>,
 
-    round_start: Option<usize>,
-    square_start: Option<usize>,
-    angle_start: Option<usize>,
+    /// Indices of the opening tokens of the spans currently open, per kind
+    /// (see `starts`). Unless nesting is allowed, there's at most one.
+    round_starts: Vec<usize>,
+    square_starts: Vec<usize>,
+    angle_starts: Vec<usize>,
+    /// Attributes of the open angle spans, alongside `angle_starts`, if
+    /// they're valid (and thus can be compared with the closing label).
+    angle_attrs: Vec<Option<Vec<Attr>>>,
 }
 
 impl<'c> Parser<'c> {
@@ -178,30 +666,22 @@ impl<'c> Parser<'c> {
             mistakes: vec![],
             nodes: vec![],
 
-            round_start: None,
-            square_start: None,
-            angle_start: None,
+            round_starts: vec![],
+            square_starts: vec![],
+            angle_starts: vec![],
+            angle_attrs: vec![],
         };
 
         let num_tokens = parser.tokens.len();
         while parser.current < num_tokens {
             parser.step();
         }
-        if let Some(at) = parser.round_start {
-            parser
-                .mistakes
-                .push(Mistake::UnclosedDelim { kind: Round, at });
-        }
-        if let Some(at) = parser.square_start {
-            parser
-                .mistakes
-                .push(Mistake::UnclosedDelim { kind: Square, at });
-        }
-        if let Some(at) = parser.angle_start {
-            parser
-                .mistakes
-                .push(Mistake::UnclosedDelim { kind: Angle, at });
+        for kind in [Round, Square, Angle] {
+            for at in std::mem::take(parser.starts(kind)) {
+                parser.mistakes.push(Mistake::UnclosedDelim { kind, at });
+            }
         }
+        parser.check_whitespace();
 
         Parsed {
             source: parser.source,
@@ -215,13 +695,48 @@ impl<'c> Parser<'c> {
         let current = &self.tokens[self.current];
         match current.kind {
             // whitespace is removed by tokenizer
+            NonDelim if self.at_span_label() => self.parse_span_label(),
             NonDelim => self.parse_word(),
-            Open(Round) => self.parse_open_round(),
-            Close(Round) => self.parse_close_round(),
-            Open(Square) => self.parse_open_square(),
-            Close(Square) => self.parse_close_square(),
             Open(Angle) => self.parse_open_angle(),
-            Close(Angle) => self.parse_close_angle(),
+            Open(kind) => self.parse_open(kind),
+            Close(Angle) if self.config.span_labels == SpanLabels::Required => {
+                self.check_span_label("");
+                self.parse_close(Angle)
+            }
+            Close(kind) => self.parse_close(kind),
+        }
+    }
+
+    fn starts(&mut self, kind: DelimKind) -> &mut Vec<usize> {
+        match kind {
+            Round => &mut self.round_starts,
+            Square => &mut self.square_starts,
+            Angle => &mut self.angle_starts,
+        }
+    }
+
+    fn check_whitespace(&mut self) {
+        lazy_static! {
+            static ref WHITESPACE_RE: Regex = Regex::new(r"\s+").unwrap();
+        }
+        let len = self.source.len();
+        for m in WHITESPACE_RE.find_iter(&self.source) {
+            let kind = if m.start() == 0 {
+                WhitespaceKind::Leading
+            } else if m.end() == len {
+                WhitespaceKind::Trailing
+            } else if m.as_str().chars().any(|c| c != ' ') {
+                WhitespaceKind::NonSpace
+            } else if m.as_str().len() > 1 {
+                WhitespaceKind::Double
+            } else {
+                continue;
+            };
+            self.mistakes.push(Mistake::Whitespace {
+                kind,
+                start: m.start(),
+                end: m.end(),
+            });
         }
     }
 
@@ -242,7 +757,7 @@ impl<'c> Parser<'c> {
         if NUMERIC_RE.is_match(token_str) {
             // plain numbers should only be allowed inside parens as counts
             // of unintelligible words
-            if self.round_start.is_none() {
+            if self.round_starts.is_empty() {
                 word_ok = false;
                 self.mistakes.push(Mistake::BadToken { at: self.current });
             }
@@ -280,73 +795,96 @@ impl<'c> Parser<'c> {
         self.current += 1;
     }
 
-    fn parse_open_round(&mut self) {
-        if let Some(i) = self.round_start {
-            self.mistakes.push(Mistake::NestedDelim {
-                kind: Round,
-                outermost_start: i,
-                at: self.current,
-            });
-        } else {
-            self.round_start = Some(self.current);
-            self.nodes.push(Node::Open(Round));
+    fn parse_open(&mut self, kind: DelimKind) {
+        let current = self.current;
+        let nesting = self.config.allows_nesting(kind);
+        let starts = self.starts(kind);
+        match starts.first() {
+            Some(&outermost_start) if !nesting => {
+                self.mistakes.push(Mistake::NestedDelim {
+                    kind,
+                    outermost_start,
+                    at: current,
+                });
+            }
+            _ => {
+                starts.push(current);
+                self.nodes.push(Node::Open(kind));
+            }
         }
         self.current += 1;
     }
 
-    fn parse_close_round(&mut self) {
-        if self.round_start.take().is_none() {
+    fn parse_close(&mut self, kind: DelimKind) {
+        if self.starts(kind).pop().is_none() {
             self.mistakes.push(Mistake::ClosingUnopenedDelim {
-                kind: Round,
+                kind,
                 at: self.current,
             })
         } else {
-            self.nodes.push(Node::Close(Round));
+            if kind == Angle {
+                self.angle_attrs.pop();
+            }
+            self.nodes.push(Node::Close(kind));
         }
         self.current += 1;
     }
 
-    // TODO: the following methods are basically copy-pastes of the two
-    // previous ones; any abstraction possible? at least a macro?
-
-    fn parse_open_square(&mut self) {
-        if let Some(i) = self.square_start {
-            self.mistakes.push(Mistake::NestedDelim {
-                kind: Square,
-                outermost_start: i,
-                at: self.current,
-            });
-        } else {
-            self.square_start = Some(self.current);
-            self.nodes.push(Node::Open(Square));
+    /// Whether the current token is the label of an angle span, i.e. right
+    /// before the > closing it (cf. `SpanLabels`).
+    fn at_span_label(&self) -> bool {
+        let labels = self.config.span_labels;
+        if labels == SpanLabels::Off || self.angle_starts.is_empty() {
+            return false;
         }
+        let (token, token_str) = Parser::get_token(self.current, &self.tokens, &self.source);
+        match self.tokens.get(self.current + 1) {
+            Some(next) if next.kind == Close(Angle) && next.start == token.end => {
+                labels == SpanLabels::Required || self.config.is_attr_list(token_str)
+            }
+            _ => false,
+        }
+    }
+
+    /// The label and the > after it.
+    fn parse_span_label(&mut self) {
+        let (_, label) = Parser::get_token(self.current, &self.tokens, &self.source);
+        let label = label.to_owned();
+        self.check_span_label(&label);
         self.current += 1;
+        self.parse_close(Angle);
     }
 
-    fn parse_close_square(&mut self) {
-        if self.square_start.take().is_none() {
-            self.mistakes.push(Mistake::ClosingUnopenedDelim {
-                kind: Square,
+    /// Compare the label (empty if missing) with the attributes of the
+    /// innermost open angle span, unless they're invalid anyway.
+    fn check_span_label(&mut self, label: &str) {
+        let expected = match self.angle_attrs.last() {
+            Some(Some(attrs)) => attrs,
+            _ => return,
+        };
+        let mut given: Vec<_> = label
+            .split('_')
+            .filter(|code| !code.is_empty())
+            .map(Attr::from)
+            .collect();
+        given.sort();
+        if given != *expected {
+            let expected: Vec<_> = expected.iter().map(Attr::to_string).collect();
+            self.mistakes.push(Mistake::MismatchedSpanLabel {
+                label: label.to_owned(),
+                expected: expected.join("_"),
                 at: self.current,
-            })
-        } else {
-            self.nodes.push(Node::Close(Square));
+            });
         }
-        self.current += 1;
     }
 
+    /// Angle spans are followed by their attribute codes.
     fn parse_open_angle(&mut self) {
-        if let Some(i) = self.angle_start {
-            self.mistakes.push(Mistake::NestedDelim {
-                kind: Angle,
-                outermost_start: i,
-                at: self.current,
-            });
-        } else {
-            self.angle_start = Some(self.current);
-            self.nodes.push(Node::Open(Angle));
+        let open = self.angle_starts.len();
+        self.parse_open(Angle);
+        if self.angle_starts.len() > open {
+            self.angle_attrs.push(None);
         }
-        self.current += 1;
 
         if self.current == self.tokens.len() {
             self.mistakes
@@ -364,37 +902,67 @@ impl<'c> Parser<'c> {
             return;
         }
 
-        let mut codes = vec![];
-        let mut codes_ok = true;
+        let mut attrs: Vec<Attr> = vec![];
+        let mut attrs_ok = true;
         for code in token_str.split('_') {
-            let code = code.to_owned();
-            if self.config.in_after_angle(&code) {
-                if !(code.is_empty() || codes.contains(&code)) {
-                    codes.push(code);
+            let attr = match code.split_once('=') {
+                Some((key, value)) => match self.config.attr_value_pattern(key) {
+                    Some(re) if re.is_match(value) => Attr::pair(key, value),
+                    Some(_) => {
+                        attrs_ok = false;
+                        self.mistakes.push(Mistake::BadAttrValue {
+                            key: key.to_owned(),
+                            value: value.to_owned(),
+                            at: self.current,
+                        });
+                        continue;
+                    }
+                    None => {
+                        attrs_ok = false;
+                        self.mistakes.push(Mistake::BadAttr {
+                            attr: key.to_owned(),
+                            at: self.current,
+                        });
+                        continue;
+                    }
+                },
+                None => {
+                    let deprecated = self.config.in_deprecated_attrs(code);
+                    if !self.config.in_after_angle(code) && !deprecated {
+                        attrs_ok = false;
+                        self.mistakes.push(Mistake::BadAttr {
+                            attr: code.to_owned(),
+                            at: self.current,
+                        });
+                        continue;
+                    }
+                    if deprecated {
+                        self.mistakes.push(Mistake::DeprecatedAttr {
+                            attr: code.to_owned(),
+                            at: self.current,
+                        });
+                    }
+                    Attr::code(code)
                 }
-            } else {
-                codes_ok = false;
-                self.mistakes.push(Mistake::BadAttr {
-                    attr: code,
+            };
+            // keys can't repeat either, even with different values
+            if attrs.iter().any(|a| a.key == attr.key) {
+                self.mistakes.push(Mistake::DuplicateAttr {
+                    attr: attr.key,
                     at: self.current,
                 });
+            } else if !attr.key.is_empty() {
+                attrs.push(attr);
             }
         }
-        if codes_ok {
-            codes.sort();
-            self.nodes.push(Node::AttrList(codes));
-        }
-        self.current += 1;
-    }
-
-    fn parse_close_angle(&mut self) {
-        if self.angle_start.take().is_none() {
-            self.mistakes.push(Mistake::ClosingUnopenedDelim {
-                kind: Angle,
-                at: self.current,
-            })
-        } else {
-            self.nodes.push(Node::Close(Angle));
+        if attrs_ok {
+            attrs.sort();
+            if self.angle_starts.len() > open {
+                if let Some(last) = self.angle_attrs.last_mut() {
+                    *last = Some(attrs.clone());
+                }
+            }
+            self.nodes.push(Node::AttrList(attrs));
         }
         self.current += 1;
     }
@@ -403,7 +971,7 @@ impl<'c> Parser<'c> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::tokenizer;
+    use crate::tokenizer::{self, WhitespacePolicy};
 
     lazy_static! {
         static ref ATOMS: Vec<String> = {
@@ -466,6 +1034,316 @@ mod tests {
         assert!(!pc.in_after_angle("_"));
     }
 
+    #[test]
+    fn test_deprecated_attrs() {
+        let config = ParserConfig::from_args(&[] as &[&str], &[] as &[&str], &ATOMS, &["SM"])
+            .with_deprecated_attrs(&["SJ"]);
+        let seg = Parser::parse(&config, tokenizer::tokenize("<SM_SJ čáp>"));
+        assert_eq!(
+            seg.mistakes,
+            vec![Mistake::DeprecatedAttr {
+                attr: "SJ".to_owned(),
+                at: 1
+            }]
+        );
+        assert!(seg.mistakes.iter().all(Mistake::is_warning));
+        assert!(seg
+            .nodes
+            .contains(&Node::AttrList(vec![Attr::code("SJ"), Attr::code("SM")])));
+
+        let seg = Parser::parse(&config, tokenizer::tokenize("<MJ čáp>"));
+        assert!(matches!(seg.mistakes[..], [Mistake::BadAttr { .. }]));
+    }
+
+    #[test]
+    fn test_flagged_tokens() {
+        let config = ParserConfig::from_args(&[] as &[&str], &[] as &[&str], &ATOMS, &["SM"]);
+        let seg = Parser::parse(&config, tokenizer::tokenize("(čáp [<SM čáp]> čáp)"));
+        let flags: Vec<_> = seg.flagged_tokens().into_iter().map(|(_, f)| f).collect();
+        let sm = Some(vec![Attr::code("SM")]);
+        assert_eq!(
+            flags,
+            vec![
+                TokenFlags {
+                    uncertain: true,
+                    overlap: false,
+                    attrs: None,
+                },
+                TokenFlags {
+                    uncertain: true,
+                    overlap: true,
+                    attrs: sm,
+                },
+                TokenFlags {
+                    uncertain: true,
+                    overlap: false,
+                    attrs: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_duplicate_attrs() {
+        let config = ParserConfig::from_args(&[] as &[&str], &[] as &[&str], &ATOMS, &["SM"]);
+        let seg = Parser::parse(&config, tokenizer::tokenize("<SM_SM čáp>"));
+        assert_eq!(
+            seg.mistakes,
+            vec![Mistake::DuplicateAttr {
+                attr: "SM".to_owned(),
+                at: 1
+            }]
+        );
+        assert!(seg.mistakes.iter().all(Mistake::is_warning));
+        assert!(seg.nodes.contains(&Node::AttrList(vec![Attr::code("SM")])));
+    }
+
+    #[test]
+    fn test_attr_values() {
+        let config = ParserConfig::from_args(&[] as &[&str], &[] as &[&str], &ATOMS, &["SM"])
+            .with_attr_values(&[("lang", "CS|EN")]);
+        let seg = Parser::parse(&config, tokenizer::tokenize("<lang=EN_SM čáp>"));
+        assert!(!seg.has_mistakes(), "{:?}", seg.mistakes);
+        assert!(seg.nodes.contains(&Node::AttrList(vec![
+            Attr::code("SM"),
+            Attr::pair("lang", "EN")
+        ])));
+        assert_eq!(Attr::pair("lang", "EN").to_string(), "lang=EN");
+
+        // values have to match as a whole
+        let seg = Parser::parse(&config, tokenizer::tokenize("<SM_lang=ENG čáp>"));
+        assert_eq!(
+            seg.mistakes,
+            vec![Mistake::BadAttrValue {
+                key: "lang".to_owned(),
+                value: "ENG".to_owned(),
+                at: 1
+            }]
+        );
+        assert!(!seg.mistakes[0].is_warning());
+
+        let seg = Parser::parse(&config, tokenizer::tokenize("<SM=EN čáp>"));
+        assert_eq!(
+            seg.mistakes,
+            vec![Mistake::BadAttr {
+                attr: "SM".to_owned(),
+                at: 1
+            }]
+        );
+
+        let seg = Parser::parse(&config, tokenizer::tokenize("<lang=CS_lang=EN čáp>"));
+        assert_eq!(
+            seg.mistakes,
+            vec![Mistake::DuplicateAttr {
+                attr: "lang".to_owned(),
+                at: 1
+            }]
+        );
+        assert!(seg
+            .nodes
+            .contains(&Node::AttrList(vec![Attr::pair("lang", "CS")])));
+    }
+
+    #[test]
+    fn test_version() {
+        let config = |after_angle: &[&str]| {
+            ParserConfig::from_args(&["hm"], &[] as &[&str], &["a"], after_angle)
+        };
+        assert_eq!(config(&["SM"]).version(), config(&["SM"]).version());
+        assert_ne!(config(&["SM"]).version(), config(&["SM", "SJ"]).version());
+        assert_ne!(
+            config(&["SM"]).version(),
+            config(&["SM"]).with_deprecated_attrs(&["SJ"]).version()
+        );
+        let swapped = ParserConfig::from_args(&[] as &[&str], &["hm"], &["a"], &["SM"]);
+        assert_ne!(config(&["SM"]).version(), swapped.version());
+        assert!(config(&[]).version().starts_with("r2-"));
+        assert_eq!(
+            config(&["SM"]).with_nesting(&[]).version(),
+            config(&["SM"]).version()
+        );
+        assert_ne!(
+            config(&["SM"]).with_nesting(&[Round]).version(),
+            config(&["SM"]).version()
+        );
+        assert_eq!(
+            config(&["SM"])
+                .with_attr_values::<&str, &str>(&[])
+                .version(),
+            config(&["SM"]).version()
+        );
+        assert_ne!(
+            config(&["SM"])
+                .with_attr_values(&[("lang", "EN")])
+                .version(),
+            config(&["SM"])
+                .with_attr_values(&[("lang", "CS")])
+                .version()
+        );
+        assert_ne!(
+            config(&["SM"])
+                .with_whitespace(WhitespacePolicy::Report)
+                .version(),
+            config(&["SM"]).version()
+        );
+        assert_ne!(
+            config(&["SM"])
+                .with_delims(TokenizerConfig::default())
+                .version(),
+            config(&["SM"]).version()
+        );
+    }
+
+    #[test]
+    fn test_nesting() {
+        let config = ParserConfig::from_args(&[] as &[&str], &[] as &[&str], &ATOMS, &["SM"])
+            .with_nesting(&[Round, Angle]);
+        let seg = Parser::parse(&config, tokenizer::tokenize("(a (b) <SM c <SM d>>) e"));
+        assert!(!seg.has_mistakes(), "{:?}", seg.mistakes);
+        let opened = seg
+            .nodes
+            .iter()
+            .filter(|n| **n == Node::Open(Round))
+            .count();
+        assert_eq!(opened, 2);
+        let flags: Vec<_> = seg.flagged_tokens().into_iter().map(|(_, f)| f).collect();
+        let uncertain: Vec<_> = flags.iter().map(|f| f.uncertain).collect();
+        assert_eq!(uncertain, vec![true, true, true, true, false]);
+        assert_eq!(flags[2].attrs, Some(vec![Attr::code("SM")]));
+        assert_eq!(
+            flags[3].attrs,
+            Some(vec![Attr::code("SM"), Attr::code("SM")])
+        );
+
+        // other kinds still can't be nested
+        let seg = Parser::parse(&config, tokenizer::tokenize("[a [b]]"));
+        assert_eq!(
+            seg.mistakes,
+            vec![
+                Mistake::NestedDelim {
+                    kind: Square,
+                    outermost_start: 0,
+                    at: 2
+                },
+                Mistake::ClosingUnopenedDelim {
+                    kind: Square,
+                    at: 5
+                },
+            ]
+        );
+
+        let seg = Parser::parse(&config, tokenizer::tokenize("(a (b"));
+        assert_eq!(
+            seg.mistakes,
+            vec![
+                Mistake::UnclosedDelim { kind: Round, at: 0 },
+                Mistake::UnclosedDelim { kind: Round, at: 2 },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_span_labels() {
+        let config = |labels| {
+            ParserConfig::from_args(&[] as &[&str], &[] as &[&str], &ATOMS, &["SM", "ZA"])
+                .with_attr_values(&[("lang", "CS|EN")])
+                .with_span_labels(labels)
+        };
+        let allowed = config(SpanLabels::Allowed);
+        let parse = |config: &ParserConfig, s: &str| Parser::parse(config, tokenizer::tokenize(s));
+        for ok in &["<SM čáp>", "<SM čáp SM>", "<ZA_lang=EN čáp lang=EN_ZA>"] {
+            let seg = parse(&allowed, ok);
+            assert!(!seg.has_mistakes(), "{}: {:?}", ok, seg.mistakes);
+        }
+        let seg = parse(&allowed, "<SM čáp ZA>");
+        assert_eq!(
+            seg.mistakes,
+            vec![Mistake::MismatchedSpanLabel {
+                label: "ZA".to_owned(),
+                expected: "SM".to_owned(),
+                at: 3
+            }]
+        );
+        // the label isn't a token of the span
+        let flags = seg.flagged_tokens();
+        assert_eq!(flags.len(), 1);
+        assert_eq!(flags[0].1.attrs, Some(vec![Attr::code("SM")]));
+
+        let required = config(SpanLabels::Required);
+        assert!(!parse(&required, "<SM čáp SM>").has_mistakes());
+        let seg = parse(&required, "<SM čáp >");
+        assert_eq!(
+            seg.mistakes,
+            vec![Mistake::MismatchedSpanLabel {
+                label: "".to_owned(),
+                expected: "SM".to_owned(),
+                at: 3
+            }]
+        );
+        assert!(matches!(
+            parse(&required, "<SM čáp>").mistakes[..],
+            [Mistake::MismatchedSpanLabel { .. }]
+        ));
+
+        // without labels, the word before > is just a word
+        let off = config(SpanLabels::Off);
+        assert_eq!(parse(&off, "<SM čáp SM>").flagged_tokens().len(), 2);
+        assert_ne!(off.version(), allowed.version());
+    }
+
+    #[test]
+    fn test_mistake_kinds() {
+        let seg = Parser::parse(&CONFIG, tokenizer::tokenize("hm ž <X )"));
+        assert!(seg.has_mistakes());
+        for mistake in &seg.mistakes {
+            assert!(Mistake::KINDS.contains(&mistake.kind()));
+        }
+    }
+
+    #[test]
+    fn test_whitespace() {
+        let seg = Parser::parse(&CONFIG, tokenizer::tokenize(" čáp  \tmáro "));
+        assert!(!seg.has_mistakes());
+
+        let source = " čáp  máro\tčáp  ";
+        let seg = Parser::parse(
+            &CONFIG,
+            tokenizer::tokenize_with(source, WhitespacePolicy::Report),
+        );
+        let config = ParserConfig::from_args(&[] as &[&str], &[] as &[&str], &ATOMS, &["SM"])
+            .with_whitespace(WhitespacePolicy::Report);
+        assert_eq!(
+            Parser::parse(&config, config.tokenize(source)).mistakes,
+            seg.mistakes
+        );
+        assert_eq!(
+            seg.mistakes,
+            vec![
+                Mistake::Whitespace {
+                    kind: WhitespaceKind::Leading,
+                    start: 0,
+                    end: 1
+                },
+                Mistake::Whitespace {
+                    kind: WhitespaceKind::Double,
+                    start: 6,
+                    end: 8
+                },
+                Mistake::Whitespace {
+                    kind: WhitespaceKind::NonSpace,
+                    start: 13,
+                    end: 14
+                },
+                Mistake::Whitespace {
+                    kind: WhitespaceKind::Trailing,
+                    start: 19,
+                    end: 21
+                },
+            ]
+        );
+        assert!(seg.mistakes.iter().all(Mistake::is_warning));
+    }
+
     #[test]
     fn test_whitelist() {
         assert!(!ATOMS.iter().any(|s| s == "."));
@@ -553,7 +1431,7 @@ mod tests {
                 end: 8,
             }),
             Node::Open(Angle),
-            Node::AttrList(vec!["SM".to_owned()]),
+            Node::AttrList(vec![Attr::code("SM")]),
             Node::Token(Token {
                 kind: NonDelim,
                 start: 13,
@@ -642,4 +1520,118 @@ mod tests {
             Mistake::UnclosedDelim { kind: Angle, at: 1 }
         );
     }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serialize() {
+        use serde_json::json;
+
+        let seg = Parser::parse(&CONFIG, tokenizer::tokenize("(no"));
+        let value = serde_json::to_value(&seg).unwrap();
+        assert_eq!(
+            value,
+            json!({
+                "source": "(no",
+                "tokens": [
+                    {"kind": {"type": "open", "delim": "round"}, "start": 0, "end": 1},
+                    {"kind": {"type": "non_delim"}, "start": 1, "end": 3},
+                ],
+                "nodes": [
+                    {"type": "open", "value": "round"},
+                    {"type": "token", "value": {"kind": {"type": "non_delim"}, "start": 1, "end": 3}},
+                ],
+                "mistakes": [{"type": "unclosed_delim", "kind": "round", "at": 0}],
+            })
+        );
+        let back: Parsed = serde_json::from_value(value).unwrap();
+        assert_eq!(back.nodes, seg.nodes);
+        assert_eq!(back.mistakes, seg.mistakes);
+
+        let seg = Parser::parse(&CONFIG, tokenizer::tokenize("<SM no>"));
+        assert_eq!(
+            serde_json::to_value(&seg.nodes[1]).unwrap(),
+            json!({"type": "attr_list", "value": ["SM"]})
+        );
+        // attributes as written, key=value ones included
+        let attrs = Node::AttrList(vec![Attr::code("SM"), Attr::pair("lang", "EN")]);
+        let value = serde_json::to_value(&attrs).unwrap();
+        assert_eq!(
+            value,
+            json!({"type": "attr_list", "value": ["SM", "lang=EN"]})
+        );
+        assert_eq!(serde_json::from_value::<Node>(value).unwrap(), attrs);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serialize_mistakes() {
+        use serde_json::json;
+
+        let mistakes = vec![
+            Mistake::BadToken { at: 0 },
+            Mistake::BadSubstr {
+                start: 1,
+                end: 2,
+                at: 0,
+            },
+            Mistake::BadAttr {
+                attr: "XY".to_owned(),
+                at: 1,
+            },
+            Mistake::DeprecatedAttr {
+                attr: "SJ".to_owned(),
+                at: 1,
+            },
+            Mistake::BadAttrValue {
+                key: "lang".to_owned(),
+                value: "ENG".to_owned(),
+                at: 1,
+            },
+            Mistake::DuplicateAttr {
+                attr: "SM".to_owned(),
+                at: 1,
+            },
+            Mistake::NestedDelim {
+                kind: Square,
+                outermost_start: 0,
+                at: 2,
+            },
+            Mistake::ClosingUnopenedDelim { kind: Angle, at: 0 },
+            Mistake::UnclosedDelim { kind: Round, at: 0 },
+            Mistake::MissingAttrs { at: 1 },
+            Mistake::MismatchedSpanLabel {
+                label: "ZA".to_owned(),
+                expected: "SM".to_owned(),
+                at: 3,
+            },
+            Mistake::Whitespace {
+                kind: WhitespaceKind::NonSpace,
+                start: 2,
+                end: 3,
+            },
+        ];
+        assert_eq!(mistakes.len(), Mistake::KINDS.len());
+        for mistake in &mistakes {
+            let value = serde_json::to_value(mistake).unwrap();
+            assert_eq!(value["type"], mistake.kind());
+            let back: Mistake = serde_json::from_value(value).unwrap();
+            assert_eq!(&back, mistake);
+        }
+        assert_eq!(
+            serde_json::to_value(&mistakes[11]).unwrap(),
+            json!({"type": "whitespace", "kind": "non_space", "start": 2, "end": 3})
+        );
+        assert_eq!(
+            serde_json::to_value(&mistakes[6]).unwrap(),
+            json!({"type": "nested_delim", "kind": "square", "outermost_start": 0, "at": 2})
+        );
+        assert_eq!(
+            serde_json::to_value(&mistakes[4]).unwrap(),
+            json!({"type": "bad_attr_value", "key": "lang", "value": "ENG", "at": 1})
+        );
+        assert_eq!(
+            serde_json::to_value(&mistakes[10]).unwrap(),
+            json!({"type": "mismatched_span_label", "label": "ZA", "expected": "SM", "at": 3})
+        );
+    }
 }
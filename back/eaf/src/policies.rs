@@ -0,0 +1,312 @@
+//! Which rules a tier's annotations are validated by.
+//!
+//! Not every tier holds freeform transcript: some hold codes, translations
+//! or comments. `TierPolicies` is an ordered list of rules, each selecting
+//! tiers by a regex matched against either the tier ID or its linguistic
+//! type (cf. `tiers::TierMapping`), and saying how their values should be
+//! validated. Tiers no rule selects are validated as before: with the
+//! tier's controlled vocabulary if its type has one, as freeform transcript
+//! otherwise.
+
+use std::fmt;
+
+use regex::Regex;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PolicyTarget {
+    TierId,
+    LinguisticType,
+}
+
+impl PolicyTarget {
+    pub fn label(self) -> &'static str {
+        match self {
+            PolicyTarget::TierId => "tier_id",
+            PolicyTarget::LinguisticType => "linguistic_type",
+        }
+    }
+
+    pub fn from_label(label: &str) -> Option<Self> {
+        match label {
+            "tier_id" => Some(PolicyTarget::TierId),
+            "linguistic_type" => Some(PolicyTarget::LinguisticType),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum PolicyError {
+    UnknownTarget(String),
+    UnknownPolicy(String),
+    /// A `pattern` policy without a pattern, or another one with one.
+    Pattern(String),
+    InvalidRegex {
+        pattern: String,
+        error: String,
+    },
+}
+
+impl fmt::Display for PolicyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PolicyError::UnknownTarget(target) => write!(
+                f,
+                "unknown policy target {:?}, expected tier_id or linguistic_type",
+                target
+            ),
+            PolicyError::UnknownPolicy(policy) => write!(
+                f,
+                "unknown policy {:?}, expected freeform, vocabulary, pattern or skip",
+                policy
+            ),
+            PolicyError::Pattern(policy) => write!(
+                f,
+                "the {} policy {} a pattern",
+                policy,
+                if policy == "pattern" {
+                    "needs"
+                } else {
+                    "doesn't take"
+                }
+            ),
+            PolicyError::InvalidRegex { pattern, error } => {
+                write!(f, "invalid pattern {:?}: {}", pattern, error)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PolicyError {}
+
+/// Like `Regex`, matching whole strings only and remembering the pattern
+/// as given.
+#[derive(Debug, Clone)]
+pub struct FullMatch {
+    source: String,
+    regex: Regex,
+}
+
+impl FullMatch {
+    pub fn new(pattern: &str) -> Result<Self, PolicyError> {
+        let regex = Regex::new(&format!(r"\A(?:{})\z", pattern)).map_err(|e| {
+            PolicyError::InvalidRegex {
+                pattern: pattern.to_owned(),
+                error: e.to_string(),
+            }
+        })?;
+        Ok(Self {
+            source: pattern.to_owned(),
+            regex,
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    pub fn is_match(&self, text: &str) -> bool {
+        self.regex.is_match(text)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Policy {
+    /// Parsed according to the project's transcription rules.
+    Freeform,
+    /// Taken from the controlled vocabulary of the tier's linguistic type.
+    Vocabulary,
+    /// Matching a pattern, e.g. for codes.
+    Pattern(FullMatch),
+    /// Not validated at all, e.g. comments.
+    Skip,
+}
+
+impl Policy {
+    pub fn new(label: &str, pattern: Option<&str>) -> Result<Self, PolicyError> {
+        let policy = match (label, pattern) {
+            ("pattern", Some(pattern)) => return Ok(Policy::Pattern(FullMatch::new(pattern)?)),
+            ("freeform", None) => Policy::Freeform,
+            ("vocabulary", None) => Policy::Vocabulary,
+            ("skip", None) => Policy::Skip,
+            ("freeform", _) | ("vocabulary", _) | ("skip", _) | ("pattern", None) => {
+                return Err(PolicyError::Pattern(label.to_owned()))
+            }
+            _ => return Err(PolicyError::UnknownPolicy(label.to_owned())),
+        };
+        Ok(policy)
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Policy::Freeform => "freeform",
+            Policy::Vocabulary => "vocabulary",
+            Policy::Pattern(_) => "pattern",
+            Policy::Skip => "skip",
+        }
+    }
+
+    pub fn pattern(&self) -> Option<&str> {
+        match self {
+            Policy::Pattern(pattern) => Some(pattern.as_str()),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PolicyRule {
+    pub target: PolicyTarget,
+    pub tiers: FullMatch,
+    pub policy: Policy,
+}
+
+impl PolicyRule {
+    pub fn new(
+        target: &str,
+        tiers: &str,
+        policy: &str,
+        pattern: Option<&str>,
+    ) -> Result<Self, PolicyError> {
+        Ok(Self {
+            target: PolicyTarget::from_label(target)
+                .ok_or_else(|| PolicyError::UnknownTarget(target.to_owned()))?,
+            tiers: FullMatch::new(tiers)?,
+            policy: Policy::new(policy, pattern)?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TierPolicies {
+    rules: Vec<PolicyRule>,
+}
+
+impl TierPolicies {
+    pub fn new(rules: Vec<PolicyRule>) -> Self {
+        Self { rules }
+    }
+
+    pub fn rules(&self) -> &[PolicyRule] {
+        &self.rules
+    }
+
+    /// The policy of the first matching rule, if any.
+    pub fn policy(&self, tier_id: &str, linguistic_type: &str) -> Option<&Policy> {
+        self.rules
+            .iter()
+            .find(|rule| match rule.target {
+                PolicyTarget::TierId => rule.tiers.is_match(tier_id),
+                PolicyTarget::LinguisticType => rule.tiers.is_match(linguistic_type),
+            })
+            .map(|rule| &rule.policy)
+    }
+}
+
+/// A value on a tier with a `Policy::Pattern` which doesn't match it.
+#[derive(Debug, PartialEq, Clone)]
+pub struct PatternMismatch {
+    pub tier: String,
+    pub annotation: String,
+    pub value: String,
+    pub pattern: String,
+}
+
+impl PatternMismatch {
+    /// Its kind, cf. `parser::Mistake::kind`.
+    pub const KIND: &'static str = "pattern_mismatch";
+
+    /// The mismatch, if the value doesn't match the policy's pattern. Like
+    /// in `vocabularies::check`, empty values are fine.
+    pub fn check(policy: &Policy, tier: &str, annotation: &str, value: &str) -> Option<Self> {
+        match policy {
+            Policy::Pattern(pattern) if !value.is_empty() && !pattern.is_match(value) => {
+                Some(Self {
+                    tier: tier.to_owned(),
+                    annotation: annotation.to_owned(),
+                    value: value.to_owned(),
+                    pattern: pattern.as_str().to_owned(),
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policies() -> TierPolicies {
+        TierPolicies::new(vec![
+            PolicyRule::new("tier_id", "comments|notes@.*", "skip", None).unwrap(),
+            PolicyRule::new("linguistic_type", "code", "pattern", Some("[A-Z]{2}")).unwrap(),
+            PolicyRule::new("linguistic_type", "ort", "freeform", None).unwrap(),
+        ])
+    }
+
+    #[test]
+    fn test_policy() {
+        let policies = policies();
+        let label =
+            |tier, linguistic_type| policies.policy(tier, linguistic_type).map(Policy::label);
+        assert_eq!(label("comments", "ort"), Some("skip"));
+        assert_eq!(label("notes@Jana", "ort"), Some("skip"));
+        // whole IDs only
+        assert_eq!(label("my comments", "ort"), Some("freeform"));
+        assert_eq!(label("code@Jana", "code"), Some("pattern"));
+        assert_eq!(label("pos@Jana", "pos"), None);
+        assert_eq!(
+            TierPolicies::default()
+                .policy("comments", "ort")
+                .map(Policy::label),
+            None
+        );
+    }
+
+    #[test]
+    fn test_pattern_mismatch() {
+        let policies = policies();
+        let policy = policies.policy("code@Jana", "code").unwrap();
+        assert_eq!(policy.pattern(), Some("[A-Z]{2}"));
+        assert_eq!(
+            PatternMismatch::check(policy, "code@Jana", "a1", "SM"),
+            None
+        );
+        assert_eq!(PatternMismatch::check(policy, "code@Jana", "a1", ""), None);
+        assert_eq!(
+            PatternMismatch::check(policy, "code@Jana", "a1", "SMS"),
+            Some(PatternMismatch {
+                tier: "code@Jana".to_owned(),
+                annotation: "a1".to_owned(),
+                value: "SMS".to_owned(),
+                pattern: "[A-Z]{2}".to_owned(),
+            })
+        );
+        assert_eq!(
+            PatternMismatch::check(&Policy::Skip, "c", "a1", "SMS"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let error = |target, policy, pattern| {
+            PolicyRule::new(target, ".*", policy, pattern)
+                .unwrap_err()
+                .to_string()
+        };
+        assert!(error("participant", "skip", None).contains("\"participant\""));
+        assert!(error("tier_id", "ignore", None).contains("\"ignore\""));
+        assert_eq!(
+            error("tier_id", "pattern", None),
+            "the pattern policy needs a pattern"
+        );
+        assert_eq!(
+            error("tier_id", "skip", Some("x")),
+            "the skip policy doesn't take a pattern"
+        );
+        assert!(error("tier_id", "pattern", Some("[")).starts_with("invalid pattern \"[\""));
+        assert!(PolicyRule::new("tier_id", "(", "skip", None).is_err());
+    }
+}
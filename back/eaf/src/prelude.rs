@@ -0,0 +1,24 @@
+//! The types needed to read and validate transcripts, for glob importing:
+//!
+//! ```
+//! use eaf::prelude::*;
+//!
+//! let config = ParserConfig::from_args::<&str, &str, &str, &str>(&[], &[], &[], &[]);
+//! let parsed = Parser::parse(&config, tokenize("no (tak"));
+//! assert!(parsed.has_mistakes());
+//! ```
+
+pub use crate::annotations::{Annotation, ReadError};
+pub use crate::intervals::IntervalMistake;
+pub use crate::media::BeyondMedia;
+pub use crate::parser::{
+    Mistake, Node, Parsed, Parser, ParserConfig, SpanLabels, TokenFlags, WhitespaceKind,
+};
+pub use crate::policies::{Policy, TierPolicies};
+pub use crate::tiers::{TierMapping, TierPattern, TierSource};
+pub use crate::timeslots::SlotMistake;
+pub use crate::tokenizer::{
+    tokenize, tokenize_with, Delim, DelimKind, Token, TokenKind, Tokenized, TokenizerConfig,
+    WhitespacePolicy,
+};
+pub use crate::vocabularies::VocabMistake;
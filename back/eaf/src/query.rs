@@ -0,0 +1,639 @@
+//! A small query language for finding tokens in parsed segments by their
+//! form and by the spans they're part of, loosely modeled on CQL:
+//!
+//! ```text
+//! [word="no"] [word~"ta.*"] within angle(code="SM") not within round
+//! ```
+//!
+//! A query is a sequence of token patterns matching consecutive tokens,
+//! optionally followed by constraints on the spans *all* of the matched
+//! tokens must (or must not) be part of:
+//!
+//! ```text
+//! query     := pattern+ constraint*
+//! pattern   := "[" (condition ("&" condition)*)? "]"
+//! condition := "word" ("=" | "!=" | "~" | "!~") string
+//! constraint:= "not"? "within" span
+//! span      := ("round" | "square" | "angle" ("(" "code" "=" string ")")?)
+//! ```
+//!
+//! `=` compares the token with the string, `~` matches it against the
+//! string as a regex, which has to match the whole token. `[]` matches any
+//! token. An angle span's `code` is either a bare code, or the key of a
+//! key=value attribute, e.g. `lang` for any value, or `lang=EN` for just
+//! that one. Strings are double-quoted, with `\"` and `\\` as escapes.
+//!
+//! With the `serde` feature, queries can also be built from a structured
+//! form (see `QuerySpec`), e.g. when they're put together in a UI rather
+//! than typed. The query above is:
+//!
+//! ```json
+//! {
+//!   "tokens": [
+//!     [{"op": "eq", "value": "no"}],
+//!     [{"op": "matches", "value": "ta.*"}]
+//!   ],
+//!   "constraints": [
+//!     {"span": "angle", "code": "SM"},
+//!     {"span": "round", "negated": true}
+//!   ]
+//! }
+//! ```
+
+use std::fmt;
+
+use regex::Regex;
+#[cfg(feature = "serde")]
+use serde::Deserialize;
+
+use super::parser::{Parsed, TokenFlags};
+use super::tokenizer::DelimKind;
+
+#[derive(Debug, PartialEq)]
+pub struct QueryError {
+    pub message: String,
+    /// Byte offset into the query.
+    pub at: usize,
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at position {}", self.message, self.at)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Lexeme {
+    Punct(&'static str),
+    Ident(String),
+    Str(String),
+}
+
+impl fmt::Display for Lexeme {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Lexeme::Punct(p) => write!(f, "`{}`", p),
+            Lexeme::Ident(i) => write!(f, "`{}`", i),
+            Lexeme::Str(s) => write!(f, "{:?}", s),
+        }
+    }
+}
+
+fn error<T>(message: impl Into<String>, at: usize) -> Result<T, QueryError> {
+    Err(QueryError {
+        message: message.into(),
+        at,
+    })
+}
+
+fn lex(query: &str) -> Result<Vec<(Lexeme, usize)>, QueryError> {
+    const PUNCT: &[&str] = &["!=", "!~", "[", "]", "(", ")", "&", "=", "~"];
+    let mut lexemes = vec![];
+    let mut chars = query.char_indices().peekable();
+    while let Some(&(at, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == '"' {
+            chars.next();
+            let mut string = String::new();
+            loop {
+                match chars.next() {
+                    Some((_, '"')) => break,
+                    Some((_, '\\')) => match chars.next() {
+                        Some((_, c)) => string.push(c),
+                        None => return error("unterminated string", at),
+                    },
+                    Some((_, c)) => string.push(c),
+                    None => return error("unterminated string", at),
+                }
+            }
+            lexemes.push((Lexeme::Str(string), at));
+        } else if c.is_alphabetic() || c == '_' {
+            let mut ident = String::new();
+            while let Some(&(_, c)) = chars.peek() {
+                if !(c.is_alphanumeric() || c == '_') {
+                    break;
+                }
+                ident.push(c);
+                chars.next();
+            }
+            lexemes.push((Lexeme::Ident(ident), at));
+        } else if let Some(punct) = PUNCT.iter().find(|p| query[at..].starts_with(*p)) {
+            for _ in 0..punct.chars().count() {
+                chars.next();
+            }
+            lexemes.push((Lexeme::Punct(punct), at));
+        } else {
+            return error(format!("unexpected character {:?}", c), at);
+        }
+    }
+    Ok(lexemes)
+}
+
+#[derive(Debug)]
+enum Condition {
+    Eq(String),
+    Ne(String),
+    Matches(Regex),
+    NotMatches(Regex),
+}
+
+impl Condition {
+    fn test(&self, word: &str) -> bool {
+        match self {
+            Condition::Eq(s) => word == s,
+            Condition::Ne(s) => word != s,
+            Condition::Matches(re) => re.is_match(word),
+            Condition::NotMatches(re) => !re.is_match(word),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct SpanPattern {
+    kind: DelimKind,
+    /// Only for angle spans.
+    code: Option<String>,
+}
+
+#[derive(Debug)]
+struct Constraint {
+    negated: bool,
+    span: SpanPattern,
+}
+
+fn within(flags: &TokenFlags, span: &SpanPattern) -> bool {
+    match (span.kind, &flags.attrs, &span.code) {
+        (DelimKind::Round, _, _) => flags.uncertain,
+        (DelimKind::Square, _, _) => flags.overlap,
+        (DelimKind::Angle, Some(attrs), Some(code)) => attrs
+            .iter()
+            .any(|attr| attr.key == *code || attr.to_string() == *code),
+        (DelimKind::Angle, attrs, _) => attrs.is_some(),
+    }
+}
+
+#[derive(Debug)]
+pub struct Query {
+    patterns: Vec<Vec<Condition>>,
+    constraints: Vec<Constraint>,
+}
+
+/// A match of a query, as indices of the first and last token matched, and
+/// the byte span they cover in the segment's source.
+#[derive(Debug, PartialEq)]
+pub struct Match {
+    pub first: usize,
+    pub last: usize,
+    pub start: usize,
+    pub end: usize,
+}
+
+/// A token condition in the structured form of a query, on the token's
+/// form like `word=`, `word!=`, `word~` and `word!~` respectively.
+#[cfg(feature = "serde")]
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "op", content = "value", rename_all = "snake_case")]
+pub enum ConditionSpec {
+    Eq(String),
+    Ne(String),
+    Matches(String),
+    NotMatches(String),
+}
+
+#[cfg(feature = "serde")]
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ConstraintSpec {
+    pub span: DelimKind,
+    /// Only for angle spans.
+    #[serde(default)]
+    pub code: Option<String>,
+    /// `not within` rather than `within`.
+    #[serde(default)]
+    pub negated: bool,
+}
+
+/// The structured form of a query: the conditions of each token pattern
+/// and the span constraints.
+#[cfg(feature = "serde")]
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct QuerySpec {
+    pub tokens: Vec<Vec<ConditionSpec>>,
+    #[serde(default)]
+    pub constraints: Vec<ConstraintSpec>,
+}
+
+/// What's wrong with a structured query, and where, as a path like
+/// `tokens[1][0]`.
+#[cfg(feature = "serde")]
+#[derive(Debug, PartialEq)]
+pub struct SpecError {
+    pub message: String,
+    pub path: String,
+}
+
+#[cfg(feature = "serde")]
+impl fmt::Display for SpecError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at {}", self.message, self.path)
+    }
+}
+
+struct QueryParser {
+    lexemes: Vec<(Lexeme, usize)>,
+    current: usize,
+    /// Position of the end of the query, for errors about missing input.
+    end: usize,
+}
+
+impl QueryParser {
+    fn peek(&self) -> Option<&Lexeme> {
+        self.lexemes.get(self.current).map(|(l, _)| l)
+    }
+
+    fn position(&self) -> usize {
+        self.lexemes
+            .get(self.current)
+            .map(|(_, at)| *at)
+            .unwrap_or(self.end)
+    }
+
+    fn next(&mut self, expected: &str) -> Result<(Lexeme, usize), QueryError> {
+        match self.lexemes.get(self.current) {
+            Some(lexeme) => {
+                self.current += 1;
+                Ok(lexeme.clone())
+            }
+            None => error(
+                format!("expected {}, found end of query", expected),
+                self.end,
+            ),
+        }
+    }
+
+    fn expect_punct(&mut self, punct: &'static str) -> Result<(), QueryError> {
+        match self.next(&format!("`{}`", punct))? {
+            (Lexeme::Punct(p), _) if p == punct => Ok(()),
+            (other, at) => error(format!("expected `{}`, found {}", punct, other), at),
+        }
+    }
+
+    fn expect_ident(&mut self, ident: &str) -> Result<(), QueryError> {
+        match self.next(&format!("`{}`", ident))? {
+            (Lexeme::Ident(i), _) if i == ident => Ok(()),
+            (other, at) => error(format!("expected `{}`, found {}", ident, other), at),
+        }
+    }
+
+    fn string(&mut self) -> Result<(String, usize), QueryError> {
+        match self.next("a string")? {
+            (Lexeme::Str(s), at) => Ok((s, at)),
+            (other, at) => error(format!("expected a string, found {}", other), at),
+        }
+    }
+
+    fn condition(&mut self) -> Result<Condition, QueryError> {
+        self.expect_ident("word")?;
+        let op = match self.next("an operator")? {
+            (Lexeme::Punct(op @ "="), _)
+            | (Lexeme::Punct(op @ "!="), _)
+            | (Lexeme::Punct(op @ "~"), _)
+            | (Lexeme::Punct(op @ "!~"), _) => op,
+            (other, at) => return error(format!("expected an operator, found {}", other), at),
+        };
+        let (string, at) = self.string()?;
+        let regex = || match Regex::new(&format!("^(?:{})$", string)) {
+            Ok(re) => Ok(re),
+            Err(e) => error(format!("bad regex: {}", e), at),
+        };
+        Ok(match op {
+            "=" => Condition::Eq(string),
+            "!=" => Condition::Ne(string),
+            "~" => Condition::Matches(regex()?),
+            _ => Condition::NotMatches(regex()?),
+        })
+    }
+
+    fn pattern(&mut self) -> Result<Vec<Condition>, QueryError> {
+        self.expect_punct("[")?;
+        let mut conditions = vec![];
+        if self.peek() == Some(&Lexeme::Punct("]")) {
+            self.current += 1;
+            return Ok(conditions);
+        }
+        loop {
+            conditions.push(self.condition()?);
+            match self.next("`&` or `]`")? {
+                (Lexeme::Punct("&"), _) => {}
+                (Lexeme::Punct("]"), _) => return Ok(conditions),
+                (other, at) => return error(format!("expected `&` or `]`, found {}", other), at),
+            }
+        }
+    }
+
+    fn span(&mut self) -> Result<SpanPattern, QueryError> {
+        let (kind, at) = match self.next("a span kind")? {
+            (Lexeme::Ident(kind), at) => (kind, at),
+            (other, at) => return error(format!("expected a span kind, found {}", other), at),
+        };
+        let kind = match kind.as_str() {
+            "round" => DelimKind::Round,
+            "square" => DelimKind::Square,
+            "angle" => DelimKind::Angle,
+            _ => {
+                return error(
+                    format!(
+                        "unknown span kind `{}`, expected round, square or angle",
+                        kind
+                    ),
+                    at,
+                )
+            }
+        };
+        let mut code = None;
+        if self.peek() == Some(&Lexeme::Punct("(")) {
+            if kind != DelimKind::Angle {
+                return error("only angle spans have codes", self.position());
+            }
+            self.current += 1;
+            self.expect_ident("code")?;
+            self.expect_punct("=")?;
+            code = Some(self.string()?.0);
+            self.expect_punct(")")?;
+        }
+        Ok(SpanPattern { kind, code })
+    }
+
+    fn query(&mut self) -> Result<Query, QueryError> {
+        let mut patterns = vec![];
+        while self.peek() == Some(&Lexeme::Punct("[")) {
+            patterns.push(self.pattern()?);
+        }
+        if patterns.is_empty() {
+            return error(
+                "expected a token pattern, e.g. [word=\"ahoj\"]",
+                self.position(),
+            );
+        }
+        let mut constraints = vec![];
+        while let Some((lexeme, at)) = self.lexemes.get(self.current).cloned() {
+            let negated = match lexeme {
+                Lexeme::Ident(ref i) if i == "within" => false,
+                Lexeme::Ident(ref i) if i == "not" => {
+                    self.current += 1;
+                    true
+                }
+                other => return error(format!("expected `within`, found {}", other), at),
+            };
+            self.expect_ident("within")?;
+            constraints.push(Constraint {
+                negated,
+                span: self.span()?,
+            });
+        }
+        Ok(Query {
+            patterns,
+            constraints,
+        })
+    }
+}
+
+impl Query {
+    pub fn parse(query: &str) -> Result<Self, QueryError> {
+        QueryParser {
+            lexemes: lex(query)?,
+            current: 0,
+            end: query.len(),
+        }
+        .query()
+    }
+
+    /// Build a query from its structured form, checking it the same way as
+    /// `parse` does.
+    #[cfg(feature = "serde")]
+    pub fn from_spec(spec: &QuerySpec) -> Result<Self, SpecError> {
+        let error = |message: String, path: String| SpecError { message, path };
+        if spec.tokens.is_empty() {
+            return Err(error(
+                "expected a token pattern".to_owned(),
+                "tokens".to_owned(),
+            ));
+        }
+        let mut patterns = vec![];
+        for (i, conditions) in spec.tokens.iter().enumerate() {
+            let mut pattern = vec![];
+            for (j, condition) in conditions.iter().enumerate() {
+                let regex = |re: &str| match Regex::new(&format!("^(?:{})$", re)) {
+                    Ok(re) => Ok(re),
+                    Err(e) => Err(error(
+                        format!("bad regex: {}", e),
+                        format!("tokens[{}][{}]", i, j),
+                    )),
+                };
+                pattern.push(match condition {
+                    ConditionSpec::Eq(s) => Condition::Eq(s.clone()),
+                    ConditionSpec::Ne(s) => Condition::Ne(s.clone()),
+                    ConditionSpec::Matches(re) => Condition::Matches(regex(re)?),
+                    ConditionSpec::NotMatches(re) => Condition::NotMatches(regex(re)?),
+                });
+            }
+            patterns.push(pattern);
+        }
+        let mut constraints = vec![];
+        for (i, constraint) in spec.constraints.iter().enumerate() {
+            if constraint.code.is_some() && constraint.span != DelimKind::Angle {
+                return Err(error(
+                    "only angle spans have codes".to_owned(),
+                    format!("constraints[{}].code", i),
+                ));
+            }
+            constraints.push(Constraint {
+                negated: constraint.negated,
+                span: SpanPattern {
+                    kind: constraint.span,
+                    code: constraint.code.clone(),
+                },
+            });
+        }
+        Ok(Query {
+            patterns,
+            constraints,
+        })
+    }
+
+    fn satisfies(&self, flags: &TokenFlags) -> bool {
+        self.constraints
+            .iter()
+            .all(|c| within(flags, &c.span) != c.negated)
+    }
+
+    /// All matches of the query in the segment, including overlapping ones,
+    /// in order of their first token.
+    pub fn find(&self, parsed: &Parsed) -> Vec<Match> {
+        let tokens = parsed.flagged_tokens();
+
+        let n = self.patterns.len();
+        let mut matches = vec![];
+        for first in 0..(tokens.len() + 1).saturating_sub(n) {
+            let window = &tokens[first..first + n];
+            let matched = window
+                .iter()
+                .zip(&self.patterns)
+                .all(|((token, flags), conditions)| {
+                    let word = &parsed.source[token.start..token.end];
+                    conditions.iter().all(|c| c.test(word)) && self.satisfies(flags)
+                });
+            if matched {
+                matches.push(Match {
+                    first,
+                    last: first + n - 1,
+                    start: window[0].0.start,
+                    end: window[n - 1].0.end,
+                });
+            }
+        }
+        matches
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{Parser, ParserConfig};
+    use crate::tokenizer;
+
+    fn find(query: &str, segment: &str) -> Vec<String> {
+        let config = ParserConfig::from_args::<&str, &str, &str, _>(&[], &[], &[], &["SM", "SJ"])
+            .with_attr_values(&[("lang", "CS|EN")]);
+        let parsed = Parser::parse(&config, tokenizer::tokenize(segment));
+        Query::parse(query)
+            .unwrap()
+            .find(&parsed)
+            .into_iter()
+            .map(|m| parsed.source[m.start..m.end].to_owned())
+            .collect()
+    }
+
+    #[test]
+    fn test_words() {
+        assert_eq!(find(r#"[word="tak"]"#, "no tak jo tak"), vec!["tak", "tak"]);
+        assert_eq!(find(r#"[word~"t.*"]"#, "no tak jo to"), vec!["tak", "to"]);
+        assert_eq!(find(r#"[word~"a"]"#, "no tak"), Vec::<String>::new());
+        assert_eq!(
+            find(r#"[word!="no" & word!~"j."]"#, "no tak jo"),
+            vec!["tak"]
+        );
+        assert_eq!(
+            find(r#"[word="no"] []"#, "no tak no jo"),
+            vec!["no tak", "no jo"]
+        );
+    }
+
+    #[test]
+    fn test_spans() {
+        let segment = "no <SM tak (jo)> [no] <SJ tak>";
+        assert_eq!(
+            find(r#"[] within angle"#, segment),
+            vec!["tak", "jo", "tak"]
+        );
+        assert_eq!(
+            find(r#"[word="tak"] within angle(code="SM")"#, segment),
+            vec!["tak"]
+        );
+        assert_eq!(
+            find(r#"[] within angle not within round"#, segment),
+            vec!["tak", "tak"]
+        );
+        assert_eq!(find(r#"[] within square"#, segment), vec!["no"]);
+        // all tokens of a match must satisfy the constraints
+        assert_eq!(
+            find(r#"[] [] within angle(code="SM")"#, segment),
+            vec!["tak (jo"]
+        );
+
+        let segment = "<SM_lang=EN yes> <lang=CS no>";
+        assert_eq!(
+            find(r#"[] within angle(code="lang")"#, segment),
+            vec!["yes", "no"]
+        );
+        assert_eq!(
+            find(r#"[] within angle(code="lang=EN")"#, segment),
+            vec!["yes"]
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_spec() {
+        let config = ParserConfig::from_args::<&str, &str, &str, _>(&[], &[], &[], &["SM"]);
+        let find = |spec: serde_json::Value, segment| -> Result<Vec<String>, SpecError> {
+            let spec: QuerySpec = serde_json::from_value(spec).unwrap();
+            let parsed = Parser::parse(&config, tokenizer::tokenize(segment));
+            Ok(Query::from_spec(&spec)?
+                .find(&parsed)
+                .into_iter()
+                .map(|m| parsed.source[m.start..m.end].to_owned())
+                .collect())
+        };
+        let segment = "no <SM tak (5)> (jo 12)";
+        assert_eq!(
+            find(
+                serde_json::json!({
+                    "tokens": [[{"op": "matches", "value": "[0-9]+"}]],
+                    "constraints": [{"span": "round"}],
+                }),
+                segment
+            ),
+            Ok(vec!["5".to_owned(), "12".to_owned()])
+        );
+        assert_eq!(
+            find(
+                serde_json::json!({
+                    "tokens": [[{"op": "eq", "value": "tak"}], []],
+                    "constraints": [{"span": "angle", "code": "SM"}],
+                }),
+                segment
+            ),
+            Ok(vec!["tak (5".to_owned()])
+        );
+        assert_eq!(
+            find(
+                serde_json::json!({
+                    "tokens": [[]],
+                    "constraints": [{"span": "round", "negated": true}],
+                }),
+                segment
+            ),
+            Ok(vec!["no".to_owned(), "tak".to_owned()])
+        );
+        let path = |spec| find(spec, segment).unwrap_err().path;
+        assert_eq!(path(serde_json::json!({ "tokens": [] })), "tokens");
+        assert_eq!(
+            path(serde_json::json!({
+                "tokens": [[], [{"op": "eq", "value": "a"}, {"op": "matches", "value": "("}]],
+            })),
+            "tokens[1][1]"
+        );
+        assert_eq!(
+            path(serde_json::json!({
+                "tokens": [[]],
+                "constraints": [{"span": "round", "code": "SM"}],
+            })),
+            "constraints[0].code"
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let at = |query| Query::parse(query).unwrap_err().at;
+        assert_eq!(at(""), 0);
+        assert_eq!(at(r#"[word="no""#), 10);
+        assert_eq!(at(r#"[lemma="no"]"#), 1);
+        assert_eq!(at(r#"[word~"("]"#), 6);
+        assert_eq!(at(r#"[] within curly"#), 10);
+        assert_eq!(at(r#"[] within round(code="SM")"#), 15);
+        assert_eq!(at(r#"[] beside angle"#), 3);
+        assert_eq!(at(r#"[word="no]"#), 6);
+        assert_eq!(at("[word # ]"), 6);
+    }
+}
@@ -0,0 +1,98 @@
+//! Spelling suggestions for tokens flagged by the parser, based on Hunspell
+//! dictionaries.
+
+use std::{fmt, fs, io, path::Path};
+
+pub use spellbook::Dictionary;
+
+use super::parser::{Mistake, Parsed};
+
+#[derive(Debug)]
+pub enum DictionaryError {
+    Io(String, io::Error),
+    Parse(String, spellbook::ParseDictionaryError),
+}
+
+impl fmt::Display for DictionaryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DictionaryError::Io(name, e) => write!(f, "can't read dictionary {:?}: {}", name, e),
+            DictionaryError::Parse(name, e) => {
+                write!(f, "can't parse dictionary {:?}: {}", name, e)
+            }
+        }
+    }
+}
+
+/// Load a dictionary stored as `<name>.aff` and `<name>.dic` in `dir`.
+pub fn load(dir: &Path, name: &str) -> Result<Dictionary, DictionaryError> {
+    let read = |ext| {
+        fs::read_to_string(dir.join(format!("{}.{}", name, ext)))
+            .map_err(|e| DictionaryError::Io(name.to_owned(), e))
+    };
+    Dictionary::new(&read("aff")?, &read("dic")?)
+        .map_err(|e| DictionaryError::Parse(name.to_owned(), e))
+}
+
+/// Suggestions for a word from all dictionaries, in the order in which
+/// the dictionaries are given, without duplicates. Words known to any of
+/// the dictionaries get no suggestions.
+pub fn suggest(dictionaries: &[&Dictionary], word: &str, limit: usize) -> Vec<String> {
+    if dictionaries.iter().any(|d| d.check(word)) {
+        return vec![];
+    }
+    let mut suggestions = vec![];
+    let mut buffer = vec![];
+    for dictionary in dictionaries {
+        dictionary.suggest(word, &mut buffer);
+        for suggestion in buffer.drain(..) {
+            if !suggestions.contains(&suggestion) {
+                suggestions.push(suggestion);
+            }
+        }
+    }
+    suggestions.truncate(limit);
+    suggestions
+}
+
+/// The token affected by the mistake, if it's one where a spelling
+/// suggestion makes sense.
+pub fn misspelled<'p>(mistake: &Mistake, parsed: &'p Parsed) -> Option<&'p str> {
+    match mistake {
+        Mistake::BadToken { at } | Mistake::BadSubstr { at, .. } => parsed
+            .tokens
+            .get(*at)
+            .map(|token| &parsed.source[token.start..token.end]),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{Parser, ParserConfig};
+    use crate::tokenizer;
+
+    const AFF: &str = "SET UTF-8\nTRY aeiouyáéíóúůýěčřšžcdhklmnprstvz\n";
+    const DIC: &str = "3\npes\nkočka\nmyš\n";
+
+    #[test]
+    fn test_suggest() {
+        let dictionary = Dictionary::new(AFF, DIC).unwrap();
+        assert!(suggest(&[&dictionary], "pes", 5).is_empty());
+        assert!(suggest(&[&dictionary], "kočla", 5).contains(&"kočka".to_owned()));
+    }
+
+    #[test]
+    fn test_misspelled() {
+        let config =
+            ParserConfig::from_args(&[] as &[&str], &["kocka"], &["[a-zčš]"], &[] as &[&str]);
+        let parsed = Parser::parse(&config, tokenizer::tokenize("pes kocka my%"));
+        let words: Vec<_> = parsed
+            .mistakes
+            .iter()
+            .filter_map(|m| misspelled(m, &parsed))
+            .collect();
+        assert_eq!(words, vec!["kocka", "my%"]);
+    }
+}
@@ -0,0 +1,293 @@
+//! Descriptive statistics of transcripts, for the whole document and per
+//! speaker: type/token ratio, mean turn length, pause frequency and talk
+//! time.
+//!
+//! Tokens are what the parser considers words, i.e. everything but
+//! delimiters and attribute codes. Consecutive segments of the same speaker
+//! count as a single turn. A pause is a stretch of at least `MIN_PAUSE`
+//! milliseconds during which nobody speaks; it's attributed to a speaker if
+//! it falls within their turn.
+
+use std::collections::{BTreeMap, HashSet};
+
+use super::annotations::Annotation;
+use super::parser::{Node, Parsed, Parser, ParserConfig};
+use super::tiers::TierMapping;
+use super::tokenizer;
+
+/// Shortest silence counted as a pause, in milliseconds.
+pub const MIN_PAUSE: u32 = 250;
+
+/// Columns of `Measures::record`, preceded by the speaker (empty for the
+/// whole document).
+pub const CSV_HEADER: &[&str] = &[
+    "speaker",
+    "tokens",
+    "types",
+    "type_token_ratio",
+    "turns",
+    "mean_turn_length",
+    "talk_time_ms",
+    "pauses",
+    "pauses_per_minute",
+    "duration_ms",
+];
+
+#[derive(Debug)]
+pub struct Segment {
+    pub speaker: String,
+    /// In milliseconds, if the segment is aligned.
+    pub start: Option<u32>,
+    pub end: Option<u32>,
+    /// Whether the timing is interpolated, cf. `Annotation::interpolated`.
+    pub interpolated: bool,
+    pub parsed: Parsed,
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Measures {
+    pub tokens: usize,
+    pub types: usize,
+    pub turns: usize,
+    /// Total duration of the segments, in milliseconds.
+    pub talk_time: u64,
+    pub pauses: usize,
+    /// Time covered, in milliseconds: from the start of the first segment
+    /// to the end of the last one for the whole document, the talk time
+    /// for speakers.
+    pub duration: u64,
+}
+
+fn ratio(numerator: f64, denominator: f64) -> Option<f64> {
+    if denominator > 0.0 {
+        Some(numerator / denominator)
+    } else {
+        None
+    }
+}
+
+impl Measures {
+    pub fn type_token_ratio(&self) -> Option<f64> {
+        ratio(self.types as f64, self.tokens as f64)
+    }
+
+    /// In tokens.
+    pub fn mean_turn_length(&self) -> Option<f64> {
+        ratio(self.tokens as f64, self.turns as f64)
+    }
+
+    pub fn pauses_per_minute(&self) -> Option<f64> {
+        ratio(self.pauses as f64, self.duration as f64 / 60_000.0)
+    }
+
+    /// A CSV record, cf. `CSV_HEADER`.
+    pub fn record(&self, speaker: &str) -> Vec<String> {
+        let optional = |x: Option<f64>| x.map_or_else(String::new, |x| format!("{:.4}", x));
+        vec![
+            speaker.to_owned(),
+            self.tokens.to_string(),
+            self.types.to_string(),
+            optional(self.type_token_ratio()),
+            self.turns.to_string(),
+            optional(self.mean_turn_length()),
+            self.talk_time.to_string(),
+            self.pauses.to_string(),
+            optional(self.pauses_per_minute()),
+            self.duration.to_string(),
+        ]
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Stats {
+    pub total: Measures,
+    /// By speaker, sorted.
+    pub speakers: Vec<(String, Measures)>,
+}
+
+impl Stats {
+    /// CSV records for the whole document and each speaker, cf.
+    /// `CSV_HEADER`.
+    pub fn records(&self) -> Vec<Vec<String>> {
+        std::iter::once(self.total.record(""))
+            .chain(self.speakers.iter().map(|(s, m)| m.record(s)))
+            .collect()
+    }
+}
+
+/// Transcript segments of the annotations, attributed to speakers according
+/// to the mapping, or failing that, to the tier's participant or the tier
+/// itself. Segments are parsed with no restrictions on tokens, so that
+/// statistics don't depend on the project's rules.
+pub fn segments(annotations: Vec<Annotation>, mapping: &TierMapping) -> Vec<Segment> {
+    let config = ParserConfig::from_args::<&str, &str, &str, &str>(&[], &[], &[], &[]);
+    annotations
+        .into_iter()
+        .filter(|a| a.is_transcript(mapping))
+        .map(|a| {
+            let participant = match a.participant.as_deref() {
+                Some("") => None,
+                participant => participant,
+            };
+            let speaker = mapping
+                .nickname(&a.tier, a.participant.as_deref())
+                .or(participant)
+                .unwrap_or(&a.tier)
+                .to_owned();
+            Segment {
+                speaker,
+                start: a.start,
+                end: a.end,
+                interpolated: a.interpolated,
+                parsed: Parser::parse(&config, tokenizer::tokenize(&a.value)),
+            }
+        })
+        .collect()
+}
+
+/// Words of each speaker, i.e. tokens in their segments, sorted by speaker.
+pub fn word_counts(segments: &[Segment]) -> BTreeMap<&str, usize> {
+    let mut counts = BTreeMap::new();
+    for segment in segments {
+        let words = segment
+            .parsed
+            .nodes
+            .iter()
+            .filter(|node| matches!(node, Node::Token(_)))
+            .count();
+        *counts.entry(segment.speaker.as_str()).or_default() += words;
+    }
+    counts
+}
+
+#[derive(Default)]
+struct Accumulator {
+    measures: Measures,
+    types: HashSet<String>,
+}
+
+pub fn compute(segments: &[Segment]) -> Stats {
+    let mut order: Vec<_> = segments.iter().collect();
+    // unaligned segments stay in document order
+    if segments.iter().all(|s| s.start.is_some()) {
+        order.sort_by_key(|s| (s.start, s.end));
+    }
+
+    let mut total = Accumulator::default();
+    let mut speakers: BTreeMap<&str, Accumulator> = BTreeMap::new();
+    let mut previous: Option<&str> = None;
+    let mut first_start = None;
+    let mut last_end: Option<u32> = None;
+    for segment in order {
+        let speaker = speakers.entry(&segment.speaker).or_default();
+        let new_turn = previous != Some(segment.speaker.as_str());
+        previous = Some(&segment.speaker);
+        for acc in &mut [&mut total, &mut *speaker] {
+            if new_turn {
+                acc.measures.turns += 1;
+            }
+            for (token, _) in segment.parsed.flagged_tokens() {
+                acc.measures.tokens += 1;
+                acc.types
+                    .insert(segment.parsed.source[token.start..token.end].to_lowercase());
+            }
+        }
+
+        if let (Some(start), Some(end)) = (segment.start, segment.end) {
+            let talk_time = u64::from(end.saturating_sub(start));
+            total.measures.talk_time += talk_time;
+            speaker.measures.talk_time += talk_time;
+            if last_end.map_or(false, |last| start >= last.saturating_add(MIN_PAUSE)) {
+                total.measures.pauses += 1;
+                if !new_turn {
+                    speaker.measures.pauses += 1;
+                }
+            }
+            first_start = first_start.or(Some(start));
+            last_end = Some(last_end.map_or(end, |last| last.max(end)));
+        }
+    }
+
+    let finish = |acc: Accumulator| Measures {
+        types: acc.types.len(),
+        ..acc.measures
+    };
+    let mut total = finish(total);
+    if let (Some(start), Some(end)) = (first_start, last_end) {
+        total.duration = u64::from(end.saturating_sub(start));
+    }
+    let speakers = speakers
+        .into_iter()
+        .map(|(speaker, acc)| {
+            let mut measures = finish(acc);
+            measures.duration = measures.talk_time;
+            (speaker.to_owned(), measures)
+        })
+        .collect();
+    Stats { total, speakers }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn segment(speaker: &str, start: u32, end: u32, text: &str) -> Segment {
+        let config = ParserConfig::from_args::<&str, &str, &str, &str>(&[], &[], &[], &[]);
+        Segment {
+            speaker: speaker.to_owned(),
+            start: Some(start),
+            end: Some(end),
+            interpolated: false,
+            parsed: Parser::parse(&config, tokenizer::tokenize(text)),
+        }
+    }
+
+    #[test]
+    fn test_compute() {
+        let stats = compute(&[
+            segment("B", 1_000, 2_000, "no jo"),
+            segment("A", 0, 1_000, "no tak (no) <SM tak>"),
+            segment("A", 2_500, 3_000, "tak"),
+            segment("A", 4_000, 6_000, "Tak jo"),
+        ]);
+        assert_eq!(
+            stats.total,
+            Measures {
+                tokens: 9,
+                types: 3,
+                turns: 3,
+                talk_time: 4_500,
+                pauses: 2,
+                duration: 6_000,
+            }
+        );
+        assert_eq!(stats.total.mean_turn_length(), Some(3.0));
+        assert_eq!(stats.total.pauses_per_minute(), Some(20.0));
+        let (name, a) = &stats.speakers[0];
+        assert_eq!(name, "A");
+        assert_eq!((a.tokens, a.types, a.turns, a.pauses), (7, 3, 2, 1));
+        assert_eq!(a.duration, 3_500);
+        assert_eq!(
+            stats.speakers[1].1.record("B"),
+            vec!["B", "2", "2", "1.0000", "1", "2.0000", "1000", "0", "0.0000", "1000"]
+        );
+    }
+
+    #[test]
+    fn test_word_counts() {
+        let segments = [
+            segment("B", 1_000, 2_000, "no jo"),
+            segment("A", 0, 1_000, "no tak (no) <SM tak>"),
+            segment("A", 2_500, 3_000, ""),
+        ];
+        let counts: Vec<_> = word_counts(&segments).into_iter().collect();
+        assert_eq!(counts, [("A", 4), ("B", 2)]);
+    }
+
+    #[test]
+    fn test_empty() {
+        let stats = compute(&[]);
+        assert_eq!(stats.total, Measures::default());
+        assert_eq!(stats.total.type_token_ratio(), None);
+    }
+}
@@ -0,0 +1,459 @@
+//! A single pass over an EAF with a streaming XML reader, collecting the
+//! header with its media descriptors, time order, linguistic types,
+//! controlled vocabularies and tiers with their annotations.
+//! Unlike a DOM, this doesn't keep the whole document around, which for
+//! recordings hours long takes several times the memory of the file itself.
+//! `annotations`, `timeslots` and `document` read EAFs this way; rewriting
+//! them still needs a DOM.
+
+use std::io::BufRead;
+
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+
+use super::annotations::ReadError;
+use super::document::{
+    Alignment, ControlledVocabulary, CvEntry, LinguisticType, MediaDescriptor, TimeSlot,
+};
+use super::header::{Header, License};
+
+#[derive(Debug)]
+pub(crate) struct RawAnnotation {
+    pub id: String,
+    pub alignment: Alignment,
+    /// Whether the value refers to an entry in a controlled vocabulary.
+    pub cve_ref: bool,
+    /// Empty if there's no ANNOTATION_VALUE.
+    pub value: String,
+}
+
+#[derive(Debug)]
+pub(crate) struct RawTier {
+    pub id: String,
+    pub linguistic_type: String,
+    pub participant: Option<String>,
+    pub annotator: Option<String>,
+    pub parent: Option<String>,
+    /// In document order.
+    pub annotations: Vec<RawAnnotation>,
+}
+
+/// The parts of an EAF the crate works with, as they are in the file.
+#[derive(Debug, Default)]
+pub(crate) struct Skeleton {
+    pub header: Header,
+    pub media: Vec<MediaDescriptor>,
+    pub time_slots: Vec<TimeSlot>,
+    pub linguistic_types: Vec<LinguisticType>,
+    pub controlled_vocabularies: Vec<ControlledVocabulary>,
+    pub tiers: Vec<RawTier>,
+}
+
+/// Where the reader is; anything else is skipped.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Context {
+    Root,
+    Header,
+    Media,
+    Property,
+    License,
+    TimeOrder,
+    TimeSlot,
+    LinguisticType,
+    Vocabulary,
+    /// CV_ENTRY_ML, with a CVE_VALUE per language.
+    Entry,
+    EntryValue,
+    /// CV_ENTRY of EAF 2.x, a single value as its text.
+    LegacyEntry,
+    Tier,
+    Annotation,
+    Inner,
+    Value,
+    Other,
+}
+
+impl Context {
+    /// The context of an element called `name` within this one.
+    fn child(self, name: &[u8]) -> Context {
+        match (self, name) {
+            (Context::Root, b"HEADER") => Context::Header,
+            (Context::Root, b"LICENSE") => Context::License,
+            (Context::Root, b"TIME_ORDER") => Context::TimeOrder,
+            (Context::Root, b"LINGUISTIC_TYPE") => Context::LinguisticType,
+            (Context::Root, b"CONTROLLED_VOCABULARY") => Context::Vocabulary,
+            (Context::Root, b"TIER") => Context::Tier,
+            (Context::Header, b"MEDIA_DESCRIPTOR") => Context::Media,
+            (Context::Header, b"PROPERTY") => Context::Property,
+            (Context::TimeOrder, b"TIME_SLOT") => Context::TimeSlot,
+            (Context::Vocabulary, b"CV_ENTRY_ML") => Context::Entry,
+            (Context::Vocabulary, b"CV_ENTRY") => Context::LegacyEntry,
+            (Context::Entry, b"CVE_VALUE") => Context::EntryValue,
+            (Context::Tier, b"ANNOTATION") => Context::Annotation,
+            (Context::Annotation, _) => Context::Inner,
+            (Context::Inner, b"ANNOTATION_VALUE") => Context::Value,
+            _ => Context::Other,
+        }
+    }
+
+    /// Whether text directly in the element is collected.
+    fn has_text(self) -> bool {
+        matches!(
+            self,
+            Context::Property
+                | Context::License
+                | Context::EntryValue
+                | Context::LegacyEntry
+                | Context::Value
+        )
+    }
+}
+
+/// Unescaped attribute values by local name.
+struct Attrs(Vec<(Vec<u8>, String)>);
+
+impl Attrs {
+    fn read<R>(element: &BytesStart, reader: &Reader<R>) -> Result<Self, ReadError> {
+        let mut attrs = vec![];
+        for attr in element.attributes() {
+            let attr = attr.map_err(|e| ReadError(e.to_string()))?;
+            let value = attr
+                .decode_and_unescape_value(reader.decoder())
+                .map_err(|e| ReadError(e.to_string()))?;
+            attrs.push((attr.key.local_name().as_ref().to_vec(), value.into_owned()));
+        }
+        Ok(Attrs(attrs))
+    }
+
+    fn get(&self, name: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|(n, _)| n == name.as_bytes())
+            .map(|(_, v)| v.as_str())
+    }
+
+    fn owned(&self, name: &str) -> Option<String> {
+        self.get(name).map(str::to_owned)
+    }
+
+    fn or_default(&self, name: &str) -> String {
+        self.owned(name).unwrap_or_default()
+    }
+}
+
+fn xml_error<R>(reader: &Reader<R>, error: quick_xml::Error) -> ReadError {
+    ReadError(format!("{} at byte {}", error, reader.error_position()))
+}
+
+pub(crate) fn read<R: BufRead>(input: R) -> Result<Skeleton, ReadError> {
+    let mut reader = Reader::from_reader(input);
+    reader.config_mut().expand_empty_elements = true;
+    let mut skeleton = Skeleton::default();
+    let mut licenses = vec![];
+    let mut stack: Vec<Context> = vec![];
+    // of the innermost element, if it's collected
+    let mut text = String::new();
+    // name of the current header property, and whether the annotation
+    // already has its value
+    let mut property = String::new();
+    let mut has_value = false;
+    let mut seen_root = false;
+    let mut buf = vec![];
+    loop {
+        let event = reader
+            .read_event_into(&mut buf)
+            .map_err(|e| xml_error(&reader, e))?;
+        match event {
+            Event::Start(element) => {
+                let name = element.local_name();
+                let context = match stack.last() {
+                    None if seen_root => {
+                        return Err(ReadError("multiple root elements".to_owned()))
+                    }
+                    None if name.as_ref() == b"ANNOTATION_DOCUMENT" => Context::Root,
+                    None => return Err(ReadError("missing ANNOTATION_DOCUMENT".to_owned())),
+                    Some(parent) => parent.child(name.as_ref()),
+                };
+                let attrs = || Attrs::read(&element, &reader);
+                match (context, name.as_ref()) {
+                    (Context::Root, _) => {
+                        seen_root = true;
+                        let attrs = attrs()?;
+                        skeleton.header.set_author(attrs.or_default("AUTHOR"));
+                        skeleton.header.set_date(attrs.or_default("DATE"));
+                    }
+                    (Context::License, _) => {
+                        licenses.push(License {
+                            url: attrs()?.owned("LICENSE_URL"),
+                            text: String::new(),
+                        });
+                    }
+                    (Context::Media, _) => {
+                        let attrs = attrs()?;
+                        skeleton.media.push(MediaDescriptor {
+                            url: attrs.or_default("MEDIA_URL"),
+                            mime_type: attrs.owned("MIME_TYPE"),
+                            relative_url: attrs.owned("RELATIVE_MEDIA_URL"),
+                            time_origin: attrs.get("TIME_ORIGIN").and_then(|v| v.parse().ok()),
+                        });
+                    }
+                    (Context::Property, _) => property = attrs()?.or_default("NAME"),
+                    (Context::TimeSlot, _) => {
+                        let attrs = attrs()?;
+                        skeleton.time_slots.push(TimeSlot {
+                            id: attrs.or_default("TIME_SLOT_ID"),
+                            value: attrs.get("TIME_VALUE").and_then(|v| v.parse().ok()),
+                        });
+                    }
+                    (Context::LinguisticType, _) => {
+                        let attrs = attrs()?;
+                        skeleton.linguistic_types.push(LinguisticType {
+                            id: attrs.or_default("LINGUISTIC_TYPE_ID"),
+                            // ELAN's default
+                            time_alignable: attrs.get("TIME_ALIGNABLE") != Some("false"),
+                            constraint: attrs.owned("CONSTRAINTS"),
+                            controlled_vocabulary: attrs.owned("CONTROLLED_VOCABULARY_REF"),
+                        });
+                    }
+                    (Context::Vocabulary, _) => {
+                        let attrs = attrs()?;
+                        skeleton.controlled_vocabularies.push(ControlledVocabulary {
+                            id: attrs.or_default("CV_ID"),
+                            external: attrs.owned("EXT_REF"),
+                            entries: vec![],
+                        });
+                    }
+                    (Context::Entry, _) | (Context::LegacyEntry, _) => {
+                        // the stack says we're in a vocabulary
+                        let vocabulary = skeleton.controlled_vocabularies.last_mut().unwrap();
+                        vocabulary.entries.push(CvEntry {
+                            id: attrs()?.owned("CVE_ID"),
+                            values: vec![],
+                        });
+                    }
+                    (Context::Tier, _) => {
+                        let attrs = attrs()?;
+                        skeleton.tiers.push(RawTier {
+                            id: attrs.or_default("TIER_ID"),
+                            linguistic_type: attrs.or_default("LINGUISTIC_TYPE_REF"),
+                            participant: attrs.owned("PARTICIPANT"),
+                            annotator: attrs.owned("ANNOTATOR"),
+                            parent: attrs.owned("PARENT_REF"),
+                            annotations: vec![],
+                        });
+                    }
+                    (Context::Inner, kind) => {
+                        let attrs = attrs()?;
+                        let alignment = match kind {
+                            b"ALIGNABLE_ANNOTATION" => Alignment::Alignable {
+                                start_slot: attrs.or_default("TIME_SLOT_REF1"),
+                                end_slot: attrs.or_default("TIME_SLOT_REF2"),
+                            },
+                            b"REF_ANNOTATION" => Alignment::Ref {
+                                parent: attrs.or_default("ANNOTATION_REF"),
+                                previous: attrs.owned("PREVIOUS_ANNOTATION"),
+                            },
+                            other => {
+                                return Err(ReadError(format!(
+                                    "unknown annotation type {}",
+                                    String::from_utf8_lossy(other)
+                                )))
+                            }
+                        };
+                        has_value = false;
+                        // the stack says we're in a tier
+                        let tier = skeleton.tiers.last_mut().unwrap();
+                        tier.annotations.push(RawAnnotation {
+                            id: attrs.or_default("ANNOTATION_ID"),
+                            alignment,
+                            cve_ref: attrs.get("CVE_REF").is_some(),
+                            value: String::new(),
+                        });
+                    }
+                    _ => {}
+                }
+                // only the first value counts
+                let context = if context == Context::Value && has_value {
+                    Context::Other
+                } else {
+                    context
+                };
+                if context.has_text() {
+                    text.clear();
+                }
+                stack.push(context);
+            }
+            Event::End(_) => {
+                match stack.pop() {
+                    Some(Context::License) => {
+                        if let Some(license) = licenses.last_mut() {
+                            license.text = text.clone();
+                        }
+                    }
+                    // ELAN keeps the first of duplicate properties
+                    Some(Context::Property) if skeleton.header.property(&property).is_none() => {
+                        skeleton.header.set_property(&*property, &*text);
+                    }
+                    Some(Context::EntryValue) | Some(Context::LegacyEntry) => {
+                        let vocabulary = skeleton.controlled_vocabularies.last_mut().unwrap();
+                        let entry = vocabulary.entries.last_mut().unwrap();
+                        entry.values.push(std::mem::take(&mut text));
+                    }
+                    Some(Context::Value) => {
+                        has_value = true;
+                        let tier = skeleton.tiers.last_mut().unwrap();
+                        let annotation = tier.annotations.last_mut().unwrap();
+                        annotation.value = std::mem::take(&mut text);
+                    }
+                    _ => {}
+                }
+            }
+            Event::Text(t) if stack.last().map_or(false, |c| c.has_text()) => {
+                text.push_str(&t.unescape().map_err(|e| xml_error(&reader, e))?);
+            }
+            Event::CData(t) if stack.last().map_or(false, |c| c.has_text()) => {
+                text.push_str(
+                    &reader
+                        .decoder()
+                        .decode(&t)
+                        .map_err(|e| xml_error(&reader, e))?,
+                );
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+    if !seen_root {
+        return Err(ReadError("missing ANNOTATION_DOCUMENT".to_owned()));
+    }
+    if !stack.is_empty() {
+        return Err(ReadError("unexpected end of document".to_owned()));
+    }
+    skeleton.header.set_licenses(licenses);
+    Ok(skeleton)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eaf(body: &str) -> String {
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<ANNOTATION_DOCUMENT xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance" AUTHOR="Jana &amp; Petr" DATE="2020-01-01T00:00:00+01:00" FORMAT="3.0" VERSION="3.0">
+    <HEADER MEDIA_FILE="" TIME_UNITS="milliseconds">
+        <MEDIA_DESCRIPTOR MEDIA_URL="file:///rec.wav" MIME_TYPE="audio/x-wav" TIME_ORIGIN="1500"/>
+        <MEDIA_DESCRIPTOR MEDIA_URL="file:///rec.mp4" RELATIVE_MEDIA_URL="./rec.mp4"/>
+        <PROPERTY NAME="lastUsedAnnotationId">2</PROPERTY>
+        <PROPERTY NAME="lastUsedAnnotationId">3</PROPERTY>
+    </HEADER>
+    <TIME_ORDER>
+        <TIME_SLOT TIME_SLOT_ID="ts1" TIME_VALUE="0"/>
+        <TIME_SLOT TIME_SLOT_ID="ts2" TIME_VALUE="x"/>
+    </TIME_ORDER>
+{}
+</ANNOTATION_DOCUMENT>"#,
+            body
+        )
+    }
+
+    #[test]
+    fn test_read() {
+        let xml = eaf(
+            r#"<TIER LINGUISTIC_TYPE_REF="ort" PARTICIPANT="Jana" TIER_ID="ort@Jana">
+        <ANNOTATION>
+            <ALIGNABLE_ANNOTATION ANNOTATION_ID="a1" TIME_SLOT_REF1="ts1" TIME_SLOT_REF2="ts2">
+                <ANNOTATION_VALUE>&lt;SM no&gt; <![CDATA[<tak>]]>&#x159;</ANNOTATION_VALUE>
+                <ANNOTATION_VALUE>ignored</ANNOTATION_VALUE>
+            </ALIGNABLE_ANNOTATION>
+        </ANNOTATION>
+        <ANNOTATION>
+            <REF_ANNOTATION ANNOTATION_ID="a2" ANNOTATION_REF="a1" CVE_REF="cv1"/>
+        </ANNOTATION>
+    </TIER>
+    <LINGUISTIC_TYPE LINGUISTIC_TYPE_ID="ort" TIME_ALIGNABLE="true"/>"#,
+        );
+        let eaf = read(xml.as_bytes()).unwrap();
+        assert_eq!(eaf.header.author(), "Jana & Petr");
+        assert_eq!(eaf.header.property("lastUsedAnnotationId"), Some("2"));
+        assert_eq!(
+            eaf.media,
+            [
+                MediaDescriptor {
+                    url: "file:///rec.wav".to_owned(),
+                    mime_type: Some("audio/x-wav".to_owned()),
+                    relative_url: None,
+                    time_origin: Some(1500),
+                },
+                MediaDescriptor {
+                    url: "file:///rec.mp4".to_owned(),
+                    mime_type: None,
+                    relative_url: Some("./rec.mp4".to_owned()),
+                    time_origin: None,
+                },
+            ]
+        );
+        assert_eq!(
+            eaf.time_slots.iter().map(|s| s.value).collect::<Vec<_>>(),
+            [Some(0), None]
+        );
+        assert_eq!(eaf.linguistic_types[0].id, "ort");
+        let tier = &eaf.tiers[0];
+        assert_eq!(tier.participant.as_deref(), Some("Jana"));
+        assert_eq!(tier.annotations[0].value, "<SM no> <tak>ř");
+        assert!(!tier.annotations[0].cve_ref);
+        assert_eq!(
+            tier.annotations[1].alignment,
+            Alignment::Ref {
+                parent: "a1".to_owned(),
+                previous: None
+            }
+        );
+        assert_eq!(tier.annotations[1].value, "");
+        assert!(tier.annotations[1].cve_ref);
+    }
+
+    #[test]
+    fn test_read_vocabularies() {
+        let xml = eaf(r#"<CONTROLLED_VOCABULARY CV_ID="pos">
+        <DESCRIPTION LANG_REF="eng">parts of speech</DESCRIPTION>
+        <CV_ENTRY_ML CVE_ID="cveid1">
+            <CVE_VALUE LANG_REF="ces">podst. jm.</CVE_VALUE>
+            <CVE_VALUE LANG_REF="eng">noun</CVE_VALUE>
+        </CV_ENTRY_ML>
+    </CONTROLLED_VOCABULARY>
+    <CONTROLLED_VOCABULARY CV_ID="old">
+        <CV_ENTRY DESCRIPTION="">A&amp;B</CV_ENTRY>
+    </CONTROLLED_VOCABULARY>
+    <CONTROLLED_VOCABULARY CV_ID="ext" EXT_REF="er1"/>"#);
+        let eaf = read(xml.as_bytes()).unwrap();
+        let vocabularies = &eaf.controlled_vocabularies;
+        assert_eq!(vocabularies.len(), 3);
+        assert_eq!(
+            vocabularies[0].entries,
+            [CvEntry {
+                id: Some("cveid1".to_owned()),
+                values: vec!["podst. jm.".to_owned(), "noun".to_owned()],
+            }]
+        );
+        assert_eq!(vocabularies[1].entries[0].id, None);
+        assert_eq!(vocabularies[1].entries[0].values, ["A&B"]);
+        assert_eq!(vocabularies[2].external.as_deref(), Some("er1"));
+        assert!(vocabularies[2].entries.is_empty());
+    }
+
+    #[test]
+    fn test_not_eaf() {
+        let error = |xml: &str| read(xml.as_bytes()).unwrap_err().0;
+        assert_eq!(error("<html/>"), "missing ANNOTATION_DOCUMENT");
+        assert_eq!(error(""), "missing ANNOTATION_DOCUMENT");
+        assert_eq!(
+            error("<ANNOTATION_DOCUMENT><TIER>"),
+            "unexpected end of document"
+        );
+        assert!(error("<ANNOTATION_DOCUMENT></TIER>").contains("TIER"));
+        let xml = eaf(r#"<TIER TIER_ID="t"><ANNOTATION><X/></ANNOTATION></TIER>"#);
+        assert_eq!(error(&xml), "unknown annotation type X");
+    }
+}
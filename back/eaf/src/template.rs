@@ -0,0 +1,114 @@
+//! Turn an existing EAF into a blank template for a new document: tier
+//! structure, linguistic types, controlled vocabularies etc. are kept, while
+//! annotations, time slots and media links are dropped.
+
+use std::fmt;
+
+use sxd_document::dom::{ChildOfElement, Document, Element};
+use sxd_document::{parser, writer};
+
+#[derive(Debug)]
+pub struct TemplateError(String);
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "can't use EAF as template: {}", self.0)
+    }
+}
+
+/// Header elements pointing to the recordings.
+pub(crate) const MEDIA: &[&str] = &["MEDIA_DESCRIPTOR", "LINKED_FILE_DESCRIPTOR"];
+
+pub(crate) fn annotation_document<'d>(doc: &Document<'d>) -> Option<Element<'d>> {
+    doc.root()
+        .children()
+        .into_iter()
+        .find_map(|c| c.element())
+        .filter(|e| e.name().local_part() == "ANNOTATION_DOCUMENT")
+}
+
+pub fn blank(xml: &str) -> Result<String, TemplateError> {
+    let package = parser::parse(xml).map_err(|e| TemplateError(format!("{:?}", e)))?;
+    let doc = package.as_document();
+    let root = annotation_document(&doc)
+        .ok_or_else(|| TemplateError("missing ANNOTATION_DOCUMENT".to_owned()))?;
+
+    for child in root.children() {
+        let element = match child {
+            ChildOfElement::Element(element) => element,
+            _ => continue,
+        };
+        match element.name().local_part() {
+            "TIER" | "TIME_ORDER" => element.clear_children(),
+            "HEADER" => {
+                for child in element.children() {
+                    let child = match child {
+                        ChildOfElement::Element(child) => child,
+                        _ => continue,
+                    };
+                    match child.name().local_part() {
+                        name if MEDIA.contains(&name) => child.remove_from_parent(),
+                        "PROPERTY"
+                            if child.attribute_value("NAME") == Some("lastUsedAnnotationId") =>
+                        {
+                            child.set_text("0");
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut out = vec![];
+    // writing to a Vec can't fail
+    writer::format_document(&doc, &mut out).unwrap();
+    String::from_utf8(out).map_err(|e| TemplateError(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EAF: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<ANNOTATION_DOCUMENT AUTHOR="" DATE="2020-01-01T00:00:00+01:00" FORMAT="3.0" VERSION="3.0">
+    <HEADER MEDIA_FILE="" TIME_UNITS="milliseconds">
+        <MEDIA_DESCRIPTOR MEDIA_URL="file:///old.wav" MIME_TYPE="audio/x-wav"/>
+        <PROPERTY NAME="lastUsedAnnotationId">42</PROPERTY>
+    </HEADER>
+    <TIME_ORDER>
+        <TIME_SLOT TIME_SLOT_ID="ts1" TIME_VALUE="0"/>
+        <TIME_SLOT TIME_SLOT_ID="ts2" TIME_VALUE="100"/>
+    </TIME_ORDER>
+    <TIER LINGUISTIC_TYPE_REF="ort" PARTICIPANT="Jana" TIER_ID="ort@Jana">
+        <ANNOTATION>
+            <ALIGNABLE_ANNOTATION ANNOTATION_ID="a42" TIME_SLOT_REF1="ts1" TIME_SLOT_REF2="ts2">
+                <ANNOTATION_VALUE>tajné</ANNOTATION_VALUE>
+            </ALIGNABLE_ANNOTATION>
+        </ANNOTATION>
+    </TIER>
+    <LINGUISTIC_TYPE GRAPHIC_REFERENCES="false" LINGUISTIC_TYPE_ID="ort" TIME_ALIGNABLE="true" CONTROLLED_VOCABULARY_REF="cv"/>
+    <CONTROLLED_VOCABULARY CV_ID="cv">
+        <CV_ENTRY_ML CVE_ID="e1"><CVE_VALUE LANG_REF="ces">smích</CVE_VALUE></CV_ENTRY_ML>
+    </CONTROLLED_VOCABULARY>
+</ANNOTATION_DOCUMENT>"#;
+
+    #[test]
+    fn test_blank() {
+        let blank = blank(EAF).unwrap();
+        assert!(!blank.contains("tajné"));
+        assert!(!blank.contains("TIME_SLOT_ID"));
+        assert!(!blank.contains("old.wav"));
+        assert!(blank.contains(r#"TIER_ID='ort@Jana'"#));
+        assert!(blank.contains(">0</PROPERTY>"));
+        assert!(blank.contains("LINGUISTIC_TYPE_ID='ort'"));
+        assert!(blank.contains("smích"));
+    }
+
+    #[test]
+    fn test_not_eaf() {
+        assert!(blank("<html/>").is_err());
+        assert!(blank("nope").is_err());
+    }
+}
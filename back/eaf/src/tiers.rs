@@ -0,0 +1,158 @@
+//! Figure out which speaker a tier belongs to.
+//!
+//! Projects name their tiers differently, e.g. `ort@Jana` vs. `Jana [ort]`,
+//! and some rely on the `PARTICIPANT` attribute instead. A `TierMapping` is
+//! an ordered list of rules, each of which extracts a speaker nickname from
+//! either the tier ID or the participant, using a pattern with a single
+//! `<nickname>` placeholder.
+
+use std::fmt;
+
+use regex::Regex;
+
+const PLACEHOLDER: &str = "<nickname>";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TierSource {
+    TierId,
+    Participant,
+}
+
+impl TierSource {
+    pub fn label(self) -> &'static str {
+        match self {
+            TierSource::TierId => "tier_id",
+            TierSource::Participant => "participant",
+        }
+    }
+
+    pub fn from_label(label: &str) -> Option<Self> {
+        match label {
+            "tier_id" => Some(TierSource::TierId),
+            "participant" => Some(TierSource::Participant),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct PatternError(String);
+
+impl fmt::Display for PatternError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "tier pattern {:?} must contain {} exactly once",
+            self.0, PLACEHOLDER
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TierPattern {
+    source: String,
+    regex: Regex,
+}
+
+impl TierPattern {
+    pub fn new(pattern: &str) -> Result<Self, PatternError> {
+        let parts: Vec<_> = pattern.split(PLACEHOLDER).collect();
+        if parts.len() != 2 {
+            return Err(PatternError(pattern.to_owned()));
+        }
+        let regex = format!(
+            r"\A{}(.+?){}\z",
+            regex::escape(parts[0]),
+            regex::escape(parts[1])
+        );
+        Ok(Self {
+            source: pattern.to_owned(),
+            regex: Regex::new(&regex).unwrap(),
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    /// The name which matches the pattern with the given nickname, e.g. for
+    /// naming a new tier.
+    pub fn fill(&self, nickname: &str) -> String {
+        self.source.replacen(PLACEHOLDER, nickname, 1)
+    }
+
+    /// The nickname, if the name matches the pattern.
+    pub fn nickname<'n>(&self, name: &'n str) -> Option<&'n str> {
+        self.regex
+            .captures(name)
+            .and_then(|caps| caps.get(1))
+            .map(|m| m.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TierMapping {
+    rules: Vec<(TierSource, TierPattern)>,
+}
+
+impl TierMapping {
+    pub fn new(rules: Vec<(TierSource, TierPattern)>) -> Self {
+        Self { rules }
+    }
+
+    pub fn rules(&self) -> &[(TierSource, TierPattern)] {
+        &self.rules
+    }
+
+    /// Nickname of the speaker according to the first matching rule.
+    pub fn nickname<'t>(&self, tier_id: &'t str, participant: Option<&'t str>) -> Option<&'t str> {
+        self.rules
+            .iter()
+            .find_map(|(source, pattern)| match source {
+                TierSource::TierId => pattern.nickname(tier_id),
+                TierSource::Participant => participant.and_then(|p| pattern.nickname(p)),
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pattern() {
+        let pattern = TierPattern::new("ort@<nickname>").unwrap();
+        assert_eq!(pattern.nickname("ort@Jana"), Some("Jana"));
+        assert_eq!(pattern.nickname("ort@Jana Nováková"), Some("Jana Nováková"));
+        assert_eq!(pattern.nickname("fon@Jana"), None);
+        assert_eq!(pattern.nickname("ort@"), None);
+        assert_eq!(pattern.fill("Jana"), "ort@Jana");
+
+        let pattern = TierPattern::new("<nickname> [ort]").unwrap();
+        assert_eq!(pattern.nickname("Jana [ort]"), Some("Jana"));
+        assert_eq!(pattern.nickname("Jana (ort)"), None);
+    }
+
+    #[test]
+    fn test_bad_pattern() {
+        assert!(TierPattern::new("ort").is_err());
+        assert!(TierPattern::new("<nickname>@<nickname>").is_err());
+    }
+
+    #[test]
+    fn test_mapping() {
+        let mapping = TierMapping::new(vec![
+            (
+                TierSource::TierId,
+                TierPattern::new("ort@<nickname>").unwrap(),
+            ),
+            (
+                TierSource::Participant,
+                TierPattern::new("<nickname>").unwrap(),
+            ),
+        ]);
+        assert_eq!(mapping.nickname("ort@Jana", Some("Petr")), Some("Jana"));
+        assert_eq!(mapping.nickname("comments", Some("Petr")), Some("Petr"));
+        assert_eq!(mapping.nickname("comments", None), None);
+    }
+}
@@ -0,0 +1,279 @@
+//! Integrity of the TIME_ORDER section, checked before annotations are
+//! parsed: time slots which alignable annotations can't be placed by, slots
+//! nothing refers to, and references which lead nowhere.
+
+use std::collections::{HashMap, HashSet};
+
+use super::annotations::{slot_times, ReadError};
+use super::document::Alignment;
+use super::stream;
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum SlotMistake {
+    /// A time slot without a (valid) time value which can't be interpolated
+    /// either, so annotations bounded by it can't be placed in the
+    /// recording.
+    NoValue { slot: String },
+    /// A time slot no annotation refers to.
+    Unreferenced { slot: String },
+    /// An annotation referring to a time slot which doesn't exist.
+    MissingSlot {
+        tier: String,
+        annotation: String,
+        slot: String,
+    },
+    /// A time slot whose value is smaller than that of a slot before it in
+    /// TIME_ORDER.
+    Unordered { slot: String, previous: String },
+    /// An annotation ending before it starts.
+    Reversed {
+        tier: String,
+        annotation: String,
+        slot: String,
+    },
+}
+
+impl SlotMistake {
+    /// All values returned by `SlotMistake::kind`.
+    pub const KINDS: &'static [&'static str] = &[
+        "slot_without_value",
+        "unreferenced_slot",
+        "missing_slot",
+        "non_monotonic_slot",
+    ];
+
+    /// Stable name of the kind of mistake, cf. `parser::Mistake::kind`.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            SlotMistake::NoValue { .. } => "slot_without_value",
+            SlotMistake::Unreferenced { .. } => "unreferenced_slot",
+            SlotMistake::MissingSlot { .. } => "missing_slot",
+            SlotMistake::Unordered { .. } | SlotMistake::Reversed { .. } => "non_monotonic_slot",
+        }
+    }
+
+    /// The offending time slot.
+    pub fn slot(&self) -> &str {
+        match self {
+            SlotMistake::NoValue { slot }
+            | SlotMistake::Unreferenced { slot }
+            | SlotMistake::MissingSlot { slot, .. }
+            | SlotMistake::Unordered { slot, .. }
+            | SlotMistake::Reversed { slot, .. } => slot,
+        }
+    }
+
+    /// The tier and annotation concerned, if any.
+    pub fn annotation(&self) -> Option<(&str, &str)> {
+        match self {
+            SlotMistake::MissingSlot {
+                tier, annotation, ..
+            }
+            | SlotMistake::Reversed {
+                tier, annotation, ..
+            } => Some((tier, annotation)),
+            _ => None,
+        }
+    }
+}
+
+/// Mistakes in the document's time slots, in TIME_ORDER order followed by
+/// those in annotations, tier by tier.
+pub fn check(xml: &str) -> Result<Vec<SlotMistake>, ReadError> {
+    let eaf = stream::read(xml.as_bytes())?;
+
+    let mut mistakes = vec![];
+    // in document order, with values where they're valid
+    let slots: Vec<(&str, Option<u32>)> = eaf
+        .time_slots
+        .iter()
+        .map(|slot| (slot.id.as_str(), slot.value))
+        .collect();
+
+    let times = slot_times(&eaf);
+    let mut latest: Option<(&str, u32)> = None;
+    for &(id, value) in &slots {
+        match (value, latest) {
+            (None, _) if times.contains_key(id) => {}
+            (None, _) => mistakes.push(SlotMistake::NoValue {
+                slot: id.to_owned(),
+            }),
+            (Some(value), Some((previous, max))) if value < max => {
+                mistakes.push(SlotMistake::Unordered {
+                    slot: id.to_owned(),
+                    previous: previous.to_owned(),
+                })
+            }
+            (Some(value), _) => latest = Some((id, value)),
+        }
+    }
+
+    let positions: HashMap<&str, usize> = slots
+        .iter()
+        .enumerate()
+        .map(|(i, &(id, _))| (id, i))
+        .collect();
+    let mut referenced = HashSet::new();
+    let mut in_annotations = vec![];
+    for tier in &eaf.tiers {
+        for annotation in &tier.annotations {
+            let (start_slot, end_slot) = match &annotation.alignment {
+                Alignment::Alignable {
+                    start_slot,
+                    end_slot,
+                } => (start_slot.as_str(), end_slot.as_str()),
+                Alignment::Ref { .. } => continue,
+            };
+            let mut bounds = vec![];
+            for &slot in &[start_slot, end_slot] {
+                match positions.get(slot) {
+                    Some(&position) => {
+                        referenced.insert(slot);
+                        bounds.push(position);
+                    }
+                    None => in_annotations.push(SlotMistake::MissingSlot {
+                        tier: tier.id.clone(),
+                        annotation: annotation.id.clone(),
+                        slot: slot.to_owned(),
+                    }),
+                }
+            }
+            if let [start, end] = bounds[..] {
+                let reversed = match (slots[start].1, slots[end].1) {
+                    (Some(start_value), Some(end_value)) => start_value > end_value,
+                    _ => start > end,
+                };
+                if reversed {
+                    in_annotations.push(SlotMistake::Reversed {
+                        tier: tier.id.clone(),
+                        annotation: annotation.id.clone(),
+                        slot: slots[end].0.to_owned(),
+                    });
+                }
+            }
+        }
+    }
+
+    for &(id, _) in &slots {
+        if !referenced.contains(id) {
+            mistakes.push(SlotMistake::Unreferenced {
+                slot: id.to_owned(),
+            });
+        }
+    }
+    mistakes.extend(in_annotations);
+    Ok(mistakes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eaf(slots: &str, annotations: &str) -> String {
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<ANNOTATION_DOCUMENT AUTHOR="" DATE="2020-01-01T00:00:00+01:00" FORMAT="3.0" VERSION="3.0">
+    <HEADER MEDIA_FILE="" TIME_UNITS="milliseconds"/>
+    <TIME_ORDER>{}</TIME_ORDER>
+    <TIER LINGUISTIC_TYPE_REF="ort" TIER_ID="ort@Jana">{}</TIER>
+</ANNOTATION_DOCUMENT>"#,
+            slots, annotations
+        )
+    }
+
+    fn slot(id: &str, value: Option<u32>) -> String {
+        match value {
+            Some(value) => format!(
+                r#"<TIME_SLOT TIME_SLOT_ID="{}" TIME_VALUE="{}"/>"#,
+                id, value
+            ),
+            None => format!(r#"<TIME_SLOT TIME_SLOT_ID="{}"/>"#, id),
+        }
+    }
+
+    fn annotation(id: &str, start: &str, end: &str) -> String {
+        format!(
+            r#"<ANNOTATION><ALIGNABLE_ANNOTATION ANNOTATION_ID="{}" TIME_SLOT_REF1="{}" TIME_SLOT_REF2="{}"><ANNOTATION_VALUE>jo</ANNOTATION_VALUE></ALIGNABLE_ANNOTATION></ANNOTATION>"#,
+            id, start, end
+        )
+    }
+
+    #[test]
+    fn test_unaligned() {
+        let xml = eaf(
+            &[
+                slot("ts1", Some(0)),
+                slot("ts2", None),
+                slot("ts3", Some(100)),
+                slot("ts4", None),
+            ]
+            .concat(),
+            &[
+                annotation("a1", "ts1", "ts2"),
+                annotation("a2", "ts2", "ts3"),
+                annotation("a3", "ts3", "ts4"),
+            ]
+            .concat(),
+        );
+        // ts2 can be interpolated
+        assert_eq!(
+            check(&xml).unwrap(),
+            vec![SlotMistake::NoValue {
+                slot: "ts4".to_owned()
+            }]
+        );
+    }
+
+    #[test]
+    fn test_check() {
+        let xml = eaf(
+            &[
+                slot("ts1", Some(100)),
+                slot("ts2", Some(50)),
+                slot("ts3", Some(200)),
+                slot("ts4", Some(300)),
+            ]
+            .concat(),
+            &[
+                annotation("a1", "ts3", "ts1"),
+                annotation("a2", "ts1", "ts5"),
+            ]
+            .concat(),
+        );
+        let mistakes = check(&xml).unwrap();
+        assert_eq!(
+            mistakes,
+            vec![
+                SlotMistake::Unordered {
+                    slot: "ts2".to_owned(),
+                    previous: "ts1".to_owned(),
+                },
+                SlotMistake::Unreferenced {
+                    slot: "ts2".to_owned()
+                },
+                SlotMistake::Unreferenced {
+                    slot: "ts4".to_owned()
+                },
+                SlotMistake::Reversed {
+                    tier: "ort@Jana".to_owned(),
+                    annotation: "a1".to_owned(),
+                    slot: "ts1".to_owned(),
+                },
+                SlotMistake::MissingSlot {
+                    tier: "ort@Jana".to_owned(),
+                    annotation: "a2".to_owned(),
+                    slot: "ts5".to_owned(),
+                },
+            ]
+        );
+        for mistake in &mistakes {
+            assert!(SlotMistake::KINDS.contains(&mistake.kind()));
+        }
+        assert_eq!(mistakes[3].annotation(), Some(("ort@Jana", "a1")));
+    }
+
+    #[test]
+    fn test_not_eaf() {
+        assert!(check("<html/>").is_err());
+    }
+}
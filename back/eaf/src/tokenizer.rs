@@ -8,20 +8,43 @@
 //! tokenization errors don't prevent further processing, because ideally, we
 //! want to inform about as many errors as possible at the same time.
 //!
-//! Whitespace is normalized prior to tokenization, as this isn't something
-//! we'd want people to fix by hand.
+//! By default, whitespace is normalized prior to tokenization, as this isn't
+//! something we'd want people to fix by hand. Projects which do want that can
+//! opt for `WhitespacePolicy::Report`, which leaves the source as is, so that
+//! the parser can report any irregular whitespace as a mistake (see
+//! `ParserConfig::with_whitespace` and `ParserConfig::tokenize`).
+//!
+//! Which strings delimit spans is up to the project (see `TokenizerConfig`),
+//! the default being `()`, `[]` and `<>`.
+
+use std::fmt;
 
 use lazy_static::lazy_static;
-use regex::{Match, Regex, RegexBuilder};
+use regex::Regex;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
+/// Serialized as `"round"`, `"square"` or `"angle"`.
 #[derive(Debug, PartialEq, Copy, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "snake_case")
+)]
 pub enum DelimKind {
     Round,
     Square,
     Angle,
 }
 
+/// Serialized as e.g. `{"type": "open", "delim": "round"}`, or
+/// `{"type": "non_delim"}`.
 #[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(tag = "type", content = "delim", rename_all = "snake_case")
+)]
 pub enum TokenKind {
     NonDelim,
     Open(DelimKind),
@@ -29,32 +52,21 @@ pub enum TokenKind {
 }
 
 #[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Token {
     pub kind: TokenKind,
+    /// Byte offsets into the (possibly normalized) source.
     pub start: usize,
     pub end: usize,
 }
 
-impl<'t> From<Match<'t>> for Token {
-    fn from(mat: Match) -> Self {
-        use DelimKind::*;
-        use TokenKind::*;
-
-        let kind = match mat.as_str() {
-            "(" => Open(Round),
-            ")" => Close(Round),
-            "[" => Open(Square),
-            "]" => Close(Square),
-            "<" => Open(Angle),
-            ">" => Close(Angle),
-            _ => NonDelim,
-        };
-        Self {
-            kind,
-            start: mat.start(),
-            end: mat.end(),
-        }
-    }
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum WhitespacePolicy {
+    /// Trim the segment and collapse whitespace runs into single spaces.
+    #[default]
+    Normalize,
+    /// Keep whitespace as is.
+    Report,
 }
 
 #[derive(Debug)]
@@ -69,43 +81,181 @@ impl Tokenized {
     }
 }
 
-pub fn tokenize(source: &str) -> Tokenized {
-    lazy_static! {
-        static ref WHITESPACE_RE: Regex = Regex::new(r"\s+").unwrap();
-        static ref TOKENIZER_RE: Regex = RegexBuilder::new(
-            r#"
-            # paired delimiter token:
-                [
-                    \[\]\(\)<>
-                ]
-            |
-            # whitespace:
-                \s+
-            |
-            # non-whitespace:
-                [^
-                    \[\]\(\)<>
-                    \s
-                ]+
-        "#
-        )
-        .ignore_whitespace(true)
-        .build()
-        .unwrap();
+/// A pair of strings delimiting a span. They may be the same, e.g. `//` for
+/// `//…//`, in which case occurrences alternate between opening and closing
+/// the span.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Delim {
+    pub open: String,
+    pub close: String,
+    pub kind: DelimKind,
+}
+
+impl Delim {
+    pub fn new(open: &str, close: &str, kind: DelimKind) -> Self {
+        Self {
+            open: open.to_owned(),
+            close: close.to_owned(),
+            kind,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct TokenizerConfigError(String);
+
+impl fmt::Display for TokenizerConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid delimiters: {}", self.0)
+    }
+}
+
+/// The delimiters a project transcribes spans with. Several pairs may
+/// delimit the same kind of span, e.g. both `()` and `{}` uncertain speech.
+#[derive(Debug)]
+pub struct TokenizerConfig {
+    delims: Vec<Delim>,
+    /// Any of the delimiters, longest first.
+    delim_re: Regex,
+}
+
+impl TokenizerConfig {
+    pub fn new(delims: Vec<Delim>) -> Result<Self, TokenizerConfigError> {
+        let mut strings: Vec<&str> = vec![];
+        for delim in &delims {
+            for string in [&delim.open, &delim.close] {
+                if string.is_empty() || string.contains(char::is_whitespace) {
+                    return Err(TokenizerConfigError(format!(
+                        "{:?} is empty or contains whitespace",
+                        string
+                    )));
+                }
+            }
+            for string in [&delim.open, &delim.close] {
+                if strings.contains(&string.as_str()) {
+                    return Err(TokenizerConfigError(format!(
+                        "{:?} is used more than once",
+                        string
+                    )));
+                }
+                strings.push(string);
+                if delim.open == delim.close {
+                    break;
+                }
+            }
+        }
+        if strings.is_empty() {
+            return Err(TokenizerConfigError("none given".to_owned()));
+        }
+        strings.sort_unstable_by_key(|s| std::cmp::Reverse(s.len()));
+        let alternatives: Vec<_> = strings.iter().map(|s| regex::escape(s)).collect();
+        let delim_re = Regex::new(&alternatives.join("|")).unwrap();
+        Ok(Self { delims, delim_re })
+    }
+
+    pub fn delims(&self) -> &[Delim] {
+        &self.delims
+    }
+
+    /// The string closing spans opened with `open`, if it opens any.
+    pub fn closing(&self, open: &str) -> Option<&str> {
+        self.delims
+            .iter()
+            .find(|delim| delim.open == open)
+            .map(|delim| delim.close.as_str())
+    }
+
+    pub fn tokenize(&self, source: &str, policy: WhitespacePolicy) -> Tokenized {
+        lazy_static! {
+            static ref WHITESPACE_RE: Regex = Regex::new(r"\s+").unwrap();
+            static ref WORD_RE: Regex = Regex::new(r"\S+").unwrap();
+        }
+        let source = match policy {
+            WhitespacePolicy::Normalize => {
+                WHITESPACE_RE.replace_all(source.trim(), " ").into_owned()
+            }
+            WhitespacePolicy::Report => source.to_owned(),
+        };
+        // whether each of the delimiters which are the same on both ends
+        // currently has a span open
+        let mut open = vec![false; self.delims.len()];
+        let mut tokens = vec![];
+        for word in WORD_RE.find_iter(&source) {
+            let mut start = word.start();
+            for mat in self.delim_re.find_iter(word.as_str()) {
+                let (delim_start, delim_end) =
+                    (word.start() + mat.start(), word.start() + mat.end());
+                if start < delim_start {
+                    tokens.push(Token {
+                        kind: TokenKind::NonDelim,
+                        start,
+                        end: delim_start,
+                    });
+                }
+                tokens.push(Token {
+                    kind: self.delim_kind(mat.as_str(), &mut open),
+                    start: delim_start,
+                    end: delim_end,
+                });
+                start = delim_end;
+            }
+            if start < word.end() {
+                tokens.push(Token {
+                    kind: TokenKind::NonDelim,
+                    start,
+                    end: word.end(),
+                });
+            }
+        }
+        Tokenized { source, tokens }
     }
-    // normalize whitespace
-    let source = WHITESPACE_RE.replace_all(source.trim(), " ").into_owned();
-    let tokens = TOKENIZER_RE
-        .find_iter(&source)
-        .filter_map::<Token, _>(|m| {
-            if m.as_str() == " " {
-                None
-            } else {
-                Some(Token::from(m))
+
+    fn delim_kind(&self, string: &str, open: &mut [bool]) -> TokenKind {
+        for (i, delim) in self.delims.iter().enumerate() {
+            if delim.open == delim.close && delim.open == string {
+                open[i] = !open[i];
+                return if open[i] {
+                    TokenKind::Open(delim.kind)
+                } else {
+                    TokenKind::Close(delim.kind)
+                };
+            } else if delim.open == string {
+                return TokenKind::Open(delim.kind);
+            } else if delim.close == string {
+                return TokenKind::Close(delim.kind);
             }
-        })
-        .collect();
-    Tokenized { source, tokens }
+        }
+        unreachable!("{:?} matched as a delimiter", string)
+    }
+}
+
+impl Default for TokenizerConfig {
+    fn default() -> Self {
+        Self::new(vec![
+            Delim::new("(", ")", DelimKind::Round),
+            Delim::new("[", "]", DelimKind::Square),
+            Delim::new("<", ">", DelimKind::Angle),
+        ])
+        .unwrap()
+    }
+}
+
+lazy_static! {
+    static ref DEFAULT: TokenizerConfig = TokenizerConfig::default();
+}
+
+/// The default delimiters, without building them over and over.
+pub(crate) fn default_config() -> &'static TokenizerConfig {
+    &DEFAULT
+}
+
+pub fn tokenize(source: &str) -> Tokenized {
+    tokenize_with(source, WhitespacePolicy::Normalize)
+}
+
+/// Tokenize with the default delimiters.
+pub fn tokenize_with(source: &str, policy: WhitespacePolicy) -> Tokenized {
+    DEFAULT.tokenize(source, policy)
 }
 
 #[cfg(test)]
@@ -147,6 +297,17 @@ mod tests {
         }
     }
 
+    #[test]
+    fn tokenize_keeping_whitespace() {
+        let seg = tokenize_with(" foo  bar\t", WhitespacePolicy::Report);
+        assert_eq!(seg.source, " foo  bar\t");
+        assert_eq!(seg.tokens.len(), 2);
+        assert_eq!(seg.as_str(&seg.tokens[1]), "bar");
+
+        let seg = tokenize(" foo  bar\t");
+        assert_eq!(seg.source, "foo bar");
+    }
+
     #[test]
     fn compare_nice() {
         compare_tokens(
@@ -164,4 +325,48 @@ mod tests {
             &["foo", "]", "[", "bar", "(", "baz", ")", ".."],
         );
     }
+
+    #[test]
+    fn tokenize_custom_delims() {
+        let config = TokenizerConfig::new(vec![
+            Delim::new("{", "}", Round),
+            Delim::new("//", "//", Square),
+            Delim::new("<<", ">>", Angle),
+        ])
+        .unwrap();
+        let seg = config.tokenize("a{b} //c<<SM d>>// (e)", WhitespacePolicy::Normalize);
+        let kinds: Vec<_> = seg.tokens.iter().map(|t| t.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                NonDelim,
+                Open(Round),
+                NonDelim,
+                Close(Round),
+                Open(Square),
+                NonDelim,
+                Open(Angle),
+                NonDelim,
+                NonDelim,
+                Close(Angle),
+                Close(Square),
+                NonDelim,
+            ]
+        );
+        assert_eq!(seg.as_str(&seg.tokens[6]), "<<");
+        assert_eq!(seg.as_str(&seg.tokens[11]), "(e)");
+    }
+
+    #[test]
+    fn invalid_delims() {
+        assert!(TokenizerConfig::new(vec![]).is_err());
+        assert!(TokenizerConfig::new(vec![Delim::new("", ")", Round)]).is_err());
+        assert!(TokenizerConfig::new(vec![Delim::new("( ", ")", Round)]).is_err());
+        assert!(TokenizerConfig::new(vec![
+            Delim::new("(", ")", Round),
+            Delim::new("[", ")", Square),
+        ])
+        .is_err());
+        assert!(TokenizerConfig::new(vec![Delim::new("|", "|", Round)]).is_ok());
+    }
 }
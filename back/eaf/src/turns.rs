@@ -0,0 +1,171 @@
+//! Turn-taking and overlap between pairs of speakers, from the timing of
+//! their segments and the overlaps marked with square brackets.
+//!
+//! Pairs are ordered: for (A, B), B is the one taking the floor. Only
+//! aligned segments are taken into account, interpolated timings are too
+//! coarse for this.
+//!
+//! - A transition is B's segment starting right after A's, in order of
+//!   start times. Its gap is the silence between them, negative if B
+//!   started before A finished.
+//! - An overlap is B starting while A is still speaking, for as long as
+//!   both of them speak. It's an interruption if A then stops before B
+//!   does, and it's marked if B's segment contains overlapping speech in
+//!   square brackets.
+
+use std::collections::BTreeMap;
+
+use super::stats::Segment;
+
+/// Columns of `PairMeasures::record`.
+pub const CSV_HEADER: &[&str] = &[
+    "speaker",
+    "next_speaker",
+    "transitions",
+    "mean_gap_ms",
+    "overlaps",
+    "overlap_ms",
+    "interruptions",
+    "marked_overlaps",
+];
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct PairMeasures {
+    pub transitions: usize,
+    /// Sum of the transitions' gaps, in milliseconds.
+    pub gaps: i64,
+    pub overlaps: usize,
+    /// Total duration of overlaps, in milliseconds.
+    pub overlap_time: u64,
+    pub interruptions: usize,
+    pub marked_overlaps: usize,
+}
+
+impl PairMeasures {
+    pub fn mean_gap(&self) -> Option<f64> {
+        if self.transitions > 0 {
+            Some(self.gaps as f64 / self.transitions as f64)
+        } else {
+            None
+        }
+    }
+
+    /// A CSV record, cf. `CSV_HEADER`.
+    pub fn record(&self, speaker: &str, next_speaker: &str) -> Vec<String> {
+        vec![
+            speaker.to_owned(),
+            next_speaker.to_owned(),
+            self.transitions.to_string(),
+            self.mean_gap()
+                .map_or_else(String::new, |g| format!("{:.1}", g)),
+            self.overlaps.to_string(),
+            self.overlap_time.to_string(),
+            self.interruptions.to_string(),
+            self.marked_overlaps.to_string(),
+        ]
+    }
+}
+
+/// Measures by pair of speakers, sorted.
+pub fn analyze(segments: &[Segment]) -> Vec<((String, String), PairMeasures)> {
+    let mut timed: Vec<_> = segments
+        .iter()
+        .filter_map(|s| match (s.start, s.end) {
+            (Some(start), Some(end)) if start <= end && !s.interpolated => Some((start, end, s)),
+            _ => None,
+        })
+        .collect();
+    timed.sort_by_key(|&(start, end, _)| (start, end));
+
+    let mut pairs: BTreeMap<(&str, &str), PairMeasures> = BTreeMap::new();
+    for (i, &(start, end, b)) in timed.iter().enumerate() {
+        if let Some(&(_, previous_end, a)) = i.checked_sub(1).map(|p| &timed[p]) {
+            if a.speaker != b.speaker {
+                let pair = pairs.entry((&a.speaker, &b.speaker)).or_default();
+                pair.transitions += 1;
+                pair.gaps += i64::from(start) - i64::from(previous_end);
+            }
+        }
+        let marked = b.parsed.flagged_tokens().iter().any(|(_, f)| f.overlap);
+        for &(_, a_end, a) in &timed[..i] {
+            if a.speaker == b.speaker || a_end <= start {
+                continue;
+            }
+            let pair = pairs.entry((&a.speaker, &b.speaker)).or_default();
+            pair.overlaps += 1;
+            pair.overlap_time += u64::from(a_end.min(end) - start);
+            if a_end < end {
+                pair.interruptions += 1;
+            }
+            if marked {
+                pair.marked_overlaps += 1;
+            }
+        }
+    }
+    pairs
+        .into_iter()
+        .map(|((a, b), measures)| ((a.to_owned(), b.to_owned()), measures))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{Parser, ParserConfig};
+    use crate::tokenizer;
+
+    fn segment(speaker: &str, start: u32, end: u32, text: &str) -> Segment {
+        let config = ParserConfig::from_args::<&str, &str, &str, &str>(&[], &[], &[], &[]);
+        Segment {
+            speaker: speaker.to_owned(),
+            start: Some(start),
+            end: Some(end),
+            interpolated: false,
+            parsed: Parser::parse(&config, tokenizer::tokenize(text)),
+        }
+    }
+
+    #[test]
+    fn test_analyze() {
+        let pairs = analyze(&[
+            segment("A", 0, 1_000, "no tak [jo]"),
+            // interrupts A
+            segment("B", 800, 2_000, "[no] ale"),
+            // backchannel within B's segment
+            segment("A", 1_200, 1_400, "hm"),
+            segment("B", 2_300, 3_000, "tak"),
+            segment("A", 3_500, 4_000, "jo"),
+        ]);
+        let get = |a: &str, b: &str| {
+            pairs
+                .iter()
+                .find(|((x, y), _)| x == a && y == b)
+                .map(|(_, m)| m.clone())
+                .unwrap()
+        };
+        assert_eq!(pairs.len(), 2);
+        assert_eq!(
+            get("A", "B"),
+            PairMeasures {
+                transitions: 2,
+                gaps: -200 + 900,
+                overlaps: 1,
+                overlap_time: 200,
+                interruptions: 1,
+                marked_overlaps: 1,
+            }
+        );
+        assert_eq!(
+            get("B", "A"),
+            PairMeasures {
+                transitions: 2,
+                gaps: -800 + 500,
+                overlaps: 1,
+                overlap_time: 200,
+                interruptions: 0,
+                marked_overlaps: 0,
+            }
+        );
+        assert_eq!(get("A", "B").mean_gap(), Some(350.0));
+    }
+}
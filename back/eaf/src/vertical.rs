@@ -0,0 +1,159 @@
+//! Export of transcripts to the vertical format of corpus managers like
+//! Manatee/NoSketchEngine: a `doc` structure containing a `u` structure per
+//! transcript annotation, with one line per word inside. The positional
+//! attributes are
+//!
+//! 1. the word,
+//! 2. the attribute codes of the enclosing angle spans,
+//! 3. the kinds of other spans the word is inside, i.e. `round` for
+//!    uncertain and `square` for overlapping speech,
+//! 4. and 5. the start and end of the word in milliseconds, if it's
+//!    aligned (see `conllu::WordTimes`).
+//!
+//! They're separated by tabs (shown as spaces below). Attributes 2 and 3
+//! are multivalued, separated by `|`, and `_` stands for no value:
+//!
+//! ```text
+//! <doc id="42">
+//! <u id="a1" speaker="JD" tier="ort@JD" start="0" end="1000">
+//! no   _    _      _    _
+//! tak  SM   round  120  480
+//! </u>
+//! </doc>
+//! ```
+//!
+//! The corresponding registry declares them as `MULTIVALUE y` and
+//! `MULTISEP "|"`. Words are numbered as in CoNLL-U exports.
+
+use std::borrow::Borrow;
+
+use super::annotations::Annotation;
+use super::conllu::{self, WordTimes};
+use super::parser::{Attr, Parsed, Parser, ParserConfig};
+use super::tiers::TierMapping;
+use super::tokenizer;
+
+/// Like `conllu::parse`, but keeping attribute codes, whatever they are.
+/// They're never words, so the numbering is the same.
+fn parse(value: &str) -> Parsed {
+    let config =
+        ParserConfig::from_args(&[] as &[&str], &[] as &[&str], &[] as &[&str], &["[^_]+"]);
+    Parser::parse(&config, tokenizer::tokenize(value))
+}
+
+/// For structure attribute values.
+fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn time(ms: Option<u32>) -> String {
+    ms.map_or_else(String::new, |ms| ms.to_string())
+}
+
+fn multivalue<S: Borrow<str>>(values: &[S]) -> String {
+    if values.is_empty() {
+        "_".to_owned()
+    } else {
+        values.join("|")
+    }
+}
+
+pub fn write(
+    doc_id: &str,
+    annotations: &[Annotation],
+    mapping: &TierMapping,
+    times: &WordTimes,
+) -> String {
+    let mut out = format!("<doc id=\"{}\">\n", escape(doc_id));
+    for annotation in annotations {
+        if !annotation.is_transcript(mapping) {
+            continue;
+        }
+        let parsed = parse(&annotation.value);
+        let words = parsed.flagged_tokens();
+        if words.is_empty() {
+            continue;
+        }
+        out.push_str(&format!(
+            "<u id=\"{}\" speaker=\"{}\" tier=\"{}\" start=\"{}\" end=\"{}\">\n",
+            escape(&annotation.id),
+            escape(conllu::speaker(annotation, mapping)),
+            escape(&annotation.tier),
+            time(annotation.start),
+            time(annotation.end),
+        ));
+        for (i, (token, flags)) in words.iter().enumerate() {
+            let attrs: Vec<_> = flags.attrs.iter().flatten().map(Attr::to_string).collect();
+            let mut context = vec![];
+            if flags.uncertain {
+                context.push("round");
+            }
+            if flags.overlap {
+                context.push("square");
+            }
+            let (start, end) = match times.get(&(annotation.id.clone(), i)) {
+                Some((start, end)) => (start.to_string(), end.to_string()),
+                None => ("_".to_owned(), "_".to_owned()),
+            };
+            out.push_str(&format!(
+                "{}\t{}\t{}\t{}\t{}\n",
+                &parsed.source[token.start..token.end],
+                multivalue(&attrs),
+                multivalue(&context),
+                start,
+                end
+            ));
+        }
+        out.push_str("</u>\n");
+    }
+    out.push_str("</doc>\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn annotation(id: &str, value: &str) -> Annotation {
+        Annotation {
+            tier: "ort@JD".to_owned(),
+            participant: Some("JD".to_owned()),
+            linguistic_type: "ort".to_owned(),
+            id: id.to_owned(),
+            value: value.to_owned(),
+            start: Some(0),
+            end: Some(1000),
+            interpolated: false,
+        }
+    }
+
+    #[test]
+    fn test_write() {
+        let mut times = WordTimes::new();
+        times.insert(("a1".to_owned(), 1), (120, 480));
+        let vertical = write(
+            "42",
+            &[
+                annotation("a1", "no <SM (tak)> [<ZA_SM jo> a]"),
+                annotation("a2", ""),
+            ],
+            &TierMapping::default(),
+            &times,
+        );
+        assert_eq!(
+            vertical,
+            "<doc id=\"42\">\n\
+             <u id=\"a1\" speaker=\"JD\" tier=\"ort@JD\" start=\"0\" end=\"1000\">\n\
+             no\t_\t_\t_\t_\n\
+             tak\tSM\tround\t120\t480\n\
+             jo\tSM|ZA\tsquare\t_\t_\n\
+             a\t_\tsquare\t_\t_\n\
+             </u>\n\
+             </doc>\n"
+        );
+    }
+}
@@ -0,0 +1,265 @@
+//! Values of annotations on tiers whose linguistic type has a controlled
+//! vocabulary, which ELAN only lets annotators pick from the vocabulary but
+//! which other tools (or hand edits) happily change to anything. Like
+//! `timeslots`, these are mistakes of the document rather than of single
+//! segments.
+
+use std::collections::HashMap;
+
+use super::annotations::ReadError;
+use super::document::ControlledVocabulary;
+use super::policies::{Policy, TierPolicies};
+use super::stream;
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum VocabMistake {
+    /// An annotation whose value isn't one of the vocabulary's entries, in
+    /// any of its languages.
+    NotInVocabulary {
+        tier: String,
+        annotation: String,
+        value: String,
+        vocabulary: String,
+    },
+    /// A tier whose linguistic type refers to a vocabulary the document
+    /// doesn't define, or none at all although its policy says its values
+    /// come from one, so they can't be checked.
+    MissingVocabulary {
+        tier: String,
+        vocabulary: Option<String>,
+    },
+}
+
+impl VocabMistake {
+    /// All values returned by `VocabMistake::kind`.
+    pub const KINDS: &'static [&'static str] = &["not_in_vocabulary", "missing_vocabulary"];
+
+    /// Stable name of the kind of mistake, cf. `parser::Mistake::kind`.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            VocabMistake::NotInVocabulary { .. } => "not_in_vocabulary",
+            VocabMistake::MissingVocabulary { .. } => "missing_vocabulary",
+        }
+    }
+
+    /// The tier concerned.
+    pub fn tier(&self) -> &str {
+        match self {
+            VocabMistake::NotInVocabulary { tier, .. }
+            | VocabMistake::MissingVocabulary { tier, .. } => tier,
+        }
+    }
+
+    /// The vocabulary concerned, if the tier refers to one.
+    pub fn vocabulary(&self) -> Option<&str> {
+        match self {
+            VocabMistake::NotInVocabulary { vocabulary, .. } => Some(vocabulary),
+            VocabMistake::MissingVocabulary { vocabulary, .. } => vocabulary.as_deref(),
+        }
+    }
+
+    /// The offending value, if any.
+    pub fn value(&self) -> Option<&str> {
+        match self {
+            VocabMistake::NotInVocabulary { value, .. } => Some(value),
+            VocabMistake::MissingVocabulary { .. } => None,
+        }
+    }
+
+    /// The tier and annotation concerned, if any.
+    pub fn annotation(&self) -> Option<(&str, &str)> {
+        match self {
+            VocabMistake::NotInVocabulary {
+                tier, annotation, ..
+            } => Some((tier, annotation)),
+            VocabMistake::MissingVocabulary { .. } => None,
+        }
+    }
+}
+
+/// Mistakes in values from controlled vocabularies, tier by tier. Empty
+/// values are fine (the annotation just hasn't been filled in yet), and so
+/// is anything in external vocabularies, whose entries aren't in the
+/// document. Tiers whose policy is other than `Policy::Vocabulary` aren't
+/// checked, see `policies`.
+pub fn check(xml: &str, policies: &TierPolicies) -> Result<Vec<VocabMistake>, ReadError> {
+    let eaf = stream::read(xml.as_bytes())?;
+
+    let vocabularies: HashMap<&str, &ControlledVocabulary> = eaf
+        .controlled_vocabularies
+        .iter()
+        .map(|v| (v.id.as_str(), v))
+        .collect();
+    let types: HashMap<&str, &str> = eaf
+        .linguistic_types
+        .iter()
+        .filter_map(|t| Some((t.id.as_str(), t.controlled_vocabulary.as_deref()?)))
+        .collect();
+
+    let mut mistakes = vec![];
+    for tier in &eaf.tiers {
+        let id = match (
+            policies.policy(&tier.id, &tier.linguistic_type),
+            types.get(tier.linguistic_type.as_str()),
+        ) {
+            (Some(Policy::Vocabulary), Some(&id)) | (None, Some(&id)) => id,
+            (Some(Policy::Vocabulary), None) => {
+                mistakes.push(VocabMistake::MissingVocabulary {
+                    tier: tier.id.clone(),
+                    vocabulary: None,
+                });
+                continue;
+            }
+            _ => continue,
+        };
+        let vocabulary = match vocabularies.get(id) {
+            Some(vocabulary) if vocabulary.external.is_some() => continue,
+            Some(vocabulary) => vocabulary,
+            None => {
+                mistakes.push(VocabMistake::MissingVocabulary {
+                    tier: tier.id.clone(),
+                    vocabulary: Some(id.to_owned()),
+                });
+                continue;
+            }
+        };
+        for annotation in &tier.annotations {
+            if annotation.value.is_empty() || vocabulary.contains(&annotation.value) {
+                continue;
+            }
+            mistakes.push(VocabMistake::NotInVocabulary {
+                tier: tier.id.clone(),
+                annotation: annotation.id.clone(),
+                value: annotation.value.clone(),
+                vocabulary: id.to_owned(),
+            });
+        }
+    }
+    Ok(mistakes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::policies::PolicyRule;
+
+    fn eaf(vocabularies: &str, tiers: &str) -> String {
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<ANNOTATION_DOCUMENT AUTHOR="" DATE="2020-01-01T00:00:00+01:00" FORMAT="3.0" VERSION="3.0">
+    <HEADER MEDIA_FILE="" TIME_UNITS="milliseconds"/>
+    <TIME_ORDER>
+        <TIME_SLOT TIME_SLOT_ID="ts1" TIME_VALUE="0"/>
+        <TIME_SLOT TIME_SLOT_ID="ts2" TIME_VALUE="1000"/>
+    </TIME_ORDER>
+    {}
+    <LINGUISTIC_TYPE LINGUISTIC_TYPE_ID="ort" TIME_ALIGNABLE="true"/>
+    <LINGUISTIC_TYPE CONTROLLED_VOCABULARY_REF="pos" LINGUISTIC_TYPE_ID="pos" TIME_ALIGNABLE="true"/>
+    {}
+</ANNOTATION_DOCUMENT>"#,
+            tiers, vocabularies
+        )
+    }
+
+    fn tier(id: &str, linguistic_type: &str, values: &[&str]) -> String {
+        let annotations: Vec<_> = values
+            .iter()
+            .enumerate()
+            .map(|(i, value)| {
+                format!(
+                    r#"<ANNOTATION><ALIGNABLE_ANNOTATION ANNOTATION_ID="{}{}" TIME_SLOT_REF1="ts1" TIME_SLOT_REF2="ts2"><ANNOTATION_VALUE>{}</ANNOTATION_VALUE></ALIGNABLE_ANNOTATION></ANNOTATION>"#,
+                    id, i, value
+                )
+            })
+            .collect();
+        format!(
+            r#"<TIER LINGUISTIC_TYPE_REF="{}" TIER_ID="{}">{}</TIER>"#,
+            linguistic_type,
+            id,
+            annotations.concat()
+        )
+    }
+
+    const POS: &str = r#"<CONTROLLED_VOCABULARY CV_ID="pos">
+        <CV_ENTRY_ML CVE_ID="cv1">
+            <CVE_VALUE LANG_REF="ces">podst. jm.</CVE_VALUE>
+            <CVE_VALUE LANG_REF="eng">noun</CVE_VALUE>
+        </CV_ENTRY_ML>
+        <CV_ENTRY_ML CVE_ID="cv2"><CVE_VALUE LANG_REF="eng">verb</CVE_VALUE></CV_ENTRY_ML>
+    </CONTROLLED_VOCABULARY>"#;
+
+    #[test]
+    fn test_in_vocabulary() {
+        let tiers =
+            tier("o", "ort", &["whatever"]) + &tier("p", "pos", &["noun", "podst. jm.", ""]);
+        assert_eq!(
+            check(&eaf(POS, &tiers), &TierPolicies::default()).unwrap(),
+            []
+        );
+    }
+
+    #[test]
+    fn test_not_in_vocabulary() {
+        let tiers = tier("p", "pos", &["verb", "Verb"]);
+        assert_eq!(
+            check(&eaf(POS, &tiers), &TierPolicies::default()).unwrap(),
+            [VocabMistake::NotInVocabulary {
+                tier: "p".to_owned(),
+                annotation: "p1".to_owned(),
+                value: "Verb".to_owned(),
+                vocabulary: "pos".to_owned(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_legacy_entries() {
+        let pos = r#"<CONTROLLED_VOCABULARY CV_ID="pos"><CV_ENTRY DESCRIPTION="">noun</CV_ENTRY></CONTROLLED_VOCABULARY>"#;
+        let mistakes = check(
+            &eaf(pos, &tier("p", "pos", &["noun", "verb"])),
+            &TierPolicies::default(),
+        )
+        .unwrap();
+        assert_eq!(mistakes.len(), 1);
+        assert_eq!(mistakes[0].value(), Some("verb"));
+        assert_eq!(mistakes[0].annotation(), Some(("p", "p1")));
+    }
+
+    #[test]
+    fn test_missing_and_external() {
+        let tiers = tier("p", "pos", &["anything"]);
+        let mistakes = check(&eaf("", &tiers), &TierPolicies::default()).unwrap();
+        assert_eq!(
+            mistakes,
+            [VocabMistake::MissingVocabulary {
+                tier: "p".to_owned(),
+                vocabulary: Some("pos".to_owned()),
+            }]
+        );
+        assert_eq!(mistakes[0].kind(), "missing_vocabulary");
+        assert_eq!(mistakes[0].annotation(), None);
+
+        let external = r#"<CONTROLLED_VOCABULARY CV_ID="pos" EXT_REF="er1"/>"#;
+        assert_eq!(
+            check(&eaf(external, &tiers), &TierPolicies::default()).unwrap(),
+            []
+        );
+    }
+
+    #[test]
+    fn test_policies() {
+        let tiers = tier("p", "pos", &["Verb"]) + &tier("o", "ort", &["whatever"]);
+        let xml = eaf(POS, &tiers);
+        let policies = TierPolicies::new(vec![
+            PolicyRule::new("tier_id", "p", "freeform", None).unwrap(),
+            PolicyRule::new("tier_id", "o", "vocabulary", None).unwrap(),
+        ]);
+        assert_eq!(
+            check(&xml, &policies).unwrap(),
+            [VocabMistake::MissingVocabulary {
+                tier: "o".to_owned(),
+                vocabulary: None,
+            }]
+        );
+    }
+}
@@ -0,0 +1,101 @@
+//! Validation in the browser, without round trips to the backend. Build
+//! with e.g.
+//!
+//! ```sh
+//! wasm-pack build --target web eaf -- --features wasm
+//! ```
+//!
+//! and use as
+//!
+//! ```js
+//! const validator = new Validator({ atoms: ["[a-z]"], after_angle: ["SM"] });
+//! const { source, mistakes } = validator.validate("no (tak", "cs");
+//! ```
+//!
+//! The config is an object with the fields of `config::ConfigFile`. Spans
+//! of mistakes are in UTF-16 code units, like JS string indices, so
+//! `source.slice(start, end)` is the offending part of the segment.
+
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+
+use super::config::ConfigFile;
+use super::messages::{self, Lang};
+use super::offsets::Unit;
+use super::parser::{Parser, ParserConfig};
+
+#[wasm_bindgen]
+pub struct Validator {
+    config: ParserConfig,
+}
+
+#[derive(Serialize)]
+struct JsMistake<'a> {
+    kind: &'static str,
+    warning: bool,
+    message: String,
+    start: usize,
+    end: usize,
+    detail: &'a super::parser::Mistake,
+}
+
+#[derive(Serialize)]
+struct Validation<'a> {
+    source: &'a str,
+    version: String,
+    mistakes: Vec<JsMistake<'a>>,
+}
+
+#[wasm_bindgen]
+impl Validator {
+    #[wasm_bindgen(constructor)]
+    pub fn new(config: JsValue) -> Result<Validator, JsError> {
+        let config: ConfigFile = if config.is_undefined() || config.is_null() {
+            ConfigFile::default()
+        } else {
+            serde_wasm_bindgen::from_value(config)?
+        };
+        Ok(Validator {
+            config: config.into_config()?,
+        })
+    }
+
+    /// See `ParserConfig::version`, for telling whether a validation done
+    /// by the backend used the same rules.
+    pub fn version(&self) -> String {
+        self.config.version()
+    }
+
+    /// The normalized segment (`source`) and its mistakes, explained in
+    /// `lang` (English by default).
+    pub fn validate(&self, segment: &str, lang: Option<String>) -> Result<JsValue, JsError> {
+        let lang: Lang = match lang {
+            Some(lang) => lang.parse()?,
+            None => Lang::default(),
+        };
+        let parsed = Parser::parse(&self.config, self.config.tokenize(segment));
+        let mistakes = parsed
+            .mistakes
+            .iter()
+            .map(|mistake| {
+                let (_, start, end) = parsed.span_in(mistake, Unit::Utf16);
+                JsMistake {
+                    kind: mistake.kind(),
+                    warning: mistake.is_warning(),
+                    message: messages::message(&parsed, mistake, lang),
+                    start,
+                    end,
+                    detail: mistake,
+                }
+            })
+            .collect();
+        let validation = Validation {
+            source: &parsed.source,
+            version: self.config.version(),
+            mistakes,
+        };
+        // plain objects rather than Maps
+        let serializer = serde_wasm_bindgen::Serializer::json_compatible();
+        Ok(validation.serialize(&serializer)?)
+    }
+}
@@ -0,0 +1,252 @@
+//! gRPC server for batch processing pipelines which need quetzal's
+//! validation and metadata but have no use for the web frontend.
+
+use std::net::SocketAddr;
+#[cfg(feature = "spellcheck")]
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use clap::Parser;
+use tonic::{transport::Server, Request, Response, Status};
+
+use db::docs::{self, DocFilter, DocState};
+use db::Conn;
+use db::{dictionaries, palette, parser_configs, people, substitutions};
+use eaf::tokenizer::WhitespacePolicy;
+use eaf::{fixes, html, parser};
+
+mod spelling;
+
+mod proto {
+    tonic::include_proto!("quetzal");
+}
+
+use proto::quetzal_server::{Quetzal, QuetzalServer};
+
+const DEFAULT_LIMIT: usize = 10;
+const MAX_LIMIT: usize = 50;
+
+#[derive(Debug, Parser)]
+#[command(name = "quetzal-grpc", version, about)]
+struct Args {
+    /// Path to the SQLite database.
+    #[arg(long, env = "DATABASE_URL")]
+    database: String,
+    /// Address to listen on.
+    #[arg(long, default_value = "127.0.0.1:50051")]
+    listen: SocketAddr,
+    /// Directory with Hunspell dictionaries (<name>.aff and <name>.dic),
+    /// for spelling suggestions.
+    #[cfg(feature = "spellcheck")]
+    #[arg(long, env = "QUETZAL_DICTIONARIES")]
+    dictionaries: Option<PathBuf>,
+}
+
+struct Service {
+    database_url: String,
+    spelling: Arc<spelling::Spelling>,
+}
+
+impl Service {
+    /// Run a blocking DB query on a fresh connection, off the async
+    /// executor.
+    async fn with_conn<T, F>(&self, f: F) -> Result<T, Status>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Conn) -> Result<T, Status> + Send + 'static,
+    {
+        let database_url = self.database_url.clone();
+        tokio::task::spawn_blocking(move || {
+            let conn = db::connect(&database_url).map_err(|e| Status::internal(e.to_string()))?;
+            f(&conn)
+        })
+        .await
+        .map_err(|e| Status::internal(e.to_string()))?
+    }
+}
+
+fn db_error(e: diesel::result::Error) -> Status {
+    Status::internal(e.to_string())
+}
+
+#[tonic::async_trait]
+impl Quetzal for Service {
+    async fn validate(
+        &self,
+        request: Request<proto::ValidateRequest>,
+    ) -> Result<Response<proto::ValidateReply>, Status> {
+        let request = request.into_inner();
+        let mut config = request.config.unwrap_or_default();
+        let mut suggester = spelling::Suggester::default();
+        if let Some(project_id) = request.project_id {
+            let spelling = self.spelling.clone();
+            let (
+                chars,
+                attr_codes,
+                deprecated,
+                report_whitespace,
+                project_substitutions,
+                project_suggester,
+            ) = self
+                .with_conn(move |conn| {
+                    let names = dictionaries::for_project(conn, project_id).map_err(db_error)?;
+                    Ok((
+                        palette::chars(conn, project_id).map_err(db_error)?,
+                        palette::attr_codes(conn, project_id).map_err(db_error)?,
+                        palette::deprecated_attr_codes(conn, project_id).map_err(db_error)?,
+                        parser_configs::for_project(conn, project_id)
+                            .map_err(db_error)?
+                            .map_or(false, |c| c.patterns.report_whitespace),
+                        substitutions::for_project(conn, project_id).map_err(db_error)?,
+                        spelling
+                            .suggester(&names)
+                            .map_err(Status::failed_precondition)?,
+                    ))
+                })
+                .await?;
+            // the config holds regexes, the palette literal strings
+            config.atoms.extend(chars.iter().map(|c| regex::escape(c)));
+            config
+                .after_angle
+                .extend(attr_codes.iter().map(|c| regex::escape(c)));
+            config
+                .deprecated_attrs
+                .extend(deprecated.iter().map(|c| regex::escape(c)));
+            // either the request or the project can opt in
+            config.report_whitespace |= report_whitespace;
+            // the request's own substitutions take precedence
+            for (source, target) in project_substitutions {
+                config.substitutions.entry(source).or_insert(target);
+            }
+            suggester = project_suggester;
+        }
+        let whitespace = if config.report_whitespace {
+            WhitespacePolicy::Report
+        } else {
+            WhitespacePolicy::Normalize
+        };
+        let substitutions = fixes::Substitutions::new(std::mem::take(&mut config.substitutions));
+        let config = parser::ParserConfig::from_args(
+            &config.whitelist,
+            &config.blacklist,
+            &config.atoms,
+            &config.after_angle,
+        )
+        .with_deprecated_attrs(&config.deprecated_attrs)
+        .with_whitespace(whitespace);
+        let segments = request
+            .segments
+            .iter()
+            .map(|segment| {
+                let parsed = parser::Parser::parse(&config, config.tokenize(segment));
+                let mistakes = parsed
+                    .mistakes
+                    .iter()
+                    .map(|mistake| {
+                        let (token, start, end) = parsed.span(mistake);
+                        proto::Mistake {
+                            kind: mistake.kind().to_owned(),
+                            token: token.map(|t| t as u32),
+                            start: start as u32,
+                            end: end as u32,
+                            warning: mistake.is_warning(),
+                            suggestions: suggester.suggest(mistake, &parsed),
+                            fix: fixes::quick_fix(&config, &parsed, mistake, &substitutions).map(
+                                |edit| proto::Edit {
+                                    start: edit.start as u32,
+                                    end: edit.end as u32,
+                                    replacement: edit.replacement,
+                                },
+                            ),
+                        }
+                    })
+                    .collect();
+                proto::Segment {
+                    html: html::render(&parsed),
+                    normalized: parsed.source,
+                    mistakes,
+                }
+            })
+            .collect();
+        Ok(Response::new(proto::ValidateReply {
+            segments,
+            rules_version: config.version(),
+        }))
+    }
+
+    async fn list_documents(
+        &self,
+        request: Request<proto::ListDocumentsRequest>,
+    ) -> Result<Response<proto::ListDocumentsReply>, Status> {
+        let request = request.into_inner();
+        let filter = DocFilter {
+            project_id: request.project_id,
+            corpus_id: request.corpus_id,
+            assigned_to_id: None,
+            states: request
+                .states
+                .iter()
+                .map(|s| s.parse::<DocState>())
+                .collect::<Result<_, _>>()
+                .map_err(Status::invalid_argument)?,
+        };
+        let documents = self
+            .with_conn(move |conn| docs::list(conn, &filter).map_err(db_error))
+            .await?
+            .into_iter()
+            .map(|doc| proto::Document {
+                id: doc.id,
+                project_id: doc.project_id,
+                project: doc.project,
+                corpora: doc.corpora,
+                state: doc.state,
+                assigned_to_id: doc.assigned_to_id,
+            })
+            .collect();
+        Ok(Response::new(proto::ListDocumentsReply { documents }))
+    }
+
+    async fn search_speakers(
+        &self,
+        request: Request<proto::SearchSpeakersRequest>,
+    ) -> Result<Response<proto::SearchSpeakersReply>, Status> {
+        let request = request.into_inner();
+        let limit = request
+            .limit
+            .map_or(DEFAULT_LIMIT, |l| (l as usize).min(MAX_LIMIT));
+        let speakers = self
+            .with_conn(move |conn| {
+                people::search_speakers(conn, &request.query, request.project_id, limit)
+                    .map_err(db_error)
+            })
+            .await?
+            .into_iter()
+            .map(|s| proto::Speaker {
+                id: s.id,
+                nickname: s.nickname,
+                project: s.project,
+                year: s.year,
+            })
+            .collect();
+        Ok(Response::new(proto::SearchSpeakersReply { speakers }))
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::parse();
+    #[cfg(feature = "spellcheck")]
+    let spelling = spelling::Spelling::new(args.dictionaries);
+    #[cfg(not(feature = "spellcheck"))]
+    let spelling = spelling::Spelling::default();
+    let service = Service {
+        database_url: args.database,
+        spelling: Arc::new(spelling),
+    };
+    eprintln!("quetzal-grpc listening on {}", args.listen);
+    Server::builder()
+        .add_service(QuetzalServer::new(service))
+        .serve(args.listen)
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,90 @@
+//! Spelling suggestions for mistakes in validated segments. Only available
+//! when built with the `spellcheck` feature and given a directory with
+//! Hunspell dictionaries; otherwise, there are never any suggestions.
+
+#[cfg(feature = "spellcheck")]
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use eaf::parser::{Mistake, Parsed};
+#[cfg(feature = "spellcheck")]
+use eaf::spelling::{self, Dictionary};
+
+/// Maximum number of suggestions per mistake.
+#[cfg(feature = "spellcheck")]
+const SUGGESTIONS: usize = 5;
+
+#[derive(Default)]
+pub struct Spelling {
+    #[cfg(feature = "spellcheck")]
+    dir: Option<PathBuf>,
+    /// Parsing a dictionary takes a while, so they're kept around.
+    #[cfg(feature = "spellcheck")]
+    cache: Mutex<HashMap<String, Arc<Dictionary>>>,
+}
+
+#[derive(Default)]
+pub struct Suggester {
+    #[cfg(feature = "spellcheck")]
+    dictionaries: Vec<Arc<Dictionary>>,
+}
+
+impl Spelling {
+    #[cfg(feature = "spellcheck")]
+    pub fn new(dir: Option<PathBuf>) -> Self {
+        Self {
+            dir,
+            cache: Mutex::default(),
+        }
+    }
+
+    /// A suggester using the named dictionaries, in the given order.
+    #[cfg(feature = "spellcheck")]
+    pub fn suggester(&self, names: &[String]) -> Result<Suggester, String> {
+        let dir = match &self.dir {
+            Some(dir) => dir,
+            None => return Ok(Suggester::default()),
+        };
+        let mut cache = self.cache.lock().map_err(|e| e.to_string())?;
+        let mut dictionaries = vec![];
+        for name in names {
+            let dictionary = match cache.get(name) {
+                Some(dictionary) => dictionary.clone(),
+                None => {
+                    let dictionary =
+                        Arc::new(spelling::load(dir, name).map_err(|e| e.to_string())?);
+                    cache.insert(name.clone(), dictionary.clone());
+                    dictionary
+                }
+            };
+            dictionaries.push(dictionary);
+        }
+        Ok(Suggester { dictionaries })
+    }
+
+    #[cfg(not(feature = "spellcheck"))]
+    pub fn suggester(&self, _names: &[String]) -> Result<Suggester, String> {
+        Ok(Suggester::default())
+    }
+}
+
+impl Suggester {
+    #[cfg(feature = "spellcheck")]
+    pub fn suggest(&self, mistake: &Mistake, parsed: &Parsed) -> Vec<String> {
+        let dictionaries: Vec<_> = self.dictionaries.iter().map(|d| &**d).collect();
+        match spelling::misspelled(mistake, parsed) {
+            Some(word) if !dictionaries.is_empty() => {
+                spelling::suggest(&dictionaries, word, SUGGESTIONS)
+            }
+            _ => vec![],
+        }
+    }
+
+    #[cfg(not(feature = "spellcheck"))]
+    pub fn suggest(&self, _mistake: &Mistake, _parsed: &Parsed) -> Vec<String> {
+        vec![]
+    }
+}
@@ -0,0 +1,118 @@
+//! Reviewers acknowledging mistakes as intentional, so that they don't
+//! count in later validations of the document (see `db::acknowledgments`).
+
+use db::acknowledgments::{self, NewAcknowledgment};
+use eaf::intervals::IntervalMistake;
+use eaf::media::BeyondMedia;
+use eaf::parser::Mistake;
+use eaf::policies::PatternMismatch;
+use eaf::timeslots::SlotMistake;
+use eaf::vocabularies::VocabMistake;
+use rocket::http::Status;
+use serde::Deserialize;
+use validator::Validate;
+
+use super::api::{self, ApiResult};
+use super::body::Valid;
+use super::conn::DbConn;
+use super::tenancy::{Allowed, DocReview, Viewer};
+
+/// The mistake as found by validation, see `mistake-patterns` and reports.
+#[derive(Debug, Deserialize, Validate)]
+pub struct AcknowledgmentRequest {
+    tier: String,
+    annotation: String,
+    kind: String,
+    segment: String,
+    #[validate(range(min = 0))]
+    start: Option<i32>,
+    /// Why the mistake is intentional, e.g. "dialectal form".
+    #[validate(length(min = 1))]
+    reason: String,
+}
+
+#[post("/documents/<doc_id>/acknowledgments", data = "<request>")]
+pub async fn create(
+    conn: DbConn,
+    viewer: Allowed<DocReview>,
+    doc_id: i32,
+    request: Valid<AcknowledgmentRequest>,
+) -> ApiResult {
+    conn.run(move |conn| {
+        viewer.doc(conn, doc_id)?;
+        let request = request.into_inner();
+        let known = Mistake::KINDS
+            .iter()
+            .chain(SlotMistake::KINDS)
+            .chain(IntervalMistake::KINDS)
+            .chain(VocabMistake::KINDS)
+            .chain(&[PatternMismatch::KIND, BeyondMedia::KIND]);
+        if !known.into_iter().any(|&kind| kind == request.kind) {
+            return Err(api::error(
+                Status::UnprocessableEntity,
+                format!("unknown kind of mistake {:?}", request.kind),
+            ));
+        }
+        let acknowledgment = NewAcknowledgment {
+            doc_id,
+            tier: request.tier,
+            annotation: request.annotation,
+            kind: request.kind,
+            segment: request.segment,
+            start: request.start,
+            reason: request.reason,
+            author_id: viewer.user_id,
+        };
+        match acknowledgments::add(conn, &acknowledgment) {
+            Ok(id) => api::ok(json!({ "id": id })),
+            Err(diesel::result::Error::DatabaseError(_, _)) => Err(api::error(
+                Status::Conflict,
+                "the mistake has already been acknowledged",
+            )),
+            Err(e) => Err(api::internal(e)),
+        }
+    })
+    .await
+}
+
+/// The document's acknowledged mistakes, oldest first.
+#[get("/documents/<doc_id>/acknowledgments")]
+pub async fn list(conn: DbConn, viewer: Viewer, doc_id: i32) -> ApiResult {
+    conn.run(move |conn| {
+        viewer.doc(conn, doc_id)?;
+        let acknowledgments: Vec<_> = acknowledgments::for_doc(conn, doc_id)
+            .map_err(api::internal)?
+            .into_iter()
+            .map(|a| {
+                json!({
+                    "id": a.id,
+                    "tier": a.tier,
+                    "annotation": a.annotation,
+                    "kind": a.kind,
+                    "segment": a.segment,
+                    "start": a.start,
+                    "reason": a.reason,
+                    "author_id": a.author_id,
+                    "author": a.author,
+                    "created_at": a.created_at.to_string(),
+                })
+            })
+            .collect();
+        api::ok(json!(acknowledgments))
+    })
+    .await
+}
+
+/// The mistake counts again from the next validation of the document on.
+#[delete("/documents/<doc_id>/acknowledgments/<id>")]
+pub async fn delete(conn: DbConn, viewer: Allowed<DocReview>, doc_id: i32, id: i32) -> ApiResult {
+    conn.run(move |conn| {
+        viewer.doc(conn, doc_id)?;
+        if acknowledgments::remove(conn, doc_id, id).map_err(api::internal)? {
+            api::ok(json!(null))
+        } else {
+            Err(api::error(Status::NotFound, "no such acknowledgment"))
+        }
+    })
+    .await
+}
@@ -0,0 +1,324 @@
+//! Word-level time alignments of transcripts, e.g. from forced alignment,
+//! for prosody research. They're checked against the transcript version
+//! they're for and included in CoNLL-U and vertical exports (see
+//! `eaf::conllu` and `eaf::vertical`). Exports can be anonymized, replacing
+//! tokens in the project's anonymization spans by placeholders (see
+//! `eaf::anonymize::Placeholders`); word numbering stays the same.
+
+use std::collections::HashMap;
+use std::fs;
+
+use db::alignments::{self, WordAlignment};
+use db::audit;
+use db::files::{self, File};
+use db::Conn;
+use diesel::result::Error;
+use eaf::annotations::{self, Annotation};
+use eaf::anonymize::Placeholders;
+use eaf::conllu::{self, WordTimes};
+use eaf::tiers::TierMapping;
+use eaf::vertical;
+use rocket::http::{ContentType, Status};
+use rocket::response::status::Custom;
+use rocket::serde::json::Value;
+use rocket::State;
+use serde::Deserialize;
+
+use super::api::{self, ApiResult};
+use super::body::JsonBody;
+use super::conn::DbConn;
+use super::exports::{Export, ExportKey, IfNoneMatch};
+use super::rules;
+use super::storage::Storage;
+use super::tenancy::{Allowed, DocEdit, Viewer};
+use super::tiers;
+
+/// Audit log action, per document.
+const CHANGED: &str = "document.alignments_changed";
+
+#[derive(Debug, Deserialize)]
+pub struct WordRequest {
+    annotation: String,
+    /// Index of the word within the annotation, from 0.
+    word: usize,
+    /// The word as aligned, which must match the transcript.
+    form: String,
+    /// In milliseconds.
+    start: u32,
+    end: u32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AlignmentsRequest {
+    /// The transcript version the words were aligned with, the latest one
+    /// by default.
+    file_id: Option<i32>,
+    words: Vec<WordRequest>,
+}
+
+/// The document's transcript, either the given version or the latest one.
+fn transcript(conn: &Conn, doc_id: i32, file_id: Option<i32>) -> Result<File, Custom<Value>> {
+    let file = match file_id {
+        Some(id) => match files::get(conn, id) {
+            Ok(file) if file.doc_id == doc_id && file.role == files::EAF => Some(file),
+            Ok(_) | Err(Error::NotFound) => None,
+            Err(e) => return Err(api::internal(e)),
+        },
+        None => files::latest(conn, doc_id, &[files::EAF]).map_err(api::internal)?,
+    };
+    file.ok_or_else(|| api::error(Status::NotFound, "no such transcript"))
+}
+
+/// Words of the transcript's annotations, by annotation ID.
+fn words(storage: &Storage, file: &File) -> Result<HashMap<String, Vec<String>>, Custom<Value>> {
+    let xml = fs::read_to_string(storage.path(&file.path)).map_err(api::internal)?;
+    let annotations =
+        annotations::read(&xml).map_err(|e| api::error(Status::UnprocessableEntity, e))?;
+    Ok(annotations
+        .into_iter()
+        .map(|a| (a.id, conllu::words(&a.value)))
+        .collect())
+}
+
+fn check(word: &WordRequest, words: &HashMap<String, Vec<String>>) -> Result<(), String> {
+    let forms = words
+        .get(&word.annotation)
+        .ok_or_else(|| format!("no annotation {}", word.annotation))?;
+    let form = forms.get(word.word).ok_or_else(|| {
+        format!(
+            "annotation {} has only {} word(s)",
+            word.annotation,
+            forms.len()
+        )
+    })?;
+    if *form != word.form {
+        return Err(format!(
+            "word {} is {:?}, not {:?}",
+            word.word, form, word.form
+        ));
+    }
+    if word.end < word.start {
+        return Err("end must not be before start".to_owned());
+    }
+    Ok(())
+}
+
+/// Store word alignments of a version of the document's transcript,
+/// replacing any stored before. Words are numbered within their annotation
+/// the same way as in CoNLL-U exports and the forms must match; the
+/// alignments are rejected as a whole otherwise, since mismatches usually
+/// mean they're for another version of the transcript.
+#[put("/documents/<doc_id>/alignments", data = "<request>")]
+pub async fn put(
+    conn: DbConn,
+    viewer: Allowed<DocEdit>,
+    storage: &State<Storage>,
+    doc_id: i32,
+    request: JsonBody<AlignmentsRequest>,
+) -> ApiResult {
+    let storage = storage.inner().clone();
+    conn.run(move |conn| {
+        viewer.doc(conn, doc_id)?;
+        let file = transcript(conn, doc_id, request.file_id)?;
+        let words = words(&storage, &file)?;
+        let errors: Vec<_> = request
+            .words
+            .iter()
+            .enumerate()
+            .filter_map(|(i, word)| {
+                check(word, &words).err().map(|message| {
+                    json!({
+                        "message": message,
+                        "field": format!("words[{}]", i),
+                        "kind": "value",
+                    })
+                })
+            })
+            .collect();
+        if !errors.is_empty() {
+            return Err(Custom(
+                Status::UnprocessableEntity,
+                json!({ "data": null, "errors": errors }),
+            ));
+        }
+        let alignments: Vec<_> = request
+            .into_inner()
+            .words
+            .into_iter()
+            .map(|word| WordAlignment {
+                annotation: word.annotation,
+                word: word.word as i32,
+                form: word.form,
+                start: word.start as i32,
+                end: word.end as i32,
+            })
+            .collect();
+        let stored =
+            alignments::replace(conn, doc_id, file.id, &alignments).map_err(|e| match e {
+                // the same word twice
+                Error::DatabaseError(_, _) => api::error(Status::UnprocessableEntity, e),
+                e => api::internal(e),
+            })?;
+        let details = json!({ "file_id": file.id, "words": stored });
+        audit::record(
+            conn,
+            Some(viewer.user_id),
+            CHANGED,
+            "document",
+            doc_id,
+            &details,
+        )
+        .map_err(api::internal)?;
+        api::ok(json!({ "file_id": file.id, "words": stored }))
+    })
+    .await
+}
+
+/// Word alignments of a version of the document's transcript, the latest
+/// one by default, in start order.
+#[get("/documents/<doc_id>/alignments?<file>")]
+pub async fn get(conn: DbConn, viewer: Viewer, doc_id: i32, file: Option<i32>) -> ApiResult {
+    conn.run(move |conn| {
+        viewer.doc(conn, doc_id)?;
+        let file = transcript(conn, doc_id, file)?;
+        let words: Vec<_> = alignments::for_file(conn, file.id)
+            .map_err(api::internal)?
+            .into_iter()
+            .map(|a| {
+                json!({
+                    "annotation": a.annotation,
+                    "word": a.word,
+                    "form": a.form,
+                    "start": a.start,
+                    "end": a.end,
+                })
+            })
+            .collect();
+        api::ok(json!({ "file_id": file.id, "words": words }))
+    })
+    .await
+}
+
+/// The document's latest transcript exported by `write`, with word
+/// alignments, optionally anonymized. Cached until the transcript, the tier
+/// mapping or the alignments change (see `exports`), or, if anonymized, the
+/// anonymization codes.
+#[allow(clippy::too_many_arguments)]
+fn export<F>(
+    conn: &Conn,
+    viewer: &Viewer,
+    storage: &Storage,
+    if_none_match: &IfNoneMatch,
+    doc_id: i32,
+    format: &'static str,
+    anonymize: bool,
+    write: F,
+) -> Result<Export, Custom<Value>>
+where
+    F: FnOnce(&[Annotation], &TierMapping, &WordTimes) -> String,
+{
+    let project_id = viewer.doc(conn, doc_id)?;
+    let file = files::latest(conn, doc_id, &[files::EAF])
+        .map_err(api::internal)?
+        .ok_or_else(|| api::error(Status::NotFound, "document has no transcript"))?;
+    let mapping = tiers::tier_mapping(conn, project_id).map_err(api::internal)?;
+    let alignments = alignments::for_file(conn, file.id).map_err(api::internal)?;
+    let config = if anonymize {
+        Some(rules::project_config(conn, project_id).map_err(api::internal)?)
+    } else {
+        None
+    };
+    let mut inputs = format!("{:?}\n{:?}", mapping, alignments);
+    let format = match &config {
+        Some(config) => {
+            inputs.push_str(&format!("\n{:?}", config.anonymized()));
+            format!("{}_anonymized", format)
+        }
+        None => format.to_owned(),
+    };
+    let key = ExportKey {
+        doc_id,
+        file_id: file.id,
+        format: &format,
+        inputs,
+    };
+    let content_type = ContentType::with_params("text", "plain", ("charset", "utf-8"));
+    Export::get(storage, &key, content_type, if_none_match, false, || {
+        let xml = fs::read_to_string(storage.path(&file.path)).map_err(api::internal)?;
+        let mut annotations =
+            annotations::read(&xml).map_err(|e| api::error(Status::UnprocessableEntity, e))?;
+        if let Some(config) = &config {
+            let mut placeholders = Placeholders::default();
+            for annotation in &mut annotations {
+                annotation.value = placeholders.scrub(config, &annotation.value);
+            }
+        }
+        let times: WordTimes = alignments
+            .iter()
+            .map(|a| {
+                (
+                    (a.annotation.clone(), a.word as usize),
+                    (a.start as u32, a.end as u32),
+                )
+            })
+            .collect();
+        Ok(write(&annotations, &mapping, &times).into_bytes())
+    })
+}
+
+/// The document's latest transcript as CoNLL-U, with word alignments in
+/// the MISC column.
+#[get("/documents/<doc_id>/conllu?<anonymize>")]
+pub async fn conllu(
+    conn: DbConn,
+    viewer: Viewer,
+    storage: &State<Storage>,
+    if_none_match: IfNoneMatch,
+    doc_id: i32,
+    anonymize: Option<bool>,
+) -> Result<Export, Custom<Value>> {
+    let storage = storage.inner().clone();
+    conn.run(move |conn| {
+        export(
+            conn,
+            &viewer,
+            &storage,
+            &if_none_match,
+            doc_id,
+            "conllu",
+            anonymize.unwrap_or(false),
+            conllu::write,
+        )
+    })
+    .await
+}
+
+/// The document's latest transcript in the vertical format of corpus
+/// managers (see `eaf::vertical`), for building corpora of several
+/// documents by concatenating them.
+#[get("/documents/<doc_id>/vertical?<anonymize>")]
+pub async fn vertical(
+    conn: DbConn,
+    viewer: Viewer,
+    storage: &State<Storage>,
+    if_none_match: IfNoneMatch,
+    doc_id: i32,
+    anonymize: Option<bool>,
+) -> Result<Export, Custom<Value>> {
+    let storage = storage.inner().clone();
+    conn.run(move |conn| {
+        export(
+            conn,
+            &viewer,
+            &storage,
+            &if_none_match,
+            doc_id,
+            "vertical",
+            anonymize.unwrap_or(false),
+            |annotations, mapping, times| {
+                vertical::write(&doc_id.to_string(), annotations, mapping, times)
+            },
+        )
+    })
+    .await
+}
@@ -0,0 +1,33 @@
+//! Helpers for building API responses in the `{"data": …, "errors": […]}`
+//! envelope expected by the frontend.
+
+use rocket::http::Status;
+use rocket::response::status::Custom;
+use rocket::serde::json::Value;
+
+/// Default and maximum number of results for endpoints which return lists
+/// of suggestions.
+const DEFAULT_LIMIT: usize = 10;
+const MAX_LIMIT: usize = 50;
+
+pub type ApiResult = Result<Value, Custom<Value>>;
+
+pub fn ok(data: Value) -> ApiResult {
+    Ok(json!({ "data": data, "errors": [] }))
+}
+
+pub fn error<E: ToString>(status: Status, error: E) -> Custom<Value> {
+    Custom(
+        status,
+        json!({ "data": null, "errors": [{ "message": error.to_string() }] }),
+    )
+}
+
+/// Most database errors are our fault, not the client's.
+pub fn internal<E: ToString>(error: E) -> Custom<Value> {
+    self::error(Status::InternalServerError, error)
+}
+
+pub fn limit(limit: Option<usize>) -> usize {
+    limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT)
+}
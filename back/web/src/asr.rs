@@ -0,0 +1,204 @@
+//! Draft transcripts from automatic speech recognition, and the machinery
+//! for turning timed segments into stored EAFs in general.
+
+use chrono::Local;
+use db::docs;
+use db::files;
+use db::Conn;
+use diesel::result::Error;
+use eaf::asr::{self, Segment};
+use eaf::draft::{Draft, DraftTier};
+use eaf::tiers::{TierMapping, TierSource};
+use rocket::data::{Data, ToByteUnit};
+use rocket::http::Status;
+use rocket::response::status::Custom;
+use rocket::serde::json::Value;
+use rocket::State;
+
+use super::api::{self, ApiResult};
+use super::conn::DbConn;
+use super::storage::{FileInfo, Storage};
+use super::tenancy::{Allowed, DocEdit};
+use super::tiers;
+
+/// Whisper JSON for a long recording can get fairly big, but not this big.
+const ASR_LIMIT: u64 = 16 * 1024 * 1024;
+
+/// Nickname for segments without a diarization label, unless given.
+const DEFAULT_SPEAKER: &str = "ASR";
+
+#[derive(FromForm)]
+pub struct AsrParams {
+    /// `whisper` or `plain`; guessed from the input if missing.
+    format: Option<String>,
+    /// Nickname of the speaker of unlabeled segments.
+    speaker: Option<String>,
+    /// ID of the user importing the draft.
+    user: Option<i32>,
+}
+
+/// Tier ID and participant for a speaker's tier, following the first
+/// matching kind of rule in the project's tier mapping.
+fn tier_names(mapping: &TierMapping, nickname: &str) -> (String, Option<String>) {
+    let fill = |source| {
+        mapping
+            .rules()
+            .iter()
+            .find(|(s, _)| *s == source)
+            .map(|(_, pattern)| pattern.fill(nickname))
+    };
+    (
+        fill(TierSource::TierId).unwrap_or_else(|| nickname.to_owned()),
+        Some(fill(TierSource::Participant).unwrap_or_else(|| nickname.to_owned())),
+    )
+}
+
+/// One tier per speaker, in order of first appearance.
+fn group(segments: Vec<Segment>, default_speaker: &str) -> Vec<(String, Vec<Segment>)> {
+    let mut speakers: Vec<(String, Vec<Segment>)> = vec![];
+    for segment in segments {
+        let nickname = segment.speaker.as_deref().unwrap_or(default_speaker);
+        match speakers.iter_mut().find(|(n, _)| n == nickname) {
+            Some((_, segments)) => segments.push(segment),
+            None => speakers.push((nickname.to_owned(), vec![segment])),
+        }
+    }
+    speakers
+}
+
+/// Create a draft EAF for the document from ASR output in the request body
+/// (Whisper JSON or tab-separated plain text), with one tier per speaker
+/// named according to the project's tier mapping.
+#[post("/documents/<doc_id>/asr?<params..>", data = "<body>")]
+pub async fn import(
+    conn: DbConn,
+    viewer: Allowed<DocEdit>,
+    storage: &State<Storage>,
+    doc_id: i32,
+    params: AsrParams,
+    body: Data<'_>,
+) -> ApiResult {
+    conn.run(move |conn| viewer.doc(conn, doc_id)).await?;
+    let input = read_body(body, ASR_LIMIT).await?;
+    let format = match params.format.as_deref() {
+        Some(format) => format,
+        None if input.trim_start().starts_with('{') => "whisper",
+        None => "plain",
+    };
+    let segments = match format {
+        "whisper" => asr::from_whisper_json(&input),
+        "plain" => asr::from_plain(&input),
+        _ => {
+            return Err(api::error(
+                Status::BadRequest,
+                format!("unknown ASR format {:?}", format),
+            ))
+        }
+    }
+    .map_err(|e| api::error(Status::UnprocessableEntity, e))?;
+    let storage = storage.inner().clone();
+    conn.run(move |conn| {
+        store_eaf(
+            conn,
+            &storage,
+            doc_id,
+            segments,
+            params.speaker.as_deref().unwrap_or(DEFAULT_SPEAKER),
+            EafInfo {
+                author: "ASR",
+                name: "draft",
+                role: files::DRAFT_EAF,
+                created_by: params.user,
+            },
+        )
+    })
+    .await
+}
+
+/// The request body as text, failing with 413 if it's over `limit` bytes.
+pub async fn read_body(body: Data<'_>, limit: u64) -> Result<String, Custom<Value>> {
+    let input = body
+        .open(limit.bytes())
+        .into_string()
+        .await
+        .map_err(|e| api::error(Status::BadRequest, e))?;
+    if !input.is_complete() {
+        return Err(api::error(
+            Status::PayloadTooLarge,
+            format!("the body is larger than {} bytes", limit),
+        ));
+    }
+    Ok(input.into_inner())
+}
+
+pub struct EafInfo<'a> {
+    pub author: &'a str,
+    /// Prefix of the file name, which is followed by a timestamp.
+    pub name: &'a str,
+    pub role: &'a str,
+    pub created_by: Option<i32>,
+}
+
+/// Store timed segments as a new EAF of the document, with one tier per
+/// speaker named according to the project's tier mapping.
+pub fn store_eaf(
+    conn: &Conn,
+    storage: &Storage,
+    doc_id: i32,
+    segments: Vec<Segment>,
+    default_speaker: &str,
+    info: EafInfo,
+) -> ApiResult {
+    let project_id = match docs::project_of(conn, doc_id) {
+        Ok(project_id) => project_id,
+        Err(Error::NotFound) => return Err(api::error(Status::NotFound, "no such document")),
+        Err(e) => return Err(api::internal(e)),
+    };
+    if segments.is_empty() {
+        return Err(api::error(Status::UnprocessableEntity, "no segments found"));
+    }
+
+    let mapping = tiers::tier_mapping(conn, project_id).map_err(api::internal)?;
+    let tiers: Vec<_> = group(segments, default_speaker)
+        .into_iter()
+        .map(|(nickname, segments)| {
+            let (id, participant) = tier_names(&mapping, &nickname);
+            DraftTier {
+                id,
+                participant,
+                segments,
+            }
+        })
+        .collect();
+    let now = Local::now();
+    let draft = Draft {
+        author: info.author.to_owned(),
+        date: now.to_rfc3339(),
+        media_url: None,
+        tiers,
+    };
+
+    let name = format!("{}-{}.eaf", info.name, now.format("%Y%m%d-%H%M%S"));
+    let xml = draft.to_xml();
+    let file_id = storage
+        .store(
+            conn,
+            doc_id,
+            &name,
+            xml.as_bytes(),
+            xml.len() as u64,
+            FileInfo {
+                role: info.role,
+                mime: "application/xml",
+                created_by: info.created_by,
+                source_id: None,
+            },
+        )
+        .map_err(api::internal)?;
+    let tiers: Vec<_> = draft
+        .tiers
+        .iter()
+        .map(|t| json!({ "id": t.id, "participant": t.participant, "segments": t.segments.len() }))
+        .collect();
+    api::ok(json!({ "file_id": file_id, "tiers": tiers }))
+}
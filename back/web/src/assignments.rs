@@ -0,0 +1,139 @@
+//! Assigning documents to transcribers and submitting them for review (see
+//! `reviews`). Both are recorded in the audit log, and supervisors can see
+//! everyone's workload per project.
+
+use chrono::{Local, NaiveDate};
+use db::docs::{self, DocState};
+use db::Conn;
+use db::{audit, members};
+use rocket::http::Status;
+use serde::Deserialize;
+
+use super::api::{self, ApiResult};
+use super::body::JsonBody;
+use super::conn::DbConn;
+use super::tenancy::{Allowed, DocAssign, Viewer};
+use super::webhooks;
+
+/// Audit log actions, per document.
+const ASSIGNED: &str = "document.assigned";
+const DONE: &str = "document.done";
+
+#[derive(Debug, Deserialize)]
+pub struct AssignRequest {
+    user_id: i32,
+    /// When the transcript should be done, `YYYY-MM-DD`; it's overdue
+    /// after the end of that day.
+    due: Option<NaiveDate>,
+}
+
+fn state_changed(conn: &Conn, project_id: i32, doc_id: i32, from: DocState, to: DocState) {
+    if from != to {
+        let data = json!({ "doc_id": doc_id, "from": from.label(), "to": to.label() });
+        webhooks::fire(conn, project_id, db::webhooks::STATE_CHANGED, data);
+    }
+}
+
+/// Assign the document to a member of its project, or reassign it to
+/// someone else. Either way, the transcript starts over as not done.
+#[post("/documents/<doc_id>/assign", data = "<request>")]
+pub async fn assign(
+    conn: DbConn,
+    viewer: Allowed<DocAssign>,
+    doc_id: i32,
+    request: JsonBody<AssignRequest>,
+) -> ApiResult {
+    conn.run(move |conn| {
+        let project_id = viewer.doc(conn, doc_id)?;
+        let request = request.into_inner();
+        let access = members::access(conn, request.user_id).map_err(api::internal)?;
+        if !access.allows(project_id) {
+            return Err(api::error(
+                Status::UnprocessableEntity,
+                format!("user {} isn't a member of the project", request.user_id),
+            ));
+        }
+        let (_, previous) = docs::access_of(conn, doc_id).map_err(api::internal)?;
+        let from = docs::state_of(conn, doc_id).map_err(api::internal)?;
+        let due_at = request.due.map(|d| d.and_hms_opt(23, 59, 59).unwrap());
+        docs::assign(conn, doc_id, request.user_id, viewer.user_id, due_at)
+            .map_err(api::internal)?;
+        let details = json!({
+            "from": previous,
+            "to": request.user_id,
+            "due_at": due_at.map(|d| d.to_string()),
+        });
+        audit::record(
+            conn,
+            Some(viewer.user_id),
+            ASSIGNED,
+            "document",
+            doc_id,
+            &details,
+        )
+        .map_err(api::internal)?;
+        state_changed(conn, project_id, doc_id, from, DocState::Assigned);
+        api::ok(json!({ "id": doc_id, "assigned_to_id": request.user_id }))
+    })
+    .await
+}
+
+/// Mark the transcript as done and submit it for review. Only the assignee
+/// can do that, and only while the document is assigned or returned.
+#[post("/documents/<doc_id>/done")]
+pub async fn done(conn: DbConn, viewer: Viewer, doc_id: i32) -> ApiResult {
+    conn.run(move |conn| {
+        let project_id = viewer.doc(conn, doc_id)?;
+        let (_, assigned_to_id) = docs::access_of(conn, doc_id).map_err(api::internal)?;
+        if assigned_to_id != Some(viewer.user_id) {
+            return Err(api::error(
+                Status::Forbidden,
+                "can only mark documents assigned to you as done",
+            ));
+        }
+        let from = docs::state_of(conn, doc_id).map_err(api::internal)?;
+        if !docs::mark_done(conn, doc_id).map_err(api::internal)? {
+            return Err(api::error(
+                Status::Conflict,
+                format!("document is {}, not in progress", from),
+            ));
+        }
+        let details = json!({ "from": from.label() });
+        audit::record(
+            conn,
+            Some(viewer.user_id),
+            DONE,
+            "document",
+            doc_id,
+            &details,
+        )
+        .map_err(api::internal)?;
+        state_changed(conn, project_id, doc_id, from, DocState::Submitted);
+        api::ok(json!({ "id": doc_id, "state": DocState::Submitted.label() }))
+    })
+    .await
+}
+
+/// Per transcriber, how many of their documents in the project are in
+/// each state, and how many are overdue.
+#[get("/projects/<project_id>/workload")]
+pub async fn workload(conn: DbConn, viewer: Allowed<DocAssign>, project_id: i32) -> ApiResult {
+    conn.run(move |conn| {
+        viewer.project(project_id)?;
+        let now = Local::now().naive_local();
+        let workload: Vec<_> = docs::workload(conn, project_id, now)
+            .map_err(api::internal)?
+            .into_iter()
+            .map(|w| {
+                json!({
+                    "user_id": w.user_id,
+                    "username": w.username,
+                    "states": w.states,
+                    "overdue": w.overdue,
+                })
+            })
+            .collect();
+        api::ok(json!(workload))
+    })
+    .await
+}
@@ -0,0 +1,153 @@
+//! Recordings: upload and transcoding to formats browsers can play.
+
+use std::path::Path;
+use std::process::Command;
+
+use chrono::Local;
+use db::Conn;
+use db::{files, jobs};
+use rocket::http::Status;
+use rocket::{Data, State};
+
+use super::api::{self, ApiResult};
+use super::conn::DbConn;
+use super::storage::{FileInfo, Storage, StorageError};
+use super::tenancy::{Allowed, DocEdit};
+
+/// Uncompressed recordings of long sessions are big, but not this big.
+const AUDIO_LIMIT: u64 = 4 * 1024 * 1024 * 1024;
+
+struct Variant {
+    role: &'static str,
+    ext: &'static str,
+    mime: &'static str,
+    /// ffmpeg output options.
+    args: &'static [&'static str],
+}
+
+const VARIANTS: &[Variant] = &[
+    Variant {
+        role: files::AUDIO_OPUS,
+        ext: "opus",
+        mime: "audio/ogg",
+        args: &["-c:a", "libopus", "-b:a", "64k"],
+    },
+    Variant {
+        role: files::AUDIO_MP3,
+        ext: "mp3",
+        mime: "audio/mpeg",
+        args: &["-c:a", "libmp3lame", "-q:a", "4"],
+    },
+    Variant {
+        role: files::AUDIO_PREVIEW,
+        ext: "preview.opus",
+        mime: "audio/ogg",
+        args: &["-ac", "1", "-c:a", "libopus", "-b:a", "16k"],
+    },
+];
+
+/// Extension and MIME type of supported uploads, going by their magic
+/// bytes.
+fn sniff(head: &[u8]) -> Option<(&'static str, &'static str)> {
+    if head.len() >= 12 && &head[..4] == b"RIFF" && &head[8..12] == b"WAVE" {
+        Some(("wav", "audio/wav"))
+    } else if head.starts_with(b"fLaC") {
+        Some(("flac", "audio/flac"))
+    } else {
+        None
+    }
+}
+
+/// Upload a WAV or FLAC recording for the document. Web-friendly variants
+/// are created in the background, see `transcode`, and so is revalidation
+/// of the transcript against the recording, see `revalidation`.
+#[post("/documents/<doc_id>/audio?<user>", data = "<body>")]
+pub async fn upload(
+    conn: DbConn,
+    viewer: Allowed<DocEdit>,
+    storage: &State<Storage>,
+    doc_id: i32,
+    user: Option<i32>,
+    mut body: Data<'_>,
+) -> ApiResult {
+    conn.run(move |conn| viewer.doc(conn, doc_id)).await?;
+    let (ext, mime) = sniff(body.peek(12).await).ok_or_else(|| {
+        api::error(
+            Status::UnsupportedMediaType,
+            "only WAV and FLAC recordings are supported",
+        )
+    })?;
+    let name = format!("audio-{}.{}", Local::now().format("%Y%m%d-%H%M%S"), ext);
+    let relative = storage
+        .receive(doc_id, &name, body, AUDIO_LIMIT)
+        .await
+        .map_err(|e| match e {
+            StorageError::TooLarge(_) => api::error(Status::PayloadTooLarge, e),
+            _ => api::internal(e),
+        })?;
+    let storage = storage.inner().clone();
+    conn.run(move |conn| {
+        let file_id = storage
+            .record(
+                conn,
+                doc_id,
+                relative,
+                FileInfo {
+                    role: files::AUDIO,
+                    mime,
+                    created_by: user,
+                    source_id: None,
+                },
+            )
+            .map_err(api::internal)?;
+        let job_id = jobs::enqueue(conn, jobs::TRANSCODE, file_id).map_err(api::internal)?;
+        if let Some(eaf) = files::latest(conn, doc_id, &[files::EAF]).map_err(api::internal)? {
+            if !jobs::is_queued(conn, jobs::REVALIDATE, eaf.id).map_err(api::internal)? {
+                jobs::enqueue(conn, jobs::REVALIDATE, eaf.id).map_err(api::internal)?;
+            }
+        }
+        api::ok(json!({ "file_id": file_id, "job_id": job_id }))
+    })
+    .await
+}
+
+/// Create all variants of an uploaded recording with ffmpeg.
+pub fn transcode(conn: &Conn, storage: &Storage, ffmpeg: &str, file_id: i32) -> Result<(), String> {
+    let source = files::get(conn, file_id).map_err(|e| e.to_string())?;
+    let stem = Path::new(&source.path)
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .ok_or_else(|| format!("bad path {:?}", source.path))?;
+    for variant in VARIANTS {
+        let name = format!("{}.{}", stem, variant.ext);
+        let relative = Storage::doc_path(source.doc_id, &name);
+        let output = Command::new(ffmpeg)
+            .args(["-y", "-nostdin", "-v", "error", "-i"])
+            .arg(storage.path(&source.path))
+            .args(variant.args)
+            .arg(storage.path(&relative))
+            .output()
+            .map_err(|e| format!("can't run {}: {}", ffmpeg, e))?;
+        if !output.status.success() {
+            return Err(format!(
+                "{} failed: {}",
+                variant.role,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        storage
+            .record(
+                conn,
+                source.doc_id,
+                relative,
+                FileInfo {
+                    role: variant.role,
+                    mime: variant.mime,
+                    created_by: source.created_by,
+                    source_id: Some(source.id),
+                },
+            )
+            .map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
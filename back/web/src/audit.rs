@@ -0,0 +1,84 @@
+//! The audit log (see `db::audit`) per document and per user, for
+//! supervisors to check who changed what. Entries are newest first; pass
+//! the ID of the last one as `before` to get older ones.
+
+use db::audit::{self, AuditFilter, Entry};
+use db::Conn;
+use rocket::http::Status;
+
+use super::api::{self, ApiResult};
+use super::conn::DbConn;
+use super::tenancy::{Allowed, AuditView};
+
+/// Entries returned unless requested otherwise.
+const DEFAULT_LIMIT: i64 = 100;
+const MAX_LIMIT: i64 = 1000;
+
+fn entries(conn: &Conn, filter: AuditFilter, limit: Option<i64>) -> ApiResult {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT);
+    if !(1..=MAX_LIMIT).contains(&limit) {
+        return Err(api::error(
+            Status::BadRequest,
+            format!("limit must be between 1 and {}", MAX_LIMIT),
+        ));
+    }
+    let filter = AuditFilter { limit, ..filter };
+    let entries: Vec<_> = audit::query(conn, &filter)
+        .map_err(api::internal)?
+        .into_iter()
+        .map(|e: Entry| {
+            json!({
+                "id": e.id,
+                "user_id": e.user_id,
+                "username": e.username,
+                "action": e.action,
+                "entity": e.entity,
+                "entity_id": e.entity_id,
+                "details": e.details,
+                "created_at": e.created_at.to_string(),
+            })
+        })
+        .collect();
+    api::ok(json!(entries))
+}
+
+#[get("/documents/<doc_id>/audit?<before>&<limit>")]
+pub async fn document(
+    conn: DbConn,
+    viewer: Allowed<AuditView>,
+    doc_id: i32,
+    before: Option<i32>,
+    limit: Option<i64>,
+) -> ApiResult {
+    conn.run(move |conn| {
+        viewer.doc(conn, doc_id)?;
+        let filter = AuditFilter {
+            entity: Some(("document", doc_id)),
+            before,
+            ..AuditFilter::default()
+        };
+        entries(conn, filter, limit)
+    })
+    .await
+}
+
+/// What the user did, across all entities.
+#[get("/users/<user_id>/audit?<before>&<limit>")]
+pub async fn user(
+    conn: DbConn,
+    viewer: Allowed<AuditView>,
+    user_id: i32,
+    before: Option<i32>,
+    limit: Option<i64>,
+) -> ApiResult {
+    conn.run(move |conn| {
+        viewer.colleague(conn, user_id)?;
+        let filter = AuditFilter {
+            user_id: Some(user_id),
+            before,
+            ..AuditFilter::default()
+        };
+        entries(conn, filter, limit)
+    })
+    .await
+}
@@ -0,0 +1,85 @@
+//! Administration of backups. Restoring is left to the CLI, as it shouldn't
+//! be done under a running server.
+
+use std::fs;
+use std::path::PathBuf;
+
+use chrono::Local;
+use db::backup::{self, BackupError, Manifest};
+use rocket::http::Status;
+use rocket::serde::json::Value;
+use rocket::State;
+
+use super::api::{self, ApiResult};
+use super::conn::DbConn;
+use super::storage::Storage;
+use super::tenancy::{Allowed, BackupManage};
+
+/// Where backups go, from the `backup_dir` config key.
+#[derive(Debug, Clone)]
+pub struct BackupDir(pub PathBuf);
+
+fn summary(name: &str, manifest: &Manifest) -> Value {
+    json!({
+        "name": name,
+        "created_at": manifest.created_at,
+        "files": manifest.files.len(),
+        "size": manifest.database.size + manifest.files.iter().map(|f| f.size).sum::<u64>(),
+        "missing": manifest.missing,
+    })
+}
+
+/// Backups are named after when they were made, newest first.
+#[get("/admin/backups")]
+pub fn list(_viewer: Allowed<BackupManage>, dir: &State<BackupDir>) -> ApiResult {
+    let mut names = vec![];
+    // no backups made yet
+    if let Ok(entries) = fs::read_dir(&dir.0) {
+        for entry in entries.flatten() {
+            names.push(entry.file_name().to_string_lossy().into_owned());
+        }
+    }
+    names.sort_unstable_by(|a, b| b.cmp(a));
+    let backups: Vec<_> = names
+        .iter()
+        .map(|name| match backup::read_manifest(&dir.0.join(name)) {
+            Ok(manifest) => summary(name, &manifest),
+            Err(e) => json!({ "name": name, "error": e.to_string() }),
+        })
+        .collect();
+    api::ok(json!(backups))
+}
+
+#[post("/admin/backups")]
+pub async fn create(
+    conn: DbConn,
+    _viewer: Allowed<BackupManage>,
+    storage: &State<Storage>,
+    dir: &State<BackupDir>,
+) -> ApiResult {
+    let storage = storage.inner().clone();
+    let dir = dir.inner().clone();
+    conn.run(move |conn| {
+        let name = Local::now().format("%Y%m%d-%H%M%S").to_string();
+        match backup::create(conn, &storage.dir, &dir.0.join(&name)) {
+            Ok(manifest) => api::ok(summary(&name, &manifest)),
+            Err(e @ BackupError::Exists(_)) => Err(api::error(Status::Conflict, e)),
+            Err(e) => Err(api::internal(e)),
+        }
+    })
+    .await
+}
+
+#[post("/admin/backups/<name>/verify")]
+pub fn verify(_viewer: Allowed<BackupManage>, name: String, dir: &State<BackupDir>) -> ApiResult {
+    let path = dir.0.join(&name);
+    if name.contains('/') || name.starts_with('.') || !path.is_dir() {
+        return Err(api::error(Status::NotFound, "no such backup"));
+    }
+    match backup::verify(&path) {
+        Ok(manifest) => api::ok(json!({ "backup": summary(&name, &manifest), "problems": [] })),
+        Err(BackupError::Corrupt(problems)) => api::ok(json!({ "problems": problems })),
+        Err(e @ BackupError::BadManifest(_)) => Err(api::error(Status::UnprocessableEntity, e)),
+        Err(e) => Err(api::internal(e)),
+    }
+}
@@ -0,0 +1,214 @@
+//! Typed JSON request bodies. Unlike `rocket::serde::json::Json`, which
+//! fails with a bare 400 or 422, these report what's wrong with each field
+//! in the usual error envelope (see `api`), e.g.
+//!
+//! ```json
+//! {"data": null, "errors": [
+//!   {"message": "missing field", "field": "places[0].label", "kind": "missing"}
+//! ]}
+//! ```
+
+use std::ops::Deref;
+
+use rocket::data::{self, ByteUnit, Data, FromData};
+use rocket::http::Status;
+use rocket::outcome::{try_outcome, Outcome};
+use rocket::response::status::Custom;
+use rocket::serde::json::Value;
+use rocket::Request;
+use serde::de::DeserializeOwned;
+use validator::{Validate, ValidationError, ValidationErrors, ValidationErrorsKind};
+
+use super::api;
+
+/// Same as Rocket's default for JSON.
+const LIMIT: ByteUnit = ByteUnit::Mebibyte(1);
+
+/// Errors in the request body, for the 400 and 422 catchers.
+struct BodyErrors(Vec<Value>);
+
+fn field_error(field: &str, kind: &str, message: &str) -> Value {
+    json!({ "message": message, "field": field, "kind": kind })
+}
+
+fn join(parent: &str, field: &str) -> String {
+    if parent.is_empty() || parent == "." {
+        field.to_owned()
+    } else {
+        format!("{}.{}", parent, field)
+    }
+}
+
+/// The field and kind of a deserialization error. Missing fields are
+/// reported at their parent, so their name is taken from the message.
+fn deserialization_error(e: serde_path_to_error::Error<serde_json::Error>) -> Value {
+    let path = e.path().to_string();
+    // serde_json appends the position, which isn't of much use here
+    let message = e.inner().to_string();
+    let message = match message.rfind(" at line ") {
+        Some(i) => &message[..i],
+        None => &message,
+    };
+    let missing = message
+        .strip_prefix("missing field `")
+        .and_then(|rest| rest.strip_suffix('`'));
+    if let Some(name) = missing {
+        return field_error(&join(&path, name), "missing", "missing field");
+    }
+    let kind = if message.starts_with("invalid type") {
+        "type"
+    } else {
+        "value"
+    };
+    let path = if path == "." { "" } else { &path };
+    field_error(path, kind, message)
+}
+
+/// A human-readable description of a failed validation rule.
+fn describe(error: &ValidationError) -> String {
+    if let Some(message) = &error.message {
+        return message.to_string();
+    }
+    let param = |name: &str| error.params.get(name).map(|v| v.to_string());
+    match (&error.code[..], param("min"), param("max")) {
+        ("range", Some(min), Some(max)) => format!("must be between {} and {}", min, max),
+        ("range", Some(min), None) => format!("must be at least {}", min),
+        ("range", None, Some(max)) => format!("must be at most {}", max),
+        ("length", Some(min), Some(max)) => format!("length must be between {} and {}", min, max),
+        ("length", Some(min), None) => format!("length must be at least {}", min),
+        ("length", None, Some(max)) => format!("length must be at most {}", max),
+        (code, _, _) => format!("invalid value ({})", code),
+    }
+}
+
+fn validation_errors(prefix: &str, errors: &ValidationErrors, out: &mut Vec<Value>) {
+    let mut fields: Vec<_> = errors.errors().iter().collect();
+    fields.sort_by_key(|(field, _)| *field);
+    for (field, kind) in fields {
+        let path = join(prefix, field);
+        match kind {
+            ValidationErrorsKind::Field(errors) => {
+                for error in errors {
+                    out.push(field_error(&path, &error.code, &describe(error)));
+                }
+            }
+            ValidationErrorsKind::Struct(errors) => validation_errors(&path, errors, out),
+            ValidationErrorsKind::List(items) => {
+                for (i, errors) in items {
+                    validation_errors(&format!("{}[{}]", path, i), errors, out);
+                }
+            }
+        }
+    }
+}
+
+fn fail<'r, T>(request: &Request, status: Status, errors: Vec<Value>) -> data::Outcome<'r, T, ()> {
+    request.local_cache(|| BodyErrors(errors));
+    Outcome::Error((status, ()))
+}
+
+/// A JSON request body deserialized to `T`.
+#[derive(Debug)]
+pub struct JsonBody<T>(T);
+
+impl<T> JsonBody<T> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Deref for JsonBody<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+#[rocket::async_trait]
+impl<'r, T: DeserializeOwned + Send> FromData<'r> for JsonBody<T> {
+    type Error = ();
+
+    async fn from_data(request: &'r Request<'_>, data: Data<'r>) -> data::Outcome<'r, Self> {
+        let limit = request.limits().get("json").unwrap_or(LIMIT);
+        let json = match data.open(limit).into_string().await {
+            Ok(json) if json.is_complete() => json.into_inner(),
+            Ok(_) => {
+                let error = json!({ "message": format!("body is larger than {}", limit) });
+                return fail(request, Status::PayloadTooLarge, vec![error]);
+            }
+            Err(e) => {
+                let error = json!({ "message": e.to_string() });
+                return fail(request, Status::BadRequest, vec![error]);
+            }
+        };
+        let deserializer = &mut serde_json::Deserializer::from_str(&json);
+        match serde_path_to_error::deserialize(deserializer) {
+            Ok(value) => Outcome::Success(JsonBody(value)),
+            Err(e) if e.inner().is_data() => fail(
+                request,
+                Status::UnprocessableEntity,
+                vec![deserialization_error(e)],
+            ),
+            Err(e) => {
+                let error = json!({ "message": format!("malformed JSON: {}", e.inner()) });
+                fail(request, Status::BadRequest, vec![error])
+            }
+        }
+    }
+}
+
+/// A JSON request body deserialized to `T` which passes its validation
+/// rules, e.g. `#[validate(range(min = 0, max = 23))]`.
+#[derive(Debug)]
+pub struct Valid<T>(T);
+
+impl<T> Valid<T> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Deref for Valid<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+#[rocket::async_trait]
+impl<'r, T: DeserializeOwned + Validate + Send> FromData<'r> for Valid<T> {
+    type Error = ();
+
+    async fn from_data(request: &'r Request<'_>, data: Data<'r>) -> data::Outcome<'r, Self> {
+        let value = try_outcome!(JsonBody::<T>::from_data(request, data).await).into_inner();
+        match value.validate() {
+            Ok(()) => Outcome::Success(Valid(value)),
+            Err(errors) => {
+                let mut out = vec![];
+                validation_errors("", &errors, &mut out);
+                fail(request, Status::UnprocessableEntity, out)
+            }
+        }
+    }
+}
+
+fn body_errors(request: &Request, status: Status, default: &str) -> Custom<Value> {
+    match request.local_cache(|| BodyErrors(vec![])) {
+        BodyErrors(errors) if !errors.is_empty() => {
+            Custom(status, json!({ "data": null, "errors": errors }))
+        }
+        _ => api::error(status, default),
+    }
+}
+
+#[catch(400)]
+pub fn bad_request(request: &Request) -> Custom<Value> {
+    body_errors(request, Status::BadRequest, "bad request")
+}
+
+#[catch(422)]
+pub fn unprocessable_entity(request: &Request) -> Custom<Value> {
+    body_errors(request, Status::UnprocessableEntity, "invalid request")
+}
@@ -0,0 +1,90 @@
+//! Per-user bookmarks on annotations.
+
+use db::bookmarks::{self, NewBookmark};
+use rocket::http::Status;
+use serde::Deserialize;
+
+use super::api::{self, ApiResult};
+use super::body::JsonBody;
+use super::conn::DbConn;
+use super::tenancy::Viewer;
+
+#[derive(Debug, Deserialize)]
+pub struct BookmarkRequest {
+    user_id: i32,
+    tier: String,
+    annotation: String,
+    #[serde(default)]
+    note: String,
+}
+
+#[post("/documents/<doc_id>/bookmarks", data = "<bookmark>")]
+pub async fn create(
+    conn: DbConn,
+    viewer: Viewer,
+    doc_id: i32,
+    bookmark: JsonBody<BookmarkRequest>,
+) -> ApiResult {
+    conn.run(move |conn| {
+        viewer.doc(conn, doc_id)?;
+        viewer.user(bookmark.user_id)?;
+        let bookmark = bookmark.into_inner();
+        let id = bookmarks::add(
+            conn,
+            &NewBookmark {
+                user_id: bookmark.user_id,
+                doc_id,
+                tier: bookmark.tier,
+                annotation: bookmark.annotation,
+                note: bookmark.note,
+            },
+        )
+        .map_err(api::internal)?;
+        api::ok(json!({ "id": id }))
+    })
+    .await
+}
+
+/// The user's bookmarks across documents, newest first.
+#[get("/users/<user_id>/bookmarks?<doc>&<project>")]
+pub async fn list(
+    conn: DbConn,
+    viewer: Viewer,
+    user_id: i32,
+    doc: Option<i32>,
+    project: Option<i32>,
+) -> ApiResult {
+    conn.run(move |conn| {
+        viewer.user(user_id)?;
+        let bookmarks: Vec<_> = bookmarks::for_user(conn, user_id, doc, project)
+            .map_err(api::internal)?
+            .into_iter()
+            .map(|b| {
+                json!({
+                    "id": b.id,
+                    "doc_id": b.doc_id,
+                    "project": b.project,
+                    "tier": b.tier,
+                    "annotation": b.annotation,
+                    "note": b.note,
+                    "created_at": b.created_at.to_string(),
+                })
+            })
+            .collect();
+        api::ok(json!(bookmarks))
+    })
+    .await
+}
+
+#[delete("/users/<user_id>/bookmarks/<id>")]
+pub async fn delete(conn: DbConn, viewer: Viewer, user_id: i32, id: i32) -> ApiResult {
+    conn.run(move |conn| {
+        viewer.user(user_id)?;
+        if bookmarks::remove(conn, id, user_id).map_err(api::internal)? {
+            api::ok(json!(null))
+        } else {
+            Err(api::error(Status::NotFound, "no such bookmark"))
+        }
+    })
+    .await
+}
@@ -0,0 +1,266 @@
+//! Anonymized bundles of documents for sharing with external
+//! collaborators: a ZIP with pseudonymized EAF transcripts, a metadata CSV
+//! and a manifest. Anonymization spans in the transcripts get placeholders
+//! which are consistent across the whole bundle (see `eaf::anonymize`).
+
+use std::collections::hash_map::{Entry, HashMap};
+use std::fs::{self, File};
+use std::io::{self, Seek, SeekFrom, Write};
+use std::process;
+
+use chrono::Local;
+use db::bundle::{self, BundleDoc};
+use db::files;
+use diesel::result::Error;
+use eaf::anonymize::{AnonymizeError, Placeholders};
+use rocket::http::{ContentType, Status};
+use rocket::request::Request;
+use rocket::response::status::Custom;
+use rocket::response::{self, Responder, Response};
+use rocket::serde::json::Value;
+use rocket::State;
+use serde::Deserialize;
+use zip::write::SimpleFileOptions;
+use zip::ZipWriter;
+
+use super::api;
+use super::body::JsonBody;
+use super::conn::DbConn;
+use super::rules;
+use super::storage::Storage;
+use super::tenancy::{Allowed, ExportBundle};
+use super::tiers;
+
+/// Bundles are assembled synchronously, so keep them reasonably small.
+const MAX_DOCS: usize = 500;
+
+#[derive(Deserialize)]
+pub struct BundleRequest {
+    doc_ids: Vec<i32>,
+}
+
+/// Consecutively numbered pseudonyms, e.g. `S001`, `S002`, …, assigned in
+/// order of first use. They're only stable within a single bundle.
+pub struct Pseudonyms<K> {
+    prefix: &'static str,
+    assigned: HashMap<K, String>,
+}
+
+impl<K: std::hash::Hash + Eq> Pseudonyms<K> {
+    pub fn new(prefix: &'static str) -> Self {
+        Self {
+            prefix,
+            assigned: HashMap::new(),
+        }
+    }
+
+    pub fn get(&mut self, key: K) -> String {
+        let next = self.assigned.len() + 1;
+        let prefix = self.prefix;
+        self.assigned
+            .entry(key)
+            .or_insert_with(|| format!("{}{:03}", prefix, next))
+            .clone()
+    }
+}
+
+pub struct Bundle {
+    file: File,
+    size: u64,
+}
+
+impl<'r> Responder<'r, 'static> for Bundle {
+    fn respond_to(self, _: &'r Request<'_>) -> response::Result<'static> {
+        let name = format!("bundle-{}.zip", Local::now().format("%Y%m%d-%H%M%S"));
+        Response::build()
+            .header(ContentType::new("application", "zip"))
+            .raw_header(
+                "Content-Disposition",
+                format!("attachment; filename=\"{}\"", name),
+            )
+            .sized_body(
+                self.size as usize,
+                rocket::tokio::fs::File::from_std(self.file),
+            )
+            .ok()
+    }
+}
+
+/// The ZIP is written to an unlinked temporary file, because the archive
+/// needs to be seekable while it's being written.
+fn temp_file(storage: &Storage) -> io::Result<File> {
+    let dir = storage.path("tmp");
+    fs::create_dir_all(&dir)?;
+    let path = dir.join(format!(
+        "bundle-{}-{}.zip",
+        process::id(),
+        Local::now().timestamp_nanos_opt().unwrap_or_default()
+    ));
+    let file = fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create_new(true)
+        .open(&path)?;
+    fs::remove_file(&path)?;
+    Ok(file)
+}
+
+fn metadata_csv(
+    docs: &[(String, &BundleDoc)],
+    speakers: &mut Pseudonyms<(i32, String)>,
+) -> Result<Vec<u8>, csv::Error> {
+    let mut writer = csv::Writer::from_writer(vec![]);
+    writer.write_record([
+        "document",
+        "project",
+        "corpora",
+        "document_region",
+        "speaker",
+        "gender",
+        "education",
+        "speaker_region",
+        "year",
+    ])?;
+    for (pseudonym, doc) in docs {
+        let corpora = doc.corpora.join("|");
+        let fields = [pseudonym.as_str(), &doc.project, &corpora, &doc.region];
+        if doc.speakers.is_empty() {
+            writer.write_record(fields.iter().chain(&["", "", "", "", ""]))?;
+        }
+        for speaker in &doc.speakers {
+            let year = speaker.year.to_string();
+            let speaker_fields = [
+                speaker.gender.as_str(),
+                &speaker.education,
+                &speaker.region,
+                &year,
+            ];
+            let name = speakers.get((doc.project_id, speaker.nickname.clone()));
+            writer.write_record(
+                fields
+                    .iter()
+                    .chain(&[name.as_str()])
+                    .chain(speaker_fields.iter()),
+            )?;
+        }
+    }
+    writer
+        .into_inner()
+        .map_err(|e| csv::Error::from(e.into_error()))
+}
+
+/// Build a ZIP of the documents' latest transcripts with speakers,
+/// documents and anonymization spans pseudonymized (see `eaf::anonymize`),
+/// along with `metadata.csv` and `manifest.json` describing the contents.
+#[post("/bundles", data = "<request>")]
+pub async fn create(
+    conn: DbConn,
+    viewer: Allowed<ExportBundle>,
+    storage: &State<Storage>,
+    request: JsonBody<BundleRequest>,
+) -> Result<Bundle, Custom<Value>> {
+    let storage = storage.inner().clone();
+    conn.run(move |conn| {
+        let mut doc_ids = vec![];
+        for &id in &request.doc_ids {
+            if !doc_ids.contains(&id) {
+                doc_ids.push(id);
+            }
+        }
+        if doc_ids.is_empty() {
+            return Err(api::error(
+                Status::UnprocessableEntity,
+                "no documents selected",
+            ));
+        }
+        if doc_ids.len() > MAX_DOCS {
+            return Err(api::error(
+                Status::UnprocessableEntity,
+                format!("at most {} documents can be bundled at once", MAX_DOCS),
+            ));
+        }
+        for &doc_id in &doc_ids {
+            viewer.doc(conn, doc_id)?;
+        }
+        let metadata = match bundle::metadata(conn, &doc_ids) {
+            Ok(metadata) => metadata,
+            Err(Error::NotFound) => return Err(api::error(Status::NotFound, "no such document")),
+            Err(e) => return Err(api::internal(e)),
+        };
+
+        let mut doc_names = Pseudonyms::new("D");
+        let mut speakers = Pseudonyms::new("S");
+        let mut placeholders = Placeholders::default();
+        let mut projects = HashMap::new();
+        let mut transcripts = vec![];
+        for doc in &metadata {
+            let file = files::latest(conn, doc.id, &[files::EAF])
+                .map_err(api::internal)?
+                .ok_or_else(|| {
+                    api::error(
+                        Status::UnprocessableEntity,
+                        format!("document {} has no transcript", doc.id),
+                    )
+                })?;
+            let xml = fs::read_to_string(storage.path(&file.path))
+                .map_err(|e| api::internal(format!("{}: {}", file.path, e)))?;
+            if let Entry::Vacant(entry) = projects.entry(doc.project_id) {
+                let mapping = tiers::tier_mapping(conn, doc.project_id).map_err(api::internal)?;
+                let config = rules::project_config(conn, doc.project_id).map_err(api::internal)?;
+                entry.insert((mapping, config));
+            }
+            let (mapping, config) = &projects[&doc.project_id];
+            let unprocessable = |e: AnonymizeError| {
+                api::error(
+                    Status::UnprocessableEntity,
+                    format!("document {}: {}", doc.id, e),
+                )
+            };
+            let scrubbed = placeholders
+                .scrub_eaf(&xml, config)
+                .map_err(unprocessable)?;
+            let anonymized = eaf::anonymize::anonymize(&scrubbed, mapping, |nickname| {
+                speakers.get((doc.project_id, nickname.to_owned()))
+            })
+            .map_err(unprocessable)?;
+            transcripts.push((doc_names.get(doc.id), doc, anonymized));
+        }
+
+        let docs: Vec<_> = transcripts
+            .iter()
+            .map(|(name, doc, _)| (name.clone(), *doc))
+            .collect();
+        let csv = metadata_csv(&docs, &mut speakers).map_err(api::internal)?;
+        let manifest = json!({
+            "created": Local::now().to_rfc3339(),
+            "documents": transcripts.iter().map(|(name, doc, _)| json!({
+                "id": name,
+                "transcript": format!("{}.eaf", name),
+                "speakers": doc.speakers
+                    .iter()
+                    .map(|s| speakers.get((doc.project_id, s.nickname.clone())))
+                    .collect::<Vec<_>>(),
+            })).collect::<Vec<_>>(),
+            "metadata": "metadata.csv",
+        });
+
+        let write = || -> zip::result::ZipResult<File> {
+            let mut zip = ZipWriter::new(temp_file(&storage)?);
+            let options = SimpleFileOptions::default();
+            for (name, _, xml) in &transcripts {
+                zip.start_file(format!("{}.eaf", name), options)?;
+                zip.write_all(xml.as_bytes())?;
+            }
+            zip.start_file("metadata.csv", options)?;
+            zip.write_all(&csv)?;
+            zip.start_file("manifest.json", options)?;
+            zip.write_all(manifest.to_string().as_bytes())?;
+            zip.finish()
+        };
+        let mut file = write().map_err(api::internal)?;
+        let size = file.seek(SeekFrom::End(0)).map_err(api::internal)?;
+        file.seek(SeekFrom::Start(0)).map_err(api::internal)?;
+        Ok(Bundle { file, size })
+    })
+    .await
+}
@@ -0,0 +1,138 @@
+//! Reviewers' comments on documents, anchored to annotations or to time
+//! ranges of the recording.
+
+use db::comments::{self, Anchor, Comment, NewComment};
+use rocket::http::Status;
+use rocket::response::status::Custom;
+use rocket::serde::json::Value;
+use serde::Deserialize;
+use validator::Validate;
+
+use super::api::{self, ApiResult};
+use super::body::Valid;
+use super::conn::DbConn;
+use super::tenancy::{Allowed, DocReview, Viewer};
+
+/// Anchored either to an annotation (`tier` and `annotation`) or to a time
+/// range (`start` and `end`, in milliseconds).
+#[derive(Debug, Deserialize, Validate)]
+pub struct CommentRequest {
+    tier: Option<String>,
+    annotation: Option<String>,
+    #[validate(range(min = 0))]
+    start: Option<i32>,
+    end: Option<i32>,
+    #[validate(length(min = 1))]
+    body: String,
+}
+
+fn anchor(request: &CommentRequest) -> Result<Anchor, Custom<Value>> {
+    let invalid = |message: &str| Err(api::error(Status::UnprocessableEntity, message));
+    match request {
+        CommentRequest {
+            tier: Some(tier),
+            annotation: Some(annotation),
+            start: None,
+            end: None,
+            ..
+        } => Ok(Anchor::Annotation {
+            tier: tier.clone(),
+            annotation: annotation.clone(),
+        }),
+        CommentRequest {
+            tier: None,
+            annotation: None,
+            start: Some(start),
+            end: Some(end),
+            ..
+        } => {
+            if end <= start {
+                return invalid("end must be after start");
+            }
+            Ok(Anchor::Time {
+                start: *start,
+                end: *end,
+            })
+        }
+        _ => invalid("anchor the comment to either a tier and annotation, or a start and end"),
+    }
+}
+
+pub fn comment_json(comment: &Comment) -> Value {
+    let (tier, annotation, start, end) = match &comment.anchor {
+        Anchor::Annotation { tier, annotation } => (Some(tier), Some(annotation), None, None),
+        Anchor::Time { start, end } => (None, None, Some(start), Some(end)),
+    };
+    json!({
+        "id": comment.id,
+        "doc_id": comment.doc_id,
+        "author_id": comment.author_id,
+        "author": comment.author,
+        "tier": tier,
+        "annotation": annotation,
+        "start": start,
+        "end": end,
+        "body": comment.body,
+        "created_at": comment.created_at.to_string(),
+    })
+}
+
+#[post("/documents/<doc_id>/comments", data = "<request>")]
+pub async fn create(
+    conn: DbConn,
+    viewer: Allowed<DocReview>,
+    doc_id: i32,
+    request: Valid<CommentRequest>,
+) -> ApiResult {
+    conn.run(move |conn| {
+        viewer.doc(conn, doc_id)?;
+        let anchor = anchor(&request)?;
+        let id = comments::add(
+            conn,
+            &NewComment {
+                doc_id,
+                author_id: viewer.user_id,
+                anchor,
+                body: request.into_inner().body,
+            },
+        )
+        .map_err(api::internal)?;
+        api::ok(json!({ "id": id }))
+    })
+    .await
+}
+
+/// The document's comments, oldest first.
+#[get("/documents/<doc_id>/comments")]
+pub async fn list(conn: DbConn, viewer: Viewer, doc_id: i32) -> ApiResult {
+    conn.run(move |conn| {
+        viewer.doc(conn, doc_id)?;
+        let comments: Vec<_> = comments::for_doc(conn, doc_id)
+            .map_err(api::internal)?
+            .iter()
+            .map(comment_json)
+            .collect();
+        api::ok(json!(comments))
+    })
+    .await
+}
+
+/// Only the author can remove a comment.
+#[delete("/documents/<doc_id>/comments/<id>")]
+pub async fn delete(conn: DbConn, viewer: Viewer, doc_id: i32, id: i32) -> ApiResult {
+    conn.run(move |conn| {
+        viewer.doc(conn, doc_id)?;
+        match comments::author_of(conn, doc_id, id).map_err(api::internal)? {
+            Some(author_id) if author_id == viewer.user_id => {
+                comments::remove(conn, doc_id, id).map_err(api::internal)?;
+                api::ok(json!(null))
+            }
+            Some(_) => Err(api::error(
+                Status::Forbidden,
+                "only the author can remove a comment",
+            )),
+            None => Err(api::error(Status::NotFound, "no such comment")),
+        }
+    })
+    .await
+}
@@ -0,0 +1,143 @@
+//! Compression of textual responses (JSON, reports, CSV and XML exports)
+//! with brotli or gzip, whichever the client prefers. A validation report
+//! of a long recording runs to megabytes, but compresses very well.
+
+use std::io::{Cursor, Write};
+
+use flate2::write::GzEncoder;
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::{ContentType, Header, Status};
+use rocket::{Request, Response};
+
+/// Brotli quality and window size: fast enough to compress on each
+/// request, though the output is a bit larger than at the maximum of 11.
+const BROTLI_QUALITY: u32 = 5;
+const BROTLI_WINDOW: u32 = 22;
+const BUFFER_SIZE: usize = 4096;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Encoding {
+    Brotli,
+    Gzip,
+}
+
+impl Encoding {
+    fn name(self) -> &'static str {
+        match self {
+            Encoding::Brotli => "br",
+            Encoding::Gzip => "gzip",
+        }
+    }
+
+    fn compress(self, data: &[u8]) -> std::io::Result<Vec<u8>> {
+        match self {
+            Encoding::Brotli => {
+                let mut writer = brotli::CompressorWriter::new(
+                    vec![],
+                    BUFFER_SIZE,
+                    BROTLI_QUALITY,
+                    BROTLI_WINDOW,
+                );
+                writer.write_all(data)?;
+                writer.flush()?;
+                Ok(writer.into_inner())
+            }
+            Encoding::Gzip => {
+                let mut encoder = GzEncoder::new(vec![], flate2::Compression::default());
+                encoder.write_all(data)?;
+                encoder.finish()
+            }
+        }
+    }
+}
+
+/// The supported encoding with the highest quality in the `Accept-Encoding`
+/// header, preferring brotli on ties.
+fn negotiate(accept: &str) -> Option<Encoding> {
+    let mut best: Option<(Encoding, f32)> = None;
+    for item in accept.split(',') {
+        let mut parts = item.split(';').map(str::trim);
+        let encoding = match parts.next() {
+            Some("br") => Encoding::Brotli,
+            Some("gzip") | Some("x-gzip") => Encoding::Gzip,
+            _ => continue,
+        };
+        let quality = parts
+            .find_map(|p| p.strip_prefix("q="))
+            .and_then(|q| q.parse().ok())
+            .unwrap_or(1.0);
+        let better = match best {
+            Some((current, q)) => {
+                quality > q || (quality == q && encoding == Encoding::Brotli && current != encoding)
+            }
+            None => true,
+        };
+        if quality > 0.0 && better {
+            best = Some((encoding, quality));
+        }
+    }
+    best.map(|(encoding, _)| encoding)
+}
+
+/// Formats worth compressing; e.g. audio and ZIP bundles already are.
+fn compressible(content_type: &ContentType) -> bool {
+    content_type.top() == "text"
+        || [
+            ContentType::JSON,
+            ContentType::XML,
+            ContentType::JavaScript,
+            ContentType::SVG,
+        ]
+        .iter()
+        .any(|ct| ct.top() == content_type.top() && ct.sub() == content_type.sub())
+}
+
+/// Fairing compressing responses of at least `min_size` bytes.
+pub struct Compression {
+    pub min_size: usize,
+}
+
+#[rocket::async_trait]
+impl Fairing for Compression {
+    fn info(&self) -> Info {
+        Info {
+            name: "Response compression",
+            kind: Kind::Response,
+        }
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        // partial content can't be compressed as a whole
+        if response.status() != Status::Ok || response.headers().contains("Content-Encoding") {
+            return;
+        }
+        match response.content_type() {
+            Some(content_type) if compressible(&content_type) => {}
+            _ => return,
+        }
+        response.adjoin_header(Header::new("Vary", "Accept-Encoding"));
+        let encoding = match request.headers().get("Accept-Encoding").find_map(negotiate) {
+            Some(encoding) => encoding,
+            None => return,
+        };
+        if response.body().is_none() {
+            return;
+        }
+        let body = match response.body_mut().to_bytes().await {
+            Ok(body) => body,
+            Err(_) => return,
+        };
+        let compressed = if body.len() >= self.min_size {
+            encoding.compress(&body).ok()
+        } else {
+            None
+        };
+        match compressed {
+            Some(compressed) => {
+                response.set_raw_header("Content-Encoding", encoding.name());
+                response.set_sized_body(compressed.len(), Cursor::new(compressed));
+            }
+            None => response.set_sized_body(body.len(), Cursor::new(body)),
+        }
+    }
+}
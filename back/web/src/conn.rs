@@ -0,0 +1,83 @@
+//! Per-request database connections, from the pool managed by the server
+//! (see `db::pool`).
+
+use std::sync::{Arc, Mutex};
+
+use db::pool::{Pool, PooledConn};
+use db::Conn;
+use diesel::result::Error;
+use diesel::Connection;
+use rocket::http::Status;
+use rocket::outcome::{try_outcome, Outcome};
+use rocket::request::{self, FromRequest, Request};
+use rocket::response::status::Custom;
+use rocket::serde::json::Value;
+use rocket::tokio::task;
+use rocket::State;
+
+use super::api;
+
+/// Request guard handing the handler a pooled connection to the database.
+/// Both waiting for a free connection (or for a lock, see `db::connect`)
+/// and querying block, so they're done off the async workers, the latter
+/// in `run`.
+pub struct DbConn(Arc<Mutex<PooledConn>>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for DbConn {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        let pool = try_outcome!(request.guard::<&State<Pool>>().await)
+            .inner()
+            .clone();
+        match task::spawn_blocking(move || pool.get()).await {
+            Ok(Ok(conn)) => Outcome::Success(DbConn(Arc::new(Mutex::new(conn)))),
+            _ => Outcome::Error((Status::ServiceUnavailable, ())),
+        }
+    }
+}
+
+/// Why a transaction was rolled back.
+enum Rollback {
+    Response(Custom<Value>),
+    Db(Error),
+}
+
+impl From<Error> for Rollback {
+    fn from(e: Error) -> Self {
+        Rollback::Db(e)
+    }
+}
+
+impl DbConn {
+    /// Run `f` with the connection on a thread where blocking is fine, like
+    /// `rocket_sync_db_pools` does, so that slow queries don't hold up
+    /// other requests. Handlers do (nearly) all their work in it.
+    pub async fn run<T, F>(&self, f: F) -> T
+    where
+        F: FnOnce(&Conn) -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let conn = self.0.clone();
+        task::spawn_blocking(move || {
+            let conn = conn.lock().expect("database connection poisoned");
+            f(&**conn)
+        })
+        .await
+        .expect("database task panicked")
+    }
+}
+
+/// Run (the rest of) a handler in a transaction, which is committed if it
+/// succeeds and rolled back if it ends in an error response, so that e.g. a
+/// failed audit entry doesn't leave its change behind.
+pub fn transaction<T, F>(conn: &Conn, f: F) -> Result<T, Custom<Value>>
+where
+    F: FnOnce() -> Result<T, Custom<Value>>,
+{
+    Connection::transaction(conn, || f().map_err(Rollback::Response)).map_err(|e| match e {
+        Rollback::Response(response) => response,
+        Rollback::Db(e) => api::internal(e),
+    })
+}
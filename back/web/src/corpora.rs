@@ -0,0 +1,107 @@
+//! Corpora and which of them documents belong to.
+
+use chrono::Local;
+use db::corpora;
+use db::Conn;
+use rocket::http::Status;
+use serde::Deserialize;
+
+use super::api::{self, ApiResult};
+use super::body::JsonBody;
+use super::conn::DbConn;
+use super::tenancy::{Allowed, DocEdit, ExportRelease, Viewer};
+
+#[get("/corpora")]
+pub async fn list(conn: DbConn, _viewer: Viewer) -> ApiResult {
+    conn.run(move |conn| {
+        let corpora: Vec<_> = corpora::all(conn)
+            .map_err(api::internal)?
+            .into_iter()
+            .map(|c| {
+                json!({
+                    "id": c.id,
+                    "label": c.label,
+                    "released_at": c.released_at.map(|r| r.to_string()),
+                })
+            })
+            .collect();
+        api::ok(json!(corpora))
+    })
+    .await
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReleaseRequest {
+    released: bool,
+}
+
+/// Release the corpus through the public API, or withdraw it.
+#[put("/corpora/<corpus_id>/release", data = "<request>")]
+pub async fn release(
+    conn: DbConn,
+    _viewer: Allowed<ExportRelease>,
+    corpus_id: i32,
+    request: JsonBody<ReleaseRequest>,
+) -> ApiResult {
+    conn.run(move |conn| {
+        let at = if request.released {
+            Some(Local::now().naive_local())
+        } else {
+            None
+        };
+        if !corpora::set_released(conn, corpus_id, at).map_err(api::internal)? {
+            return Err(api::error(Status::NotFound, "no such corpus"));
+        }
+        api::ok(json!({ "id": corpus_id, "released_at": at.map(|r| r.to_string()) }))
+    })
+    .await
+}
+
+fn doc_corpora(conn: &Conn, viewer: &Viewer, doc_id: i32) -> ApiResult {
+    viewer.doc(conn, doc_id)?;
+    let corpora: Vec<_> = corpora::for_doc(conn, doc_id)
+        .map_err(api::internal)?
+        .into_iter()
+        .map(|c| json!({ "id": c.id, "label": c.label }))
+        .collect();
+    api::ok(json!(corpora))
+}
+
+#[get("/documents/<doc_id>/corpora")]
+pub async fn get(conn: DbConn, viewer: Viewer, doc_id: i32) -> ApiResult {
+    conn.run(move |conn| doc_corpora(conn, &viewer, doc_id))
+        .await
+}
+
+/// Replace the corpora the document belongs to with the given corpus IDs.
+#[put("/documents/<doc_id>/corpora", data = "<corpus_ids>")]
+pub async fn put(
+    conn: DbConn,
+    viewer: Allowed<DocEdit>,
+    doc_id: i32,
+    corpus_ids: JsonBody<Vec<i32>>,
+) -> ApiResult {
+    conn.run(move |conn| {
+        viewer.doc(conn, doc_id)?;
+        let known: Vec<_> = corpora::all(conn)
+            .map_err(api::internal)?
+            .into_iter()
+            .map(|c| c.id)
+            .collect();
+        let mut ids = vec![];
+        for &id in corpus_ids.iter() {
+            if !known.contains(&id) {
+                return Err(api::error(
+                    Status::UnprocessableEntity,
+                    format!("no such corpus {}", id),
+                ));
+            }
+            if !ids.contains(&id) {
+                ids.push(id);
+            }
+        }
+        corpora::replace_for_doc(conn, doc_id, &ids).map_err(api::internal)?;
+        doc_corpora(conn, &viewer, doc_id)
+    })
+    .await
+}
@@ -0,0 +1,61 @@
+//! Selection of spell-checking dictionaries per project.
+
+use db::{audit, dictionaries, Conn};
+use rocket::http::Status;
+
+use super::api::{self, ApiResult};
+use super::body::JsonBody;
+use super::conn::DbConn;
+use super::tenancy::{Allowed, ConfigEdit, Viewer};
+
+/// Audit log action, per project.
+const CHANGED: &str = "project.dictionaries_changed";
+
+fn project_dictionaries(conn: &Conn, viewer: &Viewer, project_id: i32) -> ApiResult {
+    viewer.project(project_id)?;
+    let names = dictionaries::for_project(conn, project_id).map_err(api::internal)?;
+    api::ok(json!(names))
+}
+
+#[get("/projects/<project_id>/dictionaries")]
+pub async fn get(conn: DbConn, viewer: Viewer, project_id: i32) -> ApiResult {
+    conn.run(move |conn| project_dictionaries(conn, &viewer, project_id))
+        .await
+}
+
+/// Replace the project's dictionaries, which are consulted in the order
+/// given.
+#[put("/projects/<project_id>/dictionaries", data = "<names>")]
+pub async fn put(
+    conn: DbConn,
+    viewer: Allowed<ConfigEdit>,
+    project_id: i32,
+    names: JsonBody<Vec<String>>,
+) -> ApiResult {
+    conn.run(move |conn| {
+        viewer.project(project_id)?;
+        // names end up in file paths
+        if let Some(name) = names
+            .iter()
+            .find(|n| n.is_empty() || n.contains(|c: char| c == '/' || c == '\\' || c == '.'))
+        {
+            return Err(api::error(
+                Status::UnprocessableEntity,
+                format!("invalid dictionary name {:?}", name),
+            ));
+        }
+        dictionaries::replace(conn, project_id, &names).map_err(api::internal)?;
+        let details = json!(*names);
+        audit::record(
+            conn,
+            Some(viewer.user_id),
+            CHANGED,
+            "project",
+            project_id,
+            &details,
+        )
+        .map_err(api::internal)?;
+        project_dictionaries(conn, &viewer, project_id)
+    })
+    .await
+}
@@ -0,0 +1,127 @@
+//! Weekly e-mail digests for supervisors.
+
+use std::fmt::Write;
+
+use chrono::{Duration, Local, NaiveDateTime};
+use lettre::{message::Mailbox, Message, SmtpTransport, Transport};
+
+use db::digest::{self, Digest, DigestSettings, Recipient};
+
+const DATE_FMT: &str = "%-d. %-m. %Y";
+
+#[derive(Debug, Clone)]
+pub struct MailConfig {
+    pub smtp_host: String,
+    pub smtp_port: u16,
+    pub from: Mailbox,
+}
+
+/// Send all digests which are due, marking each project's digest as sent
+/// once all of its recipients have been handled.
+pub fn send_due(database_url: &str, mail: &MailConfig) -> Result<(), String> {
+    let conn = db::connect(database_url).map_err(|e| e.to_string())?;
+    let now = Local::now().naive_local();
+    let since = now - Duration::weeks(1);
+    let mailer = SmtpTransport::builder_dangerous(&mail.smtp_host)
+        .port(mail.smtp_port)
+        .build();
+
+    for settings in digest::due_digests(&conn, now).map_err(|e| e.to_string())? {
+        for recipient in
+            digest::recipients(&conn, settings.project_id).map_err(|e| e.to_string())?
+        {
+            // one bad recipient shouldn't prevent the others from getting
+            // their digest, nor those already mailed from getting it again
+            // on the next run
+            let digest = match digest::digest(&conn, settings.project_id, recipient.id, since, now)
+            {
+                Ok(digest) => digest,
+                Err(e) => {
+                    eprintln!("failed to collect digest for {}: {}", recipient.email, e);
+                    continue;
+                }
+            };
+            if digest.is_empty() {
+                continue;
+            }
+            let sent = compose(mail, &settings, &recipient, &digest)
+                .and_then(|message| mailer.send(&message).map_err(|e| e.to_string()));
+            if let Err(e) = sent {
+                eprintln!("failed to send digest to {}: {}", recipient.email, e);
+            }
+        }
+        digest::mark_sent(&conn, settings.project_id, now).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+fn compose(
+    mail: &MailConfig,
+    settings: &DigestSettings,
+    recipient: &Recipient,
+    digest: &Digest,
+) -> Result<Message, String> {
+    let to = recipient
+        .email
+        .parse::<Mailbox>()
+        .map_err(|e| format!("bad address {:?}: {}", recipient.email, e))?;
+    Message::builder()
+        .from(mail.from.clone())
+        .to(to)
+        .subject(format!(
+            "Quetzal: týdenní přehled projektu {}",
+            settings.project
+        ))
+        .body(render(&recipient.username, digest))
+        .map_err(|e| e.to_string())
+}
+
+fn render(username: &str, digest: &Digest) -> String {
+    let mut body = String::new();
+    // writing to a String can't fail
+    let _ = writeln!(body, "Dobrý den, {},", username);
+    let _ = writeln!(
+        body,
+        "\ntady je přehled vámi zadaných dokumentů od {}.",
+        format_date(digest.since)
+    );
+
+    let _ = writeln!(body, "\nOdevzdané dokumenty ({}):", digest.submitted.len());
+    for doc in &digest.submitted {
+        let _ = writeln!(body, "  - {} ({})", doc.id, assignee(&doc.assigned_to));
+    }
+
+    let _ = writeln!(body, "\nDokumenty po termínu ({}):", digest.overdue.len());
+    for doc in &digest.overdue {
+        let _ = writeln!(
+            body,
+            "  - {} ({}, termín {})",
+            doc.id,
+            assignee(&doc.assigned_to),
+            doc.due_at.map(format_date).unwrap_or_default()
+        );
+    }
+
+    let _ = writeln!(body, "\nNejvíce chyb při poslední kontrole:");
+    for hotspot in &digest.hotspots {
+        let _ = writeln!(
+            body,
+            "  - {}: {} chyb ({})",
+            hotspot.doc_id,
+            hotspot.mistakes,
+            format_date(hotspot.validated_at)
+        );
+    }
+    body
+}
+
+fn assignee(assigned_to: &Option<String>) -> &str {
+    assigned_to
+        .as_ref()
+        .map(String::as_str)
+        .unwrap_or("nepřiřazeno")
+}
+
+fn format_date(datetime: NaiveDateTime) -> String {
+    datetime.format(DATE_FMT).to_string()
+}
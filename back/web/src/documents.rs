@@ -0,0 +1,143 @@
+//! Document endpoints.
+
+use std::fs;
+
+use chrono::NaiveDate;
+use db::docs::{self, DocFilter};
+use db::files;
+use diesel::result::Error;
+use rocket::http::Status;
+use rocket::State;
+use serde::Deserialize;
+
+use super::api::{self, ApiResult};
+use super::body::JsonBody;
+use super::conn::DbConn;
+use super::storage::{FileInfo, Storage};
+use super::tenancy::{Allowed, DocEdit, Viewer};
+
+// NOTE: the route should really have `format = "application/json"`, but
+// leaving it out makes it easier to test the API from the browser.
+
+/// List documents, optionally filtered by project, corpus, assignee and by
+/// a comma-separated list of states, e.g. `?project=1&state=submitted,returned`.
+/// Transcribers only see documents assigned to them.
+#[get("/documents?<project>&<corpus>&<assigned_to>&<state>")]
+pub async fn list(
+    conn: DbConn,
+    viewer: Viewer,
+    project: Option<i32>,
+    corpus: Option<i32>,
+    assigned_to: Option<i32>,
+    state: Option<String>,
+) -> ApiResult {
+    conn.run(move |conn| {
+        let project = viewer.scope(project)?;
+        let assigned_to = match (viewer.assignee(), assigned_to) {
+            (Some(me), Some(other)) if me != other => {
+                return Err(api::error(
+                    Status::Forbidden,
+                    "can only list documents assigned to you",
+                ))
+            }
+            (me, requested) => me.or(requested),
+        };
+        let states = match state {
+            Some(states) => states
+                .split(',')
+                .map(str::parse)
+                .collect::<Result<_, _>>()
+                .map_err(|e| api::error(Status::BadRequest, e))?,
+            None => vec![],
+        };
+        let filter = DocFilter {
+            project_id: project,
+            corpus_id: corpus,
+            assigned_to_id: assigned_to,
+            states,
+        };
+        let docs: Vec<_> = docs::list(conn, &filter)
+            .map_err(api::internal)?
+            .into_iter()
+            .map(|d| {
+                json!({
+                    "id": d.id,
+                    "project": d.project,
+                    "corpora": d.corpora,
+                    "state": d.state,
+                    "assigned_to_id": d.assigned_to_id,
+                    "done": d.done,
+                })
+            })
+            .collect();
+        api::ok(json!(docs))
+    })
+    .await
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DuplicateRequest {
+    user_id: Option<i32>,
+    /// Recording date of the new document, `YYYY-MM-DD`; defaults to the
+    /// template's.
+    date: Option<NaiveDate>,
+}
+
+/// Create a new document from a template document: same project, corpora,
+/// place and speakers, and a blank copy of its transcript (tiers,
+/// linguistic types and controlled vocabularies, but no annotations).
+#[post("/documents/<template_id>/duplicate", data = "<request>")]
+pub async fn duplicate(
+    conn: DbConn,
+    viewer: Allowed<DocEdit>,
+    storage: &State<Storage>,
+    template_id: i32,
+    request: JsonBody<DuplicateRequest>,
+) -> ApiResult {
+    let storage = storage.inner().clone();
+    conn.run(move |conn| {
+        viewer.doc(conn, template_id)?;
+        let transcript = files::latest(conn, template_id, &[files::EAF, files::DRAFT_EAF])
+            .map_err(api::internal)?;
+        let blank = match transcript {
+            Some(file) => {
+                let xml = fs::read_to_string(storage.path(&file.path))
+                    .map_err(|e| api::internal(format!("{}: {}", file.path, e)))?;
+                Some(
+                    eaf::template::blank(&xml)
+                        .map_err(|e| api::error(Status::UnprocessableEntity, e))?,
+                )
+            }
+            None => None,
+        };
+
+        let date = request.date.map(|d| d.and_hms_opt(0, 0, 0).unwrap());
+        let doc_id = match docs::duplicate(conn, template_id, date) {
+            Ok(doc_id) => doc_id,
+            Err(Error::NotFound) => return Err(api::error(Status::NotFound, "no such document")),
+            Err(e) => return Err(api::internal(e)),
+        };
+        let file_id = match blank {
+            Some(xml) => Some(
+                storage
+                    .store(
+                        conn,
+                        doc_id,
+                        &format!("template-{}.eaf", template_id),
+                        xml.as_bytes(),
+                        xml.len() as u64,
+                        FileInfo {
+                            role: files::DRAFT_EAF,
+                            mime: "application/xml",
+                            created_by: request.user_id,
+                            source_id: None,
+                        },
+                    )
+                    .map_err(api::internal)?,
+            ),
+            None => None,
+        };
+        api::ok(json!({ "id": doc_id, "file_id": file_id }))
+    })
+    .await
+}
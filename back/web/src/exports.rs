@@ -0,0 +1,194 @@
+//! Generated exports of documents (validation reports, anonymized
+//! transcripts), cached on disk so that repeated downloads don't re-run the
+//! exporters. An export is keyed by the document's latest transcript file
+//! and a fingerprint of whatever else went into it, e.g. the rules version,
+//! so it's rebuilt whenever any of those change. Storing a new file for a
+//! document drops all of its cached exports (see `Storage::record`).
+
+use std::fs;
+use std::io::Cursor;
+use std::path::Path;
+
+use rocket::http::{ContentType, Status};
+use rocket::outcome::Outcome;
+use rocket::request::{self, FromRequest, Request};
+use rocket::response::status::Custom;
+use rocket::response::{self, Responder, Response};
+use rocket::serde::json::Value;
+use sha2::{Digest, Sha256};
+
+use super::api;
+use super::storage::Storage;
+
+/// What a cached export is derived from.
+pub struct ExportKey<'a> {
+    pub doc_id: i32,
+    /// The ID of the document's file it's generated from.
+    pub file_id: i32,
+    pub format: &'a str,
+    /// Any other inputs affecting the output, e.g. configuration versions.
+    pub inputs: String,
+}
+
+impl ExportKey<'_> {
+    /// Also serves as the entity tag of the export.
+    fn hash(&self) -> String {
+        let digest = Sha256::digest(
+            format!(
+                "{}\n{}\n{}\n{}",
+                self.doc_id, self.file_id, self.format, self.inputs
+            )
+            .as_bytes(),
+        );
+        format!("{:x}", digest)[..16].to_owned()
+    }
+
+    fn path(&self) -> String {
+        format!(
+            "{}/{}-{}-{}",
+            Storage::export_dir(self.doc_id),
+            self.file_id,
+            self.format,
+            self.hash()
+        )
+    }
+}
+
+/// Remove the document's exports of the given format other than `keep`,
+/// which are stale. Best effort, leftovers only take up space.
+fn prune(dir: &Path, format: &str, keep: &Path) {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+    for entry in entries.filter_map(Result::ok) {
+        let path = entry.path();
+        let name = entry.file_name();
+        // exports being written by concurrent requests are left alone
+        let stale = name
+            .to_str()
+            .filter(|n| !n.ends_with(".partial"))
+            .and_then(|n| n.split('-').nth(1))
+            .map_or(false, |f| f == format);
+        if stale && path != keep {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+/// The cached export under the key, building and caching it first if
+/// needed.
+fn cached<F>(storage: &Storage, key: &ExportKey, build: F) -> Result<Vec<u8>, Custom<Value>>
+where
+    F: FnOnce() -> Result<Vec<u8>, Custom<Value>>,
+{
+    let path = storage.path(&key.path());
+    if let Ok(contents) = fs::read(&path) {
+        return Ok(contents);
+    }
+    let contents = build()?;
+    let io_error = |e| api::internal(format!("{}: {}", path.display(), e));
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(io_error)?;
+        prune(dir, key.format, &path);
+    }
+    // write to a temporary file first so that concurrent requests never
+    // read a partial export
+    let partial = path.with_extension("partial");
+    fs::write(&partial, &contents).map_err(io_error)?;
+    fs::rename(&partial, &path).map_err(io_error)?;
+    Ok(contents)
+}
+
+/// The entity tags in the `If-None-Match` request header.
+pub struct IfNoneMatch(Vec<String>);
+
+impl IfNoneMatch {
+    fn matches(&self, etag: &str) -> bool {
+        self.0.iter().any(|tag| {
+            let tag = tag.trim_start_matches("W/");
+            tag == "*" || tag.trim_matches('"') == etag
+        })
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for IfNoneMatch {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, ()> {
+        let tags = request
+            .headers()
+            .get("If-None-Match")
+            .flat_map(|value| value.split(','))
+            .map(|tag| tag.trim().to_owned())
+            .filter(|tag| !tag.is_empty())
+            .collect();
+        Outcome::Success(IfNoneMatch(tags))
+    }
+}
+
+/// A cached export, which clients may keep as long as they revalidate it,
+/// answered with `304 Not Modified` if they still have the current version.
+pub struct Export {
+    content_type: ContentType,
+    contents: Vec<u8>,
+    etag: String,
+    not_modified: bool,
+    public: bool,
+}
+
+impl Export {
+    /// Get the export under the key, building it if it isn't cached yet.
+    /// `public` exports may also be cached by shared caches.
+    pub fn get<F>(
+        storage: &Storage,
+        key: &ExportKey,
+        content_type: ContentType,
+        if_none_match: &IfNoneMatch,
+        public: bool,
+        build: F,
+    ) -> Result<Self, Custom<Value>>
+    where
+        F: FnOnce() -> Result<Vec<u8>, Custom<Value>>,
+    {
+        let etag = key.hash();
+        let not_modified = if_none_match.matches(&etag);
+        let contents = if not_modified {
+            vec![]
+        } else {
+            cached(storage, key, build)?
+        };
+        Ok(Export {
+            content_type,
+            contents,
+            etag,
+            not_modified,
+            public,
+        })
+    }
+}
+
+impl<'r> Responder<'r, 'static> for Export {
+    fn respond_to(self, _: &'r Request<'_>) -> response::Result<'static> {
+        let mut response = Response::build();
+        response
+            .raw_header("ETag", format!("\"{}\"", self.etag))
+            .raw_header(
+                "Cache-Control",
+                if self.public {
+                    "public, no-cache"
+                } else {
+                    "private, no-cache"
+                },
+            );
+        if self.not_modified {
+            response.status(Status::NotModified);
+        } else {
+            response
+                .header(self.content_type)
+                .sized_body(self.contents.len(), Cursor::new(self.contents));
+        }
+        response.ok()
+    }
+}
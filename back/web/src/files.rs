@@ -0,0 +1,155 @@
+//! Listing and streaming of stored files.
+
+use std::fs::File;
+use std::io::{Seek, SeekFrom};
+
+use db::{files, jobs};
+use diesel::result::Error;
+use rocket::http::{ContentType, Status};
+use rocket::outcome::Outcome;
+use rocket::request::{self, FromRequest, Request};
+use rocket::response::status::Custom;
+use rocket::response::{self, Responder, Response};
+use rocket::serde::json::Value;
+use rocket::tokio::fs;
+use rocket::tokio::io::AsyncReadExt;
+use rocket::State;
+
+use super::api::{self, ApiResult};
+use super::conn::DbConn;
+use super::storage::Storage;
+use super::tenancy::Viewer;
+
+/// The document's files, with the state of any jobs still working on them.
+#[get("/documents/<doc_id>/files")]
+pub async fn list(conn: DbConn, viewer: Viewer, doc_id: i32) -> ApiResult {
+    conn.run(move |conn| {
+        viewer.doc(conn, doc_id)?;
+        let mut result = vec![];
+        for file in files::for_doc(conn, doc_id).map_err(api::internal)? {
+            let jobs: Vec<_> = jobs::for_file(conn, file.id)
+                .map_err(api::internal)?
+                .into_iter()
+                .map(|j| json!({ "id": j.id, "kind": j.kind, "state": j.state, "error": j.error }))
+                .collect();
+            result.push(json!({
+                "id": file.id,
+                "role": file.role,
+                "mime": file.mime,
+                "size": file.size,
+                "source_id": file.source_id,
+                "created_at": file.created_at.to_string(),
+                "jobs": jobs,
+            }));
+        }
+        api::ok(json!(result))
+    })
+    .await
+}
+
+/// The `Range` request header, if any.
+pub struct Range(Option<String>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Range {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        Outcome::Success(Range(request.headers().get_one("Range").map(str::to_owned)))
+    }
+}
+
+/// Parse a single `bytes=start-end` range (multiple ranges aren't
+/// supported) into inclusive offsets.
+fn parse_range(header: &str, size: u64) -> Option<(u64, u64)> {
+    let spec = header.strip_prefix("bytes=")?;
+    let (start, end) = spec.split_once('-')?;
+    let (start, end) = match (start.trim(), end.trim()) {
+        // suffix range: the last n bytes
+        ("", n) => (size.checked_sub(n.parse().ok()?)?, size.checked_sub(1)?),
+        (start, "") => (start.parse().ok()?, size.checked_sub(1)?),
+        (start, end) => (start.parse().ok()?, end.parse::<u64>().ok()?.min(size - 1)),
+    };
+    if start <= end && end < size {
+        Some((start, end))
+    } else {
+        None
+    }
+}
+
+pub struct Stream {
+    file: File,
+    mime: ContentType,
+    size: u64,
+    /// Inclusive byte offsets.
+    range: Option<(u64, u64)>,
+}
+
+impl<'r> Responder<'r, 'static> for Stream {
+    fn respond_to(mut self, _: &'r Request<'_>) -> response::Result<'static> {
+        let mut response = Response::build();
+        response
+            .header(self.mime)
+            .raw_header("Accept-Ranges", "bytes");
+        match self.range {
+            Some((start, end)) => {
+                self.file
+                    .seek(SeekFrom::Start(start))
+                    .map_err(|_| Status::InternalServerError)?;
+                let len = end - start + 1;
+                response
+                    .status(Status::PartialContent)
+                    .raw_header(
+                        "Content-Range",
+                        format!("bytes {}-{}/{}", start, end, self.size),
+                    )
+                    .raw_header("Content-Length", len.to_string())
+                    .streamed_body(fs::File::from_std(self.file).take(len));
+            }
+            None => {
+                response.sized_body(self.size as usize, fs::File::from_std(self.file));
+            }
+        }
+        response.ok()
+    }
+}
+
+/// Stream a stored file, supporting range requests so that audio players
+/// can seek.
+#[get("/files/<id>")]
+pub async fn stream(
+    conn: DbConn,
+    viewer: Viewer,
+    storage: &State<Storage>,
+    id: i32,
+    range: Range,
+) -> Result<Stream, Custom<Value>> {
+    let storage = storage.inner().clone();
+    conn.run(move |conn| {
+        viewer.file(conn, id)?;
+        let file = match files::get(conn, id) {
+            Ok(file) => file,
+            Err(Error::NotFound) => return Err(api::error(Status::NotFound, "no such file")),
+            Err(e) => return Err(api::internal(e)),
+        };
+        let handle = File::open(storage.path(&file.path))
+            .map_err(|e| api::internal(format!("{}: {}", file.path, e)))?;
+        let size = handle.metadata().map_err(api::internal)?.len();
+        let range = match range.0 {
+            Some(header) => Some(parse_range(&header, size).ok_or_else(|| {
+                api::error(
+                    Status::RangeNotSatisfiable,
+                    format!("bad range {:?}", header),
+                )
+            })?),
+            None => None,
+        };
+        Ok(Stream {
+            file: handle,
+            mime: ContentType::parse_flexible(&file.mime).unwrap_or(ContentType::Binary),
+            size,
+            range,
+        })
+    })
+    .await
+}
@@ -0,0 +1,154 @@
+//! Frequency lists of tokens or matches of a query (see
+//! `eaf::frequencies`) across validated documents, i.e. those whose latest
+//! transcript was validated with no outstanding mistakes, optionally only
+//! counting speakers with the given attributes.
+
+use std::collections::hash_map::{Entry, HashMap};
+use std::collections::HashSet;
+use std::fs;
+
+use db::docs::{self, DocFilter};
+use db::speakers::{self, SpeakerFilter};
+use db::validation;
+use db::Conn;
+use eaf::frequencies::{self, FrequencyList};
+use rocket::http::ContentType;
+use rocket::response::status::Custom;
+use rocket::serde::json::Value;
+use rocket::State;
+
+use super::api::{self, ApiResult};
+use super::conn::DbConn;
+use super::rules;
+use super::search;
+use super::storage::Storage;
+use super::tenancy::Viewer;
+use super::tiers;
+
+#[derive(FromForm)]
+pub struct FrequencyParams {
+    project: Option<i32>,
+    corpus: Option<i32>,
+    /// Query whose matches are counted, every token by default.
+    query: Option<String>,
+    /// IDs of the speakers' gender, education and region.
+    gender: Option<i32>,
+    education: Option<i32>,
+    region: Option<i32>,
+}
+
+fn frequency_list(
+    conn: &Conn,
+    viewer: &Viewer,
+    storage: &Storage,
+    params: &FrequencyParams,
+) -> Result<FrequencyList, Custom<Value>> {
+    let project = viewer.scope(params.project)?;
+    let query = search::parse_query(params.query.as_deref().unwrap_or("[]"))?;
+    let speaker_filter = SpeakerFilter {
+        gender_id: params.gender,
+        education_id: params.education,
+        region_id: params.region,
+    };
+    let filter = DocFilter {
+        project_id: project,
+        corpus_id: params.corpus,
+        assigned_to_id: viewer.assignee(),
+        states: vec![],
+    };
+    let doc_ids: HashSet<_> = docs::list(conn, &filter)
+        .map_err(api::internal)?
+        .into_iter()
+        .map(|doc| doc.id)
+        .collect();
+
+    let mut projects = HashMap::new();
+    let mut list = FrequencyList::default();
+    for transcript in validation::transcripts(conn, project).map_err(api::internal)? {
+        if !doc_ids.contains(&transcript.file.doc_id) || !transcript.is_clean() {
+            continue;
+        }
+        let project_id = transcript.project_id;
+        if let Entry::Vacant(entry) = projects.entry(project_id) {
+            let config = rules::project_config(conn, project_id).map_err(api::internal)?;
+            let mapping = tiers::tier_mapping(conn, project_id).map_err(api::internal)?;
+            let nicknames = if speaker_filter.is_empty() {
+                None
+            } else {
+                Some(
+                    speakers::nicknames(conn, project_id, &speaker_filter)
+                        .map_err(api::internal)?,
+                )
+            };
+            entry.insert((config, mapping, nicknames));
+        }
+        let (config, mapping, nicknames) = &projects[&project_id];
+        let speaker = |nickname: &str| nicknames.as_ref().map_or(true, |n| n.contains(nickname));
+        // one broken transcript shouldn't make the whole corpus uncountable
+        let counted = fs::read_to_string(storage.path(&transcript.file.path))
+            .map_err(|e| e.to_string())
+            .and_then(|xml| {
+                list.add_transcript(&xml, mapping, config, &query, speaker)
+                    .map_err(|e| e.to_string())
+            });
+        if let Err(e) = counted {
+            eprintln!(
+                "skipping document {} in frequencies: {}",
+                transcript.file.doc_id, e
+            );
+        }
+    }
+    Ok(list)
+}
+
+/// The most frequent tokens (or matches of `query` in the query language),
+/// up to `limit` of them, along with the number of all of them.
+#[get("/frequencies?<limit>&<params..>")]
+pub async fn list(
+    conn: DbConn,
+    viewer: Viewer,
+    storage: &State<Storage>,
+    limit: Option<usize>,
+    params: FrequencyParams,
+) -> ApiResult {
+    let storage = storage.inner().clone();
+    conn.run(move |conn| {
+        let list = frequency_list(conn, &viewer, &storage, &params)?;
+        let tokens: Vec<_> = list
+            .sorted()
+            .into_iter()
+            .take(api::limit(limit))
+            .map(|(token, count)| json!({ "token": token, "count": count }))
+            .collect();
+        api::ok(json!({ "total": list.total(), "tokens": tokens }))
+    })
+    .await
+}
+
+/// The whole list, most frequent first.
+#[get("/frequencies.csv?<params..>")]
+pub async fn csv(
+    conn: DbConn,
+    viewer: Viewer,
+    storage: &State<Storage>,
+    params: FrequencyParams,
+) -> Result<(ContentType, Vec<u8>), Custom<Value>> {
+    let storage = storage.inner().clone();
+    conn.run(move |conn| {
+        let list = frequency_list(conn, &viewer, &storage, &params)?;
+        let mut writer = csv::Writer::from_writer(vec![]);
+        let mut write = || -> Result<(), csv::Error> {
+            writer.write_record(frequencies::CSV_HEADER)?;
+            for record in list.records() {
+                writer.write_record(record)?;
+            }
+            Ok(())
+        };
+        write().map_err(api::internal)?;
+        let body = writer
+            .into_inner()
+            .map_err(|e| api::internal(e.into_error()))?;
+        Ok((ContentType::CSV, body))
+    })
+    .await
+}
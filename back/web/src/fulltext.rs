@@ -0,0 +1,277 @@
+//! Full-text index of transcripts, for finding examples across the corpus
+//! without reading every transcript (cf. the query language in `search`).
+//! Segments on transcript tiers are indexed as their tokens, i.e. without
+//! markup, whenever a transcript is validated. Only each document's latest
+//! transcript is kept in the index.
+
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+
+use db::Conn;
+use db::{docs, files, jobs};
+use diesel::QueryResult;
+use eaf::annotations;
+use eaf::parser::{Node, Parser, ParserConfig};
+use rocket::serde::json::Value;
+use tantivy::collector::TopDocs;
+use tantivy::directory::MmapDirectory;
+use tantivy::query::{BooleanQuery, Occur, Query, QueryParser, TermSetQuery};
+use tantivy::schema::{Field, Schema, INDEXED, STORED, TEXT};
+use tantivy::tokenizer::TextAnalyzer;
+use tantivy::{Document, IndexReader, ReloadPolicy, Term};
+
+use super::rules;
+use super::storage::Storage;
+use super::tiers;
+
+/// Where the index goes, relative to the storage directory.
+const DIR: &str = "search";
+/// Memory for buffering a transcript's segments before they're committed.
+const WRITER_HEAP: usize = 50_000_000;
+/// How many tokens hits show on either side of the match.
+const CONTEXT: usize = 5;
+
+#[derive(Debug)]
+pub enum SearchError {
+    /// The query doesn't parse.
+    Query(String),
+    Index(tantivy::TantivyError),
+}
+
+impl fmt::Display for SearchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SearchError::Query(e) => write!(f, "bad query: {}", e),
+            SearchError::Index(e) => e.fmt(f),
+        }
+    }
+}
+
+impl From<tantivy::TantivyError> for SearchError {
+    fn from(e: tantivy::TantivyError) -> Self {
+        SearchError::Index(e)
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Fields {
+    doc_id: Field,
+    file_id: Field,
+    tier: Field,
+    annotation: Field,
+    /// Time offsets of the segment, in milliseconds.
+    start: Field,
+    end: Field,
+    /// The segment's tokens, separated by spaces.
+    text: Field,
+}
+
+fn schema() -> (Schema, Fields) {
+    let mut builder = Schema::builder();
+    let fields = Fields {
+        doc_id: builder.add_u64_field("doc_id", INDEXED | STORED),
+        file_id: builder.add_u64_field("file_id", STORED),
+        tier: builder.add_text_field("tier", STORED),
+        annotation: builder.add_text_field("annotation", STORED),
+        start: builder.add_u64_field("start", STORED),
+        end: builder.add_u64_field("end", STORED),
+        text: builder.add_text_field("text", TEXT | STORED),
+    };
+    (builder.build(), fields)
+}
+
+/// The segment's tokens, separated by spaces.
+fn tokens(config: &ParserConfig, value: &str) -> String {
+    let parsed = Parser::parse(config, config.tokenize(value));
+    let tokens: Vec<_> = parsed
+        .nodes
+        .iter()
+        .filter_map(|node| match node {
+            Node::Token(token) => Some(&parsed.source[token.start..token.end]),
+            _ => None,
+        })
+        .collect();
+    tokens.join(" ")
+}
+
+/// Token ranges of the runs of consecutive tokens matching any of the
+/// (analyzed) terms.
+fn runs(
+    analyzer: &mut TextAnalyzer,
+    tokens: &[&str],
+    terms: &HashSet<String>,
+) -> Vec<(usize, usize)> {
+    let mut runs: Vec<(usize, usize)> = vec![];
+    for (i, token) in tokens.iter().enumerate() {
+        let mut matched = false;
+        analyzer
+            .token_stream(token)
+            .process(&mut |t| matched |= terms.contains(&t.text));
+        if !matched {
+            continue;
+        }
+        match runs.last_mut() {
+            Some((_, end)) if *end == i => *end = i + 1,
+            _ => runs.push((i, i + 1)),
+        }
+    }
+    runs
+}
+
+/// Cheap to clone, the index and its reader being shared.
+#[derive(Clone)]
+pub struct Index {
+    index: tantivy::Index,
+    reader: IndexReader,
+    fields: Fields,
+}
+
+impl Index {
+    /// Open the index in the storage directory, creating it if need be.
+    pub fn open(storage: &Storage) -> tantivy::Result<Self> {
+        let dir = storage.path(DIR);
+        fs::create_dir_all(&dir)?;
+        let (schema, fields) = schema();
+        let index = tantivy::Index::open_or_create(MmapDirectory::open(&dir)?, schema)?;
+        let reader = index
+            .reader_builder()
+            .reload_policy(ReloadPolicy::OnCommit)
+            .try_into()?;
+        Ok(Index {
+            index,
+            reader,
+            fields,
+        })
+    }
+
+    /// Replace what's indexed of the file's document with the file's
+    /// segments, unless a newer transcript has been stored meanwhile.
+    fn reindex(&self, conn: &Conn, storage: &Storage, file_id: i32) -> Result<(), String> {
+        let file = files::get(conn, file_id).map_err(|e| e.to_string())?;
+        let latest = files::latest(conn, file.doc_id, &[files::EAF]).map_err(|e| e.to_string())?;
+        if latest.map(|f| f.id) != Some(file.id) {
+            return Ok(());
+        }
+        let project_id = docs::project_of(conn, file.doc_id).map_err(|e| e.to_string())?;
+        let path = storage.path(&file.path);
+        let xml = fs::read_to_string(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let config = rules::project_config(conn, project_id).map_err(|e| e.to_string())?;
+        let mapping = tiers::tier_mapping(conn, project_id)?;
+
+        let fields = self.fields;
+        let mut writer = self.index.writer(WRITER_HEAP).map_err(|e| e.to_string())?;
+        writer.delete_term(Term::from_field_u64(fields.doc_id, file.doc_id as u64));
+        for annotation in annotations::read(&xml).map_err(|e| e.to_string())? {
+            if !annotation.is_transcript(&mapping) {
+                continue;
+            }
+            let text = tokens(&config, &annotation.value);
+            if text.is_empty() {
+                continue;
+            }
+            let mut doc = Document::default();
+            doc.add_u64(fields.doc_id, file.doc_id as u64);
+            doc.add_u64(fields.file_id, file.id as u64);
+            doc.add_text(fields.tier, &annotation.tier);
+            doc.add_text(fields.annotation, &annotation.id);
+            if let Some(start) = annotation.start {
+                doc.add_u64(fields.start, start.into());
+            }
+            if let Some(end) = annotation.end {
+                doc.add_u64(fields.end, end.into());
+            }
+            doc.add_text(fields.text, &text);
+            writer.add_document(doc).map_err(|e| e.to_string())?;
+        }
+        writer.commit().map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    /// Segments of the given documents matching the query (in tantivy's
+    /// query syntax, all terms required by default), best first. Each run
+    /// of matching tokens in a segment is a separate hit, with some context
+    /// on either side.
+    pub fn search(
+        &self,
+        q: &str,
+        doc_ids: &[i32],
+        limit: usize,
+    ) -> Result<Vec<Value>, SearchError> {
+        if doc_ids.is_empty() {
+            return Ok(vec![]);
+        }
+        let fields = self.fields;
+        let mut parser = QueryParser::for_index(&self.index, vec![fields.text]);
+        parser.set_conjunction_by_default();
+        let query = parser
+            .parse_query(q)
+            .map_err(|e| SearchError::Query(e.to_string()))?;
+        let docs = TermSetQuery::new(
+            doc_ids
+                .iter()
+                .map(|&id| Term::from_field_u64(fields.doc_id, id as u64)),
+        );
+        let query = BooleanQuery::new(vec![
+            (Occur::Must, query),
+            (Occur::Must, Box::new(docs) as Box<dyn Query>),
+        ]);
+
+        let mut analyzer = self.index.tokenizer_for_field(fields.text)?;
+        let mut terms = HashSet::new();
+        analyzer.token_stream(q).process(&mut |t| {
+            terms.insert(t.text.clone());
+        });
+
+        let searcher = self.reader.searcher();
+        let mut hits = vec![];
+        for (_, address) in searcher.search(&query, &TopDocs::with_limit(limit))? {
+            let doc = searcher.doc(address)?;
+            let number = |field: Field| doc.get_first(field).and_then(|v| v.as_u64());
+            let text = |field: Field| {
+                doc.get_first(field)
+                    .and_then(|v| v.as_text())
+                    .unwrap_or_default()
+            };
+            let tokens: Vec<_> = text(fields.text).split(' ').collect();
+            let mut found = runs(&mut analyzer, &tokens, &terms);
+            // e.g. only a prefix of a token matched
+            if found.is_empty() {
+                found.push((0, tokens.len()));
+            }
+            for (start, end) in found {
+                hits.push(json!({
+                    "doc_id": number(fields.doc_id),
+                    "file_id": number(fields.file_id),
+                    "tier": text(fields.tier),
+                    "annotation": text(fields.annotation),
+                    "start": number(fields.start),
+                    "end": number(fields.end),
+                    "left": tokens[start.saturating_sub(CONTEXT)..start].join(" "),
+                    "match": tokens[start..end].join(" "),
+                    "right": tokens[end..(end + CONTEXT).min(tokens.len())].join(" "),
+                }));
+            }
+        }
+        hits.truncate(limit);
+        Ok(hits)
+    }
+}
+
+/// Queue indexing of a (validated) transcript, unless it's queued already.
+/// Returns whether it was queued now.
+pub fn enqueue(conn: &Conn, file_id: i32) -> QueryResult<bool> {
+    if jobs::is_queued(conn, jobs::INDEX, file_id)? {
+        return Ok(false);
+    }
+    jobs::enqueue(conn, jobs::INDEX, file_id)?;
+    Ok(true)
+}
+
+/// Index a stored transcript, replacing its document's previous one. Only
+/// the job worker does this, as the index only admits one writer at a time.
+pub fn index(conn: &Conn, storage: &Storage, file_id: i32) -> Result<(), String> {
+    Index::open(storage)
+        .map_err(|e| e.to_string())?
+        .reindex(conn, storage, file_id)
+}
@@ -0,0 +1,291 @@
+//! Places and regions: typeahead endpoints, so that forms don't need to
+//! download the whole gazetteer, management of the hierarchy, and a map of
+//! corpus coverage.
+
+use db::geo::{self, Completion, InUse, PlaceData};
+use db::Conn;
+use rocket::http::{ContentType, Status};
+use rocket::response::status::Custom;
+use rocket::serde::json::Value;
+use serde::Deserialize;
+use validator::Validate;
+
+use super::api::{self, ApiResult};
+use super::body::{JsonBody, Valid};
+use super::conn::DbConn;
+use super::tenancy::{Allowed, GeoEdit, Viewer};
+
+fn to_json(completions: Vec<Completion>) -> Value {
+    let completions: Vec<_> = completions
+        .into_iter()
+        .map(|c| json!({ "id": c.id, "label": c.label, "region": c.region, "uses": c.uses }))
+        .collect();
+    json!(completions)
+}
+
+#[get("/places/complete?<q>&<limit>")]
+pub async fn complete_places(conn: DbConn, q: String, limit: Option<usize>) -> ApiResult {
+    conn.run(move |conn| {
+        let completions =
+            geo::complete_places(conn, &q, api::limit(limit)).map_err(api::internal)?;
+        api::ok(to_json(completions))
+    })
+    .await
+}
+
+#[get("/regions/complete?<q>&<limit>")]
+pub async fn complete_regions(conn: DbConn, q: String, limit: Option<usize>) -> ApiResult {
+    conn.run(move |conn| {
+        let completions =
+            geo::complete_regions(conn, &q, api::limit(limit)).map_err(api::internal)?;
+        api::ok(to_json(completions))
+    })
+    .await
+}
+
+fn in_use(e: InUse, what: &str) -> Custom<Value> {
+    match e {
+        InUse::Uses(n) => api::error(
+            Status::UnprocessableEntity,
+            format!("{} is still in use ({} references)", what, n),
+        ),
+        InUse::Db(e) => api::internal(e),
+    }
+}
+
+/// Regions with their places, and the dialect areas places can be
+/// classified into.
+#[get("/regions")]
+pub async fn list(conn: DbConn) -> ApiResult {
+    conn.run(move |conn| {
+        let places = geo::places(conn, None).map_err(api::internal)?;
+        let regions: Vec<_> = geo::regions(conn)
+            .map_err(api::internal)?
+            .into_iter()
+            .map(|r| {
+                let places: Vec<_> = places
+                    .iter()
+                    .filter(|p| p.region_id == r.id)
+                    .map(|p| {
+                        json!({
+                            "id": p.id,
+                            "label": p.label,
+                            "latitude": p.latitude,
+                            "longitude": p.longitude,
+                            "dialect_area_id": p.dialect_area_id,
+                        })
+                    })
+                    .collect();
+                json!({ "id": r.id, "label": r.label, "places": places })
+            })
+            .collect();
+        let dialect_areas: Vec<_> = geo::dialect_areas(conn)
+            .map_err(api::internal)?
+            .into_iter()
+            .map(|a| json!({ "id": a.id, "label": a.label }))
+            .collect();
+        api::ok(json!({ "regions": regions, "dialect_areas": dialect_areas }))
+    })
+    .await
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RegionRequest {
+    label: String,
+}
+
+fn check_region(conn: &Conn, id: Option<i32>, label: &str) -> Result<(), Custom<Value>> {
+    if label.is_empty() {
+        return Err(api::error(Status::UnprocessableEntity, "empty label"));
+    }
+    let regions = geo::regions(conn).map_err(api::internal)?;
+    if regions.iter().any(|r| r.label == label && Some(r.id) != id) {
+        return Err(api::error(
+            Status::UnprocessableEntity,
+            format!("region {:?} already exists", label),
+        ));
+    }
+    Ok(())
+}
+
+#[post("/regions", data = "<request>")]
+pub async fn add_region(
+    conn: DbConn,
+    _viewer: Allowed<GeoEdit>,
+    request: JsonBody<RegionRequest>,
+) -> ApiResult {
+    conn.run(move |conn| {
+        let label = request.label.trim();
+        check_region(conn, None, label)?;
+        let id = geo::add_region(conn, label).map_err(api::internal)?;
+        api::ok(json!({ "id": id }))
+    })
+    .await
+}
+
+#[put("/regions/<id>", data = "<request>")]
+pub async fn rename_region(
+    conn: DbConn,
+    _viewer: Allowed<GeoEdit>,
+    id: i32,
+    request: JsonBody<RegionRequest>,
+) -> ApiResult {
+    conn.run(move |conn| {
+        let label = request.label.trim();
+        check_region(conn, Some(id), label)?;
+        if !geo::rename_region(conn, id, label).map_err(api::internal)? {
+            return Err(api::error(Status::NotFound, "no such region"));
+        }
+        api::ok(json!({ "id": id }))
+    })
+    .await
+}
+
+/// Only regions without places can be removed.
+#[delete("/regions/<id>")]
+pub async fn remove_region(conn: DbConn, _viewer: Allowed<GeoEdit>, id: i32) -> ApiResult {
+    conn.run(move |conn| {
+        if !geo::remove_region(conn, id).map_err(|e| in_use(e, "region"))? {
+            return Err(api::error(Status::NotFound, "no such region"));
+        }
+        api::ok(json!(null))
+    })
+    .await
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct PlaceRequest {
+    label: String,
+    region_id: i32,
+    #[validate(range(min = -90.0, max = 90.0))]
+    latitude: Option<f64>,
+    #[validate(range(min = -180.0, max = 180.0))]
+    longitude: Option<f64>,
+    dialect_area_id: Option<i32>,
+}
+
+fn place_data(
+    conn: &Conn,
+    id: Option<i32>,
+    request: &PlaceRequest,
+) -> Result<PlaceData, Custom<Value>> {
+    let invalid = |message: String| Err(api::error(Status::UnprocessableEntity, message));
+    let label = request.label.trim();
+    if label.is_empty() {
+        return invalid("empty label".to_owned());
+    }
+    let places = geo::places(conn, None).map_err(api::internal)?;
+    if places.iter().any(|p| p.label == label && Some(p.id) != id) {
+        return invalid(format!("place {:?} already exists", label));
+    }
+    let regions = geo::regions(conn).map_err(api::internal)?;
+    if !regions.iter().any(|r| r.id == request.region_id) {
+        return invalid(format!("no such region {}", request.region_id));
+    }
+    if let Some(area_id) = request.dialect_area_id {
+        let areas = geo::dialect_areas(conn).map_err(api::internal)?;
+        if !areas.iter().any(|a| a.id == area_id) {
+            return invalid(format!("no such dialect area {}", area_id));
+        }
+    }
+    if request.latitude.is_some() != request.longitude.is_some() {
+        return invalid("latitude and longitude must be given together".to_owned());
+    }
+    Ok(PlaceData {
+        label: label.to_owned(),
+        region_id: request.region_id,
+        latitude: request.latitude,
+        longitude: request.longitude,
+        dialect_area_id: request.dialect_area_id,
+    })
+}
+
+#[post("/places", data = "<request>")]
+pub async fn add_place(
+    conn: DbConn,
+    _viewer: Allowed<GeoEdit>,
+    request: Valid<PlaceRequest>,
+) -> ApiResult {
+    conn.run(move |conn| {
+        let place = place_data(conn, None, &request)?;
+        let id = geo::add_place(conn, &place).map_err(api::internal)?;
+        api::ok(json!({ "id": id }))
+    })
+    .await
+}
+
+#[put("/places/<id>", data = "<request>")]
+pub async fn update_place(
+    conn: DbConn,
+    _viewer: Allowed<GeoEdit>,
+    id: i32,
+    request: Valid<PlaceRequest>,
+) -> ApiResult {
+    conn.run(move |conn| {
+        let place = place_data(conn, Some(id), &request)?;
+        if !geo::update_place(conn, id, &place).map_err(api::internal)? {
+            return Err(api::error(Status::NotFound, "no such place"));
+        }
+        api::ok(json!({ "id": id }))
+    })
+    .await
+}
+
+/// Only places no speakers or documents refer to can be removed.
+#[delete("/places/<id>")]
+pub async fn remove_place(conn: DbConn, _viewer: Allowed<GeoEdit>, id: i32) -> ApiResult {
+    conn.run(move |conn| {
+        if !geo::remove_place(conn, id).map_err(|e| in_use(e, "place"))? {
+            return Err(api::error(Status::NotFound, "no such place"));
+        }
+        api::ok(json!(null))
+    })
+    .await
+}
+
+/// Corpus coverage as a GeoJSON feature collection of places, with the
+/// number of documents and speakers from each, optionally only in one
+/// project and/or corpus. This is plain GeoJSON rather than the usual API
+/// envelope, so that map libraries can load it directly. Places without
+/// coordinates are listed separately under `unlocated`.
+#[get("/coverage?<project>&<corpus>")]
+pub async fn coverage(
+    conn: DbConn,
+    viewer: Viewer,
+    project: Option<i32>,
+    corpus: Option<i32>,
+) -> Result<(ContentType, Value), Custom<Value>> {
+    conn.run(move |conn| {
+        let project = viewer.scope(project)?;
+        let mut features = vec![];
+        let mut unlocated = vec![];
+        for c in geo::coverage(conn, project, corpus).map_err(api::internal)? {
+            let properties = json!({
+                "id": c.place_id,
+                "place": c.place,
+                "region": c.region,
+                "dialect_area": c.dialect_area,
+                "documents": c.documents,
+                "speakers": c.speakers,
+            });
+            match (c.latitude, c.longitude) {
+                (Some(lat), Some(lon)) => features.push(json!({
+                    "type": "Feature",
+                    // GeoJSON has longitude first
+                    "geometry": { "type": "Point", "coordinates": [lon, lat] },
+                    "properties": properties,
+                })),
+                _ => unlocated.push(properties),
+            }
+        }
+        Ok((
+            ContentType::new("application", "geo+json"),
+            json!({
+                "type": "FeatureCollection",
+                "features": features,
+                "unlocated": unlocated,
+            }),
+        ))
+    })
+    .await
+}
@@ -0,0 +1,251 @@
+//! Header metadata of documents' latest transcripts (see `eaf::header`):
+//! author, date, licenses and properties. Stamps change some of them and
+//! keep the rest, either for a single document or in bulk for a project or
+//! corpus, e.g. at release time. Each stamped transcript is stored as a new
+//! version and recorded in the audit log.
+
+use std::collections::BTreeMap;
+use std::fs;
+
+use chrono::{DateTime, Local};
+use db::docs::{self, DocFilter};
+use db::Conn;
+use db::{audit, files};
+use eaf::header::{self, Header, License};
+use rocket::http::Status;
+use rocket::response::status::Custom;
+use rocket::serde::json::Value;
+use rocket::State;
+use serde::Deserialize;
+
+use super::api::{self, ApiResult};
+use super::body::JsonBody;
+use super::conn::DbConn;
+use super::storage::{FileInfo, Storage};
+use super::tenancy::{Allowed, DocEdit, ExportRelease, Viewer};
+
+/// Audit log action for stamps, per document.
+const STAMP: &str = "document.header";
+
+#[derive(Debug, Deserialize)]
+pub struct LicenseRequest {
+    url: Option<String>,
+    text: String,
+}
+
+/// Changes to make to headers, missing fields are left alone.
+#[derive(Debug, Deserialize)]
+pub struct Stamp {
+    author: Option<String>,
+    /// An RFC 3339 timestamp.
+    date: Option<String>,
+    /// Replace all licenses.
+    licenses: Option<Vec<LicenseRequest>>,
+    /// Properties to set, or remove where null.
+    #[serde(default)]
+    properties: BTreeMap<String, Option<String>>,
+    user_id: Option<i32>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BulkStamp {
+    project: Option<i32>,
+    corpus: Option<i32>,
+    #[serde(flatten)]
+    stamp: Stamp,
+}
+
+impl Stamp {
+    fn validate(&self) -> Result<(), Custom<Value>> {
+        if let Some(date) = &self.date {
+            DateTime::parse_from_rfc3339(date).map_err(|e| {
+                api::error(
+                    Status::UnprocessableEntity,
+                    format!("invalid date {:?}: {}", date, e),
+                )
+            })?;
+        }
+        if self.properties.keys().any(|name| name.trim().is_empty()) {
+            return Err(api::error(
+                Status::UnprocessableEntity,
+                "empty property name",
+            ));
+        }
+        Ok(())
+    }
+
+    fn apply(&self, header: &mut Header) {
+        if let Some(author) = &self.author {
+            header.set_author(author.as_str());
+        }
+        if let Some(date) = &self.date {
+            header.set_date(date.as_str());
+        }
+        if let Some(licenses) = &self.licenses {
+            header.set_licenses(
+                licenses
+                    .iter()
+                    .map(|l| License {
+                        url: l.url.clone(),
+                        text: l.text.clone(),
+                    })
+                    .collect(),
+            );
+        }
+        for (name, value) in &self.properties {
+            match value {
+                Some(value) => header.set_property(name.as_str(), value.as_str()),
+                None => {
+                    header.remove_property(name);
+                }
+            }
+        }
+    }
+}
+
+fn header_json(header: &Header) -> Value {
+    let licenses: Vec<_> = header
+        .licenses()
+        .iter()
+        .map(|l| json!({ "url": l.url, "text": l.text }))
+        .collect();
+    let properties: Vec<_> = header
+        .properties()
+        .iter()
+        .map(|(name, value)| json!({ "name": name, "value": value }))
+        .collect();
+    json!({
+        "author": header.author(),
+        "date": header.date(),
+        "licenses": licenses,
+        "properties": properties,
+    })
+}
+
+/// Apply the stamp to the document's latest transcript and store the
+/// result as a new version, unless nothing changed. Returns the ID of the
+/// transcript's (possibly new) latest version and its header, or `None` if
+/// the document has no transcript.
+fn stamp_doc(
+    conn: &Conn,
+    storage: &Storage,
+    doc_id: i32,
+    stamp: &Stamp,
+) -> Result<Option<(i32, Header)>, Custom<Value>> {
+    let file = match files::latest(conn, doc_id, &[files::EAF]).map_err(api::internal)? {
+        Some(file) => file,
+        None => return Ok(None),
+    };
+    let xml = fs::read_to_string(storage.path(&file.path)).map_err(api::internal)?;
+    let unprocessable = |e: header::HeaderError| {
+        api::error(
+            Status::UnprocessableEntity,
+            format!("document {}: {}", doc_id, e),
+        )
+    };
+    let mut header = header::read(&xml).map_err(unprocessable)?;
+    let before = header.clone();
+    stamp.apply(&mut header);
+    if header == before {
+        return Ok(Some((file.id, header)));
+    }
+    let xml = header::write(&xml, &header).map_err(unprocessable)?;
+
+    let stamp_time = Local::now().format("%Y%m%d-%H%M%S");
+    let file_id = storage
+        .store(
+            conn,
+            doc_id,
+            &format!("header-{}-{}.eaf", stamp_time, file.id),
+            xml.as_bytes(),
+            xml.len() as u64,
+            FileInfo {
+                role: files::EAF,
+                mime: "application/xml",
+                created_by: stamp.user_id,
+                source_id: Some(file.id),
+            },
+        )
+        .map_err(api::internal)?;
+    let details = json!({
+        "file_id": file_id,
+        "source_id": file.id,
+        "header": header_json(&header),
+    });
+    audit::record(conn, stamp.user_id, STAMP, "document", doc_id, &details)
+        .map_err(api::internal)?;
+    Ok(Some((file_id, header)))
+}
+
+#[get("/documents/<doc_id>/header")]
+pub async fn get(conn: DbConn, viewer: Viewer, storage: &State<Storage>, doc_id: i32) -> ApiResult {
+    let storage = storage.inner().clone();
+    conn.run(move |conn| {
+        viewer.doc(conn, doc_id)?;
+        let file = match files::latest(conn, doc_id, &[files::EAF]).map_err(api::internal)? {
+            Some(file) => file,
+            None => return Err(api::error(Status::NotFound, "document has no transcript")),
+        };
+        let xml = fs::read_to_string(storage.path(&file.path)).map_err(api::internal)?;
+        let header = header::read(&xml).map_err(|e| api::error(Status::UnprocessableEntity, e))?;
+        api::ok(json!({ "file_id": file.id, "header": header_json(&header) }))
+    })
+    .await
+}
+
+#[patch("/documents/<doc_id>/header", data = "<stamp>")]
+pub async fn patch(
+    conn: DbConn,
+    viewer: Allowed<DocEdit>,
+    storage: &State<Storage>,
+    doc_id: i32,
+    stamp: JsonBody<Stamp>,
+) -> ApiResult {
+    let storage = storage.inner().clone();
+    conn.run(move |conn| {
+        viewer.doc(conn, doc_id)?;
+        stamp.validate()?;
+        match stamp_doc(conn, &storage, doc_id, &stamp)? {
+            Some((file_id, header)) => {
+                api::ok(json!({ "file_id": file_id, "header": header_json(&header) }))
+            }
+            None => Err(api::error(Status::NotFound, "document has no transcript")),
+        }
+    })
+    .await
+}
+
+/// Stamp all documents of the project and/or corpus which have a
+/// transcript.
+#[post("/admin/header", data = "<request>")]
+pub async fn stamp_all(
+    conn: DbConn,
+    _viewer: Allowed<ExportRelease>,
+    storage: &State<Storage>,
+    request: JsonBody<BulkStamp>,
+) -> ApiResult {
+    let storage = storage.inner().clone();
+    conn.run(move |conn| {
+        if request.project.is_none() && request.corpus.is_none() {
+            return Err(api::error(
+                Status::UnprocessableEntity,
+                "select a project or corpus",
+            ));
+        }
+        request.stamp.validate()?;
+        let filter = DocFilter {
+            project_id: request.project,
+            corpus_id: request.corpus,
+            assigned_to_id: None,
+            states: vec![],
+        };
+        let mut stamped = vec![];
+        for doc in docs::list(conn, &filter).map_err(api::internal)? {
+            if let Some((file_id, _)) = stamp_doc(conn, &storage, doc.id, &request.stamp)? {
+                stamped.push(json!({ "doc_id": doc.id, "file_id": file_id }));
+            }
+        }
+        api::ok(json!(stamped))
+    })
+    .await
+}
@@ -0,0 +1,50 @@
+//! Conversion of legacy plain-text transcripts to EAF.
+
+use db::files;
+use rocket::http::Status;
+use rocket::{Data, State};
+
+use super::api::{self, ApiResult};
+use super::asr::{self, EafInfo};
+use super::conn::DbConn;
+use super::storage::Storage;
+use super::tenancy::{Allowed, DocEdit};
+
+/// Legacy transcripts are plain text, a few hundred KiB at most.
+const LEGACY_LIMIT: u64 = 4 * 1024 * 1024;
+
+/// Convert a legacy transcript in the request body (see `eaf::legacy`) to
+/// the document's EAF transcript, with approximate alignments between the
+/// timestamp markers.
+#[post("/documents/<doc_id>/legacy-transcript?<user>", data = "<body>")]
+pub async fn import(
+    conn: DbConn,
+    viewer: Allowed<DocEdit>,
+    storage: &State<Storage>,
+    doc_id: i32,
+    user: Option<i32>,
+    body: Data<'_>,
+) -> ApiResult {
+    conn.run(move |conn| viewer.doc(conn, doc_id)).await?;
+    let text = asr::read_body(body, LEGACY_LIMIT).await?;
+    let segments =
+        eaf::legacy::read(&text).map_err(|e| api::error(Status::UnprocessableEntity, e))?;
+    let storage = storage.inner().clone();
+    conn.run(move |conn| {
+        asr::store_eaf(
+            conn,
+            &storage,
+            doc_id,
+            segments,
+            // every legacy segment has a speaker
+            "",
+            EafInfo {
+                author: "legacy import",
+                name: "legacy",
+                role: files::EAF,
+                created_by: user,
+            },
+        )
+    })
+    .await
+}
@@ -1,54 +1,451 @@
-#![feature(proc_macro_hygiene, decl_macro)]
-
 use std::path::PathBuf;
 
 #[macro_use]
 extern crate rocket;
 #[macro_use]
-extern crate rocket_contrib;
+extern crate serde_json;
 
-use rocket::response::content::{Html, JavaScript};
-use rocket_contrib::json::JsonValue;
-// use rocket_contrib::serve::StaticFiles;
+mod acknowledgments;
+mod alignments;
+mod api;
+mod asr;
+mod assignments;
+mod audio;
+mod audit;
+mod backups;
+mod body;
+mod bookmarks;
+mod bundle;
+mod comments;
+mod compression;
+mod conn;
+mod corpora;
+mod dictionaries;
+mod digest;
+mod documents;
+mod exports;
+mod files;
+mod frequencies;
+mod fulltext;
+mod geo;
+mod header;
+mod legacy;
+mod members;
+mod metadata;
+mod openapi;
+mod palette;
+mod parser_configs;
+mod public;
+mod ratelimit;
+mod replace;
+mod report;
+mod revalidation;
+mod reviews;
+mod revisions;
+mod roles;
+mod rules;
+mod scheduler;
+mod search;
+mod sessions;
+mod speakers;
+mod stats;
+mod storage;
+mod substitutions;
+mod sync;
+mod tenancy;
+mod tier_policies;
+mod tiers;
+mod transcripts;
+mod two_factor;
+mod users;
+mod validate;
+mod validation;
+mod vc;
+mod webhooks;
+mod worker;
 
-// _path below currently doesn't capture empty paths, so we need to treat
-// index specially and redirect from it manually; cf.
-// https://github.com/SergioBenitez/Rocket/issues/985
-#[get("/", format = "text/html")]
-fn index() -> Html<String> {
-    frontend_ui(None)
-}
+use rocket::fairing::AdHoc;
+use rocket::figment::Figment;
+use rocket::response::content::{RawHtml, RawJavaScript};
+use rocket::{Build, Rocket, Route};
 
-#[get("/<_path..>", format = "text/html")]
-fn frontend_ui(_path: Option<PathBuf>) -> Html<String> {
+// ranked low so that it doesn't shadow partially dynamic API routes; also
+// matches the index, i.e. an empty path
+#[get("/<_path..>", format = "text/html", rank = 20)]
+fn frontend_ui(_path: PathBuf) -> RawHtml<String> {
     let main_html = include_str!("../../../front/src/main.html");
-    Html(main_html.replace("MAIN_JS", "/main.js"))
+    RawHtml(main_html.replace("MAIN_JS", "/main.js"))
 }
 
 // implement ?v=hash cache bypass
 #[get("/main.js", format = "application/javascript")]
-fn main_js() -> JavaScript<&'static str> {
-    JavaScript(include_str!("../../../front/target/main.js"))
-}
-
-// this is more correct...
-// #[get("/documents", format = "application/json")]
-// ... but this makes it easier to test the API by sending requests from the
-// browser:
-#[get("/documents")]
-fn documents() -> JsonValue {
-    json!({
-        "data": [
-            { "id": "12A001N" },
-            { "id": "12A002N" }
-        ],
-        "errors": []
+fn main_js() -> RawJavaScript<&'static str> {
+    RawJavaScript(include_str!("../../../front/target/main.js"))
+}
+
+/// Where stored files go unless configured otherwise.
+const DEFAULT_STORAGE_DIR: &str = "storage";
+/// Where backups go unless configured otherwise.
+const DEFAULT_BACKUP_DIR: &str = "backups";
+/// Where version control repositories go unless configured otherwise.
+const DEFAULT_VC_DIR: &str = "vc";
+/// Requests per minute a client can make to the public API unless
+/// configured otherwise.
+const DEFAULT_PUBLIC_RATE_LIMIT: i64 = 60;
+/// Responses smaller than this many bytes aren't compressed unless
+/// configured otherwise.
+const DEFAULT_COMPRESSION_MIN_SIZE: i64 = 1024;
+/// Database connections handed out to requests at once unless configured
+/// otherwise. Most requests also take one for checking the viewer.
+const DEFAULT_DATABASE_POOL_SIZE: u32 = 16;
+
+fn pool(config: &Figment) -> Result<db::pool::Pool, String> {
+    let database_url: String = config
+        .extract_inner("database_url")
+        .map_err(|e| e.to_string())?;
+    let size = config
+        .extract_inner("database_pool_size")
+        .unwrap_or(DEFAULT_DATABASE_POOL_SIZE);
+    db::pool::new(&database_url, size).map_err(|e| e.to_string())
+}
+
+fn storage(config: &Figment) -> Result<storage::Storage, String> {
+    let dir = config
+        .extract_inner::<String>("storage_dir")
+        .unwrap_or_else(|_| DEFAULT_STORAGE_DIR.to_owned());
+    let backend = match config
+        .extract_inner::<String>("transcript_backend")
+        .as_deref()
+    {
+        Ok("files") => storage::Backend::Files,
+        Err(e) if e.missing() => storage::Backend::Files,
+        Ok("git") => storage::Backend::Git(vc_repos(config)),
+        Ok(other) => return Err(format!("unknown transcript backend {}", other)),
+        Err(e) => return Err(e.to_string()),
+    };
+    Ok(storage::Storage {
+        dir: dir.into(),
+        backend,
     })
 }
 
-fn main() {
-    rocket::ignite()
-        .mount("/", routes![index, frontend_ui, main_js])
-        .mount("/api", routes![documents])
-        .launch();
+fn backup_dir(config: &Figment) -> backups::BackupDir {
+    let dir = config
+        .extract_inner::<String>("backup_dir")
+        .unwrap_or_else(|_| DEFAULT_BACKUP_DIR.to_owned());
+    backups::BackupDir(dir.into())
+}
+
+fn vc_repos(config: &Figment) -> vc::Repos {
+    let dir = config
+        .extract_inner::<String>("vc_dir")
+        .unwrap_or_else(|_| DEFAULT_VC_DIR.to_owned());
+    vc::Repos(dir.into())
+}
+
+fn worker_config(config: &Figment) -> Result<worker::WorkerConfig, String> {
+    let database_url = config
+        .extract_inner("database_url")
+        .map_err(|e| e.to_string())?;
+    let ffmpeg = config
+        .extract_inner::<String>("ffmpeg")
+        .unwrap_or_else(|_| "ffmpeg".to_owned());
+    Ok(worker::WorkerConfig {
+        database_url,
+        storage: storage(config)?,
+        ffmpeg,
+    })
+}
+
+fn scheduler_config(config: &Figment) -> Result<scheduler::SchedulerConfig, String> {
+    let database_url = config
+        .extract_inner("database_url")
+        .map_err(|e| e.to_string())?;
+    let mail = match config.extract_inner::<String>("smtp_host") {
+        Ok(smtp_host) => {
+            let smtp_port = config.extract_inner("smtp_port").unwrap_or(25);
+            let from = config
+                .extract_inner::<String>("mail_from")
+                .map_err(|e| e.to_string())?
+                .parse()
+                .map_err(|e| format!("bad mail_from: {}", e))?;
+            Some(digest::MailConfig {
+                smtp_host,
+                smtp_port,
+                from,
+            })
+        }
+        Err(_) => None,
+    };
+    Ok(scheduler::SchedulerConfig {
+        database_url,
+        mail,
+        storage: storage(config)?,
+    })
+}
+
+/// The internal API, mounted under `openapi::BASE`.
+fn api_routes() -> Vec<Route> {
+    routes![
+        acknowledgments::create,
+        acknowledgments::delete,
+        acknowledgments::list,
+        alignments::conllu,
+        alignments::vertical,
+        alignments::get,
+        alignments::put,
+        asr::import,
+        assignments::assign,
+        assignments::done,
+        assignments::workload,
+        audio::upload,
+        audit::document,
+        audit::user,
+        backups::create,
+        backups::list,
+        backups::verify,
+        bookmarks::create,
+        bookmarks::delete,
+        bookmarks::list,
+        bundle::create,
+        comments::create,
+        comments::delete,
+        comments::list,
+        corpora::get,
+        corpora::list,
+        corpora::put,
+        corpora::release,
+        dictionaries::get,
+        dictionaries::put,
+        documents::duplicate,
+        documents::list,
+        files::list,
+        files::stream,
+        frequencies::csv,
+        frequencies::list,
+        geo::add_place,
+        geo::add_region,
+        geo::complete_places,
+        geo::complete_regions,
+        geo::coverage,
+        geo::list,
+        geo::remove_place,
+        geo::remove_region,
+        geo::rename_region,
+        geo::update_place,
+        header::get,
+        header::patch,
+        header::stamp_all,
+        legacy::import,
+        members::add,
+        members::list,
+        members::remove,
+        metadata::discrepancies,
+        metadata::upload,
+        palette::attrs,
+        palette::get,
+        palette::put,
+        parser_configs::get,
+        parser_configs::put,
+        replace::preview,
+        replace::replace,
+        report::report,
+        reviews::create,
+        reviews::list,
+        reviews::return_reasons,
+        revisions::changes,
+        revisions::get,
+        revisions::list,
+        revisions::restore,
+        roles::create,
+        roles::list,
+        roles::mine,
+        roles::permissions,
+        roles::set_permissions,
+        rules::latest,
+        rules::stale,
+        rules::version,
+        search::concordance,
+        search::reindex,
+        search::search,
+        sessions::create,
+        sessions::list,
+        sessions::login,
+        sessions::logout,
+        sessions::revoke,
+        sessions::revoke_all,
+        speakers::create,
+        speakers::delete,
+        speakers::doc_speakers,
+        speakers::duplicates,
+        speakers::fields,
+        speakers::get,
+        speakers::import,
+        speakers::link,
+        speakers::list,
+        speakers::merge,
+        speakers::search,
+        speakers::unlink,
+        speakers::update,
+        stats::csv,
+        stats::document,
+        stats::list,
+        stats::project,
+        stats::turn_taking_csv,
+        stats::word_counts,
+        substitutions::get,
+        substitutions::put,
+        sync::sync,
+        tier_policies::get,
+        tier_policies::put,
+        tiers::get,
+        tiers::put,
+        tiers::resolve,
+        transcripts::edit_annotation,
+        transcripts::upload,
+        two_factor::confirm,
+        two_factor::disable,
+        two_factor::enroll,
+        two_factor::recovery_codes,
+        two_factor::status,
+        users::mistake_patterns,
+        users::search,
+        users::set_password,
+        validate::validate,
+        validation::doc_mistake_kinds,
+        validation::project_mistake_kinds,
+        validation::regressions,
+        validation::revalidate,
+        vc::commit,
+        vc::commits,
+        vc::history,
+        vc::normalize,
+        vc::restore,
+        vc::revision,
+        webhooks::add,
+        webhooks::deliveries,
+        webhooks::list,
+        webhooks::remove,
+        webhooks::update,
+    ]
+}
+
+#[launch]
+fn rocket() -> Rocket<Build> {
+    let mut api = api_routes();
+    api.extend(routes![openapi::spec, openapi::ui]);
+    let spec = openapi::Spec::new(&api);
+    rocket::build()
+        .mount("/", routes![frontend_ui, main_js])
+        .mount(openapi::BASE, api)
+        .mount(
+            "/public",
+            routes![
+                public::corpora,
+                public::document,
+                public::documents,
+                public::search,
+                public::transcript_eaf,
+            ],
+        )
+        .manage(spec)
+        .register(
+            "/",
+            catchers![
+                body::bad_request,
+                body::unprocessable_entity,
+                ratelimit::too_many_requests,
+                tenancy::forbidden,
+                tenancy::unauthorized
+            ],
+        )
+        .attach(AdHoc::try_on_ignite("Database", |rocket| async move {
+            match pool(rocket.figment()) {
+                Ok(pool) => Ok(rocket.manage(pool)),
+                Err(e) => {
+                    eprintln!("bad database configuration: {}", e);
+                    Err(rocket)
+                }
+            }
+        }))
+        .attach(AdHoc::try_on_ignite("Storage", |rocket| async move {
+            match storage(rocket.figment()) {
+                Ok(storage) => Ok(rocket.manage(storage)),
+                Err(e) => {
+                    eprintln!("bad storage configuration: {}", e);
+                    Err(rocket)
+                }
+            }
+        }))
+        .attach(AdHoc::try_on_ignite("Search index", |rocket| async move {
+            let index = storage(rocket.figment())
+                .and_then(|storage| fulltext::Index::open(&storage).map_err(|e| e.to_string()));
+            match index {
+                Ok(index) => Ok(rocket.manage(index)),
+                Err(e) => {
+                    eprintln!("can't open search index: {}", e);
+                    Err(rocket)
+                }
+            }
+        }))
+        .attach(AdHoc::on_ignite("Rate limit", |rocket| async move {
+            let per_minute = rocket
+                .figment()
+                .extract_inner("public_rate_limit")
+                .unwrap_or(DEFAULT_PUBLIC_RATE_LIMIT);
+            rocket.manage(ratelimit::RateLimiter::new(per_minute.max(0) as u32))
+        }))
+        .attach(AdHoc::on_ignite("Backups", |rocket| async move {
+            let dir = backup_dir(rocket.figment());
+            rocket.manage(dir)
+        }))
+        .attach(AdHoc::on_ignite("Version control", |rocket| async move {
+            let repos = vc_repos(rocket.figment());
+            rocket.manage(repos)
+        }))
+        .attach(AdHoc::on_ignite("Compression", |rocket| async move {
+            let min_size = rocket
+                .figment()
+                .extract_inner("compression_min_size")
+                .unwrap_or(DEFAULT_COMPRESSION_MIN_SIZE);
+            rocket.attach(compression::Compression {
+                min_size: min_size.max(0) as usize,
+            })
+        }))
+        .attach(AdHoc::try_on_ignite("Webhooks", |rocket| async move {
+            match rocket.figment().extract_inner("database_url") {
+                Ok(url) => {
+                    webhooks::spawn(url);
+                    Ok(rocket)
+                }
+                Err(e) => {
+                    eprintln!("missing database configuration: {}", e);
+                    Err(rocket)
+                }
+            }
+        }))
+        .attach(AdHoc::try_on_ignite("Worker", |rocket| async move {
+            match worker_config(rocket.figment()) {
+                Ok(config) => {
+                    worker::spawn(config);
+                    Ok(rocket)
+                }
+                Err(e) => {
+                    eprintln!("invalid worker configuration: {}", e);
+                    Err(rocket)
+                }
+            }
+        }))
+        .attach(AdHoc::try_on_ignite("Scheduler", |rocket| async move {
+            match scheduler_config(rocket.figment()) {
+                Ok(config) => {
+                    scheduler::spawn(config);
+                    Ok(rocket)
+                }
+                Err(e) => {
+                    eprintln!("invalid scheduler configuration: {}", e);
+                    Err(rocket)
+                }
+            }
+        }))
 }
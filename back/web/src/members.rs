@@ -0,0 +1,83 @@
+//! Membership of users in projects, which decides what they can access
+//! (see `tenancy`). Only users with the `member.edit` permission can change
+//! it.
+
+use db::{audit, members};
+use diesel::result::Error;
+use rocket::http::Status;
+
+use super::api::{self, ApiResult};
+use super::conn::DbConn;
+use super::tenancy::{Allowed, MemberEdit, Viewer};
+
+/// Audit log actions for changes in membership, per project.
+const ADDED: &str = "project.member_added";
+const REMOVED: &str = "project.member_removed";
+
+#[get("/projects/<project_id>/members")]
+pub async fn list(conn: DbConn, viewer: Viewer, project_id: i32) -> ApiResult {
+    conn.run(move |conn| {
+        viewer.project(project_id)?;
+        let members: Vec<_> = members::for_project(conn, project_id)
+            .map_err(api::internal)?
+            .into_iter()
+            .map(|m| json!({ "id": m.id, "username": m.username, "role_id": m.role_id }))
+            .collect();
+        api::ok(json!(members))
+    })
+    .await
+}
+
+#[put("/admin/projects/<project_id>/members/<user_id>")]
+pub async fn add(
+    conn: DbConn,
+    viewer: Allowed<MemberEdit>,
+    project_id: i32,
+    user_id: i32,
+) -> ApiResult {
+    conn.run(move |conn| match members::add(conn, project_id, user_id) {
+        Ok(true) => {
+            let details = json!({ "user_id": user_id });
+            audit::record(
+                conn,
+                Some(viewer.user_id),
+                ADDED,
+                "project",
+                project_id,
+                &details,
+            )
+            .map_err(api::internal)?;
+            api::ok(json!({ "added": true }))
+        }
+        Ok(false) => api::ok(json!({ "added": false })),
+        Err(Error::NotFound) => Err(api::error(Status::NotFound, "no such project or user")),
+        Err(e) => Err(api::internal(e)),
+    })
+    .await
+}
+
+#[delete("/admin/projects/<project_id>/members/<user_id>")]
+pub async fn remove(
+    conn: DbConn,
+    viewer: Allowed<MemberEdit>,
+    project_id: i32,
+    user_id: i32,
+) -> ApiResult {
+    conn.run(move |conn| {
+        if !members::remove(conn, project_id, user_id).map_err(api::internal)? {
+            return Err(api::error(Status::NotFound, "no such member"));
+        }
+        let details = json!({ "user_id": user_id });
+        audit::record(
+            conn,
+            Some(viewer.user_id),
+            REMOVED,
+            "project",
+            project_id,
+            &details,
+        )
+        .map_err(api::internal)?;
+        api::ok(json!(null))
+    })
+    .await
+}
@@ -0,0 +1,142 @@
+//! Session metadata (CMDI or IMDI) accompanying transcripts, cross-checked
+//! against the DB on upload (see `db::metadata`).
+
+use chrono::Local;
+use db::metadata::{self, Session, SessionSpeaker};
+use db::Conn;
+use db::{audit, files};
+use eaf::metadata::{Format, Metadata, Sex};
+use rocket::http::Status;
+use rocket::response::status::Custom;
+use rocket::serde::json::Value;
+use rocket::{Data, State};
+
+use super::api::{self, ApiResult};
+use super::asr;
+use super::conn::DbConn;
+use super::storage::{FileInfo, Storage};
+use super::tenancy::{Allowed, DocEdit, Viewer};
+
+/// Metadata files are small, even with many actors.
+const METADATA_LIMIT: u64 = 1024 * 1024;
+/// Audit log action, per document.
+const LINKED: &str = "document.speakers_linked";
+
+fn session(metadata: &Metadata) -> Session {
+    Session {
+        date: metadata.date.clone(),
+        place: metadata.place.clone(),
+        region: metadata.region.clone(),
+        speakers: metadata
+            .actors
+            .iter()
+            .map(|actor| SessionSpeaker {
+                names: actor.names().map(str::to_owned).collect(),
+                gender_id: actor.sex.map(|sex| match sex {
+                    Sex::Male => metadata::MALE,
+                    Sex::Female => metadata::FEMALE,
+                }),
+                year: actor.birth_year,
+            })
+            .collect(),
+    }
+}
+
+/// Store a metadata file of the document and cross-check the document
+/// against it, e.g. the sidecar of an uploaded EAF.
+pub fn import(
+    conn: &Conn,
+    storage: &Storage,
+    doc_id: i32,
+    xml: &str,
+    created_by: Option<i32>,
+) -> Result<Value, Custom<Value>> {
+    let metadata =
+        eaf::metadata::read(xml).map_err(|e| api::error(Status::UnprocessableEntity, e))?;
+    let extension = match metadata.format {
+        Format::Cmdi => "cmdi",
+        Format::Imdi => "imdi",
+    };
+    let name = format!(
+        "metadata-{}.{}",
+        Local::now().format("%Y%m%d-%H%M%S"),
+        extension
+    );
+    let file_id = storage
+        .store(
+            conn,
+            doc_id,
+            &name,
+            xml.as_bytes(),
+            METADATA_LIMIT,
+            FileInfo {
+                role: files::METADATA,
+                mime: "application/xml",
+                created_by,
+                source_id: None,
+            },
+        )
+        .map_err(api::internal)?;
+    let check =
+        metadata::cross_check(conn, doc_id, file_id, &session(&metadata)).map_err(api::internal)?;
+    if !check.linked.is_empty() {
+        audit::record(
+            conn,
+            created_by,
+            LINKED,
+            "document",
+            doc_id,
+            &json!({ "file_id": file_id, "speakers": check.linked }),
+        )
+        .map_err(api::internal)?;
+    }
+    Ok(json!({
+        "file_id": file_id,
+        "linked_speakers": check.linked,
+        "discrepancies": check.discrepancies,
+    }))
+}
+
+/// Upload a CMDI or IMDI file with the document's session metadata. Returns
+/// the speakers linked to the document based on it and the number of
+/// discrepancies found.
+#[post("/documents/<doc_id>/metadata?<user>", data = "<body>")]
+pub async fn upload(
+    conn: DbConn,
+    viewer: Allowed<DocEdit>,
+    storage: &State<Storage>,
+    doc_id: i32,
+    user: Option<i32>,
+    body: Data<'_>,
+) -> ApiResult {
+    conn.run(move |conn| viewer.doc(conn, doc_id)).await?;
+    let xml = asr::read_body(body, METADATA_LIMIT).await?;
+    let storage = storage.inner().clone();
+    let check = conn
+        .run(move |conn| import(conn, &storage, doc_id, &xml, user))
+        .await?;
+    api::ok(check)
+}
+
+/// Where the document's latest metadata disagrees with the DB.
+#[get("/documents/<doc_id>/metadata/discrepancies")]
+pub async fn discrepancies(conn: DbConn, viewer: Viewer, doc_id: i32) -> ApiResult {
+    conn.run(move |conn| {
+        viewer.doc(conn, doc_id)?;
+        let discrepancies: Vec<_> = metadata::discrepancies(conn, doc_id)
+            .map_err(api::internal)?
+            .into_iter()
+            .map(|d| {
+                json!({
+                    "field": d.field,
+                    "speaker": d.speaker,
+                    "metadata": d.in_metadata,
+                    "db": d.in_db,
+                    "found_at": d.created_at.to_string(),
+                })
+            })
+            .collect();
+        api::ok(json!(discrepancies))
+    })
+    .await
+}
@@ -0,0 +1,161 @@
+//! A machine-readable description of the internal API in OpenAPI 3,
+//! generated from the mounted routes so that it can't go stale, and Swagger
+//! UI for browsing it. The routes only tell their paths and parameters;
+//! what the bodies look like is up to the handlers' docs, the responses are
+//! all in the usual envelope (see `api`).
+
+use std::collections::BTreeMap;
+
+use rocket::response::content::RawHtml;
+use rocket::serde::json::Value;
+use rocket::{Route, State};
+use serde_json::Map;
+
+/// Where the API is mounted.
+pub const BASE: &str = "/api";
+
+/// The description, built once at launch.
+pub struct Spec(Value);
+
+/// Path parameter names, with the path in OpenAPI syntax, e.g.
+/// `/documents/{doc_id}` for `/documents/<doc_id>`. Trailing `<path..>`
+/// parameters become `{path}`, which may contain slashes.
+fn path_params(path: &str) -> (String, Vec<String>) {
+    let mut params = vec![];
+    let segments: Vec<_> = path
+        .split('/')
+        .map(
+            |segment| match segment.strip_prefix('<').and_then(|s| s.strip_suffix('>')) {
+                Some(name) => {
+                    let name = name.trim_end_matches("..");
+                    params.push(name.to_owned());
+                    format!("{{{}}}", name)
+                }
+                None => segment.to_owned(),
+            },
+        )
+        .collect();
+    (segments.join("/"), params)
+}
+
+/// Query parameter names. Parameters collecting a whole form (`<form..>`)
+/// have no single name, so they're left out.
+fn query_params(query: Option<&str>) -> Vec<String> {
+    query
+        .into_iter()
+        .flat_map(|q| q.split('&'))
+        .filter_map(|segment| {
+            let name = segment.trim_start_matches('<').trim_end_matches('>');
+            if name.ends_with("..") || name.is_empty() {
+                None
+            } else {
+                Some(name.to_owned())
+            }
+        })
+        .collect()
+}
+
+fn param(name: &str, location: &str, required: bool) -> Value {
+    json!({
+        "name": name,
+        "in": location,
+        "required": required,
+        "schema": { "type": "string" },
+    })
+}
+
+impl Spec {
+    pub fn new(routes: &[Route]) -> Self {
+        let mut paths: BTreeMap<String, Map<String, Value>> = BTreeMap::new();
+        for route in routes {
+            let (path, in_path) = path_params(route.uri.path());
+            let mut params: Vec<_> = in_path.iter().map(|p| param(p, "path", true)).collect();
+            params.extend(
+                query_params(route.uri.query())
+                    .iter()
+                    .map(|p| param(p, "query", false)),
+            );
+            let tag = path.split('/').find(|s| !s.is_empty()).unwrap_or_default();
+            let mut operation = json!({
+                "tags": [tag],
+                "parameters": params,
+                "responses": {
+                    "default": {
+                        "description": "the data or errors in the usual envelope",
+                        "content": {
+                            "application/json": {
+                                "schema": { "$ref": "#/components/schemas/Envelope" },
+                            },
+                        },
+                    },
+                },
+            });
+            if let (Some(name), Value::Object(operation)) = (&route.name, &mut operation) {
+                operation.insert("summary".to_owned(), Value::from(name.as_ref()));
+            }
+            paths
+                .entry(path)
+                .or_default()
+                .insert(route.method.as_str().to_lowercase(), operation);
+        }
+        Spec(json!({
+            "openapi": "3.0.3",
+            "info": {
+                "title": "Quetzal",
+                "version": env!("CARGO_PKG_VERSION"),
+            },
+            "servers": [{ "url": BASE }],
+            "paths": paths,
+            "components": {
+                "schemas": {
+                    "Envelope": {
+                        "type": "object",
+                        "required": ["data", "errors"],
+                        "properties": {
+                            "data": { "nullable": true },
+                            "errors": {
+                                "type": "array",
+                                "items": {
+                                    "type": "object",
+                                    "required": ["message"],
+                                    "properties": {
+                                        "message": { "type": "string" },
+                                        "field": { "type": "string" },
+                                        "kind": { "type": "string" },
+                                    },
+                                },
+                            },
+                        },
+                    },
+                },
+            },
+        }))
+    }
+}
+
+/// The description itself, as is rather than in the envelope, for tools
+/// which expect that.
+#[get("/openapi.json")]
+pub fn spec(spec: &State<Spec>) -> Value {
+    spec.0.clone()
+}
+
+#[get("/docs")]
+pub fn ui() -> RawHtml<String> {
+    RawHtml(format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+  <meta charset="utf-8">
+  <title>Quetzal API</title>
+  <link rel="stylesheet" href="https://unpkg.com/swagger-ui-dist@5/swagger-ui.css">
+</head>
+<body>
+  <div id="swagger-ui"></div>
+  <script src="https://unpkg.com/swagger-ui-dist@5/swagger-ui-bundle.js"></script>
+  <script>SwaggerUIBundle({{ url: "{}/openapi.json", dom_id: "#swagger-ui" }});</script>
+</body>
+</html>"#,
+        BASE
+    ))
+}
@@ -0,0 +1,164 @@
+//! Per-project palette of special characters and attribute codes.
+
+use std::collections::{BTreeMap, HashSet};
+
+use db::audit;
+use db::palette::{self, Entry};
+use db::Conn;
+use rocket::http::Status;
+use rocket::serde::json::Value;
+use serde::{Deserialize, Serialize};
+
+use super::api::{self, ApiResult};
+use super::body::JsonBody;
+use super::conn::DbConn;
+use super::tenancy::{Allowed, ConfigEdit, Viewer};
+
+/// Characters with a special meaning in transcripts, which therefore can't
+/// be part of palette entries.
+pub const RESERVED: &[char] = &['(', ')', '[', ']', '<', '>'];
+
+/// Audit log action, per project.
+const CHANGED: &str = "project.palette_changed";
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct PaletteEntry {
+    value: String,
+    #[serde(default)]
+    description: String,
+    shortcut: Option<String>,
+    /// Only for attribute codes.
+    #[serde(default)]
+    deprecated: bool,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Palette {
+    #[serde(default)]
+    chars: Vec<PaletteEntry>,
+    #[serde(default)]
+    attrs: Vec<PaletteEntry>,
+}
+
+fn check(palette: &Palette) -> Result<(), String> {
+    let mut shortcuts = HashSet::new();
+    for (kind, entries) in &[
+        (palette::CHAR, &palette.chars),
+        (palette::ATTR, &palette.attrs),
+    ] {
+        let mut values = HashSet::new();
+        for entry in entries.iter() {
+            let value = &entry.value;
+            if value.is_empty()
+                || value.contains(char::is_whitespace)
+                || value.contains(RESERVED)
+                || (*kind == palette::ATTR && value.contains('_'))
+            {
+                return Err(format!("invalid {} {:?}", kind, value));
+            }
+            if *kind == palette::CHAR && entry.deprecated {
+                return Err(format!("only attrs can be deprecated, not {:?}", value));
+            }
+            if !values.insert(value) {
+                return Err(format!("duplicate {} {:?}", kind, value));
+            }
+            if let Some(shortcut) = &entry.shortcut {
+                if !shortcuts.insert(shortcut) {
+                    return Err(format!("duplicate shortcut {:?}", shortcut));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn to_json(entries: &[Entry], kind: &str) -> Vec<Value> {
+    entries
+        .iter()
+        .filter(|e| e.kind == kind)
+        .map(|e| {
+            json!({
+                "value": e.value,
+                "description": e.description,
+                "shortcut": e.shortcut,
+                "deprecated": e.deprecated,
+            })
+        })
+        .collect()
+}
+
+fn project_palette(conn: &Conn, viewer: &Viewer, project_id: i32) -> ApiResult {
+    viewer.project(project_id)?;
+    let entries = palette::for_project(conn, project_id).map_err(api::internal)?;
+    api::ok(json!({
+        "chars": to_json(&entries, palette::CHAR),
+        "attrs": to_json(&entries, palette::ATTR),
+    }))
+}
+
+#[get("/projects/<project_id>/palette")]
+pub async fn get(conn: DbConn, viewer: Viewer, project_id: i32) -> ApiResult {
+    conn.run(move |conn| project_palette(conn, &viewer, project_id))
+        .await
+}
+
+/// The project's attribute codes keyed by code, for looking up tooltips
+/// explaining e.g. what `SM` means.
+#[get("/projects/<project_id>/attrs")]
+pub async fn attrs(conn: DbConn, viewer: Viewer, project_id: i32) -> ApiResult {
+    conn.run(move |conn| {
+        viewer.project(project_id)?;
+        let attrs: BTreeMap<_, _> = palette::for_project(conn, project_id)
+            .map_err(api::internal)?
+            .into_iter()
+            .filter(|e| e.kind == palette::ATTR)
+            .map(|e| {
+                let info = json!({ "description": e.description, "deprecated": e.deprecated });
+                (e.value, info)
+            })
+            .collect();
+        api::ok(json!(attrs))
+    })
+    .await
+}
+
+/// Replace the project's palette; entries are shown in the order given.
+#[put("/projects/<project_id>/palette", data = "<palette>")]
+pub async fn put(
+    conn: DbConn,
+    viewer: Allowed<ConfigEdit>,
+    project_id: i32,
+    palette: JsonBody<Palette>,
+) -> ApiResult {
+    conn.run(move |conn| {
+        viewer.project(project_id)?;
+        check(&palette).map_err(|e| api::error(Status::UnprocessableEntity, e))?;
+        let details = json!(*palette);
+        let palette = palette.into_inner();
+        let entries: Vec<_> = palette
+            .chars
+            .into_iter()
+            .map(|e| (palette::CHAR, e))
+            .chain(palette.attrs.into_iter().map(|e| (palette::ATTR, e)))
+            .map(|(kind, e)| Entry {
+                kind: kind.to_owned(),
+                value: e.value,
+                description: e.description,
+                shortcut: e.shortcut,
+                deprecated: e.deprecated,
+            })
+            .collect();
+        palette::replace(conn, project_id, &entries).map_err(api::internal)?;
+        audit::record(
+            conn,
+            Some(viewer.user_id),
+            CHANGED,
+            "project",
+            project_id,
+            &details,
+        )
+        .map_err(api::internal)?;
+        project_palette(conn, &viewer, project_id)
+    })
+    .await
+}
@@ -0,0 +1,122 @@
+//! Per-project parser patterns, on top of the palette (see `rules`).
+
+use std::collections::BTreeMap;
+
+use db::audit;
+use db::parser_configs::{self, Patterns};
+use db::Conn;
+use eaf::config::ConfigFile;
+use rocket::http::Status;
+use serde::{Deserialize, Serialize};
+
+use super::api::{self, ApiResult};
+use super::body::JsonBody;
+use super::conn::DbConn;
+use super::rules;
+use super::tenancy::{Allowed, ConfigEdit, Viewer};
+
+/// Audit log action, per project.
+const CHANGED: &str = "project.parser_config_changed";
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct PatternsBody {
+    #[serde(default)]
+    whitelist: Vec<String>,
+    #[serde(default)]
+    blacklist: Vec<String>,
+    #[serde(default)]
+    atoms: Vec<String>,
+    #[serde(default)]
+    after_angle: Vec<String>,
+    /// Codes of spans to anonymize in exports, see `eaf::anonymize`.
+    #[serde(default)]
+    anonymize: Vec<String>,
+    /// Keys of key=value attributes, with patterns of their values.
+    #[serde(default)]
+    attr_values: BTreeMap<String, String>,
+    /// Report irregular whitespace instead of normalizing it.
+    #[serde(default)]
+    report_whitespace: bool,
+}
+
+fn project_patterns(conn: &Conn, viewer: &Viewer, project_id: i32) -> ApiResult {
+    viewer.project(project_id)?;
+    let config = parser_configs::for_project(conn, project_id).map_err(api::internal)?;
+    let version = rules::project_config(conn, project_id)
+        .map_err(api::internal)?
+        .version();
+    let (patterns, updated_by, updated_at) = match config {
+        Some(c) => (c.patterns, c.updated_by, Some(c.updated_at.to_string())),
+        None => (Patterns::default(), None, None),
+    };
+    api::ok(json!({
+        "whitelist": patterns.whitelist,
+        "blacklist": patterns.blacklist,
+        "atoms": patterns.atoms,
+        "after_angle": patterns.after_angle,
+        "anonymize": patterns.anonymize,
+        "attr_values": patterns.attr_values,
+        "report_whitespace": patterns.report_whitespace,
+        "updated_by": updated_by,
+        "updated_at": updated_at,
+        "version": version,
+    }))
+}
+
+/// The project's patterns, empty if it has none, and the version of the
+/// rules they make up together with the palette.
+#[get("/projects/<project_id>/parser-config")]
+pub async fn get(conn: DbConn, viewer: Viewer, project_id: i32) -> ApiResult {
+    conn.run(move |conn| project_patterns(conn, &viewer, project_id))
+        .await
+}
+
+/// Replace the project's patterns. They're regexes, each of which must be
+/// valid on its own.
+#[put("/projects/<project_id>/parser-config", data = "<body>")]
+pub async fn put(
+    conn: DbConn,
+    viewer: Allowed<ConfigEdit>,
+    project_id: i32,
+    body: JsonBody<PatternsBody>,
+) -> ApiResult {
+    conn.run(move |conn| {
+        viewer.project(project_id)?;
+        let details = json!(*body);
+        let body = body.into_inner();
+        let patterns = Patterns {
+            whitelist: body.whitelist,
+            blacklist: body.blacklist,
+            atoms: body.atoms,
+            after_angle: body.after_angle,
+            anonymize: body.anonymize,
+            attr_values: body.attr_values,
+            report_whitespace: body.report_whitespace,
+        };
+        ConfigFile {
+            whitelist: patterns.whitelist.clone(),
+            blacklist: patterns.blacklist.clone(),
+            atoms: patterns.atoms.clone(),
+            after_angle: patterns.after_angle.clone(),
+            anonymize: patterns.anonymize.clone(),
+            attr_values: patterns.attr_values.clone(),
+            report_whitespace: Some(patterns.report_whitespace),
+            ..ConfigFile::default()
+        }
+        .into_config()
+        .map_err(|e| api::error(Status::UnprocessableEntity, e))?;
+        parser_configs::set(conn, project_id, &patterns, viewer.user_id).map_err(api::internal)?;
+        audit::record(
+            conn,
+            Some(viewer.user_id),
+            CHANGED,
+            "project",
+            project_id,
+            &details,
+        )
+        .map_err(api::internal)?;
+        project_patterns(conn, &viewer, project_id)
+    })
+    .await
+}
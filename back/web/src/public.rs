@@ -0,0 +1,291 @@
+//! Read-only public API for released corpora, mounted separately from the
+//! internal API under `/public`. Only accepted documents of released corpora
+//! are exposed, with transcripts anonymized, anonymization spans included
+//! (see `eaf::anonymize`), and metadata as coarse as in bundles (see
+//! `db::bundle`). Speakers are identified by pseudonyms derived from their
+//! IDs, so that they're stable across requests. All endpoints are rate
+//! limited per client.
+
+use std::collections::hash_map::{Entry, HashMap};
+use std::fs;
+
+use db::bundle::{self, BundleDoc};
+use db::corpora::{self, Corpus};
+use db::files;
+use db::Conn;
+use eaf::annotations;
+use eaf::anonymize::Placeholders;
+use eaf::parser::ParserConfig;
+use eaf::query::Query;
+use eaf::tiers::TierMapping;
+use rocket::http::{ContentType, Status};
+use rocket::response::status::Custom;
+use rocket::serde::json::Value;
+use rocket::State;
+
+use super::api::{self, ApiResult};
+use super::bundle::Pseudonyms;
+use super::conn::DbConn;
+use super::exports::{Export, ExportKey, IfNoneMatch};
+use super::ratelimit::RateLimited;
+use super::rules;
+use super::search;
+use super::storage::Storage;
+use super::tiers;
+
+/// Search reads transcripts on the fly, so queries need to be somewhat
+/// selective.
+const MIN_QUERY: usize = 2;
+
+fn speaker_pseudonym(speaker_id: i32) -> String {
+    format!("S{}", speaker_id)
+}
+
+fn doc_json(doc: &BundleDoc, corpora: &[Corpus]) -> Value {
+    let speakers: Vec<_> = doc
+        .speakers
+        .iter()
+        .map(|s| {
+            json!({
+                "id": speaker_pseudonym(s.id),
+                "gender": s.gender,
+                "education": s.education,
+                "region": s.region,
+                "year": s.year,
+            })
+        })
+        .collect();
+    let corpora: Vec<_> = corpora.iter().map(|c| &c.label).collect();
+    json!({
+        "id": doc.id,
+        "corpora": corpora,
+        "region": doc.region,
+        "speakers": speakers,
+    })
+}
+
+/// The released corpora the document belongs to; `NotFound` unless it's
+/// released.
+fn released_doc(conn: &Conn, doc_id: i32) -> Result<Vec<Corpus>, Custom<Value>> {
+    corpora::released_docs(conn, None)
+        .map_err(api::internal)?
+        .into_iter()
+        .find(|(id, _)| *id == doc_id)
+        .map(|(_, corpora)| corpora)
+        .ok_or_else(|| api::error(Status::NotFound, "no such document"))
+}
+
+/// The document's latest transcript, anonymized, if it has one. The tier
+/// mapping and config are those of the document's project.
+fn transcript(
+    conn: &Conn,
+    storage: &Storage,
+    doc: &BundleDoc,
+    mapping: &TierMapping,
+    config: &ParserConfig,
+) -> Result<Option<String>, String> {
+    let file = match files::latest(conn, doc.id, &[files::EAF]).map_err(|e| e.to_string())? {
+        Some(file) => file,
+        None => return Ok(None),
+    };
+    let xml = fs::read_to_string(storage.path(&file.path))
+        .map_err(|e| format!("{}: {}", file.path, e))?;
+    let xml = Placeholders::default()
+        .scrub_eaf(&xml, config)
+        .map_err(|e| format!("document {}: {}", doc.id, e))?;
+    let speakers: HashMap<_, _> = doc
+        .speakers
+        .iter()
+        .map(|s| (s.nickname.as_str(), speaker_pseudonym(s.id)))
+        .collect();
+    // speakers who appear in the transcript but aren't linked to the
+    // document still need distinct names
+    let mut unknown = Pseudonyms::new("X");
+    eaf::anonymize::anonymize(&xml, mapping, |nickname| match speakers.get(nickname) {
+        Some(pseudonym) => pseudonym.clone(),
+        None => unknown.get(nickname.to_owned()),
+    })
+    .map(Some)
+    .map_err(|e| format!("document {}: {}", doc.id, e))
+}
+
+#[get("/corpora")]
+pub async fn corpora(_limit: RateLimited, conn: DbConn) -> ApiResult {
+    conn.run(move |conn| {
+        let docs = corpora::released_docs(conn, None).map_err(api::internal)?;
+        let corpora: Vec<_> = corpora::released(conn)
+            .map_err(api::internal)?
+            .into_iter()
+            .map(|c| {
+                let documents = docs
+                    .iter()
+                    .filter(|(_, corpora)| corpora.iter().any(|d| d.id == c.id))
+                    .count();
+                json!({
+                    "id": c.id,
+                    "label": c.label,
+                    "released_at": c.released_at.map(|r| r.to_string()),
+                    "documents": documents,
+                })
+            })
+            .collect();
+        api::ok(json!(corpora))
+    })
+    .await
+}
+
+#[get("/corpora/<corpus_id>/documents")]
+pub async fn documents(_limit: RateLimited, conn: DbConn, corpus_id: i32) -> ApiResult {
+    conn.run(move |conn| {
+        let released = corpora::released(conn).map_err(api::internal)?;
+        if !released.iter().any(|c| c.id == corpus_id) {
+            return Err(api::error(Status::NotFound, "no such corpus"));
+        }
+        let docs = corpora::released_docs(conn, Some(corpus_id)).map_err(api::internal)?;
+        let doc_ids: Vec<_> = docs.iter().map(|(id, _)| *id).collect();
+        let metadata = bundle::metadata(conn, &doc_ids).map_err(api::internal)?;
+        let docs: Vec<_> = metadata
+            .iter()
+            .zip(&docs)
+            .map(|(doc, (_, corpora))| doc_json(doc, corpora))
+            .collect();
+        api::ok(json!(docs))
+    })
+    .await
+}
+
+#[get("/documents/<doc_id>")]
+pub async fn document(_limit: RateLimited, conn: DbConn, doc_id: i32) -> ApiResult {
+    conn.run(move |conn| {
+        let corpora = released_doc(conn, doc_id)?;
+        let metadata = bundle::metadata(conn, &[doc_id]).map_err(api::internal)?;
+        api::ok(doc_json(&metadata[0], &corpora))
+    })
+    .await
+}
+
+#[get("/documents/<doc_id>/transcript")]
+pub async fn transcript_eaf(
+    _limit: RateLimited,
+    conn: DbConn,
+    storage: &State<Storage>,
+    if_none_match: IfNoneMatch,
+    doc_id: i32,
+) -> Result<Export, Custom<Value>> {
+    let storage = storage.inner().clone();
+    conn.run(move |conn| {
+        released_doc(conn, doc_id)?;
+        let metadata = bundle::metadata(conn, &[doc_id]).map_err(api::internal)?;
+        let doc = &metadata[0];
+        let file = files::latest(conn, doc_id, &[files::EAF])
+            .map_err(api::internal)?
+            .ok_or_else(|| api::error(Status::NotFound, "document has no transcript"))?;
+        let mapping = tiers::tier_mapping(conn, doc.project_id).map_err(api::internal)?;
+        let config = rules::project_config(conn, doc.project_id).map_err(api::internal)?;
+        let speakers: Vec<_> = doc.speakers.iter().map(|s| (s.id, &s.nickname)).collect();
+        let key = ExportKey {
+            doc_id,
+            file_id: file.id,
+            format: "transcript",
+            inputs: format!("{:?}\n{:?}\n{:?}", mapping, speakers, config.anonymized()),
+        };
+        Export::get(
+            &storage,
+            &key,
+            ContentType::XML,
+            &if_none_match,
+            true,
+            || match transcript(conn, &storage, doc, &mapping, &config).map_err(api::internal)? {
+                Some(xml) => Ok(xml.into_bytes()),
+                None => Err(api::error(Status::NotFound, "document has no transcript")),
+            },
+        )
+    })
+    .await
+}
+
+enum Search {
+    Substring(String),
+    Query(Query),
+}
+
+/// Annotations in transcripts of released documents, optionally only those
+/// in the given corpus, either containing `q` (case-insensitively) or
+/// matching `query` in the query language (see `eaf::query`).
+#[get("/search?<q>&<query>&<corpus>&<limit>")]
+pub async fn search(
+    _limit: RateLimited,
+    conn: DbConn,
+    storage: &State<Storage>,
+    q: Option<String>,
+    query: Option<String>,
+    corpus: Option<i32>,
+    limit: Option<usize>,
+) -> ApiResult {
+    let storage = storage.inner().clone();
+    conn.run(move |conn| {
+        let search = match (q, query) {
+            (Some(q), None) => {
+                let q = q.trim().to_lowercase();
+                if q.chars().count() < MIN_QUERY {
+                    return Err(api::error(
+                        Status::UnprocessableEntity,
+                        format!("query must be at least {} characters long", MIN_QUERY),
+                    ));
+                }
+                Search::Substring(q)
+            }
+            (None, Some(query)) => Search::Query(search::parse_query(&query)?),
+            _ => {
+                return Err(api::error(
+                    Status::UnprocessableEntity,
+                    "exactly one of q and query is required",
+                ))
+            }
+        };
+        let limit = api::limit(limit);
+        let docs = corpora::released_docs(conn, corpus).map_err(api::internal)?;
+        let doc_ids: Vec<_> = docs.iter().map(|(id, _)| *id).collect();
+
+        let mut projects = HashMap::new();
+        let mut hits = vec![];
+        for doc in bundle::metadata(conn, &doc_ids).map_err(api::internal)? {
+            if let Entry::Vacant(entry) = projects.entry(doc.project_id) {
+                let config = rules::project_config(conn, doc.project_id).map_err(api::internal)?;
+                let mapping = tiers::tier_mapping(conn, doc.project_id).map_err(api::internal)?;
+                entry.insert((config, mapping));
+            }
+            let (config, mapping) = &projects[&doc.project_id];
+            let xml =
+                match transcript(conn, &storage, &doc, mapping, config).map_err(api::internal)? {
+                    Some(xml) => xml,
+                    None => continue,
+                };
+            match &search {
+                Search::Substring(q) => {
+                    for annotation in annotations::read(&xml).map_err(api::internal)? {
+                        if annotation.value.to_lowercase().contains(q) {
+                            hits.push(json!({
+                                "doc_id": doc.id,
+                                "tier": annotation.tier,
+                                "annotation": annotation.id,
+                                "value": annotation.value,
+                            }));
+                        }
+                    }
+                }
+                Search::Query(query) => {
+                    let found = search::hits(doc.id, &xml, mapping, config, query)
+                        .map_err(api::internal)?;
+                    hits.extend(found);
+                }
+            }
+            if hits.len() >= limit {
+                hits.truncate(limit);
+                break;
+            }
+        }
+        api::ok(json!(hits))
+    })
+    .await
+}
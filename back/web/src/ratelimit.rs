@@ -0,0 +1,76 @@
+//! Per-client rate limiting for endpoints open to the public.
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use rocket::http::Status;
+use rocket::outcome::{try_outcome, Outcome};
+use rocket::request::{self, FromRequest, Request};
+use rocket::response::status::Custom;
+use rocket::serde::json::Value;
+use rocket::State;
+
+use super::api;
+
+const WINDOW: Duration = Duration::from_secs(60);
+/// Forget clients which haven't been seen for a while once there's this
+/// many of them, so that the table doesn't grow without bound.
+const PRUNE_AT: usize = 10_000;
+
+/// Allows each client a number of requests per minute, counted in fixed
+/// windows starting with the client's first request.
+pub struct RateLimiter {
+    per_minute: u32,
+    windows: Mutex<HashMap<IpAddr, (Instant, u32)>>,
+}
+
+impl RateLimiter {
+    pub fn new(per_minute: u32) -> Self {
+        Self {
+            per_minute,
+            windows: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn allow(&self, client: IpAddr) -> bool {
+        let now = Instant::now();
+        let mut windows = self.windows.lock().unwrap_or_else(|e| e.into_inner());
+        if windows.len() >= PRUNE_AT {
+            windows.retain(|_, (start, _)| now.duration_since(*start) < WINDOW);
+        }
+        let (start, count) = windows.entry(client).or_insert((now, 0));
+        if now.duration_since(*start) >= WINDOW {
+            *start = now;
+            *count = 0;
+        }
+        *count += 1;
+        *count <= self.per_minute
+    }
+}
+
+/// Request guard failing with 429 Too Many Requests once the client has
+/// used up its allowance.
+pub struct RateLimited;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for RateLimited {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        let limiter = try_outcome!(request.guard::<&State<RateLimiter>>().await);
+        match request.client_ip() {
+            Some(ip) if !limiter.allow(ip) => Outcome::Error((Status::TooManyRequests, ())),
+            _ => Outcome::Success(RateLimited),
+        }
+    }
+}
+
+#[catch(429)]
+pub fn too_many_requests() -> Custom<Value> {
+    api::error(
+        Status::TooManyRequests,
+        "too many requests, please slow down",
+    )
+}
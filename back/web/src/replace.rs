@@ -0,0 +1,237 @@
+//! Regex search-and-replace across the transcripts of selected documents,
+//! for migrating between conventions, e.g. when renaming an attribute code.
+//!
+//! Replacing is a two-step process: a preview lists every hit with the
+//! annotation before and after, plus a confirmation token, which the
+//! replacement proper requires. The token covers the request and the
+//! transcripts' versions, so anything changing in between voids the
+//! confirmation. Replacing stores a new version of each changed transcript,
+//! derived from the previous one, and records it in the audit log.
+
+use std::fs;
+
+use chrono::Local;
+use db::Conn;
+use db::{audit, docs, files};
+use diesel::result::Error;
+use eaf::annotations;
+use regex::Regex;
+use rocket::http::Status;
+use rocket::response::status::Custom;
+use rocket::serde::json::Value;
+use rocket::State;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use validator::Validate;
+
+use super::api::{self, ApiResult};
+use super::body::Valid;
+use super::conn::DbConn;
+use super::storage::{FileInfo, Storage};
+use super::tenancy::{Allowed, MaintenanceRun};
+use super::tiers;
+
+/// Audit log action for replacements, per document.
+const REPLACE: &str = "document.replace";
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct ReplaceRequest {
+    /// Regex, replacements can refer to its groups as `$1`, `$name` etc.
+    #[validate(length(min = 1))]
+    pattern: String,
+    replacement: String,
+    #[validate(length(min = 1))]
+    documents: Vec<i32>,
+    /// The token from the preview, only for replacing.
+    confirm: Option<String>,
+    user_id: Option<i32>,
+}
+
+/// A transcript with replacements made.
+struct Changed {
+    doc_id: i32,
+    file: files::File,
+    xml: String,
+    hits: Vec<Value>,
+}
+
+fn compile(request: &ReplaceRequest) -> Result<Regex, Custom<Value>> {
+    Regex::new(&request.pattern).map_err(|e| api::error(Status::UnprocessableEntity, e))
+}
+
+/// Replace in the transcript tiers of the documents' latest transcripts.
+fn replace_all(
+    conn: &Conn,
+    storage: &Storage,
+    re: &Regex,
+    replacement: &str,
+    doc_ids: &[i32],
+) -> Result<Vec<Changed>, Custom<Value>> {
+    let mut changed = vec![];
+    for &doc_id in doc_ids {
+        let project_id = match docs::project_of(conn, doc_id) {
+            Ok(project_id) => project_id,
+            Err(Error::NotFound) => {
+                return Err(api::error(
+                    Status::NotFound,
+                    format!("no such document {}", doc_id),
+                ))
+            }
+            Err(e) => return Err(api::internal(e)),
+        };
+        let file = match files::latest(conn, doc_id, &[files::EAF]).map_err(api::internal)? {
+            Some(file) => file,
+            None => continue,
+        };
+        let mapping = tiers::tier_mapping(conn, project_id).map_err(api::internal)?;
+        let xml = fs::read_to_string(storage.path(&file.path)).map_err(api::internal)?;
+        let mut hits = vec![];
+        let xml = annotations::rewrite(&xml, |annotation| {
+            if !annotation.is_transcript(&mapping) || !re.is_match(&annotation.value) {
+                return None;
+            }
+            let after = re.replace_all(&annotation.value, replacement).into_owned();
+            let matches: Vec<_> = re
+                .find_iter(&annotation.value)
+                .map(|m| json!({ "start": m.start(), "end": m.end() }))
+                .collect();
+            hits.push(json!({
+                "doc_id": doc_id,
+                "tier": annotation.tier,
+                "annotation": annotation.id,
+                "before": annotation.value,
+                "after": after,
+                "matches": matches,
+            }));
+            Some(after)
+        })
+        .map_err(|e| {
+            api::error(
+                Status::UnprocessableEntity,
+                format!("document {}: {}", doc_id, e),
+            )
+        })?;
+        if !hits.is_empty() {
+            changed.push(Changed {
+                doc_id,
+                file,
+                xml,
+                hits,
+            });
+        }
+    }
+    Ok(changed)
+}
+
+fn token(request: &ReplaceRequest, changed: &[Changed]) -> String {
+    let mut hasher = Sha256::new();
+    for part in &[&request.pattern, &request.replacement] {
+        hasher.update(part.as_bytes());
+        hasher.update([0]);
+    }
+    for c in changed {
+        hasher.update(format!("{}:{};", c.doc_id, c.file.id).as_bytes());
+    }
+    format!("{:x}", hasher.finalize())
+}
+
+#[post("/admin/replace/preview", data = "<request>")]
+pub async fn preview(
+    conn: DbConn,
+    _viewer: Allowed<MaintenanceRun>,
+    storage: &State<Storage>,
+    request: Valid<ReplaceRequest>,
+) -> ApiResult {
+    let storage = storage.inner().clone();
+    conn.run(move |conn| {
+        let re = compile(&request)?;
+        let changed = replace_all(
+            conn,
+            &storage,
+            &re,
+            &request.replacement,
+            &request.documents,
+        )?;
+        let hits: Vec<_> = changed.iter().flat_map(|c| c.hits.clone()).collect();
+        api::ok(json!({
+            "token": token(&request, &changed),
+            "documents": changed.len(),
+            "hits": hits,
+        }))
+    })
+    .await
+}
+
+#[post("/admin/replace", data = "<request>")]
+pub async fn replace(
+    conn: DbConn,
+    _viewer: Allowed<MaintenanceRun>,
+    storage: &State<Storage>,
+    request: Valid<ReplaceRequest>,
+) -> ApiResult {
+    let storage = storage.inner().clone();
+    conn.run(move |conn| {
+        let re = compile(&request)?;
+        let changed = replace_all(
+            conn,
+            &storage,
+            &re,
+            &request.replacement,
+            &request.documents,
+        )?;
+        match &request.confirm {
+            Some(confirm) if *confirm == token(&request, &changed) => {}
+            Some(_) => {
+                return Err(api::error(
+                    Status::Conflict,
+                    "transcripts changed since the preview, review it again",
+                ))
+            }
+            None => {
+                return Err(api::error(
+                    Status::UnprocessableEntity,
+                    "confirm the token from the preview",
+                ))
+            }
+        }
+
+        let stamp = Local::now().format("%Y%m%d-%H%M%S");
+        let mut replaced = vec![];
+        for c in changed {
+            let file_id = storage
+                .store(
+                    conn,
+                    c.doc_id,
+                    &format!("replace-{}-{}.eaf", stamp, c.file.id),
+                    c.xml.as_bytes(),
+                    c.xml.len() as u64,
+                    FileInfo {
+                        role: files::EAF,
+                        mime: "application/xml",
+                        created_by: request.user_id,
+                        source_id: Some(c.file.id),
+                    },
+                )
+                .map_err(api::internal)?;
+            let details = json!({
+                "pattern": request.pattern,
+                "replacement": request.replacement,
+                "file_id": file_id,
+                "source_id": c.file.id,
+                "hits": c.hits.len(),
+            });
+            audit::record(
+                conn,
+                request.user_id,
+                REPLACE,
+                "document",
+                c.doc_id,
+                &details,
+            )
+            .map_err(api::internal)?;
+            replaced.push(json!({ "doc_id": c.doc_id, "file_id": file_id, "hits": c.hits.len() }));
+        }
+        api::ok(json!(replaced))
+    })
+    .await
+}
@@ -0,0 +1,163 @@
+//! A printable validation report of a document's latest transcript, with
+//! segments rendered the same way as in the frontend (see `eaf::html`).
+//! Acknowledged mistakes are listed too, but counted separately. Reports
+//! are cached until the transcript, rules, tier mapping or acknowledgments
+//! change (see `exports`).
+
+use std::fs;
+
+use db::acknowledgments::{self, Acknowledgment};
+use db::files;
+use db::validation::NewMistake;
+use eaf::annotations;
+use eaf::html;
+use eaf::parser::{Parser, ParserConfig};
+use eaf::tiers::TierMapping;
+use rocket::http::{ContentType, Status};
+use rocket::response::status::Custom;
+use rocket::serde::json::Value;
+use rocket::State;
+
+use super::api;
+use super::conn::DbConn;
+use super::exports::{Export, ExportKey, IfNoneMatch};
+use super::rules;
+use super::storage::Storage;
+use super::tenancy::Viewer;
+use super::tiers;
+
+const STYLE: &str = "
+body { font-family: sans-serif; }
+td { padding: 0.2em 0.5em; vertical-align: top; }
+.span-round { color: #555; }
+.span-square { background: #eef; }
+.span-angle { background: #efe; }
+.attrs { font-variant: small-caps; }
+.mistake { text-decoration: underline wavy red; }
+.mistake.warning { text-decoration-color: orange; }
+.mistake:empty::after { content: '\u{2038}'; color: red; }
+";
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn render(
+    doc_id: i32,
+    path: &str,
+    xml: &str,
+    config: &ParserConfig,
+    mapping: &TierMapping,
+    acknowledgments: &[Acknowledgment],
+) -> Result<String, Custom<Value>> {
+    let annotations =
+        annotations::read(xml).map_err(|e| api::error(Status::UnprocessableEntity, e))?;
+
+    let mut rows = String::new();
+    let mut total = 0;
+    let mut total_acknowledged = 0;
+    for annotation in annotations {
+        if !annotation.is_transcript(mapping) {
+            continue;
+        }
+        let parsed = Parser::parse(config, config.tokenize(&annotation.value));
+        let acknowledged = parsed
+            .mistakes
+            .iter()
+            .filter(|mistake| {
+                let (_, start, end) = parsed.span(mistake);
+                let mistake = NewMistake {
+                    tier: annotation.tier.clone(),
+                    annotation: annotation.id.clone(),
+                    kind: mistake.kind().to_owned(),
+                    segment: parsed.source.clone(),
+                    start: Some(start as i32),
+                    end: Some(end as i32),
+                };
+                acknowledgments.iter().any(|a| a.covers(&mistake))
+            })
+            .count();
+        total += parsed.mistakes.len();
+        total_acknowledged += acknowledged;
+        // acknowledged mistakes are still listed, just not held against
+        // the transcript
+        let count = if acknowledged > 0 {
+            format!("{} ({} acknowledged)", parsed.mistakes.len(), acknowledged)
+        } else {
+            parsed.mistakes.len().to_string()
+        };
+        rows.push_str(&format!(
+            "<tr><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+            escape(
+                annotation
+                    .participant
+                    .as_deref()
+                    .unwrap_or(&annotation.tier)
+            ),
+            html::render(&parsed),
+            count,
+        ));
+    }
+    Ok(format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+         <title>{title}</title>\n<style>{style}</style>\n</head>\n<body>\n\
+         <h1>{title}</h1>\n<p>Rules version {version}, {total} mistake(s), {acknowledged} acknowledged.</p>\n\
+         <table>\n{rows}</table>\n</body>\n</html>\n",
+        title = escape(&format!("Document {}: {}", doc_id, path)),
+        style = STYLE,
+        version = escape(&config.version()),
+        total = total,
+        acknowledged = total_acknowledged,
+        rows = rows,
+    ))
+}
+
+#[get("/documents/<doc_id>/report")]
+pub async fn report(
+    conn: DbConn,
+    viewer: Viewer,
+    storage: &State<Storage>,
+    if_none_match: IfNoneMatch,
+    doc_id: i32,
+) -> Result<Export, Custom<Value>> {
+    let storage = storage.inner().clone();
+    conn.run(move |conn| {
+        let project_id = viewer.doc(conn, doc_id)?;
+        let file = files::latest(conn, doc_id, &[files::EAF])
+            .map_err(api::internal)?
+            .ok_or_else(|| api::error(Status::NotFound, "document has no transcript"))?;
+        let config = rules::project_config(conn, project_id).map_err(api::internal)?;
+        let mapping = tiers::tier_mapping(conn, project_id).map_err(api::internal)?;
+        let acknowledgments = acknowledgments::for_doc(conn, doc_id).map_err(api::internal)?;
+        let acknowledged: Vec<_> = acknowledgments.iter().map(|a| a.id).collect();
+        let key = ExportKey {
+            doc_id,
+            file_id: file.id,
+            format: "report",
+            inputs: format!("{}\n{:?}\n{:?}", config.version(), mapping, acknowledged),
+        };
+        Export::get(
+            &storage,
+            &key,
+            ContentType::HTML,
+            &if_none_match,
+            false,
+            || {
+                let xml = fs::read_to_string(storage.path(&file.path)).map_err(api::internal)?;
+                render(
+                    doc_id,
+                    &file.path,
+                    &xml,
+                    &config,
+                    &mapping,
+                    &acknowledgments,
+                )
+                .map(String::into_bytes)
+            },
+        )
+    })
+    .await
+}
@@ -0,0 +1,252 @@
+//! Keeping persisted validation results current: stored transcripts are
+//! revalidated in the background when their project's rules change or the
+//! transcripts themselves are modified. Once a document has a recording,
+//! its transcript is also checked against the recording's duration.
+
+use std::collections::hash_map::{Entry, HashMap};
+use std::fs;
+
+use chrono::Local;
+use db::files::{self, File};
+use db::validation::{self, NewMistake, NewRun, Transcript};
+use db::Conn;
+use db::{docs, jobs, tasks};
+use eaf::media::{self, BeyondMedia};
+use eaf::parser::Parser;
+use eaf::policies::{PatternMismatch, Policy};
+use eaf::{annotations, intervals, timeslots, vocabularies};
+use sha2::{Digest, Sha256};
+
+use super::fulltext;
+use super::rules;
+use super::storage::Storage;
+use super::tier_policies;
+use super::tiers;
+use super::webhooks;
+
+/// Local time after which the nightly revalidation is due.
+const NIGHTLY_HOUR: u32 = 2;
+
+fn checksum(contents: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(contents))
+}
+
+/// Whether the transcript's latest validation doesn't reflect the given
+/// (current) rules version or the transcript's current contents.
+fn is_stale(storage: &Storage, transcript: &Transcript, version: &str) -> Result<bool, String> {
+    let run = match &transcript.run {
+        Some(run) => run,
+        None => return Ok(true),
+    };
+    if run.rules_version.as_deref() != Some(version) || run.file_id != Some(transcript.file.id) {
+        return Ok(true);
+    }
+    let path = storage.path(&transcript.file.path);
+    let contents = fs::read(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    Ok(run.checksum.as_deref() != Some(checksum(&contents).as_str()))
+}
+
+/// Queue revalidation of stored transcripts whose validation is stale,
+/// optionally only those in the given project. Returns how many were
+/// queued.
+pub fn enqueue_stale(
+    conn: &Conn,
+    storage: &Storage,
+    project_id: Option<i32>,
+) -> Result<usize, String> {
+    let mut versions = HashMap::new();
+    let mut queued = 0;
+    for transcript in validation::transcripts(conn, project_id).map_err(|e| e.to_string())? {
+        if let Entry::Vacant(entry) = versions.entry(transcript.project_id) {
+            let config =
+                rules::project_config(conn, transcript.project_id).map_err(|e| e.to_string())?;
+            entry.insert(config.version());
+        }
+        let version = &versions[&transcript.project_id];
+        // a missing file shouldn't hold up the rest, the job will fail
+        // with a proper error
+        if !is_stale(storage, &transcript, version).unwrap_or(true) {
+            continue;
+        }
+        let file_id = transcript.file.id;
+        if !jobs::is_queued(conn, jobs::REVALIDATE, file_id).map_err(|e| e.to_string())? {
+            jobs::enqueue(conn, jobs::REVALIDATE, file_id).map_err(|e| e.to_string())?;
+            queued += 1;
+        }
+    }
+    Ok(queued)
+}
+
+/// Queue revalidation of all stale transcripts, if it hasn't been done yet
+/// tonight.
+pub fn run_due(database_url: &str, storage: &Storage) -> Result<(), String> {
+    let conn = db::connect(database_url).map_err(|e| e.to_string())?;
+    let now = Local::now().naive_local();
+    let last_run =
+        tasks::last_run(&conn, tasks::NIGHTLY_REVALIDATION).map_err(|e| e.to_string())?;
+    if !tasks::is_due(last_run, now, NIGHTLY_HOUR) {
+        return Ok(());
+    }
+    enqueue_stale(&conn, storage, None)?;
+    tasks::mark_run(&conn, tasks::NIGHTLY_REVALIDATION, now).map_err(|e| e.to_string())
+}
+
+/// Validate a stored transcript against its project's current rules and
+/// record the result.
+pub fn revalidate(conn: &Conn, storage: &Storage, file_id: i32) -> Result<(), String> {
+    let file = files::get(conn, file_id).map_err(|e| e.to_string())?;
+    validate(conn, storage, &file, None).map(drop)
+}
+
+/// Like `revalidate`, for validations requested by a user. Returns the
+/// mistakes found.
+pub fn validate(
+    conn: &Conn,
+    storage: &Storage,
+    file: &File,
+    user_id: Option<i32>,
+) -> Result<Vec<NewMistake>, String> {
+    let project_id = docs::project_of(conn, file.doc_id).map_err(|e| e.to_string())?;
+    let path = storage.path(&file.path);
+    let contents = fs::read(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let xml = std::str::from_utf8(&contents).map_err(|e| e.to_string())?;
+
+    let config = rules::project_config(conn, project_id).map_err(|e| e.to_string())?;
+    let mapping = tiers::tier_mapping(conn, project_id)?;
+    let policies = tier_policies::tier_policies(conn, project_id)?;
+    // document-level mistakes first, with the offending time slot, the
+    // annotation overlapped, the value or vocabulary in place of the segment
+    let mut found: Vec<_> = timeslots::check(xml)
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .map(|mistake| {
+            let (tier, annotation) = mistake.annotation().unwrap_or_default();
+            NewMistake {
+                tier: tier.to_owned(),
+                annotation: annotation.to_owned(),
+                kind: mistake.kind().to_owned(),
+                segment: mistake.slot().to_owned(),
+                start: None,
+                end: None,
+            }
+        })
+        .collect();
+    for mistake in intervals::check(xml).map_err(|e| e.to_string())? {
+        let (tier, annotation) = mistake.annotation();
+        found.push(NewMistake {
+            tier: tier.to_owned(),
+            annotation: annotation.to_owned(),
+            kind: mistake.kind().to_owned(),
+            segment: mistake.related().to_owned(),
+            start: None,
+            end: None,
+        });
+    }
+    if let Some(audio) =
+        files::latest(conn, file.doc_id, &[files::AUDIO]).map_err(|e| e.to_string())?
+    {
+        // a broken recording shouldn't hold up validating the transcript
+        match media::probe(&storage.path(&audio.path)) {
+            Ok(duration) => {
+                for mistake in media::check(xml, duration).map_err(|e| e.to_string())? {
+                    found.push(NewMistake {
+                        tier: mistake.tier,
+                        annotation: mistake.annotation,
+                        kind: BeyondMedia::KIND.to_owned(),
+                        segment: mistake.slot,
+                        start: None,
+                        end: None,
+                    });
+                }
+            }
+            Err(e) => eprintln!("skipping media check of document {}: {}", file.doc_id, e),
+        }
+    }
+    for mistake in vocabularies::check(xml, &policies).map_err(|e| e.to_string())? {
+        let (_, annotation) = mistake.annotation().unwrap_or_default();
+        found.push(NewMistake {
+            tier: mistake.tier().to_owned(),
+            annotation: annotation.to_owned(),
+            kind: mistake.kind().to_owned(),
+            segment: mistake
+                .value()
+                .or_else(|| mistake.vocabulary())
+                .unwrap_or_default()
+                .to_owned(),
+            start: None,
+            end: None,
+        });
+    }
+    for annotation in annotations::read(xml).map_err(|e| e.to_string())? {
+        // tiers without a policy are validated if they're transcript,
+        // controlled vocabularies are checked above
+        match policies.policy(&annotation.tier, &annotation.linguistic_type) {
+            Some(Policy::Freeform) => {}
+            None if annotation.is_transcript(&mapping) => {}
+            Some(policy) => {
+                let mismatch = PatternMismatch::check(
+                    policy,
+                    &annotation.tier,
+                    &annotation.id,
+                    &annotation.value,
+                );
+                if let Some(mismatch) = mismatch {
+                    found.push(NewMistake {
+                        tier: mismatch.tier,
+                        annotation: mismatch.annotation,
+                        kind: PatternMismatch::KIND.to_owned(),
+                        segment: mismatch.value,
+                        start: None,
+                        end: None,
+                    });
+                }
+                continue;
+            }
+            None => continue,
+        }
+        let parsed = Parser::parse(&config, config.tokenize(&annotation.value));
+        for mistake in &parsed.mistakes {
+            let (_, start, end) = parsed.span(mistake);
+            found.push(NewMistake {
+                tier: annotation.tier.clone(),
+                annotation: annotation.id.clone(),
+                kind: mistake.kind().to_owned(),
+                segment: parsed.source.clone(),
+                start: Some(start as i32),
+                end: Some(end as i32),
+            });
+        }
+    }
+
+    let checksum = checksum(&contents);
+    let version = config.version();
+    let run_id = validation::record_run(
+        conn,
+        &NewRun {
+            doc_id: file.doc_id,
+            user_id,
+            rules_version: &version,
+            file_id: Some(file.id),
+            checksum: Some(&checksum),
+        },
+        &found,
+    )
+    .map_err(|e| e.to_string())?;
+    fulltext::enqueue(conn, file.id).map_err(|e| e.to_string())?;
+    let (mistakes, acknowledged, regression) = validation::latest_run(conn, file.doc_id)
+        .map_err(|e| e.to_string())?
+        .map_or((found.len() as i32, 0, false), |r| {
+            (r.mistakes, r.acknowledged, r.regression)
+        });
+    let data = json!({
+        "doc_id": file.doc_id,
+        "run_id": run_id,
+        "file_id": file.id,
+        "mistakes": mistakes,
+        "acknowledged": acknowledged,
+        "regression": regression,
+        "rules_version": version,
+    });
+    webhooks::fire(conn, project_id, db::webhooks::VALIDATION_COMPLETED, data);
+    Ok(found)
+}
@@ -0,0 +1,135 @@
+//! Supervisor reviews of submitted documents.
+
+use db::docs::{self, DocState};
+use db::reviews::{self, ReviewError, Verdict};
+use rocket::http::Status;
+use rocket::State;
+use serde::Deserialize;
+
+use super::api::{self, ApiResult};
+use super::body::JsonBody;
+use super::conn::DbConn;
+use super::storage::Storage;
+use super::tenancy::{Allowed, DocReview, Viewer};
+use super::vc::{self, Repos};
+use super::webhooks;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VerdictKind {
+    Accepted,
+    Returned,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReviewRequest {
+    reviewer_id: i32,
+    verdict: VerdictKind,
+    reason: Option<String>,
+    #[serde(default)]
+    notes: String,
+}
+
+#[post("/documents/<doc_id>/reviews", data = "<review>")]
+pub async fn create(
+    conn: DbConn,
+    viewer: Allowed<DocReview>,
+    storage: &State<Storage>,
+    repos: &State<Repos>,
+    doc_id: i32,
+    review: JsonBody<ReviewRequest>,
+) -> ApiResult {
+    let storage = storage.inner().clone();
+    let repos = repos.inner().clone();
+    conn.run(move |conn| {
+        viewer.doc(conn, doc_id)?;
+        let review = review.into_inner();
+        let verdict = Verdict {
+            reviewer_id: review.reviewer_id,
+            accepted: match review.verdict {
+                VerdictKind::Accepted => true,
+                VerdictKind::Returned => false,
+            },
+            reason: review.reason,
+            notes: review.notes,
+        };
+        let reviewer_id = verdict.reviewer_id;
+        let state = if verdict.accepted {
+            DocState::Accepted
+        } else {
+            DocState::Returned
+        };
+        match reviews::record(conn, doc_id, verdict) {
+            Ok(id) => {
+                let project_id = docs::project_of(conn, doc_id).map_err(api::internal)?;
+                let data = json!({
+                    "doc_id": doc_id,
+                    "from": DocState::Submitted.label(),
+                    "to": state.label(),
+                    "review_id": id,
+                });
+                webhooks::fire(conn, project_id, db::webhooks::STATE_CHANGED, data);
+                if state == DocState::Accepted {
+                    // the review stands even if archiving it fails
+                    let message = format!("Accept document {} (review {})", doc_id, id);
+                    if let Err(e) = vc::commit_latest(
+                        conn,
+                        &storage,
+                        &repos,
+                        doc_id,
+                        Some(reviewer_id),
+                        &message,
+                    ) {
+                        eprintln!("can't commit document {}: {}", doc_id, e);
+                    }
+                }
+                api::ok(json!({ "id": id }))
+            }
+            Err(ReviewError::Db(diesel::result::Error::NotFound)) => {
+                Err(api::error(Status::NotFound, "no such document"))
+            }
+            Err(ReviewError::Db(e)) => Err(api::internal(e)),
+            Err(e) => Err(api::error(Status::UnprocessableEntity, e)),
+        }
+    })
+    .await
+}
+
+#[get("/documents/<doc_id>/reviews")]
+pub async fn list(conn: DbConn, viewer: Viewer, doc_id: i32) -> ApiResult {
+    conn.run(move |conn| {
+        viewer.doc(conn, doc_id)?;
+        let reviews: Vec<_> = reviews::for_doc(conn, doc_id)
+            .map_err(api::internal)?
+            .into_iter()
+            .map(|r| {
+                json!({
+                    "id": r.id,
+                    "reviewer": r.reviewer,
+                    "validation_run_id": r.validation_run_id,
+                    "verdict": if r.accepted { "accepted" } else { "returned" },
+                    "reason": r.reason,
+                    "notes": r.notes,
+                    "created_at": r.created_at.to_string(),
+                })
+            })
+            .collect();
+        api::ok(json!(reviews))
+    })
+    .await
+}
+
+/// How often documents in the project bounce, and why.
+#[get("/projects/<project_id>/return-reasons")]
+pub async fn return_reasons(conn: DbConn, viewer: Viewer, project_id: i32) -> ApiResult {
+    conn.run(move |conn| {
+        viewer.project(project_id)?;
+        let reasons: Vec<_> = reviews::return_reasons(conn, project_id)
+            .map_err(api::internal)?
+            .into_iter()
+            .map(|(reason, count)| json!({ "reason": reason, "count": count }))
+            .collect();
+        api::ok(json!(reasons))
+    })
+    .await
+}
@@ -0,0 +1,183 @@
+//! The history of a document's transcript, revision by revision (see
+//! `db::revisions`): who stored which version when, what changed between
+//! two of them annotation by annotation, and restoring an old one, which
+//! stores it as a new revision.
+
+use std::fs;
+
+use db::revisions::{self, Revision};
+use db::Conn;
+use db::{audit, files, jobs};
+use diesel::result::Error;
+use eaf::annotations::Annotation;
+use eaf::diff::{self, Change};
+use rocket::http::{ContentType, Status};
+use rocket::response::status::Custom;
+use rocket::serde::json::Value;
+use rocket::State;
+
+use super::api::{self, ApiResult};
+use super::conn::{transaction, DbConn};
+use super::storage::{FileInfo, Storage};
+use super::tenancy::{Allowed, DocEdit, Viewer};
+
+/// Audit log action for restoring old revisions, per document.
+const RESTORED: &str = "document.revision_restored";
+
+fn revision(conn: &Conn, doc_id: i32, number: i32) -> Result<Revision, Custom<Value>> {
+    revisions::get(conn, doc_id, number).map_err(|e| match e {
+        Error::NotFound => api::error(Status::NotFound, format!("no such revision {}", number)),
+        e => api::internal(e),
+    })
+}
+
+fn read(storage: &Storage, revision: &Revision) -> Result<String, Custom<Value>> {
+    fs::read_to_string(storage.path(&revision.path)).map_err(api::internal)
+}
+
+fn annotation_json(a: &Annotation) -> Value {
+    json!({ "value": a.value, "start": a.start, "end": a.end })
+}
+
+fn change_json(change: &Change) -> Value {
+    let (tier, annotation) = change.annotation();
+    let (kind, before, after) = match change {
+        Change::Added(a) => ("added", None, Some(annotation_json(a))),
+        Change::Removed(a) => ("removed", Some(annotation_json(a)), None),
+        Change::Changed { before, after } => (
+            "changed",
+            Some(annotation_json(before)),
+            Some(annotation_json(after)),
+        ),
+    };
+    json!({
+        "tier": tier,
+        "annotation": annotation,
+        "kind": kind,
+        "before": before,
+        "after": after,
+    })
+}
+
+/// The document's revisions, newest first.
+#[get("/documents/<doc_id>/revisions")]
+pub async fn list(conn: DbConn, viewer: Viewer, doc_id: i32) -> ApiResult {
+    conn.run(move |conn| {
+        viewer.doc(conn, doc_id)?;
+        let revisions: Vec<_> = revisions::for_doc(conn, doc_id)
+            .map_err(api::internal)?
+            .into_iter()
+            .map(|r| {
+                json!({
+                    "number": r.number,
+                    "file_id": r.file_id,
+                    "author_id": r.author_id,
+                    "author": r.author,
+                    "created_at": r.created_at.to_string(),
+                })
+            })
+            .collect();
+        api::ok(json!(revisions))
+    })
+    .await
+}
+
+#[get("/documents/<doc_id>/revisions/<number>")]
+pub async fn get(
+    conn: DbConn,
+    viewer: Viewer,
+    storage: &State<Storage>,
+    doc_id: i32,
+    number: i32,
+) -> Result<(ContentType, String), Custom<Value>> {
+    let storage = storage.inner().clone();
+    conn.run(move |conn| {
+        viewer.doc(conn, doc_id)?;
+        let revision = revision(conn, doc_id, number)?;
+        Ok((ContentType::XML, read(&storage, &revision)?))
+    })
+    .await
+}
+
+/// Annotations added, removed and changed from revision `from` to `to`.
+#[get("/documents/<doc_id>/revisions/<from>/diff/<to>")]
+pub async fn changes(
+    conn: DbConn,
+    viewer: Viewer,
+    storage: &State<Storage>,
+    doc_id: i32,
+    from: i32,
+    to: i32,
+) -> ApiResult {
+    let storage = storage.inner().clone();
+    conn.run(move |conn| {
+        viewer.doc(conn, doc_id)?;
+        let before = read(&storage, &revision(conn, doc_id, from)?)?;
+        let after = read(&storage, &revision(conn, doc_id, to)?)?;
+        let changes: Vec<_> = diff::diff(&before, &after)
+            .map_err(|e| api::error(Status::UnprocessableEntity, e))?
+            .iter()
+            .map(change_json)
+            .collect();
+        api::ok(json!({ "from": from, "to": to, "changes": changes }))
+    })
+    .await
+}
+
+/// Store the revision as the document's latest transcript, i.e. its newest
+/// revision, which is revalidated in the background.
+#[post("/documents/<doc_id>/revisions/<number>/restore")]
+pub async fn restore(
+    conn: DbConn,
+    viewer: Allowed<DocEdit>,
+    storage: &State<Storage>,
+    doc_id: i32,
+    number: i32,
+) -> ApiResult {
+    let storage = storage.inner().clone();
+    conn.run(move |conn| {
+        viewer.doc(conn, doc_id)?;
+        let revision = revision(conn, doc_id, number)?;
+        let contents = read(&storage, &revision)?;
+        // the file, its job and audit entry go together
+        transaction(conn, || {
+            let file_id = storage
+                .store(
+                    conn,
+                    doc_id,
+                    &format!("revision-{}-{}.eaf", number, revision.file_id),
+                    contents.as_bytes(),
+                    contents.len() as u64,
+                    FileInfo {
+                        role: files::EAF,
+                        mime: "application/xml",
+                        created_by: Some(viewer.user_id),
+                        source_id: Some(revision.file_id),
+                    },
+                )
+                .map_err(api::internal)?;
+            jobs::enqueue(conn, jobs::REVALIDATE, file_id).map_err(api::internal)?;
+            let details = json!({
+                "number": number,
+                "file_id": file_id,
+                "source_id": revision.file_id,
+            });
+            audit::record(
+                conn,
+                Some(viewer.user_id),
+                RESTORED,
+                "document",
+                doc_id,
+                &details,
+            )
+            .map_err(api::internal)?;
+            let latest = revisions::for_doc(conn, doc_id)
+                .map_err(api::internal)?
+                .into_iter()
+                .find(|r| r.file_id == file_id)
+                .map(|r| r.number);
+            api::ok(json!({ "file_id": file_id, "number": latest }))
+        })
+    })
+    .await
+}
@@ -0,0 +1,111 @@
+//! Roles and the permissions granted to them (see `db::permissions`), so
+//! that new roles can be set up without changes to the code.
+
+use db::audit;
+use db::permissions::{self, RoleError};
+use diesel::result::Error;
+use rocket::http::Status;
+use rocket::response::status::Custom;
+use rocket::serde::json::Value;
+use serde::Deserialize;
+use validator::Validate;
+
+use super::api::{self, ApiResult};
+use super::body::{JsonBody, Valid};
+use super::conn::DbConn;
+use super::tenancy::{Allowed, RoleEdit, Viewer};
+
+/// Audit log actions for changes in roles.
+const CREATED: &str = "role.created";
+const PERMISSIONS_CHANGED: &str = "role.permissions_changed";
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct RoleRequest {
+    #[validate(length(min = 1))]
+    label: String,
+    permissions: Vec<String>,
+}
+
+fn role_error(e: RoleError) -> Custom<Value> {
+    match e {
+        RoleError::UnknownPermission(_) => api::error(Status::UnprocessableEntity, e),
+        RoleError::DuplicateRole(_) => api::error(Status::Conflict, e),
+        RoleError::Db(Error::NotFound) => api::error(Status::NotFound, "no such role"),
+        RoleError::Db(e) => api::internal(e),
+    }
+}
+
+#[get("/permissions")]
+pub async fn permissions(conn: DbConn, _viewer: Viewer) -> ApiResult {
+    conn.run(move |conn| {
+        let all: Vec<_> = permissions::all(conn)
+            .map_err(api::internal)?
+            .into_iter()
+            .map(|p| json!({ "id": p.id, "label": p.label, "description": p.description }))
+            .collect();
+        api::ok(json!(all))
+    })
+    .await
+}
+
+/// The viewer's own permissions, e.g. for the frontend to hide what they
+/// can't do.
+#[get("/permissions/mine")]
+pub fn mine(viewer: Viewer) -> ApiResult {
+    api::ok(json!(viewer.permissions))
+}
+
+#[get("/roles")]
+pub async fn list(conn: DbConn, _viewer: Viewer) -> ApiResult {
+    conn.run(move |conn| {
+        let roles: Vec<_> = permissions::roles(conn)
+            .map_err(api::internal)?
+            .into_iter()
+            .map(|r| json!({ "id": r.id, "label": r.label, "permissions": r.permissions }))
+            .collect();
+        api::ok(json!(roles))
+    })
+    .await
+}
+
+#[post("/admin/roles", data = "<request>")]
+pub async fn create(
+    conn: DbConn,
+    viewer: Allowed<RoleEdit>,
+    request: Valid<RoleRequest>,
+) -> ApiResult {
+    conn.run(move |conn| {
+        let id = permissions::add_role(conn, &request.label, &request.permissions)
+            .map_err(role_error)?;
+        let details = json!({ "label": request.label, "permissions": request.permissions });
+        audit::record(conn, Some(viewer.user_id), CREATED, "role", id, &details)
+            .map_err(api::internal)?;
+        api::ok(json!({ "id": id }))
+    })
+    .await
+}
+
+/// Replace the role's permissions.
+#[put("/admin/roles/<role_id>/permissions", data = "<labels>")]
+pub async fn set_permissions(
+    conn: DbConn,
+    viewer: Allowed<RoleEdit>,
+    role_id: i32,
+    labels: JsonBody<Vec<String>>,
+) -> ApiResult {
+    conn.run(move |conn| {
+        permissions::set_permissions(conn, role_id, &labels).map_err(role_error)?;
+        let details = json!({ "permissions": labels.into_inner() });
+        audit::record(
+            conn,
+            Some(viewer.user_id),
+            PERMISSIONS_CHANGED,
+            "role",
+            role_id,
+            &details,
+        )
+        .map_err(api::internal)?;
+        api::ok(json!(null))
+    })
+    .await
+}
@@ -0,0 +1,112 @@
+//! Which rules transcripts of a project are validated against, and whether
+//! their latest validation still reflects them.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+
+use db::{parser_configs, validation, Conn};
+use diesel::result::Error;
+use diesel::QueryResult;
+use eaf::config::ConfigFile;
+use eaf::parser::ParserConfig;
+
+use super::api::{self, ApiResult};
+use super::conn::DbConn;
+use super::tenancy::Viewer;
+
+/// Compiled configs per project, along with what they were compiled from.
+/// Compiling the regexes is what's expensive, so the inputs are still
+/// loaded every time, but the config is only rebuilt when they change,
+/// whoever changed them.
+static CONFIGS: OnceLock<Mutex<ConfigCache>> = OnceLock::new();
+
+type ConfigCache = HashMap<i32, (ConfigFile, Arc<ParserConfig>)>;
+
+/// The project's parser config, see `parser_configs::config_file`.
+pub fn project_config(conn: &Conn, project_id: i32) -> QueryResult<Arc<ParserConfig>> {
+    let inputs = parser_configs::config_file(conn, project_id)?;
+    let mut configs = CONFIGS
+        .get_or_init(Default::default)
+        .lock()
+        .expect("config cache poisoned");
+    if let Some((cached, config)) = configs.get(&project_id) {
+        if *cached == inputs {
+            return Ok(config.clone());
+        }
+    }
+    // patterns are checked before they're stored, so this is only about
+    // ones put into the DB by other means
+    let config = Arc::new(
+        inputs
+            .clone()
+            .into_config()
+            .map_err(|e| Error::DeserializationError(Box::new(e)))?,
+    );
+    configs.insert(project_id, (inputs, config.clone()));
+    Ok(config)
+}
+
+#[get("/projects/<project_id>/rules-version")]
+pub async fn version(conn: DbConn, viewer: Viewer, project_id: i32) -> ApiResult {
+    conn.run(move |conn| {
+        viewer.project(project_id)?;
+        let config = project_config(conn, project_id).map_err(api::internal)?;
+        api::ok(json!({ "version": config.version() }))
+    })
+    .await
+}
+
+/// Documents whose latest validation used rules other than the project's
+/// current ones, so that its result may no longer hold.
+#[get("/projects/<project_id>/stale-validations")]
+pub async fn stale(conn: DbConn, viewer: Viewer, project_id: i32) -> ApiResult {
+    conn.run(move |conn| {
+        viewer.project(project_id)?;
+        let version = project_config(conn, project_id)
+            .map_err(api::internal)?
+            .version();
+        let runs: Vec<_> = validation::stale_runs(conn, project_id, &version)
+            .map_err(api::internal)?
+            .into_iter()
+            .map(|r| {
+                json!({
+                    "doc_id": r.doc_id,
+                    "run_id": r.id,
+                    "created_at": r.created_at.to_string(),
+                    "mistakes": r.mistakes,
+                    "rules_version": r.rules_version,
+                })
+            })
+            .collect();
+        api::ok(json!({ "version": version, "documents": runs }))
+    })
+    .await
+}
+
+/// The latest validation of the document, if any, and whether it used the
+/// current rules.
+#[get("/documents/<doc_id>/validation")]
+pub async fn latest(conn: DbConn, viewer: Viewer, doc_id: i32) -> ApiResult {
+    conn.run(move |conn| {
+        let project_id = viewer.doc(conn, doc_id)?;
+        let version = project_config(conn, project_id)
+            .map_err(api::internal)?
+            .version();
+        let run = validation::latest_run(conn, doc_id)
+            .map_err(api::internal)?
+            .map(|r| {
+                json!({
+                    "id": r.id,
+                    "created_at": r.created_at.to_string(),
+                    "mistakes": r.mistakes,
+                    "acknowledged": r.acknowledged,
+                    "user_id": r.user_id,
+                    "rules_version": r.rules_version,
+                    "stale": r.rules_version.as_deref() != Some(version.as_str()),
+                    "regression": r.regression,
+                })
+            });
+        api::ok(json!({ "version": version, "run": run }))
+    })
+    .await
+}
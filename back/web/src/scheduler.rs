@@ -0,0 +1,33 @@
+//! Periodic background tasks running alongside the web server.
+
+use std::{thread, time::Duration};
+
+use super::digest::{self, MailConfig};
+use super::revalidation;
+use super::storage::Storage;
+
+/// How often the scheduler wakes up to check whether there's anything to do.
+/// Tasks are responsible for figuring out whether they're due themselves.
+const TICK: Duration = Duration::from_secs(15 * 60);
+
+#[derive(Debug, Clone)]
+pub struct SchedulerConfig {
+    pub database_url: String,
+    /// If missing, digests are not sent.
+    pub mail: Option<MailConfig>,
+    pub storage: Storage,
+}
+
+pub fn spawn(config: SchedulerConfig) -> thread::JoinHandle<()> {
+    thread::spawn(move || loop {
+        if let Some(mail) = &config.mail {
+            if let Err(e) = digest::send_due(&config.database_url, mail) {
+                eprintln!("sending digests failed: {}", e);
+            }
+        }
+        if let Err(e) = revalidation::run_due(&config.database_url, &config.storage) {
+            eprintln!("nightly revalidation failed: {}", e);
+        }
+        thread::sleep(TICK);
+    })
+}
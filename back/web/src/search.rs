@@ -0,0 +1,276 @@
+//! Searching transcripts, either for words in the full-text index (see
+//! `fulltext`) or with the query language (see `eaf::query`). For the
+//! latter, transcripts are read and parsed on the fly, so keep the set of
+//! documents searched reasonably small using the filters.
+
+use std::collections::hash_map::{Entry, HashMap};
+use std::fs;
+
+use db::docs::{self, DocFilter};
+use db::Conn;
+use db::{files, validation};
+use eaf::annotations;
+use eaf::parser::{Parser, ParserConfig};
+use eaf::query::{Query, QuerySpec};
+use eaf::tiers::TierMapping;
+use rocket::http::Status;
+use rocket::response::status::Custom;
+use rocket::serde::json::Value;
+use rocket::State;
+use serde::Deserialize;
+
+use super::api::{self, ApiResult};
+use super::body::JsonBody;
+use super::conn::DbConn;
+use super::fulltext::{self, Index, SearchError};
+use super::rules;
+use super::storage::Storage;
+use super::tenancy::{Allowed, ConfigEdit, Viewer};
+use super::tiers;
+
+/// How many tokens concordance lines show on either side of the match.
+const CONTEXT: usize = 5;
+
+#[derive(Deserialize)]
+pub struct ConcordanceRequest {
+    query: QuerySpec,
+    project: Option<i32>,
+    corpus: Option<i32>,
+    limit: Option<usize>,
+}
+
+pub fn parse_query(query: &str) -> Result<Query, Custom<Value>> {
+    Query::parse(query).map_err(|e| api::error(Status::UnprocessableEntity, e))
+}
+
+/// Annotations on transcript tiers matching the query, with the spans of
+/// the matches in the (normalized) segment.
+pub fn hits(
+    doc_id: i32,
+    xml: &str,
+    mapping: &TierMapping,
+    config: &ParserConfig,
+    query: &Query,
+) -> Result<Vec<Value>, annotations::ReadError> {
+    let mut hits = vec![];
+    for annotation in annotations::read(xml)? {
+        if !annotation.is_transcript(mapping) {
+            continue;
+        }
+        let parsed = Parser::parse(config, config.tokenize(&annotation.value));
+        let matches: Vec<_> = query
+            .find(&parsed)
+            .into_iter()
+            .map(|m| json!({ "start": m.start, "end": m.end }))
+            .collect();
+        if !matches.is_empty() {
+            hits.push(json!({
+                "doc_id": doc_id,
+                "tier": annotation.tier,
+                "annotation": annotation.id,
+                "value": parsed.source,
+                "matches": matches,
+            }));
+        }
+    }
+    Ok(hits)
+}
+
+/// Matches of the query in annotations on transcript tiers, with the
+/// tokens around them and the annotation's time offsets in milliseconds.
+pub fn concordance_lines(
+    doc_id: i32,
+    xml: &str,
+    mapping: &TierMapping,
+    config: &ParserConfig,
+    query: &Query,
+) -> Result<Vec<Value>, annotations::ReadError> {
+    let mut lines = vec![];
+    for annotation in annotations::read(xml)? {
+        if !annotation.is_transcript(mapping) {
+            continue;
+        }
+        let parsed = Parser::parse(config, config.tokenize(&annotation.value));
+        let tokens = parsed.flagged_tokens();
+        let words = |from: usize, to: usize| {
+            tokens[from..to]
+                .iter()
+                .map(|(token, _)| &parsed.source[token.start..token.end])
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        for m in query.find(&parsed) {
+            let right = (m.last + 1 + CONTEXT).min(tokens.len());
+            lines.push(json!({
+                "doc_id": doc_id,
+                "tier": annotation.tier,
+                "annotation": annotation.id,
+                "start": annotation.start,
+                "end": annotation.end,
+                "left": words(m.first.saturating_sub(CONTEXT), m.first),
+                "match": &parsed.source[m.start..m.end],
+                "right": words(m.last + 1, right),
+            }));
+        }
+    }
+    Ok(lines)
+}
+
+/// Results of `find` on the latest transcripts of the documents matching
+/// the filter, up to `limit` of them.
+fn scan<F>(
+    conn: &Conn,
+    storage: &Storage,
+    filter: &DocFilter,
+    limit: usize,
+    mut find: F,
+) -> Result<Vec<Value>, Custom<Value>>
+where
+    F: FnMut(i32, &str, &TierMapping, &ParserConfig) -> Result<Vec<Value>, annotations::ReadError>,
+{
+    let mut projects = HashMap::new();
+    let mut results = vec![];
+    for doc in docs::list(conn, filter).map_err(api::internal)? {
+        let file = match files::latest(conn, doc.id, &[files::EAF]).map_err(api::internal)? {
+            Some(file) => file,
+            None => continue,
+        };
+        if let Entry::Vacant(entry) = projects.entry(doc.project_id) {
+            let config = rules::project_config(conn, doc.project_id).map_err(api::internal)?;
+            let mapping = tiers::tier_mapping(conn, doc.project_id).map_err(api::internal)?;
+            entry.insert((config, mapping));
+        }
+        let (config, mapping) = &projects[&doc.project_id];
+        // one broken transcript shouldn't make the whole corpus unsearchable
+        let found = fs::read_to_string(storage.path(&file.path))
+            .map_err(|e| e.to_string())
+            .and_then(|xml| find(doc.id, &xml, mapping, config).map_err(|e| e.to_string()));
+        match found {
+            Ok(found) => results.extend(found),
+            Err(e) => eprintln!("skipping document {} in search: {}", doc.id, e),
+        }
+        if results.len() >= limit {
+            results.truncate(limit);
+            break;
+        }
+    }
+    Ok(results)
+}
+
+/// Annotations in transcripts the user can access, optionally only those in
+/// the given project or corpus, either containing the words in `q` (as
+/// keywords in context, see `fulltext::Index::search`) or matching `query`
+/// in the query language.
+#[get("/search?<q>&<query>&<project>&<corpus>&<limit>")]
+#[allow(clippy::too_many_arguments)]
+pub async fn search(
+    conn: DbConn,
+    viewer: Viewer,
+    storage: &State<Storage>,
+    index: &State<Index>,
+    q: Option<String>,
+    query: Option<String>,
+    project: Option<i32>,
+    corpus: Option<i32>,
+    limit: Option<usize>,
+) -> ApiResult {
+    let storage = storage.inner().clone();
+    let index = index.inner().clone();
+    conn.run(move |conn| {
+        let project = viewer.scope(project)?;
+        let limit = api::limit(limit);
+        let filter = DocFilter {
+            project_id: project,
+            corpus_id: corpus,
+            assigned_to_id: viewer.assignee(),
+            states: vec![],
+        };
+        let query = match (q, query) {
+            (Some(q), None) => {
+                let docs = docs::list(conn, &filter).map_err(api::internal)?;
+                let doc_ids: Vec<_> = docs.iter().map(|doc| doc.id).collect();
+                return match index.search(&q, &doc_ids, limit) {
+                    Ok(hits) => api::ok(json!(hits)),
+                    Err(e @ SearchError::Query(_)) => {
+                        Err(api::error(Status::UnprocessableEntity, e))
+                    }
+                    Err(e) => Err(api::internal(e)),
+                };
+            }
+            (None, Some(query)) => parse_query(&query)?,
+            _ => {
+                return Err(api::error(
+                    Status::UnprocessableEntity,
+                    "exactly one of q and query is required",
+                ))
+            }
+        };
+        let found = scan(
+            conn,
+            &storage,
+            &filter,
+            limit,
+            |doc_id, xml, mapping, config| hits(doc_id, xml, mapping, config, &query),
+        )?;
+        api::ok(json!(found))
+    })
+    .await
+}
+
+/// Concordance lines of matches of the query in its structured form (see
+/// `eaf::query::QuerySpec`) in transcripts the user can access, e.g. of
+/// numbers in uncertain passages:
+///
+/// ```json
+/// {"query": {
+///   "tokens": [[{"op": "matches", "value": "[0-9]+"}]],
+///   "constraints": [{"span": "round"}]
+/// }}
+/// ```
+#[post("/concordance", data = "<request>")]
+pub async fn concordance(
+    conn: DbConn,
+    viewer: Viewer,
+    storage: &State<Storage>,
+    request: JsonBody<ConcordanceRequest>,
+) -> ApiResult {
+    let storage = storage.inner().clone();
+    conn.run(move |conn| {
+        let project = viewer.scope(request.project)?;
+        let query = Query::from_spec(&request.query)
+            .map_err(|e| api::error(Status::UnprocessableEntity, e))?;
+        let filter = DocFilter {
+            project_id: project,
+            corpus_id: request.corpus,
+            assigned_to_id: viewer.assignee(),
+            states: vec![],
+        };
+        let limit = api::limit(request.limit);
+        let lines = scan(
+            conn,
+            &storage,
+            &filter,
+            limit,
+            |doc_id, xml, mapping, config| concordance_lines(doc_id, xml, mapping, config, &query),
+        )?;
+        api::ok(json!(lines))
+    })
+    .await
+}
+
+/// Queue (re)indexing of the project's transcripts for full-text search,
+/// e.g. of those last validated before the index existed.
+#[post("/projects/<project_id>/reindex")]
+pub async fn reindex(conn: DbConn, viewer: Allowed<ConfigEdit>, project_id: i32) -> ApiResult {
+    conn.run(move |conn| {
+        viewer.project(project_id)?;
+        let mut queued = 0;
+        for transcript in validation::transcripts(conn, Some(project_id)).map_err(api::internal)? {
+            if fulltext::enqueue(conn, transcript.file.id).map_err(api::internal)? {
+                queued += 1;
+            }
+        }
+        api::ok(json!({ "queued": queued }))
+    })
+    .await
+}
@@ -0,0 +1,228 @@
+//! Logging in and out, and sessions, so that users can see where they're
+//! logged in and log out devices they no longer use. The session token is
+//! kept in a cookie.
+
+use db::Conn;
+use db::{audit, sessions, two_factor, users};
+use diesel::result::Error;
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use rocket::http::{Cookie, CookieJar, SameSite, Status};
+use rocket::outcome::Outcome;
+use rocket::request::{self, FromRequest, Request};
+use rocket::response::status::Custom;
+use rocket::serde::json::Value;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use super::api::{self, ApiResult};
+use super::body::JsonBody;
+use super::conn::DbConn;
+use super::ratelimit::RateLimited;
+use super::tenancy::{Allowed, SessionRevoke, Viewer};
+use super::two_factor::{two_factor_error, unix_time};
+
+pub const SESSION_COOKIE: &str = "quetzal_session";
+/// Unlike the session cookie, this one is readable by the frontend, which
+/// has to send its value in the header with requests changing anything.
+pub const CSRF_COOKIE: &str = "quetzal_csrf";
+pub const CSRF_HEADER: &str = "X-CSRF-Token";
+/// Audit log action for logging a user out everywhere.
+const REVOKED: &str = "user.sessions_revoked";
+/// Characters in a session token, i.e. about 256 random bits.
+const TOKEN_LENGTH: usize = 43;
+
+/// The `User-Agent` request header, to tell sessions apart.
+pub struct Device(String);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Device {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        let device = request.headers().get_one("User-Agent").unwrap_or("unknown");
+        Outcome::Success(Device(device.to_owned()))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SessionRequest {
+    /// Required if the user has two-factor authentication enabled.
+    code: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LoginRequest {
+    username: String,
+    password: String,
+    /// Required if the user has two-factor authentication enabled.
+    code: Option<String>,
+}
+
+fn token() -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(TOKEN_LENGTH)
+        .map(char::from)
+        .collect()
+}
+
+/// The CSRF token of the session, derived from its token so that it
+/// needn't be stored and can't be forged without knowing the session's.
+pub fn csrf_token(session_token: &str) -> String {
+    format!(
+        "{:x}",
+        Sha256::digest(format!("csrf:{}", session_token).as_bytes())
+    )
+}
+
+/// Start a session for the user, checking the two-factor code if they've
+/// enabled it. Returns the session's ID and token.
+fn start(
+    conn: &Conn,
+    user_id: i32,
+    code: Option<String>,
+    device: &Device,
+) -> Result<(i32, String), Custom<Value>> {
+    if two_factor::is_enabled(conn, user_id).map_err(api::internal)? {
+        let code =
+            code.ok_or_else(|| api::error(Status::Unauthorized, "two-factor code required"))?;
+        two_factor::verify(conn, user_id, &code, unix_time()).map_err(two_factor_error)?;
+    }
+    let token = token();
+    let id = sessions::create(conn, user_id, &token, &device.0).map_err(api::internal)?;
+    Ok((id, token))
+}
+
+/// Set the cookies of the session `start`ed.
+fn started(cookies: &CookieJar<'_>, (id, token): (i32, String)) -> ApiResult {
+    let csrf = csrf_token(&token);
+    cookies.add(
+        Cookie::build((SESSION_COOKIE, token))
+            .path("/")
+            .http_only(true)
+            .same_site(SameSite::Lax),
+    );
+    cookies.add(
+        Cookie::build((CSRF_COOKIE, csrf.clone()))
+            .path("/")
+            .same_site(SameSite::Lax),
+    );
+    api::ok(json!({ "id": id, "csrf_token": csrf }))
+}
+
+/// Start a session for the user identified otherwise, i.e. by the header
+/// in development builds (see `tenancy`).
+#[post("/sessions", data = "<request>")]
+pub async fn create(
+    conn: DbConn,
+    viewer: Viewer,
+    device: Device,
+    cookies: &CookieJar<'_>,
+    request: Option<JsonBody<SessionRequest>>,
+) -> ApiResult {
+    let code = request.and_then(|r| r.into_inner().code);
+    let session = conn
+        .run(move |conn| start(conn, viewer.user_id, code, &device))
+        .await?;
+    started(cookies, session)
+}
+
+/// Log in with username and password. Rate limited per client, like the
+/// public API, to slow down guessing.
+#[post("/login", data = "<request>")]
+pub async fn login(
+    _limit: RateLimited,
+    conn: DbConn,
+    device: Device,
+    cookies: &CookieJar<'_>,
+    request: JsonBody<LoginRequest>,
+) -> ApiResult {
+    let request = request.into_inner();
+    let session = conn
+        .run(move |conn| {
+            let user_id = users::authenticate(conn, &request.username, &request.password)
+                .map_err(api::internal)?
+                .ok_or_else(|| api::error(Status::Unauthorized, "invalid username or password"))?;
+            start(conn, user_id, request.code, &device)
+        })
+        .await?;
+    started(cookies, session)
+}
+
+/// End the current session, if any.
+#[post("/logout")]
+pub async fn logout(conn: DbConn, viewer: Viewer, cookies: &CookieJar<'_>) -> ApiResult {
+    if let Some(id) = viewer.session_id {
+        conn.run(move |conn| sessions::revoke(conn, viewer.user_id, id))
+            .await
+            .map_err(api::internal)?;
+    }
+    cookies.remove(SESSION_COOKIE);
+    cookies.remove(CSRF_COOKIE);
+    api::ok(json!(null))
+}
+
+/// The user's sessions, most recently seen first.
+#[get("/sessions")]
+pub async fn list(conn: DbConn, viewer: Viewer) -> ApiResult {
+    conn.run(move |conn| {
+        let sessions: Vec<_> = sessions::for_user(conn, viewer.user_id)
+            .map_err(api::internal)?
+            .into_iter()
+            .map(|s| {
+                json!({
+                    "id": s.id,
+                    "device": s.device,
+                    "created_at": s.created_at.to_string(),
+                    "last_seen_at": s.last_seen_at.to_string(),
+                    "current": Some(s.id) == viewer.session_id,
+                })
+            })
+            .collect();
+        api::ok(json!(sessions))
+    })
+    .await
+}
+
+#[delete("/sessions/<id>")]
+pub async fn revoke(conn: DbConn, viewer: Viewer, id: i32, cookies: &CookieJar<'_>) -> ApiResult {
+    let user_id = viewer.user_id;
+    let revoked = conn
+        .run(move |conn| sessions::revoke(conn, user_id, id))
+        .await
+        .map_err(api::internal)?;
+    if !revoked {
+        return Err(api::error(Status::NotFound, "no such session"));
+    }
+    if viewer.session_id == Some(id) {
+        cookies.remove(SESSION_COOKIE);
+        cookies.remove(CSRF_COOKIE);
+    }
+    api::ok(json!(null))
+}
+
+/// Log the user out of all their sessions.
+#[delete("/admin/users/<user_id>/sessions")]
+pub async fn revoke_all(conn: DbConn, viewer: Allowed<SessionRevoke>, user_id: i32) -> ApiResult {
+    conn.run(move |conn| {
+        match users::role(conn, user_id) {
+            Ok(_) => {}
+            Err(Error::NotFound) => return Err(api::error(Status::NotFound, "no such user")),
+            Err(e) => return Err(api::internal(e)),
+        }
+        let revoked = sessions::revoke_all(conn, user_id).map_err(api::internal)?;
+        let details = json!({ "revoked": revoked });
+        audit::record(
+            conn,
+            Some(viewer.user_id),
+            REVOKED,
+            "user",
+            user_id,
+            &details,
+        )
+        .map_err(api::internal)?;
+        api::ok(json!({ "revoked": revoked }))
+    })
+    .await
+}
@@ -0,0 +1,482 @@
+//! Speaker metadata endpoints: editing speakers one by one or importing
+//! them in bulk, linking them to documents, and cleaning up duplicates.
+
+use db::import::{self, ColumnMapping, NewSpeaker};
+use db::speakers::{self, EditError, MergeError, Speaker};
+use db::Conn;
+use db::{audit, people};
+use diesel::result::Error;
+use rocket::data::{Data, ToByteUnit};
+use rocket::http::Status;
+use rocket::response::status::Custom;
+use rocket::serde::json::Value;
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+use super::api::{self, ApiResult};
+use super::body::{JsonBody, Valid};
+use super::conn::DbConn;
+use super::tenancy::{Allowed, SpeakerEdit, Viewer};
+
+/// Speaker spreadsheets are small, anything bigger than this is a mistake.
+const CSV_LIMIT: u64 = 1024 * 1024;
+
+/// Audit log actions, per speaker.
+const CREATED: &str = "speaker.created";
+const UPDATED: &str = "speaker.updated";
+const DELETED: &str = "speaker.deleted";
+/// Audit log actions, per document.
+const LINKED: &str = "document.speaker_linked";
+const UNLINKED: &str = "document.speaker_unlinked";
+
+#[derive(FromForm)]
+pub struct ImportParams {
+    /// ID of the user who recruited the speakers.
+    user: i32,
+    dry_run: Option<bool>,
+    // column mapping overrides
+    nickname: Option<String>,
+    gender: Option<String>,
+    education: Option<String>,
+    place: Option<String>,
+    year: Option<String>,
+}
+
+impl ImportParams {
+    fn mapping(&self) -> ColumnMapping {
+        let mut mapping = ColumnMapping::default();
+        let overrides = vec![
+            (&mut mapping.nickname, &self.nickname),
+            (&mut mapping.gender, &self.gender),
+            (&mut mapping.education, &self.education),
+            (&mut mapping.place, &self.place),
+            (&mut mapping.year, &self.year),
+        ];
+        for (column, over) in overrides {
+            if let Some(over) = over {
+                *column = over.clone();
+            }
+        }
+        mapping
+    }
+}
+
+/// Import speakers from a CSV request body. All problems are reported in
+/// the `errors` list, and nothing is imported unless there are none.
+#[post("/projects/<project_id>/speakers/import?<params..>", data = "<csv>")]
+pub async fn import(
+    conn: DbConn,
+    viewer: Allowed<SpeakerEdit>,
+    project_id: i32,
+    params: ImportParams,
+    csv: Data<'_>,
+) -> ApiResult {
+    viewer.project(project_id)?;
+    let user = params.user;
+    conn.run(move |conn| viewer.colleague(conn, user)).await?;
+    let body = csv
+        .open(CSV_LIMIT.bytes())
+        .into_bytes()
+        .await
+        .map_err(|e| api::error(Status::BadRequest, e))?;
+    if !body.is_complete() {
+        return Err(api::error(
+            Status::PayloadTooLarge,
+            format!("the CSV is larger than {} bytes", CSV_LIMIT),
+        ));
+    }
+    let body = body.into_inner();
+    let dry_run = params.dry_run.unwrap_or(false);
+    let (report, imported) = conn
+        .run(move |conn| -> Result<_, Custom<Value>> {
+            let report = import::check(conn, project_id, params.user, &params.mapping(), &body[..])
+                .map_err(api::internal)?;
+            let imported = if dry_run {
+                0
+            } else {
+                import::commit(conn, &report).map_err(api::internal)?
+            };
+            Ok((report, imported))
+        })
+        .await?;
+    let errors: Vec<_> = report
+        .problems
+        .iter()
+        .map(|p| json!({ "line": p.line, "column": p.column, "message": p.message }))
+        .collect();
+    let response = json!({
+        "data": { "dry_run": dry_run, "valid": report.speakers.len(), "imported": imported },
+        "errors": errors,
+    });
+    if report.is_ok() {
+        Ok(response)
+    } else {
+        Err(Custom(Status::UnprocessableEntity, response))
+    }
+}
+
+/// Search speakers by (part of) their nickname, tolerating typos.
+#[get("/speakers/search?<q>&<project>&<limit>")]
+pub async fn search(
+    conn: DbConn,
+    viewer: Viewer,
+    q: String,
+    project: Option<i32>,
+    limit: Option<usize>,
+) -> ApiResult {
+    conn.run(move |conn| {
+        let project = viewer.scope(project)?;
+        let speakers: Vec<_> = people::search_speakers(conn, &q, project, api::limit(limit))
+            .map_err(api::internal)?
+            .into_iter()
+            .map(
+                |s| json!({ "id": s.id, "nickname": s.nickname, "project": s.project, "year": s.year }),
+            )
+            .collect();
+        api::ok(json!(speakers))
+    })
+    .await
+}
+
+/// Groups of speakers who are likely the same person, optionally only those
+/// involving a speaker from the given project.
+#[get("/speakers/duplicates?<project>")]
+pub async fn duplicates(conn: DbConn, viewer: Viewer, project: Option<i32>) -> ApiResult {
+    conn.run(move |conn| {
+        let project = viewer.scope(project)?;
+        let groups: Vec<_> = speakers::duplicates(conn, project)
+            .map_err(api::internal)?
+            .into_iter()
+            // only admins get to see speakers from other projects
+            .map(|group| {
+                group
+                    .into_iter()
+                    .filter(|c| viewer.is_admin() || Some(c.project_id) == project)
+                    .collect::<Vec<_>>()
+            })
+            .filter(|group| group.len() > 1)
+            .map(|group| {
+                let group: Vec<_> = group
+                    .into_iter()
+                    .map(|c| {
+                        json!({
+                            "id": c.id,
+                            "nickname": c.nickname,
+                            "project": c.project,
+                            "place": c.place,
+                            "year": c.year,
+                            "docs": c.docs,
+                        })
+                    })
+                    .collect();
+                json!(group)
+            })
+            .collect();
+        api::ok(json!(groups))
+    })
+    .await
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MergeRequest {
+    /// The speaker to merge into this one, which will be deleted.
+    from_id: i32,
+    user_id: Option<i32>,
+}
+
+#[post("/speakers/<into_id>/merge", data = "<request>")]
+pub async fn merge(
+    conn: DbConn,
+    viewer: Allowed<SpeakerEdit>,
+    into_id: i32,
+    request: JsonBody<MergeRequest>,
+) -> ApiResult {
+    conn.run(move |conn| {
+        for &speaker_id in &[into_id, request.from_id] {
+            match speakers::project_of(conn, speaker_id) {
+                Ok(project_id) if viewer.access.allows(project_id) => {}
+                Ok(_) | Err(Error::NotFound) => {
+                    return Err(api::error(
+                        Status::NotFound,
+                        MergeError::NotFound(speaker_id),
+                    ))
+                }
+                Err(e) => return Err(api::internal(e)),
+            }
+        }
+        match speakers::merge(conn, request.from_id, into_id, request.user_id) {
+            Ok(relinked) => api::ok(json!({ "id": into_id, "relinked": relinked })),
+            Err(e @ MergeError::NotFound(_)) => Err(api::error(Status::NotFound, e)),
+            Err(e @ MergeError::SameSpeaker) | Err(e @ MergeError::DifferentProjects) => {
+                Err(api::error(Status::UnprocessableEntity, e))
+            }
+            Err(MergeError::Db(e)) => Err(api::internal(e)),
+        }
+    })
+    .await
+}
+
+fn speaker_json(speaker: &Speaker) -> Value {
+    json!({
+        "id": speaker.id,
+        "user_id": speaker.user_id,
+        "project_id": speaker.project_id,
+        "nickname": speaker.nickname,
+        "gender_id": speaker.gender_id,
+        "education_id": speaker.education_id,
+        "place_id": speaker.place_id,
+        "year": speaker.year,
+    })
+}
+
+fn edit_error(e: EditError) -> Custom<Value> {
+    match e {
+        e @ EditError::NotFound(_) => api::error(Status::NotFound, e),
+        e @ EditError::NicknameTaken(_) | e @ EditError::Linked(_) => {
+            api::error(Status::Conflict, e)
+        }
+        e @ EditError::Invalid(_) => api::error(Status::UnprocessableEntity, e),
+        EditError::Db(e) => api::internal(e),
+    }
+}
+
+/// The speaker's project, if the user can access it.
+fn speaker_project(conn: &Conn, viewer: &Viewer, speaker_id: i32) -> Result<i32, Custom<Value>> {
+    match speakers::project_of(conn, speaker_id) {
+        Ok(project_id) if viewer.access.allows(project_id) => Ok(project_id),
+        Ok(_) | Err(Error::NotFound) => Err(edit_error(EditError::NotFound(speaker_id))),
+        Err(e) => Err(api::internal(e)),
+    }
+}
+
+/// The choices for the speaker fields which aren't free text, for forms.
+/// Places are looked up with `/places/complete`.
+#[get("/speakers/fields")]
+pub async fn fields(conn: DbConn, _viewer: Viewer) -> ApiResult {
+    conn.run(move |conn| {
+        let options = |rows: Vec<(i32, String)>| -> Vec<Value> {
+            rows.into_iter()
+                .map(|(id, label)| json!({ "id": id, "label": label }))
+                .collect()
+        };
+        api::ok(json!({
+            "genders": options(speakers::genders(conn).map_err(api::internal)?),
+            "educations": options(speakers::educations(conn).map_err(api::internal)?),
+        }))
+    })
+    .await
+}
+
+#[get("/projects/<project_id>/speakers")]
+pub async fn list(conn: DbConn, viewer: Viewer, project_id: i32) -> ApiResult {
+    conn.run(move |conn| {
+        viewer.project(project_id)?;
+        let speakers: Vec<_> = speakers::for_project(conn, project_id)
+            .map_err(api::internal)?
+            .iter()
+            .map(speaker_json)
+            .collect();
+        api::ok(json!(speakers))
+    })
+    .await
+}
+
+#[derive(Debug, Deserialize, Serialize, Validate)]
+pub struct SpeakerRequest {
+    /// Who recruited the speaker, the current user by default.
+    user_id: Option<i32>,
+    #[validate(length(min = 1))]
+    nickname: String,
+    gender_id: i32,
+    education_id: i32,
+    place_id: i32,
+    year: i32,
+}
+
+impl SpeakerRequest {
+    fn new_speaker(&self, project_id: i32, user_id: i32) -> NewSpeaker {
+        NewSpeaker {
+            user_id: self.user_id.unwrap_or(user_id),
+            project_id,
+            nickname: self.nickname.trim().to_owned(),
+            gender_id: self.gender_id,
+            education_id: self.education_id,
+            place_id: self.place_id,
+            year: self.year,
+        }
+    }
+}
+
+#[post("/projects/<project_id>/speakers", data = "<request>")]
+pub async fn create(
+    conn: DbConn,
+    viewer: Allowed<SpeakerEdit>,
+    project_id: i32,
+    request: Valid<SpeakerRequest>,
+) -> ApiResult {
+    conn.run(move |conn| {
+        viewer.project(project_id)?;
+        if let Some(user_id) = request.user_id {
+            viewer.colleague(conn, user_id)?;
+        }
+        let speaker = request.new_speaker(project_id, viewer.user_id);
+        let id = speakers::create(conn, &speaker).map_err(edit_error)?;
+        audit::record(
+            conn,
+            Some(viewer.user_id),
+            CREATED,
+            "speaker",
+            id,
+            &json!(*request),
+        )
+        .map_err(api::internal)?;
+        speaker_by_id(conn, &viewer, id)
+    })
+    .await
+}
+
+fn speaker_by_id(conn: &Conn, viewer: &Viewer, id: i32) -> ApiResult {
+    speaker_project(conn, viewer, id)?;
+    let speaker = speakers::find(conn, id).map_err(edit_error)?;
+    api::ok(speaker_json(&speaker))
+}
+
+#[get("/speakers/<id>", rank = 2)]
+pub async fn get(conn: DbConn, viewer: Viewer, id: i32) -> ApiResult {
+    conn.run(move |conn| speaker_by_id(conn, &viewer, id)).await
+}
+
+/// Update everything about the speaker except their project.
+#[put("/speakers/<id>", data = "<request>")]
+pub async fn update(
+    conn: DbConn,
+    viewer: Allowed<SpeakerEdit>,
+    id: i32,
+    request: Valid<SpeakerRequest>,
+) -> ApiResult {
+    conn.run(move |conn| {
+        let project_id = speaker_project(conn, &viewer, id)?;
+        let previous = speakers::find(conn, id).map_err(edit_error)?;
+        // keeping a recruiter who has since left the project is fine
+        if let Some(user_id) = request.user_id.filter(|&id| id != previous.user_id) {
+            viewer.colleague(conn, user_id)?;
+        }
+        let speaker = request.new_speaker(project_id, previous.user_id);
+        speakers::update(conn, id, &speaker).map_err(edit_error)?;
+        let details = json!({ "from": speaker_json(&previous), "to": *request });
+        audit::record(conn, Some(viewer.user_id), UPDATED, "speaker", id, &details)
+            .map_err(api::internal)?;
+        speaker_by_id(conn, &viewer, id)
+    })
+    .await
+}
+
+/// Only speakers who don't appear in any documents can be deleted.
+#[delete("/speakers/<id>")]
+pub async fn delete(conn: DbConn, viewer: Allowed<SpeakerEdit>, id: i32) -> ApiResult {
+    conn.run(move |conn| {
+        speaker_project(conn, &viewer, id)?;
+        let speaker = speakers::find(conn, id).map_err(edit_error)?;
+        speakers::delete(conn, id).map_err(edit_error)?;
+        audit::record(
+            conn,
+            Some(viewer.user_id),
+            DELETED,
+            "speaker",
+            id,
+            &speaker_json(&speaker),
+        )
+        .map_err(api::internal)?;
+        api::ok(json!(null))
+    })
+    .await
+}
+
+fn linked_speakers(conn: &Conn, viewer: &Viewer, doc_id: i32) -> ApiResult {
+    viewer.doc(conn, doc_id)?;
+    let speakers: Vec<_> = speakers::links(conn, doc_id)
+        .map_err(api::internal)?
+        .iter()
+        .map(|(speaker, words)| json!({ "speaker": speaker_json(speaker), "words": words }))
+        .collect();
+    api::ok(json!(speakers))
+}
+
+/// Speakers appearing in the document, with how many words they say in it
+/// if that's known.
+#[get("/documents/<doc_id>/speakers")]
+pub async fn doc_speakers(conn: DbConn, viewer: Viewer, doc_id: i32) -> ApiResult {
+    conn.run(move |conn| linked_speakers(conn, &viewer, doc_id))
+        .await
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct LinkRequest {
+    #[validate(range(min = 0))]
+    words: Option<i32>,
+}
+
+/// Link a speaker of the document's project to it, or update their word
+/// count if they're linked already.
+#[put("/documents/<doc_id>/speakers/<speaker_id>", data = "<request>")]
+pub async fn link(
+    conn: DbConn,
+    viewer: Allowed<SpeakerEdit>,
+    doc_id: i32,
+    speaker_id: i32,
+    request: Valid<LinkRequest>,
+) -> ApiResult {
+    conn.run(move |conn| {
+        let project_id = viewer.doc(conn, doc_id)?;
+        if speaker_project(conn, &viewer, speaker_id)? != project_id {
+            return Err(api::error(
+                Status::UnprocessableEntity,
+                "speaker is from another project",
+            ));
+        }
+        let linked =
+            speakers::link(conn, doc_id, speaker_id, request.words).map_err(api::internal)?;
+        let details = json!({ "speaker_id": speaker_id, "words": request.words, "new": linked });
+        audit::record(
+            conn,
+            Some(viewer.user_id),
+            LINKED,
+            "document",
+            doc_id,
+            &details,
+        )
+        .map_err(api::internal)?;
+        linked_speakers(conn, &viewer, doc_id)
+    })
+    .await
+}
+
+#[delete("/documents/<doc_id>/speakers/<speaker_id>")]
+pub async fn unlink(
+    conn: DbConn,
+    viewer: Allowed<SpeakerEdit>,
+    doc_id: i32,
+    speaker_id: i32,
+) -> ApiResult {
+    conn.run(move |conn| {
+        viewer.doc(conn, doc_id)?;
+        if !speakers::unlink(conn, doc_id, speaker_id).map_err(api::internal)? {
+            return Err(api::error(
+                Status::NotFound,
+                "speaker isn't linked to the document",
+            ));
+        }
+        let details = json!({ "speaker_id": speaker_id });
+        audit::record(
+            conn,
+            Some(viewer.user_id),
+            UNLINKED,
+            "document",
+            doc_id,
+            &details,
+        )
+        .map_err(api::internal)?;
+        linked_speakers(conn, &viewer, doc_id)
+    })
+    .await
+}
@@ -0,0 +1,320 @@
+//! Descriptive statistics of documents' latest transcripts (see
+//! `eaf::stats`), as JSON or CSV for further processing, and turn-taking
+//! between their speakers (see `eaf::turns`). Word counts per speaker are
+//! also stored with the links between documents and speakers, and summed
+//! up in projects' dashboards (see `db::project_stats`).
+
+use std::fs;
+
+use db::docs::{self, DocFilter};
+use db::project_stats::{self, Totals};
+use db::Conn;
+use db::{audit, files, speakers, tier_mappings};
+use eaf::annotations;
+use eaf::stats::{self, Measures, Segment, Stats};
+use eaf::turns;
+use rocket::http::{ContentType, Status};
+use rocket::response::status::Custom;
+use rocket::serde::json::Value;
+use rocket::State;
+
+use super::api::{self, ApiResult};
+use super::conn::DbConn;
+use super::storage::Storage;
+use super::tenancy::{Allowed, DocAssign, SpeakerEdit, Viewer};
+use super::tiers;
+
+/// Audit log action, per document.
+const WORDS_COUNTED: &str = "document.words_counted";
+
+fn measures_json(measures: &Measures) -> Value {
+    json!({
+        "tokens": measures.tokens,
+        "types": measures.types,
+        "type_token_ratio": measures.type_token_ratio(),
+        "turns": measures.turns,
+        "mean_turn_length": measures.mean_turn_length(),
+        "talk_time_ms": measures.talk_time,
+        "pauses": measures.pauses,
+        "pauses_per_minute": measures.pauses_per_minute(),
+        "duration_ms": measures.duration,
+    })
+}
+
+fn stats_json(stats: &Stats) -> Value {
+    let speakers: Vec<_> = stats
+        .speakers
+        .iter()
+        .map(|(speaker, measures)| json!({ "speaker": speaker, "measures": measures_json(measures) }))
+        .collect();
+    json!({ "total": measures_json(&stats.total), "speakers": speakers })
+}
+
+/// Segments of the document's latest transcript, if it has one.
+fn doc_segments(
+    conn: &Conn,
+    storage: &Storage,
+    doc_id: i32,
+    project_id: i32,
+) -> Result<Option<Vec<Segment>>, String> {
+    let file = match files::latest(conn, doc_id, &[files::EAF]).map_err(|e| e.to_string())? {
+        Some(file) => file,
+        None => return Ok(None),
+    };
+    let xml = fs::read_to_string(storage.path(&file.path))
+        .map_err(|e| format!("{}: {}", file.path, e))?;
+    let mapping = tiers::tier_mapping(conn, project_id).map_err(|e| e.to_string())?;
+    let annotations = annotations::read(&xml).map_err(|e| e.to_string())?;
+    Ok(Some(stats::segments(annotations, &mapping)))
+}
+
+/// Segments of the selected documents, skipping those without a readable
+/// transcript.
+fn filtered_segments(
+    conn: &Conn,
+    storage: &Storage,
+    project: Option<i32>,
+    corpus: Option<i32>,
+    assigned_to: Option<i32>,
+) -> Result<Vec<(i32, Vec<Segment>)>, Custom<Value>> {
+    let filter = DocFilter {
+        project_id: project,
+        corpus_id: corpus,
+        assigned_to_id: assigned_to,
+        states: vec![],
+    };
+    let mut all = vec![];
+    for doc in docs::list(conn, &filter).map_err(api::internal)? {
+        match doc_segments(conn, storage, doc.id, doc.project_id) {
+            Ok(Some(segments)) => all.push((doc.id, segments)),
+            Ok(None) => {}
+            Err(e) => eprintln!("skipping document {} in stats: {}", doc.id, e),
+        }
+    }
+    Ok(all)
+}
+
+#[get("/documents/<doc_id>/stats")]
+pub async fn document(
+    conn: DbConn,
+    viewer: Viewer,
+    storage: &State<Storage>,
+    doc_id: i32,
+) -> ApiResult {
+    let storage = storage.inner().clone();
+    conn.run(move |conn| {
+        let project_id = viewer.doc(conn, doc_id)?;
+        match doc_segments(conn, &storage, doc_id, project_id).map_err(api::internal)? {
+            Some(segments) => api::ok(stats_json(&stats::compute(&segments))),
+            None => Err(api::error(Status::NotFound, "document has no transcript")),
+        }
+    })
+    .await
+}
+
+/// Count the words of each speaker in the document's latest transcript and
+/// store them, linking speakers found by nickname to the document. Returns
+/// `None` if there's no transcript, else the counts stored and those of
+/// speakers matching no one in the project.
+pub fn update_word_counts(
+    conn: &Conn,
+    storage: &Storage,
+    doc_id: i32,
+    project_id: i32,
+) -> Result<Option<Value>, String> {
+    let segments = match doc_segments(conn, storage, doc_id, project_id)? {
+        Some(segments) => segments,
+        None => return Ok(None),
+    };
+    let mut matched = vec![];
+    let mut stored = vec![];
+    let mut unmatched = vec![];
+    for (nickname, words) in stats::word_counts(&segments) {
+        let words = words as i32;
+        match tier_mappings::speaker_for_nickname(conn, doc_id, nickname)
+            .map_err(|e| e.to_string())?
+        {
+            Some(speaker_id) => {
+                matched.push((speaker_id, words));
+                stored.push(
+                    json!({ "speaker_id": speaker_id, "nickname": nickname, "words": words }),
+                );
+            }
+            None => unmatched.push(json!({ "nickname": nickname, "words": words })),
+        }
+    }
+    speakers::set_words(conn, doc_id, &matched).map_err(|e| e.to_string())?;
+    Ok(Some(json!({ "speakers": stored, "unmatched": unmatched })))
+}
+
+/// Recount the words of the document's speakers, e.g. after the project's
+/// tier mapping changed. Uploads of transcripts do so on their own.
+#[post("/documents/<doc_id>/word-counts")]
+pub async fn word_counts(
+    conn: DbConn,
+    viewer: Allowed<SpeakerEdit>,
+    storage: &State<Storage>,
+    doc_id: i32,
+) -> ApiResult {
+    let storage = storage.inner().clone();
+    conn.run(move |conn| {
+        let project_id = viewer.doc(conn, doc_id)?;
+        let counts =
+            match update_word_counts(conn, &storage, doc_id, project_id).map_err(api::internal)? {
+                Some(counts) => counts,
+                None => return Err(api::error(Status::NotFound, "document has no transcript")),
+            };
+        audit::record(
+            conn,
+            Some(viewer.user_id),
+            WORDS_COUNTED,
+            "document",
+            doc_id,
+            &counts,
+        )
+        .map_err(api::internal)?;
+        api::ok(counts)
+    })
+    .await
+}
+
+fn totals_json(totals: &Totals) -> Value {
+    json!({
+        "docs": totals.docs,
+        "done": totals.done,
+        "assigned": totals.assigned,
+        "words": totals.words,
+        "mistakes": totals.mistakes,
+    })
+}
+
+/// Everything the project's dashboard shows, in one go. Word counts are
+/// those last stored, see `word_counts`.
+#[get("/projects/<project_id>/stats")]
+pub async fn project(conn: DbConn, viewer: Allowed<DocAssign>, project_id: i32) -> ApiResult {
+    conn.run(move |conn| {
+        viewer.project(project_id)?;
+        let stats = project_stats::for_project(conn, project_id).map_err(api::internal)?;
+        let regions: Vec<_> = stats
+            .regions
+            .iter()
+            .map(|r| {
+                json!({
+                    "region_id": r.region_id,
+                    "region": r.region,
+                    "totals": totals_json(&r.totals),
+                })
+            })
+            .collect();
+        let speakers: Vec<_> = stats
+            .speakers
+            .iter()
+            .map(|s| json!({ "speaker_id": s.id, "nickname": s.name, "words": s.words }))
+            .collect();
+        let transcribers: Vec<_> = stats
+            .transcribers
+            .iter()
+            .map(|t| json!({ "user_id": t.id, "username": t.name, "words": t.words }))
+            .collect();
+        api::ok(json!({
+            "totals": totals_json(&stats.totals),
+            "regions": regions,
+            "speakers": speakers,
+            "transcribers": transcribers,
+        }))
+    })
+    .await
+}
+
+#[get("/stats?<project>&<corpus>")]
+pub async fn list(
+    conn: DbConn,
+    viewer: Viewer,
+    storage: &State<Storage>,
+    project: Option<i32>,
+    corpus: Option<i32>,
+) -> ApiResult {
+    let storage = storage.inner().clone();
+    conn.run(move |conn| {
+        let project = viewer.scope(project)?;
+        let all: Vec<_> = filtered_segments(conn, &storage, project, corpus, viewer.assignee())?
+            .iter()
+            .map(|(doc_id, segments)| {
+                json!({ "doc_id": doc_id, "stats": stats_json(&stats::compute(segments)) })
+            })
+            .collect();
+        api::ok(json!(all))
+    })
+    .await
+}
+
+/// CSV with a header and one or more records per document.
+fn csv_response(
+    header: &[&str],
+    records: impl Iterator<Item = Vec<String>>,
+) -> Result<(ContentType, Vec<u8>), Custom<Value>> {
+    let mut writer = csv::Writer::from_writer(vec![]);
+    let mut write = || -> Result<(), csv::Error> {
+        writer.write_record(std::iter::once("doc_id").chain(header.iter().copied()))?;
+        for record in records {
+            writer.write_record(record)?;
+        }
+        Ok(())
+    };
+    write().map_err(api::internal)?;
+    let body = writer
+        .into_inner()
+        .map_err(|e| api::internal(e.into_error()))?;
+    Ok((ContentType::CSV, body))
+}
+
+/// One row per document (with an empty speaker) and per speaker in it.
+#[get("/stats.csv?<project>&<corpus>")]
+pub async fn csv(
+    conn: DbConn,
+    viewer: Viewer,
+    storage: &State<Storage>,
+    project: Option<i32>,
+    corpus: Option<i32>,
+) -> Result<(ContentType, Vec<u8>), Custom<Value>> {
+    let storage = storage.inner().clone();
+    conn.run(move |conn| {
+        let project = viewer.scope(project)?;
+        let all = filtered_segments(conn, &storage, project, corpus, viewer.assignee())?;
+        let records = all.iter().flat_map(|(doc_id, segments)| {
+            stats::compute(segments)
+                .records()
+                .into_iter()
+                .map(move |record| std::iter::once(doc_id.to_string()).chain(record).collect())
+        });
+        csv_response(stats::CSV_HEADER, records)
+    })
+    .await
+}
+
+/// One row per document and ordered pair of speakers in it.
+#[get("/turn-taking.csv?<project>&<corpus>")]
+pub async fn turn_taking_csv(
+    conn: DbConn,
+    viewer: Viewer,
+    storage: &State<Storage>,
+    project: Option<i32>,
+    corpus: Option<i32>,
+) -> Result<(ContentType, Vec<u8>), Custom<Value>> {
+    let storage = storage.inner().clone();
+    conn.run(move |conn| {
+        let project = viewer.scope(project)?;
+        let all = filtered_segments(conn, &storage, project, corpus, viewer.assignee())?;
+        let records = all.iter().flat_map(|(doc_id, segments)| {
+            turns::analyze(segments)
+                .into_iter()
+                .map(move |((a, b), measures)| {
+                    std::iter::once(doc_id.to_string())
+                        .chain(measures.record(&a, &b))
+                        .collect()
+                })
+        });
+        csv_response(turns::CSV_HEADER, records)
+    })
+    .await
+}
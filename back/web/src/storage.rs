@@ -0,0 +1,187 @@
+//! Files stored on disk alongside documents, e.g. drafts and recordings.
+//! Transcripts can additionally be kept in git, see `Backend`.
+
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::PathBuf;
+
+use db::files::{self, NewFile};
+use db::revisions;
+use db::Conn;
+use rocket::data::{Data, ToByteUnit};
+
+use super::vc::{self, Repos};
+
+/// Where saved transcripts go besides the storage directory, from the
+/// `transcript_backend` config key.
+#[derive(Debug, Clone)]
+pub enum Backend {
+    /// Nowhere, their history is that of the stored files (`"files"`, the
+    /// default).
+    Files,
+    /// Each is also committed to its project's repository (`"git"`), see
+    /// `vc::commit_saved`.
+    Git(Repos),
+}
+
+#[derive(Debug, Clone)]
+pub struct Storage {
+    /// Root directory for stored files, from the `storage_dir` config key.
+    pub dir: PathBuf,
+    pub backend: Backend,
+}
+
+#[derive(Debug)]
+pub enum StorageError {
+    /// The file is bigger than the given limit.
+    TooLarge(u64),
+    Io(PathBuf, io::Error),
+    Db(diesel::result::Error),
+}
+
+impl fmt::Display for StorageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StorageError::TooLarge(limit) => write!(f, "file is larger than {} bytes", limit),
+            StorageError::Io(path, e) => write!(f, "{}: {}", path.display(), e),
+            StorageError::Db(e) => e.fmt(f),
+        }
+    }
+}
+
+pub struct FileInfo<'a> {
+    pub role: &'a str,
+    pub mime: &'a str,
+    pub created_by: Option<i32>,
+    pub source_id: Option<i32>,
+}
+
+impl Storage {
+    pub fn path(&self, relative: &str) -> PathBuf {
+        self.dir.join(relative)
+    }
+
+    /// Where a document's file of the given name goes, relative to the
+    /// storage directory.
+    pub fn doc_path(doc_id: i32, name: &str) -> String {
+        format!("docs/{}/{}", doc_id, name)
+    }
+
+    /// Where a document's cached exports go, relative to the storage
+    /// directory (see `exports`).
+    pub fn export_dir(doc_id: i32) -> String {
+        format!("exports/{}", doc_id)
+    }
+
+    /// Write a document's file and record it in the DB. Returns the file's
+    /// ID.
+    pub fn store<R: Read>(
+        &self,
+        conn: &Conn,
+        doc_id: i32,
+        name: &str,
+        contents: R,
+        limit: u64,
+        file: FileInfo,
+    ) -> Result<i32, StorageError> {
+        let relative = Self::doc_path(doc_id, name);
+        let path = self.path(&relative);
+        let io_error = |e| StorageError::Io(path.clone(), e);
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(io_error)?;
+        }
+        let size = io::copy(
+            &mut contents.take(limit.saturating_add(1)),
+            &mut File::create(&path).map_err(io_error)?,
+        )
+        .map_err(io_error)?;
+        if size > limit {
+            // best effort, an orphaned file is no big deal
+            let _ = fs::remove_file(&path);
+            return Err(StorageError::TooLarge(limit));
+        }
+        self.record(conn, doc_id, relative, file)
+    }
+
+    /// Write a document's file straight from the request body, without
+    /// recording it; see `record`. Returns the path relative to the storage
+    /// directory.
+    pub async fn receive(
+        &self,
+        doc_id: i32,
+        name: &str,
+        body: Data<'_>,
+        limit: u64,
+    ) -> Result<String, StorageError> {
+        let relative = Self::doc_path(doc_id, name);
+        let path = self.path(&relative);
+        let io_error = |e| StorageError::Io(path.clone(), e);
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(io_error)?;
+        }
+        let written = body
+            .open(limit.bytes())
+            .into_file(&path)
+            .await
+            .map_err(io_error)?;
+        if !written.is_complete() {
+            // best effort, an orphaned file is no big deal
+            let _ = fs::remove_file(&path);
+            return Err(StorageError::TooLarge(limit));
+        }
+        Ok(relative)
+    }
+
+    /// Record a document's file which was already written to the given path
+    /// (relative to the storage directory), e.g. by an external program.
+    /// Exports of the document cached so far are dropped, as they may be
+    /// based on an older version. Transcripts become the document's newest
+    /// revision, see `revisions`, and are committed with the git backend.
+    pub fn record(
+        &self,
+        conn: &Conn,
+        doc_id: i32,
+        relative: String,
+        file: FileInfo,
+    ) -> Result<i32, StorageError> {
+        let path = self.path(&relative);
+        let size = fs::metadata(&path)
+            .map_err(|e| StorageError::Io(path, e))?
+            .len();
+        let file_id = files::add(
+            conn,
+            &NewFile {
+                doc_id,
+                role: file.role.to_owned(),
+                path: relative,
+                mime: file.mime.to_owned(),
+                size: size as i64,
+                created_by: file.created_by,
+                source_id: file.source_id,
+            },
+        )
+        .map_err(StorageError::Db)?;
+        if file.role == files::EAF {
+            revisions::add(conn, doc_id, file_id, file.created_by).map_err(StorageError::Db)?;
+            if let Backend::Git(repos) = &self.backend {
+                // the stored file is authoritative, a failed commit only
+                // leaves a gap in the archive
+                let commit = files::get(conn, file_id)
+                    .map_err(vc::VcError::from)
+                    .and_then(|saved| vc::commit_saved(conn, self, repos, &saved));
+                if let Err(e) = commit {
+                    eprintln!("can't commit document {}: {}", doc_id, e);
+                }
+            }
+        }
+        let exports = self.path(&Self::export_dir(doc_id));
+        match fs::remove_dir_all(&exports) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                return Err(StorageError::Io(exports, e))
+            }
+            _ => {}
+        }
+        Ok(file_id)
+    }
+}
@@ -0,0 +1,69 @@
+//! Per-project substitutions of commonly mistyped graphemes, offered as
+//! quick fixes for mistakes (see `eaf::fixes`).
+
+use std::collections::BTreeMap;
+
+use db::{audit, substitutions, Conn};
+use rocket::http::Status;
+
+use super::api::{self, ApiResult};
+use super::body::JsonBody;
+use super::conn::DbConn;
+use super::palette::RESERVED;
+use super::tenancy::{Allowed, ConfigEdit, Viewer};
+
+/// Audit log action, per project.
+const CHANGED: &str = "project.substitutions_changed";
+
+fn project_substitutions(conn: &Conn, viewer: &Viewer, project_id: i32) -> ApiResult {
+    viewer.project(project_id)?;
+    let pairs: BTreeMap<_, _> = substitutions::for_project(conn, project_id)
+        .map_err(api::internal)?
+        .into_iter()
+        .collect();
+    api::ok(json!(pairs))
+}
+
+#[get("/projects/<project_id>/substitutions")]
+pub async fn get(conn: DbConn, viewer: Viewer, project_id: i32) -> ApiResult {
+    conn.run(move |conn| project_substitutions(conn, &viewer, project_id))
+        .await
+}
+
+/// Replace the project's substitutions, given as an object mapping what's
+/// mistyped to its replacement.
+#[put("/projects/<project_id>/substitutions", data = "<pairs>")]
+pub async fn put(
+    conn: DbConn,
+    viewer: Allowed<ConfigEdit>,
+    project_id: i32,
+    pairs: JsonBody<BTreeMap<String, String>>,
+) -> ApiResult {
+    conn.run(move |conn| {
+        viewer.project(project_id)?;
+        let invalid = |s: &str| s.contains(char::is_whitespace) || s.contains(RESERVED);
+        if let Some((source, target)) = pairs
+            .iter()
+            .find(|(source, target)| source.is_empty() || invalid(source) || invalid(target))
+        {
+            return Err(api::error(
+                Status::UnprocessableEntity,
+                format!("invalid substitution {:?} -> {:?}", source, target),
+            ));
+        }
+        let details = json!(*pairs);
+        let pairs: Vec<_> = pairs.into_inner().into_iter().collect();
+        substitutions::replace(conn, project_id, &pairs).map_err(api::internal)?;
+        audit::record(
+            conn,
+            Some(viewer.user_id),
+            CHANGED,
+            "project",
+            project_id,
+            &details,
+        )
+        .map_err(api::internal)?;
+        project_substitutions(conn, &viewer, project_id)
+    })
+    .await
+}
@@ -0,0 +1,110 @@
+//! Delta sync for offline-capable clients (see `db::sync`). A client starts
+//! without a cursor, stores the one it gets back and passes it on the next
+//! sync, repeating while `more` is true.
+
+use db::sync;
+use rocket::http::Status;
+
+use super::api::{self, ApiResult};
+use super::comments::comment_json;
+use super::conn::DbConn;
+use super::tenancy::Viewer;
+
+/// Changes returned per sync unless requested otherwise.
+const DEFAULT_LIMIT: i64 = 100;
+/// Keeps the ID lists within what SQLite takes in a single query.
+const MAX_LIMIT: i64 = 500;
+
+#[get("/sync?<cursor>&<limit>")]
+pub async fn sync(
+    conn: DbConn,
+    viewer: Viewer,
+    cursor: Option<i32>,
+    limit: Option<i64>,
+) -> ApiResult {
+    conn.run(move |conn| {
+        let limit = limit.unwrap_or(DEFAULT_LIMIT);
+        if !(1..=MAX_LIMIT).contains(&limit) {
+            return Err(api::error(
+                Status::BadRequest,
+                format!("limit must be between 1 and {}", MAX_LIMIT),
+            ));
+        }
+        let changes =
+            sync::since(conn, &viewer.access, cursor.unwrap_or(0), limit).map_err(api::internal)?;
+        let documents: Vec<_> = changes
+            .documents
+            .iter()
+            .map(|d| {
+                json!({
+                    "id": d.id,
+                    "project_id": d.project_id,
+                    "state": d.state,
+                    "place_id": d.place_id,
+                    "done_at": d.done_at.map(|t| t.to_string()),
+                })
+            })
+            .collect();
+        let assignments: Vec<_> = changes
+            .assignments
+            .iter()
+            .map(|a| {
+                json!({
+                    "doc_id": a.doc_id,
+                    "assigned_to_id": a.assigned_to_id,
+                    "assigned_by_id": a.assigned_by_id,
+                    "assigned_at": a.assigned_at.map(|t| t.to_string()),
+                    "due_at": a.due_at.map(|t| t.to_string()),
+                })
+            })
+            .collect();
+        let reviews: Vec<_> = changes
+            .reviews
+            .iter()
+            .map(|(doc_id, r)| {
+                json!({
+                    "id": r.id,
+                    "doc_id": doc_id,
+                    "reviewer": r.reviewer,
+                    "validation_run_id": r.validation_run_id,
+                    "accepted": r.accepted,
+                    "reason": r.reason,
+                    "notes": r.notes,
+                    "created_at": r.created_at.to_string(),
+                })
+            })
+            .collect();
+        let comments: Vec<_> = changes.comments.iter().map(comment_json).collect();
+        let validation_runs: Vec<_> = changes
+            .validation_runs
+            .iter()
+            .map(|r| {
+                json!({
+                    "id": r.id,
+                    "doc_id": r.doc_id,
+                    "created_at": r.created_at.to_string(),
+                    "mistakes": r.mistakes,
+                    "acknowledged": r.acknowledged,
+                    "rules_version": r.rules_version,
+                    "regression": r.regression,
+                })
+            })
+            .collect();
+        let deleted: Vec<_> = changes
+            .deleted
+            .iter()
+            .map(|(entity, id)| json!({ "entity": entity, "id": id }))
+            .collect();
+        api::ok(json!({
+            "cursor": changes.cursor,
+            "more": changes.more,
+            "documents": documents,
+            "assignments": assignments,
+            "reviews": reviews,
+            "comments": comments,
+            "validation_runs": validation_runs,
+            "deleted": deleted,
+        }))
+    })
+    .await
+}
@@ -0,0 +1,296 @@
+//! Isolation of projects: who's asking, which projects they may access and
+//! what they may do there (see `db::permissions`). Clients identify the
+//! user by the session cookie (see `sessions`), which they get by logging
+//! in. Development builds also trust the `X-User-Id` header, for testing
+//! the API without logging in. Requests authenticated by the cookie which
+//! change anything must also carry the CSRF token.
+//!
+//! Documents, files and webhooks of projects the user can't access are
+//! reported as missing, so as not to leak that they exist. So are documents
+//! not assigned to the user, unless they may access all documents of their
+//! projects (`doc.all`, i.e. supervisors and admins).
+
+use std::marker::PhantomData;
+use std::ops::Deref;
+
+use db::members::{self, Access};
+use db::permissions::{self, DOC_ALL, USER_ACT_FOR};
+use db::Conn;
+use db::{docs, files, sessions};
+use diesel::result::Error;
+use rocket::http::{Method, Status};
+use rocket::outcome::{try_outcome, Outcome};
+use rocket::request::{self, FromRequest, Request};
+use rocket::response::status::Custom;
+use rocket::serde::json::Value;
+
+use super::api;
+use super::conn::DbConn;
+use super::sessions::{csrf_token, CSRF_HEADER, SESSION_COOKIE};
+
+const USER_HEADER: &str = "X-User-Id";
+
+/// Request guard failing with 401 Unauthorized unless the request
+/// identifies an existing user.
+#[derive(Debug)]
+pub struct Viewer {
+    pub user_id: i32,
+    /// Unless identified by the header.
+    pub session_id: Option<i32>,
+    pub access: Access,
+    pub permissions: Vec<String>,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Viewer {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        let conn = try_outcome!(request.guard::<DbConn>().await);
+        let token = request
+            .cookies()
+            .get(SESSION_COOKIE)
+            .map(|c| c.value().to_owned());
+        let resumed = match token.clone() {
+            Some(token) => Some(conn.run(move |conn| sessions::resume(conn, &token)).await),
+            None => None,
+        };
+        let (user_id, session_id) = match resumed {
+            Some(Ok(Some((session_id, user_id)))) => (user_id, Some(session_id)),
+            Some(Ok(None)) => return Outcome::Error((Status::Unauthorized, ())),
+            Some(Err(_)) => return Outcome::Error((Status::ServiceUnavailable, ())),
+            None if cfg!(debug_assertions) => {
+                match request.headers().get_one(USER_HEADER).map(str::parse) {
+                    Some(Ok(user_id)) => (user_id, None),
+                    _ => return Outcome::Error((Status::Unauthorized, ())),
+                }
+            }
+            None => return Outcome::Error((Status::Unauthorized, ())),
+        };
+        // browsers send the cookie along with requests forged by other
+        // sites, but those can't read it to derive the CSRF token
+        if let Some(token) = token {
+            let safe = matches!(
+                request.method(),
+                Method::Get | Method::Head | Method::Options
+            );
+            if !safe && request.headers().get_one(CSRF_HEADER) != Some(&csrf_token(&token)) {
+                request.local_cache(|| {
+                    Refusal(Some(format!("missing or invalid {} header", CSRF_HEADER)))
+                });
+                return Outcome::Error((Status::Forbidden, ()));
+            }
+        }
+        let viewer = conn
+            .run(move |conn| -> Result<_, Error> {
+                let access = members::access(conn, user_id)?;
+                let permissions = permissions::for_user(conn, user_id)?;
+                Ok(Viewer {
+                    user_id,
+                    session_id,
+                    access,
+                    permissions,
+                })
+            })
+            .await;
+        match viewer {
+            Ok(viewer) => Outcome::Success(viewer),
+            Err(Error::NotFound) => Outcome::Error((Status::Unauthorized, ())),
+            Err(_) => Outcome::Error((Status::ServiceUnavailable, ())),
+        }
+    }
+}
+
+fn deny(message: &str) -> Custom<Value> {
+    api::error(Status::Forbidden, message)
+}
+
+impl Viewer {
+    /// Whether the user can access all projects.
+    pub fn is_admin(&self) -> bool {
+        self.access == Access::All
+    }
+
+    pub fn can(&self, permission: &str) -> bool {
+        self.permissions.iter().any(|p| p == permission)
+    }
+
+    /// Users can act on their own behalf, some (i.e. admins) on anyone's.
+    pub fn user(&self, user_id: i32) -> Result<(), Custom<Value>> {
+        if self.can(USER_ACT_FOR) || user_id == self.user_id {
+            Ok(())
+        } else {
+            Err(deny("can't act on behalf of other users"))
+        }
+    }
+
+    /// Users can see others who share a project with them.
+    pub fn colleague(&self, conn: &Conn, user_id: i32) -> Result<(), Custom<Value>> {
+        let shared = match (&self.access, members::access(conn, user_id)) {
+            (Access::All, Ok(_)) => true,
+            (Access::Projects(ids), Ok(Access::Projects(theirs))) => {
+                ids.iter().any(|id| theirs.contains(id))
+            }
+            (Access::Projects(_), Ok(Access::All)) => false,
+            (_, Err(Error::NotFound)) => false,
+            (_, Err(e)) => return Err(api::internal(e)),
+        };
+        if shared || user_id == self.user_id {
+            Ok(())
+        } else {
+            Err(api::error(Status::NotFound, "no such user"))
+        }
+    }
+
+    pub fn project(&self, project_id: i32) -> Result<(), Custom<Value>> {
+        if self.access.allows(project_id) {
+            Ok(())
+        } else {
+            Err(deny(&format!("no access to project {}", project_id)))
+        }
+    }
+
+    /// The document's project, if the user can access it.
+    pub fn doc(&self, conn: &Conn, doc_id: i32) -> Result<i32, Custom<Value>> {
+        match docs::access_of(conn, doc_id) {
+            Ok((project_id, assigned_to_id))
+                if self.access.allows(project_id)
+                    && (self.can(DOC_ALL) || assigned_to_id == Some(self.user_id)) =>
+            {
+                Ok(project_id)
+            }
+            Ok(_) | Err(Error::NotFound) => Err(api::error(Status::NotFound, "no such document")),
+            Err(e) => Err(api::internal(e)),
+        }
+    }
+
+    /// The assignee to restrict document listings to: the user themselves,
+    /// unless they may access all documents.
+    pub fn assignee(&self) -> Option<i32> {
+        if self.can(DOC_ALL) {
+            None
+        } else {
+            Some(self.user_id)
+        }
+    }
+
+    /// The file's document, if the user can access it.
+    pub fn file(&self, conn: &Conn, file_id: i32) -> Result<i32, Custom<Value>> {
+        let doc_id = match files::get(conn, file_id) {
+            Ok(file) => file.doc_id,
+            Err(Error::NotFound) => return Err(api::error(Status::NotFound, "no such file")),
+            Err(e) => return Err(api::internal(e)),
+        };
+        match self.doc(conn, doc_id) {
+            Ok(_) => Ok(doc_id),
+            Err(_) => Err(api::error(Status::NotFound, "no such file")),
+        }
+    }
+
+    /// The project to restrict a listing to: the requested one, if the
+    /// user can access it, or the user's only project. Admins can list
+    /// across projects.
+    pub fn scope(&self, project_id: Option<i32>) -> Result<Option<i32>, Custom<Value>> {
+        match (project_id, &self.access) {
+            (Some(project_id), _) => self.project(project_id).map(|_| Some(project_id)),
+            (None, Access::All) => Ok(None),
+            (None, Access::Projects(ids)) => match ids.as_slice() {
+                [] => Err(deny("not a member of any project")),
+                [project_id] => Ok(Some(*project_id)),
+                _ => Err(api::error(Status::UnprocessableEntity, "select a project")),
+            },
+        }
+    }
+}
+
+/// A permission checked by the `Allowed` request guard.
+pub trait Permission: Send + Sync {
+    const LABEL: &'static str;
+}
+
+macro_rules! permission_guards {
+    ($($name:ident => $label:ident,)*) => {
+        $(
+            #[derive(Debug)]
+            pub enum $name {}
+
+            impl Permission for $name {
+                const LABEL: &'static str = permissions::$label;
+            }
+        )*
+    };
+}
+
+permission_guards! {
+    AuditView => AUDIT_VIEW,
+    BackupManage => BACKUP_MANAGE,
+    ConfigEdit => CONFIG_EDIT,
+    DocAssign => DOC_ASSIGN,
+    DocEdit => DOC_EDIT,
+    DocReview => DOC_REVIEW,
+    ExportBundle => EXPORT_BUNDLE,
+    ExportRelease => EXPORT_RELEASE,
+    GeoEdit => GEO_EDIT,
+    MaintenanceRun => MAINTENANCE_RUN,
+    MemberEdit => MEMBER_EDIT,
+    RoleEdit => ROLE_EDIT,
+    SessionRevoke => SESSION_REVOKE,
+    SpeakerEdit => SPEAKER_EDIT,
+    TwoFactor => ACCOUNT_TWO_FACTOR,
+    WebhookEdit => WEBHOOK_EDIT,
+}
+
+/// Why a request guard refused the request, for the 403 catcher.
+struct Refusal(Option<String>);
+
+/// Request guard failing with 403 Forbidden unless the viewer has the
+/// permission `P`, e.g. `Allowed<DocEdit>`.
+#[derive(Debug)]
+pub struct Allowed<P>(Viewer, PhantomData<P>);
+
+impl<P> Allowed<P> {
+    pub fn into_inner(self) -> Viewer {
+        self.0
+    }
+}
+
+impl<P> Deref for Allowed<P> {
+    type Target = Viewer;
+
+    fn deref(&self) -> &Viewer {
+        &self.0
+    }
+}
+
+#[rocket::async_trait]
+impl<'r, P: Permission> FromRequest<'r> for Allowed<P> {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        let viewer = try_outcome!(request.guard::<Viewer>().await);
+        if viewer.can(P::LABEL) {
+            Outcome::Success(Allowed(viewer, PhantomData))
+        } else {
+            request.local_cache(|| Refusal(Some(format!("missing permission {}", P::LABEL))));
+            Outcome::Error((Status::Forbidden, ()))
+        }
+    }
+}
+
+#[catch(401)]
+pub fn unauthorized() -> Custom<Value> {
+    if cfg!(debug_assertions) {
+        api::error(
+            Status::Unauthorized,
+            format!("log in or identify yourself in the {} header", USER_HEADER),
+        )
+    } else {
+        api::error(Status::Unauthorized, "log in first")
+    }
+}
+
+#[catch(403)]
+pub fn forbidden(request: &Request) -> Custom<Value> {
+    let Refusal(reason) = request.local_cache(|| Refusal(None));
+    deny(reason.as_deref().unwrap_or("forbidden"))
+}
@@ -0,0 +1,107 @@
+//! Configuration of how tiers are validated, see `eaf::policies`.
+
+use db::audit;
+use db::tier_policies::{self, PolicyRule};
+use db::Conn;
+use eaf::policies::{self, TierPolicies};
+use rocket::http::Status;
+use serde::{Deserialize, Serialize};
+
+use super::api::{self, ApiResult};
+use super::body::JsonBody;
+use super::conn::DbConn;
+use super::tenancy::{Allowed, ConfigEdit, Viewer};
+
+/// Audit log action, per project.
+const CHANGED: &str = "project.tier_policies_changed";
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Rule {
+    /// `tier_id` or `linguistic_type`.
+    target: String,
+    /// Regex matching whole tier IDs or linguistic types.
+    tiers: String,
+    /// `freeform`, `vocabulary`, `pattern` or `skip`.
+    policy: String,
+    /// Regex values must match, for the `pattern` policy only.
+    pattern: Option<String>,
+}
+
+fn parse_rule(rule: &PolicyRule) -> Result<policies::PolicyRule, String> {
+    policies::PolicyRule::new(
+        &rule.target,
+        &rule.tiers,
+        &rule.policy,
+        rule.pattern.as_deref(),
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// The project's tier policies, ready to be applied to a document's tiers.
+pub fn tier_policies(conn: &Conn, project_id: i32) -> Result<TierPolicies, String> {
+    let rules = tier_policies::for_project(conn, project_id).map_err(|e| e.to_string())?;
+    let rules = rules.iter().map(parse_rule).collect::<Result<_, _>>()?;
+    Ok(TierPolicies::new(rules))
+}
+
+fn project_policies(conn: &Conn, viewer: &Viewer, project_id: i32) -> ApiResult {
+    viewer.project(project_id)?;
+    let rules: Vec<_> = tier_policies::for_project(conn, project_id)
+        .map_err(api::internal)?
+        .into_iter()
+        .map(|r| {
+            json!({
+                "target": r.target,
+                "tiers": r.tiers,
+                "policy": r.policy,
+                "pattern": r.pattern,
+            })
+        })
+        .collect();
+    api::ok(json!(rules))
+}
+
+#[get("/projects/<project_id>/tier-policies")]
+pub async fn get(conn: DbConn, viewer: Viewer, project_id: i32) -> ApiResult {
+    conn.run(move |conn| project_policies(conn, &viewer, project_id))
+        .await
+}
+
+/// Replace the project's rules, which are tried in the order given.
+#[put("/projects/<project_id>/tier-policies", data = "<rules>")]
+pub async fn put(
+    conn: DbConn,
+    viewer: Allowed<ConfigEdit>,
+    project_id: i32,
+    rules: JsonBody<Vec<Rule>>,
+) -> ApiResult {
+    conn.run(move |conn| {
+        viewer.project(project_id)?;
+        let details = json!(*rules);
+        let rules: Vec<_> = rules
+            .into_inner()
+            .into_iter()
+            .map(|r| PolicyRule {
+                target: r.target,
+                tiers: r.tiers,
+                policy: r.policy,
+                pattern: r.pattern,
+            })
+            .collect();
+        for rule in &rules {
+            parse_rule(rule).map_err(|e| api::error(Status::UnprocessableEntity, e))?;
+        }
+        tier_policies::replace(conn, project_id, &rules).map_err(api::internal)?;
+        audit::record(
+            conn,
+            Some(viewer.user_id),
+            CHANGED,
+            "project",
+            project_id,
+            &details,
+        )
+        .map_err(api::internal)?;
+        project_policies(conn, &viewer, project_id)
+    })
+    .await
+}
@@ -0,0 +1,117 @@
+//! Configuration of how tiers map to speakers.
+
+use db::audit;
+use db::tier_mappings::{self, TierRule};
+use db::Conn;
+use eaf::tiers::{TierMapping, TierPattern, TierSource};
+use rocket::http::Status;
+use serde::{Deserialize, Serialize};
+
+use super::api::{self, ApiResult};
+use super::body::JsonBody;
+use super::conn::DbConn;
+use super::tenancy::{Allowed, ConfigEdit, Viewer};
+
+/// Audit log action, per project.
+const CHANGED: &str = "project.tier_mappings_changed";
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Rule {
+    source: String,
+    pattern: String,
+}
+
+fn parse_rule(source: &str, pattern: &str) -> Result<(TierSource, TierPattern), String> {
+    let source = TierSource::from_label(source)
+        .ok_or_else(|| format!("unknown tier source {:?}", source))?;
+    let pattern = TierPattern::new(pattern).map_err(|e| e.to_string())?;
+    Ok((source, pattern))
+}
+
+/// The project's tier mapping, ready to be applied to a document's tiers.
+pub fn tier_mapping(conn: &Conn, project_id: i32) -> Result<TierMapping, String> {
+    let rules = tier_mappings::for_project(conn, project_id).map_err(|e| e.to_string())?;
+    let rules = rules
+        .iter()
+        .map(|r| parse_rule(&r.source, &r.pattern))
+        .collect::<Result<_, _>>()?;
+    Ok(TierMapping::new(rules))
+}
+
+fn project_mappings(conn: &Conn, viewer: &Viewer, project_id: i32) -> ApiResult {
+    viewer.project(project_id)?;
+    let rules: Vec<_> = tier_mappings::for_project(conn, project_id)
+        .map_err(api::internal)?
+        .into_iter()
+        .map(|r| json!({ "source": r.source, "pattern": r.pattern }))
+        .collect();
+    api::ok(json!(rules))
+}
+
+#[get("/projects/<project_id>/tier-mappings")]
+pub async fn get(conn: DbConn, viewer: Viewer, project_id: i32) -> ApiResult {
+    conn.run(move |conn| project_mappings(conn, &viewer, project_id))
+        .await
+}
+
+/// Replace the project's rules, which are tried in the order given.
+#[put("/projects/<project_id>/tier-mappings", data = "<rules>")]
+pub async fn put(
+    conn: DbConn,
+    viewer: Allowed<ConfigEdit>,
+    project_id: i32,
+    rules: JsonBody<Vec<Rule>>,
+) -> ApiResult {
+    conn.run(move |conn| {
+        viewer.project(project_id)?;
+        for rule in rules.iter() {
+            parse_rule(&rule.source, &rule.pattern)
+                .map_err(|e| api::error(Status::UnprocessableEntity, e))?;
+        }
+        let details = json!(*rules);
+        let rules: Vec<_> = rules
+            .into_inner()
+            .into_iter()
+            .map(|r| TierRule {
+                source: r.source,
+                pattern: r.pattern,
+            })
+            .collect();
+        tier_mappings::replace(conn, project_id, &rules).map_err(api::internal)?;
+        audit::record(
+            conn,
+            Some(viewer.user_id),
+            CHANGED,
+            "project",
+            project_id,
+            &details,
+        )
+        .map_err(api::internal)?;
+        project_mappings(conn, &viewer, project_id)
+    })
+    .await
+}
+
+/// Which speaker a tier of the document would be attributed to, for
+/// checking the configuration.
+#[get("/documents/<doc_id>/tier-speaker?<tier>&<participant>")]
+pub async fn resolve(
+    conn: DbConn,
+    viewer: Viewer,
+    doc_id: i32,
+    tier: String,
+    participant: Option<String>,
+) -> ApiResult {
+    conn.run(move |conn| {
+        let project_id = viewer.doc(conn, doc_id)?;
+        let mapping = tier_mapping(conn, project_id).map_err(api::internal)?;
+        let nickname = mapping.nickname(&tier, participant.as_deref());
+        let speaker_id = match nickname {
+            Some(nickname) => tier_mappings::speaker_for_nickname(conn, doc_id, nickname)
+                .map_err(api::internal)?,
+            None => None,
+        };
+        api::ok(json!({ "nickname": nickname, "speaker_id": speaker_id }))
+    })
+    .await
+}
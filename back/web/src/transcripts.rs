@@ -0,0 +1,229 @@
+//! Uploads of EAF transcripts and edits of single annotations in them,
+//! which are validated right away so that the uploader or editor sees what
+//! needs fixing without waiting for the background revalidation. Speakers'
+//! word counts are updated too.
+
+use std::fs;
+
+use chrono::Local;
+use db::audit;
+use db::files::{self, File};
+use db::validation::NewMistake;
+use db::Conn;
+use eaf::annotations;
+use rocket::http::Status;
+use rocket::response::status::Custom;
+use rocket::serde::json::Value;
+use rocket::{Data, State};
+use serde::Deserialize;
+
+use super::api::{self, ApiResult};
+use super::asr;
+use super::body::JsonBody;
+use super::conn::DbConn;
+use super::revalidation;
+use super::stats;
+use super::storage::{FileInfo, Storage, StorageError};
+use super::tenancy::{Allowed, DocEdit};
+
+/// Hours of densely annotated speech with plenty of tiers.
+const EAF_LIMIT: u64 = 64 * 1024 * 1024;
+
+/// Audit log action, per document.
+const UPLOADED: &str = "document.eaf_uploaded";
+
+/// Audit log action for edits of single annotations, per document.
+const EDITED: &str = "document.annotation_edited";
+
+#[derive(Debug, Deserialize)]
+pub struct AnnotationEdit {
+    value: String,
+    /// The value the edit started from. If given, the edit is refused when
+    /// the annotation has changed since, e.g. by someone else.
+    before: Option<String>,
+    /// The transcript version edited, an older one is refused unless
+    /// `before` still matches.
+    file_id: Option<i32>,
+}
+
+fn mistake_json(m: &NewMistake) -> Value {
+    json!({
+        "tier": m.tier,
+        "annotation": m.annotation,
+        "kind": m.kind,
+        "segment": m.segment,
+        "start": m.start,
+        "end": m.end,
+    })
+}
+
+/// Upload a new version of the document's transcript, in the request body.
+/// Returns the stored file and the mistakes in it, in document order with
+/// their tier and annotation. Mistakes in time slots, annotation intervals,
+/// alignment with the recording and controlled vocabularies come first,
+/// with the slot, annotation overlapped, value or vocabulary in place of
+/// the segment and no span.
+#[post("/documents/<doc_id>/eaf", data = "<body>")]
+pub async fn upload(
+    conn: DbConn,
+    viewer: Allowed<DocEdit>,
+    storage: &State<Storage>,
+    doc_id: i32,
+    body: Data<'_>,
+) -> ApiResult {
+    let user_id = viewer.user_id;
+    let project_id = conn.run(move |conn| viewer.doc(conn, doc_id)).await?;
+    let xml = asr::read_body(body, EAF_LIMIT).await?;
+    eaf::annotations::read(&xml).map_err(|e| api::error(Status::UnprocessableEntity, e))?;
+    let name = format!("eaf-{}.eaf", Local::now().format("%Y%m%d-%H%M%S"));
+    let storage = storage.inner().clone();
+    conn.run(move |conn| {
+        let file_id = storage
+            .store(
+                conn,
+                doc_id,
+                &name,
+                xml.as_bytes(),
+                EAF_LIMIT,
+                FileInfo {
+                    role: files::EAF,
+                    mime: "application/xml",
+                    created_by: Some(user_id),
+                    source_id: None,
+                },
+            )
+            .map_err(|e| match e {
+                StorageError::TooLarge(_) => api::error(Status::PayloadTooLarge, e),
+                _ => api::internal(e),
+            })?;
+        let details = json!({ "file_id": file_id });
+        audit::record(conn, Some(user_id), UPLOADED, "document", doc_id, &details)
+            .map_err(api::internal)?;
+
+        let file = files::get(conn, file_id).map_err(api::internal)?;
+        let mistakes: Vec<_> = revalidation::validate(conn, &storage, &file, Some(user_id))
+            .map_err(|e| api::error(Status::UnprocessableEntity, e))?
+            .iter()
+            .map(mistake_json)
+            .collect();
+        stats::update_word_counts(conn, &storage, doc_id, project_id).map_err(api::internal)?;
+        api::ok(json!({ "file_id": file_id, "mistakes": mistakes }))
+    })
+    .await
+}
+
+/// Store the transcript with an annotation edited, as a new version derived
+/// from `source`.
+fn store_edit(
+    conn: &Conn,
+    storage: &Storage,
+    doc_id: i32,
+    source: &File,
+    xml: &str,
+    user_id: i32,
+) -> Result<i32, Custom<Value>> {
+    let stamp = Local::now().format("%Y%m%d-%H%M%S");
+    storage
+        .store(
+            conn,
+            doc_id,
+            &format!("edit-{}-{}.eaf", stamp, source.id),
+            xml.as_bytes(),
+            EAF_LIMIT,
+            FileInfo {
+                role: files::EAF,
+                mime: "application/xml",
+                created_by: Some(user_id),
+                source_id: Some(source.id),
+            },
+        )
+        .map_err(|e| match e {
+            StorageError::TooLarge(_) => api::error(Status::PayloadTooLarge, e),
+            _ => api::internal(e),
+        })
+}
+
+/// Change the value of a single annotation in the document's latest
+/// transcript, storing the result as a new version derived from it.
+/// Returns the stored file and the mistakes in the edited annotation, as in
+/// `upload`; the whole transcript is revalidated, though.
+#[patch("/documents/<doc_id>/annotations/<ann_id>", data = "<edit>")]
+pub async fn edit_annotation(
+    conn: DbConn,
+    viewer: Allowed<DocEdit>,
+    storage: &State<Storage>,
+    doc_id: i32,
+    ann_id: String,
+    edit: JsonBody<AnnotationEdit>,
+) -> ApiResult {
+    let storage = storage.inner().clone();
+    conn.run(move |conn| {
+        let project_id = viewer.doc(conn, doc_id)?;
+        let file = match files::latest(conn, doc_id, &[files::EAF]).map_err(api::internal)? {
+            Some(file) => file,
+            None => return Err(api::error(Status::NotFound, "document has no transcript")),
+        };
+        let xml = fs::read_to_string(storage.path(&file.path)).map_err(api::internal)?;
+        let mut before = None;
+        let xml = annotations::rewrite(&xml, |annotation| {
+            if annotation.id != ann_id {
+                return None;
+            }
+            before = Some(annotation.value.clone());
+            Some(edit.value.clone())
+        })
+        .map_err(|e| api::error(Status::UnprocessableEntity, e))?;
+        let before = match before {
+            Some(before) => before,
+            None => {
+                return Err(api::error(
+                    Status::NotFound,
+                    format!("no such annotation {}", ann_id),
+                ))
+            }
+        };
+        let stale = match (&edit.before, edit.file_id) {
+            (Some(expected), _) => *expected != before,
+            (None, Some(file_id)) => file_id != file.id,
+            (None, None) => false,
+        };
+        if stale {
+            return Err(api::error(
+                Status::Conflict,
+                "the annotation changed since, reload it",
+            ));
+        }
+        // saving an unchanged annotation just checks it again
+        let file = if before == edit.value {
+            file
+        } else {
+            let file_id = store_edit(conn, &storage, doc_id, &file, &xml, viewer.user_id)?;
+            let details = json!({
+                "file_id": file_id,
+                "source_id": file.id,
+                "annotation": ann_id,
+                "before": before,
+                "after": edit.value,
+            });
+            audit::record(
+                conn,
+                Some(viewer.user_id),
+                EDITED,
+                "document",
+                doc_id,
+                &details,
+            )
+            .map_err(api::internal)?;
+            files::get(conn, file_id).map_err(api::internal)?
+        };
+        let mistakes: Vec<_> = revalidation::validate(conn, &storage, &file, Some(viewer.user_id))
+            .map_err(|e| api::error(Status::UnprocessableEntity, e))?
+            .iter()
+            .filter(|m| m.annotation == ann_id)
+            .map(mistake_json)
+            .collect();
+        stats::update_word_counts(conn, &storage, doc_id, project_id).map_err(api::internal)?;
+        api::ok(json!({ "file_id": file.id, "mistakes": mistakes }))
+    })
+    .await
+}
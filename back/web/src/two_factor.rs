@@ -0,0 +1,131 @@
+//! Two-factor authentication of accounts which can do serious damage (see
+//! `db::two_factor`). Once enabled, starting a session takes a code from
+//! the user's authenticator app or one of their recovery codes.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use db::two_factor::{self, TwoFactorError};
+use db::{audit, users};
+use rocket::http::Status;
+use rocket::response::status::Custom;
+use rocket::serde::json::Value;
+use serde::Deserialize;
+
+use super::api::{self, ApiResult};
+use super::body::JsonBody;
+use super::conn::DbConn;
+use super::tenancy::{Allowed, TwoFactor, Viewer};
+
+/// Shown by authenticator apps next to the account name.
+const ISSUER: &str = "Quetzal";
+/// Audit log actions, per user.
+const ENABLED: &str = "user.two_factor_enabled";
+const DISABLED: &str = "user.two_factor_disabled";
+
+#[derive(Debug, Deserialize)]
+pub struct CodeRequest {
+    code: String,
+}
+
+pub fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+pub fn two_factor_error(e: TwoFactorError) -> Custom<Value> {
+    match e {
+        TwoFactorError::NotEnrolled | TwoFactorError::AlreadyEnabled => {
+            api::error(Status::Conflict, e)
+        }
+        TwoFactorError::BadCode => api::error(Status::Unauthorized, e),
+        TwoFactorError::Db(e) => api::internal(e),
+    }
+}
+
+#[get("/account/two-factor")]
+pub async fn status(conn: DbConn, viewer: Viewer) -> ApiResult {
+    conn.run(move |conn| {
+        let enabled = two_factor::is_enabled(conn, viewer.user_id).map_err(api::internal)?;
+        let left = two_factor::recovery_codes_left(conn, viewer.user_id).map_err(api::internal)?;
+        api::ok(json!({ "enabled": enabled, "recovery_codes_left": left }))
+    })
+    .await
+}
+
+/// A new secret to add to an authenticator app, also as an `otpauth://`
+/// URI for showing as a QR code. Confirm it with a code from the app.
+#[post("/account/two-factor")]
+pub async fn enroll(conn: DbConn, viewer: Allowed<TwoFactor>) -> ApiResult {
+    conn.run(move |conn| {
+        let secret = two_factor::enroll(conn, viewer.user_id).map_err(two_factor_error)?;
+        let username = users::identity(conn, viewer.user_id)
+            .map_err(api::internal)?
+            .username;
+        let uri = two_factor::provisioning_uri(&secret, &username, ISSUER);
+        api::ok(json!({ "secret": secret, "uri": uri }))
+    })
+    .await
+}
+
+/// Returns recovery codes, which won't be shown again.
+#[post("/account/two-factor/confirm", data = "<request>")]
+pub async fn confirm(
+    conn: DbConn,
+    viewer: Allowed<TwoFactor>,
+    request: JsonBody<CodeRequest>,
+) -> ApiResult {
+    conn.run(move |conn| {
+        let codes = two_factor::confirm(conn, viewer.user_id, &request.code, unix_time())
+            .map_err(two_factor_error)?;
+        audit::record(
+            conn,
+            Some(viewer.user_id),
+            ENABLED,
+            "user",
+            viewer.user_id,
+            &json!({}),
+        )
+        .map_err(api::internal)?;
+        api::ok(json!({ "recovery_codes": codes }))
+    })
+    .await
+}
+
+/// Replace the recovery codes, e.g. when running out of them.
+#[post("/account/two-factor/recovery-codes", data = "<request>")]
+pub async fn recovery_codes(
+    conn: DbConn,
+    viewer: Viewer,
+    request: JsonBody<CodeRequest>,
+) -> ApiResult {
+    conn.run(move |conn| {
+        two_factor::verify(conn, viewer.user_id, &request.code, unix_time())
+            .map_err(two_factor_error)?;
+        let codes =
+            two_factor::regenerate_recovery_codes(conn, viewer.user_id).map_err(api::internal)?;
+        api::ok(json!({ "recovery_codes": codes }))
+    })
+    .await
+}
+
+#[delete("/account/two-factor", data = "<request>")]
+pub async fn disable(conn: DbConn, viewer: Viewer, request: JsonBody<CodeRequest>) -> ApiResult {
+    conn.run(move |conn| {
+        two_factor::verify(conn, viewer.user_id, &request.code, unix_time())
+            .map_err(two_factor_error)?;
+        two_factor::disable(conn, viewer.user_id).map_err(api::internal)?;
+        audit::record(
+            conn,
+            Some(viewer.user_id),
+            DISABLED,
+            "user",
+            viewer.user_id,
+            &json!({}),
+        )
+        .map_err(api::internal)?;
+        api::ok(json!(null))
+    })
+    .await
+}
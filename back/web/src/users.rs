@@ -0,0 +1,129 @@
+//! User endpoints.
+
+use chrono::{Duration, Local};
+use db::{audit, people, sessions, users, validation};
+use diesel::result::Error;
+use rocket::http::Status;
+use serde::Deserialize;
+use validator::Validate;
+
+use super::api::{self, ApiResult};
+use super::body::Valid;
+use super::conn::DbConn;
+use super::tenancy::Viewer;
+
+/// Audit log action for setting someone else's password.
+const PASSWORD_RESET: &str = "user.password_reset";
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct PasswordRequest {
+    /// Required when changing one's own password, if there's one already.
+    current: Option<String>,
+    #[validate(length(min = 8))]
+    password: String,
+}
+
+/// Search users by (part of) their username or badge, tolerating typos.
+#[get("/users/search?<q>&<limit>")]
+pub async fn search(conn: DbConn, viewer: Viewer, q: String, limit: Option<usize>) -> ApiResult {
+    conn.run(move |conn| {
+        let users: Vec<_> = people::search_users(conn, &q, &viewer.access, api::limit(limit))
+            .map_err(api::internal)?
+            .into_iter()
+            .map(|u| json!({ "id": u.id, "username": u.username, "badge": u.badge, "role_id": u.role_id }))
+            .collect();
+        api::ok(json!(users))
+    })
+    .await
+}
+
+/// Weeks of history to consider for mistake patterns by default.
+const PATTERN_WEEKS: i64 = 12;
+
+/// The kinds of mistakes the user makes most often, with example segments,
+/// so that they know what to focus on.
+#[get("/users/<user_id>/mistake-patterns?<weeks>")]
+pub async fn mistake_patterns(
+    conn: DbConn,
+    viewer: Viewer,
+    user_id: i32,
+    weeks: Option<i64>,
+) -> ApiResult {
+    conn.run(move |conn| {
+        viewer.colleague(conn, user_id)?;
+        let since = Local::now().naive_local() - Duration::weeks(weeks.unwrap_or(PATTERN_WEEKS));
+        let patterns: Vec<_> = validation::mistake_patterns(conn, user_id, since)
+            .map_err(api::internal)?
+            .into_iter()
+            .map(|p| {
+                let weekly: Vec<_> = p
+                    .weekly
+                    .iter()
+                    .map(
+                        |(year, week, count)| json!({ "year": year, "week": week, "count": count }),
+                    )
+                    .collect();
+                let examples: Vec<_> = p
+                    .examples
+                    .iter()
+                    .map(|e| {
+                        json!({
+                            "doc_id": e.doc_id,
+                            "tier": e.tier,
+                            "annotation": e.annotation,
+                            "segment": e.segment,
+                            "start": e.start,
+                            "end": e.end,
+                        })
+                    })
+                    .collect();
+                json!({ "kind": p.kind, "count": p.count, "weekly": weekly, "examples": examples })
+            })
+            .collect();
+        api::ok(json!(patterns))
+    })
+    .await
+}
+
+/// Set the user's password. Users can change their own, giving the current
+/// one, admins can reset anyone's, which logs the user out everywhere.
+#[put("/users/<user_id>/password", data = "<request>")]
+pub async fn set_password(
+    conn: DbConn,
+    viewer: Viewer,
+    user_id: i32,
+    request: Valid<PasswordRequest>,
+) -> ApiResult {
+    conn.run(move |conn| {
+        viewer.user(user_id)?;
+        let request = request.into_inner();
+        if user_id == viewer.user_id && users::has_password(conn, user_id).map_err(api::internal)? {
+            let current = request.current.as_deref().unwrap_or_default();
+            if !users::check_password(conn, user_id, current).map_err(api::internal)? {
+                return Err(api::error(Status::Forbidden, "wrong current password"));
+            }
+        }
+        match users::set_password(conn, user_id, &request.password) {
+            Ok(true) => {}
+            Ok(false) | Err(Error::NotFound) => {
+                return Err(api::error(Status::NotFound, "no such user"))
+            }
+            Err(e) => return Err(api::internal(e)),
+        }
+        if user_id != viewer.user_id {
+            let revoked = sessions::revoke_all(conn, user_id).map_err(api::internal)?;
+            let details = json!({ "sessions_revoked": revoked });
+            audit::record(
+                conn,
+                Some(viewer.user_id),
+                PASSWORD_RESET,
+                "user",
+                user_id,
+                &details,
+            )
+            .map_err(api::internal)?;
+        }
+        api::ok(json!(null))
+    })
+    .await
+}
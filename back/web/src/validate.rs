@@ -0,0 +1,102 @@
+//! Validating a single segment as it's being typed, so that the frontend
+//! can underline mistakes inline without saving the transcript first.
+
+use std::sync::Arc;
+
+use db::{docs, substitutions};
+use diesel::result::Error;
+use eaf::fixes::{self, Substitutions};
+use eaf::messages::{self, Lang};
+use eaf::offsets::Unit;
+use eaf::parser::{Parser, ParserConfig};
+use rocket::http::Status;
+use serde::Deserialize;
+
+use super::api::{self, ApiResult};
+use super::body::JsonBody;
+use super::conn::DbConn;
+use super::rules;
+use super::tenancy::Viewer;
+
+#[derive(Debug, Deserialize)]
+pub struct ValidateRequest {
+    segment: String,
+    /// Label of the project whose rules to apply. Without one, only the
+    /// structure is checked: delimiters, attribute lists and whitespace.
+    config: Option<String>,
+    /// Language of the `message`s, e.g. `cs`; English by default.
+    lang: Option<String>,
+}
+
+/// Any characters and attribute codes allowed.
+fn structural_config() -> ParserConfig {
+    ParserConfig::from_args(&[] as &[&str], &[] as &[&str], &["."], &["[^_]+"])
+}
+
+/// Mistakes in the segment, with spans in graphemes of the normalized
+/// segment (`source`) and a `message` explaining them. `detail` is the mistake as serialized by `eaf`, with
+/// byte and token offsets. Mistakes with an obvious remedy come with a
+/// `fix`, and `fixed` is the segment with all of them applied.
+#[post("/validate", data = "<request>")]
+pub async fn validate(
+    conn: DbConn,
+    viewer: Viewer,
+    request: JsonBody<ValidateRequest>,
+) -> ApiResult {
+    conn.run(move |conn| {
+        let lang = match &request.lang {
+            Some(lang) => lang
+                .parse()
+                .map_err(|e| api::error(Status::UnprocessableEntity, e))?,
+            None => Lang::default(),
+        };
+        let (config, substitutions) = match &request.config {
+            Some(label) => {
+                let project_id = match docs::project_by_label(conn, label) {
+                    Ok(id) => id,
+                    Err(Error::NotFound) => {
+                        return Err(api::error(Status::NotFound, "no such config"));
+                    }
+                    Err(e) => return Err(api::internal(e)),
+                };
+                viewer.project(project_id)?;
+                let config = rules::project_config(conn, project_id).map_err(api::internal)?;
+                let pairs = substitutions::for_project(conn, project_id).map_err(api::internal)?;
+                (config, Substitutions::new(pairs))
+            }
+            None => (Arc::new(structural_config()), Substitutions::default()),
+        };
+        let parsed = Parser::parse(&config, config.tokenize(&request.segment));
+        let mistakes: Vec<_> = parsed
+            .mistakes
+            .iter()
+            .map(|mistake| {
+                let (token, start, end) = parsed.span_in(mistake, Unit::Grapheme);
+                let fix = fixes::quick_fix(&config, &parsed, mistake, &substitutions).map(|edit| {
+                    json!({
+                        "start": parsed.offset(edit.start, Unit::Grapheme),
+                        "end": parsed.offset(edit.end, Unit::Grapheme),
+                        "replacement": edit.replacement,
+                    })
+                });
+                json!({
+                    "kind": mistake.kind(),
+                    "warning": mistake.is_warning(),
+                    "message": messages::message(&parsed, mistake, lang),
+                    "token": token,
+                    "start": start,
+                    "end": end,
+                    "detail": mistake,
+                    "fix": fix,
+                })
+            })
+            .collect();
+        api::ok(json!({
+            "source": parsed.source,
+            "version": config.version(),
+            "mistakes": mistakes,
+            "fixed": parsed.apply_fixes(&config, &substitutions),
+        }))
+    })
+    .await
+}
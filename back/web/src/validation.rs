@@ -0,0 +1,110 @@
+//! Endpoints exposing the results of transcript validation.
+
+use db::validation;
+use eaf::intervals::IntervalMistake;
+use eaf::media::BeyondMedia;
+use eaf::parser::Mistake;
+use eaf::policies::PatternMismatch;
+use eaf::timeslots::SlotMistake;
+use eaf::vocabularies::VocabMistake;
+use rocket::serde::json::Value;
+use rocket::State;
+
+use super::api::{self, ApiResult};
+use super::conn::DbConn;
+use super::revalidation;
+use super::storage::Storage;
+use super::tenancy::{Allowed, ConfigEdit, Viewer};
+
+/// Counts for all known kinds of mistakes (so that charts have a stable set
+/// of categories), plus any other kinds found in the DB.
+fn breakdown(counts: Vec<(String, i64)>) -> Value {
+    let known: Vec<&str> = Mistake::KINDS
+        .iter()
+        .chain(SlotMistake::KINDS)
+        .chain(IntervalMistake::KINDS)
+        .chain(VocabMistake::KINDS)
+        .chain(&[PatternMismatch::KIND, BeyondMedia::KIND])
+        .copied()
+        .collect();
+    let mut breakdown: Vec<_> = known
+        .iter()
+        .map(|&kind| {
+            let count = counts
+                .iter()
+                .find(|(k, _)| k == kind)
+                .map(|(_, c)| *c)
+                .unwrap_or(0);
+            json!({ "kind": kind, "count": count })
+        })
+        .collect();
+    for (kind, count) in &counts {
+        if !known.contains(&kind.as_str()) {
+            breakdown.push(json!({ "kind": kind, "count": count }));
+        }
+    }
+    json!(breakdown)
+}
+
+#[get("/documents/<doc_id>/mistake-kinds")]
+pub async fn doc_mistake_kinds(conn: DbConn, viewer: Viewer, doc_id: i32) -> ApiResult {
+    conn.run(move |conn| {
+        viewer.doc(conn, doc_id)?;
+        let counts = validation::kinds_for_doc(conn, doc_id).map_err(api::internal)?;
+        api::ok(breakdown(counts))
+    })
+    .await
+}
+
+#[get("/projects/<project_id>/mistake-kinds")]
+pub async fn project_mistake_kinds(conn: DbConn, viewer: Viewer, project_id: i32) -> ApiResult {
+    conn.run(move |conn| {
+        viewer.project(project_id)?;
+        let counts = validation::kinds_for_project(conn, project_id).map_err(api::internal)?;
+        api::ok(breakdown(counts))
+    })
+    .await
+}
+
+/// Documents whose latest validation found more mistakes than the one
+/// before it.
+#[get("/projects/<project_id>/regressions")]
+pub async fn regressions(conn: DbConn, viewer: Viewer, project_id: i32) -> ApiResult {
+    conn.run(move |conn| {
+        viewer.project(project_id)?;
+        let runs: Vec<_> = validation::regressions(conn, project_id)
+            .map_err(api::internal)?
+            .into_iter()
+            .map(|r| {
+                json!({
+                    "doc_id": r.doc_id,
+                    "run_id": r.id,
+                    "created_at": r.created_at.to_string(),
+                    "mistakes": r.mistakes,
+                    "user_id": r.user_id,
+                })
+            })
+            .collect();
+        api::ok(json!(runs))
+    })
+    .await
+}
+
+/// Queue revalidation of the project's stale transcripts right away rather
+/// than waiting for the nightly run.
+#[post("/projects/<project_id>/revalidate")]
+pub async fn revalidate(
+    conn: DbConn,
+    viewer: Allowed<ConfigEdit>,
+    storage: &State<Storage>,
+    project_id: i32,
+) -> ApiResult {
+    let storage = storage.inner().clone();
+    conn.run(move |conn| {
+        viewer.project(project_id)?;
+        let queued =
+            revalidation::enqueue_stale(conn, &storage, Some(project_id)).map_err(api::internal)?;
+        api::ok(json!({ "queued": queued }))
+    })
+    .await
+}
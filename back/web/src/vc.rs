@@ -0,0 +1,604 @@
+//! Version control of transcripts in git, in bare repositories with a
+//! `<doc_id>.eaf` file per document. A document's transcript is committed
+//! to the repositories of all its corpora whenever it's accepted or
+//! restored, authored by whoever created that version of it. With the git
+//! transcript backend (see `storage::Backend`), every saved transcript is
+//! also committed to its project's repository, authored by whoever saved
+//! it. Speaker and document metadata stay in the database for now.
+//!
+//! Transcripts are committed in canonical formatting (see `eaf::canonical`)
+//! so that diffs only show changes to their content. Transcripts committed
+//! before that, or under different formatting rules, can be brought in line
+//! with a maintenance commit.
+//!
+//! The repositories are an archive alongside the stored files, which stay
+//! authoritative: restoring an old revision stores it as a new version.
+
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, NaiveDateTime};
+use db::Conn;
+use db::{audit, corpora, docs, files, users};
+use diesel::result::Error;
+use eaf::canonical;
+use git2::{ErrorCode, Oid, Repository, Signature};
+use rocket::http::{ContentType, Status};
+use rocket::response::status::Custom;
+use rocket::serde::json::Value;
+use rocket::State;
+use serde::Deserialize;
+
+use super::api::{self, ApiResult};
+use super::body::JsonBody;
+use super::conn::DbConn;
+use super::storage::{FileInfo, Storage};
+use super::tenancy::{Allowed, DocEdit, MaintenanceRun, Viewer};
+
+/// Audit log action for restoring old revisions.
+const RESTORE: &str = "document.restore";
+/// Audit log action for normalizing a corpus's repository.
+const NORMALIZE: &str = "corpus.normalize";
+/// Signs commits of changes made by nobody in particular, e.g. imports.
+const FALLBACK_NAME: &str = "quetzal";
+
+/// Directory with the repositories, from the `vc_dir` config key.
+#[derive(Debug, Clone)]
+pub struct Repos(pub PathBuf);
+
+/// Whose repository: a corpus's, with accepted transcripts, or a
+/// project's, with every saved one.
+#[derive(Debug, Clone, Copy)]
+pub enum Scope {
+    Corpus(i32),
+    Project(i32),
+}
+
+#[derive(Debug)]
+pub enum VcError {
+    Git(git2::Error),
+    Io(PathBuf, std::io::Error),
+    Db(diesel::result::Error),
+}
+
+impl fmt::Display for VcError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            VcError::Git(e) => write!(f, "git: {}", e.message()),
+            VcError::Io(path, e) => write!(f, "{}: {}", path.display(), e),
+            VcError::Db(e) => e.fmt(f),
+        }
+    }
+}
+
+impl From<git2::Error> for VcError {
+    fn from(e: git2::Error) -> Self {
+        VcError::Git(e)
+    }
+}
+
+impl From<diesel::result::Error> for VcError {
+    fn from(e: diesel::result::Error) -> Self {
+        VcError::Db(e)
+    }
+}
+
+#[derive(Debug)]
+pub struct Revision {
+    pub id: String,
+    pub author: String,
+    pub email: String,
+    pub time: NaiveDateTime,
+    pub message: String,
+}
+
+/// The outcome of normalizing a repository.
+#[derive(Debug, Default)]
+pub struct Normalized {
+    pub commit: Option<String>,
+    /// Files whose formatting changed.
+    pub changed: Vec<String>,
+    /// Files which couldn't be normalized, with why.
+    pub failed: Vec<(String, String)>,
+}
+
+fn file_name(doc_id: i32) -> String {
+    format!("{}.eaf", doc_id)
+}
+
+fn is_missing(e: &git2::Error) -> bool {
+    matches!(e.code(), ErrorCode::NotFound | ErrorCode::UnbornBranch)
+}
+
+/// The blob at `name` in the commit's tree, if any.
+fn blob_id(commit: &git2::Commit<'_>, name: &str) -> Result<Option<Oid>, git2::Error> {
+    match commit.tree()?.get_path(Path::new(name)) {
+        Ok(entry) => Ok(Some(entry.id())),
+        Err(e) if is_missing(&e) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+impl Repos {
+    fn path(&self, scope: Scope) -> PathBuf {
+        match scope {
+            Scope::Corpus(id) => self.0.join(format!("corpus-{}.git", id)),
+            Scope::Project(id) => self.0.join(format!("project-{}.git", id)),
+        }
+    }
+
+    /// The repository, if it was created already.
+    fn open(&self, scope: Scope) -> Result<Option<Repository>, git2::Error> {
+        match Repository::open_bare(self.path(scope)) {
+            Ok(repo) => Ok(Some(repo)),
+            Err(e) if is_missing(&e) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Commit a new version of the document's transcript to the
+    /// repository, creating it if needed. Returns the commit's ID, or
+    /// `None` if the transcript didn't change.
+    pub fn commit(
+        &self,
+        scope: Scope,
+        doc_id: i32,
+        contents: &[u8],
+        author: &Signature<'_>,
+        message: &str,
+    ) -> Result<Option<String>, VcError> {
+        let repo = match self.open(scope)? {
+            Some(repo) => repo,
+            None => {
+                fs::create_dir_all(&self.0).map_err(|e| VcError::Io(self.0.clone(), e))?;
+                Repository::init_bare(self.path(scope))?
+            }
+        };
+        let name = file_name(doc_id);
+        let blob = repo.blob(contents)?;
+        let parent = match repo.head() {
+            Ok(head) => Some(head.peel_to_commit()?),
+            Err(e) if is_missing(&e) => None,
+            Err(e) => return Err(e.into()),
+        };
+        if let Some(parent) = &parent {
+            if blob_id(parent, &name)? == Some(blob) {
+                return Ok(None);
+            }
+        }
+        let parent_tree = parent.as_ref().map(|p| p.tree()).transpose()?;
+        let mut builder = repo.treebuilder(parent_tree.as_ref())?;
+        builder.insert(&name, blob, 0o100_644)?;
+        let tree = repo.find_tree(builder.write()?)?;
+        let committer = Signature::now(FALLBACK_NAME, &format!("{}@localhost", FALLBACK_NAME))?;
+        let parents: Vec<_> = parent.iter().collect();
+        let id = repo.commit(Some("HEAD"), author, &committer, message, &tree, &parents)?;
+        Ok(Some(id.to_string()))
+    }
+
+    /// Commit all transcripts in the repository in canonical formatting,
+    /// if it exists.
+    pub fn normalize(
+        &self,
+        scope: Scope,
+        author: &Signature<'_>,
+    ) -> Result<Option<Normalized>, git2::Error> {
+        let repo = match self.open(scope)? {
+            Some(repo) => repo,
+            None => return Ok(None),
+        };
+        let parent = match repo.head() {
+            Ok(head) => head.peel_to_commit()?,
+            Err(e) if is_missing(&e) => return Ok(Some(Normalized::default())),
+            Err(e) => return Err(e),
+        };
+        let tree = parent.tree()?;
+        let mut builder = repo.treebuilder(Some(&tree))?;
+        let mut normalized = Normalized::default();
+        for entry in tree.iter() {
+            let name = match entry.name() {
+                Some(name) if name.ends_with(".eaf") => name.to_owned(),
+                _ => continue,
+            };
+            let blob = repo.find_blob(entry.id())?;
+            let xml = match std::str::from_utf8(blob.content()) {
+                Ok(xml) => xml,
+                Err(e) => {
+                    normalized.failed.push((name, e.to_string()));
+                    continue;
+                }
+            };
+            match canonical::normalize(xml) {
+                Ok(canonical) if canonical != xml => {
+                    builder.insert(&name, repo.blob(canonical.as_bytes())?, entry.filemode())?;
+                    normalized.changed.push(name);
+                }
+                Ok(_) => {}
+                Err(e) => normalized.failed.push((name, e.to_string())),
+            }
+        }
+        if !normalized.changed.is_empty() {
+            let tree = repo.find_tree(builder.write()?)?;
+            let id = repo.commit(
+                Some("HEAD"),
+                author,
+                author,
+                "Normalize formatting",
+                &tree,
+                &[&parent],
+            )?;
+            normalized.commit = Some(id.to_string());
+        }
+        Ok(Some(normalized))
+    }
+
+    /// Commits changing the document's transcript, newest first.
+    pub fn history(&self, scope: Scope, doc_id: i32) -> Result<Vec<Revision>, git2::Error> {
+        let repo = match self.open(scope)? {
+            Some(repo) => repo,
+            None => return Ok(vec![]),
+        };
+        let mut walk = repo.revwalk()?;
+        match walk.push_head() {
+            Ok(()) => {}
+            Err(e) if is_missing(&e) => return Ok(vec![]),
+            Err(e) => return Err(e),
+        }
+        let name = file_name(doc_id);
+        let mut revisions = vec![];
+        for id in walk {
+            let commit = repo.find_commit(id?)?;
+            let blob = blob_id(&commit, &name)?;
+            let previous = match commit.parents().next() {
+                Some(parent) => blob_id(&parent, &name)?,
+                None => None,
+            };
+            if blob.is_none() || blob == previous {
+                continue;
+            }
+            let author = commit.author();
+            revisions.push(Revision {
+                id: commit.id().to_string(),
+                author: author.name().unwrap_or_default().to_owned(),
+                email: author.email().unwrap_or_default().to_owned(),
+                time: DateTime::from_timestamp(commit.time().seconds(), 0)
+                    .unwrap_or_default()
+                    .naive_utc(),
+                message: commit.message().unwrap_or_default().to_owned(),
+            });
+        }
+        Ok(revisions)
+    }
+
+    /// The document's transcript as of the revision, if it's there.
+    pub fn revision(
+        &self,
+        scope: Scope,
+        doc_id: i32,
+        revision: &str,
+    ) -> Result<Option<Vec<u8>>, git2::Error> {
+        let repo = match self.open(scope)? {
+            Some(repo) => repo,
+            None => return Ok(None),
+        };
+        let commit = match Oid::from_str(revision).and_then(|id| repo.find_commit(id)) {
+            Ok(commit) => commit,
+            Err(e) if is_missing(&e) || e.class() == git2::ErrorClass::Invalid => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        match blob_id(&commit, &file_name(doc_id))? {
+            Some(id) => Ok(Some(repo.find_blob(id)?.content().to_vec())),
+            None => Ok(None),
+        }
+    }
+}
+
+/// A signature for the user, by their username and e-mail.
+fn signature(conn: &Conn, user_id: Option<i32>) -> Result<Signature<'static>, VcError> {
+    let (name, email) = match user_id {
+        Some(user_id) => {
+            let users::Identity { username, email } = users::identity(conn, user_id)?;
+            let email = email
+                .filter(|e| !e.trim().is_empty())
+                .unwrap_or_else(|| format!("{}@localhost", username));
+            (username, email)
+        }
+        None => (
+            FALLBACK_NAME.to_owned(),
+            format!("{}@localhost", FALLBACK_NAME),
+        ),
+    };
+    Ok(Signature::now(&name, &email)?)
+}
+
+/// Commit the document's latest transcript to the repositories of all its
+/// corpora, authored by its creator or else `user_id`. Returns the IDs of
+/// new commits.
+pub fn commit_latest(
+    conn: &Conn,
+    storage: &Storage,
+    repos: &Repos,
+    doc_id: i32,
+    user_id: Option<i32>,
+    message: &str,
+) -> Result<Vec<String>, VcError> {
+    let file = match files::latest(conn, doc_id, &[files::EAF])? {
+        Some(file) => file,
+        None => return Ok(vec![]),
+    };
+    let contents = canonical_contents(storage, &file)?;
+    let author = signature(conn, file.created_by.or(user_id))?;
+    let mut commits = vec![];
+    for corpus in corpora::for_doc(conn, doc_id)? {
+        let scope = Scope::Corpus(corpus.id);
+        if let Some(id) = repos.commit(scope, doc_id, &contents, &author, message)? {
+            commits.push(id);
+        }
+    }
+    Ok(commits)
+}
+
+/// Commit a transcript just saved to its project's repository, authored by
+/// whoever saved it. Returns the commit's ID, or `None` if the transcript
+/// didn't change.
+pub fn commit_saved(
+    conn: &Conn,
+    storage: &Storage,
+    repos: &Repos,
+    file: &files::File,
+) -> Result<Option<String>, VcError> {
+    let project_id = docs::project_of(conn, file.doc_id)?;
+    let contents = canonical_contents(storage, file)?;
+    let author = signature(conn, file.created_by)?;
+    let message = format!("Save document {} as {}", file.doc_id, file.path);
+    repos.commit(
+        Scope::Project(project_id),
+        file.doc_id,
+        &contents,
+        &author,
+        &message,
+    )
+}
+
+/// The stored transcript, in canonical formatting if possible.
+fn canonical_contents(storage: &Storage, file: &files::File) -> Result<Vec<u8>, VcError> {
+    let path = storage.path(&file.path);
+    let contents = fs::read(&path).map_err(|e| VcError::Io(path, e))?;
+    // commit as is what can't be normalized, it's still worth keeping
+    if let Ok(canonical) = std::str::from_utf8(&contents).map(canonical::normalize) {
+        match canonical {
+            Ok(canonical) => return Ok(canonical.into_bytes()),
+            Err(e) => eprintln!("document {}: {}", file.doc_id, e),
+        }
+    }
+    Ok(contents)
+}
+
+fn history_json(repos: &Repos, scope: Scope, doc_id: i32) -> ApiResult {
+    let revisions: Vec<_> = repos
+        .history(scope, doc_id)
+        .map_err(api::internal)?
+        .into_iter()
+        .map(|r| {
+            json!({
+                "id": r.id,
+                "author": r.author,
+                "email": r.email,
+                "time": r.time.to_string(),
+                "message": r.message,
+            })
+        })
+        .collect();
+    api::ok(json!(revisions))
+}
+
+fn revision_xml(
+    repos: &Repos,
+    scope: Scope,
+    doc_id: i32,
+    revision: &str,
+) -> Result<(ContentType, Vec<u8>), Custom<Value>> {
+    match repos
+        .revision(scope, doc_id, revision)
+        .map_err(api::internal)?
+    {
+        Some(contents) => Ok((ContentType::XML, contents)),
+        None => Err(api::error(Status::NotFound, "no such revision")),
+    }
+}
+
+fn check_membership(conn: &Conn, corpus_id: i32, doc_id: i32) -> Result<(), Custom<Value>> {
+    match corpora::for_doc(conn, doc_id) {
+        Ok(corpora) if corpora.iter().any(|c| c.id == corpus_id) => Ok(()),
+        Ok(_) | Err(Error::NotFound) => Err(api::error(
+            Status::NotFound,
+            "no such document in the corpus",
+        )),
+        Err(e) => Err(api::internal(e)),
+    }
+}
+
+#[get("/corpora/<corpus_id>/documents/<doc_id>/history")]
+pub async fn history(
+    conn: DbConn,
+    viewer: Viewer,
+    repos: &State<Repos>,
+    corpus_id: i32,
+    doc_id: i32,
+) -> ApiResult {
+    let repos = repos.inner().clone();
+    conn.run(move |conn| {
+        viewer.doc(conn, doc_id)?;
+        check_membership(conn, corpus_id, doc_id)?;
+        history_json(&repos, Scope::Corpus(corpus_id), doc_id)
+    })
+    .await
+}
+
+#[get("/corpora/<corpus_id>/documents/<doc_id>/history/<revision>")]
+pub async fn revision(
+    conn: DbConn,
+    viewer: Viewer,
+    repos: &State<Repos>,
+    corpus_id: i32,
+    doc_id: i32,
+    revision: String,
+) -> Result<(ContentType, Vec<u8>), Custom<Value>> {
+    let repos = repos.inner().clone();
+    conn.run(move |conn| {
+        viewer.doc(conn, doc_id)?;
+        check_membership(conn, corpus_id, doc_id)?;
+        revision_xml(&repos, Scope::Corpus(corpus_id), doc_id, &revision)
+    })
+    .await
+}
+
+/// Commits of every saved version of the document's transcript to its
+/// project's repository, newest first. Empty unless the git transcript
+/// backend is configured.
+#[get("/documents/<doc_id>/commits")]
+pub async fn commits(conn: DbConn, viewer: Viewer, repos: &State<Repos>, doc_id: i32) -> ApiResult {
+    let repos = repos.inner().clone();
+    conn.run(move |conn| {
+        let project_id = viewer.doc(conn, doc_id)?;
+        history_json(&repos, Scope::Project(project_id), doc_id)
+    })
+    .await
+}
+
+/// The document's transcript as of a commit to its project's repository.
+#[get("/documents/<doc_id>/commits/<commit>")]
+pub async fn commit(
+    conn: DbConn,
+    viewer: Viewer,
+    repos: &State<Repos>,
+    doc_id: i32,
+    commit: String,
+) -> Result<(ContentType, Vec<u8>), Custom<Value>> {
+    let repos = repos.inner().clone();
+    conn.run(move |conn| {
+        let project_id = viewer.doc(conn, doc_id)?;
+        revision_xml(&repos, Scope::Project(project_id), doc_id, &commit)
+    })
+    .await
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RestoreRequest {
+    revision: String,
+    user_id: Option<i32>,
+}
+
+/// Store the revision as the document's latest transcript, and commit it
+/// as such.
+#[post("/corpora/<corpus_id>/documents/<doc_id>/restore", data = "<request>")]
+pub async fn restore(
+    conn: DbConn,
+    viewer: Allowed<DocEdit>,
+    storage: &State<Storage>,
+    repos: &State<Repos>,
+    corpus_id: i32,
+    doc_id: i32,
+    request: JsonBody<RestoreRequest>,
+) -> ApiResult {
+    let storage = storage.inner().clone();
+    let repos = repos.inner().clone();
+    conn.run(move |conn| {
+        viewer.doc(conn, doc_id)?;
+        check_membership(conn, corpus_id, doc_id)?;
+        let contents = match repos
+            .revision(Scope::Corpus(corpus_id), doc_id, &request.revision)
+            .map_err(api::internal)?
+        {
+            Some(contents) => contents,
+            None => return Err(api::error(Status::NotFound, "no such revision")),
+        };
+        let source_id = files::latest(conn, doc_id, &[files::EAF])
+            .map_err(api::internal)?
+            .map(|f| f.id);
+        let short: String = request.revision.chars().take(10).collect();
+        let file_id = storage
+            .store(
+                conn,
+                doc_id,
+                &format!("restore-{}.eaf", short),
+                contents.as_slice(),
+                contents.len() as u64,
+                FileInfo {
+                    role: files::EAF,
+                    mime: "application/xml",
+                    created_by: request.user_id,
+                    source_id,
+                },
+            )
+            .map_err(api::internal)?;
+        let message = format!("Restore document {} to {}", doc_id, short);
+        let commits = commit_latest(conn, &storage, &repos, doc_id, request.user_id, &message)
+            .map_err(api::internal)?;
+        let details = json!({
+            "corpus_id": corpus_id,
+            "revision": request.revision,
+            "file_id": file_id,
+            "source_id": source_id,
+        });
+        audit::record(conn, request.user_id, RESTORE, "document", doc_id, &details)
+            .map_err(api::internal)?;
+        api::ok(json!({ "file_id": file_id, "commits": commits }))
+    })
+    .await
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NormalizeRequest {
+    user_id: Option<i32>,
+}
+
+/// Commit all transcripts in all repositories in canonical formatting.
+#[post("/admin/vc/normalize", data = "<request>")]
+pub async fn normalize(
+    conn: DbConn,
+    _viewer: Allowed<MaintenanceRun>,
+    repos: &State<Repos>,
+    request: JsonBody<NormalizeRequest>,
+) -> ApiResult {
+    let repos = repos.inner().clone();
+    conn.run(move |conn| {
+        let author = signature(conn, request.user_id).map_err(api::internal)?;
+        let mut results = vec![];
+        for corpus in corpora::all(conn).map_err(api::internal)? {
+            let normalized = match repos
+                .normalize(Scope::Corpus(corpus.id), &author)
+                .map_err(api::internal)?
+            {
+                Some(normalized) => normalized,
+                None => continue,
+            };
+            let failed: Vec<_> = normalized
+                .failed
+                .iter()
+                .map(|(file, error)| json!({ "file": file, "error": error }))
+                .collect();
+            let result = json!({
+                "corpus_id": corpus.id,
+                "commit": normalized.commit,
+                "changed": normalized.changed,
+                "failed": failed,
+            });
+            if normalized.commit.is_some() {
+                audit::record(
+                    conn,
+                    request.user_id,
+                    NORMALIZE,
+                    "corpus",
+                    corpus.id,
+                    &result,
+                )
+                .map_err(api::internal)?;
+            }
+            results.push(result);
+        }
+        api::ok(json!(results))
+    })
+    .await
+}
@@ -0,0 +1,260 @@
+//! Outgoing webhooks: events are queued in the DB when they happen and
+//! delivered by a background thread, so that slow or unreachable receivers
+//! don't hold up requests. Receivers can verify a delivery came from us by
+//! checking the `X-Quetzal-Signature` header, an HMAC-SHA256 of the body
+//! keyed with the webhook's secret.
+
+use std::{thread, time::Duration};
+
+use chrono::Local;
+use db::webhooks::{self, Delivery, Webhook, WebhookData};
+use db::Conn;
+use diesel::result::Error;
+use hmac::{Hmac, Mac, NewMac};
+use rocket::http::Status;
+use rocket::response::status::Custom;
+use rocket::serde::json::Value;
+use serde::Deserialize;
+use sha2::Sha256;
+
+use super::api::{self, ApiResult};
+use super::body::JsonBody;
+use super::conn::DbConn;
+use super::tenancy::{Allowed, Viewer, WebhookEdit};
+
+/// How often the dispatcher checks for deliveries to make.
+const POLL: Duration = Duration::from_secs(10);
+/// How long to wait for a receiver before counting the attempt as failed.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Queue the event for delivery to the project's webhooks. Failing to do so
+/// is logged rather than returned, as it shouldn't undo or fail whatever
+/// triggered the event.
+pub fn fire(conn: &Conn, project_id: i32, event: &str, data: Value) {
+    let payload = json!({
+        "event": event,
+        "project_id": project_id,
+        "created_at": Local::now().naive_local().to_string(),
+        "data": data,
+    });
+    if let Err(e) = webhooks::enqueue(conn, project_id, event, &payload.to_string()) {
+        eprintln!("failed to queue {} webhooks: {}", event, e);
+    }
+}
+
+fn signature(secret: &str, payload: &str) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_varkey(secret.as_bytes()).expect("HMAC takes keys of any size");
+    mac.update(payload.as_bytes());
+    format!("sha256={:x}", mac.finalize().into_bytes())
+}
+
+/// Attempt the delivery, returning the HTTP status of the response, if any,
+/// and an error if the receiver didn't accept it.
+fn deliver(
+    agent: &ureq::Agent,
+    delivery: &Delivery,
+    url: &str,
+    secret: &str,
+) -> (Option<i32>, Option<String>) {
+    let result = agent
+        .post(url)
+        .set("Content-Type", "application/json")
+        .set("X-Quetzal-Event", &delivery.event)
+        .set("X-Quetzal-Delivery", &delivery.id.to_string())
+        .set("X-Quetzal-Signature", &signature(secret, &delivery.payload))
+        .send_string(&delivery.payload);
+    match result {
+        Ok(response) => (Some(response.status() as i32), None),
+        Err(ureq::Error::Status(status, _)) => (
+            Some(status as i32),
+            Some(format!("receiver responded with {}", status)),
+        ),
+        Err(e) => (None, Some(e.to_string())),
+    }
+}
+
+fn dispatch(database_url: &str) -> Result<(), String> {
+    let conn = db::connect(database_url).map_err(|e| e.to_string())?;
+    let agent = ureq::AgentBuilder::new().timeout(TIMEOUT).build();
+    loop {
+        let now = Local::now().naive_local();
+        for (delivery, url, secret) in webhooks::due(&conn, now).map_err(|e| e.to_string())? {
+            let (status, error) = deliver(&agent, &delivery, &url, &secret);
+            if let Some(e) = &error {
+                eprintln!("webhook delivery {} to {} failed: {}", delivery.id, url, e);
+            }
+            let now = Local::now().naive_local();
+            webhooks::finish(&conn, &delivery, status, error, now).map_err(|e| e.to_string())?;
+        }
+        thread::sleep(POLL);
+    }
+}
+
+pub fn spawn(database_url: String) -> thread::JoinHandle<()> {
+    thread::spawn(move || loop {
+        // only DB trouble gets us here; wait it out and start over
+        if let Err(e) = dispatch(&database_url) {
+            eprintln!("webhook dispatcher failed: {}", e);
+        }
+        thread::sleep(POLL);
+    })
+}
+
+/// Secrets are write-only, so that they don't leak through the API.
+#[get("/projects/<project_id>/webhooks")]
+pub async fn list(conn: DbConn, viewer: Viewer, project_id: i32) -> ApiResult {
+    conn.run(move |conn| {
+        viewer.project(project_id)?;
+        let hooks: Vec<_> = webhooks::for_project(conn, project_id)
+            .map_err(api::internal)?
+            .into_iter()
+            .map(|h| {
+                json!({
+                    "id": h.id,
+                    "url": h.url,
+                    "active": h.active,
+                    "created_at": h.created_at.to_string(),
+                })
+            })
+            .collect();
+        api::ok(json!(hooks))
+    })
+    .await
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WebhookRequest {
+    url: String,
+    /// Keeps the current secret when updating if missing.
+    secret: Option<String>,
+    #[serde(default = "active_default")]
+    active: bool,
+}
+
+fn active_default() -> bool {
+    true
+}
+
+fn check_url(url: &str) -> Result<(), Custom<Value>> {
+    if url.starts_with("http://") || url.starts_with("https://") {
+        Ok(())
+    } else {
+        Err(api::error(
+            Status::UnprocessableEntity,
+            format!("{:?} is not an HTTP(S) URL", url),
+        ))
+    }
+}
+
+fn check_secret(secret: &str) -> Result<(), Custom<Value>> {
+    if secret.is_empty() {
+        Err(api::error(Status::UnprocessableEntity, "empty secret"))
+    } else {
+        Ok(())
+    }
+}
+
+#[post("/projects/<project_id>/webhooks", data = "<request>")]
+pub async fn add(
+    conn: DbConn,
+    viewer: Allowed<WebhookEdit>,
+    project_id: i32,
+    request: JsonBody<WebhookRequest>,
+) -> ApiResult {
+    conn.run(move |conn| {
+        viewer.project(project_id)?;
+        let request = request.into_inner();
+        let url = request.url.trim().to_owned();
+        check_url(&url)?;
+        let secret = request.secret.unwrap_or_default();
+        check_secret(&secret)?;
+        let data = WebhookData {
+            url,
+            secret,
+            active: request.active,
+        };
+        match webhooks::add(conn, project_id, &data) {
+            Ok(id) => api::ok(json!({ "id": id })),
+            Err(Error::NotFound) => Err(api::error(Status::NotFound, "no such project")),
+            Err(e) => Err(api::internal(e)),
+        }
+    })
+    .await
+}
+
+/// The webhook, if it's in a project the user can access.
+fn check_webhook(conn: &Conn, viewer: &Viewer, id: i32) -> Result<Webhook, Custom<Value>> {
+    match webhooks::get(conn, id) {
+        Ok(hook) if viewer.access.allows(hook.project_id) => Ok(hook),
+        Ok(_) | Err(Error::NotFound) => Err(api::error(Status::NotFound, "no such webhook")),
+        Err(e) => Err(api::internal(e)),
+    }
+}
+
+#[put("/webhooks/<id>", data = "<request>")]
+pub async fn update(
+    conn: DbConn,
+    viewer: Allowed<WebhookEdit>,
+    id: i32,
+    request: JsonBody<WebhookRequest>,
+) -> ApiResult {
+    conn.run(move |conn| {
+        let hook = check_webhook(conn, &viewer, id)?;
+        let request = request.into_inner();
+        let url = request.url.trim().to_owned();
+        check_url(&url)?;
+        let secret = match request.secret {
+            Some(secret) => secret,
+            None => hook.secret,
+        };
+        check_secret(&secret)?;
+        let data = WebhookData {
+            url,
+            secret,
+            active: request.active,
+        };
+        if !webhooks::update(conn, id, &data).map_err(api::internal)? {
+            return Err(api::error(Status::NotFound, "no such webhook"));
+        }
+        api::ok(json!({ "id": id }))
+    })
+    .await
+}
+
+#[delete("/webhooks/<id>")]
+pub async fn remove(conn: DbConn, viewer: Allowed<WebhookEdit>, id: i32) -> ApiResult {
+    conn.run(move |conn| {
+        check_webhook(conn, &viewer, id)?;
+        if !webhooks::remove(conn, id).map_err(api::internal)? {
+            return Err(api::error(Status::NotFound, "no such webhook"));
+        }
+        api::ok(json!(null))
+    })
+    .await
+}
+
+#[get("/webhooks/<id>/deliveries?<limit>")]
+pub async fn deliveries(conn: DbConn, viewer: Viewer, id: i32, limit: Option<usize>) -> ApiResult {
+    conn.run(move |conn| {
+        check_webhook(conn, &viewer, id)?;
+        let deliveries: Vec<_> = webhooks::deliveries(conn, id, api::limit(limit))
+            .map_err(api::internal)?
+            .into_iter()
+            .map(|d| {
+                json!({
+                    "id": d.id,
+                    "event": d.event,
+                    "state": d.state,
+                    "attempts": d.attempts,
+                    "status": d.status,
+                    "error": d.error,
+                    "created_at": d.created_at.to_string(),
+                    "next_attempt_at": d.next_attempt_at.to_string(),
+                })
+            })
+            .collect();
+        api::ok(json!(deliveries))
+    })
+    .await
+}
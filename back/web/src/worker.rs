@@ -0,0 +1,58 @@
+//! Worker thread processing background jobs one at a time.
+
+use std::{thread, time::Duration};
+
+use db::jobs::{self, Job};
+use db::Conn;
+
+use super::audio;
+use super::fulltext;
+use super::revalidation;
+use super::storage::Storage;
+
+/// How long to wait before checking for new jobs when the queue is empty.
+const POLL: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone)]
+pub struct WorkerConfig {
+    pub database_url: String,
+    pub storage: Storage,
+    /// Path to the ffmpeg binary.
+    pub ffmpeg: String,
+}
+
+fn run(conn: &Conn, config: &WorkerConfig, job: &Job) -> Result<(), String> {
+    match job.kind.as_str() {
+        jobs::TRANSCODE => audio::transcode(conn, &config.storage, &config.ffmpeg, job.file_id),
+        jobs::REVALIDATE => revalidation::revalidate(conn, &config.storage, job.file_id),
+        jobs::INDEX => fulltext::index(conn, &config.storage, job.file_id),
+        kind => Err(format!("unknown job kind {:?}", kind)),
+    }
+}
+
+fn work(config: &WorkerConfig) -> Result<(), String> {
+    let conn = db::connect(&config.database_url).map_err(|e| e.to_string())?;
+    jobs::requeue_running(&conn).map_err(|e| e.to_string())?;
+    loop {
+        match jobs::claim_next(&conn).map_err(|e| e.to_string())? {
+            Some(job) => {
+                let result = run(&conn, config, &job);
+                if let Err(e) = &result {
+                    eprintln!("job {} ({}) failed: {}", job.id, job.kind, e);
+                }
+                jobs::finish(&conn, job.id, result).map_err(|e| e.to_string())?;
+            }
+            None => thread::sleep(POLL),
+        }
+    }
+}
+
+pub fn spawn(config: WorkerConfig) -> thread::JoinHandle<()> {
+    thread::spawn(move || loop {
+        // only DB trouble gets us here; wait it out and start over
+        if let Err(e) = work(&config) {
+            eprintln!("job worker failed: {}", e);
+        }
+        thread::sleep(POLL);
+    })
+}